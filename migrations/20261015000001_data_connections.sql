@@ -0,0 +1,15 @@
+-- External data sources (e.g. a customer's Postgres) registered per workspace.
+-- Connection strings hold credentials and are stored AES-256-GCM encrypted.
+CREATE TABLE IF NOT EXISTS data_connections (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    kind VARCHAR(50) NOT NULL DEFAULT 'POSTGRES',
+    encrypted_connection_string TEXT NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE(workspace_id, name),
+    CONSTRAINT valid_connection_kind CHECK (kind IN ('POSTGRES'))
+);
+CREATE INDEX IF NOT EXISTS idx_data_connections_workspace_id ON data_connections(workspace_id);
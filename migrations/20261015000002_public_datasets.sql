@@ -0,0 +1,4 @@
+-- Public read-only sharing of individual datasets via an unguessable token
+ALTER TABLE datasets
+    ADD COLUMN IF NOT EXISTS public_token VARCHAR(64);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_datasets_public_token ON datasets(public_token) WHERE public_token IS NOT NULL;
@@ -0,0 +1,7 @@
+-- Columns of a dataset hidden from members below ADMIN and from public viewers
+CREATE TABLE IF NOT EXISTS dataset_column_restrictions (
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    column_name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (dataset_id, column_name)
+);
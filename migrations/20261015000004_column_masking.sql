@@ -0,0 +1,11 @@
+-- Restricted columns can be masked instead of hidden
+ALTER TABLE dataset_column_restrictions
+    ADD COLUMN IF NOT EXISTS mode VARCHAR(20) NOT NULL DEFAULT 'HIDE';
+
+DO $$
+BEGIN
+    ALTER TABLE dataset_column_restrictions
+        ADD CONSTRAINT valid_restriction_mode CHECK (mode IN ('HIDE', 'HASH', 'REDACT', 'PARTIAL'));
+EXCEPTION
+    WHEN duplicate_object THEN NULL;
+END $$;
@@ -0,0 +1,11 @@
+-- Per-user data keys for encrypting uploaded files at rest (envelope encryption).
+-- Each key is stored wrapped by the master key (SECRETS_ENCRYPTION_KEY); plaintext keys never touch the DB.
+CREATE TABLE IF NOT EXISTS user_data_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    wrapped_key TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- Key the staging file was encrypted with; NULL for plaintext (presigned) uploads
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS encryption_key_id UUID REFERENCES user_data_keys(id);
@@ -0,0 +1,4 @@
+-- Per-query figures for the account usage dashboard
+ALTER TABLE query_history ADD COLUMN IF NOT EXISTS bytes_processed BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE query_history ADD COLUMN IF NOT EXISTS tables_referenced TEXT[] NOT NULL DEFAULT '{}';
+CREATE INDEX IF NOT EXISTS idx_query_history_user_id_created_at ON query_history(user_id, created_at);
@@ -0,0 +1,26 @@
+-- Usage figures are derived from the rows they describe instead of counters that drift when
+-- a write fails part-way. storage_used_bytes was never kept in sync with datasets.
+ALTER TABLE workspaces DROP COLUMN IF EXISTS storage_used_bytes;
+
+CREATE OR REPLACE VIEW workspace_stats AS
+SELECT w.id AS workspace_id,
+       COUNT(d.id) AS dataset_count,
+       COALESCE(SUM(d.file_size_bytes), 0)::BIGINT AS storage_used_bytes
+FROM workspaces w
+LEFT JOIN datasets d ON d.workspace_id = w.id
+GROUP BY w.id;
+
+CREATE OR REPLACE VIEW user_stats AS
+SELECT u.id AS user_id,
+       (SELECT COUNT(*) FROM query_history q WHERE q.user_id = u.id) AS total_queries,
+       (SELECT COUNT(*)
+        FROM datasets d
+        JOIN workspaces w ON w.id = d.workspace_id
+        WHERE w.owner_user_id = u.id AND d.status = 'READY') AS total_files_processed,
+       (SELECT COALESCE(SUM(d.file_size_bytes), 0)::BIGINT
+        FROM datasets d
+        JOIN workspaces w ON w.id = d.workspace_id
+        WHERE w.owner_user_id = u.id) AS total_storage_bytes
+FROM users u;
+
+CREATE INDEX IF NOT EXISTS idx_datasets_workspace_id_status ON datasets(workspace_id, status);
@@ -0,0 +1,3 @@
+-- Per-dataset query usage, so stale datasets can be found and cleaned up
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS last_queried_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS query_count BIGINT NOT NULL DEFAULT 0;
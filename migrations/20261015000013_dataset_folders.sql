@@ -0,0 +1,20 @@
+-- Folders for organizing a workspace's datasets. Deleting a folder removes its subfolders;
+-- datasets inside are kept and become unfiled.
+CREATE TABLE IF NOT EXISTS folders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    parent_id UUID REFERENCES folders(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_folders_workspace_id ON folders(workspace_id);
+-- Sibling names are unique, also among top-level folders (NULL parent)
+CREATE UNIQUE INDEX IF NOT EXISTS idx_folders_sibling_name ON folders(
+    workspace_id,
+    COALESCE(parent_id, '00000000-0000-0000-0000-000000000000'::uuid),
+    LOWER(name)
+);
+
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS folder_id UUID REFERENCES folders(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_datasets_folder_id ON datasets(folder_id);
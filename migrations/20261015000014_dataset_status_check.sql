@@ -0,0 +1,4 @@
+-- Dataset processing lifecycle: PENDING_UPLOAD -> QUEUED -> PROCESSING -> READY | FAILED (error_message set)
+ALTER TABLE datasets DROP CONSTRAINT IF EXISTS datasets_status_check;
+ALTER TABLE datasets ADD CONSTRAINT datasets_status_check
+    CHECK (status IN ('PENDING_UPLOAD', 'QUEUED', 'PROCESSING', 'READY', 'FAILED')) NOT VALID;
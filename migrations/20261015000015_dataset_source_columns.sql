@@ -0,0 +1,2 @@
+-- Original headers of the uploaded file and the sanitized names they were read under
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS source_columns JSONB NOT NULL DEFAULT '[]';
@@ -0,0 +1,2 @@
+-- Problems found scanning the uploaded file before conversion (ragged rows, bad encoding, type mismatches)
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS validation_report JSONB;
@@ -0,0 +1,2 @@
+-- Encoding the uploaded file was detected in before being transcoded to UTF-8
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS source_encoding TEXT;
@@ -0,0 +1,2 @@
+-- Column types inferred for the uploaded file; full-scan results are reused when it is converted again
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS inferred_schema JSONB;
@@ -0,0 +1,37 @@
+-- Background work for the in-process job runner. Workers claim due jobs with FOR UPDATE SKIP LOCKED,
+-- so several workers (or replicas) never run the same job; failures are retried with backoff.
+-- The initial schema created a `background_jobs` table for a planned ingestion queue that was never
+-- used; replace it where it remains, since `CREATE TABLE IF NOT EXISTS` would keep its shape.
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = 'background_jobs' AND column_name = 's3_staging_key'
+    ) THEN
+        DROP TABLE background_jobs;
+    END IF;
+END $$;
+
+CREATE TABLE IF NOT EXISTS background_jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- NULL for platform-wide jobs
+    workspace_id UUID REFERENCES workspaces(id) ON DELETE CASCADE,
+    kind VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'QUEUED'
+        CHECK (status IN ('QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED')),
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL DEFAULT 5,
+    -- Earliest time the job may (next) run
+    run_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    -- When a worker claimed it; a RUNNING job whose lease has expired is claimed again
+    locked_at TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    result JSONB,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+CREATE INDEX IF NOT EXISTS idx_background_jobs_due ON background_jobs (run_at) WHERE status IN ('QUEUED', 'RUNNING');
+CREATE INDEX IF NOT EXISTS idx_background_jobs_workspace ON background_jobs (workspace_id, created_at DESC);
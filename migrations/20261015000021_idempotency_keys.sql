@@ -0,0 +1,17 @@
+-- Responses to requests sent with an Idempotency-Key, replayed when a client retries the same
+-- request. Keys are scoped to the user and endpoint and kept for a day.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    endpoint VARCHAR(100) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    -- SHA-256 of the request, so a key reused for a different request is refused
+    request_hash VARCHAR(64) NOT NULL,
+    -- NULL while the first request is still running
+    status_code SMALLINT,
+    content_type VARCHAR(255),
+    content_disposition VARCHAR(255),
+    body BYTEA,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, endpoint, key)
+);
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys (created_at);
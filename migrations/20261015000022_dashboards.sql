@@ -0,0 +1,26 @@
+-- Dashboards: named sets of tiles, each showing the result of a pipeline's query
+CREATE TABLE IF NOT EXISTS dashboards (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_dashboards_workspace_id ON dashboards(workspace_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_dashboards_workspace_name ON dashboards(workspace_id, LOWER(name));
+
+-- Tiles in display order. A tile reads every ready dataset of the workspace, or only `dataset_id`;
+-- it goes away with its pipeline or dataset.
+CREATE TABLE IF NOT EXISTS dashboard_tiles (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dashboard_id UUID NOT NULL REFERENCES dashboards(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    title VARCHAR(255),
+    pipeline_id UUID NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+    dataset_id UUID REFERENCES datasets(id) ON DELETE CASCADE,
+    visualization VARCHAR(20) NOT NULL CHECK (visualization IN ('TABLE', 'BAR', 'LINE'))
+);
+CREATE INDEX IF NOT EXISTS idx_dashboard_tiles_dashboard_id ON dashboard_tiles(dashboard_id, position);
+CREATE INDEX IF NOT EXISTS idx_dashboard_tiles_pipeline_id ON dashboard_tiles(pipeline_id);
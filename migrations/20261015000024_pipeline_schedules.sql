@@ -0,0 +1,28 @@
+-- Schedules: a pipeline run over every ready dataset of the workspace every `interval_minutes`,
+-- with its summary posted to a Slack or Teams incoming webhook when `notify_when` holds.
+CREATE TABLE IF NOT EXISTS pipeline_schedules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    pipeline_id UUID NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+    interval_minutes INTEGER NOT NULL CHECK (interval_minutes >= 5),
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    next_run_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    channel VARCHAR(10) NOT NULL CHECK (channel IN ('SLACK', 'TEAMS')),
+    -- Encrypted with SECRETS_ENCRYPTION_KEY; the URL itself is the webhook's credential
+    encrypted_webhook_url TEXT NOT NULL,
+    notify_when VARCHAR(20) NOT NULL CHECK (notify_when IN ('ALWAYS', 'ANY_ROWS', 'THRESHOLD')),
+    threshold_column VARCHAR(255),
+    threshold_op VARCHAR(3) CHECK (threshold_op IN ('GT', 'GTE', 'LT', 'LTE', 'EQ', 'NE')),
+    threshold_value DOUBLE PRECISION,
+    top_rows INTEGER NOT NULL DEFAULT 5,
+    last_run_at TIMESTAMP WITH TIME ZONE,
+    last_status VARCHAR(20) CHECK (last_status IN ('NOTIFIED', 'QUIET', 'FAILED')),
+    last_error TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CHECK (notify_when <> 'THRESHOLD' OR (threshold_column IS NOT NULL AND threshold_op IS NOT NULL AND threshold_value IS NOT NULL))
+);
+CREATE INDEX IF NOT EXISTS idx_pipeline_schedules_workspace_id ON pipeline_schedules(workspace_id);
+CREATE INDEX IF NOT EXISTS idx_pipeline_schedules_pipeline_id ON pipeline_schedules(pipeline_id);
+CREATE INDEX IF NOT EXISTS idx_pipeline_schedules_due ON pipeline_schedules(next_run_at) WHERE enabled;
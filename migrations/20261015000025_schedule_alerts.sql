@@ -0,0 +1,40 @@
+-- A schedule may run for its alerts alone, without posting a summary of every run
+ALTER TABLE pipeline_schedules ALTER COLUMN channel DROP NOT NULL;
+ALTER TABLE pipeline_schedules ALTER COLUMN encrypted_webhook_url DROP NOT NULL;
+ALTER TABLE pipeline_schedules DROP CONSTRAINT IF EXISTS pipeline_schedules_notify_when_check;
+ALTER TABLE pipeline_schedules ADD CONSTRAINT pipeline_schedules_notify_when_check
+    CHECK (notify_when IN ('ALWAYS', 'ANY_ROWS', 'THRESHOLD', 'NEVER'));
+ALTER TABLE pipeline_schedules ADD CONSTRAINT pipeline_schedules_webhook_check
+    CHECK (notify_when = 'NEVER' OR (channel IS NOT NULL AND encrypted_webhook_url IS NOT NULL));
+
+-- Alerts: a condition on an aggregate of a scheduled run's result. The alert notifies when it
+-- starts firing (and, with notify_on_resolve, when it stops), not on every run it keeps firing.
+CREATE TABLE IF NOT EXISTS schedule_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    schedule_id UUID NOT NULL REFERENCES pipeline_schedules(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    aggregate VARCHAR(10) NOT NULL CHECK (aggregate IN ('ROW_COUNT', 'SUM', 'MIN', 'MAX', 'AVG')),
+    column_name VARCHAR(255),
+    op VARCHAR(3) NOT NULL CHECK (op IN ('GT', 'GTE', 'LT', 'LTE', 'EQ', 'NE')),
+    threshold DOUBLE PRECISION NOT NULL,
+    emails TEXT[] NOT NULL DEFAULT '{}',
+    channel VARCHAR(10) CHECK (channel IN ('SLACK', 'TEAMS')),
+    encrypted_webhook_url TEXT,
+    notify_on_resolve BOOLEAN NOT NULL DEFAULT TRUE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    state VARCHAR(10) NOT NULL DEFAULT 'OK' CHECK (state IN ('OK', 'FIRING')),
+    last_value DOUBLE PRECISION,
+    last_evaluated_at TIMESTAMP WITH TIME ZONE,
+    state_changed_at TIMESTAMP WITH TIME ZONE,
+    last_notified_at TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    CHECK (aggregate = 'ROW_COUNT' OR column_name IS NOT NULL),
+    CHECK ((channel IS NULL) = (encrypted_webhook_url IS NULL)),
+    CHECK (cardinality(emails) > 0 OR channel IS NOT NULL)
+);
+CREATE INDEX IF NOT EXISTS idx_schedule_alerts_schedule_id ON schedule_alerts(schedule_id);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_schedule_alerts_schedule_name ON schedule_alerts(schedule_id, LOWER(name));
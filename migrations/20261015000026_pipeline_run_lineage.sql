@@ -0,0 +1,4 @@
+-- Where each run's result came from: the datasets and query it ran and, per result column, the
+-- table columns and expressions behind it. Recorded at run time since the pipeline's query may
+-- change afterwards; NULL for failed runs and runs from before lineage was recorded.
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS lineage JSONB;
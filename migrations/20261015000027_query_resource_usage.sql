@@ -0,0 +1,7 @@
+-- Resources each query and pipeline run used, measured by the query engine: bytes read from
+-- Parquet files and the most memory reserved at once. Zero for rows from before they were recorded.
+ALTER TABLE query_history ADD COLUMN IF NOT EXISTS bytes_scanned BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE query_history ADD COLUMN IF NOT EXISTS peak_memory_bytes BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS execution_time_ms BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS bytes_scanned BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE pipeline_runs ADD COLUMN IF NOT EXISTS peak_memory_bytes BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,22 @@
+-- Queries that ran at or over the slow-query threshold, with what was asked and how it ran, for
+-- performance triage by platform admins
+CREATE TABLE IF NOT EXISTS slow_queries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    -- NULL for pipeline runs outside a request
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    pipeline_id UUID REFERENCES pipelines(id) ON DELETE SET NULL,
+    sql_executed TEXT NOT NULL,
+    -- The analytics request or pipeline query the SQL was built from
+    operations JSONB NOT NULL,
+    -- Physical plan with each operator's rows, bytes and compute time
+    plan TEXT NOT NULL,
+    execution_time_ms BIGINT NOT NULL,
+    row_count BIGINT NOT NULL,
+    bytes_scanned BIGINT NOT NULL,
+    peak_memory_bytes BIGINT NOT NULL,
+    threshold_ms BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_slow_queries_created_at ON slow_queries(created_at DESC, id DESC);
+CREATE INDEX IF NOT EXISTS idx_slow_queries_workspace_id ON slow_queries(workspace_id);
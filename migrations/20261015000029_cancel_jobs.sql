@@ -0,0 +1,4 @@
+-- Jobs can be cancelled while queued or running; a cancelled job is never claimed again.
+ALTER TABLE background_jobs DROP CONSTRAINT IF EXISTS background_jobs_status_check;
+ALTER TABLE background_jobs ADD CONSTRAINT background_jobs_status_check
+    CHECK (status IN ('QUEUED', 'RUNNING', 'SUCCEEDED', 'FAILED', 'CANCELLED'));
@@ -0,0 +1,22 @@
+-- Resumable uploads: a file sent as chunks, each stored as its own staging object, turned into a
+-- dataset once every byte has arrived
+CREATE TABLE IF NOT EXISTS upload_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    filename VARCHAR(255) NOT NULL,
+    distributor_source VARCHAR(100) NOT NULL,
+    total_bytes BIGINT NOT NULL,
+    -- The next chunk starts at this offset
+    bytes_received BIGINT NOT NULL DEFAULT 0,
+    -- Staging keys of the chunks, in file order
+    chunk_keys TEXT[] NOT NULL DEFAULT '{}',
+    strict BOOLEAN NOT NULL DEFAULT FALSE,
+    inference JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL DEFAULT 'OPEN' CHECK (status IN ('OPEN', 'COMPLETING', 'COMPLETED')),
+    -- The dataset created when the session was completed
+    dataset_id UUID REFERENCES datasets(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_upload_sessions_workspace_user ON upload_sessions(workspace_id, user_id);
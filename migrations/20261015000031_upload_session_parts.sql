@@ -0,0 +1,23 @@
+-- Upload sessions are assembled with an S3 multipart upload: the file is sent as numbered parts of
+-- `chunk_size` bytes, in any order, each checksummed and replaceable by sending it again
+-- Sessions opened with the earlier in-order chunks cannot be resumed as parts
+DELETE FROM upload_sessions WHERE status <> 'COMPLETED';
+ALTER TABLE upload_sessions DROP COLUMN IF EXISTS chunk_keys;
+ALTER TABLE upload_sessions
+    ADD COLUMN IF NOT EXISTS chunk_size BIGINT NOT NULL DEFAULT 8388608,
+    ADD COLUMN IF NOT EXISTS part_count INT GENERATED ALWAYS AS (((total_bytes + chunk_size - 1) / chunk_size)::INT) STORED,
+    -- Where the file is assembled, and the S3 multipart upload assembling it
+    ADD COLUMN IF NOT EXISTS staging_key VARCHAR(512) NOT NULL DEFAULT '',
+    ADD COLUMN IF NOT EXISTS multipart_upload_id TEXT NOT NULL DEFAULT '';
+
+CREATE TABLE IF NOT EXISTS upload_session_parts (
+    session_id UUID NOT NULL REFERENCES upload_sessions(id) ON DELETE CASCADE,
+    part_number INT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    -- Hex SHA-256 of the part, as sent by the client and verified on receipt
+    sha256 VARCHAR(64) NOT NULL,
+    -- S3's ETag for the part, needed to complete the multipart upload
+    etag TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (session_id, part_number)
+);
@@ -0,0 +1,4 @@
+-- Uploads flagged by the content scanner are QUARANTINED instead of converted
+ALTER TABLE datasets DROP CONSTRAINT IF EXISTS datasets_status_check;
+ALTER TABLE datasets ADD CONSTRAINT datasets_status_check
+    CHECK (status IN ('PENDING_UPLOAD', 'QUEUED', 'PROCESSING', 'READY', 'FAILED', 'QUARANTINED')) NOT VALID;
@@ -0,0 +1,13 @@
+-- Discussion on a pipeline. Replies point at the comment they answer; `mentions` holds the
+-- workspace members @-mentioned in the body, resolved when it was written.
+CREATE TABLE IF NOT EXISTS pipeline_comments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    pipeline_id UUID NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+    parent_id UUID REFERENCES pipeline_comments(id) ON DELETE CASCADE,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    body TEXT NOT NULL,
+    mentions UUID[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_pipeline_comments_pipeline_id ON pipeline_comments(pipeline_id, created_at);
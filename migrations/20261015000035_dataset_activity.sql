@@ -0,0 +1,13 @@
+-- What happened to a dataset and who did it, for the dataset's activity feed. Pipeline runs are
+-- read from `pipeline_runs` instead of being copied here.
+CREATE TABLE IF NOT EXISTS dataset_activity (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    -- NULL once the user is deleted
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    action VARCHAR(20) NOT NULL CHECK (action IN ('CREATED', 'QUERIED', 'DOWNLOADED', 'SHARED', 'UNSHARED')),
+    details JSONB,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_dataset_activity_dataset_id ON dataset_activity(dataset_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_pipeline_runs_dataset_id ON pipeline_runs(dataset_id, created_at DESC);
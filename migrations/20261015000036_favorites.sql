@@ -0,0 +1,15 @@
+-- Datasets and pipelines each user has favorited. Per user, so resources shared in a workspace can
+-- be favorited by every member without affecting the others.
+CREATE TABLE IF NOT EXISTS dataset_favorites (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, dataset_id)
+);
+
+CREATE TABLE IF NOT EXISTS pipeline_favorites (
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    pipeline_id UUID NOT NULL REFERENCES pipelines(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (user_id, pipeline_id)
+);
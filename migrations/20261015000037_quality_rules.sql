@@ -0,0 +1,27 @@
+-- Column-level expectations on `royalty_data`, checked against one dataset at a time: on demand,
+-- or automatically when a dataset finishes processing if `run_on_upload` is set.
+CREATE TABLE IF NOT EXISTS quality_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    workspace_id UUID NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    column_name VARCHAR(255) NOT NULL,
+    -- {"type": "not_null" | "unique" | "in_set" | "range" | "regex", ...parameters}
+    expectation JSONB NOT NULL,
+    run_on_upload BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_quality_rules_workspace_name ON quality_rules(workspace_id, LOWER(name));
+
+-- One check of a dataset against the workspace's rules, with each rule's outcome and failing-row samples
+CREATE TABLE IF NOT EXISTS quality_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    dataset_id UUID NOT NULL REFERENCES datasets(id) ON DELETE CASCADE,
+    trigger VARCHAR(20) NOT NULL CHECK (trigger IN ('UPLOAD', 'MANUAL')),
+    passed BOOLEAN NOT NULL,
+    results JSONB NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_quality_runs_dataset_id ON quality_runs(dataset_id, created_at DESC);
@@ -0,0 +1,6 @@
+-- Converted Parquet is now encrypted with the dataset's data key, whatever the upload path, while
+-- only direct uploads are staged encrypted; that is recorded on its own
+ALTER TABLE datasets ADD COLUMN IF NOT EXISTS staging_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE datasets SET staging_encrypted = TRUE WHERE encryption_key_id IS NOT NULL;
+-- Parquet converted before this was written unencrypted
+UPDATE datasets SET encryption_key_id = NULL WHERE status = 'READY';
@@ -0,0 +1,474 @@
+//! Command-line client for the Doubledecker API: sign in, upload statements and pull query results
+//! to local files, for scripted pipelines that should not have to speak raw HTTP.
+
+use reqwest::header::{AUTHORIZATION, CONTENT_LENGTH, RETRY_AFTER};
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Client, RequestBuilder, Response, StatusCode};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+const USAGE: &str = "\
+Usage: doubledecker-cli [--url URL] <command> [options]
+
+Commands:
+  login --email EMAIL                  Sign in and store the token (password from
+                                       DOUBLEDECKER_PASSWORD or prompted)
+  workspaces                           List your workspaces
+  datasets  --workspace ID             List the datasets of a workspace
+  pipelines --workspace ID             List the saved pipelines of a workspace
+  upload    --workspace ID FILE        Upload a statement
+            [--source NAME] [--strict] [--wait]
+  query     --workspace ID             Run a query and save or print the result
+            (--sql SQL | --pipeline ID | --json FILE)
+            [--datasets ID,ID] [--out FILE] [--format csv|parquet|xlsx]
+
+The API is at --url, DOUBLEDECKER_URL or http://localhost:3000. The token is read from
+DOUBLEDECKER_TOKEN, or from the file `login` writes under ~/.doubledecker.";
+
+/// Largest file sent through the direct upload route; bigger files go straight to storage through
+/// a presigned URL.
+const DIRECT_UPLOAD_LIMIT: u64 = 50 * 1024 * 1024;
+/// Attempts of a request that fails with a connection error or a retryable status.
+const MAX_ATTEMPTS: u32 = 5;
+/// Time `upload --wait` gives ingestion before giving up.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug)]
+struct CliError(String);
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<reqwest::Error> for CliError {
+    fn from(e: reqwest::Error) -> Self {
+        CliError(format!("Request failed: {}", e))
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError(e.to_string())
+    }
+}
+
+type CliResult<T> = Result<T, CliError>;
+
+/// Command-line arguments split into positionals, `--name value` options and bare `--flag`s.
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+/// Options that take no value.
+const FLAGS: [&str; 3] = ["strict", "wait", "help"];
+
+impl Args {
+    fn parse(mut raw: impl Iterator<Item = String>) -> CliResult<Self> {
+        let mut args = Args { positional: Vec::new(), options: HashMap::new() };
+        while let Some(arg) = raw.next() {
+            let Some(name) = arg.strip_prefix("--") else {
+                args.positional.push(arg);
+                continue;
+            };
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                None if FLAGS.contains(&name) => (name.to_string(), "true".to_string()),
+                None => {
+                    let value = raw.next().ok_or_else(|| CliError(format!("--{} needs a value", name)))?;
+                    (name.to_string(), value)
+                }
+            };
+            args.options.insert(name, value);
+        }
+        Ok(args)
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn require(&self, name: &str) -> CliResult<&str> {
+        self.get(name).ok_or_else(|| CliError(format!("--{} is required\n\n{}", name, USAGE)))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.get(name).is_some_and(|v| matches!(v, "true" | "1" | "yes"))
+    }
+}
+
+struct Api {
+    client: Client,
+    url: String,
+    token: Option<String>,
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> CliResult<()> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let Some(command) = args.positional.first().cloned() else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    if args.flag("help") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let url = args
+        .get("url")
+        .map(str::to_string)
+        .or_else(|| std::env::var("DOUBLEDECKER_URL").ok())
+        .unwrap_or_else(|| "http://localhost:3000".to_string());
+    let api = Api {
+        client: Client::builder().connect_timeout(Duration::from_secs(10)).build()?,
+        url: url.trim_end_matches('/').to_string(),
+        token: std::env::var("DOUBLEDECKER_TOKEN").ok().or_else(read_stored_token),
+    };
+
+    match command.as_str() {
+        "login" => login(&api, args.require("email")?).await,
+        "workspaces" => print_page(&api, "/api/workspaces".to_string()).await,
+        "datasets" => print_page(&api, format!("/api/workspaces/{}/datasets", args.require("workspace")?)).await,
+        "pipelines" => print_page(&api, format!("/api/workspaces/{}/pipelines", args.require("workspace")?)).await,
+        "upload" => {
+            let file = args.positional.get(1).ok_or_else(|| CliError(format!("upload needs a FILE\n\n{}", USAGE)))?;
+            upload(&api, &args, args.require("workspace")?, Path::new(file)).await
+        }
+        "query" => query(&api, &args, args.require("workspace")?).await,
+        other => Err(CliError(format!("Unknown command '{}'\n\n{}", other, USAGE))),
+    }
+}
+
+fn token_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| Path::new(&home).join(".doubledecker").join("token"))
+}
+
+fn read_stored_token() -> Option<String> {
+    let token = std::fs::read_to_string(token_path()?).ok()?;
+    Some(token.trim().to_string()).filter(|t| !t.is_empty())
+}
+
+async fn login(api: &Api, email: &str) -> CliResult<()> {
+    let password = match std::env::var("DOUBLEDECKER_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => {
+            eprint!("Password for {}: ", email);
+            std::io::stderr().flush()?;
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    let body = json!({ "email": email, "password": password });
+    let response = api.send(|| async { Ok(api.client.post(api.endpoint("/auth/login")).json(&body)) }).await?;
+    let token = response.json::<Value>().await?["token"]
+        .as_str()
+        .ok_or_else(|| CliError("Login response has no token".to_string()))?
+        .to_string();
+
+    let path = token_path().ok_or_else(|| CliError("HOME is not set; export DOUBLEDECKER_TOKEN instead".to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, &token)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    eprintln!("Signed in as {}; token stored in {}", email, path.display());
+    Ok(())
+}
+
+/// Print the first page of a listing as JSON.
+async fn print_page(api: &Api, path: String) -> CliResult<()> {
+    let response = api.send(|| async { Ok(api.authorized(api.client.get(api.endpoint(&path)))?.query(&[("limit", "100")])) }).await?;
+    let page: Value = response.json().await?;
+    println!("{}", serde_json::to_string_pretty(&page["data"]).unwrap_or_default());
+    Ok(())
+}
+
+async fn upload(api: &Api, args: &Args, workspace_id: &str, file: &Path) -> CliResult<()> {
+    let size = tokio::fs::metadata(file).await.map_err(|e| CliError(format!("{}: {}", file.display(), e)))?.len();
+    let filename = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| CliError(format!("{} has no usable file name", file.display())))?
+        .to_string();
+    let source = args.get("source").unwrap_or("auto").to_string();
+    let strict = args.flag("strict");
+
+    let dataset = if size <= DIRECT_UPLOAD_LIMIT {
+        // One key for every attempt, so a retry after a lost response does not upload twice
+        let idempotency_key = Uuid::new_v4().to_string();
+        let endpoint = api.endpoint(&format!("/api/workspaces/{}/datasets/upload", workspace_id));
+        let response = api
+            .send(|| async {
+                let part = Part::stream_with_length(Body::from(tokio::fs::File::open(file).await?), size)
+                    .file_name(filename.clone())
+                    .mime_str("text/csv")?;
+                let form = Form::new()
+                    .text("distributor_source", source.clone())
+                    .text("strict", strict.to_string())
+                    .part("file", part);
+                Ok(api
+                    .authorized(api.client.post(&endpoint))?
+                    .header("Idempotency-Key", &idempotency_key)
+                    .multipart(form))
+            })
+            .await?;
+        response.json::<Value>().await?
+    } else {
+        upload_presigned(api, workspace_id, file, &filename, size, &source, strict).await?
+    };
+
+    let dataset_id = dataset["id"].as_str().unwrap_or_default().to_string();
+    eprintln!("Uploaded {} as dataset {} ({})", filename, dataset_id, dataset["status"].as_str().unwrap_or("?"));
+    let dataset = if args.flag("wait") { wait_for_dataset(api, workspace_id, &dataset_id).await? } else { dataset };
+    println!("{}", serde_json::to_string_pretty(&dataset).unwrap_or_default());
+    Ok(())
+}
+
+/// Files over the direct upload limit: PUT them to a presigned storage URL, then confirm.
+async fn upload_presigned(
+    api: &Api,
+    workspace_id: &str,
+    file: &Path,
+    filename: &str,
+    size: u64,
+    source: &str,
+    strict: bool,
+) -> CliResult<Value> {
+    let request = json!({ "filename": filename, "distributor_source": source, "file_size_bytes": size });
+    let presigned_endpoint = api.endpoint(&format!("/api/workspaces/{}/datasets/presigned_url", workspace_id));
+    let presigned: Value = api
+        .send(|| async { Ok(api.authorized(api.client.post(&presigned_endpoint))?.json(&request)) })
+        .await?
+        .json()
+        .await?;
+    let put_url = presigned["presigned_url"]
+        .as_str()
+        .ok_or_else(|| CliError("Presigned URL response has no presigned_url".to_string()))?;
+
+    api.send(|| async {
+        let body = Body::from(tokio::fs::File::open(file).await?);
+        Ok(api.client.put(put_url).header(CONTENT_LENGTH, size).body(body))
+    })
+    .await?;
+
+    let confirm = json!({
+        "dataset_id": presigned["dataset_id"],
+        "staging_key": presigned["staging_key"],
+        "strict": strict,
+    });
+    let confirm_endpoint = api.endpoint(&format!("/api/workspaces/{}/datasets/confirm", workspace_id));
+    Ok(api
+        .send(|| async { Ok(api.authorized(api.client.post(&confirm_endpoint))?.json(&confirm)) })
+        .await?
+        .json()
+        .await?)
+}
+
+/// Poll the workspace's datasets until the upload is READY or FAILED.
+async fn wait_for_dataset(api: &Api, workspace_id: &str, dataset_id: &str) -> CliResult<Value> {
+    let endpoint = api.endpoint(&format!("/api/workspaces/{}/datasets", workspace_id));
+    let started = std::time::Instant::now();
+    loop {
+        let page: Value = api
+            .send(|| async { Ok(api.authorized(api.client.get(&endpoint))?.query(&[("limit", "100")])) })
+            .await?
+            .json()
+            .await?;
+        let dataset = page["data"].as_array().and_then(|d| d.iter().find(|d| d["id"] == dataset_id)).cloned();
+        match dataset {
+            Some(dataset) if dataset["status"] == "READY" => return Ok(dataset),
+            Some(dataset) if dataset["status"] == "FAILED" => {
+                return Err(CliError(format!(
+                    "Dataset {} failed: {}",
+                    dataset_id,
+                    dataset["error_message"].as_str().unwrap_or("no reason given")
+                )));
+            }
+            _ if started.elapsed() > WAIT_TIMEOUT => {
+                return Err(CliError(format!("Dataset {} was not processed within {}s", dataset_id, WAIT_TIMEOUT.as_secs())));
+            }
+            _ => tokio::time::sleep(Duration::from_secs(2)).await,
+        }
+    }
+}
+
+async fn query(api: &Api, args: &Args, workspace_id: &str) -> CliResult<()> {
+    let mut body = if let Some(sql) = args.get("sql") {
+        json!({ "sql": sql })
+    } else if let Some(pipeline_id) = args.get("pipeline") {
+        // A pipeline's structured query is sent as an ad-hoc query over the workspace
+        let endpoint = api.endpoint(&format!("/api/workspaces/{}/pipelines/{}", workspace_id, pipeline_id));
+        let pipeline: Value = api.send(|| async { api.authorized(api.client.get(&endpoint)) }).await?.json().await?;
+        pipeline["query"].clone()
+    } else if let Some(path) = args.get("json") {
+        let text = std::fs::read_to_string(path).map_err(|e| CliError(format!("{}: {}", path, e)))?;
+        serde_json::from_str(&text).map_err(|e| CliError(format!("{} is not valid JSON: {}", path, e)))?
+    } else {
+        return Err(CliError(format!("query needs --sql, --pipeline or --json\n\n{}", USAGE)));
+    };
+    if let Some(ids) = args.get("datasets") {
+        body["dataset_ids"] = json!(ids.split(',').map(str::trim).filter(|id| !id.is_empty()).collect::<Vec<_>>());
+    }
+
+    let out = args.get("out").map(PathBuf::from);
+    let format = match (args.get("format"), &out) {
+        (Some(format), _) => format.to_string(),
+        (None, Some(path)) => path.extension().and_then(|e| e.to_str()).unwrap_or("csv").to_ascii_lowercase(),
+        (None, None) => "csv".to_string(),
+    };
+    if !matches!(format.as_str(), "csv" | "parquet" | "xlsx") {
+        return Err(CliError(format!("Unsupported format '{}'; use csv, parquet or xlsx", format)));
+    }
+    if out.is_none() && format != "csv" {
+        return Err(CliError(format!("{} output needs --out FILE", format)));
+    }
+
+    let idempotency_key = Uuid::new_v4().to_string();
+    let endpoint = api.endpoint(&format!("/api/workspaces/{}/analytics/download", workspace_id));
+    let mut response = api
+        .send(|| async {
+            Ok(api
+                .authorized(api.client.post(&endpoint))?
+                .query(&[("format", format.as_str())])
+                .header("Idempotency-Key", &idempotency_key)
+                .json(&body))
+        })
+        .await?;
+
+    match out {
+        Some(path) => {
+            let mut file = tokio::fs::File::create(&path).await?;
+            let mut written = 0u64;
+            while let Some(chunk) = response.chunk().await? {
+                file.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+            }
+            file.flush().await?;
+            eprintln!("Wrote {} bytes to {}", written, path.display());
+        }
+        None => {
+            let mut stdout = tokio::io::stdout();
+            while let Some(chunk) = response.chunk().await? {
+                stdout.write_all(&chunk).await?;
+            }
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+impl Api {
+    fn endpoint(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    fn authorized(&self, request: RequestBuilder) -> CliResult<RequestBuilder> {
+        let token = self
+            .token
+            .as_deref()
+            .ok_or_else(|| CliError("Not signed in; run `doubledecker-cli login --email ...` or set DOUBLEDECKER_TOKEN".to_string()))?;
+        Ok(request.header(AUTHORIZATION, format!("Bearer {}", token)))
+    }
+
+    /// Send a request, rebuilding it for every attempt, and retry connection failures, timeouts,
+    /// rate limiting and unavailable servers with exponential backoff (or the server's Retry-After).
+    async fn send<F, Fut>(&self, build: F) -> CliResult<Response>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = CliResult<RequestBuilder>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let delay = Duration::from_secs(1 << (attempt - 1));
+            match build().await?.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) if attempt < MAX_ATTEMPTS && is_retryable(response.status()) => {
+                    let delay = retry_after(&response).unwrap_or(delay);
+                    eprintln!("{}; retrying in {}s", response.status(), delay.as_secs());
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(response) => return Err(api_error(response).await),
+                Err(e) if attempt < MAX_ATTEMPTS && (e.is_connect() || e.is_timeout() || e.is_request()) => {
+                    eprintln!("{}; retrying in {}s", e, delay.as_secs());
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+            attempt += 1;
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// The API's error message and code, or the raw body when it is not an API error.
+async fn api_error(response: Response) -> CliError {
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<Value>(&text) {
+        Ok(body) if body["error"].is_string() => {
+            let mut message = format!("{} ({}): {}", status, body["code"].as_str().unwrap_or("ERROR"), body["error"].as_str().unwrap_or(""));
+            if let Some(fields) = body["fields"].as_array() {
+                for field in fields {
+                    message.push_str(&format!("\n  {}: {}", field["field"].as_str().unwrap_or("?"), field["message"].as_str().unwrap_or("")));
+                }
+            }
+            CliError(message)
+        }
+        _ => CliError(format!("{}: {}", status, text.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_split_options_flags_and_positionals() {
+        let raw = ["upload", "--workspace", "w1", "june.csv", "--strict", "--source=tunecore"];
+        let args = Args::parse(raw.iter().map(|a| a.to_string())).unwrap();
+        assert_eq!(args.positional, vec!["upload", "june.csv"]);
+        assert_eq!(args.get("workspace"), Some("w1"));
+        assert_eq!(args.get("source"), Some("tunecore"));
+        assert!(args.flag("strict"));
+        assert!(!args.flag("wait"));
+
+        assert!(Args::parse(["query", "--sql"].iter().map(|a| a.to_string())).is_err());
+    }
+}
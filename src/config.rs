@@ -0,0 +1,359 @@
+use crate::utils::error::DoubledeckerError;
+use ipnet::IpNet;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const DEFAULT_JWT_SECRET: &str = "your-secret-key";
+const DEFAULT_QUERY_MEMORY_LIMIT_MB: usize = 2048;
+const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 10_000;
+const DEFAULT_JSON_BODY_LIMIT_KB: usize = 1024;
+const DEFAULT_UPLOAD_BODY_LIMIT_MB: usize = 50;
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 1024;
+const DEFAULT_DOWNLOAD_URL_EXPIRY_SECS: u64 = 300;
+const DEFAULT_UPLOAD_URL_EXPIRY_SECS: u64 = 3600;
+const DEFAULT_MAX_UPLOAD_COLUMNS: u64 = 1000;
+const DEFAULT_MAX_UPLOAD_ROWS: u64 = 50_000_000;
+const DEFAULT_MAX_UPLOAD_FILE_MB: u64 = 5 * 1024;
+/// Percentage of an upload limit a dataset may reach before its workspace is warned
+const UPLOAD_LIMIT_WARNING_PERCENT: u64 = 80;
+/// Shortest lifetime a presigned URL can be asked for
+pub const MIN_PRESIGNED_URL_EXPIRY_SECS: u64 = 60;
+/// Longest lifetime S3 accepts for a presigned URL (SigV4 allows 7 days)
+pub const MAX_PRESIGNED_URL_EXPIRY_SECS: u64 = 7 * 24 * 3600;
+
+static JWT_CONFIG: LazyLock<JwtConfig> = LazyLock::new(JwtConfig::from_env);
+static PRESIGN_CONFIG: LazyLock<PresignConfig> = LazyLock::new(PresignConfig::from_env);
+static UPLOAD_LIMITS: LazyLock<UploadLimits> = LazyLock::new(UploadLimits::from_env);
+static TRUSTED_PROXIES: LazyLock<Vec<IpNet>> = LazyLock::new(|| networks_from_env("TRUSTED_PROXIES"));
+static CONNECTION_ALLOWED_NETWORKS: LazyLock<Vec<IpNet>> =
+    LazyLock::new(|| networks_from_env("CONNECTION_ALLOWED_NETWORKS"));
+static MASK_HASH_KEY: LazyLock<Option<Vec<u8>>> = LazyLock::new(mask_hash_key_from_env);
+
+/// An HMAC secret with the key id (`kid`) stamped into the header of tokens it signs.
+#[derive(Debug, Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    pub secret: String,
+}
+
+impl JwtKey {
+    /// The kid is derived from the secret, so rotating only requires moving secrets between variables.
+    pub fn from_secret(secret: &str) -> Self {
+        let digest = format!("{:x}", Sha256::digest(secret.as_bytes()));
+        Self {
+            kid: digest[..16].to_string(),
+            secret: secret.to_string(),
+        }
+    }
+}
+
+/// JWT signing configuration.
+///
+/// Rotation: set the new secret as `JWT_SECRET` and move the old one into `JWT_PREVIOUS_SECRETS`
+/// (comma-separated). New tokens are signed with the current key; tokens signed with a previous key
+/// keep working until they expire, after which the old secret can be dropped.
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    pub current: JwtKey,
+    pub previous: Vec<JwtKey>,
+}
+
+impl JwtConfig {
+    pub fn from_env() -> Self {
+        let current = env::var("JWT_SECRET").unwrap_or_else(|_| DEFAULT_JWT_SECRET.to_string());
+        Self::new(
+            &current,
+            env::var("JWT_PREVIOUS_SECRETS").unwrap_or_default().split(','),
+        )
+    }
+
+    pub fn new<'a>(current: &str, previous: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            current: JwtKey::from_secret(current),
+            previous: previous
+                .into_iter()
+                .map(str::trim)
+                .filter(|s| !s.is_empty() && *s != current)
+                .map(JwtKey::from_secret)
+                .collect(),
+        }
+    }
+
+    /// Key able to verify a token with this header `kid`. Tokens without a kid predate rotation
+    /// support and were signed with some configured secret, so `None` yields every key to try.
+    pub fn decoding_keys(&self, kid: Option<&str>) -> Vec<&JwtKey> {
+        std::iter::once(&self.current)
+            .chain(&self.previous)
+            .filter(|key| kid.is_none_or(|kid| key.kid == kid))
+            .collect()
+    }
+}
+
+/// DataFusion runtime limits shared by every query session.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// Size of the server-wide memory pool all running queries draw from (`QUERY_MEMORY_LIMIT_MB`)
+    pub memory_limit_bytes: usize,
+    /// Let sorts, aggregations and joins spill to disk once their share of the pool is used up
+    /// (`QUERY_SPILL_TO_DISK`, default on). When off, such queries fail with a memory error instead.
+    pub spill_to_disk: bool,
+    /// Directories for spill files (`QUERY_SPILL_DIRS`, comma-separated); the OS temp dir when empty
+    pub spill_dirs: Vec<PathBuf>,
+    /// Partitions a query's scans and operators are split into, so a dataset's files are read in
+    /// parallel (`QUERY_TARGET_PARTITIONS`, default the server's CPU count)
+    pub target_partitions: usize,
+    /// Queries running at least this long are logged with their plan for triage
+    /// (`SLOW_QUERY_THRESHOLD_MS`, default 10 seconds; 0 turns the log off)
+    pub slow_query_threshold: Option<Duration>,
+}
+
+impl EngineConfig {
+    pub fn from_env() -> Self {
+        let memory_limit_mb = env::var("QUERY_MEMORY_LIMIT_MB")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_QUERY_MEMORY_LIMIT_MB);
+        Self {
+            memory_limit_bytes: memory_limit_mb.max(1) * 1024 * 1024,
+            spill_to_disk: env::var("QUERY_SPILL_TO_DISK")
+                .map(|v| !matches!(v.trim().to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            spill_dirs: env::var("QUERY_SPILL_DIRS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(PathBuf::from)
+                .collect(),
+            target_partitions: env::var("QUERY_TARGET_PARTITIONS")
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+                .max(1),
+            slow_query_threshold: Some(
+                env::var("SLOW_QUERY_THRESHOLD_MS")
+                    .ok()
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD_MS),
+            )
+            .filter(|ms| *ms > 0)
+            .map(Duration::from_millis),
+        }
+    }
+}
+
+/// Request body size limits. JSON endpoints get a small limit so a broken client cannot make the
+/// server buffer a huge body; direct uploads get their own, larger one.
+#[derive(Debug, Clone)]
+pub struct BodyLimits {
+    /// Every route without its own limit (`JSON_BODY_LIMIT_KB`, default 1 MB)
+    pub json_bytes: usize,
+    /// Direct multipart uploads (`UPLOAD_BODY_LIMIT_MB`, default 50 MB). Larger files go through
+    /// presigned S3 uploads, which do not pass through the server.
+    pub upload_bytes: usize,
+}
+
+impl BodyLimits {
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            env::var(name).ok().and_then(|v| v.trim().parse::<usize>().ok()).unwrap_or(default).max(1)
+        };
+        Self {
+            json_bytes: read("JSON_BODY_LIMIT_KB", DEFAULT_JSON_BODY_LIMIT_KB) * 1024,
+            upload_bytes: read("UPLOAD_BODY_LIMIT_MB", DEFAULT_UPLOAD_BODY_LIMIT_MB) * 1024 * 1024,
+        }
+    }
+}
+
+/// Server-wide request limits, so a flood of slow requests is turned away instead of piling up tasks.
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Time a request gets to produce its response (`REQUEST_TIMEOUT_SECS`, default 120); a streamed
+    /// body may take longer once it has started
+    pub timeout: Duration,
+    /// Requests handled at once (`MAX_CONCURRENT_REQUESTS`, default 1024); more are rejected with a 503
+    /// rather than queued
+    pub max_concurrent_requests: usize,
+}
+
+impl RequestLimits {
+    pub fn from_env() -> Self {
+        Self {
+            timeout: Duration::from_secs(
+                env::var("REQUEST_TIMEOUT_SECS")
+                    .ok()
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS)
+                    .max(1),
+            ),
+            max_concurrent_requests: env::var("MAX_CONCURRENT_REQUESTS")
+                .ok()
+                .and_then(|v| v.trim().parse::<usize>().ok())
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS)
+                .max(1),
+        }
+    }
+}
+
+/// Default lifetimes of presigned S3 URLs, for requests that do not ask for one. Both are kept
+/// within `MIN_PRESIGNED_URL_EXPIRY_SECS` and `MAX_PRESIGNED_URL_EXPIRY_SECS`.
+#[derive(Debug, Clone)]
+pub struct PresignConfig {
+    /// Dataset download links (`DOWNLOAD_URL_EXPIRY_SECS`, default 5 minutes). Kept short since they
+    /// grant direct S3 access
+    pub download_expiry_secs: u64,
+    /// Presigned upload URLs (`UPLOAD_URL_EXPIRY_SECS`, default 1 hour)
+    pub upload_expiry_secs: u64,
+}
+
+impl PresignConfig {
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+                .clamp(MIN_PRESIGNED_URL_EXPIRY_SECS, MAX_PRESIGNED_URL_EXPIRY_SECS)
+        };
+        Self {
+            download_expiry_secs: read("DOWNLOAD_URL_EXPIRY_SECS", DEFAULT_DOWNLOAD_URL_EXPIRY_SECS),
+            upload_expiry_secs: read("UPLOAD_URL_EXPIRY_SECS", DEFAULT_UPLOAD_URL_EXPIRY_SECS),
+        }
+    }
+}
+
+/// Largest dataset an upload may hold, whichever way it arrives. Sizes are checked before the file
+/// is sent where the client declares them; columns and rows when the file is validated.
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    /// Columns in the header (`MAX_UPLOAD_COLUMNS`, default 1000)
+    pub max_columns: u64,
+    /// Data rows (`MAX_UPLOAD_ROWS`, default 50 million)
+    pub max_rows: u64,
+    /// File size (`MAX_UPLOAD_FILE_MB`, default 5 GB)
+    pub max_file_bytes: u64,
+}
+
+impl UploadLimits {
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: u64| {
+            env::var(name).ok().and_then(|v| v.trim().parse::<u64>().ok()).unwrap_or(default).max(1)
+        };
+        Self {
+            max_columns: read("MAX_UPLOAD_COLUMNS", DEFAULT_MAX_UPLOAD_COLUMNS),
+            max_rows: read("MAX_UPLOAD_ROWS", DEFAULT_MAX_UPLOAD_ROWS),
+            max_file_bytes: read("MAX_UPLOAD_FILE_MB", DEFAULT_MAX_UPLOAD_FILE_MB) * 1024 * 1024,
+        }
+    }
+
+    pub fn check_file_size(&self, bytes: u64) -> Result<(), DoubledeckerError> {
+        check_limit("file_bytes", self.max_file_bytes, bytes)
+    }
+
+    pub fn check_shape(&self, columns: u64, rows: u64) -> Result<(), DoubledeckerError> {
+        check_limit("columns", self.max_columns, columns)?;
+        check_limit("rows", self.max_rows, rows)
+    }
+
+    /// Limits a dataset within them is close to, as `(limit, max, actual)`.
+    pub fn nearing(&self, file_bytes: u64, columns: u64, rows: u64) -> Vec<(&'static str, u64, u64)> {
+        [("file_bytes", self.max_file_bytes, file_bytes), ("columns", self.max_columns, columns), ("rows", self.max_rows, rows)]
+            .into_iter()
+            .filter(|(_, max, actual)| {
+                (*actual as u128) * 100 >= (*max as u128) * UPLOAD_LIMIT_WARNING_PERCENT as u128
+            })
+            .collect()
+    }
+}
+
+fn check_limit(limit: &'static str, max: u64, actual: u64) -> Result<(), DoubledeckerError> {
+    if actual > max {
+        return Err(DoubledeckerError::UploadLimitExceeded { limit: limit.to_string(), max, actual });
+    }
+    Ok(())
+}
+
+/// Settings of the HTTP layer built by `build_app`.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub body_limits: BodyLimits,
+    pub request_limits: RequestLimits,
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        Self {
+            body_limits: BodyLimits::from_env(),
+            request_limits: RequestLimits::from_env(),
+        }
+    }
+}
+
+/// Process-wide JWT configuration, read from the environment on first use.
+pub fn jwt_config() -> &'static JwtConfig {
+    &JWT_CONFIG
+}
+
+/// Process-wide presigned URL lifetimes, read from the environment on first use.
+pub fn presign_config() -> &'static PresignConfig {
+    &PRESIGN_CONFIG
+}
+
+/// Process-wide upload limits, read from the environment on first use.
+pub fn upload_limits() -> &'static UploadLimits {
+    &UPLOAD_LIMITS
+}
+
+/// Process-wide trusted proxies, read from the environment on first use.
+pub fn trusted_proxies() -> &'static [IpNet] {
+    &TRUSTED_PROXIES
+}
+
+/// Process-wide private networks external connections may reach, read from the environment on first use.
+pub fn connection_allowed_networks() -> &'static [IpNet] {
+    &CONNECTION_ALLOWED_NETWORKS
+}
+
+/// Comma-separated addresses or CIDR ranges of `var`, e.g. `10.0.0.0/8`; empty when unset.
+///
+/// `TRUSTED_PROXIES` lists the load balancers allowed to report the client address in
+/// `X-Forwarded-For`; by default the TCP peer is the client. `CONNECTION_ALLOWED_NETWORKS` lists the
+/// private ranges external database connections may reach, e.g. a database inside the same VPC; by
+/// default only public addresses can be connected to.
+fn networks_from_env(var: &str) -> Vec<IpNet> {
+    let mut networks = Vec::new();
+    for entry in env::var(var).unwrap_or_default().split(',').map(str::trim) {
+        if entry.is_empty() {
+            continue;
+        }
+        match entry.parse::<IpNet>().or_else(|_| entry.parse::<std::net::IpAddr>().map(IpNet::from)) {
+            Ok(net) => networks.push(net),
+            Err(_) => eprintln!("Ignoring invalid {} entry '{}'", var, entry),
+        }
+    }
+    networks
+}
+
+/// Process-wide key of the `mask_hash` HMAC, read from the environment on first use; `None` when
+/// `MASK_HASH_KEY` is unset or too short.
+pub fn mask_hash_key() -> Option<&'static [u8]> {
+    MASK_HASH_KEY.as_deref()
+}
+
+/// `MASK_HASH_KEY`, any string of at least 32 bytes. It must be the same on every instance and
+/// across restarts for hashed values to stay comparable, so there is no generated fallback: without
+/// it the hash mask cannot be chosen and existing hash masks redact instead.
+fn mask_hash_key_from_env() -> Option<Vec<u8>> {
+    match env::var("MASK_HASH_KEY") {
+        Ok(key) if key.trim().len() >= 32 => Some(key.trim().as_bytes().to_vec()),
+        Ok(key) if !key.trim().is_empty() => {
+            eprintln!("MASK_HASH_KEY is shorter than 32 bytes; the hash mask is unavailable");
+            None
+        }
+        _ => None,
+    }
+}
@@ -121,9 +121,68 @@ pub struct QueryHistoryRecord {
     pub sql_executed: String,
     pub row_count: i64,
     pub execution_time_ms: i64,
+    /// In-memory size of the result batches
+    pub bytes_processed: i64,
+    /// Bytes read from Parquet files
+    pub bytes_scanned: i64,
+    /// Most memory the query reserved at once
+    pub peak_memory_bytes: i64,
+    pub tables_referenced: Vec<String>,
     pub created_at: DateTime<Utc>,
 }
 
+/// A query that ran at or over the slow-query threshold.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct SlowQuery {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub user_id: Option<Uuid>,
+    /// Set when the query was a pipeline run
+    pub pipeline_id: Option<Uuid>,
+    pub sql_executed: String,
+    /// The analytics request or pipeline query the SQL was built from
+    #[schema(value_type = Object)]
+    pub operations: sqlx::types::Json<serde_json::Value>,
+    /// Physical plan with each operator's rows, bytes and compute time
+    pub plan: String,
+    pub execution_time_ms: i64,
+    pub row_count: i64,
+    pub bytes_scanned: i64,
+    pub peak_memory_bytes: i64,
+    /// Threshold in force when the query ran
+    pub threshold_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Lifetime query totals for one user, across all workspaces.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserQueryTotals {
+    pub total_queries: i64,
+    pub total_bytes_scanned: i64,
+    pub avg_execution_time_ms: f64,
+}
+
+/// A user's usage counts from the `user_stats` view, always derived from the underlying rows.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UserStats {
+    pub total_queries: i64,
+    /// Datasets in the user's workspaces that finished processing
+    pub total_files_processed: i64,
+    pub total_storage_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DailyQueryCount {
+    pub day: NaiveDate,
+    pub query_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct TableQueryCount {
+    pub table_name: String,
+    pub query_count: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct User {
     pub id: Uuid,
@@ -136,6 +195,132 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+/// An issued login token. Tokens carry the session id (`sid`) and are rejected once it is revoked.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// An event waiting in the transactional outbox to be sent to Inngest.
+#[derive(Debug, Clone, FromRow)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub event_name: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A request made with an `Idempotency-Key`: still running while `status_code` is NULL, otherwise
+/// the response to replay.
+#[derive(Debug, Clone, FromRow)]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    pub status_code: Option<i16>,
+    pub content_type: Option<String>,
+    pub content_disposition: Option<String>,
+    pub body: Option<Vec<u8>>,
+}
+
+/// An account suspended by a platform admin. Suspended users are refused on every authenticated endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UserSuspension {
+    pub user_id: Uuid,
+    pub email: String,
+    pub reason: Option<String>,
+    pub suspended_at: DateTime<Utc>,
+    /// Admin who suspended the account
+    pub suspended_by: Option<Uuid>,
+}
+
+/// Platform-wide counters for the admin dashboard.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct PlatformTotals {
+    pub total_users: i64,
+    pub total_workspaces: i64,
+    pub total_datasets: i64,
+    pub total_storage_bytes: i64,
+    pub total_queries: i64,
+}
+
+/// Datasets and stored bytes across the workspaces a user owns.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct UserStorageUsage {
+    pub user_id: Uuid,
+    pub email: String,
+    pub dataset_count: i64,
+    pub storage_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct ErrorTypeCount {
+    pub error_type: String,
+    pub occurrences: i64,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct DatasetSize {
+    pub dataset_id: Uuid,
+    pub workspace_id: Uuid,
+    pub filename: String,
+    pub row_count: i64,
+    pub file_size_bytes: i64,
+}
+
+/// Display preferences stored server-side so they follow the user across devices.
+/// Every field is optional; unset means the client's own default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UserSettings {
+    /// Page size for paginated lists, 1-100
+    pub default_page_size: Option<u32>,
+    pub default_export_format: Option<crate::server::dtos::analytics::ExportFormat>,
+    /// IANA time zone name, e.g. `Europe/Berlin`
+    pub timezone: Option<String>,
+    pub date_format: Option<DateFormat>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum DateFormat {
+    #[serde(rename = "YYYY-MM-DD")]
+    Iso,
+    #[serde(rename = "DD/MM/YYYY")]
+    DayMonthYear,
+    #[serde(rename = "MM/DD/YYYY")]
+    MonthDayYear,
+}
+
+impl crate::server::validation::Validate for UserSettings {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        let mut errors = Vec::new();
+        if self.default_page_size.is_some_and(|size| !(1..=100).contains(&size)) {
+            errors.push(crate::utils::error::FieldError::new(
+                "default_page_size",
+                "out_of_range",
+                "default_page_size must be between 1 and 100",
+            ));
+        }
+        if let Some(tz) = &self.timezone
+            && tz.parse::<chrono_tz::Tz>().is_err()
+        {
+            errors.push(crate::utils::error::FieldError::new(
+                "timezone",
+                "invalid_timezone",
+                format!("Unknown time zone '{}'; use an IANA name like Europe/Berlin", tz),
+            ));
+        }
+        errors
+    }
+}
+
 impl User {
     pub fn get_user_type(&self) -> UserType {
         UserType::from_str(&self.user_type).unwrap_or(UserType::Artist)
@@ -147,6 +332,7 @@ pub struct Workspace {
     pub id: Uuid,
     pub owner_user_id: Uuid,
     pub name: String,
+    /// Total size of the workspace's datasets, computed from the `workspace_stats` view
     pub storage_used_bytes: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -219,10 +405,685 @@ pub struct Dataset {
     pub row_count: i64,
     pub status: String,
     pub error_message: Option<String>,
+    pub public_token: Option<String>,
+    /// Data key the converted Parquet is encrypted with
+    pub encryption_key_id: Option<Uuid>,
+    /// The staging file is encrypted with `encryption_key_id` too; only direct uploads pass
+    /// through the server on their way to staging
+    pub staging_encrypted: bool,
+    pub folder_id: Option<Uuid>,
+    #[schema(value_type = Vec<crate::normalization::SourceColumn>)]
+    pub source_columns: sqlx::types::Json<Vec<crate::normalization::SourceColumn>>,
+    pub source_encoding: Option<String>,
+    #[schema(value_type = Option<crate::normalization::InferredSchema>)]
+    pub inferred_schema: Option<sqlx::types::Json<crate::normalization::InferredSchema>>,
+    #[schema(value_type = Option<crate::normalization::ValidationReport>)]
+    pub validation_report: Option<sqlx::types::Json<crate::normalization::ValidationReport>>,
+    pub last_queried_at: Option<DateTime<Utc>>,
+    pub query_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where a dataset is in the upload and conversion pipeline. Stored as text in `datasets.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DatasetStatus {
+    /// Presigned URL issued; waiting for the client to PUT the file and confirm
+    PendingUpload,
+    /// File received; waiting for the ingestion worker
+    Queued,
+    /// Being converted to Parquet
+    Processing,
+    /// Queryable
+    Ready,
+    /// Conversion failed; `error_message` says why
+    Failed,
+    /// A content scan flagged the file, which was moved to quarantine and never converted;
+    /// `error_message` names what was found
+    Quarantined,
+}
+
+impl DatasetStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DatasetStatus::PendingUpload => "PENDING_UPLOAD",
+            DatasetStatus::Queued => "QUEUED",
+            DatasetStatus::Processing => "PROCESSING",
+            DatasetStatus::Ready => "READY",
+            DatasetStatus::Failed => "FAILED",
+            DatasetStatus::Quarantined => "QUARANTINED",
+        }
+    }
+}
+
+impl fmt::Display for DatasetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Something that happened to a dataset, as listed in its activity feed. Stored as text in
+/// `dataset_activity.action`, except `PipelineRun`, which comes from `pipeline_runs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum DatasetAction {
+    /// Uploaded, or made by compacting or importing other datasets
+    Created,
+    /// Read by an analytics query
+    Queried,
+    /// Its Parquet file was downloaded
+    Downloaded,
+    /// Made readable through a public link
+    Shared,
+    /// Its public link was revoked
+    Unshared,
+    /// A pipeline ran over it
+    PipelineRun,
+}
+
+impl DatasetAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DatasetAction::Created => "CREATED",
+            DatasetAction::Queried => "QUERIED",
+            DatasetAction::Downloaded => "DOWNLOADED",
+            DatasetAction::Shared => "SHARED",
+            DatasetAction::Unshared => "UNSHARED",
+            DatasetAction::PipelineRun => "PIPELINE_RUN",
+        }
+    }
+}
+
+/// One entry of a dataset's activity feed.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DatasetActivity {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    /// `CREATED`, `QUERIED`, `DOWNLOADED`, `SHARED`, `UNSHARED` or `PIPELINE_RUN`
+    pub action: String,
+    /// Who did it; `None` for pipeline runs, or once the user is deleted
+    pub user_id: Option<Uuid>,
+    pub user_name: Option<String>,
+    /// Action-specific, e.g. the datasets a compaction merged or the pipeline that ran
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<sqlx::types::Json<serde_json::Value>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A dataset, folder, pipeline, dashboard or segment whose name or description matched a search.
+/// `kind` is `DATASET`, `FOLDER`, `PIPELINE`, `DASHBOARD` or `SEGMENT`.
+#[derive(Debug, Clone, FromRow)]
+pub struct SearchHit {
+    pub kind: String,
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub workspace_name: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A resumable upload of one file, sent as numbered parts of `chunk_size` bytes (the last one may be
+/// shorter) in any order and assembled by an S3 multipart upload. After a dropped connection the
+/// client sends the parts missing from `received_parts` instead of starting over.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct UploadSession {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub user_id: Uuid,
+    pub filename: String,
+    pub distributor_source: String,
+    pub total_bytes: i64,
+    /// Size of every part but the last
+    pub chunk_size: i64,
+    /// Parts the file is sent as, numbered from 1
+    pub part_count: i32,
+    /// Bytes of the parts stored so far
+    pub bytes_received: i64,
+    /// Numbers of the parts stored so far, ascending
+    pub received_parts: Vec<i32>,
+    /// Where the file is assembled; ingested from there like a presigned upload
+    #[serde(skip)]
+    pub staging_key: String,
+    #[serde(skip)]
+    pub multipart_upload_id: String,
+    pub strict: bool,
+    /// Column type inference options the dataset is converted with
+    #[schema(value_type = Object)]
+    pub inference: sqlx::types::Json<serde_json::Value>,
+    /// `OPEN` while parts are accepted, `COMPLETING` while the file is assembled, then `COMPLETED`
+    pub status: String,
+    /// The dataset created from the file once the session is completed
+    pub dataset_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A stored part of an upload session.
+#[derive(Debug, Clone, FromRow)]
+pub struct UploadSessionPart {
+    pub part_number: i32,
+    pub size_bytes: i64,
+    pub sha256: String,
+    pub etag: String,
+}
+
+/// A folder organizing a workspace's datasets. Folders nest through `parent_id` (`None` at the top level).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Folder {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A saved structured query applied to datasets: on demand, or to every dataset whose filename
+/// matches `dataset_pattern` once it is processed.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Pipeline {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Glob on dataset filenames (`*` and `?`, case-insensitive), e.g. `sales_*`
+    pub dataset_pattern: Option<String>,
+    #[schema(value_type = crate::server::dtos::analytics::StructuredAnalyticsQuery)]
+    pub query: sqlx::types::Json<crate::server::dtos::analytics::StructuredAnalyticsQuery>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A named, reusable group of filters, e.g. "EU streaming", that structured queries reference by id
+/// in `segment_ids`. The filters are added to a query each time it runs.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Segment {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Conditions rows must all meet, as in a structured query's `filters`
+    #[schema(value_type = Vec<crate::server::dtos::analytics::QueryFilter>)]
+    pub filters: sqlx::types::Json<Vec<crate::server::dtos::analytics::QueryFilter>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An expectation on one column of `royalty_data`, e.g. "isrc is never null", checked against a
+/// dataset's rows by quality runs.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct QualityRule {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub column_name: String,
+    #[schema(value_type = crate::server::dtos::quality::Expectation)]
+    pub expectation: sqlx::types::Json<crate::server::dtos::quality::Expectation>,
+    /// Checked on every dataset when it finishes processing, not only on demand
+    pub run_on_upload: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One check of a dataset against the rules of its workspace. `passed` when every rule did.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct QualityRun {
+    pub id: Uuid,
+    pub dataset_id: Uuid,
+    /// `UPLOAD` or `MANUAL`
+    pub trigger: String,
+    pub passed: bool,
+    #[schema(value_type = Vec<crate::server::dtos::quality::RuleOutcome>)]
+    pub results: sqlx::types::Json<Vec<crate::server::dtos::quality::RuleOutcome>>,
+    /// Who asked for a manual run
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A comment on a pipeline; replies set `parent_id` to the comment they answer.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PipelineComment {
+    pub id: Uuid,
+    pub pipeline_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    /// `None` once the author's account is deleted
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub body: String,
+    /// Workspace members @-mentioned by email in the body
+    pub mentions: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What started a pipeline or quality run. Stored as text in `pipeline_runs.trigger` and
+/// `quality_runs.trigger`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PipelineTrigger {
+    /// A matching dataset, or for quality runs any dataset, finished processing
+    Upload,
+    Manual,
+}
+
+impl PipelineTrigger {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PipelineTrigger::Upload => "UPLOAD",
+            PipelineTrigger::Manual => "MANUAL",
+        }
+    }
+}
+
+/// One application of a pipeline to one dataset. `result` holds the query output in the row layout.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PipelineRun {
+    pub id: Uuid,
+    pub pipeline_id: Uuid,
+    pub dataset_id: Uuid,
+    /// `UPLOAD` or `MANUAL`
+    pub trigger: String,
+    /// `SUCCEEDED` or `FAILED`
+    pub status: String,
+    pub row_count: i64,
+    #[schema(value_type = Option<crate::server::dtos::analytics::AnalyticsQueryResponse>)]
+    pub result: Option<sqlx::types::Json<serde_json::Value>>,
+    pub error_message: Option<String>,
+    pub execution_time_ms: i64,
+    /// Bytes read from Parquet files
+    pub bytes_scanned: i64,
+    /// Most memory the query reserved at once
+    pub peak_memory_bytes: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A named set of tiles, each showing the result of a pipeline's query.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Dashboard {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How a client should draw a dashboard tile. Stored as text in `dashboard_tiles.visualization`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Visualization {
+    Table,
+    Bar,
+    Line,
+}
+
+impl Visualization {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visualization::Table => "TABLE",
+            Visualization::Bar => "BAR",
+            Visualization::Line => "LINE",
+        }
+    }
+}
+
+/// One tile of a dashboard: a pipeline run on every ready dataset of the workspace, or on
+/// `dataset_id` only.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DashboardTile {
+    pub id: Uuid,
+    pub dashboard_id: Uuid,
+    /// Display order, from 0
+    pub position: i32,
+    /// Shown instead of the pipeline's name when set
+    pub title: Option<String>,
+    pub pipeline_id: Uuid,
+    pub dataset_id: Option<Uuid>,
+    /// `TABLE`, `BAR` or `LINE`
+    pub visualization: String,
+}
+
+/// Where a schedule posts its summaries. Stored as text in `pipeline_schedules.channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotifyChannel {
+    /// A Slack incoming webhook
+    Slack,
+    /// A Microsoft Teams incoming webhook or workflow
+    Teams,
+}
+
+impl NotifyChannel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotifyChannel::Slack => "SLACK",
+            NotifyChannel::Teams => "TEAMS",
+        }
+    }
+}
+
+impl FromStr for NotifyChannel {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SLACK" => Ok(NotifyChannel::Slack),
+            "TEAMS" => Ok(NotifyChannel::Teams),
+            _ => Err(()),
+        }
+    }
+}
+
+/// When a scheduled run posts its summary. Stored as text in `pipeline_schedules.notify_when`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum NotifyWhen {
+    /// After every run
+    Always,
+    /// When the query returns any rows, e.g. a check for orders with missing SKUs
+    AnyRows,
+    /// When any row's threshold column compares true against the threshold value
+    Threshold,
+    /// Never; the schedule only feeds its alerts
+    Never,
+}
+
+impl NotifyWhen {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NotifyWhen::Always => "ALWAYS",
+            NotifyWhen::AnyRows => "ANY_ROWS",
+            NotifyWhen::Threshold => "THRESHOLD",
+            NotifyWhen::Never => "NEVER",
+        }
+    }
+}
+
+impl FromStr for NotifyWhen {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ALWAYS" => Ok(NotifyWhen::Always),
+            "ANY_ROWS" => Ok(NotifyWhen::AnyRows),
+            "THRESHOLD" => Ok(NotifyWhen::Threshold),
+            "NEVER" => Ok(NotifyWhen::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Comparison of a schedule's threshold. Stored as text in `pipeline_schedules.threshold_op`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ThresholdOp {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Eq,
+    Ne,
+}
+
+impl ThresholdOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThresholdOp::Gt => "GT",
+            ThresholdOp::Gte => "GTE",
+            ThresholdOp::Lt => "LT",
+            ThresholdOp::Lte => "LTE",
+            ThresholdOp::Eq => "EQ",
+            ThresholdOp::Ne => "NE",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            ThresholdOp::Gt => ">",
+            ThresholdOp::Gte => ">=",
+            ThresholdOp::Lt => "<",
+            ThresholdOp::Lte => "<=",
+            ThresholdOp::Eq => "=",
+            ThresholdOp::Ne => "!=",
+        }
+    }
+
+    pub fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            ThresholdOp::Gt => value > threshold,
+            ThresholdOp::Gte => value >= threshold,
+            ThresholdOp::Lt => value < threshold,
+            ThresholdOp::Lte => value <= threshold,
+            ThresholdOp::Eq => value == threshold,
+            ThresholdOp::Ne => value != threshold,
+        }
+    }
+}
+
+impl FromStr for ThresholdOp {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "GT" => Ok(ThresholdOp::Gt),
+            "GTE" => Ok(ThresholdOp::Gte),
+            "LT" => Ok(ThresholdOp::Lt),
+            "LTE" => Ok(ThresholdOp::Lte),
+            "EQ" => Ok(ThresholdOp::Eq),
+            "NE" => Ok(ThresholdOp::Ne),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A pipeline run over every ready dataset of the workspace on an interval, with its summary
+/// posted to a Slack or Teams webhook.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct PipelineSchedule {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub pipeline_id: Uuid,
+    pub interval_minutes: i32,
+    pub enabled: bool,
+    pub next_run_at: DateTime<Utc>,
+    /// `SLACK` or `TEAMS`; unset when `notify_when` is `NEVER`
+    pub channel: Option<String>,
+    /// Never serialized; decrypt with `utils::crypto::decrypt_secret` when posting.
+    #[serde(skip)]
+    pub encrypted_webhook_url: Option<String>,
+    /// `ALWAYS`, `ANY_ROWS`, `THRESHOLD` or `NEVER`
+    pub notify_when: String,
+    pub threshold_column: Option<String>,
+    /// `GT`, `GTE`, `LT`, `LTE`, `EQ` or `NE`
+    pub threshold_op: Option<String>,
+    pub threshold_value: Option<f64>,
+    /// Rows quoted in each summary
+    pub top_rows: i32,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// `NOTIFIED`, `QUIET` (the condition did not hold) or `FAILED`
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What an alert compares against its threshold. Stored as text in `schedule_alerts.aggregate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertAggregate {
+    /// Rows in the result; needs no column
+    RowCount,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl AlertAggregate {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertAggregate::RowCount => "ROW_COUNT",
+            AlertAggregate::Sum => "SUM",
+            AlertAggregate::Min => "MIN",
+            AlertAggregate::Max => "MAX",
+            AlertAggregate::Avg => "AVG",
+        }
+    }
+}
+
+impl FromStr for AlertAggregate {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ROW_COUNT" => Ok(AlertAggregate::RowCount),
+            "SUM" => Ok(AlertAggregate::Sum),
+            "MIN" => Ok(AlertAggregate::Min),
+            "MAX" => Ok(AlertAggregate::Max),
+            "AVG" => Ok(AlertAggregate::Avg),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether an alert's condition held on its last evaluation. Stored as text in `schedule_alerts.state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlertState {
+    Ok,
+    Firing,
+}
+
+impl AlertState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertState::Ok => "OK",
+            AlertState::Firing => "FIRING",
+        }
+    }
+}
+
+/// A condition on an aggregate of a schedule's result, e.g. `SUM(errors) > 10`, evaluated after each
+/// run. It notifies its emails and webhook when it starts firing and, if `notify_on_resolve`, when
+/// it stops; runs that leave the state unchanged notify nobody.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct ScheduleAlert {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub schedule_id: Uuid,
+    pub name: String,
+    /// `ROW_COUNT`, `SUM`, `MIN`, `MAX` or `AVG`
+    pub aggregate: String,
+    /// Result column aggregated; unset for `ROW_COUNT`
+    pub column_name: Option<String>,
+    /// `GT`, `GTE`, `LT`, `LTE`, `EQ` or `NE`
+    pub op: String,
+    pub threshold: f64,
+    pub emails: Vec<String>,
+    /// `SLACK` or `TEAMS` when the alert posts to a webhook
+    pub channel: Option<String>,
+    /// Never serialized; decrypt with `utils::crypto::decrypt_secret` when posting.
+    #[serde(skip)]
+    pub encrypted_webhook_url: Option<String>,
+    pub notify_on_resolve: bool,
+    pub enabled: bool,
+    /// `OK` or `FIRING`
+    pub state: String,
+    /// The aggregate on the last evaluation; unset when there was nothing to aggregate
+    pub last_value: Option<f64>,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    pub state_changed_at: Option<DateTime<Utc>>,
+    pub last_notified_at: Option<DateTime<Utc>>,
+    /// Why the last evaluation or notification failed
+    pub last_error: Option<String>,
+    pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Where a background job is. Stored as text in `background_jobs.status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum JobStatus {
+    /// Waiting for `run_at`, including between retries
+    Queued,
+    Running,
+    Succeeded,
+    /// Failed on its last attempt; `last_error` says why
+    Failed,
+    /// Cancelled by a user before it finished
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "QUEUED",
+            JobStatus::Running => "RUNNING",
+            JobStatus::Succeeded => "SUCCEEDED",
+            JobStatus::Failed => "FAILED",
+            JobStatus::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+/// A unit of background work, run by the job runner in `workers::jobs`.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct BackgroundJob {
+    pub id: Uuid,
+    pub workspace_id: Option<Uuid>,
+    /// Handler that runs the job, e.g. `pipeline.run`
+    pub kind: String,
+    #[schema(value_type = Object)]
+    pub payload: sqlx::types::Json<serde_json::Value>,
+    /// `QUEUED`, `RUNNING`, `SUCCEEDED`, `FAILED` or `CANCELLED`
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Earliest time the job runs, or runs again after a failed attempt
+    pub run_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<sqlx::types::Json<serde_json::Value>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct DataConnection {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub kind: String,
+    /// Never serialized; decrypt with `utils::crypto::decrypt_secret` when connecting.
+    #[serde(skip)]
+    pub encrypted_connection_string: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A user's data key, wrapped by the master key. Unwrap with `utils::crypto::unwrap_data_key`.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserDataKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub wrapped_key: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A governed column of a dataset. `mode` is one of HIDE, HASH, REDACT, PARTIAL.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ColumnRestriction {
+    pub column_name: String,
+    pub mode: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct FxRate {
     pub date: NaiveDate,
@@ -256,6 +1117,204 @@ impl PaginationParams {
     }
 }
 
+impl crate::server::validation::Validate for PaginationParams {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        let mut errors = Vec::new();
+        if let Some(cursor) = &self.cursor
+            && Uuid::parse_str(cursor).is_err()
+        {
+            errors.push(crate::utils::error::FieldError::new(
+                "cursor",
+                "invalid_format",
+                "cursor must be a value returned as next_cursor",
+            ));
+        }
+        if self.limit == Some(0) || self.limit.is_some_and(|l| l > 100) {
+            errors.push(crate::utils::error::FieldError::new(
+                "limit",
+                "out_of_range",
+                "limit must be between 1 and 100",
+            ));
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetSortField {
+    #[default]
+    CreatedAt,
+    Filename,
+    FileSizeBytes,
+    RowCount,
+    LastQueriedAt,
+    QueryCount,
+}
+
+impl DatasetSortField {
+    pub fn column(self) -> &'static str {
+        match self {
+            DatasetSortField::CreatedAt => "created_at",
+            DatasetSortField::Filename => "filename",
+            DatasetSortField::FileSizeBytes => "file_size_bytes",
+            DatasetSortField::RowCount => "row_count",
+            // Never-queried datasets sort as the least recently used
+            DatasetSortField::LastQueriedAt => "COALESCE(last_queried_at, '-infinity'::timestamptz)",
+            DatasetSortField::QueryCount => "query_count",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Status filter for listing a workspace's background jobs, on top of cursor pagination.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct JobListParams {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    pub status: Option<JobStatus>,
+}
+
+impl JobListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl crate::server::validation::Validate for JobListParams {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        self.pagination().validate()
+    }
+}
+
+/// Workspace filter for listing slow queries, on top of cursor pagination.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct SlowQueryListParams {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    pub workspace_id: Option<Uuid>,
+}
+
+impl SlowQueryListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl crate::server::validation::Validate for SlowQueryListParams {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        self.pagination().validate()
+    }
+}
+
+/// Favorites filter for listing a workspace's pipelines, on top of cursor pagination.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct PipelineListParams {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    /// Only pipelines the caller has favorited
+    pub favorites: Option<bool>,
+}
+
+impl PipelineListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl crate::server::validation::Validate for PipelineListParams {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        self.pagination().validate()
+    }
+}
+
+/// Search, filters and ordering for listing a workspace's datasets, on top of cursor pagination.
+/// The cursor stays valid only while the sort and filters are unchanged.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct DatasetListParams {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    /// Case-insensitive substring of the filename
+    pub search: Option<String>,
+    /// Exact distributor source, e.g. `spotify` (the statement format of the file)
+    pub distributor_source: Option<String>,
+    /// Processing status
+    pub status: Option<DatasetStatus>,
+    pub min_size_bytes: Option<i64>,
+    pub max_size_bytes: Option<i64>,
+    /// Uploaded at or after this instant
+    pub created_after: Option<DateTime<Utc>>,
+    /// Uploaded before this instant
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only datasets directly in this folder
+    pub folder_id: Option<Uuid>,
+    /// Only datasets not in any folder
+    pub unfiled: Option<bool>,
+    /// Only datasets the caller has favorited
+    pub favorites: Option<bool>,
+    /// Default `created_at`
+    pub sort_by: Option<DatasetSortField>,
+    /// Default `desc`
+    pub sort_dir: Option<SortDirection>,
+}
+
+impl DatasetListParams {
+    pub fn pagination(&self) -> PaginationParams {
+        PaginationParams {
+            cursor: self.cursor.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl crate::server::validation::Validate for DatasetListParams {
+    fn validate(&self) -> Vec<crate::utils::error::FieldError> {
+        use crate::utils::error::FieldError;
+        let mut errors = self.pagination().validate();
+        if self.min_size_bytes.is_some_and(|s| s < 0) {
+            errors.push(FieldError::new("min_size_bytes", "out_of_range", "min_size_bytes cannot be negative"));
+        }
+        if let (Some(min), Some(max)) = (self.min_size_bytes, self.max_size_bytes)
+            && min > max
+        {
+            errors.push(FieldError::new(
+                "max_size_bytes",
+                "out_of_range",
+                "max_size_bytes must not be less than min_size_bytes",
+            ));
+        }
+        if self.folder_id.is_some() && self.unfiled == Some(true) {
+            errors.push(FieldError::new("unfiled", "conflict", "unfiled cannot be combined with folder_id"));
+        }
+        if let (Some(after), Some(before)) = (self.created_after, self.created_before)
+            && after >= before
+        {
+            errors.push(FieldError::new(
+                "created_before",
+                "out_of_range",
+                "created_before must be later than created_after",
+            ));
+        }
+        errors
+    }
+}
+
 #[derive(Debug, Clone, Serialize, ToSchema)]
 pub struct PaginationMeta {
     pub next_cursor: Option<String>,
@@ -273,7 +1332,22 @@ pub struct PaginationMeta {
     PaginatedPayees = PaginatedResponse<Payee>,
     PaginatedSplits = PaginatedResponse<CascadingSplit>,
     PaginatedDatasets = PaginatedResponse<crate::server::dtos::common::DatasetResponse>,
-    PaginatedQueryHistory = PaginatedResponse<QueryHistoryRecord>
+    PaginatedQueryHistory = PaginatedResponse<QueryHistoryRecord>,
+    PaginatedDataConnections = PaginatedResponse<DataConnection>,
+    PaginatedFolders = PaginatedResponse<Folder>,
+    PaginatedPipelines = PaginatedResponse<Pipeline>,
+    PaginatedSegments = PaginatedResponse<Segment>,
+    PaginatedQualityRules = PaginatedResponse<QualityRule>,
+    PaginatedQualityRuns = PaginatedResponse<QualityRun>,
+    PaginatedPipelineRuns = PaginatedResponse<PipelineRun>,
+    PaginatedPipelineComments = PaginatedResponse<PipelineComment>,
+    PaginatedDatasetActivity = PaginatedResponse<DatasetActivity>,
+    PaginatedDashboards = PaginatedResponse<Dashboard>,
+    PaginatedPipelineSchedules = PaginatedResponse<PipelineSchedule>,
+    PaginatedScheduleAlerts = PaginatedResponse<ScheduleAlert>,
+    PaginatedBackgroundJobs = PaginatedResponse<BackgroundJob>,
+    PaginatedUserSuspensions = PaginatedResponse<UserSuspension>,
+    PaginatedSlowQueries = PaginatedResponse<SlowQuery>
 )]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
@@ -0,0 +1,67 @@
+use crate::db::models::{DatasetAction, DatasetActivity, PaginatedResponse};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use serde_json::Value;
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Add an entry to a dataset's activity feed.
+pub async fn record_dataset_activity(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    user_id: Option<Uuid>,
+    action: DatasetAction,
+    details: Option<Value>,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("INSERT INTO dataset_activity (dataset_id, user_id, action, details) VALUES ($1, $2, $3, $4)")
+        .bind(dataset_id)
+        .bind(user_id)
+        .bind(action.as_str())
+        .bind(details.map(Json))
+        .execute(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+/// A dataset's activity, newest first: its recorded activity together with the pipeline runs on it.
+pub async fn list_dataset_activity(
+    pool: &PgPool,
+    dataset_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<DatasetActivity>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, DatasetActivity>(
+        r#"
+        WITH feed AS (
+            SELECT a.id, a.dataset_id, a.action, a.user_id, u.name AS user_name, a.details, a.created_at
+            FROM dataset_activity a LEFT JOIN users u ON u.id = a.user_id
+            WHERE a.dataset_id = $1
+            UNION ALL
+            SELECT r.id, r.dataset_id, 'PIPELINE_RUN', NULL, NULL,
+                   jsonb_build_object('pipeline_id', r.pipeline_id, 'pipeline_name', p.name, 'status', r.status),
+                   r.created_at
+            FROM pipeline_runs r JOIN pipelines p ON p.id = r.pipeline_id
+            WHERE r.dataset_id = $1
+        )
+        SELECT id, dataset_id, action, user_id, user_name, details, created_at
+        FROM feed
+        WHERE $2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM feed WHERE id = $2)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(dataset_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
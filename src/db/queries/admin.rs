@@ -0,0 +1,77 @@
+use crate::db::models::{DatasetSize, ErrorTypeCount, PlatformTotals, UserStorageUsage};
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+
+/// Platform-wide counters for the admin dashboard.
+pub async fn get_platform_totals(pool: &PgPool) -> Result<PlatformTotals, DoubledeckerError> {
+    sqlx::query_as::<_, PlatformTotals>(
+        r#"
+        SELECT (SELECT COUNT(*) FROM users) AS total_users,
+               (SELECT COUNT(*) FROM workspaces) AS total_workspaces,
+               (SELECT COUNT(*) FROM datasets) AS total_datasets,
+               (SELECT COALESCE(SUM(file_size_bytes), 0)::BIGINT FROM datasets) AS total_storage_bytes,
+               (SELECT COUNT(*) FROM query_history) AS total_queries
+        "#,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Uploads and stored bytes per user, counting datasets in the workspaces they own. Heaviest first.
+pub async fn list_storage_by_user(pool: &PgPool, limit: i64) -> Result<Vec<UserStorageUsage>, DoubledeckerError> {
+    sqlx::query_as::<_, UserStorageUsage>(
+        r#"
+        SELECT u.id AS user_id, u.email,
+               COUNT(d.id) AS dataset_count,
+               COALESCE(SUM(d.file_size_bytes), 0)::BIGINT AS storage_bytes
+        FROM users u
+        LEFT JOIN workspaces w ON w.owner_user_id = u.id
+        LEFT JOIN datasets d ON d.workspace_id = w.id
+        GROUP BY u.id, u.email
+        ORDER BY storage_bytes DESC, dataset_count DESC, u.email
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Most frequent dataset processing errors, grouped by the text before the first `:`
+/// so the same failure with different details counts once.
+pub async fn list_top_error_types(pool: &PgPool, limit: i64) -> Result<Vec<ErrorTypeCount>, DoubledeckerError> {
+    sqlx::query_as::<_, ErrorTypeCount>(
+        r#"
+        SELECT LEFT(SPLIT_PART(error_message, ':', 1), 200) AS error_type,
+               COUNT(*) AS occurrences,
+               MAX(updated_at) AS last_seen_at
+        FROM datasets
+        WHERE error_message IS NOT NULL
+        GROUP BY 1
+        ORDER BY occurrences DESC, last_seen_at DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Largest datasets on the platform by row count.
+pub async fn list_largest_datasets(pool: &PgPool, limit: i64) -> Result<Vec<DatasetSize>, DoubledeckerError> {
+    sqlx::query_as::<_, DatasetSize>(
+        r#"
+        SELECT id AS dataset_id, workspace_id, filename, row_count, file_size_bytes
+        FROM datasets
+        ORDER BY row_count DESC, file_size_bytes DESC
+        LIMIT $1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
@@ -0,0 +1,197 @@
+use crate::db::models::{PaginatedResponse, ScheduleAlert};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+const ALERT_COLUMNS: &str = "id, workspace_id, schedule_id, name, aggregate, column_name, op, threshold, emails, channel, \
+                             encrypted_webhook_url, notify_on_resolve, enabled, state, last_value, last_evaluated_at, \
+                             state_changed_at, last_notified_at, last_error, created_by, created_at, updated_at";
+
+fn alert_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Alert not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("This schedule already has an alert with this name".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+/// Settings of an alert as stored, shared by create and update.
+pub struct AlertSettings<'a> {
+    pub name: &'a str,
+    pub aggregate: &'a str,
+    pub column_name: Option<&'a str>,
+    pub op: &'a str,
+    pub threshold: f64,
+    pub emails: &'a [String],
+    pub channel: Option<&'a str>,
+    pub encrypted_webhook_url: Option<&'a str>,
+    pub notify_on_resolve: bool,
+    pub enabled: bool,
+}
+
+pub async fn create_alert(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    schedule_id: Uuid,
+    settings: &AlertSettings<'_>,
+    created_by: Uuid,
+) -> Result<ScheduleAlert, DoubledeckerError> {
+    sqlx::query_as::<_, ScheduleAlert>(&format!(
+        r#"
+        INSERT INTO schedule_alerts (
+            workspace_id, schedule_id, name, aggregate, column_name, op, threshold, emails, channel,
+            encrypted_webhook_url, notify_on_resolve, enabled, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        RETURNING {ALERT_COLUMNS}
+        "#
+    ))
+    .bind(workspace_id)
+    .bind(schedule_id)
+    .bind(settings.name)
+    .bind(settings.aggregate)
+    .bind(settings.column_name)
+    .bind(settings.op)
+    .bind(settings.threshold)
+    .bind(settings.emails)
+    .bind(settings.channel)
+    .bind(settings.encrypted_webhook_url)
+    .bind(settings.notify_on_resolve)
+    .bind(settings.enabled)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(alert_error)
+}
+
+pub async fn list_alerts(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<ScheduleAlert>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, ScheduleAlert>(&format!(
+        r#"
+        SELECT {ALERT_COLUMNS}
+        FROM schedule_alerts
+        WHERE schedule_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM schedule_alerts WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#
+    ))
+    .bind(schedule_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+/// Enabled alerts of a schedule, evaluated after each of its runs.
+pub async fn list_enabled_schedule_alerts(
+    pool: &PgPool,
+    schedule_id: Uuid,
+) -> Result<Vec<ScheduleAlert>, DoubledeckerError> {
+    sqlx::query_as::<_, ScheduleAlert>(&format!(
+        "SELECT {ALERT_COLUMNS} FROM schedule_alerts WHERE schedule_id = $1 AND enabled ORDER BY created_at"
+    ))
+    .bind(schedule_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_alert(pool: &PgPool, schedule_id: Uuid, alert_id: Uuid) -> Result<ScheduleAlert, DoubledeckerError> {
+    sqlx::query_as::<_, ScheduleAlert>(&format!(
+        "SELECT {ALERT_COLUMNS} FROM schedule_alerts WHERE id = $1 AND schedule_id = $2"
+    ))
+    .bind(alert_id)
+    .bind(schedule_id)
+    .fetch_one(pool)
+    .await
+    .map_err(alert_error)
+}
+
+/// Replace an alert's settings. Its condition may have changed, so it starts over as `OK`.
+pub async fn update_alert(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    alert_id: Uuid,
+    settings: &AlertSettings<'_>,
+) -> Result<ScheduleAlert, DoubledeckerError> {
+    sqlx::query_as::<_, ScheduleAlert>(&format!(
+        r#"
+        UPDATE schedule_alerts
+        SET name = $3, aggregate = $4, column_name = $5, op = $6, threshold = $7, emails = $8, channel = $9,
+            encrypted_webhook_url = $10, notify_on_resolve = $11, enabled = $12, state = 'OK', last_value = NULL,
+            last_error = NULL, state_changed_at = NULL, updated_at = NOW()
+        WHERE id = $1 AND schedule_id = $2
+        RETURNING {ALERT_COLUMNS}
+        "#
+    ))
+    .bind(alert_id)
+    .bind(schedule_id)
+    .bind(settings.name)
+    .bind(settings.aggregate)
+    .bind(settings.column_name)
+    .bind(settings.op)
+    .bind(settings.threshold)
+    .bind(settings.emails)
+    .bind(settings.channel)
+    .bind(settings.encrypted_webhook_url)
+    .bind(settings.notify_on_resolve)
+    .bind(settings.enabled)
+    .fetch_one(pool)
+    .await
+    .map_err(alert_error)
+}
+
+pub async fn delete_alert(pool: &PgPool, schedule_id: Uuid, alert_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let result = sqlx::query("DELETE FROM schedule_alerts WHERE id = $1 AND schedule_id = $2")
+        .bind(alert_id)
+        .bind(schedule_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
+
+/// Record an evaluation of an alert: its (possibly unchanged) state, the aggregate it saw, whether
+/// anyone was notified and what went wrong, if anything.
+pub async fn record_alert_evaluation(
+    pool: &PgPool,
+    alert_id: Uuid,
+    state: &str,
+    value: Option<f64>,
+    notified: bool,
+    error: Option<&str>,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query(
+        r#"
+        UPDATE schedule_alerts
+        SET state = $2, last_value = $3, last_evaluated_at = NOW(), last_error = $5,
+            state_changed_at = CASE WHEN state <> $2 THEN NOW() ELSE state_changed_at END,
+            last_notified_at = CASE WHEN $4 THEN NOW() ELSE last_notified_at END
+        WHERE id = $1
+        "#,
+    )
+    .bind(alert_id)
+    .bind(state)
+    .bind(value)
+    .bind(notified)
+    .bind(error)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
@@ -158,26 +158,26 @@ pub async fn upsert_album(
     upc: Option<String>,
     release_date: Option<NaiveDate>,
 ) -> Result<Album, DoubledeckerError> {
-    if let Some(ref upc_val) = upc {
-        if !upc_val.trim().is_empty() {
-            let album = sqlx::query_as::<_, Album>(
-                r#"
-                INSERT INTO albums (owner_user_id, artist_id, title, upc, release_date)
-                VALUES ($1, $2, $3, $4, $5)
-                ON CONFLICT (owner_user_id, upc) DO UPDATE SET title = EXCLUDED.title, artist_id = EXCLUDED.artist_id
-                RETURNING id, owner_user_id, artist_id, title, upc, release_date, created_at
-                "#,
-            )
-            .bind(owner_user_id)
-            .bind(artist_id)
-            .bind(&title)
-            .bind(upc)
-            .bind(release_date)
-            .fetch_one(pool)
-            .await
-            .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
-            return Ok(album);
-        }
+    if let Some(ref upc_val) = upc
+        && !upc_val.trim().is_empty()
+    {
+        let album = sqlx::query_as::<_, Album>(
+            r#"
+            INSERT INTO albums (owner_user_id, artist_id, title, upc, release_date)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (owner_user_id, upc) DO UPDATE SET title = EXCLUDED.title, artist_id = EXCLUDED.artist_id
+            RETURNING id, owner_user_id, artist_id, title, upc, release_date, created_at
+            "#,
+        )
+        .bind(owner_user_id)
+        .bind(artist_id)
+        .bind(&title)
+        .bind(upc)
+        .bind(release_date)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+        return Ok(album);
     }
 
     if let Ok(existing) = sqlx::query_as::<_, Album>(
@@ -0,0 +1,158 @@
+use crate::db::models::{PaginatedResponse, PipelineComment};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn comment_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Comment not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+pub async fn create_pipeline_comment(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    parent_id: Option<Uuid>,
+    author_id: Uuid,
+    body: &str,
+    mentions: &[Uuid],
+) -> Result<PipelineComment, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineComment>(
+        r#"
+        WITH c AS (
+            INSERT INTO pipeline_comments (pipeline_id, parent_id, author_id, body, mentions)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+        )
+        SELECT c.id, c.pipeline_id, c.parent_id, c.author_id, u.name AS author_name, c.body, c.mentions,
+               c.created_at, c.updated_at
+        FROM c LEFT JOIN users u ON u.id = c.author_id
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(parent_id)
+    .bind(author_id)
+    .bind(body)
+    .bind(mentions)
+    .fetch_one(pool)
+    .await
+    .map_err(comment_error)
+}
+
+/// Comments of a pipeline, oldest first; clients assemble threads from `parent_id`.
+pub async fn list_pipeline_comments(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<PipelineComment>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, PipelineComment>(
+        r#"
+        SELECT c.id, c.pipeline_id, c.parent_id, c.author_id, u.name AS author_name, c.body, c.mentions,
+               c.created_at, c.updated_at
+        FROM pipeline_comments c LEFT JOIN users u ON u.id = c.author_id
+        WHERE c.pipeline_id = $1
+          AND ($2::uuid IS NULL OR (c.created_at, c.id) > (SELECT created_at, id FROM pipeline_comments WHERE id = $2))
+        ORDER BY c.created_at ASC, c.id ASC
+        LIMIT $3
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_pipeline_comment(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    comment_id: Uuid,
+) -> Result<PipelineComment, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineComment>(
+        r#"
+        SELECT c.id, c.pipeline_id, c.parent_id, c.author_id, u.name AS author_name, c.body, c.mentions,
+               c.created_at, c.updated_at
+        FROM pipeline_comments c LEFT JOIN users u ON u.id = c.author_id
+        WHERE c.id = $1 AND c.pipeline_id = $2
+        "#,
+    )
+    .bind(comment_id)
+    .bind(pipeline_id)
+    .fetch_one(pool)
+    .await
+    .map_err(comment_error)
+}
+
+pub async fn update_pipeline_comment(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    comment_id: Uuid,
+    body: &str,
+    mentions: &[Uuid],
+) -> Result<PipelineComment, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineComment>(
+        r#"
+        WITH c AS (
+            UPDATE pipeline_comments
+            SET body = $3, mentions = $4, updated_at = NOW()
+            WHERE id = $1 AND pipeline_id = $2
+            RETURNING *
+        )
+        SELECT c.id, c.pipeline_id, c.parent_id, c.author_id, u.name AS author_name, c.body, c.mentions,
+               c.created_at, c.updated_at
+        FROM c LEFT JOIN users u ON u.id = c.author_id
+        "#,
+    )
+    .bind(comment_id)
+    .bind(pipeline_id)
+    .bind(body)
+    .bind(mentions)
+    .fetch_one(pool)
+    .await
+    .map_err(comment_error)
+}
+
+/// Delete a comment and, with it, the replies to it.
+pub async fn delete_pipeline_comment(pool: &PgPool, pipeline_id: Uuid, comment_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM pipeline_comments WHERE id = $1 AND pipeline_id = $2")
+        .bind(comment_id)
+        .bind(pipeline_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// The owner and members of the workspace with one of `emails` (compared case-insensitively).
+pub async fn find_workspace_members_by_email(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    emails: &[String],
+) -> Result<Vec<Uuid>, DoubledeckerError> {
+    sqlx::query_scalar(
+        r#"
+        SELECT u.id
+        FROM users u
+        WHERE LOWER(u.email) = ANY($2)
+          AND (EXISTS (SELECT 1 FROM workspaces w WHERE w.id = $1 AND w.owner_user_id = u.id)
+               OR EXISTS (SELECT 1 FROM workspace_members m WHERE m.workspace_id = $1 AND m.user_id = u.id))
+        ORDER BY u.id
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(emails.iter().map(|e| e.to_lowercase()).collect::<Vec<_>>())
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
@@ -0,0 +1,104 @@
+use crate::db::models::{DataConnection, PaginatedResponse};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+pub async fn create_data_connection(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    kind: &str,
+    encrypted_connection_string: &str,
+    created_by: Uuid,
+) -> Result<DataConnection, DoubledeckerError> {
+    let conn = sqlx::query_as::<_, DataConnection>(
+        r#"
+        INSERT INTO data_connections (workspace_id, name, kind, encrypted_connection_string, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, workspace_id, name, kind, encrypted_connection_string, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(kind)
+    .bind(encrypted_connection_string)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest(format!("A connection named '{}' already exists", name))
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    })?;
+
+    Ok(conn)
+}
+
+pub async fn list_data_connections(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<DataConnection>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, DataConnection>(
+        r#"
+        SELECT id, workspace_id, name, kind, encrypted_connection_string, created_by, created_at, updated_at
+        FROM data_connections
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM data_connections WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_data_connection(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    connection_id: Uuid,
+) -> Result<DataConnection, DoubledeckerError> {
+    sqlx::query_as::<_, DataConnection>(
+        r#"
+        SELECT id, workspace_id, name, kind, encrypted_connection_string, created_by, created_at, updated_at
+        FROM data_connections
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(connection_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Connection not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    })
+}
+
+pub async fn delete_data_connection(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    connection_id: Uuid,
+) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM data_connections WHERE id = $1 AND workspace_id = $2")
+        .bind(connection_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,183 @@
+use crate::db::models::{Dashboard, DashboardTile, PaginatedResponse};
+use crate::db::queries::common::paginate_rows;
+use crate::server::dtos::dashboards::DashboardTileRequest;
+use crate::utils::error::DoubledeckerError;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn dashboard_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Dashboard not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("A dashboard with this name already exists".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+/// Insert `tiles` for the dashboard, in order.
+async fn insert_tiles(
+    tx: &mut Transaction<'_, Postgres>,
+    dashboard_id: Uuid,
+    tiles: &[DashboardTileRequest],
+) -> Result<(), DoubledeckerError> {
+    if tiles.is_empty() {
+        return Ok(());
+    }
+    let positions: Vec<i32> = (0..tiles.len() as i32).collect();
+    let titles: Vec<Option<&str>> = tiles.iter().map(|t| t.title.as_deref().map(str::trim)).collect();
+    let pipeline_ids: Vec<Uuid> = tiles.iter().map(|t| t.pipeline_id).collect();
+    let dataset_ids: Vec<Option<Uuid>> = tiles.iter().map(|t| t.dataset_id).collect();
+    let visualizations: Vec<&str> = tiles.iter().map(|t| t.visualization.as_str()).collect();
+    sqlx::query(
+        r#"
+        INSERT INTO dashboard_tiles (dashboard_id, position, title, pipeline_id, dataset_id, visualization)
+        SELECT $1, p, t, pl, d, v FROM UNNEST($2::int[], $3::text[], $4::uuid[], $5::uuid[], $6::text[]) AS x(p, t, pl, d, v)
+        "#,
+    )
+    .bind(dashboard_id)
+    .bind(&positions)
+    .bind(&titles)
+    .bind(&pipeline_ids)
+    .bind(&dataset_ids)
+    .bind(&visualizations)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+pub async fn create_dashboard(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    tiles: &[DashboardTileRequest],
+    created_by: Uuid,
+) -> Result<Dashboard, DoubledeckerError> {
+    let mut tx = pool.begin().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    let dashboard = sqlx::query_as::<_, Dashboard>(
+        r#"
+        INSERT INTO dashboards (workspace_id, name, description, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, workspace_id, name, description, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .bind(created_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(dashboard_error)?;
+    insert_tiles(&mut tx, dashboard.id, tiles).await?;
+    tx.commit().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(dashboard)
+}
+
+pub async fn list_dashboards(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<Dashboard>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, Dashboard>(
+        r#"
+        SELECT id, workspace_id, name, description, created_by, created_at, updated_at
+        FROM dashboards
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM dashboards WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_dashboard(pool: &PgPool, workspace_id: Uuid, dashboard_id: Uuid) -> Result<Dashboard, DoubledeckerError> {
+    sqlx::query_as::<_, Dashboard>(
+        r#"
+        SELECT id, workspace_id, name, description, created_by, created_at, updated_at
+        FROM dashboards
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(dashboard_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(dashboard_error)
+}
+
+/// Tiles of a dashboard in display order.
+pub async fn list_dashboard_tiles(pool: &PgPool, dashboard_id: Uuid) -> Result<Vec<DashboardTile>, DoubledeckerError> {
+    sqlx::query_as::<_, DashboardTile>(
+        r#"
+        SELECT id, dashboard_id, position, title, pipeline_id, dataset_id, visualization
+        FROM dashboard_tiles
+        WHERE dashboard_id = $1
+        ORDER BY position
+        "#,
+    )
+    .bind(dashboard_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Replace a dashboard's name, description and tiles.
+pub async fn update_dashboard(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dashboard_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    tiles: &[DashboardTileRequest],
+) -> Result<Dashboard, DoubledeckerError> {
+    let mut tx = pool.begin().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    let dashboard = sqlx::query_as::<_, Dashboard>(
+        r#"
+        UPDATE dashboards
+        SET name = $3, description = $4, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, name, description, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(dashboard_id)
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(dashboard_error)?;
+
+    sqlx::query("DELETE FROM dashboard_tiles WHERE dashboard_id = $1")
+        .bind(dashboard_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    insert_tiles(&mut tx, dashboard_id, tiles).await?;
+    tx.commit().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(dashboard)
+}
+
+pub async fn delete_dashboard(pool: &PgPool, workspace_id: Uuid, dashboard_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let result = sqlx::query("DELETE FROM dashboards WHERE id = $1 AND workspace_id = $2")
+        .bind(dashboard_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
@@ -1,65 +1,108 @@
-use crate::db::models::{Dataset, PaginatedResponse};
+use crate::db::models::{Dataset, DatasetListParams, DatasetStatus, PaginatedResponse, SortDirection};
 use crate::db::queries::common::paginate_rows;
+use crate::normalization::{InferredSchema, SourceColumn, SourceEncoding, ValidationReport};
 use crate::utils::error::DoubledeckerError;
 use chrono::Utc;
-use sqlx::PgPool;
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
 use std::str::FromStr;
 use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn create_dataset(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
+    id: Uuid,
     workspace_id: Uuid,
     distributor_source: String,
     filename: String,
     s3_parquet_key: String,
     file_size_bytes: i64,
-    status: String,
+    status: DatasetStatus,
+    encryption_key_id: Option<Uuid>,
 ) -> Result<Dataset, DoubledeckerError> {
     let dataset = sqlx::query_as::<_, Dataset>(
         r#"
-        INSERT INTO datasets (workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, status)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, created_at, updated_at
+        INSERT INTO datasets (id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, status, encryption_key_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
         "#,
     )
+    .bind(id)
     .bind(workspace_id)
     .bind(&distributor_source)
     .bind(&filename)
     .bind(&s3_parquet_key)
     .bind(file_size_bytes)
-    .bind(&status)
-    .fetch_one(pool)
+    .bind(status.as_str())
+    .bind(encryption_key_id)
+    .fetch_one(executor)
     .await
     .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
 
     Ok(dataset)
 }
 
+/// A page of the workspace's datasets, searched, filtered and ordered as `params` asks; `user_id`
+/// is whose favorites `favorites` keeps. Pagination is keyset on (sort column, id), so the cursor
+/// row anchors the next page.
 pub async fn get_datasets(
     pool: &PgPool,
     workspace_id: Uuid,
-    cursor: Option<String>,
-    limit: usize,
+    user_id: Uuid,
+    params: &DatasetListParams,
 ) -> Result<PaginatedResponse<Dataset>, DoubledeckerError> {
-    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let limit = params.pagination().effective_limit();
+    let cursor_uuid = params.cursor.as_deref().and_then(|c| Uuid::from_str(c).ok());
     let fetch_limit = (limit + 1) as i64;
 
-    let rows = sqlx::query_as::<_, Dataset>(
+    let column = params.sort_by.unwrap_or_default().column();
+    let (cmp, dir) = match params.sort_dir.unwrap_or_default() {
+        SortDirection::Asc => (">", "ASC"),
+        SortDirection::Desc => ("<", "DESC"),
+    };
+    let search = params.search.as_deref().map(str::trim).filter(|s| !s.is_empty()).map(|s| {
+        format!("%{}%", s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+    });
+
+    let sql = format!(
         r#"
-        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, created_at, updated_at
+        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
         FROM datasets
         WHERE workspace_id = $1
-          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM datasets WHERE id = $2))
-        ORDER BY created_at DESC, id DESC
+          AND ($2::uuid IS NULL OR ({column}, id) {cmp} (SELECT {column}, id FROM datasets WHERE id = $2))
+          AND ($4::text IS NULL OR filename ILIKE $4)
+          AND ($5::text IS NULL OR distributor_source = $5)
+          AND ($6::text IS NULL OR status = $6)
+          AND ($7::bigint IS NULL OR file_size_bytes >= $7)
+          AND ($8::bigint IS NULL OR file_size_bytes <= $8)
+          AND ($9::timestamptz IS NULL OR created_at >= $9)
+          AND ($10::timestamptz IS NULL OR created_at < $10)
+          AND ($11::uuid IS NULL OR folder_id = $11)
+          AND (NOT $12 OR folder_id IS NULL)
+          AND (NOT $13 OR id IN (SELECT dataset_id FROM dataset_favorites WHERE user_id = $14))
+        ORDER BY {column} {dir}, id {dir}
         LIMIT $3
-        "#,
-    )
-    .bind(workspace_id)
-    .bind(cursor_uuid)
-    .bind(fetch_limit)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+        "#
+    );
+
+    let rows = sqlx::query_as::<_, Dataset>(&sql)
+        .bind(workspace_id)
+        .bind(cursor_uuid)
+        .bind(fetch_limit)
+        .bind(search)
+        .bind(&params.distributor_source)
+        .bind(params.status.map(DatasetStatus::as_str))
+        .bind(params.min_size_bytes)
+        .bind(params.max_size_bytes)
+        .bind(params.created_after)
+        .bind(params.created_before)
+        .bind(params.folder_id)
+        .bind(params.unfiled.unwrap_or(false))
+        .bind(params.favorites.unwrap_or(false))
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
 
     Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
 }
@@ -71,7 +114,7 @@ pub async fn get_dataset_by_id(
 ) -> Result<Dataset, DoubledeckerError> {
     let dataset = sqlx::query_as::<_, Dataset>(
         r#"
-        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, created_at, updated_at
+        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
         FROM datasets
         WHERE id = $1 AND workspace_id = $2
         "#,
@@ -88,31 +131,288 @@ pub async fn get_dataset_by_id(
     Ok(dataset)
 }
 
-pub async fn update_dataset_status(
+/// Every dataset of the workspace, oldest first.
+pub async fn list_workspace_datasets(pool: &PgPool, workspace_id: Uuid) -> Result<Vec<Dataset>, DoubledeckerError> {
+    sqlx::query_as::<_, Dataset>(
+        r#"
+        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
+        FROM datasets
+        WHERE workspace_id = $1
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// The datasets among `dataset_ids` that belong to the workspace.
+pub async fn get_datasets_by_ids(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_ids: &[Uuid],
+) -> Result<Vec<Dataset>, DoubledeckerError> {
+    sqlx::query_as::<_, Dataset>(
+        r#"
+        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
+        FROM datasets
+        WHERE workspace_id = $1 AND id = ANY($2)
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(dataset_ids)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Delete datasets of the workspace, returning the ids that were removed.
+pub async fn delete_datasets(
+    executor: impl PgExecutor<'_>,
+    workspace_id: Uuid,
+    dataset_ids: &[Uuid],
+) -> Result<Vec<Uuid>, DoubledeckerError> {
+    sqlx::query_scalar::<_, Uuid>("DELETE FROM datasets WHERE workspace_id = $1 AND id = ANY($2) RETURNING id")
+        .bind(workspace_id)
+        .bind(dataset_ids)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Record the header mapping the dataset's file was converted with.
+pub async fn set_dataset_source_columns(
     pool: &PgPool,
     dataset_id: Uuid,
-    status: &str,
-    row_count: i64,
+    source_columns: &[SourceColumn],
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET source_columns = $2, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .bind(Json(source_columns))
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Record that the dataset's staging file was encrypted with its data key on the way in.
+pub async fn set_dataset_staging_encrypted(executor: impl PgExecutor<'_>, dataset_id: Uuid) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET staging_encrypted = TRUE, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .execute(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Record the encoding the dataset's uploaded file was detected in, e.g. `windows-1252`.
+pub async fn set_dataset_source_encoding(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    encoding: SourceEncoding,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET source_encoding = $2, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .bind(encoding.as_str())
+        .execute(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Record the column types inferred for the dataset's uploaded file.
+pub async fn set_dataset_inferred_schema(
+    pool: &PgPool,
+    dataset_id: Uuid,
+    schema: &InferredSchema,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET inferred_schema = $2, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .bind(Json(schema))
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Record what scanning the dataset's uploaded file found, including its row count, so the count is
+/// known from upload time rather than only once the dataset is READY.
+pub async fn set_dataset_validation_report(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    report: &ValidationReport,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET validation_report = $2, row_count = $3, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .bind(Json(report))
+        .bind(report.total_rows as i64)
+        .execute(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Count a query against the datasets it read: those in `dataset_ids`, or every READY dataset
+/// of the workspace when the query was not limited to specific datasets. Each gets a `QUERIED`
+/// entry in its activity feed.
+pub async fn record_dataset_usage(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_ids: Option<&[Uuid]>,
+    user_id: Option<Uuid>,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query(
+        r#"
+        WITH used AS (
+            UPDATE datasets
+            SET query_count = query_count + 1,
+                last_queried_at = NOW()
+            WHERE workspace_id = $1
+              AND status = 'READY'
+              AND ($2::uuid[] IS NULL OR id = ANY($2))
+            RETURNING id
+        )
+        INSERT INTO dataset_activity (dataset_id, user_id, action)
+        SELECT id, $3, 'QUERIED' FROM used
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(dataset_ids)
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Move a dataset to `status`. `row_count` is given once the Parquet file is written; otherwise the
+/// count stored when the upload was validated is kept.
+pub async fn update_dataset_status(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    status: DatasetStatus,
+    row_count: Option<i64>,
     error_message: Option<String>,
 ) -> Result<(), DoubledeckerError> {
     sqlx::query(
         r#"
         UPDATE datasets
         SET status = $2,
-            row_count = $3,
+            row_count = COALESCE($3, row_count),
             error_message = $4,
             updated_at = $5
         WHERE id = $1
         "#,
     )
     .bind(dataset_id)
-    .bind(status)
+    .bind(status.as_str())
     .bind(row_count)
     .bind(error_message)
     .bind(Utc::now())
-    .execute(pool)
+    .execute(executor)
     .await
     .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
 
     Ok(())
 }
+
+/// `update_dataset_status`, only while the dataset is in one of the `from` statuses. Returns
+/// whether it was.
+pub async fn transition_dataset_status(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    from: &[DatasetStatus],
+    to: DatasetStatus,
+    row_count: Option<i64>,
+    error_message: Option<String>,
+) -> Result<bool, DoubledeckerError> {
+    let from: Vec<&str> = from.iter().map(|status| status.as_str()).collect();
+    let res = sqlx::query(
+        r#"
+        UPDATE datasets
+        SET status = $2,
+            row_count = COALESCE($3, row_count),
+            error_message = $4,
+            updated_at = NOW()
+        WHERE id = $1 AND status = ANY($5)
+        "#,
+    )
+    .bind(dataset_id)
+    .bind(to.as_str())
+    .bind(row_count)
+    .bind(error_message)
+    .bind(&from)
+    .execute(executor)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected() > 0)
+}
+
+/// Record the size of the dataset's file, e.g. once a compaction has written it.
+pub async fn set_dataset_file_size(
+    executor: impl PgExecutor<'_>,
+    dataset_id: Uuid,
+    file_size_bytes: i64,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE datasets SET file_size_bytes = $2, updated_at = NOW() WHERE id = $1")
+        .bind(dataset_id)
+        .bind(file_size_bytes)
+        .execute(executor)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Set or clear (`None`) the public share token of a dataset.
+pub async fn set_dataset_public_token(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    public_token: Option<&str>,
+) -> Result<Dataset, DoubledeckerError> {
+    let dataset = sqlx::query_as::<_, Dataset>(
+        r#"
+        UPDATE datasets
+        SET public_token = $3,
+            updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
+        "#,
+    )
+    .bind(dataset_id)
+    .bind(workspace_id)
+    .bind(public_token)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    dataset.ok_or_else(|| DoubledeckerError::NotFound("Dataset not found".to_string()))
+}
+
+pub async fn get_dataset_by_public_token(
+    pool: &PgPool,
+    public_token: &str,
+) -> Result<Dataset, DoubledeckerError> {
+    let dataset = sqlx::query_as::<_, Dataset>(
+        r#"
+        SELECT id, workspace_id, distributor_source, filename, s3_parquet_key, file_size_bytes, row_count, status, error_message, public_token, encryption_key_id, staging_encrypted, folder_id, source_columns, source_encoding, inferred_schema, validation_report, last_queried_at, query_count, created_at, updated_at
+        FROM datasets
+        WHERE public_token = $1
+        "#,
+    )
+    .bind(public_token)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    dataset.ok_or_else(|| DoubledeckerError::NotFound("Public table not found".to_string()))
+}
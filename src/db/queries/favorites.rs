@@ -0,0 +1,45 @@
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Favorite or unfavorite a dataset for one user. Either is a no-op when already so.
+pub async fn set_dataset_favorite(
+    pool: &PgPool,
+    user_id: Uuid,
+    dataset_id: Uuid,
+    favorite: bool,
+) -> Result<(), DoubledeckerError> {
+    let sql = if favorite {
+        "INSERT INTO dataset_favorites (user_id, dataset_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM dataset_favorites WHERE user_id = $1 AND dataset_id = $2"
+    };
+    sqlx::query(sql)
+        .bind(user_id)
+        .bind(dataset_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+/// Favorite or unfavorite a pipeline for one user. Either is a no-op when already so.
+pub async fn set_pipeline_favorite(
+    pool: &PgPool,
+    user_id: Uuid,
+    pipeline_id: Uuid,
+    favorite: bool,
+) -> Result<(), DoubledeckerError> {
+    let sql = if favorite {
+        "INSERT INTO pipeline_favorites (user_id, pipeline_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM pipeline_favorites WHERE user_id = $1 AND pipeline_id = $2"
+    };
+    sqlx::query(sql)
+        .bind(user_id)
+        .bind(pipeline_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use crate::db::models::{Folder, PaginatedResponse};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn folder_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Folder not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("A folder with this name already exists here".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+pub async fn create_folder(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    parent_id: Option<Uuid>,
+    name: &str,
+) -> Result<Folder, DoubledeckerError> {
+    sqlx::query_as::<_, Folder>(
+        r#"
+        INSERT INTO folders (workspace_id, parent_id, name)
+        VALUES ($1, $2, $3)
+        RETURNING id, workspace_id, parent_id, name, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(parent_id)
+    .bind(name)
+    .fetch_one(pool)
+    .await
+    .map_err(folder_error)
+}
+
+/// Every folder of the workspace; clients assemble the tree from `parent_id`.
+pub async fn list_folders(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<Folder>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, Folder>(
+        r#"
+        SELECT id, workspace_id, parent_id, name, created_at, updated_at
+        FROM folders
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM folders WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_folder(pool: &PgPool, workspace_id: Uuid, folder_id: Uuid) -> Result<Folder, DoubledeckerError> {
+    sqlx::query_as::<_, Folder>(
+        r#"
+        SELECT id, workspace_id, parent_id, name, created_at, updated_at
+        FROM folders
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(folder_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(folder_error)
+}
+
+pub async fn rename_folder(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    folder_id: Uuid,
+    name: &str,
+) -> Result<Folder, DoubledeckerError> {
+    sqlx::query_as::<_, Folder>(
+        r#"
+        UPDATE folders
+        SET name = $3, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, parent_id, name, created_at, updated_at
+        "#,
+    )
+    .bind(folder_id)
+    .bind(workspace_id)
+    .bind(name)
+    .fetch_one(pool)
+    .await
+    .map_err(folder_error)
+}
+
+/// Re-parent a folder (`None` moves it to the top level). Moving a folder into itself or one of
+/// its descendants is rejected, since it would detach the subtree from the root.
+pub async fn move_folder(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    folder_id: Uuid,
+    parent_id: Option<Uuid>,
+) -> Result<Folder, DoubledeckerError> {
+    if let Some(parent_id) = parent_id {
+        let creates_cycle: bool = sqlx::query_scalar(
+            r#"
+            WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM folders WHERE id = $1 AND workspace_id = $3
+                UNION
+                SELECT f.id, f.parent_id FROM folders f JOIN ancestors a ON f.id = a.parent_id
+            )
+            SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $2)
+            "#,
+        )
+        .bind(parent_id)
+        .bind(folder_id)
+        .bind(workspace_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+        if creates_cycle {
+            return Err(DoubledeckerError::BadRequest(
+                "A folder cannot be moved into itself or one of its subfolders".to_string(),
+            ));
+        }
+    }
+
+    sqlx::query_as::<_, Folder>(
+        r#"
+        UPDATE folders
+        SET parent_id = $3, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, parent_id, name, created_at, updated_at
+        "#,
+    )
+    .bind(folder_id)
+    .bind(workspace_id)
+    .bind(parent_id)
+    .fetch_one(pool)
+    .await
+    .map_err(folder_error)
+}
+
+pub async fn delete_folder(pool: &PgPool, workspace_id: Uuid, folder_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM folders WHERE id = $1 AND workspace_id = $2")
+        .bind(folder_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// File datasets of the workspace under a folder (`None` leaves them unfiled). Returns the ids moved.
+pub async fn move_datasets_to_folder(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_ids: &[Uuid],
+    folder_id: Option<Uuid>,
+) -> Result<Vec<Uuid>, DoubledeckerError> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        UPDATE datasets
+        SET folder_id = $3, updated_at = NOW()
+        WHERE workspace_id = $1 AND id = ANY($2)
+        RETURNING id
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(dataset_ids)
+    .bind(folder_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
@@ -1,32 +1,41 @@
-use crate::db::models::{PaginatedResponse, QueryHistoryRecord};
+use crate::db::models::{
+    DailyQueryCount, PaginatedResponse, QueryHistoryRecord, SlowQuery, TableQueryCount, UserQueryTotals,
+};
 use crate::db::queries::common::paginate_rows;
+use crate::engine::QueryMetrics;
 use crate::utils::error::DoubledeckerError;
 use sqlx::PgPool;
 use std::str::FromStr;
 use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn record_query_history(
     pool: &PgPool,
     workspace_id: Uuid,
     user_id: Option<Uuid>,
     query_id: &str,
     sql_executed: &str,
-    row_count: i64,
-    execution_time_ms: i64,
+    bytes_processed: i64,
+    metrics: &QueryMetrics,
+    tables_referenced: &[String],
 ) -> Result<QueryHistoryRecord, DoubledeckerError> {
     let rec = sqlx::query_as::<_, QueryHistoryRecord>(
         r#"
-        INSERT INTO query_history (workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        RETURNING id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, created_at
+        INSERT INTO query_history (workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, bytes_processed, bytes_scanned, peak_memory_bytes, tables_referenced)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, bytes_processed, bytes_scanned, peak_memory_bytes, tables_referenced, created_at
         "#,
     )
     .bind(workspace_id)
     .bind(user_id)
     .bind(query_id)
     .bind(sql_executed)
-    .bind(row_count)
-    .bind(execution_time_ms)
+    .bind(metrics.output_rows)
+    .bind(metrics.elapsed_ms)
+    .bind(bytes_processed)
+    .bind(metrics.bytes_scanned)
+    .bind(metrics.peak_memory_bytes)
+    .bind(tables_referenced)
     .fetch_one(pool)
     .await
     .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
@@ -45,7 +54,7 @@ pub async fn list_query_history(
 
     let rows = sqlx::query_as::<_, QueryHistoryRecord>(
         r#"
-        SELECT id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, created_at
+        SELECT id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, bytes_processed, bytes_scanned, peak_memory_bytes, tables_referenced, created_at
         FROM query_history
         WHERE workspace_id = $1
           AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM query_history WHERE id = $2))
@@ -70,7 +79,7 @@ pub async fn get_query_history_by_id(
 ) -> Result<QueryHistoryRecord, DoubledeckerError> {
     let rec = sqlx::query_as::<_, QueryHistoryRecord>(
         r#"
-        SELECT id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, created_at
+        SELECT id, workspace_id, user_id, query_id, sql_executed, row_count, execution_time_ms, bytes_processed, bytes_scanned, peak_memory_bytes, tables_referenced, created_at
         FROM query_history
         WHERE workspace_id = $1 AND query_id = $2
         ORDER BY created_at DESC
@@ -85,3 +94,140 @@ pub async fn get_query_history_by_id(
 
     rec.ok_or_else(|| DoubledeckerError::NotFound("Query history record not found".to_string()))
 }
+
+/// Lifetime totals of a user's recorded queries.
+pub async fn get_user_query_totals(pool: &PgPool, user_id: Uuid) -> Result<UserQueryTotals, DoubledeckerError> {
+    sqlx::query_as::<_, UserQueryTotals>(
+        r#"
+        SELECT COUNT(*) AS total_queries,
+               COALESCE(SUM(bytes_scanned), 0)::BIGINT AS total_bytes_scanned,
+               COALESCE(AVG(execution_time_ms), 0)::DOUBLE PRECISION AS avg_execution_time_ms
+        FROM query_history
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Queries per UTC day over the last `days` days, oldest first, including days without queries;
+/// those of one user, or of every user when `user_id` is `None`.
+pub async fn count_queries_per_day(
+    pool: &PgPool,
+    user_id: Option<Uuid>,
+    days: i32,
+) -> Result<Vec<DailyQueryCount>, DoubledeckerError> {
+    sqlx::query_as::<_, DailyQueryCount>(
+        r#"
+        SELECT d.day::DATE AS day, COUNT(q.id) AS query_count
+        FROM generate_series(
+            (NOW() AT TIME ZONE 'UTC')::DATE - ($2 - 1),
+            (NOW() AT TIME ZONE 'UTC')::DATE,
+            INTERVAL '1 day'
+        ) AS d(day)
+        LEFT JOIN query_history q
+          ON ($1::UUID IS NULL OR q.user_id = $1) AND (q.created_at AT TIME ZONE 'UTC')::DATE = d.day::DATE
+        GROUP BY d.day
+        ORDER BY d.day
+        "#,
+    )
+    .bind(user_id)
+    .bind(days)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// The tables a user queries most, by number of queries referencing them.
+pub async fn most_queried_tables(
+    pool: &PgPool,
+    user_id: Uuid,
+    limit: i64,
+) -> Result<Vec<TableQueryCount>, DoubledeckerError> {
+    sqlx::query_as::<_, TableQueryCount>(
+        r#"
+        SELECT t.table_name, COUNT(*) AS query_count
+        FROM query_history q, UNNEST(q.tables_referenced) AS t(table_name)
+        WHERE q.user_id = $1
+        GROUP BY t.table_name
+        ORDER BY query_count DESC, t.table_name
+        LIMIT $2
+        "#,
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Log a query that ran at or over the slow-query threshold.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_slow_query(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    user_id: Option<Uuid>,
+    pipeline_id: Option<Uuid>,
+    sql_executed: &str,
+    operations: serde_json::Value,
+    plan: &str,
+    metrics: &QueryMetrics,
+    threshold_ms: i64,
+) -> Result<SlowQuery, DoubledeckerError> {
+    sqlx::query_as::<_, SlowQuery>(
+        r#"
+        INSERT INTO slow_queries (workspace_id, user_id, pipeline_id, sql_executed, operations, plan, execution_time_ms,
+                                  row_count, bytes_scanned, peak_memory_bytes, threshold_ms)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING id, workspace_id, user_id, pipeline_id, sql_executed, operations, plan, execution_time_ms, row_count,
+                  bytes_scanned, peak_memory_bytes, threshold_ms, created_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(user_id)
+    .bind(pipeline_id)
+    .bind(sql_executed)
+    .bind(sqlx::types::Json(operations))
+    .bind(plan)
+    .bind(metrics.elapsed_ms)
+    .bind(metrics.output_rows)
+    .bind(metrics.bytes_scanned)
+    .bind(metrics.peak_memory_bytes)
+    .bind(threshold_ms)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Slow queries across the platform, or of one workspace, newest first.
+pub async fn list_slow_queries(
+    pool: &PgPool,
+    workspace_id: Option<Uuid>,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<SlowQuery>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, SlowQuery>(
+        r#"
+        SELECT id, workspace_id, user_id, pipeline_id, sql_executed, operations, plan, execution_time_ms, row_count,
+               bytes_scanned, peak_memory_bytes, threshold_ms, created_at
+        FROM slow_queries
+        WHERE ($1::uuid IS NULL OR workspace_id = $1)
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM slow_queries WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
@@ -0,0 +1,116 @@
+use crate::db::models::IdempotencyRecord;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Claim `key` for a new request. Returns `None` when the caller now owns it, or the existing
+/// record when the key was used before. Expired keys are purged first, and a key whose request never
+/// finished (the server went down mid-request) is given up after ten minutes.
+pub async fn claim_idempotency_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    endpoint: &str,
+    key: &str,
+    request_hash: &str,
+) -> Result<Option<IdempotencyRecord>, DoubledeckerError> {
+    sqlx::query(
+        r#"
+        DELETE FROM idempotency_keys
+        WHERE created_at < NOW() - INTERVAL '24 hours'
+           OR (user_id = $1 AND endpoint = $2 AND key = $3
+               AND status_code IS NULL AND created_at < NOW() - INTERVAL '10 minutes')
+        "#,
+    )
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(key)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    let claimed = sqlx::query(
+        r#"
+        INSERT INTO idempotency_keys (user_id, endpoint, key, request_hash)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(key)
+    .bind(request_hash)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    .rows_affected()
+        == 1;
+    if claimed {
+        return Ok(None);
+    }
+
+    sqlx::query_as::<_, IdempotencyRecord>(
+        r#"
+        SELECT request_hash, status_code, content_type, content_disposition, body
+        FROM idempotency_keys
+        WHERE user_id = $1 AND endpoint = $2 AND key = $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(key)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    // Purged between the insert and the read; the retry can claim it
+    .ok_or_else(|| DoubledeckerError::Conflict("Idempotency-Key expired while in use; retry the request".to_string()))
+    .map(Some)
+}
+
+/// Store the response of a claimed key.
+#[allow(clippy::too_many_arguments)]
+pub async fn complete_idempotency_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    endpoint: &str,
+    key: &str,
+    status_code: i16,
+    content_type: Option<&str>,
+    content_disposition: Option<&str>,
+    body: &[u8],
+) -> Result<(), DoubledeckerError> {
+    sqlx::query(
+        r#"
+        UPDATE idempotency_keys
+        SET status_code = $4, content_type = $5, content_disposition = $6, body = $7
+        WHERE user_id = $1 AND endpoint = $2 AND key = $3
+        "#,
+    )
+    .bind(user_id)
+    .bind(endpoint)
+    .bind(key)
+    .bind(status_code)
+    .bind(content_type)
+    .bind(content_disposition)
+    .bind(body)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+/// Give up a claimed key, so a retry runs the request again.
+pub async fn release_idempotency_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    endpoint: &str,
+    key: &str,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND endpoint = $2 AND key = $3")
+        .bind(user_id)
+        .bind(endpoint)
+        .bind(key)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
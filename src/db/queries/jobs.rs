@@ -0,0 +1,230 @@
+use crate::db::models::{BackgroundJob, JobStatus, PaginatedResponse};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+const JOB_COLUMNS: &str = "id, workspace_id, kind, payload, status, attempts, max_attempts, run_at, last_error, \
+                           result, created_by, created_at, updated_at, finished_at";
+
+/// Queue a job to run as soon as a worker is free.
+pub async fn enqueue_job(
+    pool: &PgPool,
+    workspace_id: Option<Uuid>,
+    kind: &str,
+    payload: serde_json::Value,
+    max_attempts: i32,
+    created_by: Option<Uuid>,
+) -> Result<BackgroundJob, DoubledeckerError> {
+    sqlx::query_as::<_, BackgroundJob>(&format!(
+        r#"
+        INSERT INTO background_jobs (workspace_id, kind, payload, max_attempts, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING {JOB_COLUMNS}
+        "#
+    ))
+    .bind(workspace_id)
+    .bind(kind)
+    .bind(Json(payload))
+    .bind(max_attempts)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Queue a workspace-less job of `kind` unless one is already queued or running. Returns whether it
+/// was queued.
+pub async fn enqueue_job_unless_pending(
+    pool: &PgPool,
+    kind: &str,
+    max_attempts: i32,
+) -> Result<bool, DoubledeckerError> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO background_jobs (kind, payload, max_attempts)
+        SELECT $1, '{}'::jsonb, $2
+        WHERE NOT EXISTS (SELECT 1 FROM background_jobs WHERE kind = $1 AND status IN ('QUEUED', 'RUNNING'))
+        "#,
+    )
+    .bind(kind)
+    .bind(max_attempts)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Claim the next due job and mark it RUNNING, counting the attempt. Rows locked by another worker
+/// are skipped, so concurrent workers never claim the same job. A RUNNING job whose lease has
+/// expired (its worker died) is claimed again while it has attempts left; see `fail_expired_jobs`
+/// for the rest.
+pub async fn claim_next_job(pool: &PgPool, lease_secs: i64) -> Result<Option<BackgroundJob>, DoubledeckerError> {
+    sqlx::query_as::<_, BackgroundJob>(&format!(
+        r#"
+        UPDATE background_jobs
+        SET status = 'RUNNING', attempts = attempts + 1, locked_at = NOW(), updated_at = NOW()
+        WHERE id = (
+            SELECT id FROM background_jobs
+            WHERE (status = 'QUEUED' AND run_at <= NOW())
+               OR (status = 'RUNNING' AND locked_at < NOW() - make_interval(secs => $1) AND attempts < max_attempts)
+            ORDER BY run_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {JOB_COLUMNS}
+        "#
+    ))
+    .bind(lease_secs as f64)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Fail the RUNNING jobs whose lease expired on their last attempt, instead of running them once
+/// more than allowed. Returns the failed jobs.
+pub async fn fail_expired_jobs(pool: &PgPool, lease_secs: i64) -> Result<Vec<BackgroundJob>, DoubledeckerError> {
+    sqlx::query_as::<_, BackgroundJob>(&format!(
+        r#"
+        UPDATE background_jobs
+        SET status = 'FAILED', last_error = 'The worker running the job stopped responding',
+            locked_at = NULL, finished_at = NOW(), updated_at = NOW()
+        WHERE status = 'RUNNING' AND locked_at < NOW() - make_interval(secs => $1) AND attempts >= max_attempts
+        RETURNING {JOB_COLUMNS}
+        "#
+    ))
+    .bind(lease_secs as f64)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Extend a RUNNING job's lease, so no other worker claims it while it runs. Returns false once
+/// the job is no longer running, e.g. it was cancelled.
+pub async fn renew_job_lease(pool: &PgPool, job_id: Uuid) -> Result<bool, DoubledeckerError> {
+    let res = sqlx::query("UPDATE background_jobs SET locked_at = NOW() WHERE id = $1 AND status = 'RUNNING'")
+        .bind(job_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Record a successful run. Returns false if the job was no longer running, e.g. cancelled meanwhile.
+pub async fn complete_job(pool: &PgPool, id: Uuid, result: serde_json::Value) -> Result<bool, DoubledeckerError> {
+    let res = sqlx::query(
+        r#"
+        UPDATE background_jobs
+        SET status = 'SUCCEEDED', result = $2, last_error = NULL, locked_at = NULL,
+            finished_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND status = 'RUNNING'
+        "#,
+    )
+    .bind(id)
+    .bind(Json(result))
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Record a failed attempt: the job is queued again after `retry_in_secs`, or fails for good once
+/// it has used all its attempts (or `retry_in_secs` is `None`). Returns whether it failed for good.
+pub async fn fail_job(
+    pool: &PgPool,
+    id: Uuid,
+    error: &str,
+    retry_in_secs: Option<i64>,
+) -> Result<bool, DoubledeckerError> {
+    let status: Option<String> = sqlx::query_scalar(
+        r#"
+        UPDATE background_jobs
+        SET status = CASE WHEN $3::float8 IS NOT NULL AND attempts < max_attempts THEN 'QUEUED' ELSE 'FAILED' END,
+            run_at = CASE WHEN $3::float8 IS NOT NULL THEN NOW() + make_interval(secs => $3) ELSE run_at END,
+            finished_at = CASE WHEN $3::float8 IS NOT NULL AND attempts < max_attempts THEN NULL ELSE NOW() END,
+            last_error = $2, locked_at = NULL, updated_at = NOW()
+        WHERE id = $1 AND status = 'RUNNING'
+        RETURNING status
+        "#,
+    )
+    .bind(id)
+    .bind(error)
+    .bind(retry_in_secs.map(|s| s as f64))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(status.as_deref() == Some("FAILED"))
+}
+
+/// Cancel a job that has not finished. A RUNNING job is stopped by its worker, which checks for
+/// cancellation while the job runs; finished jobs are left as they are.
+pub async fn cancel_job(pool: &PgPool, workspace_id: Uuid, job_id: Uuid) -> Result<BackgroundJob, DoubledeckerError> {
+    let cancelled = sqlx::query_as::<_, BackgroundJob>(&format!(
+        r#"
+        UPDATE background_jobs
+        SET status = 'CANCELLED', locked_at = NULL, finished_at = NOW(), updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2 AND status IN ('QUEUED', 'RUNNING')
+        RETURNING {JOB_COLUMNS}
+        "#
+    ))
+    .bind(job_id)
+    .bind(workspace_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    match cancelled {
+        Some(job) => Ok(job),
+        None => {
+            let job = get_job(pool, workspace_id, job_id).await?;
+            Err(DoubledeckerError::Conflict(format!("Job has already finished ({})", job.status)))
+        }
+    }
+}
+
+pub async fn get_job(pool: &PgPool, workspace_id: Uuid, job_id: Uuid) -> Result<BackgroundJob, DoubledeckerError> {
+    sqlx::query_as::<_, BackgroundJob>(&format!(
+        "SELECT {JOB_COLUMNS} FROM background_jobs WHERE id = $1 AND workspace_id = $2"
+    ))
+    .bind(job_id)
+    .bind(workspace_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| DoubledeckerError::NotFound("Job not found".to_string()))
+}
+
+/// Jobs of a workspace, newest first, optionally only those in one status.
+pub async fn list_jobs(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    status: Option<JobStatus>,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<BackgroundJob>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, BackgroundJob>(&format!(
+        r#"
+        SELECT {JOB_COLUMNS}
+        FROM background_jobs
+        WHERE workspace_id = $1
+          AND ($2::text IS NULL OR status = $2)
+          AND ($3::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM background_jobs WHERE id = $3))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $4
+        "#
+    ))
+    .bind(workspace_id)
+    .bind(status.map(JobStatus::as_str))
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
@@ -0,0 +1,83 @@
+use crate::db::models::UserDataKey;
+use crate::utils::crypto::unwrap_data_key;
+use crate::utils::error::DoubledeckerError;
+use aes_gcm::{Aes256Gcm, Key};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn get_user_data_key(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Option<UserDataKey>, DoubledeckerError> {
+    sqlx::query_as::<_, UserDataKey>(
+        "SELECT id, user_id, wrapped_key, created_at FROM user_data_keys WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_data_key_by_id(pool: &PgPool, key_id: Uuid) -> Result<UserDataKey, DoubledeckerError> {
+    sqlx::query_as::<_, UserDataKey>(
+        "SELECT id, user_id, wrapped_key, created_at FROM user_data_keys WHERE id = $1",
+    )
+    .bind(key_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| DoubledeckerError::NotFound(format!("Data key {} not found", key_id)))
+}
+
+/// Store a user's wrapped data key. If a concurrent request already created one, that key is returned instead.
+pub async fn create_user_data_key(
+    pool: &PgPool,
+    user_id: Uuid,
+    wrapped_key: &str,
+) -> Result<UserDataKey, DoubledeckerError> {
+    sqlx::query_as::<_, UserDataKey>(
+        r#"
+        INSERT INTO user_data_keys (user_id, wrapped_key)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET user_id = EXCLUDED.user_id
+        RETURNING id, user_id, wrapped_key, created_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(wrapped_key)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Parquet key and wrapped data key of each of the workspace's datasets stored encrypted.
+pub async fn list_workspace_sealed_files(
+    pool: &PgPool,
+    workspace_id: Uuid,
+) -> Result<Vec<(String, String)>, DoubledeckerError> {
+    sqlx::query_as::<_, (String, String)>(
+        r#"
+        SELECT d.s3_parquet_key, k.wrapped_key
+        FROM datasets d
+        JOIN user_data_keys k ON k.id = d.encryption_key_id
+        WHERE d.workspace_id = $1
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// The data key stored files are encrypted with under `encryption_key_id`, unwrapped; `None` for
+/// files stored unencrypted.
+pub async fn dataset_data_key(
+    pool: &PgPool,
+    encryption_key_id: Option<Uuid>,
+) -> Result<Option<Key<Aes256Gcm>>, DoubledeckerError> {
+    let Some(key_id) = encryption_key_id else {
+        return Ok(None);
+    };
+    let data_key = get_data_key_by_id(pool, key_id).await?;
+    unwrap_data_key(&data_key.wrapped_key).map(Some)
+}
@@ -1,18 +1,60 @@
+pub mod activity;
+pub mod admin;
+pub mod alerts;
 pub mod catalog;
+pub mod comments;
 pub mod common;
+pub mod connections;
+pub mod dashboards;
+pub mod favorites;
 pub mod datasets;
+pub mod folders;
 pub mod history;
+pub mod idempotency;
+pub mod jobs;
+pub mod keys;
+pub mod outbox;
 pub mod payees;
+pub mod pipelines;
+pub mod quality;
 pub mod rbac;
+pub mod restrictions;
+pub mod schedules;
+pub mod search;
+pub mod segments;
+pub mod sessions;
 pub mod splits;
+pub mod suspensions;
+pub mod upload_sessions;
 pub mod users;
 pub mod workspaces;
 
+pub use activity::*;
+pub use admin::*;
+pub use alerts::*;
 pub use catalog::*;
+pub use comments::*;
+pub use connections::*;
+pub use dashboards::*;
+pub use favorites::*;
 pub use datasets::*;
+pub use folders::*;
 pub use history::*;
+pub use idempotency::*;
+pub use jobs::*;
+pub use keys::*;
+pub use outbox::*;
 pub use payees::*;
+pub use pipelines::*;
+pub use quality::*;
 pub use rbac::*;
+pub use restrictions::*;
+pub use schedules::*;
+pub use search::*;
+pub use segments::*;
+pub use sessions::*;
 pub use splits::*;
+pub use suspensions::*;
+pub use upload_sessions::*;
 pub use users::*;
 pub use workspaces::*;
@@ -0,0 +1,66 @@
+use crate::db::models::OutboxEvent;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgConnection;
+
+/// Record an event to publish once the surrounding transaction commits.
+pub async fn enqueue_outbox_event(
+    conn: &mut PgConnection,
+    event_name: &str,
+    payload: serde_json::Value,
+) -> Result<i64, DoubledeckerError> {
+    let (id,): (i64,) = sqlx::query_as(
+        "INSERT INTO event_outbox (event_name, payload) VALUES ($1, $2) RETURNING id",
+    )
+    .bind(event_name)
+    .bind(payload)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(id)
+}
+
+/// Lock up to `limit` undispatched events, oldest first. Rows locked by another dispatcher are skipped,
+/// so concurrent dispatchers never send the same batch.
+pub async fn claim_pending_outbox_events(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<OutboxEvent>, DoubledeckerError> {
+    sqlx::query_as::<_, OutboxEvent>(
+        r#"
+        SELECT id, event_name, payload, attempts, created_at
+        FROM event_outbox
+        WHERE dispatched_at IS NULL
+        ORDER BY id
+        LIMIT $1
+        FOR UPDATE SKIP LOCKED
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(conn)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn mark_outbox_event_dispatched(conn: &mut PgConnection, id: i64) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE event_outbox SET dispatched_at = NOW(), attempts = attempts + 1, last_error = NULL WHERE id = $1")
+        .bind(id)
+        .execute(conn)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+pub async fn record_outbox_event_failure(
+    conn: &mut PgConnection,
+    id: i64,
+    error: &str,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query("UPDATE event_outbox SET attempts = attempts + 1, last_error = $2 WHERE id = $1")
+        .bind(id)
+        .bind(error)
+        .execute(conn)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
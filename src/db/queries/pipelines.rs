@@ -0,0 +1,270 @@
+use crate::db::models::{PaginatedResponse, Pipeline, PipelineRun, PipelineTrigger};
+use crate::db::queries::common::paginate_rows;
+use crate::engine::QueryMetrics;
+use crate::server::dtos::analytics::StructuredAnalyticsQuery;
+use crate::utils::error::DoubledeckerError;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn pipeline_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Pipeline not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("A pipeline with this name already exists".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+pub async fn create_pipeline(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    dataset_pattern: Option<&str>,
+    query: &StructuredAnalyticsQuery,
+    created_by: Uuid,
+) -> Result<Pipeline, DoubledeckerError> {
+    sqlx::query_as::<_, Pipeline>(
+        r#"
+        INSERT INTO pipelines (workspace_id, name, description, dataset_pattern, query, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .bind(dataset_pattern)
+    .bind(Json(query))
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(pipeline_error)
+}
+
+/// A page of the workspace's pipelines, newest first; with `favorites_only`, just those `user_id`
+/// has favorited.
+pub async fn list_pipelines(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    favorites_only: bool,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<Pipeline>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, Pipeline>(
+        r#"
+        SELECT id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        FROM pipelines
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM pipelines WHERE id = $2))
+          AND (NOT $4 OR id IN (SELECT pipeline_id FROM pipeline_favorites WHERE user_id = $5))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .bind(favorites_only)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+/// Every pipeline of the workspace, oldest first.
+pub async fn list_workspace_pipelines(pool: &PgPool, workspace_id: Uuid) -> Result<Vec<Pipeline>, DoubledeckerError> {
+    sqlx::query_as::<_, Pipeline>(
+        r#"
+        SELECT id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        FROM pipelines
+        WHERE workspace_id = $1
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Pipelines of the workspace that apply automatically, i.e. have a dataset pattern.
+pub async fn list_patterned_pipelines(pool: &PgPool, workspace_id: Uuid) -> Result<Vec<Pipeline>, DoubledeckerError> {
+    sqlx::query_as::<_, Pipeline>(
+        r#"
+        SELECT id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        FROM pipelines
+        WHERE workspace_id = $1 AND dataset_pattern IS NOT NULL
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_pipeline(pool: &PgPool, workspace_id: Uuid, pipeline_id: Uuid) -> Result<Pipeline, DoubledeckerError> {
+    sqlx::query_as::<_, Pipeline>(
+        r#"
+        SELECT id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        FROM pipelines
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(pipeline_error)
+}
+
+pub async fn update_pipeline(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    pipeline_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    dataset_pattern: Option<&str>,
+    query: &StructuredAnalyticsQuery,
+) -> Result<Pipeline, DoubledeckerError> {
+    sqlx::query_as::<_, Pipeline>(
+        r#"
+        UPDATE pipelines
+        SET name = $3, description = $4, dataset_pattern = $5, query = $6, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, name, description, dataset_pattern, query, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .bind(dataset_pattern)
+    .bind(Json(query))
+    .fetch_one(pool)
+    .await
+    .map_err(pipeline_error)
+}
+
+pub async fn delete_pipeline(pool: &PgPool, workspace_id: Uuid, pipeline_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM pipelines WHERE id = $1 AND workspace_id = $2")
+        .bind(pipeline_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// Record a run; a run either has a `result` or an `error_message`.
+pub async fn record_pipeline_run(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    dataset_id: Uuid,
+    trigger: PipelineTrigger,
+    outcome: Result<(serde_json::Value, QueryMetrics), String>,
+    lineage: Option<serde_json::Value>,
+) -> Result<PipelineRun, DoubledeckerError> {
+    let (status, result, metrics, error_message) = match outcome {
+        Ok((result, metrics)) => ("SUCCEEDED", Some(Json(result)), metrics, None),
+        Err(message) => ("FAILED", None, QueryMetrics::default(), Some(message)),
+    };
+    sqlx::query_as::<_, PipelineRun>(
+        r#"
+        INSERT INTO pipeline_runs (pipeline_id, dataset_id, trigger, status, row_count, result, error_message, lineage,
+                                   execution_time_ms, bytes_scanned, peak_memory_bytes)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING id, pipeline_id, dataset_id, trigger, status, row_count, result, error_message, execution_time_ms,
+                  bytes_scanned, peak_memory_bytes, created_at
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(dataset_id)
+    .bind(trigger.as_str())
+    .bind(status)
+    .bind(metrics.output_rows)
+    .bind(result)
+    .bind(error_message)
+    .bind(lineage.map(Json))
+    .bind(metrics.elapsed_ms)
+    .bind(metrics.bytes_scanned)
+    .bind(metrics.peak_memory_bytes)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Lineage recorded with a run of the pipeline; `None` for runs that have none.
+pub async fn get_pipeline_run_lineage(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    run_id: Uuid,
+) -> Result<Option<serde_json::Value>, DoubledeckerError> {
+    let lineage: Option<Json<serde_json::Value>> =
+        sqlx::query_scalar("SELECT lineage FROM pipeline_runs WHERE id = $1 AND pipeline_id = $2")
+            .bind(run_id)
+            .bind(pipeline_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Pipeline run not found".to_string()),
+                _ => DoubledeckerError::DatabaseError(e.to_string()),
+            })?;
+    Ok(lineage.map(|l| l.0))
+}
+
+/// Runs of a pipeline, newest first.
+pub async fn list_pipeline_runs(
+    pool: &PgPool,
+    pipeline_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<PipelineRun>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, PipelineRun>(
+        r#"
+        SELECT id, pipeline_id, dataset_id, trigger, status, row_count, result, error_message, execution_time_ms, bytes_scanned,
+               peak_memory_bytes, created_at
+        FROM pipeline_runs
+        WHERE pipeline_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM pipeline_runs WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(pipeline_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+/// Ready datasets of the workspace as `(id, filename)`, for matching against pipeline patterns.
+pub async fn list_ready_dataset_filenames(
+    pool: &PgPool,
+    workspace_id: Uuid,
+) -> Result<Vec<(Uuid, String)>, DoubledeckerError> {
+    sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT id, filename FROM datasets WHERE workspace_id = $1 AND status = 'READY' ORDER BY created_at, id",
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
@@ -0,0 +1,229 @@
+use crate::db::models::{PaginatedResponse, PipelineTrigger, QualityRule, QualityRun};
+use crate::db::queries::common::paginate_rows;
+use crate::server::dtos::quality::{Expectation, RuleOutcome};
+use crate::utils::error::DoubledeckerError;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn quality_rule_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Quality rule not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("A quality rule with this name already exists".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+pub async fn create_quality_rule(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    column_name: &str,
+    expectation: &Expectation,
+    run_on_upload: bool,
+    created_by: Uuid,
+) -> Result<QualityRule, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRule>(
+        r#"
+        INSERT INTO quality_rules (workspace_id, name, column_name, expectation, run_on_upload, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, workspace_id, name, column_name, expectation, run_on_upload, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(column_name)
+    .bind(Json(expectation))
+    .bind(run_on_upload)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(quality_rule_error)
+}
+
+pub async fn list_quality_rules(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<QualityRule>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, QualityRule>(
+        r#"
+        SELECT id, workspace_id, name, column_name, expectation, run_on_upload, created_by, created_at, updated_at
+        FROM quality_rules
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM quality_rules WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+/// Every rule of the workspace, oldest first, or only those that run on upload.
+pub async fn list_workspace_quality_rules(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    on_upload_only: bool,
+) -> Result<Vec<QualityRule>, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRule>(
+        r#"
+        SELECT id, workspace_id, name, column_name, expectation, run_on_upload, created_by, created_at, updated_at
+        FROM quality_rules
+        WHERE workspace_id = $1 AND (run_on_upload OR NOT $2)
+        ORDER BY created_at, id
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(on_upload_only)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn get_quality_rule(pool: &PgPool, workspace_id: Uuid, rule_id: Uuid) -> Result<QualityRule, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRule>(
+        r#"
+        SELECT id, workspace_id, name, column_name, expectation, run_on_upload, created_by, created_at, updated_at
+        FROM quality_rules
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(rule_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(quality_rule_error)
+}
+
+pub async fn update_quality_rule(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    rule_id: Uuid,
+    name: &str,
+    column_name: &str,
+    expectation: &Expectation,
+    run_on_upload: bool,
+) -> Result<QualityRule, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRule>(
+        r#"
+        UPDATE quality_rules
+        SET name = $3, column_name = $4, expectation = $5, run_on_upload = $6, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, name, column_name, expectation, run_on_upload, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(rule_id)
+    .bind(workspace_id)
+    .bind(name)
+    .bind(column_name)
+    .bind(Json(expectation))
+    .bind(run_on_upload)
+    .fetch_one(pool)
+    .await
+    .map_err(quality_rule_error)
+}
+
+pub async fn delete_quality_rule(pool: &PgPool, workspace_id: Uuid, rule_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM quality_rules WHERE id = $1 AND workspace_id = $2")
+        .bind(rule_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
+
+/// Record a run; it passed when every rule did.
+pub async fn record_quality_run(
+    pool: &PgPool,
+    dataset_id: Uuid,
+    trigger: PipelineTrigger,
+    results: &[RuleOutcome],
+    created_by: Option<Uuid>,
+) -> Result<QualityRun, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRun>(
+        r#"
+        INSERT INTO quality_runs (dataset_id, trigger, passed, results, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, dataset_id, trigger, passed, results, created_by, created_at
+        "#,
+    )
+    .bind(dataset_id)
+    .bind(trigger.as_str())
+    .bind(results.iter().all(|r| r.passed))
+    .bind(Json(results))
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn list_quality_runs(
+    pool: &PgPool,
+    dataset_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<QualityRun>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, QualityRun>(
+        r#"
+        SELECT id, dataset_id, trigger, passed, results, created_by, created_at
+        FROM quality_runs
+        WHERE dataset_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM quality_runs WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(dataset_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+/// A run of a dataset of the workspace.
+pub async fn get_quality_run(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    run_id: Uuid,
+) -> Result<QualityRun, DoubledeckerError> {
+    sqlx::query_as::<_, QualityRun>(
+        r#"
+        SELECT r.id, r.dataset_id, r.trigger, r.passed, r.results, r.created_by, r.created_at
+        FROM quality_runs r
+        JOIN datasets d ON d.id = r.dataset_id
+        WHERE r.id = $1 AND r.dataset_id = $2 AND d.workspace_id = $3
+        "#,
+    )
+    .bind(run_id)
+    .bind(dataset_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Quality run not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    })
+}
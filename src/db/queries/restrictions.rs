@@ -0,0 +1,80 @@
+use crate::db::models::ColumnRestriction;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn list_dataset_column_restrictions(
+    pool: &PgPool,
+    dataset_id: Uuid,
+) -> Result<Vec<ColumnRestriction>, DoubledeckerError> {
+    sqlx::query_as::<_, ColumnRestriction>(
+        "SELECT column_name, mode FROM dataset_column_restrictions WHERE dataset_id = $1 ORDER BY column_name",
+    )
+    .bind(dataset_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Restrictions over every dataset in a workspace. Workspace-wide queries read all datasets
+/// through one table, so a column governed anywhere is governed everywhere.
+pub async fn list_workspace_column_restrictions(
+    pool: &PgPool,
+    workspace_id: Uuid,
+) -> Result<Vec<ColumnRestriction>, DoubledeckerError> {
+    sqlx::query_as::<_, ColumnRestriction>(
+        r#"
+        SELECT DISTINCT r.column_name, r.mode
+        FROM dataset_column_restrictions r
+        JOIN datasets d ON d.id = r.dataset_id
+        WHERE d.workspace_id = $1
+        ORDER BY r.column_name
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Replace the full set of column restrictions for a dataset.
+pub async fn replace_dataset_column_restrictions(
+    pool: &PgPool,
+    dataset_id: Uuid,
+    restrictions: &[ColumnRestriction],
+) -> Result<(), DoubledeckerError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    sqlx::query("DELETE FROM dataset_column_restrictions WHERE dataset_id = $1")
+        .bind(dataset_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    if !restrictions.is_empty() {
+        let columns: Vec<&str> = restrictions.iter().map(|r| r.column_name.as_str()).collect();
+        let modes: Vec<&str> = restrictions.iter().map(|r| r.mode.as_str()).collect();
+        sqlx::query(
+            r#"
+            INSERT INTO dataset_column_restrictions (dataset_id, column_name, mode)
+            SELECT $1, c, m FROM UNNEST($2::text[], $3::text[]) AS t(c, m)
+            ON CONFLICT (dataset_id, column_name) DO UPDATE SET mode = EXCLUDED.mode
+            "#,
+        )
+        .bind(dataset_id)
+        .bind(&columns)
+        .bind(&modes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use crate::db::models::{PaginatedResponse, PipelineSchedule};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+const SCHEDULE_COLUMNS: &str = "id, workspace_id, pipeline_id, interval_minutes, enabled, next_run_at, channel, \
+                                encrypted_webhook_url, notify_when, threshold_column, threshold_op, threshold_value, \
+                                top_rows, last_run_at, last_status, last_error, created_by, created_at, updated_at";
+
+fn schedule_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Schedule not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+/// Settings of a schedule as stored, shared by create and update.
+pub struct ScheduleSettings<'a> {
+    pub pipeline_id: Uuid,
+    pub interval_minutes: i32,
+    pub enabled: bool,
+    pub channel: Option<&'a str>,
+    pub encrypted_webhook_url: Option<&'a str>,
+    pub notify_when: &'a str,
+    pub threshold_column: Option<&'a str>,
+    pub threshold_op: Option<&'a str>,
+    pub threshold_value: Option<f64>,
+    pub top_rows: i32,
+}
+
+/// Create a schedule whose first run is one interval from now.
+pub async fn create_schedule(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    settings: &ScheduleSettings<'_>,
+    created_by: Uuid,
+) -> Result<PipelineSchedule, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineSchedule>(&format!(
+        r#"
+        INSERT INTO pipeline_schedules (
+            workspace_id, pipeline_id, interval_minutes, enabled, next_run_at, channel, encrypted_webhook_url,
+            notify_when, threshold_column, threshold_op, threshold_value, top_rows, created_by
+        )
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(mins => $3), $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING {SCHEDULE_COLUMNS}
+        "#
+    ))
+    .bind(workspace_id)
+    .bind(settings.pipeline_id)
+    .bind(settings.interval_minutes)
+    .bind(settings.enabled)
+    .bind(settings.channel)
+    .bind(settings.encrypted_webhook_url)
+    .bind(settings.notify_when)
+    .bind(settings.threshold_column)
+    .bind(settings.threshold_op)
+    .bind(settings.threshold_value)
+    .bind(settings.top_rows)
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(schedule_error)
+}
+
+pub async fn list_schedules(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<PipelineSchedule>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, PipelineSchedule>(&format!(
+        r#"
+        SELECT {SCHEDULE_COLUMNS}
+        FROM pipeline_schedules
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM pipeline_schedules WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#
+    ))
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_schedule(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    schedule_id: Uuid,
+) -> Result<PipelineSchedule, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineSchedule>(&format!(
+        "SELECT {SCHEDULE_COLUMNS} FROM pipeline_schedules WHERE id = $1 AND workspace_id = $2"
+    ))
+    .bind(schedule_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(schedule_error)
+}
+
+/// Replace a schedule's settings. The next run moves to one (possibly new) interval from now.
+pub async fn update_schedule(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    schedule_id: Uuid,
+    settings: &ScheduleSettings<'_>,
+) -> Result<PipelineSchedule, DoubledeckerError> {
+    sqlx::query_as::<_, PipelineSchedule>(&format!(
+        r#"
+        UPDATE pipeline_schedules
+        SET pipeline_id = $3, interval_minutes = $4, enabled = $5, next_run_at = NOW() + make_interval(mins => $4),
+            channel = $6, encrypted_webhook_url = $7, notify_when = $8, threshold_column = $9, threshold_op = $10,
+            threshold_value = $11, top_rows = $12, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING {SCHEDULE_COLUMNS}
+        "#
+    ))
+    .bind(schedule_id)
+    .bind(workspace_id)
+    .bind(settings.pipeline_id)
+    .bind(settings.interval_minutes)
+    .bind(settings.enabled)
+    .bind(settings.channel)
+    .bind(settings.encrypted_webhook_url)
+    .bind(settings.notify_when)
+    .bind(settings.threshold_column)
+    .bind(settings.threshold_op)
+    .bind(settings.threshold_value)
+    .bind(settings.top_rows)
+    .fetch_one(pool)
+    .await
+    .map_err(schedule_error)
+}
+
+pub async fn delete_schedule(pool: &PgPool, workspace_id: Uuid, schedule_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let result = sqlx::query("DELETE FROM pipeline_schedules WHERE id = $1 AND workspace_id = $2")
+        .bind(schedule_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
+
+/// Queue a `job_kind` job for every enabled schedule that is due and move each to its next run, in
+/// one statement so a schedule is never queued twice. Schedules locked by another replica are
+/// skipped. Returns how many were queued.
+pub async fn enqueue_due_schedules(
+    pool: &PgPool,
+    job_kind: &str,
+    max_attempts: i32,
+    batch: i64,
+) -> Result<u64, DoubledeckerError> {
+    let result = sqlx::query(
+        r#"
+        WITH due AS (
+            UPDATE pipeline_schedules
+            SET next_run_at = NOW() + make_interval(mins => interval_minutes)
+            WHERE id IN (
+                SELECT id FROM pipeline_schedules
+                WHERE enabled AND next_run_at <= NOW()
+                ORDER BY next_run_at
+                LIMIT $3
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, workspace_id, created_by
+        )
+        INSERT INTO background_jobs (workspace_id, kind, payload, max_attempts, created_by)
+        SELECT workspace_id, $1, jsonb_build_object('schedule_id', id), $2, created_by FROM due
+        "#,
+    )
+    .bind(job_kind)
+    .bind(max_attempts)
+    .bind(batch)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected())
+}
+
+/// Record the outcome of a scheduled run: `NOTIFIED`, `QUIET` or `FAILED` with its error.
+pub async fn record_schedule_run(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    status: &str,
+    error: Option<&str>,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query(
+        "UPDATE pipeline_schedules SET last_run_at = NOW(), last_status = $2, last_error = $3 WHERE id = $1",
+    )
+    .bind(schedule_id)
+    .bind(status)
+    .bind(error)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::db::models::SearchHit;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Datasets, folders, pipelines, dashboards and segments of every workspace `user_id` owns or is a
+/// member of whose name (or description) contains `query`, ignoring case. Names starting with it
+/// come first, then the most recently updated. Quarantined datasets are left out.
+pub async fn search_user_resources(
+    pool: &PgPool,
+    user_id: Uuid,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>, DoubledeckerError> {
+    let escaped = query.trim().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+
+    sqlx::query_as::<_, SearchHit>(
+        r#"
+        WITH accessible AS (
+            SELECT id, name FROM workspaces WHERE owner_user_id = $1
+            UNION
+            SELECT w.id, w.name
+            FROM workspaces w JOIN workspace_members m ON m.workspace_id = w.id
+            WHERE m.user_id = $1
+        ),
+        hits AS (
+            SELECT 'DATASET' AS kind, d.id, d.workspace_id, d.filename AS name, NULL::text AS description, d.updated_at
+            FROM datasets d
+            WHERE d.workspace_id IN (SELECT id FROM accessible)
+              AND d.status <> 'QUARANTINED'
+              AND d.filename ILIKE $2
+            UNION ALL
+            SELECT 'FOLDER', f.id, f.workspace_id, f.name, NULL, f.updated_at
+            FROM folders f
+            WHERE f.workspace_id IN (SELECT id FROM accessible) AND f.name ILIKE $2
+            UNION ALL
+            SELECT 'PIPELINE', p.id, p.workspace_id, p.name, p.description, p.updated_at
+            FROM pipelines p
+            WHERE p.workspace_id IN (SELECT id FROM accessible) AND (p.name ILIKE $2 OR p.description ILIKE $2)
+            UNION ALL
+            SELECT 'DASHBOARD', b.id, b.workspace_id, b.name, b.description, b.updated_at
+            FROM dashboards b
+            WHERE b.workspace_id IN (SELECT id FROM accessible) AND (b.name ILIKE $2 OR b.description ILIKE $2)
+            UNION ALL
+            SELECT 'SEGMENT', s.id, s.workspace_id, s.name, s.description, s.updated_at
+            FROM segments s
+            WHERE s.workspace_id IN (SELECT id FROM accessible) AND (s.name ILIKE $2 OR s.description ILIKE $2)
+        )
+        SELECT h.kind, h.id, h.workspace_id, a.name AS workspace_name, h.name, h.description, h.updated_at
+        FROM hits h JOIN accessible a ON a.id = h.workspace_id
+        ORDER BY h.name ILIKE $3 DESC, h.updated_at DESC, h.id
+        LIMIT $4
+        "#,
+    )
+    .bind(user_id)
+    .bind(format!("%{}%", escaped))
+    .bind(format!("{}%", escaped))
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
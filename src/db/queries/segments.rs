@@ -0,0 +1,145 @@
+use crate::db::models::{PaginatedResponse, Segment};
+use crate::db::queries::common::paginate_rows;
+use crate::server::dtos::analytics::QueryFilter;
+use crate::utils::error::DoubledeckerError;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+fn segment_error(e: sqlx::Error) -> DoubledeckerError {
+    match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Segment not found".to_string()),
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            DoubledeckerError::BadRequest("A segment with this name already exists".to_string())
+        }
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    }
+}
+
+pub async fn create_segment(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    filters: &[QueryFilter],
+    created_by: Uuid,
+) -> Result<Segment, DoubledeckerError> {
+    sqlx::query_as::<_, Segment>(
+        r#"
+        INSERT INTO segments (workspace_id, name, description, filters, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, workspace_id, name, description, filters, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .bind(Json(filters))
+    .bind(created_by)
+    .fetch_one(pool)
+    .await
+    .map_err(segment_error)
+}
+
+pub async fn list_segments(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<Segment>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, Segment>(
+        r#"
+        SELECT id, workspace_id, name, description, filters, created_by, created_at, updated_at
+        FROM segments
+        WHERE workspace_id = $1
+          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM segments WHERE id = $2))
+        ORDER BY created_at DESC, id DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.id.to_string()))
+}
+
+pub async fn get_segment(pool: &PgPool, workspace_id: Uuid, segment_id: Uuid) -> Result<Segment, DoubledeckerError> {
+    sqlx::query_as::<_, Segment>(
+        r#"
+        SELECT id, workspace_id, name, description, filters, created_by, created_at, updated_at
+        FROM segments
+        WHERE id = $1 AND workspace_id = $2
+        "#,
+    )
+    .bind(segment_id)
+    .bind(workspace_id)
+    .fetch_one(pool)
+    .await
+    .map_err(segment_error)
+}
+
+/// Segments of the workspace among `segment_ids`; ids of other workspaces or deleted segments are
+/// left out.
+pub async fn get_segments_by_ids(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    segment_ids: &[Uuid],
+) -> Result<Vec<Segment>, DoubledeckerError> {
+    sqlx::query_as::<_, Segment>(
+        r#"
+        SELECT id, workspace_id, name, description, filters, created_by, created_at, updated_at
+        FROM segments
+        WHERE workspace_id = $1 AND id = ANY($2)
+        "#,
+    )
+    .bind(workspace_id)
+    .bind(segment_ids)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn update_segment(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    segment_id: Uuid,
+    name: &str,
+    description: Option<&str>,
+    filters: &[QueryFilter],
+) -> Result<Segment, DoubledeckerError> {
+    sqlx::query_as::<_, Segment>(
+        r#"
+        UPDATE segments
+        SET name = $3, description = $4, filters = $5, updated_at = NOW()
+        WHERE id = $1 AND workspace_id = $2
+        RETURNING id, workspace_id, name, description, filters, created_by, created_at, updated_at
+        "#,
+    )
+    .bind(segment_id)
+    .bind(workspace_id)
+    .bind(name)
+    .bind(description)
+    .bind(Json(filters))
+    .fetch_one(pool)
+    .await
+    .map_err(segment_error)
+}
+
+pub async fn delete_segment(pool: &PgPool, workspace_id: Uuid, segment_id: Uuid) -> Result<u64, DoubledeckerError> {
+    let res = sqlx::query("DELETE FROM segments WHERE id = $1 AND workspace_id = $2")
+        .bind(segment_id)
+        .bind(workspace_id)
+        .execute(pool)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,88 @@
+use crate::db::models::UserSession;
+use crate::utils::error::DoubledeckerError;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn create_user_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    user_agent: Option<String>,
+    ip_address: Option<String>,
+    expires_at: DateTime<Utc>,
+) -> Result<UserSession, DoubledeckerError> {
+    sqlx::query_as::<_, UserSession>(
+        r#"
+        INSERT INTO user_sessions (user_id, user_agent, ip_address, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at, revoked_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_agent)
+    .bind(ip_address)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Mark a session as used if it is still valid. Returns false for revoked, expired or unknown sessions.
+pub async fn touch_user_session(
+    pool: &PgPool,
+    session_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, DoubledeckerError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE user_sessions
+        SET last_used_at = NOW()
+        WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_active_user_sessions(
+    pool: &PgPool,
+    user_id: Uuid,
+) -> Result<Vec<UserSession>, DoubledeckerError> {
+    sqlx::query_as::<_, UserSession>(
+        r#"
+        SELECT id, user_id, user_agent, ip_address, created_at, last_used_at, expires_at, revoked_at
+        FROM user_sessions
+        WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+        ORDER BY last_used_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+pub async fn revoke_user_session(
+    pool: &PgPool,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<(), DoubledeckerError> {
+    let result = sqlx::query(
+        "UPDATE user_sessions SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err(DoubledeckerError::NotFound("Session not found".to_string()));
+    }
+    Ok(())
+}
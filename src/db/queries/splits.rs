@@ -7,6 +7,7 @@ use sqlx::PgPool;
 use std::str::FromStr;
 use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn create_split(
     pool: &PgPool,
     workspace_id: Uuid,
@@ -120,6 +121,7 @@ pub async fn delete_split(
     Ok(result.rows_affected())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn update_split(
     pool: &PgPool,
     workspace_id: Uuid,
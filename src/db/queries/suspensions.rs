@@ -0,0 +1,90 @@
+use crate::db::models::{PaginatedResponse, UserSuspension};
+use crate::db::queries::common::paginate_rows;
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The user's active suspension, if any.
+pub async fn get_user_suspension(pool: &PgPool, user_id: Uuid) -> Result<Option<UserSuspension>, DoubledeckerError> {
+    sqlx::query_as::<_, UserSuspension>(
+        r#"
+        SELECT id AS user_id, email, suspension_reason AS reason, suspended_at, suspended_by
+        FROM users
+        WHERE id = $1 AND suspended_at IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Suspend a user. Re-suspending an already suspended user updates the reason but keeps the original time.
+pub async fn suspend_user(
+    pool: &PgPool,
+    user_id: Uuid,
+    reason: &str,
+    suspended_by: Uuid,
+) -> Result<UserSuspension, DoubledeckerError> {
+    sqlx::query_as::<_, UserSuspension>(
+        r#"
+        UPDATE users
+        SET suspended_at = COALESCE(suspended_at, NOW()), suspension_reason = $2, suspended_by = $3, updated_at = NOW()
+        WHERE id = $1
+        RETURNING id AS user_id, email, suspension_reason AS reason, suspended_at, suspended_by
+        "#,
+    )
+    .bind(user_id)
+    .bind(reason)
+    .bind(suspended_by)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| DoubledeckerError::NotFound("User not found".to_string()))
+}
+
+/// Lift a suspension. Returns false if the user was not suspended.
+pub async fn lift_user_suspension(pool: &PgPool, user_id: Uuid) -> Result<bool, DoubledeckerError> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET suspended_at = NULL, suspension_reason = NULL, suspended_by = NULL, updated_at = NOW()
+        WHERE id = $1 AND suspended_at IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Currently suspended users, most recently suspended first.
+pub async fn list_user_suspensions(
+    pool: &PgPool,
+    cursor: Option<String>,
+    limit: usize,
+) -> Result<PaginatedResponse<UserSuspension>, DoubledeckerError> {
+    let cursor_uuid = cursor.and_then(|c| Uuid::from_str(&c).ok());
+    let fetch_limit = (limit + 1) as i64;
+
+    let rows = sqlx::query_as::<_, UserSuspension>(
+        r#"
+        SELECT id AS user_id, email, suspension_reason AS reason, suspended_at, suspended_by
+        FROM users
+        WHERE suspended_at IS NOT NULL
+          AND ($1::uuid IS NULL OR (suspended_at, id) < (SELECT suspended_at, id FROM users WHERE id = $1))
+        ORDER BY suspended_at DESC, id DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(cursor_uuid)
+    .bind(fetch_limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(paginate_rows(rows, limit, |item| item.user_id.to_string()))
+}
@@ -0,0 +1,235 @@
+use crate::db::models::{UploadSession, UploadSessionPart};
+use crate::utils::error::DoubledeckerError;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const UPLOAD_SESSION_COLUMNS: &str = "id, workspace_id, user_id, filename, distributor_source, total_bytes, \
+                                      chunk_size, part_count, bytes_received, \
+                                      ARRAY(SELECT part_number FROM upload_session_parts p \
+                                            WHERE p.session_id = upload_sessions.id ORDER BY part_number) \
+                                      AS received_parts, \
+                                      staging_key, multipart_upload_id, strict, inference, status, dataset_id, \
+                                      created_at, updated_at";
+
+/// The parts of a session arrive through `record_upload_part` into the multipart upload
+/// `multipart_upload_id`, which assembles the file at `staging_key`.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_upload_session(
+    pool: &PgPool,
+    session_id: Uuid,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    filename: &str,
+    distributor_source: &str,
+    total_bytes: i64,
+    chunk_size: i64,
+    staging_key: &str,
+    multipart_upload_id: &str,
+    strict: bool,
+    inference: serde_json::Value,
+) -> Result<UploadSession, DoubledeckerError> {
+    sqlx::query_as::<_, UploadSession>(&format!(
+        r#"
+        INSERT INTO upload_sessions (id, workspace_id, user_id, filename, distributor_source, total_bytes, chunk_size,
+                                     staging_key, multipart_upload_id, strict, inference)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING {UPLOAD_SESSION_COLUMNS}
+        "#
+    ))
+    .bind(session_id)
+    .bind(workspace_id)
+    .bind(user_id)
+    .bind(filename)
+    .bind(distributor_source)
+    .bind(total_bytes)
+    .bind(chunk_size)
+    .bind(staging_key)
+    .bind(multipart_upload_id)
+    .bind(strict)
+    .bind(sqlx::types::Json(inference))
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// A session of the user who created it; other members of the workspace cannot see it.
+pub async fn get_upload_session(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    session_id: Uuid,
+) -> Result<UploadSession, DoubledeckerError> {
+    sqlx::query_as::<_, UploadSession>(&format!(
+        "SELECT {UPLOAD_SESSION_COLUMNS} FROM upload_sessions WHERE id = $1 AND workspace_id = $2 AND user_id = $3"
+    ))
+    .bind(session_id)
+    .bind(workspace_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?
+    .ok_or_else(|| DoubledeckerError::NotFound("Upload session not found".to_string()))
+}
+
+/// Record a stored part, replacing an earlier copy of it, and recount the session's bytes. Returns
+/// `None` when the session is no longer open.
+pub async fn record_upload_part(
+    pool: &PgPool,
+    session_id: Uuid,
+    part_number: i32,
+    size_bytes: i64,
+    sha256: &str,
+    etag: &str,
+) -> Result<Option<UploadSession>, DoubledeckerError> {
+    let mut tx = pool.begin().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    // Locking the session keeps the part from landing after a completion has claimed it
+    let open = sqlx::query("SELECT id FROM upload_sessions WHERE id = $1 AND status = 'OPEN' FOR UPDATE")
+        .bind(session_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    if open.is_none() {
+        return Ok(None);
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO upload_session_parts (session_id, part_number, size_bytes, sha256, etag)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (session_id, part_number)
+        DO UPDATE SET size_bytes = EXCLUDED.size_bytes, sha256 = EXCLUDED.sha256, etag = EXCLUDED.etag, created_at = NOW()
+        "#,
+    )
+    .bind(session_id)
+    .bind(part_number)
+    .bind(size_bytes)
+    .bind(sha256)
+    .bind(etag)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    let session = sqlx::query_as::<_, UploadSession>(&format!(
+        r#"
+        UPDATE upload_sessions
+        SET bytes_received = (SELECT COALESCE(SUM(size_bytes), 0) FROM upload_session_parts WHERE session_id = $1)::BIGINT,
+            updated_at = NOW()
+        WHERE id = $1
+        RETURNING {UPLOAD_SESSION_COLUMNS}
+        "#
+    ))
+    .bind(session_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    tx.commit().await.map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(Some(session))
+}
+
+/// The stored parts of a session, in part order.
+pub async fn list_upload_session_parts(
+    pool: &PgPool,
+    session_id: Uuid,
+) -> Result<Vec<UploadSessionPart>, DoubledeckerError> {
+    sqlx::query_as::<_, UploadSessionPart>(
+        "SELECT part_number, size_bytes, sha256, etag FROM upload_session_parts WHERE session_id = $1 ORDER BY part_number",
+    )
+    .bind(session_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Move a fully received session to COMPLETING, so only one request turns it into a dataset. A
+/// session left COMPLETING for `completing_timeout_secs`, by a request that died midway, is claimed
+/// again. Returns `None` if it is not open or still missing parts. Every part is checked for its
+/// size when stored, so a session holding `total_bytes` has them all.
+pub async fn claim_upload_session(
+    pool: &PgPool,
+    session_id: Uuid,
+    completing_timeout_secs: i64,
+) -> Result<Option<UploadSession>, DoubledeckerError> {
+    sqlx::query_as::<_, UploadSession>(&format!(
+        r#"
+        UPDATE upload_sessions
+        SET status = 'COMPLETING', updated_at = NOW()
+        WHERE id = $1 AND bytes_received = total_bytes
+          AND (status = 'OPEN'
+               OR (status = 'COMPLETING' AND updated_at < NOW() - make_interval(secs => $2)))
+        RETURNING {UPLOAD_SESSION_COLUMNS}
+        "#
+    ))
+    .bind(session_id)
+    .bind(completing_timeout_secs as f64)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
+
+/// Finish a claimed session: COMPLETED with the dataset created from it, or back to OPEN so
+/// completing can be retried.
+pub async fn finish_upload_session(
+    pool: &PgPool,
+    session_id: Uuid,
+    dataset_id: Option<Uuid>,
+) -> Result<(), DoubledeckerError> {
+    sqlx::query(
+        r#"
+        UPDATE upload_sessions
+        SET status = CASE WHEN $2::uuid IS NULL THEN 'OPEN' ELSE 'COMPLETED' END, dataset_id = $2, updated_at = NOW()
+        WHERE id = $1 AND status = 'COMPLETING'
+        "#,
+    )
+    .bind(session_id)
+    .bind(dataset_id)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(())
+}
+
+/// Delete a session that is not being completed, or whose completion stalled for
+/// `completing_timeout_secs`. Returns whether it was deleted.
+pub async fn delete_upload_session(
+    pool: &PgPool,
+    session_id: Uuid,
+    completing_timeout_secs: i64,
+) -> Result<bool, DoubledeckerError> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM upload_sessions
+        WHERE id = $1 AND (status <> 'COMPLETING' OR updated_at < NOW() - make_interval(secs => $2))
+        "#,
+    )
+    .bind(session_id)
+    .bind(completing_timeout_secs as f64)
+    .execute(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete up to `limit` sessions that never became a dataset and have not changed for `idle_secs`,
+/// returning their (staging key, multipart upload id) so the stored parts can be discarded.
+pub async fn delete_idle_upload_sessions(
+    pool: &PgPool,
+    idle_secs: i64,
+    limit: i64,
+) -> Result<Vec<(String, String)>, DoubledeckerError> {
+    sqlx::query_as::<_, (String, String)>(
+        r#"
+        DELETE FROM upload_sessions
+        WHERE id IN (
+            SELECT id FROM upload_sessions
+            WHERE status IN ('OPEN', 'COMPLETING') AND updated_at < NOW() - make_interval(secs => $1)
+            ORDER BY updated_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING staging_key, multipart_upload_id
+        "#,
+    )
+    .bind(idle_secs as f64)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))
+}
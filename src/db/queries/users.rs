@@ -1,11 +1,11 @@
-use crate::db::models::User;
+use crate::db::models::{User, UserSettings, UserStats};
 use crate::utils::error::DoubledeckerError;
 use bcrypt::{hash, verify, DEFAULT_COST};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
 pub async fn create_user(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     name: String,
     email: String,
     password: String,
@@ -27,7 +27,7 @@ pub async fn create_user(
     .bind(&email)
     .bind(&password_hash)
     .bind(&user_type_str)
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
     .map_err(|e| match e {
         sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
@@ -77,6 +77,59 @@ pub async fn get_user_by_id(pool: &PgPool, id: Uuid) -> Result<User, Doubledecke
     Ok(user)
 }
 
+/// Usage counts for a user, computed from their queries and datasets.
+pub async fn get_user_stats(pool: &PgPool, user_id: Uuid) -> Result<UserStats, DoubledeckerError> {
+    sqlx::query_as::<_, UserStats>(
+        r#"
+        SELECT total_queries, total_files_processed, total_storage_bytes
+        FROM user_stats
+        WHERE user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("User not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    })
+}
+
+/// The user's stored settings, or defaults if none were saved yet.
+pub async fn get_user_settings(pool: &PgPool, user_id: Uuid) -> Result<UserSettings, DoubledeckerError> {
+    let row: Option<(sqlx::types::Json<UserSettings>,)> =
+        sqlx::query_as("SELECT settings FROM user_settings WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(row.map(|(settings,)| settings.0).unwrap_or_default())
+}
+
+/// Replace the user's settings.
+pub async fn upsert_user_settings(
+    pool: &PgPool,
+    user_id: Uuid,
+    settings: &UserSettings,
+) -> Result<UserSettings, DoubledeckerError> {
+    let (saved,): (sqlx::types::Json<UserSettings>,) = sqlx::query_as(
+        r#"
+        INSERT INTO user_settings (user_id, settings)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET settings = EXCLUDED.settings, updated_at = CURRENT_TIMESTAMP
+        RETURNING settings
+        "#,
+    )
+    .bind(user_id)
+    .bind(sqlx::types::Json(settings))
+    .fetch_one(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(saved.0)
+}
+
 pub fn verify_password(password: &str, hash_str: &str) -> Result<bool, DoubledeckerError> {
     verify(password, hash_str)
         .map_err(|e| DoubledeckerError::Internal(format!("Password verification failed: {}", e)))
@@ -1,13 +1,12 @@
 use crate::db::models::{PaginatedResponse, Workspace};
 use crate::db::queries::common::paginate_rows;
 use crate::utils::error::DoubledeckerError;
-use chrono::Utc;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use std::str::FromStr;
 use uuid::Uuid;
 
 pub async fn create_workspace(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     owner_user_id: Uuid,
     name: String,
 ) -> Result<Workspace, DoubledeckerError> {
@@ -15,12 +14,12 @@ pub async fn create_workspace(
         r#"
         INSERT INTO workspaces (owner_user_id, name)
         VALUES ($1, $2)
-        RETURNING id, owner_user_id, name, storage_used_bytes, created_at, updated_at
+        RETURNING id, owner_user_id, name, 0::BIGINT AS storage_used_bytes, created_at, updated_at
         "#,
     )
     .bind(owner_user_id)
     .bind(&name)
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await
     .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
 
@@ -38,11 +37,12 @@ pub async fn get_workspaces_for_user(
 
     let rows = sqlx::query_as::<_, Workspace>(
         r#"
-        SELECT id, owner_user_id, name, storage_used_bytes, created_at, updated_at
-        FROM workspaces
-        WHERE owner_user_id = $1
-          AND ($2::uuid IS NULL OR (created_at, id) < (SELECT created_at, id FROM workspaces WHERE id = $2))
-        ORDER BY created_at DESC, id DESC
+        SELECT w.id, w.owner_user_id, w.name, s.storage_used_bytes, w.created_at, w.updated_at
+        FROM workspaces w
+        JOIN workspace_stats s ON s.workspace_id = w.id
+        WHERE w.owner_user_id = $1
+          AND ($2::uuid IS NULL OR (w.created_at, w.id) < (SELECT created_at, id FROM workspaces WHERE id = $2))
+        ORDER BY w.created_at DESC, w.id DESC
         LIMIT $3
         "#,
     )
@@ -63,9 +63,10 @@ pub async fn get_workspace_by_id(
 ) -> Result<Workspace, DoubledeckerError> {
     let workspace = sqlx::query_as::<_, Workspace>(
         r#"
-        SELECT id, owner_user_id, name, storage_used_bytes, created_at, updated_at
-        FROM workspaces
-        WHERE id = $1 AND owner_user_id = $2
+        SELECT w.id, w.owner_user_id, w.name, s.storage_used_bytes, w.created_at, w.updated_at
+        FROM workspaces w
+        JOIN workspace_stats s ON s.workspace_id = w.id
+        WHERE w.id = $1 AND w.owner_user_id = $2
         "#,
     )
     .bind(workspace_id)
@@ -80,27 +81,23 @@ pub async fn get_workspace_by_id(
     Ok(workspace)
 }
 
-pub async fn update_workspace_storage(
-    pool: &PgPool,
-    workspace_id: Uuid,
-    delta_bytes: i64,
-) -> Result<(), DoubledeckerError> {
-    sqlx::query(
+/// A workspace by id, whoever owns it; callers check access first.
+pub async fn get_workspace(pool: &PgPool, workspace_id: Uuid) -> Result<Workspace, DoubledeckerError> {
+    sqlx::query_as::<_, Workspace>(
         r#"
-        UPDATE workspaces
-        SET storage_used_bytes = storage_used_bytes + $2,
-            updated_at = $3
-        WHERE id = $1
+        SELECT w.id, w.owner_user_id, w.name, s.storage_used_bytes, w.created_at, w.updated_at
+        FROM workspaces w
+        JOIN workspace_stats s ON s.workspace_id = w.id
+        WHERE w.id = $1
         "#,
     )
     .bind(workspace_id)
-    .bind(delta_bytes)
-    .bind(Utc::now())
-    .execute(pool)
+    .fetch_one(pool)
     .await
-    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
-
-    Ok(())
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => DoubledeckerError::NotFound("Workspace not found".to_string()),
+        _ => DoubledeckerError::DatabaseError(e.to_string()),
+    })
 }
 
 pub async fn update_workspace(
@@ -110,10 +107,11 @@ pub async fn update_workspace(
 ) -> Result<Workspace, DoubledeckerError> {
     let ws = sqlx::query_as::<_, Workspace>(
         r#"
-        UPDATE workspaces
+        UPDATE workspaces w
         SET name = $2, updated_at = NOW()
-        WHERE id = $1
-        RETURNING id, owner_user_id, name, storage_used_bytes, created_at, updated_at
+        FROM workspace_stats s
+        WHERE w.id = $1 AND s.workspace_id = w.id
+        RETURNING w.id, w.owner_user_id, w.name, s.storage_used_bytes, w.created_at, w.updated_at
         "#,
     )
     .bind(workspace_id)
@@ -142,3 +140,23 @@ pub async fn delete_workspace(
 
     Ok(res.rows_affected())
 }
+
+/// Owner plus every member of a workspace, used to address workspace-wide notifications.
+pub async fn list_workspace_user_ids(
+    pool: &PgPool,
+    workspace_id: Uuid,
+) -> Result<Vec<Uuid>, DoubledeckerError> {
+    let rows: Vec<(Uuid,)> = sqlx::query_as(
+        r#"
+        SELECT owner_user_id FROM workspaces WHERE id = $1
+        UNION
+        SELECT user_id FROM workspace_members WHERE workspace_id = $1
+        "#,
+    )
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
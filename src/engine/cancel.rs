@@ -0,0 +1,176 @@
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::{DataFusionError, Result};
+use datafusion::execution::{RecordBatchStream, SendableRecordBatchStream, TaskContext};
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::stream::RecordBatchReceiverStream;
+use datafusion::physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties, collect, execute_stream};
+use futures::{Stream, StreamExt};
+use std::any::Any;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+
+/// Lets a query be stopped by dropping whatever waits for it, e.g. a request handler whose client
+/// disconnected. DataFusion operators rarely yield: a single-partition plan runs inline in the task
+/// polling it, producing batch after batch without returning to the runtime, so neither a dropped
+/// connection nor an aborted task is noticed until the query ends. Queries therefore run on a task
+/// of their own, and every operator of a plan wrapped by `wrap` checks the cancellation flag before
+/// each batch it produces.
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self { cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    /// Wrap every operator of `plan` so it stops once this cancellation is dropped. Plan display
+    /// and metrics are those of the plan as given.
+    pub fn wrap(&self, plan: Arc<dyn ExecutionPlan>) -> Result<Arc<dyn ExecutionPlan>> {
+        let children = plan
+            .children()
+            .into_iter()
+            .map(|child| self.wrap(child.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let inner = if children.is_empty() { plan } else { plan.with_new_children(children)? };
+        Ok(Arc::new(CancellableExec { inner, cancelled: self.cancelled.clone() }))
+    }
+
+    /// Collect a plan returned by `wrap` on a task of its own. Dropping the returned future
+    /// cancels the query.
+    pub async fn collect(self, plan: Arc<dyn ExecutionPlan>, ctx: Arc<TaskContext>) -> Result<Vec<RecordBatch>> {
+        let task = tokio::spawn(collect(plan, ctx));
+        let _abort = AbortOnDrop(task.abort_handle());
+        task.await.map_err(|e| DataFusionError::External(Box::new(e)))?
+    }
+
+    /// Stream a plan returned by `wrap` from a task of its own. Dropping the stream cancels the query.
+    pub fn stream(self, plan: Arc<dyn ExecutionPlan>, ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        let mut builder = RecordBatchReceiverStream::builder(plan.schema(), 2);
+        let tx = builder.tx();
+        builder.spawn(async move {
+            let mut batches = execute_stream(plan, ctx)?;
+            while let Some(batch) = batches.next().await {
+                if tx.send(batch).await.is_err() {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        Box::pin(CancelOnDropStream { inner: builder.build(), _cancellation: self })
+    }
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Cancellation {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// An operator that stops producing batches once its query is cancelled. Otherwise transparent:
+/// it displays, reports metrics and is rebuilt as the operator it wraps, whose children are
+/// wrapped in turn.
+#[derive(Debug)]
+struct CancellableExec {
+    inner: Arc<dyn ExecutionPlan>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl DisplayAs for CancellableExec {
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt_as(t, f)
+    }
+}
+
+impl ExecutionPlan for CancellableExec {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.inner.properties()
+    }
+
+    fn children(&self) -> Vec<&Arc<dyn ExecutionPlan>> {
+        self.inner.children()
+    }
+
+    fn with_new_children(self: Arc<Self>, children: Vec<Arc<dyn ExecutionPlan>>) -> Result<Arc<dyn ExecutionPlan>> {
+        let inner = self.inner.clone().with_new_children(children)?;
+        Ok(Arc::new(CancellableExec { inner, cancelled: self.cancelled.clone() }))
+    }
+
+    fn execute(&self, partition: usize, context: Arc<TaskContext>) -> Result<SendableRecordBatchStream> {
+        let inner = self.inner.execute(partition, context)?;
+        Ok(Box::pin(CancellableStream { inner, cancelled: self.cancelled.clone() }))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        self.inner.metrics()
+    }
+}
+
+/// A stream that ends with an error once its query is cancelled.
+struct CancellableStream {
+    inner: SendableRecordBatchStream,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Stream for CancellableStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Poll::Ready(Some(Err(DataFusionError::Execution("Query cancelled".to_string()))));
+        }
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl RecordBatchStream for CancellableStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+/// The output of `Cancellation::stream`, cancelling its query when dropped.
+struct CancelOnDropStream {
+    inner: SendableRecordBatchStream,
+    _cancellation: Cancellation,
+}
+
+impl Stream for CancelOnDropStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl RecordBatchStream for CancelOnDropStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
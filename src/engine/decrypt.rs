@@ -0,0 +1,169 @@
+use crate::utils::crypto::{decrypt_file_range, sealed_file_plaintext_len, sealed_file_range};
+use crate::utils::error::DoubledeckerError;
+use aes_gcm::{Aes256Gcm, Key};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use object_store::path::Path;
+use object_store::{
+    GetOptions, GetRange, GetResult, GetResultPayload, ListResult, MultipartUpload, ObjectMeta, ObjectStore,
+    PutMultipartOpts, PutOptions, PutPayload, PutResult,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Read view of a store where some files are sealed with a dataset's data key (see
+/// `crypto::FileEncryptor`). Those files are listed, sized and read as their plaintext, a range
+/// read fetching and opening only the chunks it overlaps; every other file passes through.
+pub struct DecryptingStore {
+    inner: Arc<dyn ObjectStore>,
+    keys: HashMap<Path, Key<Aes256Gcm>>,
+}
+
+impl DecryptingStore {
+    /// `keys` maps the sealed files' paths, relative to `inner`, to their data keys.
+    pub fn new(inner: Arc<dyn ObjectStore>, keys: HashMap<Path, Key<Aes256Gcm>>) -> Self {
+        Self { inner, keys }
+    }
+
+    fn plaintext_meta(&self, mut meta: ObjectMeta) -> object_store::Result<ObjectMeta> {
+        if self.keys.contains_key(&meta.location) {
+            meta.size = sealed_file_plaintext_len(meta.size).map_err(decrypt_err)?;
+        }
+        Ok(meta)
+    }
+}
+
+// Keys stay out of logs
+impl fmt::Debug for DecryptingStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DecryptingStore").field("inner", &self.inner).field("sealed_files", &self.keys.len()).finish()
+    }
+}
+
+impl fmt::Display for DecryptingStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DecryptingStore({})", self.inner)
+    }
+}
+
+fn decrypt_err(e: DoubledeckerError) -> object_store::Error {
+    object_store::Error::Generic { store: "DecryptingStore", source: Box::new(e) }
+}
+
+/// `range` of an object `len` bytes long, as the stores resolve it.
+fn resolve_range(range: &GetRange, len: usize) -> object_store::Result<Range<usize>> {
+    let resolved = match range {
+        GetRange::Bounded(r) => r.start..r.end.min(len),
+        GetRange::Offset(start) => *start..len,
+        GetRange::Suffix(n) => len.saturating_sub(*n)..len,
+    };
+    if resolved.start > resolved.end || (resolved.start == len && len > 0) {
+        return Err(decrypt_err(DoubledeckerError::BadRequest(format!(
+            "Range {:?} is outside an object of {} bytes",
+            range, len
+        ))));
+    }
+    Ok(resolved)
+}
+
+#[async_trait]
+impl ObjectStore for DecryptingStore {
+    async fn put_opts(&self, location: &Path, payload: PutPayload, opts: PutOptions) -> object_store::Result<PutResult> {
+        self.inner.put_opts(location, payload, opts).await
+    }
+
+    async fn put_multipart_opts(
+        &self,
+        location: &Path,
+        opts: PutMultipartOpts,
+    ) -> object_store::Result<Box<dyn MultipartUpload>> {
+        self.inner.put_multipart_opts(location, opts).await
+    }
+
+    async fn get_opts(&self, location: &Path, options: GetOptions) -> object_store::Result<GetResult> {
+        let Some(key) = self.keys.get(location) else {
+            return self.inner.get_opts(location, options).await;
+        };
+
+        // Preconditions are checked on the stored object; the range is resolved on the plaintext
+        let range = options.range.clone();
+        let stored = self.inner.get_opts(location, GetOptions { range: None, head: true, ..options }).await?;
+        let sealed_len = stored.meta.size;
+        let meta = self.plaintext_meta(stored.meta)?;
+        let range = match &range {
+            Some(range) => resolve_range(range, meta.size)?,
+            None => 0..meta.size,
+        };
+        let sealed = self.inner.get_range(location, sealed_file_range(&range, sealed_len)).await?;
+        let plaintext = decrypt_file_range(key, sealed_len, range.clone(), &sealed).map_err(decrypt_err)?;
+        Ok(GetResult {
+            payload: GetResultPayload::Stream(stream::once(async move { Ok(Bytes::from(plaintext)) }).boxed()),
+            meta,
+            range,
+            attributes: stored.attributes,
+        })
+    }
+
+    async fn head(&self, location: &Path) -> object_store::Result<ObjectMeta> {
+        self.plaintext_meta(self.inner.head(location).await?)
+    }
+
+    async fn delete(&self, location: &Path) -> object_store::Result<()> {
+        self.inner.delete(location).await
+    }
+
+    fn list(&self, prefix: Option<&Path>) -> BoxStream<'_, object_store::Result<ObjectMeta>> {
+        self.inner.list(prefix).map(|meta| meta.and_then(|m| self.plaintext_meta(m))).boxed()
+    }
+
+    async fn list_with_delimiter(&self, prefix: Option<&Path>) -> object_store::Result<ListResult> {
+        let mut result = self.inner.list_with_delimiter(prefix).await?;
+        result.objects = result
+            .objects
+            .into_iter()
+            .map(|meta| self.plaintext_meta(meta))
+            .collect::<object_store::Result<_>>()?;
+        Ok(result)
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn copy_if_not_exists(&self, from: &Path, to: &Path) -> object_store::Result<()> {
+        self.inner.copy_if_not_exists(from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::crypto::{encrypt_file, generate_data_key};
+    use object_store::memory::InMemory;
+
+    #[tokio::test]
+    async fn test_sealed_files_read_as_plaintext() {
+        let inner = Arc::new(InMemory::new());
+        let key = generate_data_key();
+        let file: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        let sealed = Path::from("processed/sealed.parquet");
+        let plain = Path::from("processed/plain.parquet");
+        inner.put(&sealed, encrypt_file(&key, &file).unwrap().into()).await.unwrap();
+        inner.put(&plain, file.clone().into()).await.unwrap();
+        let store = DecryptingStore::new(inner, HashMap::from([(sealed.clone(), key)]));
+
+        for path in [&sealed, &plain] {
+            assert_eq!(store.head(path).await.unwrap().size, file.len());
+            assert_eq!(store.get(path).await.unwrap().bytes().await.unwrap(), file);
+            assert_eq!(store.get_range(path, 70_000..140_001).await.unwrap(), file[70_000..140_001]);
+            let footer = store.get_opts(path, GetOptions { range: Some(GetRange::Suffix(8)), ..GetOptions::default() });
+            assert_eq!(footer.await.unwrap().bytes().await.unwrap(), file[file.len() - 8..]);
+        }
+        let listed: Vec<ObjectMeta> = store.list(None).map(|m| m.unwrap()).collect().await;
+        assert!(listed.iter().all(|meta| meta.size == file.len()));
+    }
+}
@@ -0,0 +1,111 @@
+use datafusion::common::tree_node::TreeNodeRecursion;
+use datafusion::logical_expr::LogicalPlan;
+
+/// What a query reads from one table scan, taken from its optimized logical plan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRead {
+    /// Registered table name, e.g. `royalty_data` rather than an alias or CTE name
+    pub table: String,
+    /// Columns of the table's files the scan reads after projection pushdown
+    pub columns_read: usize,
+    pub columns_total: usize,
+    /// Filters pushed into the scan, which may skip row groups
+    pub filtered: bool,
+    /// Rows the scan stops after, from a pushed-down `LIMIT`
+    pub fetch: Option<usize>,
+}
+
+/// Table scans of an optimized logical plan, including those in subqueries. Tables registered as
+/// views, such as `royalty_data`, are inlined by the optimizer; their scans are named after the
+/// innermost enclosing alias found in `tables`.
+pub fn table_reads(plan: &LogicalPlan, tables: &[String]) -> Vec<TableRead> {
+    let mut reads = Vec::new();
+    collect_reads(plan, None, tables, &mut reads);
+    reads
+}
+
+fn collect_reads(plan: &LogicalPlan, view: Option<&str>, tables: &[String], reads: &mut Vec<TableRead>) {
+    let view = match plan {
+        LogicalPlan::SubqueryAlias(alias) if tables.iter().any(|t| t == alias.alias.table()) => {
+            Some(alias.alias.table())
+        }
+        _ => view,
+    };
+    if let LogicalPlan::TableScan(scan) = plan {
+        let columns_total = scan.source.schema().fields().len();
+        reads.push(TableRead {
+            table: view.unwrap_or(scan.table_name.table()).to_string(),
+            columns_read: scan.projection.as_ref().map_or(columns_total, |p| p.len()),
+            columns_total,
+            filtered: !scan.filters.is_empty(),
+            fetch: scan.fetch,
+        });
+    }
+    for input in plan.inputs() {
+        collect_reads(input, view, tables, reads);
+    }
+    let _ = plan.apply_subqueries(|subquery| {
+        collect_reads(subquery, view, tables, reads);
+        Ok(TreeNodeRecursion::Jump)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+    use datafusion::prelude::SessionContext;
+    use std::sync::Arc;
+
+    async fn reads_of(sql: &str) -> Vec<TableRead> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("platform", DataType::Utf8, false),
+            Field::new("net_revenue", DataType::Float64, false),
+            Field::new("streams", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["Spotify"])),
+                Arc::new(Float64Array::from(vec![1.5])),
+                Arc::new(Int64Array::from(vec![10])),
+            ],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        // Registered like the engine registers datasets: a view over a file scan
+        let view = ctx.read_table(Arc::new(table)).unwrap().into_view();
+        ctx.register_table("royalty_data", view).unwrap();
+        let plan = ctx.sql(sql).await.unwrap().into_optimized_plan().unwrap();
+        table_reads(&plan, &["royalty_data".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_table_reads() {
+        let reads = reads_of("SELECT platform, SUM(net_revenue) FROM royalty_data r GROUP BY platform").await;
+        assert_eq!(
+            reads,
+            vec![TableRead {
+                table: "royalty_data".to_string(),
+                columns_read: 2,
+                columns_total: 3,
+                filtered: false,
+                fetch: None
+            }]
+        );
+
+        let reads = reads_of("SELECT * FROM royalty_data LIMIT 5").await;
+        assert_eq!(reads[0].columns_read, 3);
+        assert_eq!(reads[0].fetch, Some(5));
+
+        let reads = reads_of(
+            "SELECT platform FROM royalty_data WHERE streams > (SELECT AVG(streams) FROM royalty_data)",
+        )
+        .await;
+        assert_eq!(reads.len(), 2, "{:?}", reads);
+        assert!(reads.iter().all(|r| r.table == "royalty_data"));
+    }
+}
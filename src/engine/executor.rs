@@ -1,47 +1,303 @@
+use crate::config::EngineConfig;
+use crate::db::queries::list_workspace_sealed_files;
+use crate::engine::cancel::Cancellation;
+use crate::engine::decrypt::DecryptingStore;
+use crate::engine::estimate::{TableRead, table_reads};
+use crate::engine::lineage::{ColumnLineage, column_lineage};
+use crate::engine::metrics::{PeakMemoryPool, QueryMetrics, bytes_scanned};
 use crate::normalization::unified_royalty_schema;
 use crate::utils::error::DoubledeckerError;
+use crate::utils::crypto::{secrets_key_configured, unwrap_data_key};
+use crate::utils::helpers::localize_timestamps;
+use crate::utils::s3::workspace_prefix;
+use aes_gcm::{Aes256Gcm, Key};
 use datafusion::arrow::array::{ArrayRef, Float64Array, RecordBatch, StringArray};
-use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::catalog_common::resolve_table_references;
+use datafusion::datasource::file_format::options::ReadOptions;
+use datafusion::datasource::listing::{ListingTable, ListingTableConfig, ListingTableUrl};
+use datafusion::execution::cache::cache_manager::CacheManagerConfig;
+use datafusion::execution::cache::cache_unit::DefaultFileStatisticsCache;
 use datafusion::execution::disk_manager::DiskManagerConfig;
-use datafusion::execution::memory_pool::FairSpillPool;
+use datafusion::execution::memory_pool::{FairSpillPool, MemoryConsumer, MemoryReservation};
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
-use datafusion::prelude::{ParquetReadOptions, SessionConfig, SessionContext};
+use datafusion::execution::SendableRecordBatchStream;
+use crate::engine::udfs::MaskMode;
+use datafusion::execution::FunctionRegistry;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::common::ScalarValue;
+use datafusion::prelude::{DataFrame, ParquetReadOptions, SessionConfig, SessionContext, cast, ident, lit};
+use datafusion::sql::parser::DFParser;
+use std::collections::{BTreeMap, HashMap};
 use object_store::aws::AmazonS3Builder;
+use object_store::path::Path;
 use object_store::prefix::PrefixStore;
+use object_store::ObjectStore;
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 use uuid::Uuid;
 
+/// Narrows what a query session exposes. The default is the whole workspace.
+#[derive(Debug, Clone, Default)]
+pub struct QueryScope {
+    /// Restrict `royalty_data` to these datasets' Parquet files.
+    pub dataset_ids: Option<Vec<Uuid>>,
+    /// Anonymous access: only `royalty_data` is registered, internal tables like splits are not.
+    pub public: bool,
+    /// Column-level governance on `royalty_data`: hidden or masked columns for this caller.
+    pub column_masks: HashMap<String, MaskMode>,
+    /// Caller's IANA time zone: session time zone for `now()` and timestamp literals.
+    pub timezone: Option<String>,
+    /// Datasets registered on their own, under `lookup_table_name`, for enrichment joins.
+    pub lookup_dataset_ids: Vec<Uuid>,
+    /// Exploratory run: `royalty_data` holds only its first `PREVIEW_SAMPLE_ROWS` rows, read into
+    /// memory, and the result is cut to `PREVIEW_RESULT_ROWS`.
+    pub preview: bool,
+}
+
+impl QueryScope {
+    pub fn public_dataset(dataset_id: Uuid) -> Self {
+        Self {
+            dataset_ids: Some(vec![dataset_id]),
+            public: true,
+            ..Self::default()
+        }
+    }
+
+    /// Add a column mask, keeping the stricter mode if the column is already governed.
+    pub fn with_column_mask(mut self, column: &str, mode: MaskMode) -> Self {
+        let entry = self.column_masks.entry(column.to_string()).or_insert(mode);
+        if mode.strictness() > entry.strictness() {
+            *entry = mode;
+        }
+        self
+    }
+
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn with_lookup_datasets(mut self, dataset_ids: Vec<Uuid>) -> Self {
+        self.lookup_dataset_ids = dataset_ids;
+        self
+    }
+
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+}
+
+/// Rows of `royalty_data` a preview query reads. Small enough that sorting or grouping them takes
+/// well under a second.
+pub const PREVIEW_SAMPLE_ROWS: usize = 10_000;
+/// Rows a preview query returns.
+pub const PREVIEW_RESULT_ROWS: usize = 100;
+
+/// Table a lookup dataset is registered under in a query session.
+pub fn lookup_table_name(dataset_id: Uuid) -> String {
+    format!("lookup_{}", dataset_id.simple())
+}
+
+/// Hide or mask governed columns of a dataset read.
+fn apply_column_masks(
+    ctx: &SessionContext,
+    df: DataFrame,
+    column_masks: &HashMap<String, MaskMode>,
+) -> datafusion::error::Result<DataFrame> {
+    if column_masks.is_empty() {
+        return Ok(df);
+    }
+    let mut projection = Vec::new();
+    for field in df.schema().fields() {
+        let name = field.name();
+        match column_masks.get(name).map(|m| m.udf_name()) {
+            None => projection.push(ident(name)),
+            // Numbers keep their type, so aggregates over a masked column still plan
+            Some(Some(_)) if field.data_type().is_numeric() => {
+                projection.push(lit(ScalarValue::try_from(field.data_type())?).alias(name))
+            }
+            Some(Some(udf_name)) => projection.push(
+                ctx.udf(udf_name)?
+                    .call(vec![cast(ident(name), DataType::Utf8)])
+                    .alias(name),
+            ),
+            // MaskMode::Hide drops the column
+            Some(None) => {}
+        }
+    }
+    df.select(projection)
+}
+
+/// Re-express timestamp columns in `timezone`, as `localize_timestamps` does for rows held in
+/// memory, so `date_trunc` on them groups by the user's local day. The session time zone alone
+/// does not apply to timestamps stored without a zone.
+fn localize_timestamp_columns(df: DataFrame, timezone: Option<&str>) -> datafusion::error::Result<DataFrame> {
+    let Some(timezone) = timezone else {
+        return Ok(df);
+    };
+    if !df.schema().fields().iter().any(|f| matches!(f.data_type(), DataType::Timestamp(..))) {
+        return Ok(df);
+    }
+    let projection = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::Timestamp(unit, zone) => {
+                let name = field.name();
+                // Timestamps without a zone are UTC; zone -> zone casts keep the instant
+                let utc = match zone {
+                    None => cast(ident(name), DataType::Timestamp(*unit, Some("UTC".into()))),
+                    Some(_) => ident(name),
+                };
+                cast(utc, DataType::Timestamp(*unit, Some(timezone.into()))).alias(name)
+            }
+            _ => ident(field.name()),
+        })
+        .collect::<Vec<_>>();
+    df.select(projection)
+}
+
+/// Session config shared by all query contexts; `timezone` sets the session time zone.
+fn session_config(timezone: Option<&str>, target_partitions: usize) -> SessionConfig {
+    let mut config = SessionConfig::new()
+        .with_information_schema(true)
+        .with_target_partitions(target_partitions)
+        .with_collect_statistics(true);
+    if let Some(tz) = timezone {
+        config.options_mut().execution.time_zone = Some(tz.to_string());
+    }
+    config
+}
+
+/// A table over the Parquet files at `paths` (files, or directories of them), e.g. every appended
+/// dataset of a workspace. Files are split into up to the session's target partitions so they are
+/// scanned in parallel, and their row counts and column min/max are collected for the planner,
+/// using the runtime's statistics cache. The schema is merged from every path, not only the first.
+async fn parquet_table(ctx: &SessionContext, paths: &[String]) -> datafusion::error::Result<DataFrame> {
+    let config = ctx.copied_config();
+    let options = ParquetReadOptions::default()
+        .to_listing_options(&config, ctx.copied_table_options())
+        .with_collect_stat(true)
+        .with_target_partitions(config.target_partitions());
+    let state = ctx.state();
+    let mut urls = Vec::with_capacity(paths.len());
+    let mut schemas = Vec::with_capacity(paths.len());
+    for path in paths {
+        let url = ListingTableUrl::parse(path)?;
+        schemas.push(options.infer_schema(&state, &url).await?.as_ref().clone());
+        urls.push(url);
+    }
+    let schema = Schema::try_merge(schemas)?;
+    let table = ListingTable::try_new(
+        ListingTableConfig::new_with_multi_paths(urls)
+            .with_listing_options(options)
+            .with_schema(Arc::new(schema)),
+    )?
+    .with_cache(ctx.runtime_env().cache_manager.get_file_statistic_cache());
+    ctx.read_table(Arc::new(table))
+}
+
+/// The first `PREVIEW_SAMPLE_ROWS` rows of `df`, read once and held in memory, so no plan over the
+/// sample can scan past it (a limit in the plan can be lost when the optimizer merges limits).
+async fn preview_sample(ctx: &SessionContext, df: DataFrame) -> datafusion::error::Result<DataFrame> {
+    let schema = Arc::new(df.schema().as_arrow().clone());
+    let batches = df.limit(0, Some(PREVIEW_SAMPLE_ROWS))?.collect().await?;
+    let sample = datafusion::datasource::MemTable::try_new(schema, vec![batches])?;
+    ctx.read_table(Arc::new(sample))
+}
+
+/// Names of the tables a SQL statement reads from, CTEs excluded. Unparseable SQL yields nothing.
+pub fn referenced_tables(query_sql: &str) -> Vec<String> {
+    let Some(statement) = DFParser::parse_sql(query_sql).ok().and_then(|mut s| s.pop_front()) else {
+        return Vec::new();
+    };
+    let Ok((tables, _ctes)) = resolve_table_references(&statement, true) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = tables.iter().map(|t| t.table().to_string()).collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// A query run by `EngineProvider::execute_with_metrics`.
+pub struct ExecutedQuery {
+    pub batches: Vec<RecordBatch>,
+    pub metrics: QueryMetrics,
+    /// For a query at or over the slow-query threshold, its physical plan with each operator's
+    /// rows, bytes and compute time
+    pub slow_plan: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct EngineProvider {
-    s3_bucket: String,
+    /// Store holding the workspaces' Parquet files, shared by every session so its connections and
+    /// credentials are reused; S3 configured from the environment unless replaced
+    object_store: Option<Arc<dyn ObjectStore>>,
     db_pool: PgPool,
     rt_env: Arc<RuntimeEnv>,
+    target_partitions: usize,
+    slow_query_threshold: Option<Duration>,
 }
 
 impl EngineProvider {
-    pub fn new(db_pool: PgPool) -> Self {
+    pub fn new(db_pool: PgPool, config: &EngineConfig) -> Self {
         let s3_bucket = std::env::var("S3_BUCKET").unwrap_or_else(|_| "dd-query-csv-bucket".to_string());
 
-        // 1. Configure a single, server-wide bounded memory pool (2GB RAM limit)
-        // and let DataFusion automatically manage ephemeral OS temp directories for disk spilling.
+        // 1. Configure a single, server-wide bounded memory pool shared fairly between spilling
+        // operators, so one huge group-by fails (or spills) instead of taking the process down.
+        let disk_manager = match (config.spill_to_disk, config.spill_dirs.is_empty()) {
+            (false, _) => DiskManagerConfig::Disabled,
+            (true, true) => DiskManagerConfig::NewOs,
+            (true, false) => DiskManagerConfig::NewSpecified(config.spill_dirs.clone()),
+        };
+        // File statistics are read from Parquet footers once per file and shared by every session;
+        // an entry is dropped when the file's size or modification time changes
+        let cache_config = CacheManagerConfig::default()
+            .with_files_statistics_cache(Some(Arc::new(DefaultFileStatisticsCache::default())));
         let rt_config = RuntimeConfig::new()
-            .with_memory_pool(Arc::new(FairSpillPool::new(2 * 1024 * 1024 * 1024)))
-            .with_disk_manager(DiskManagerConfig::NewOs);
+            .with_memory_pool(Arc::new(FairSpillPool::new(config.memory_limit_bytes)))
+            .with_disk_manager(disk_manager)
+            .with_cache_manager(cache_config);
 
         let rt_env = Arc::new(
             RuntimeEnv::try_new(rt_config)
                 .expect("Failed to initialize global DataFusion RuntimeEnv"),
         );
 
+        let object_store = match AmazonS3Builder::from_env().with_bucket_name(&s3_bucket).build() {
+            Ok(store) => Some(Arc::new(store) as Arc<dyn ObjectStore>),
+            Err(e) => {
+                eprintln!("Could not configure S3 for queries: {}", e);
+                None
+            }
+        };
+
         Self {
-            s3_bucket,
+            object_store,
             db_pool,
             rt_env,
+            target_partitions: config.target_partitions,
+            slow_query_threshold: config.slow_query_threshold,
         }
     }
 
+    /// Queries running at least this long carry their plan in `ExecutedQuery::slow_plan`.
+    pub fn slow_query_threshold(&self) -> Option<Duration> {
+        self.slow_query_threshold
+    }
+
+    /// Read workspace data from `store` instead of S3, e.g. an in-memory store in tests.
+    pub fn with_object_store(mut self, store: Arc<dyn ObjectStore>) -> Self {
+        self.object_store = Some(store);
+        self
+    }
+
     /// Executes analytics queries in a strictly isolated, ephemeral session context.
     /// Eliminates race conditions and cross-tenant data leakage.
     pub async fn execute_royalty_analytics(
@@ -49,43 +305,378 @@ impl EngineProvider {
         workspace_id: Uuid,
         query_sql: &str,
     ) -> Result<Vec<RecordBatch>, DoubledeckerError> {
+        self.execute_scoped_analytics(workspace_id, &QueryScope::default(), query_sql)
+            .await
+    }
+
+    /// `execute_royalty_analytics` restricted to a `QueryScope`.
+    pub async fn execute_scoped_analytics(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<Vec<RecordBatch>, DoubledeckerError> {
+        Ok(self.execute_with_metrics(workspace_id, scope, query_sql).await?.batches)
+    }
+
+    /// `execute_scoped_analytics`, also reporting the resources the query used. Its memory is
+    /// reserved from the shared pool as usual, through a per-query pool that records the peak.
+    pub async fn execute_with_metrics(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<ExecutedQuery, DoubledeckerError> {
+        let start = Instant::now();
+        let memory = Arc::new(PeakMemoryPool::new(self.rt_env.memory_pool.clone()));
+        let rt_env = RuntimeEnv { memory_pool: memory.clone(), ..self.rt_env.as_ref().clone() };
+        let ctx = self.tenant_session_in(workspace_id, scope, Arc::new(rt_env)).await;
+
+        // 5. Execute relational query plan
+        let mut df = ctx
+            .sql(query_sql)
+            .await
+            .map_err(DoubledeckerError::from_query_planning)?;
+        if scope.preview {
+            df = df
+                .limit(0, Some(PREVIEW_RESULT_ROWS))
+                .map_err(DoubledeckerError::from_query_planning)?;
+        }
+        let cancellation = Cancellation::new();
+        let plan = df
+            .create_physical_plan()
+            .await
+            .and_then(|plan| cancellation.wrap(plan))
+            .map_err(DoubledeckerError::from_query_planning)?;
+
+        // Dropping this future, e.g. when the client disconnects, stops the query
+        let batches = cancellation
+            .collect(plan.clone(), ctx.task_ctx())
+            .await
+            .map_err(DoubledeckerError::from_query_execution)?;
+
+        let metrics = QueryMetrics {
+            elapsed_ms: start.elapsed().as_millis() as i64,
+            output_rows: batches.iter().map(|b| b.num_rows() as i64).sum(),
+            bytes_scanned: bytes_scanned(plan.as_ref()) as i64,
+            peak_memory_bytes: memory.peak() as i64,
+        };
+        let slow_plan = self
+            .slow_query_threshold
+            .filter(|threshold| start.elapsed() >= *threshold)
+            .map(|_| DisplayableExecutionPlan::with_metrics(plan.as_ref()).indent(true).to_string());
+        Ok(ExecutedQuery { batches, metrics, slow_plan })
+    }
+
+    /// Plan a query without running it and return the schema of its result.
+    pub async fn plan_schema(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<SchemaRef, DoubledeckerError> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+        let df = ctx
+            .sql(query_sql)
+            .await
+            .map_err(DoubledeckerError::from_query_planning)?;
+        Ok(Arc::new(df.schema().as_arrow().clone()))
+    }
+
+    /// Plan a query without running it and trace each result column back to the table columns it
+    /// is computed from.
+    pub async fn plan_lineage(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<Vec<ColumnLineage>, DoubledeckerError> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+        let df = ctx
+            .sql(query_sql)
+            .await
+            .map_err(DoubledeckerError::from_query_planning)?;
+        Ok(column_lineage(df.logical_plan()))
+    }
+
+    /// Plan and optimize a query without running it and list what each of its table scans reads,
+    /// with dataset views (`royalty_data`, lookups) named as registered.
+    pub async fn plan_table_reads(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<Vec<TableRead>, DoubledeckerError> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+        let plan = ctx
+            .sql(query_sql)
+            .await
+            .and_then(|df| df.into_optimized_plan())
+            .map_err(DoubledeckerError::from_query_planning)?;
+        let mut tables = vec!["royalty_data".to_string()];
+        tables.extend(scope.lookup_dataset_ids.iter().map(|id| lookup_table_name(*id)));
+        Ok(table_reads(&plan, &tables))
+    }
+
+    /// Same isolation as `execute_scoped_analytics`, but yields batches as they are produced
+    /// instead of collecting the full result in memory. Dropping the stream stops the query.
+    pub async fn stream_scoped_analytics(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        query_sql: &str,
+    ) -> Result<SendableRecordBatchStream, DoubledeckerError> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+
+        let df = ctx
+            .sql(query_sql)
+            .await
+            .map_err(DoubledeckerError::from_query_planning)?;
+
+        let cancellation = Cancellation::new();
+        let plan = df
+            .create_physical_plan()
+            .await
+            .and_then(|plan| cancellation.wrap(plan))
+            .map_err(DoubledeckerError::from_query_execution)?;
+        Ok(cancellation.stream(plan, ctx.task_ctx()))
+    }
+
+    /// A reservation against the server-wide memory pool for data held outside a query plan, e.g.
+    /// rows pulled from an external connection; released when dropped.
+    pub fn reserve_memory(&self, name: &str) -> MemoryReservation {
+        MemoryConsumer::new(name).register(&self.rt_env.memory_pool)
+    }
+
+    /// Runs SQL over an in-memory table (e.g. rows pulled from an external connection)
+    /// using the shared runtime, so the server-wide memory limit still applies.
+    /// With a `timezone`, timestamp columns are moved into it first so `date_trunc` groups by local day.
+    pub async fn execute_on_batches(
+        &self,
+        table_name: &str,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+        query_sql: &str,
+        timezone: Option<&str>,
+    ) -> Result<Vec<RecordBatch>, DoubledeckerError> {
+        let ctx = SessionContext::new_with_config_rt(session_config(timezone, self.target_partitions), self.rt_env.clone());
+        crate::engine::udfs::register_music_udfs(&ctx);
+
+        let (schema, batches) = match timezone {
+            Some(tz) => {
+                let batches = batches
+                    .iter()
+                    .map(|b| localize_timestamps(b, tz))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let empty = RecordBatch::new_empty(schema);
+                (localize_timestamps(&empty, tz)?.schema(), batches)
+            }
+            None => (schema, batches),
+        };
+
+        let mem_table = datafusion::datasource::MemTable::try_new(schema, vec![batches])
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to stage external table: {}", e)))?;
+        ctx.register_table(table_name, Arc::new(mem_table))
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to register external table: {}", e)))?;
+
+        let df = ctx
+            .sql(query_sql)
+            .await
+            .map_err(DoubledeckerError::from_query_planning)?;
+
+        let cancellation = Cancellation::new();
+        let plan = df
+            .create_physical_plan()
+            .await
+            .and_then(|plan| cancellation.wrap(plan))
+            .map_err(DoubledeckerError::from_query_planning)?;
+        cancellation
+            .collect(plan, ctx.task_ctx())
+            .await
+            .map_err(DoubledeckerError::from_query_execution)
+    }
+
+    /// Find the first step of a multi-step query that does not plan. Steps, given as `(name, sql)`,
+    /// are planned in order in one session, each registered as a view for the ones after it. Returns
+    /// the step's index, its planning error, and the columns of every table it could read.
+    pub async fn diagnose_steps(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+        steps: &[(String, String)],
+    ) -> Option<(usize, DoubledeckerError, BTreeMap<String, Vec<String>>)> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+
+        let mut available = BTreeMap::new();
+        let mut tables: Vec<String> = vec!["royalty_data".to_string(), "cascading_splits".to_string()];
+        tables.extend(scope.lookup_dataset_ids.iter().map(|id| lookup_table_name(*id)));
+        for name in tables {
+            if let Ok(provider) = ctx.table_provider(name.as_str()).await {
+                available.insert(name, provider.schema().fields().iter().map(|f| f.name().clone()).collect());
+            }
+        }
+
+        for (i, (name, sql)) in steps.iter().enumerate() {
+            let df = match ctx.sql(sql).await {
+                Ok(df) => df,
+                Err(e) => return Some((i, DoubledeckerError::from_query_planning(e), available)),
+            };
+            let columns = df.schema().fields().iter().map(|f| f.name().clone()).collect();
+            if let Err(e) = ctx.register_table(name.as_str(), df.into_view()) {
+                return Some((i, DoubledeckerError::from_query_planning(e), available));
+            }
+            available.insert(name.clone(), columns);
+        }
+        None
+    }
+
+    /// Names and schemas of the logical tables a workspace can query.
+    pub async fn list_tables(
+        &self,
+        workspace_id: Uuid,
+        scope: &QueryScope,
+    ) -> Result<Vec<(String, SchemaRef)>, DoubledeckerError> {
+        let ctx = self.tenant_session(workspace_id, scope).await;
+
+        let mut tables = Vec::new();
+        for name in ["royalty_data", "cascading_splits"] {
+            if let Ok(provider) = ctx.table_provider(name).await {
+                tables.push((name.to_string(), provider.schema()));
+            }
+        }
+        Ok(tables)
+    }
+
+    /// Data keys of the workspace's encrypted Parquet files, by path under the workspace prefix.
+    /// Without a master key no file is stored encrypted, and Postgres is not asked.
+    async fn sealed_file_keys(&self, workspace_id: Uuid) -> HashMap<Path, Key<Aes256Gcm>> {
+        if !secrets_key_configured() {
+            return HashMap::new();
+        }
+        let files = match list_workspace_sealed_files(&self.db_pool, workspace_id).await {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Could not load data keys for workspace {}: {}", workspace_id, e);
+                return HashMap::new();
+            }
+        };
+        let prefix = workspace_prefix(workspace_id);
+        let mut unwrapped: HashMap<String, Key<Aes256Gcm>> = HashMap::new();
+        let mut keys = HashMap::with_capacity(files.len());
+        for (parquet_key, wrapped_key) in files {
+            let Some(path) = parquet_key.strip_prefix(&prefix) else {
+                continue;
+            };
+            let key = match unwrapped.get(&wrapped_key) {
+                Some(key) => *key,
+                None => match unwrap_data_key(&wrapped_key) {
+                    Ok(key) => *unwrapped.entry(wrapped_key).or_insert(key),
+                    Err(e) => {
+                        eprintln!("Could not unwrap a data key of workspace {}: {}", workspace_id, e);
+                        continue;
+                    }
+                },
+            };
+            keys.insert(Path::from(path), key);
+        }
+        keys
+    }
+
+    /// Builds an ephemeral session scoped to a single workspace with all tenant tables registered.
+    async fn tenant_session(&self, workspace_id: Uuid, scope: &QueryScope) -> SessionContext {
+        self.tenant_session_in(workspace_id, scope, self.rt_env.clone()).await
+    }
+
+    /// `tenant_session` on `rt_env`, the shared runtime or one derived from it.
+    async fn tenant_session_in(&self, workspace_id: Uuid, scope: &QueryScope, rt_env: Arc<RuntimeEnv>) -> SessionContext {
         // 1. Create an ephemeral session context borrowing the shared global runtime environment
-        let session_config = SessionConfig::new().with_information_schema(true);
-        let ctx = SessionContext::new_with_config_rt(session_config, self.rt_env.clone());
+        let ctx = SessionContext::new_with_config_rt(
+            session_config(scope.timezone.as_deref(), self.target_partitions),
+            rt_env,
+        );
 
-        // 2. Instantiate Tenant-Scoped Object Store rooted strictly at the workspace prefix
+        // 2. Instantiate Tenant-Scoped Object Store rooted strictly at the workspace prefix, reading
+        //    the workspace's encrypted files as plaintext
         let prefix = format!("workspaces/{}", workspace_id);
-        if let Ok(s3_store) = AmazonS3Builder::from_env()
-            .with_bucket_name(&self.s3_bucket)
-            .build()
-        {
-            let prefix_store = PrefixStore::new(s3_store, prefix);
+        if let Some(store) = &self.object_store {
+            let prefix_store = PrefixStore::new(store.clone(), prefix);
+            let store = DecryptingStore::new(Arc::new(prefix_store), self.sealed_file_keys(workspace_id).await);
             if let Ok(url) = Url::parse("s3://tenant_data/") {
                 ctx.runtime_env()
-                    .register_object_store(&url, Arc::new(prefix_store));
+                    .register_object_store(&url, Arc::new(store));
             }
         }
 
         // 3. Register music UDFs
         crate::engine::udfs::register_music_udfs(&ctx);
 
-        // 4. Register logical table `royalty_data`
-        let parquet_url = "s3://tenant_data/processed/";
-        let options = ParquetReadOptions::default();
-        if let Err(e) = ctx.register_parquet("royalty_data", parquet_url, options).await {
+        // 4. Register logical table `royalty_data` over every dataset's file, optionally limited to
+        //    specific datasets and with restricted columns projected away
+        let source = match &scope.dataset_ids {
+            None => parquet_table(&ctx, &["s3://tenant_data/processed/".to_string()]).await,
+            Some(ids) if ids.is_empty() => Err(datafusion::error::DataFusionError::Plan(
+                "No datasets in scope".to_string(),
+            )),
+            Some(ids) => {
+                let paths: Vec<String> = ids
+                    .iter()
+                    .map(|id| format!("s3://tenant_data/processed/{}.parquet", id))
+                    .collect();
+                parquet_table(&ctx, &paths).await
+            }
+        };
+        let source = match source {
+            Ok(df) if scope.preview => preview_sample(&ctx, df).await,
+            other => other,
+        };
+        let registered = source.and_then(|df| {
+            let df = localize_timestamp_columns(df, scope.timezone.as_deref())?;
+            let df = apply_column_masks(&ctx, df, &scope.column_masks)?;
+            ctx.register_table("royalty_data", df.into_view()).map(|_| ())
+        });
+        if let Err(e) = registered {
             eprintln!(
                 "Note: Could not register Parquet files for workspace {} (may be empty): {}. Registering empty memory table.",
                 workspace_id, e
             );
-            let empty_batch = RecordBatch::new_empty(unified_royalty_schema());
-            if let Ok(mem_table) = datafusion::datasource::MemTable::try_new(
-                unified_royalty_schema(),
-                vec![vec![empty_batch]],
-            ) {
+            let full_schema = unified_royalty_schema();
+            let visible_fields: Vec<Field> = full_schema
+                .fields()
+                .iter()
+                .filter(|f| scope.column_masks.get(f.name()) != Some(&MaskMode::Hide))
+                .map(|f| match scope.column_masks.get(f.name()) {
+                    Some(_) if f.data_type().is_numeric() => f.as_ref().clone().with_nullable(true),
+                    Some(_) => Field::new(f.name(), DataType::Utf8, true),
+                    None => f.as_ref().clone(),
+                })
+                .collect();
+            let schema = Arc::new(Schema::new(visible_fields));
+            let empty_batch = RecordBatch::new_empty(schema.clone());
+            if let Ok(mem_table) =
+                datafusion::datasource::MemTable::try_new(schema, vec![vec![empty_batch]])
+            {
                 let _ = ctx.register_table("royalty_data", Arc::new(mem_table));
             }
         }
 
+        // 4a. Register lookup datasets under their own names, governed like `royalty_data`
+        for dataset_id in &scope.lookup_dataset_ids {
+            let path = format!("s3://tenant_data/processed/{}.parquet", dataset_id);
+            let registered = match parquet_table(&ctx, &[path]).await {
+                Ok(df) => localize_timestamp_columns(df, scope.timezone.as_deref())
+                    .and_then(|df| apply_column_masks(&ctx, df, &scope.column_masks))
+                    .and_then(|df| ctx.register_table(lookup_table_name(*dataset_id), df.into_view()).map(|_| ())),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = registered {
+                eprintln!("Note: Could not register lookup dataset {}: {}", dataset_id, e);
+            }
+        }
+
+        if scope.public {
+            return ctx;
+        }
+
         // 4b. Load and register cascading splits for relational join fan-out
         if let Ok(splits) = crate::db::queries::get_effective_splits(
             &self.db_pool,
@@ -126,26 +717,159 @@ impl EngineProvider {
             if let Ok(batch) = RecordBatch::try_new(
                 splits_schema.clone(),
                 vec![payee_arr, pct_arr, track_arr, album_arr, artist_arr],
-            ) {
-                if let Ok(mem_table) =
-                    datafusion::datasource::MemTable::try_new(splits_schema, vec![vec![batch]])
-                {
-                    let _ = ctx.register_table("cascading_splits", Arc::new(mem_table));
-                }
+            ) && let Ok(mem_table) = datafusion::datasource::MemTable::try_new(splits_schema, vec![vec![batch]])
+            {
+                let _ = ctx.register_table("cascading_splits", Arc::new(mem_table));
             }
         }
 
-        // 5. Execute relational query plan
-        let df = ctx
-            .sql(query_sql)
-            .await
-            .map_err(|e| DoubledeckerError::Internal(format!("SQL query planning error: {}", e)))?;
+        ctx
+    }
+}
 
-        let batches = df
-            .collect()
-            .await
-            .map_err(|e| DoubledeckerError::Internal(format!("Query execution error: {}", e)))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::helpers::batches_to_parquet;
+    use datafusion::arrow::array::{Int64Array, TimestampMicrosecondArray};
+    use datafusion::arrow::compute::concat_batches;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+    use sqlx::postgres::PgPoolOptions;
+
+    async fn put_parquet(store: &InMemory, path: &str, columns: Vec<(&str, ArrayRef)>) {
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let bytes = batches_to_parquet(&[batch]).unwrap();
+        store.put(&Path::from(path), bytes.into()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parquet_table_spans_appended_files() {
+        let store = InMemory::new();
+        let platforms = Arc::new(StringArray::from(vec!["Spotify", "Apple"])) as ArrayRef;
+        let revenue = Arc::new(Float64Array::from(vec![1.5, 2.0])) as ArrayRef;
+        put_parquet(&store, "processed/a.parquet", vec![("platform", platforms.clone())]).await;
+        put_parquet(&store, "processed/b.parquet", vec![("platform", platforms), ("net_revenue", revenue)]).await;
+
+        let ctx = SessionContext::new_with_config(session_config(None, 4));
+        ctx.runtime_env().register_object_store(&Url::parse("s3://tenant_data/").unwrap(), Arc::new(store));
+        let paths = ["a", "b"].map(|id| format!("s3://tenant_data/processed/{}.parquet", id));
+        let df = parquet_table(&ctx, &paths).await.unwrap();
+
+        let columns: Vec<&String> = df.schema().fields().iter().map(|f| f.name()).collect();
+        assert_eq!(columns, ["platform", "net_revenue"], "columns of later appends are kept");
+        assert_eq!(df.count().await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_preview_reads_a_sample() {
+        let store = InMemory::new();
+        let (workspace_id, dataset_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let rows = (0..(PREVIEW_SAMPLE_ROWS + 50) as i64).collect::<Vec<_>>();
+        let path = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+        put_parquet(&store, &path, vec![("quantity", Arc::new(Int64Array::from(rows)) as ArrayRef)]).await;
+        // Without a master key, public scopes never reach Postgres
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let engine = EngineProvider::new(pool, &EngineConfig::from_env()).with_object_store(Arc::new(store));
+
+        let count = |preview: bool| {
+            let scope = QueryScope::public_dataset(dataset_id).with_preview(preview);
+            let engine = engine.clone();
+            async move {
+                let batches =
+                    engine.execute_scoped_analytics(workspace_id, &scope, "SELECT COUNT(*) FROM royalty_data").await;
+                batches.unwrap()[0].column(0).as_any().downcast_ref::<Int64Array>().unwrap().value(0) as usize
+            }
+        };
+        assert_eq!(count(false).await, PREVIEW_SAMPLE_ROWS + 50);
+        assert_eq!(count(true).await, PREVIEW_SAMPLE_ROWS);
 
-        Ok(batches)
+        let scope = QueryScope::public_dataset(dataset_id).with_preview(true);
+        let sql = "SELECT quantity FROM royalty_data ORDER BY quantity DESC";
+        let batches = engine.execute_scoped_analytics(workspace_id, &scope, sql).await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), PREVIEW_RESULT_ROWS);
+    }
+
+    #[tokio::test]
+    async fn test_masked_numeric_columns_keep_their_type() {
+        let store = InMemory::new();
+        let (workspace_id, dataset_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+        let platforms = Arc::new(StringArray::from(vec!["Spotify", "Spotify", "Apple"])) as ArrayRef;
+        let quantities = Arc::new(Int64Array::from(vec![10, 20, 30])) as ArrayRef;
+        put_parquet(&store, &path, vec![("platform", platforms), ("quantity", quantities)]).await;
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let engine = EngineProvider::new(pool, &EngineConfig::from_env()).with_object_store(Arc::new(store));
+        let scope = QueryScope::public_dataset(dataset_id)
+            .with_column_mask("platform", MaskMode::Partial)
+            .with_column_mask("quantity", MaskMode::Hash);
+
+        let sql = "SELECT platform, SUM(quantity) AS total, AVG(quantity) AS average FROM royalty_data GROUP BY platform";
+        let batches = engine.execute_scoped_analytics(workspace_id, &scope, sql).await.unwrap();
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 2, "masked values still group");
+        assert_eq!(batch.schema().field(1).data_type(), &DataType::Int64);
+        assert_eq!(batch.column(1).null_count(), 2, "masked numbers are null");
+        assert_eq!(batch.column(2).null_count(), 2);
+        let platforms = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert!(platforms.iter().all(|p| p.is_some_and(|p| p.starts_with('*') && p != "Spotify")));
+    }
+
+    #[tokio::test]
+    async fn test_date_trunc_groups_by_local_day() {
+        let store = InMemory::new();
+        let (workspace_id, dataset_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+        // Noon and 23:30 UTC on June 30th; the later one is already July 1st in Lagos (UTC+1)
+        let noon = chrono::NaiveDate::from_ymd_opt(2026, 6, 30).unwrap().and_hms_opt(12, 0, 0).unwrap();
+        let late = noon + chrono::Duration::minutes(11 * 60 + 30);
+        let played_at = TimestampMicrosecondArray::from(vec![
+            noon.and_utc().timestamp_micros(),
+            late.and_utc().timestamp_micros(),
+        ]);
+        put_parquet(&store, &path, vec![("played_at", Arc::new(played_at) as ArrayRef)]).await;
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let engine = EngineProvider::new(pool, &EngineConfig::from_env()).with_object_store(Arc::new(store));
+
+        let sql = "SELECT CAST(date_trunc('day', played_at) AS VARCHAR) AS day, COUNT(*) AS plays \
+                   FROM royalty_data GROUP BY 1 ORDER BY 1";
+        let days = |timezone: Option<&str>| {
+            let scope = QueryScope::public_dataset(dataset_id).with_timezone(timezone.map(str::to_string));
+            let engine = engine.clone();
+            async move {
+                let batches = engine.execute_scoped_analytics(workspace_id, &scope, sql).await.unwrap();
+                let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+                let days = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+                (0..batch.num_rows()).map(|i| days.value(i)[..10].to_string()).collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(days(None).await, ["2026-06-30"]);
+        assert_eq!(days(Some("Africa/Lagos")).await, ["2026-06-30", "2026-07-01"]);
+    }
+
+    // Multi-threaded like the server: partitions run on spawned tasks that must not starve the caller
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_dropped_query_stops() {
+        let store = InMemory::new();
+        let (workspace_id, dataset_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+        let rows = Arc::new(Int64Array::from((0..2_000).collect::<Vec<i64>>())) as ArrayRef;
+        put_parquet(&store, &path, vec![("quantity", rows)]).await;
+        let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused").unwrap();
+        let engine = EngineProvider::new(pool, &EngineConfig::from_env()).with_object_store(Arc::new(store));
+
+        // Billions of grouped rows: far longer than the test, holding memory while it runs
+        let sql = "SELECT a.quantity * 2000 + b.quantity AS k, COUNT(*) FROM royalty_data a, royalty_data b, \
+                   royalty_data c GROUP BY k";
+        let scope = QueryScope::public_dataset(dataset_id);
+        let query = engine.execute_scoped_analytics(workspace_id, &scope, sql);
+        assert!(tokio::time::timeout(Duration::from_millis(500), query).await.is_err());
+        // Aborted partition tasks stop at their next await
+        let mut waited = 0;
+        while engine.rt_env.memory_pool.reserved() > 0 && waited < 20 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            waited += 1;
+        }
+        assert_eq!(engine.rt_env.memory_pool.reserved(), 0, "a dropped query releases its memory");
     }
 }
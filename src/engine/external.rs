@@ -0,0 +1,286 @@
+use crate::config::connection_allowed_networks;
+use crate::normalization::{InferenceOptions, infer_csv_schema};
+use crate::utils::error::DoubledeckerError;
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::execution::memory_pool::MemoryReservation;
+use futures::TryStreamExt;
+use ipnet::IpNet;
+use serde::Serialize;
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{Connection, PgConnection};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+/// Upper bound on rows pulled from an external table into a single query session.
+pub const MAX_EXTERNAL_ROWS: usize = 1_000_000;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Ranges an external connection cannot reach unless listed in `CONNECTION_ALLOWED_NETWORKS`:
+/// loopback, private, shared (CGNAT), link-local (including the cloud metadata service at
+/// 169.254.169.254), multicast and reserved addresses, and the local-use NAT64 prefix.
+const BLOCKED_NETWORKS: [&str; 17] = [
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.0.0.0/24",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/128",
+    "::1/128",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+    "64:ff9b:1::/48",
+];
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExternalTable {
+    pub schema: String,
+    pub name: String,
+}
+
+/// Open a single connection to an external Postgres database.
+/// The host is resolved once and every address it resolves to must be public (or in
+/// `CONNECTION_ALLOWED_NETWORKS`); the connection then goes to that vetted address, so a second
+/// lookup cannot send it elsewhere. Failures are reported without the driver's message, which
+/// would otherwise tell a caller what answered on an address.
+pub async fn connect_postgres(connection_string: &str) -> Result<PgConnection, DoubledeckerError> {
+    let options = PgConnectOptions::from_str(connection_string)
+        .map_err(|_| DoubledeckerError::BadRequest("Invalid connection string".to_string()))?;
+    let connect = async {
+        let options = pin_public_host(options).await?;
+        PgConnection::connect_with(&options).await.map_err(|e| external_error("Could not connect to external database", e))
+    };
+    tokio::time::timeout(CONNECT_TIMEOUT, connect)
+        .await
+        .map_err(|_| DoubledeckerError::BadRequest("Could not connect to external database".to_string()))?
+}
+
+/// `options` pointed at the address its host resolves to, once every resolved address is allowed.
+async fn pin_public_host(options: PgConnectOptions) -> Result<PgConnectOptions, DoubledeckerError> {
+    let blocked = || DoubledeckerError::BadRequest("Connections to private or internal addresses are not allowed".to_string());
+    if options.get_socket().is_some() {
+        return Err(blocked());
+    }
+    // IPv6 literals keep the brackets of the URL
+    let host = options.get_host().trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = tokio::net::lookup_host((host, options.get_port()))
+        .await
+        .map_err(|e| external_error("Could not connect to external database", e))?
+        .map(|a| a.ip())
+        .collect();
+    let allowed = connection_allowed_networks();
+    match addresses.first() {
+        Some(address) if addresses.iter().all(|a| is_allowed_address(*a, allowed)) => {
+            Ok(options.host(&address.to_string()))
+        }
+        Some(_) => Err(blocked()),
+        None => Err(DoubledeckerError::BadRequest("Could not connect to external database".to_string())),
+    }
+}
+
+/// Whether an external connection may reach `address`: any public address, or one inside `allowed`.
+pub fn is_allowed_address(address: IpAddr, allowed: &[IpNet]) -> bool {
+    let address = reached_address(address);
+    allowed.iter().any(|net| net.contains(&address))
+        || !BLOCKED_NETWORKS
+            .iter()
+            .filter_map(|net| net.parse::<IpNet>().ok())
+            .any(|net| net.contains(&address))
+}
+
+/// The address a connection to `address` ends up at: IPv6 addresses that embed an IPv4 one
+/// (IPv4-mapped, NAT64 `64:ff9b::/96`, 6to4 `2002::/16`) are checked as that IPv4 address.
+fn reached_address(address: IpAddr) -> IpAddr {
+    let IpAddr::V6(v6) = address.to_canonical() else {
+        return address.to_canonical();
+    };
+    let octets = v6.octets();
+    match v6.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => IpAddr::from([octets[12], octets[13], octets[14], octets[15]]),
+        [0x2002, ..] => IpAddr::from([octets[2], octets[3], octets[4], octets[5]]),
+        _ => IpAddr::V6(v6),
+    }
+}
+
+/// A `BadRequest` saying only what failed; the cause is logged for operators.
+fn external_error(message: &str, cause: impl std::fmt::Display) -> DoubledeckerError {
+    eprintln!("{}: {}", message, cause);
+    DoubledeckerError::BadRequest(message.to_string())
+}
+
+/// List user-visible base tables and views, skipping Postgres system schemas.
+pub async fn list_postgres_tables(
+    connection_string: &str,
+) -> Result<Vec<ExternalTable>, DoubledeckerError> {
+    let mut conn = connect_postgres(connection_string).await?;
+    let rows: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT table_schema::text, table_name::text
+        FROM information_schema.tables
+        WHERE table_schema NOT IN ('pg_catalog', 'information_schema')
+          AND table_type IN ('BASE TABLE', 'VIEW')
+        ORDER BY table_schema, table_name
+        "#,
+    )
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|e| external_error("Could not list external tables", e))?;
+    let _ = conn.close().await;
+
+    Ok(rows
+        .into_iter()
+        .map(|(schema, name)| ExternalTable { schema, name })
+        .collect())
+}
+
+/// Pull up to `max_rows` rows of `schema.table` into Arrow batches.
+/// Data is streamed out with `COPY ... TO STDOUT (FORMAT csv)` and typed with the same CSV
+/// inference used for uploaded reports, so no per-column Postgres type mapping is needed.
+/// The pulled data is charged to `reservation` as it arrives, so a table larger than the engine's
+/// memory pool fails with `QueryMemoryExceeded` instead of being buffered; the batches stay charged
+/// until the reservation is dropped.
+pub async fn fetch_postgres_table(
+    connection_string: &str,
+    schema: &str,
+    table: &str,
+    max_rows: usize,
+    inference: &InferenceOptions,
+    reservation: &mut MemoryReservation,
+) -> Result<(SchemaRef, Vec<RecordBatch>), DoubledeckerError> {
+    let mut conn = connect_postgres(connection_string).await?;
+
+    let exists: Option<(i32,)> = sqlx::query_as(
+        "SELECT 1 FROM information_schema.tables WHERE table_schema = $1 AND table_name = $2",
+    )
+    .bind(schema)
+    .bind(table)
+    .fetch_optional(&mut conn)
+    .await
+    .map_err(|e| external_error("Could not inspect external table", e))?;
+    if exists.is_none() {
+        return Err(DoubledeckerError::TableNotFound(format!("{}.{}", schema, table)));
+    }
+
+    let statement = format!(
+        "COPY (SELECT * FROM {}.{} LIMIT {}) TO STDOUT WITH (FORMAT csv, HEADER true)",
+        quote_pg_ident(schema),
+        quote_pg_ident(table),
+        max_rows
+    );
+    let mut csv_bytes = Vec::new();
+    {
+        let mut stream = conn
+            .copy_out_raw(&statement)
+            .await
+            .map_err(|e| external_error("External table export failed", e))?;
+        while let Some(chunk) = stream
+            .try_next()
+            .await
+            .map_err(|e| external_error("External table export failed", e))?
+        {
+            reservation.try_grow(chunk.len()).map_err(DoubledeckerError::from_query_execution)?;
+            csv_bytes.extend_from_slice(&chunk);
+        }
+    }
+    let _ = conn.close().await;
+
+    let schema_ref = Arc::new(infer_csv_schema(&csv_bytes, inference)?);
+    let csv_size = csv_bytes.len();
+    let cursor = std::io::Cursor::new(csv_bytes);
+
+    let reader = arrow_csv::ReaderBuilder::new(schema_ref.clone())
+        .with_header(true)
+        .build(cursor)
+        .map_err(|e| DoubledeckerError::Internal(format!("CSV reader error: {}", e)))?;
+    let mut batches = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| DoubledeckerError::Internal(format!("CSV batch read error: {}", e)))?;
+        reservation.try_grow(batch.get_array_memory_size()).map_err(DoubledeckerError::from_query_execution)?;
+        batches.push(batch);
+    }
+    // The CSV text went with the reader
+    reservation.shrink(csv_size);
+
+    Ok((schema_ref, batches))
+}
+
+fn quote_pg_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_internal_addresses_are_blocked() {
+        for address in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.5",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b:1::a00:1",
+            "2002:a9fe:a9fe::",
+            "2002:c0a8:101::1",
+        ] {
+            assert!(!is_allowed_address(ip(address), &[]), "{} should be blocked", address);
+        }
+        let public = ["8.8.8.8", "52.1.2.3", "2606:4700::1111", "::ffff:8.8.8.8", "64:ff9b::808:808", "2002:808:808::1"];
+        for address in public {
+            assert!(is_allowed_address(ip(address), &[]), "{} should be allowed", address);
+        }
+    }
+
+    #[test]
+    fn test_allowed_networks_open_private_ranges() {
+        let allowed = ["10.20.0.0/16".parse::<IpNet>().unwrap()];
+        assert!(is_allowed_address(ip("10.20.3.4"), &allowed));
+        assert!(!is_allowed_address(ip("10.21.3.4"), &allowed));
+        assert!(!is_allowed_address(ip("169.254.169.254"), &allowed));
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_internal_hosts_before_connecting() {
+        for conn_str in [
+            "postgres://user:pw@127.0.0.1:5432/db",
+            "postgres://user:pw@localhost/db",
+            "postgres://user:pw@169.254.169.254/db",
+            "postgres://user:pw@[::1]:5432/db",
+            "postgres://user:pw@example.com/db?host=/var/run/postgresql",
+        ] {
+            let err = connect_postgres(conn_str).await.unwrap_err();
+            assert_eq!(
+                err.message(),
+                "Bad request: Connections to private or internal addresses are not allowed",
+                "{}",
+                conn_str
+            );
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use datafusion::logical_expr::{Expr, JoinType, LogicalPlan};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use utoipa::ToSchema;
+
+/// Where one result column came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ColumnLineage {
+    pub name: String,
+    /// Table columns the values are computed from, as `table.column`, e.g. `royalty_data.net_revenue`
+    pub sources: Vec<String>,
+    /// Expressions applied on the way, outermost first, e.g. `sum(royalty_data.net_revenue)`; empty
+    /// when the column is copied unchanged
+    pub transformations: Vec<String>,
+}
+
+/// Provenance of each output column of an unoptimized logical plan, traced back through
+/// projections, aggregates, windows, joins, unions and subqueries to the table scans. Columns from
+/// literals or `VALUES` have no sources.
+pub fn column_lineage(plan: &LogicalPlan) -> Vec<ColumnLineage> {
+    plan.schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let mut trace = Trace::default();
+            trace.column(plan, index);
+            ColumnLineage {
+                name: field.name().clone(),
+                sources: trace.sources.into_iter().collect(),
+                transformations: trace.transformations,
+            }
+        })
+        .collect()
+}
+
+#[derive(Default)]
+struct Trace {
+    sources: BTreeSet<String>,
+    transformations: Vec<String>,
+}
+
+impl Trace {
+    /// Follow output column `index` of `plan` down to the tables it reads.
+    fn column(&mut self, plan: &LogicalPlan, index: usize) {
+        match plan {
+            LogicalPlan::TableScan(scan) => {
+                let schema = scan.source.schema();
+                let column = match &scan.projection {
+                    Some(projection) => projection.get(index).map(|i| schema.field(*i).name().clone()),
+                    None => schema.fields().get(index).map(|f| f.name().clone()),
+                };
+                if let Some(column) = column {
+                    self.sources.insert(format!("{}.{}", scan.table_name, column));
+                }
+            }
+            LogicalPlan::Projection(projection) => {
+                if let Some(expr) = projection.expr.get(index) {
+                    self.expr(expr, &projection.input);
+                }
+            }
+            LogicalPlan::Aggregate(aggregate) => {
+                let groups = aggregate.group_expr.len();
+                let expr = if index < groups {
+                    aggregate.group_expr.get(index)
+                } else {
+                    aggregate.aggr_expr.get(index - groups)
+                };
+                if let Some(expr) = expr {
+                    self.expr(expr, &aggregate.input);
+                }
+            }
+            LogicalPlan::Window(window) => {
+                let passed = window.input.schema().fields().len();
+                if index < passed {
+                    self.column(&window.input, index);
+                } else if let Some(expr) = window.window_expr.get(index - passed) {
+                    self.expr(expr, &window.input);
+                }
+            }
+            LogicalPlan::Join(join) => {
+                let left = join.left.schema().fields().len();
+                match join.join_type {
+                    JoinType::RightSemi | JoinType::RightAnti => self.column(&join.right, index),
+                    _ if index < left => self.column(&join.left, index),
+                    _ => self.column(&join.right, index - left),
+                }
+            }
+            LogicalPlan::Union(union) => {
+                for input in &union.inputs {
+                    self.column(input, index);
+                }
+            }
+            LogicalPlan::Values(_) | LogicalPlan::EmptyRelation(_) => {}
+            // Filters, sorts, limits, aliases and the like pass their input's columns through
+            other => {
+                let inputs = other.inputs();
+                if let [input] = inputs.as_slice()
+                    && input.schema().fields().len() == other.schema().fields().len()
+                {
+                    self.column(input, index);
+                }
+            }
+        }
+    }
+
+    /// Record `expr`, evaluated over `input`, and follow the columns it reads.
+    fn expr(&mut self, expr: &Expr, input: &LogicalPlan) {
+        let unaliased = expr.clone().unalias();
+        if !matches!(unaliased, Expr::Column(_)) {
+            let text = unaliased.to_string();
+            if !self.transformations.contains(&text) {
+                self.transformations.push(text);
+            }
+        }
+        let mut columns: Vec<_> = unaliased.column_refs().into_iter().cloned().collect();
+        columns.sort_by_key(|c| c.flat_name());
+        for column in columns {
+            if let Ok(index) = input.schema().index_of_column(&column) {
+                self.column(input, index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+    use datafusion::prelude::SessionContext;
+    use std::sync::Arc;
+
+    async fn lineage_of(sql: &str) -> Vec<ColumnLineage> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("platform", DataType::Utf8, false),
+            Field::new("net_revenue", DataType::Float64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["Spotify"])),
+                Arc::new(Float64Array::from(vec![1.5])),
+            ],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+        ctx.register_table("royalty_data", Arc::new(table)).unwrap();
+        column_lineage(ctx.sql(sql).await.unwrap().logical_plan())
+    }
+
+    #[tokio::test]
+    async fn test_column_lineage() {
+        let lineage = lineage_of(
+            "WITH totals AS (SELECT platform, SUM(net_revenue) AS total_revenue FROM royalty_data GROUP BY platform) \
+             SELECT platform AS store, total_revenue * 100 AS cents, 'USD' AS currency FROM totals",
+        )
+        .await;
+        assert_eq!(lineage.len(), 3);
+        assert_eq!(lineage[0].name, "store");
+        assert_eq!(lineage[0].sources, vec!["royalty_data.platform"]);
+        assert!(lineage[0].transformations.is_empty(), "{:?}", lineage[0]);
+        assert_eq!(lineage[1].sources, vec!["royalty_data.net_revenue"]);
+        assert_eq!(lineage[1].transformations.len(), 2, "{:?}", lineage[1]);
+        assert!(lineage[1].transformations[1].contains("sum(royalty_data.net_revenue)"), "{:?}", lineage[1]);
+        assert!(lineage[2].sources.is_empty());
+
+        let lineage = lineage_of(
+            "SELECT platform, net_revenue, SUM(net_revenue) OVER (ORDER BY platform) AS running FROM royalty_data",
+        )
+        .await;
+        assert_eq!(lineage[2].sources, vec!["royalty_data.net_revenue", "royalty_data.platform"]);
+    }
+}
@@ -0,0 +1,108 @@
+use datafusion::common::Result;
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryPool, MemoryReservation};
+use datafusion::physical_plan::ExecutionPlan;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use utoipa::ToSchema;
+
+/// Resources one query used, measured while it ran.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QueryMetrics {
+    /// Planning and execution time
+    pub elapsed_ms: i64,
+    pub output_rows: i64,
+    /// Bytes read from Parquet files, after column projection and row-group pruning
+    pub bytes_scanned: i64,
+    /// Most memory reserved by the query's operators at once
+    pub peak_memory_bytes: i64,
+}
+
+/// One query's view of the server-wide memory pool: reservations go to `inner`, so the shared limit
+/// and fair spilling still apply, while the query's own reserved bytes and their peak are counted.
+#[derive(Debug)]
+pub struct PeakMemoryPool {
+    inner: Arc<dyn MemoryPool>,
+    reserved: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl PeakMemoryPool {
+    pub fn new(inner: Arc<dyn MemoryPool>) -> Self {
+        Self { inner, reserved: AtomicUsize::new(0), peak: AtomicUsize::new(0) }
+    }
+
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    fn add(&self, additional: usize) {
+        let reserved = self.reserved.fetch_add(additional, Ordering::Relaxed) + additional;
+        self.peak.fetch_max(reserved, Ordering::Relaxed);
+    }
+}
+
+impl MemoryPool for PeakMemoryPool {
+    fn register(&self, consumer: &MemoryConsumer) {
+        self.inner.register(consumer)
+    }
+
+    fn unregister(&self, consumer: &MemoryConsumer) {
+        self.inner.unregister(consumer)
+    }
+
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.inner.grow(reservation, additional);
+        self.add(additional);
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.inner.shrink(reservation, shrink);
+        self.reserved.fetch_sub(shrink, Ordering::Relaxed);
+    }
+
+    fn try_grow(&self, reservation: &MemoryReservation, additional: usize) -> Result<()> {
+        self.inner.try_grow(reservation, additional)?;
+        self.add(additional);
+        Ok(())
+    }
+
+    /// Server-wide, like the pool it wraps, since operators compare it with the shared limit
+    fn reserved(&self) -> usize {
+        self.inner.reserved()
+    }
+}
+
+/// Bytes the Parquet scans of an executed plan read, summed over the whole plan tree.
+pub fn bytes_scanned(plan: &dyn ExecutionPlan) -> usize {
+    let own = plan
+        .metrics()
+        .and_then(|m| m.sum_by_name("bytes_scanned"))
+        .map_or(0, |v| v.as_usize());
+    own + plan.children().into_iter().map(|c| bytes_scanned(c.as_ref())).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::execution::memory_pool::GreedyMemoryPool;
+
+    #[test]
+    fn test_peak_memory_pool() {
+        let shared: Arc<dyn MemoryPool> = Arc::new(GreedyMemoryPool::new(100));
+        let mut other = MemoryConsumer::new("other").register(&shared);
+        other.try_grow(50).unwrap();
+
+        let pool = Arc::new(PeakMemoryPool::new(shared.clone()));
+        let query_pool: Arc<dyn MemoryPool> = pool.clone();
+        let mut reservation = MemoryConsumer::new("sort").register(&query_pool);
+        reservation.try_grow(30).unwrap();
+        reservation.shrink(20);
+        reservation.grow(15);
+        assert!(reservation.try_grow(30).is_err(), "the shared limit applies");
+        assert_eq!(shared.reserved(), 75);
+        assert_eq!(pool.peak(), 30, "only the query's own reservations count");
+        drop(reservation);
+        assert_eq!(shared.reserved(), 50);
+    }
+}
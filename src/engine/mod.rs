@@ -1,4 +1,16 @@
+pub mod cancel;
+pub mod decrypt;
+pub mod estimate;
 pub mod executor;
+pub mod external;
+pub mod lineage;
+pub mod metrics;
 pub mod udfs;
 
-pub use executor::EngineProvider;
+pub use estimate::{TableRead, table_reads};
+pub use executor::{
+    EngineProvider, ExecutedQuery, PREVIEW_RESULT_ROWS, PREVIEW_SAMPLE_ROWS, QueryScope, lookup_table_name,
+    referenced_tables,
+};
+pub use lineage::{ColumnLineage, column_lineage};
+pub use metrics::QueryMetrics;
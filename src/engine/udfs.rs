@@ -1,7 +1,179 @@
+use datafusion::arrow::array::{Array, ArrayRef, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::ScalarValue;
+use datafusion::logical_expr::{ColumnarValue, ScalarUDF, Volatility, create_udf};
 use datafusion::prelude::SessionContext;
+use crate::config::mask_hash_key;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// How a governed column is presented to callers without access to the raw values. Masked text
+/// columns stay text; masked numeric columns keep their type with every value null, so sums and
+/// averages over them still run and come out null rather than failing on strings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskMode {
+    /// Column is removed from the table entirely
+    Hide,
+    /// HMAC-SHA256 hex digest under the server's `MASK_HASH_KEY`; equal inputs stay
+    /// joinable/groupable, but values cannot be confirmed by hashing guesses. Redacts instead on a
+    /// server without the key
+    Hash,
+    /// Every non-null value becomes `[REDACTED]`
+    Redact,
+    /// Keep the last 4 characters (or first letter + domain for emails)
+    Partial,
+}
+
+impl MaskMode {
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            MaskMode::Hide => "HIDE",
+            MaskMode::Hash => "HASH",
+            MaskMode::Redact => "REDACT",
+            MaskMode::Partial => "PARTIAL",
+        }
+    }
+
+    pub fn from_db_str(s: &str) -> Self {
+        match s.to_uppercase().as_str() {
+            "HASH" => MaskMode::Hash,
+            "REDACT" => MaskMode::Redact,
+            "PARTIAL" => MaskMode::Partial,
+            _ => MaskMode::Hide,
+        }
+    }
+
+    /// SQL function implementing this mask; `None` for `Hide`, which drops the column.
+    pub fn udf_name(&self) -> Option<&'static str> {
+        match self {
+            MaskMode::Hide => None,
+            MaskMode::Hash => Some("mask_hash"),
+            MaskMode::Redact => Some("mask_redact"),
+            MaskMode::Partial => Some("mask_partial"),
+        }
+    }
+
+    /// Higher is stricter; used when one column carries different masks across datasets.
+    pub fn strictness(&self) -> u8 {
+        match self {
+            MaskMode::Partial => 1,
+            MaskMode::Hash => 2,
+            MaskMode::Redact => 3,
+            MaskMode::Hide => 4,
+        }
+    }
+}
+
+pub fn mask_hash(value: &str) -> String {
+    mask_hash_with(mask_hash_key(), value)
+}
+
+/// The digest under `key`, or `[REDACTED]` without one: a per-process key would make the digests
+/// differ between instances and restarts.
+fn mask_hash_with(key: Option<&[u8]>, value: &str) -> String {
+    match key {
+        Some(key) => hmac_sha256_hex(key, value),
+        None => mask_redact(value),
+    }
+}
+
+fn hmac_sha256_hex(key: &[u8], value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(value.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn mask_redact(_value: &str) -> String {
+    "[REDACTED]".to_string()
+}
+
+pub fn mask_partial(value: &str) -> String {
+    if let Some((local, domain)) = value.split_once('@')
+        && let Some(first) = local.chars().next()
+    {
+        return format!("{}***@{}", first, domain);
+    }
+    let chars: Vec<char> = value.chars().collect();
+    let keep = if chars.len() > 4 { 4 } else { 0 };
+    let masked = chars.len() - keep;
+    std::iter::repeat_n('*', masked)
+        .chain(chars[masked..].iter().copied())
+        .collect()
+}
+
+/// Wraps a `&str -> String` function as a null-preserving Utf8 scalar UDF.
+fn string_mask_udf(name: &str, mask: fn(&str) -> String) -> ScalarUDF {
+    create_udf(
+        name,
+        vec![DataType::Utf8],
+        DataType::Utf8,
+        Volatility::Immutable,
+        Arc::new(move |args: &[ColumnarValue]| {
+            let is_scalar = matches!(args[0], ColumnarValue::Scalar(_));
+            let input = args[0].clone().into_array(1)?;
+            let input = cast(&input, &DataType::Utf8)?;
+            let input = input
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .expect("cast to Utf8 yields a StringArray");
+            let output: StringArray = input.iter().map(|v| v.map(mask)).collect();
+            let output: ArrayRef = Arc::new(output);
+            if is_scalar {
+                Ok(ColumnarValue::Scalar(ScalarValue::try_from_array(&output, 0)?))
+            } else {
+                Ok(ColumnarValue::Array(output))
+            }
+        }),
+    )
+}
 
 /// Registers domain-specific royalty scalar UDFs and analytical functions into DataFusion
-pub fn register_music_udfs(_ctx: &SessionContext) {
+pub fn register_music_udfs(ctx: &SessionContext) {
+    // Data-governance masking functions, usable directly in SQL and applied to masked columns
+    ctx.register_udf(string_mask_udf("mask_hash", mask_hash));
+    ctx.register_udf(string_mask_udf("mask_redact", mask_redact));
+    ctx.register_udf(string_mask_udf("mask_partial", mask_partial));
+
     // Custom UDF registrations (e.g. APPLY_FX, NORMALIZE_PLATFORM) can be attached here
     eprintln!("✓ Music royalty analytical functions ready");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_functions() {
+        assert_eq!(mask_partial("123-45-6789"), "*******6789");
+        assert_eq!(mask_partial("jane.doe@example.com"), "j***@example.com");
+        assert_eq!(mask_partial("abc"), "***");
+        assert_eq!(mask_redact("anything"), "[REDACTED]");
+        let key = Some(&b"0123456789abcdef0123456789abcdef"[..]);
+        assert_eq!(mask_hash_with(key, "a"), mask_hash_with(key, "a"));
+        assert_ne!(mask_hash_with(key, "a"), mask_hash_with(key, "b"));
+        assert_eq!(mask_hash_with(key, "a").len(), 64);
+        assert_eq!(mask_hash_with(None, "a"), "[REDACTED]", "no hashing under a made-up key");
+    }
+
+    #[test]
+    fn test_mask_hash_is_keyed() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hmac_sha256_hex(b"Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(hmac_sha256_hex(b"one key", "a"), hmac_sha256_hex(b"another key", "a"));
+        // A plain SHA-256 of the value would let anyone confirm a guess
+        let key = Some(&b"0123456789abcdef0123456789abcdef"[..]);
+        assert_ne!(mask_hash_with(key, "a"), "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb");
+    }
+}
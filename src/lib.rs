@@ -0,0 +1,13 @@
+#![allow(dead_code)]
+
+pub mod config;
+pub mod db;
+pub mod engine;
+pub mod normalization;
+pub mod server;
+pub mod utils;
+pub mod workers;
+
+pub use config::ServerConfig;
+pub use server::build_app;
+pub use server::state::AppState;
@@ -3,6 +3,7 @@ use arrow::array::{
 };
 use arrow::compute::cast;
 use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use crate::normalization::headers::normalize_header_name;
 use crate::utils::error::DoubledeckerError;
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
@@ -43,9 +44,8 @@ pub enum DistributorSource {
 impl DistributorSource {
     /// Automatically detects distributor source from CSV content snippet
     pub fn detect_from_csv_bytes(bytes: &[u8]) -> Self {
-        let snippet = std::str::from_utf8(&bytes[..bytes.len().min(1024)])
-            .unwrap_or("")
-            .to_lowercase();
+        // Lossy: the 1KB cut may split a multi-byte character, which must not blank the whole snippet
+        let snippet = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_lowercase();
 
         if snippet.contains("sales type") || snippet.contains("total earned") {
             Self::TuneCore
@@ -69,7 +69,7 @@ impl DistributorSource {
         }
     }
 
-    pub fn to_adapter(&self) -> Box<dyn RoyaltyAdapter> {
+    pub fn to_adapter(self) -> Box<dyn RoyaltyAdapter> {
         match self {
             Self::DistroKid => Box::new(DistroKidAdapter),
             Self::TuneCore => Box::new(TunecoreAdapter),
@@ -88,12 +88,14 @@ impl DistributorSource {
     }
 }
 
+/// First column whose name equals or contains one of the aliases. Both sides are compared in
+/// `normalize_header_name` form, so raw (`Song Title`) and sanitized (`song_title`) headers match alike.
 fn find_column_idx(schema: &Schema, aliases: &[&str]) -> Option<usize> {
+    let aliases: Vec<String> = aliases.iter().map(|a| normalize_header_name(a)).collect();
     for (idx, field) in schema.fields().iter().enumerate() {
-        let name_lower = field.name().to_lowercase();
-        let name_trimmed = name_lower.trim();
-        for alias in aliases {
-            if name_trimmed == *alias || name_trimmed.contains(alias) {
+        let name = normalize_header_name(field.name());
+        for alias in &aliases {
+            if name == *alias || name.contains(alias.as_str()) {
                 return Some(idx);
             }
         }
@@ -102,18 +104,18 @@ fn find_column_idx(schema: &Schema, aliases: &[&str]) -> Option<usize> {
 }
 
 fn array_to_string_vec(arr: &ArrayRef, num_rows: usize) -> Vec<String> {
-    if let Ok(cast_arr) = cast(arr, &DataType::Utf8) {
-        if let Some(str_arr) = cast_arr.as_string_opt::<i32>() {
-            return (0..num_rows)
-                .map(|i| {
-                    if str_arr.is_null(i) {
-                        String::new()
-                    } else {
-                        str_arr.value(i).to_string()
-                    }
-                })
-                .collect();
-        }
+    if let Ok(cast_arr) = cast(arr, &DataType::Utf8)
+        && let Some(str_arr) = cast_arr.as_string_opt::<i32>()
+    {
+        return (0..num_rows)
+            .map(|i| {
+                if str_arr.is_null(i) {
+                    String::new()
+                } else {
+                    str_arr.value(i).to_string()
+                }
+            })
+            .collect();
     }
     vec![String::new(); num_rows]
 }
@@ -136,18 +138,18 @@ fn get_opt_string_array(
 }
 
 fn array_to_i64_vec(arr: &ArrayRef, num_rows: usize) -> Vec<i64> {
-    if let Ok(cast_arr) = cast(arr, &DataType::Int64) {
-        if let Some(int_arr) = cast_arr.as_any().downcast_ref::<Int64Array>() {
-            return (0..num_rows)
-                .map(|i| {
-                    if int_arr.is_null(i) {
-                        1
-                    } else {
-                        int_arr.value(i)
-                    }
-                })
-                .collect();
-        }
+    if let Ok(cast_arr) = cast(arr, &DataType::Int64)
+        && let Some(int_arr) = cast_arr.as_any().downcast_ref::<Int64Array>()
+    {
+        return (0..num_rows)
+            .map(|i| {
+                if int_arr.is_null(i) {
+                    1
+                } else {
+                    int_arr.value(i)
+                }
+            })
+            .collect();
     }
     let str_vec = array_to_string_vec(arr, num_rows);
     str_vec
@@ -160,9 +162,15 @@ fn array_to_i64_vec(arr: &ArrayRef, num_rows: usize) -> Vec<i64> {
 }
 
 fn parse_date_to_epoch_days(s: &str) -> i32 {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+    parse_date(s).map(|date| (date - epoch).num_days() as i32).unwrap_or(0)
+}
+
+/// Parse the date formats distributors report in, including month-only periods (first of the month).
+pub(crate) fn parse_date(s: &str) -> Option<NaiveDate> {
     let s_trimmed = s.trim();
     if s_trimmed.is_empty() {
-        return 0;
+        return None;
     }
 
     let formats = [
@@ -174,23 +182,21 @@ fn parse_date_to_epoch_days(s: &str) -> i32 {
         "%m/%Y",
     ];
 
-    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
-
     for fmt in &formats {
         if let Ok(date) = NaiveDate::parse_from_str(s_trimmed, fmt) {
-            return (date - epoch).num_days() as i32;
+            return Some(date);
         }
     }
 
     // Try adding day if Year-Month or Month-Year failed parse_from_str
     if let Ok(date) = NaiveDate::parse_from_str(&format!("{}-01", s_trimmed), "%Y-%m-%d") {
-        return (date - epoch).num_days() as i32;
+        return Some(date);
     }
     if let Ok(date) = NaiveDate::parse_from_str(&format!("01/{}", s_trimmed), "%d/%m/%Y") {
-        return (date - epoch).num_days() as i32;
+        return Some(date);
     }
 
-    0
+    None
 }
 
 fn parse_currency_to_decimal_mantissa(s: &str) -> i128 {
@@ -528,19 +534,19 @@ mod tests {
         let norm = adapter.normalize_batch(batch).unwrap();
 
         assert_eq!(norm.num_rows(), 1);
-        assert_eq!(norm.schema().fields().len(), 7);
+        assert_eq!(norm.schema().fields().len(), 12);
 
         let isrc_col = norm.column(0).as_any().downcast_ref::<StringArray>().unwrap();
         assert_eq!(isrc_col.value(0), "US1234567890");
 
-        let rev_col = norm.column(4).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        let rev_col = norm.column(9).as_any().downcast_ref::<Decimal128Array>().unwrap();
         // 12.345678 scaled by 10^9 is 12345678000
         assert_eq!(rev_col.value(0), 12_345_678_000);
 
-        let curr_col = norm.column(5).as_any().downcast_ref::<StringArray>().unwrap();
+        let curr_col = norm.column(10).as_any().downcast_ref::<StringArray>().unwrap();
         assert_eq!(curr_col.value(0), "USD");
 
-        let qty_col = norm.column(6).as_any().downcast_ref::<Int64Array>().unwrap();
+        let qty_col = norm.column(11).as_any().downcast_ref::<Int64Array>().unwrap();
         assert_eq!(qty_col.value(0), 1);
     }
 
@@ -565,7 +571,7 @@ mod tests {
         let isrc_col = norm.column(0).as_any().downcast_ref::<StringArray>().unwrap();
         assert_eq!(isrc_col.value(0), "TC9876543210");
 
-        let rev_col = norm.column(4).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        let rev_col = norm.column(9).as_any().downcast_ref::<Decimal128Array>().unwrap();
         // 99.99 scaled by 10^9 is 99990000000
         assert_eq!(rev_col.value(0), 99_990_000_000);
     }
@@ -0,0 +1,126 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Character encoding an uploaded file was detected in. Files are transcoded to UTF-8 before
+/// anything else reads them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceEncoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Single-byte Western European; bytes 0x80-0x9F were used as Windows-1252 punctuation
+    Windows1252,
+    /// Single-byte Western European with nothing in the 0x80-0x9F range
+    Latin1,
+}
+
+impl SourceEncoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceEncoding::Utf8 => "utf-8",
+            SourceEncoding::Utf16Le => "utf-16le",
+            SourceEncoding::Utf16Be => "utf-16be",
+            SourceEncoding::Windows1252 => "windows-1252",
+            SourceEncoding::Latin1 => "latin-1",
+        }
+    }
+}
+
+/// Bytes sniffed for BOM-less UTF-16.
+const UTF16_SNIFF_BYTES: usize = 1024;
+
+/// Guess the encoding of an uploaded file. A BOM wins; otherwise NUL bytes in every other position
+/// mean UTF-16, and anything that is not UTF-8 is read as Windows-1252, of which Latin-1 is a subset
+/// for printable text. A file with valid multi-byte UTF-8 next to a few stray bytes stays UTF-8: it
+/// is damaged, not in another encoding, and validation reports the bad rows.
+pub fn detect_encoding(bytes: &[u8]) -> SourceEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return match encoding {
+            e if e == UTF_16LE => SourceEncoding::Utf16Le,
+            e if e == UTF_16BE => SourceEncoding::Utf16Be,
+            _ => SourceEncoding::Utf8,
+        };
+    }
+
+    let head = &bytes[..bytes.len().min(UTF16_SNIFF_BYTES) & !1];
+    if !head.is_empty() {
+        let pairs = head.len() / 2;
+        let nul_at = |parity: usize| head.iter().skip(parity).step_by(2).filter(|b| **b == 0).count();
+        if nul_at(1) * 2 > pairs && nul_at(0) == 0 {
+            return SourceEncoding::Utf16Le;
+        }
+        if nul_at(0) * 2 > pairs && nul_at(1) == 0 {
+            return SourceEncoding::Utf16Be;
+        }
+    }
+
+    let mut multibyte_chars = 0usize;
+    let mut invalid = false;
+    for chunk in bytes.utf8_chunks() {
+        multibyte_chars += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+        invalid |= !chunk.invalid().is_empty();
+    }
+    if !invalid || multibyte_chars > 0 {
+        SourceEncoding::Utf8
+    } else if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) {
+        SourceEncoding::Windows1252
+    } else {
+        SourceEncoding::Latin1
+    }
+}
+
+/// Detect the file's encoding and return its contents as UTF-8, without a BOM. UTF-8 input is
+/// returned unchanged apart from the BOM.
+pub fn transcode_to_utf8(bytes: Vec<u8>) -> (Vec<u8>, SourceEncoding) {
+    let detected = detect_encoding(&bytes);
+    let encoding = match detected {
+        SourceEncoding::Utf8 => {
+            let bom = if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) { 3 } else { 0 };
+            let mut bytes = bytes;
+            bytes.drain(..bom);
+            return (bytes, detected);
+        }
+        SourceEncoding::Utf16Le => UTF_16LE,
+        SourceEncoding::Utf16Be => UTF_16BE,
+        SourceEncoding::Windows1252 | SourceEncoding::Latin1 => WINDOWS_1252,
+    };
+    // `decode` strips a matching BOM; without one the encoding we detected is used
+    let (text, _, _) = encoding.decode(&bytes);
+    (text.into_owned().into_bytes(), detected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcode_common_exports() {
+        // Excel "CSV" from a Windows machine: Windows-1252 with a euro sign and curly quotes
+        let (text, encoding) = transcode_to_utf8(b"Title,Earnings\n\x93Caf\xe9\x94,\x8012\n".to_vec());
+        assert_eq!(encoding, SourceEncoding::Windows1252);
+        assert_eq!(String::from_utf8(text).unwrap(), "Title,Earnings\n\u{201c}Caf\u{e9}\u{201d},\u{20ac}12\n");
+
+        let (text, encoding) = transcode_to_utf8(b"Title\nCaf\xe9\n".to_vec());
+        assert_eq!(encoding, SourceEncoding::Latin1);
+        assert_eq!(String::from_utf8(text).unwrap(), "Title\nCaf\u{e9}\n");
+
+        // Excel "Unicode text": UTF-16LE with a BOM
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("Title\nCafé\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let (text, encoding) = transcode_to_utf8(utf16);
+        assert_eq!(encoding, SourceEncoding::Utf16Le);
+        assert_eq!(String::from_utf8(text).unwrap(), "Title\nCafé\n");
+
+        let be: Vec<u8> = "Title\nCafé\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(transcode_to_utf8(be).1, SourceEncoding::Utf16Be);
+
+        let (text, encoding) = transcode_to_utf8("\u{feff}Title\nCafé\n".as_bytes().to_vec());
+        assert_eq!(encoding, SourceEncoding::Utf8);
+        assert_eq!(text, "Title\nCafé\n".as_bytes());
+
+        // Damaged UTF-8 is left for validation to report rather than re-read as Windows-1252
+        assert_eq!(detect_encoding(b"Caf\xc3\xa9,\xff\n"), SourceEncoding::Utf8);
+    }
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+
+/// A header of the uploaded file and the column name it was normalized to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct SourceColumn {
+    pub original: String,
+    pub normalized: String,
+}
+
+/// Canonical form of a header or alias: trimmed, lowercased, with every run of characters other
+/// than ASCII letters and digits collapsed into one underscore (`" Earnings (USD)"` -> `earnings_usd`).
+pub fn normalize_header_name(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            out.push(c);
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    if out.ends_with('_') {
+        out.pop();
+    }
+    out
+}
+
+/// Normalize a file's header row. Blank headers become `column_<position>` and repeated names get
+/// a numeric suffix (`amount`, `amount_2`), so every column ends up with a unique, queryable name.
+pub fn sanitize_headers<S: AsRef<str>>(headers: &[S]) -> Vec<SourceColumn> {
+    let bases: Vec<String> = headers
+        .iter()
+        .enumerate()
+        .map(|(idx, h)| match normalize_header_name(h.as_ref()) {
+            name if name.is_empty() => format!("column_{}", idx + 1),
+            name => name,
+        })
+        .collect();
+
+    // Names taken up front, so a generated `amount_2` can't clash with a real `amount_2` header
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut seen: HashSet<&str> = HashSet::new();
+    let mut duplicate = vec![false; bases.len()];
+    for (idx, base) in bases.iter().enumerate() {
+        if !seen.insert(base) {
+            duplicate[idx] = true;
+        } else {
+            taken.insert(base.clone());
+        }
+    }
+
+    headers
+        .iter()
+        .zip(&bases)
+        .zip(duplicate)
+        .map(|((original, base), is_duplicate)| {
+            let normalized = if is_duplicate {
+                (2..)
+                    .map(|n| format!("{}_{}", base, n))
+                    .find(|candidate| !taken.contains(candidate))
+                    .unwrap_or_else(|| base.clone())
+            } else {
+                base.clone()
+            };
+            taken.insert(normalized.clone());
+            SourceColumn {
+                original: original.as_ref().to_string(),
+                normalized,
+            }
+        })
+        .collect()
+}
+
+/// The column `name` refers to, matched exactly or, failing that, by normalized form, so `Platform`,
+/// `"Net Revenue"` and `net.revenue` find `platform` and `net_revenue`. `None` when nothing matches or
+/// the normalized form matches more than one column.
+pub fn resolve_column<'a, S: AsRef<str>>(name: &str, columns: &'a [S]) -> Option<&'a str> {
+    let columns = columns.iter().map(AsRef::as_ref);
+    if let Some(exact) = columns.clone().find(|c| *c == name) {
+        return Some(exact);
+    }
+    let wanted = normalize_header_name(name);
+    let mut matches = columns.filter(|c| normalize_header_name(c) == wanted);
+    match (matches.next(), matches.next()) {
+        (Some(only), None) => Some(only),
+        _ => None,
+    }
+}
+
+/// The column closest to an unknown `name`, for "did you mean" hints: within two edits, or a third
+/// of the name's length for longer names.
+pub fn closest_column<'a, S: AsRef<str>>(name: &str, columns: &'a [S]) -> Option<&'a str> {
+    let wanted = normalize_header_name(name);
+    let allowed = (wanted.chars().count() / 3).max(2);
+    columns
+        .iter()
+        .map(|c| (edit_distance(&wanted, &normalize_header_name(c.as_ref())), c.as_ref()))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between two strings, in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(headers: &[&str]) -> Vec<String> {
+        sanitize_headers(headers).into_iter().map(|c| c.normalized).collect()
+    }
+
+    #[test]
+    fn test_sanitize_headers() {
+        assert_eq!(normalize_header_name("  Earnings (USD) "), "earnings_usd");
+        assert_eq!(normalize_header_name("EAN/UPC"), "ean_upc");
+        assert_eq!(
+            normalized(&["Amount", "amount", " ", "Song Title", "amount_2", "AMOUNT"]),
+            vec!["amount", "amount_3", "column_3", "song_title", "amount_2", "amount_4"]
+        );
+        assert_eq!(sanitize_headers(&["Song Title"])[0].original, "Song Title");
+    }
+
+    #[test]
+    fn test_resolve_column() {
+        let columns = ["net_revenue", "platform", "Platform", "unit_price"];
+        assert_eq!(resolve_column("Platform", &columns), Some("Platform"));
+        assert_eq!(resolve_column("Net Revenue", &columns), Some("net_revenue"));
+        assert_eq!(resolve_column("unit.price", &columns), Some("unit_price"));
+        // `PLATFORM` normalizes to two columns and picks neither
+        assert_eq!(resolve_column("PLATFORM", &columns), None);
+        assert_eq!(resolve_column("revenue", &columns), None);
+
+        assert_eq!(closest_column("plattform", &columns), Some("platform"));
+        assert_eq!(closest_column("Net Revenu", &columns), Some("net_revenue"));
+        assert_eq!(closest_column("territory", &columns), None);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::utils::error::DoubledeckerError;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow_csv::reader::Format;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/// Rows sampled to infer CSV column types when a request does not say.
+pub const DEFAULT_INFER_MAX_RECORDS: usize = 1000;
+/// Deepest sample a request may ask for short of a full scan.
+pub const MAX_INFER_MAX_RECORDS: usize = 1_000_000;
+
+/// How much of a CSV is read to infer its column types. Sampling only the first rows mis-types
+/// columns that are empty or integer-only early on and change further down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InferenceOptions {
+    /// Rows sampled to infer column types (default 1000)
+    pub infer_max_records: Option<usize>,
+    /// Read every row to infer column types; overrides `infer_max_records`
+    #[serde(default)]
+    pub full_scan_inference: bool,
+}
+
+impl InferenceOptions {
+    /// Records to sample, or `None` to scan the whole file.
+    pub fn max_records(&self) -> Option<usize> {
+        if self.full_scan_inference {
+            None
+        } else {
+            Some(self.infer_max_records.unwrap_or(DEFAULT_INFER_MAX_RECORDS))
+        }
+    }
+}
+
+/// Column types inferred for an uploaded file, kept on the dataset so a full scan is not repeated
+/// when the file is converted again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct InferredSchema {
+    /// Whether every row was read; sampled schemas are not reused
+    pub full_scan: bool,
+    pub columns: Vec<InferredColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct InferredColumn {
+    /// Header as it appears in the file
+    pub name: String,
+    /// Arrow type, e.g. `Int64`, `Utf8`, `Date32`
+    pub data_type: String,
+}
+
+impl InferredSchema {
+    pub fn from_schema(schema: &Schema, full_scan: bool) -> Self {
+        Self {
+            full_scan,
+            columns: schema
+                .fields()
+                .iter()
+                .map(|f| InferredColumn { name: f.name().clone(), data_type: f.data_type().to_string() })
+                .collect(),
+        }
+    }
+
+    pub fn to_schema(&self) -> Result<Schema, DoubledeckerError> {
+        let fields = self
+            .columns
+            .iter()
+            .map(|c| {
+                let data_type = DataType::from_str(&c.data_type).map_err(|e| {
+                    DoubledeckerError::Internal(format!("Stored type of column {} is invalid: {}", c.name, e))
+                })?;
+                Ok(Field::new(&c.name, data_type, true))
+            })
+            .collect::<Result<Vec<_>, DoubledeckerError>>()?;
+        Ok(Schema::new(fields))
+    }
+}
+
+/// Infer the column types of a CSV with a header row.
+pub fn infer_csv_schema(csv_bytes: &[u8], options: &InferenceOptions) -> Result<Schema, DoubledeckerError> {
+    let mut cursor = std::io::Cursor::new(csv_bytes);
+    let (schema, _) = Format::default()
+        .with_header(true)
+        .infer_schema(&mut cursor, options.max_records())
+        .map_err(|e| DoubledeckerError::Internal(format!("CSV schema infer error: {}", e)))?;
+    Ok(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_scan_sees_late_values() {
+        let mut csv = String::from("upc,notes\n");
+        for i in 0..20 {
+            csv.push_str(&format!("{},\n", 100 + i));
+        }
+        csv.push_str("0-12345-67890-1,late note\n");
+
+        let sampled = infer_csv_schema(
+            csv.as_bytes(),
+            &InferenceOptions { infer_max_records: Some(10), full_scan_inference: false },
+        )
+        .unwrap();
+        assert_eq!(sampled.field(0).data_type(), &DataType::Int64);
+
+        let full = infer_csv_schema(
+            csv.as_bytes(),
+            &InferenceOptions { infer_max_records: Some(10), full_scan_inference: true },
+        )
+        .unwrap();
+        assert_eq!(full.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(full.field(1).data_type(), &DataType::Utf8);
+
+        let stored = InferredSchema::from_schema(&full, true);
+        assert_eq!(stored.to_schema().unwrap(), full);
+    }
+}
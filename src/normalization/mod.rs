@@ -1,7 +1,17 @@
 pub mod adapters;
+pub mod encoding;
+pub mod headers;
+pub mod inference;
+pub mod sniff;
+pub mod validation;
 
 #[allow(unused_imports)]
 pub use adapters::{
     unified_royalty_schema, CDBabyAdapter, DistroKidAdapter, DistributorSource, RoyaltyAdapter,
     SymphonicAdapter, TunecoreAdapter,
 };
+pub use encoding::{SourceEncoding, transcode_to_utf8};
+pub use headers::{SourceColumn, sanitize_headers};
+pub use inference::{InferenceOptions, InferredSchema, infer_csv_schema};
+pub use sniff::sniff_tabular;
+pub use validation::{ValidationReport, validate_csv};
@@ -0,0 +1,75 @@
+use crate::normalization::transcode_to_utf8;
+use crate::utils::error::DoubledeckerError;
+
+/// Bytes of the start of a file that are checked.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Control characters other than tab and line breaks tolerated per 1000 characters; text exports
+/// occasionally carry a stray one, binary files are full of them.
+const MAX_CONTROL_PER_MILLE: usize = 10;
+
+/// Leading bytes of formats people upload by mistake, with how to name them in the error.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "a PNG image"),
+    (b"\xFF\xD8\xFF", "a JPEG image"),
+    (b"GIF87a", "a GIF image"),
+    (b"GIF89a", "a GIF image"),
+    (b"%PDF-", "a PDF document"),
+    (b"PK\x03\x04", "a ZIP archive (Excel .xlsx workbooks are ZIP archives; export the sheet as CSV)"),
+    (b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "a legacy Office document (export the sheet as CSV)"),
+    (b"\x1F\x8B", "a gzip archive"),
+    (b"PAR1", "a Parquet file"),
+    (b"\x7FELF", "an executable"),
+];
+
+/// Check that a file (or its start) is CSV text before anything stores or converts it: not a
+/// known binary format, text in an encoding `transcode_to_utf8` reads, and starting with a header
+/// record that ends within the sniffed bytes. An empty file passes; validation reports it.
+pub fn sniff_tabular(bytes: &[u8]) -> Result<(), DoubledeckerError> {
+    if let Some((_, format)) = SIGNATURES.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return Err(DoubledeckerError::UnsupportedFileType(format!("The file is {}, not CSV", format)));
+    }
+
+    let (head, _) = transcode_to_utf8(bytes[..bytes.len().min(SNIFF_BYTES)].to_vec());
+    let text = String::from_utf8_lossy(&head);
+    let chars = text.chars().count();
+    let control = text.chars().filter(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r')).count();
+    if text.contains('\0') || control * 1000 > chars * MAX_CONTROL_PER_MILLE {
+        return Err(DoubledeckerError::UnsupportedFileType("The file is binary, not CSV text".to_string()));
+    }
+
+    // A header record that runs past the sniffed bytes has no line break in 8KB: not a table
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(head.as_slice());
+    let mut header = csv::ByteRecord::new();
+    let parsed = reader.read_byte_record(&mut header).is_ok();
+    if !parsed || (bytes.len() > SNIFF_BYTES && reader.position().byte() as usize >= head.len()) {
+        return Err(DoubledeckerError::UnsupportedFileType(format!(
+            "No CSV header row in the first {}KB of the file",
+            SNIFF_BYTES / 1024
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_files_are_rejected_and_text_passes() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        let err = sniff_tabular(&png).unwrap_err();
+        assert!(matches!(&err, DoubledeckerError::UnsupportedFileType(m) if m.contains("PNG")), "{:?}", err);
+        assert!(sniff_tabular(b"PK\x03\x04\x14\0\x06\0").is_err());
+
+        let junk: Vec<u8> = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        assert!(matches!(sniff_tabular(&junk), Err(DoubledeckerError::UnsupportedFileType(_))));
+
+        assert!(sniff_tabular(b"ISRC,Title,Earnings\nUS1,Song,1.50\n").is_ok());
+        assert!(sniff_tabular(b"").is_ok());
+        assert!(sniff_tabular(&vec![b'a'; 3 * SNIFF_BYTES]).is_err(), "one endless line is no table");
+        // UTF-16 exports are full of NUL bytes until transcoded
+        let utf16: Vec<u8> = "\u{FEFF}ISRC\tTitle\r\nUS1\tSong\r\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert!(sniff_tabular(&utf16).is_ok());
+    }
+}
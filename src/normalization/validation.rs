@@ -0,0 +1,213 @@
+use crate::normalization::adapters::parse_date;
+use crate::normalization::sanitize_headers;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Example rows kept per problem; counts cover the whole file.
+const MAX_SAMPLES: usize = 5;
+/// Share of a column's non-empty values that must parse as a type for the column to be treated as that type.
+const TYPE_MAJORITY: f64 = 0.9;
+/// Longest sample value echoed back in a report.
+const MAX_SAMPLE_CHARS: usize = 80;
+
+/// What an uploaded file looked like before conversion: rows the converter would misread or choke on.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationReport {
+    /// Data rows scanned, excluding the header
+    pub total_rows: u64,
+    /// Columns in the header
+    #[serde(default)]
+    pub total_columns: u64,
+    /// Rows whose field count differs from the header's
+    pub ragged_rows: RowIssues,
+    /// Rows containing bytes that are not valid UTF-8
+    pub encoding_errors: RowIssues,
+    /// Columns where most values share a type but some do not parse as it
+    pub column_issues: Vec<ColumnIssue>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RowIssues {
+    pub count: u64,
+    /// First few offending rows
+    pub samples: Vec<RowSample>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RowSample {
+    /// 1-based line number in the file (the header is line 1)
+    pub line: u64,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpectedType {
+    Number,
+    Date,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ColumnIssue {
+    /// Sanitized column name, as it is queried
+    pub column: String,
+    pub expected_type: ExpectedType,
+    /// Non-empty values that did not parse as `expected_type`
+    pub failed_count: u64,
+    pub samples: Vec<RowSample>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.ragged_rows.count == 0 && self.encoding_errors.count == 0 && self.column_issues.is_empty()
+    }
+
+    /// One-line summary, used as the error message when a strict upload is rejected.
+    pub fn summary(&self) -> String {
+        let failed_values: u64 = self.column_issues.iter().map(|c| c.failed_count).sum();
+        format!(
+            "{} rows with the wrong number of fields, {} rows with invalid UTF-8, {} values that do not match their column's type",
+            self.ragged_rows.count, self.encoding_errors.count, failed_values
+        )
+    }
+}
+
+impl RowIssues {
+    fn push(&mut self, line: u64, detail: impl FnOnce() -> String) {
+        self.count += 1;
+        if self.samples.len() < MAX_SAMPLES {
+            self.samples.push(RowSample { line, detail: detail() });
+        }
+    }
+}
+
+/// Per-column tallies for type inference.
+#[derive(Default)]
+struct ColumnStats {
+    non_empty: u64,
+    numbers: RowIssues,
+    dates: RowIssues,
+}
+
+/// Scan an uploaded CSV without converting it. The header row defines the expected field count;
+/// type checks only look at rows that have it.
+pub fn validate_csv(bytes: &[u8]) -> ValidationReport {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .from_reader(bytes);
+    let mut report = ValidationReport::default();
+
+    let headers: Vec<String> = match reader.byte_headers() {
+        Ok(headers) => headers.iter().map(|h| String::from_utf8_lossy(h).into_owned()).collect(),
+        Err(_) => return report,
+    };
+    report.total_columns = headers.len() as u64;
+    let mut stats: Vec<ColumnStats> = headers.iter().map(|_| ColumnStats::default()).collect();
+
+    let mut record = csv::ByteRecord::new();
+    while let Ok(true) = reader.read_byte_record(&mut record) {
+        report.total_rows += 1;
+        let line = record.position().map(|p| p.line()).unwrap_or(report.total_rows + 1);
+
+        if let Some(field) = record.iter().position(|f| std::str::from_utf8(f).is_err()) {
+            report.encoding_errors.push(line, || {
+                format!("column {}: {}", field + 1, sample(&String::from_utf8_lossy(&record[field])))
+            });
+        }
+        if record.len() != headers.len() {
+            report.ragged_rows.push(line, || {
+                format!("expected {} fields, found {}", headers.len(), record.len())
+            });
+            continue;
+        }
+
+        for (value, column) in record.iter().zip(stats.iter_mut()) {
+            let value = String::from_utf8_lossy(value);
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            column.non_empty += 1;
+            if !is_number(value) {
+                column.numbers.push(line, || sample(value));
+            }
+            if parse_date(value).is_none() {
+                column.dates.push(line, || sample(value));
+            }
+        }
+    }
+
+    for (column, stats) in sanitize_headers(&headers).into_iter().zip(stats) {
+        let candidates = [(ExpectedType::Number, stats.numbers), (ExpectedType::Date, stats.dates)];
+        if let Some((expected_type, failures)) = candidates.into_iter().find(|(_, failures)| {
+            let parsed = stats.non_empty - failures.count;
+            failures.count > 0 && parsed as f64 >= stats.non_empty as f64 * TYPE_MAJORITY
+        }) {
+            report.column_issues.push(ColumnIssue {
+                column: column.normalized,
+                expected_type,
+                failed_count: failures.count,
+                samples: failures.samples,
+            });
+        }
+    }
+
+    report
+}
+
+/// Whether a value reads as a number once currency symbols and thousands separators are dropped.
+fn is_number(value: &str) -> bool {
+    let cleaned: String = value
+        .trim_start_matches(['$', '€', '£'])
+        .chars()
+        .filter(|c| *c != ',')
+        .collect();
+    cleaned.parse::<f64>().is_ok()
+}
+
+fn sample(value: &str) -> String {
+    if value.chars().count() > MAX_SAMPLE_CHARS {
+        format!("{}…", value.chars().take(MAX_SAMPLE_CHARS).collect::<String>())
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_csv_reports_problem_rows() {
+        let mut csv = b"ISRC,Reporting Month,Earnings (USD)\n".to_vec();
+        for i in 0..20 {
+            csv.extend_from_slice(format!("US{:010},2026-06,{}.25\n", i, i).as_bytes());
+        }
+        csv.extend_from_slice(b"USX,2026-06,N/A\n");
+        csv.extend_from_slice(b"USY,2026-06\n");
+        csv.extend_from_slice(b"US\xff,not a month,1.00\n");
+
+        let report = validate_csv(&csv);
+
+        assert_eq!(report.total_rows, 23);
+        assert_eq!(report.ragged_rows.count, 1);
+        assert_eq!(report.ragged_rows.samples[0].line, 23);
+        assert_eq!(report.encoding_errors.count, 1);
+        assert_eq!(report.encoding_errors.samples[0].line, 24);
+
+        let columns: Vec<(&str, ExpectedType, u64)> = report
+            .column_issues
+            .iter()
+            .map(|c| (c.column.as_str(), c.expected_type, c.failed_count))
+            .collect();
+        assert_eq!(
+            columns,
+            vec![("reporting_month", ExpectedType::Date, 1), ("earnings_usd", ExpectedType::Number, 1)]
+        );
+        assert_eq!(report.column_issues[1].samples[0].detail, "N/A");
+        assert!(!report.is_clean());
+
+        assert!(validate_csv(b"a,b\n1,x\n2,y\n").is_clean());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::db::models::{
+    DatasetSize, ErrorTypeCount, PaginatedResponse, PaginationParams, SlowQuery, SlowQueryListParams, UserStorageUsage,
+    UserSuspension,
+};
+use crate::db::queries::{
+    count_queries_per_day, get_platform_totals, get_user_by_email, lift_user_suspension,
+    list_largest_datasets, list_slow_queries, list_storage_by_user, list_top_error_types, list_user_suspensions,
+    suspend_user,
+};
+use crate::server::demo::seed_demo_workspace;
+use crate::server::dtos::DeleteResponse;
+use crate::server::dtos::admin::*;
+use crate::server::middleware::AdminUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::server::state::AppState;
+use crate::utils::error::DoubledeckerError;
+use axum::Json;
+use axum::extract::{Path, State};
+use uuid::Uuid;
+
+/// Clear failed-login tracking and any lockout for an account, on the instance serving the request
+#[utoipa::path(
+    post,
+    path = "/admin/accounts/unlock",
+    request_body = UnlockAccountRequest,
+    responses(
+        (status = 200, description = "Account unlocked", body = UnlockAccountResponse),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn unlock_account_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<UnlockAccountRequest>,
+) -> Result<Json<UnlockAccountResponse>, DoubledeckerError> {
+    let was_locked = state.login_guard.unlock_account(&payload.email)?;
+    Ok(Json(UnlockAccountResponse {
+        email: payload.email,
+        was_locked,
+    }))
+}
+
+/// Platform totals and query volume over time
+#[utoipa::path(
+    get,
+    path = "/admin/metrics/overview",
+    params(AdminMetricsParams),
+    responses(
+        (status = 200, description = "Platform totals and daily query volume", body = AdminOverviewResponse),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn admin_overview_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AdminMetricsParams>,
+) -> Result<Json<AdminOverviewResponse>, DoubledeckerError> {
+    let (totals, queries_per_day) = tokio::try_join!(
+        get_platform_totals(&state.db_pool),
+        count_queries_per_day(&state.db_pool, None, params.effective_days()),
+    )?;
+    Ok(Json(AdminOverviewResponse {
+        totals,
+        queries_per_day,
+    }))
+}
+
+/// Uploads and storage per user, heaviest first
+#[utoipa::path(
+    get,
+    path = "/admin/metrics/storage",
+    params(AdminMetricsParams),
+    responses(
+        (status = 200, description = "Storage usage per user", body = Vec<UserStorageUsage>),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn admin_storage_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AdminMetricsParams>,
+) -> Result<Json<Vec<UserStorageUsage>>, DoubledeckerError> {
+    Ok(Json(list_storage_by_user(&state.db_pool, params.effective_limit()).await?))
+}
+
+/// Most frequent dataset processing errors
+#[utoipa::path(
+    get,
+    path = "/admin/metrics/errors",
+    params(AdminMetricsParams),
+    responses(
+        (status = 200, description = "Error types by number of occurrences", body = Vec<ErrorTypeCount>),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn admin_errors_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AdminMetricsParams>,
+) -> Result<Json<Vec<ErrorTypeCount>>, DoubledeckerError> {
+    Ok(Json(list_top_error_types(&state.db_pool, params.effective_limit()).await?))
+}
+
+/// Largest datasets by row count
+#[utoipa::path(
+    get,
+    path = "/admin/metrics/largest-datasets",
+    params(AdminMetricsParams),
+    responses(
+        (status = 200, description = "Datasets with the most rows", body = Vec<DatasetSize>),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn admin_largest_datasets_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<AdminMetricsParams>,
+) -> Result<Json<Vec<DatasetSize>>, DoubledeckerError> {
+    Ok(Json(list_largest_datasets(&state.db_pool, params.effective_limit()).await?))
+}
+
+/// Queries that ran at or over the slow-query threshold (`SLOW_QUERY_THRESHOLD_MS`), newest first,
+/// with the request they came from and their plan and timings
+#[utoipa::path(
+    get,
+    path = "/admin/slow-queries",
+    params(SlowQueryListParams),
+    responses(
+        (status = 200, description = "Slow queries", body = PaginatedSlowQueries),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn list_slow_queries_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<SlowQueryListParams>,
+) -> Result<Json<PaginatedResponse<SlowQuery>>, DoubledeckerError> {
+    let limit = params.pagination().effective_limit();
+    Ok(Json(list_slow_queries(&state.db_pool, params.workspace_id, params.cursor, limit).await?))
+}
+
+/// Load the bundled sample royalty statements and example pipelines into a new workspace owned by
+/// an existing account, so a fresh instance has data to query. Each call creates another workspace.
+#[utoipa::path(
+    post,
+    path = "/admin/demo-workspaces",
+    request_body = SeedDemoRequest,
+    responses(
+        (status = 200, description = "Demo workspace created; its datasets are queued for ingestion", body = DemoWorkspaceResponse),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse),
+        (status = 404, description = "No account with this email", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn seed_demo_workspace_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<SeedDemoRequest>,
+) -> Result<Json<DemoWorkspaceResponse>, DoubledeckerError> {
+    let owner = get_user_by_email(&state.db_pool, payload.email.trim()).await?;
+    Ok(Json(seed_demo_workspace(&state, owner.id).await?))
+}
+
+/// Suspend an account, blocking all of its authenticated requests immediately
+#[utoipa::path(
+    post,
+    path = "/admin/users/{user_id}/suspension",
+    params(("user_id" = Uuid, Path, description = "User to suspend")),
+    request_body = SuspendUserRequest,
+    responses(
+        (status = 200, description = "Account suspended", body = UserSuspension),
+        (status = 400, description = "Admins cannot suspend themselves", body = crate::server::dtos::common::ErrorResponse),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse),
+        (status = 404, description = "User not found", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn suspend_user_handler(
+    AdminUser(admin): AdminUser,
+    Path(user_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<SuspendUserRequest>,
+) -> Result<Json<UserSuspension>, DoubledeckerError> {
+    if user_id == admin.user_id {
+        return Err(DoubledeckerError::BadRequest("You cannot suspend your own account".to_string()));
+    }
+    let suspension = suspend_user(&state.db_pool, user_id, payload.reason.trim(), admin.user_id).await?;
+    Ok(Json(suspension))
+}
+
+/// Lift an account's suspension
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{user_id}/suspension",
+    params(("user_id" = Uuid, Path, description = "Suspended user")),
+    responses(
+        (status = 200, description = "Suspension lifted", body = DeleteResponse),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse),
+        (status = 404, description = "User is not suspended", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn lift_suspension_handler(
+    _admin: AdminUser,
+    Path(user_id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    if !lift_user_suspension(&state.db_pool, user_id).await? {
+        return Err(DoubledeckerError::NotFound("User is not suspended".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Suspension lifted".to_string(),
+    }))
+}
+
+/// Currently suspended accounts, most recent first
+#[utoipa::path(
+    get,
+    path = "/admin/suspensions",
+    params(PaginationParams),
+    responses(
+        (status = 200, description = "Suspended accounts", body = PaginatedUserSuspensions),
+        (status = 403, description = "Caller is not a platform admin", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "admin"
+)]
+pub async fn list_suspensions_handler(
+    _admin: AdminUser,
+    State(state): State<AppState>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+) -> Result<Json<PaginatedResponse<UserSuspension>>, DoubledeckerError> {
+    let limit = pagination.effective_limit();
+    Ok(Json(list_user_suspensions(&state.db_pool, pagination.cursor, limit).await?))
+}
@@ -0,0 +1,374 @@
+use crate::db::models::{
+    AlertAggregate, AlertState, NotifyChannel, PaginationParams, Pipeline, ScheduleAlert, ThresholdOp, WorkspaceRole,
+};
+use crate::db::queries::{
+    AlertSettings, create_alert, delete_alert, get_alert, get_schedule, list_alerts, list_enabled_schedule_alerts,
+    record_alert_evaluation, update_alert,
+};
+use crate::server::dtos::alerts::*;
+use crate::server::dtos::analytics::AnalyticsQueryResponse;
+use crate::server::dtos::DeleteResponse;
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+use crate::utils::email::EmailSender;
+use crate::utils::error::{DoubledeckerError, FieldError};
+use crate::utils::webhooks::{post_webhook, webhook_payload};
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::Json;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// The aggregate an alert compares: the row count, or the sum, minimum, maximum or mean of a
+/// column's numbers (decimals arrive as numeric strings). `None` when a column has no numbers to
+/// take a minimum, maximum or mean of; the sum of none is 0.
+pub fn aggregate_result(
+    result: &AnalyticsQueryResponse,
+    aggregate: AlertAggregate,
+    column: Option<&str>,
+) -> Result<Option<f64>, DoubledeckerError> {
+    if aggregate == AlertAggregate::RowCount {
+        return Ok(Some(result.rows.len() as f64));
+    }
+    let column = column.unwrap_or_default();
+    let index = result.columns.iter().position(|c| c == column).ok_or_else(|| {
+        DoubledeckerError::BadRequest(format!("Alert column '{}' is not in the pipeline's result", column))
+    })?;
+    let values: Vec<f64> = result
+        .rows
+        .iter()
+        .filter_map(|row| match row.get(index) {
+            Some(Value::Number(n)) => n.as_f64(),
+            Some(Value::String(s)) => s.trim().parse().ok(),
+            _ => None,
+        })
+        .collect();
+    Ok(match aggregate {
+        AlertAggregate::Sum => Some(values.iter().sum()),
+        _ if values.is_empty() => None,
+        AlertAggregate::Min => values.iter().copied().reduce(f64::min),
+        AlertAggregate::Max => values.iter().copied().reduce(f64::max),
+        AlertAggregate::Avg => Some(values.iter().sum::<f64>() / values.len() as f64),
+        AlertAggregate::RowCount => unreachable!("handled above"),
+    })
+}
+
+fn describe_condition(alert: &ScheduleAlert, op: ThresholdOp) -> String {
+    let subject = match alert.column_name.as_deref() {
+        Some(column) if alert.aggregate != AlertAggregate::RowCount.as_str() => format!("{}({})", alert.aggregate, column),
+        _ => "Row count".to_string(),
+    };
+    format!("{} {} {}", subject, op.symbol(), alert.threshold)
+}
+
+/// Email and post the alert's change of state to everyone it notifies.
+async fn notify_alert(
+    http: &reqwest::Client,
+    email: Option<&EmailSender>,
+    alert: &ScheduleAlert,
+    pipeline: &Pipeline,
+    state: AlertState,
+    condition: &str,
+    value: Option<f64>,
+) -> Result<(), DoubledeckerError> {
+    let title = match state {
+        AlertState::Firing => format!("Alert firing: {}", alert.name),
+        AlertState::Ok => format!("Alert resolved: {}", alert.name),
+    };
+    let value = value.map(|v| v.to_string()).unwrap_or_else(|| "empty".to_string());
+    let text = format!("{} on pipeline \"{}\" (value {}).", condition, pipeline.name, value);
+
+    let mut failures = Vec::new();
+    if !alert.emails.is_empty() {
+        let sent = match email {
+            Some(sender) => sender.send(&alert.emails, &title, &text).await,
+            None => Err(DoubledeckerError::Internal("Email is not configured on this server".to_string())),
+        };
+        failures.extend(sent.err().map(|e| e.message()));
+    }
+    if let (Some(channel), Some(encrypted)) = (alert.channel.as_deref(), alert.encrypted_webhook_url.as_deref()) {
+        let channel = NotifyChannel::from_str(channel)
+            .map_err(|_| DoubledeckerError::Internal(format!("Unknown channel '{}'", channel)))?;
+        let url = decrypt_secret(encrypted)?;
+        let posted = post_webhook(http, &url, &webhook_payload(channel, &title, &text)).await;
+        failures.extend(posted.err().map(|e| e.message()));
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(DoubledeckerError::Internal(failures.join("; ")))
+    }
+}
+
+/// Evaluate a schedule's enabled alerts against the result of its run. An alert notifies only when
+/// its state changes. When notifying fails its state is left as it was, so the next run tries again.
+pub async fn evaluate_alerts(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    email: Option<&EmailSender>,
+    schedule_id: Uuid,
+    pipeline: &Pipeline,
+    result: &AnalyticsQueryResponse,
+) -> Result<Vec<AlertEvaluation>, DoubledeckerError> {
+    let mut evaluations = Vec::new();
+    for alert in list_enabled_schedule_alerts(pool, schedule_id).await? {
+        let aggregate = AlertAggregate::from_str(&alert.aggregate)
+            .map_err(|_| DoubledeckerError::Internal(format!("Unknown aggregate '{}'", alert.aggregate)))?;
+        let op = ThresholdOp::from_str(&alert.op)
+            .map_err(|_| DoubledeckerError::Internal(format!("Unknown operator '{}'", alert.op)))?;
+
+        let (state, value, notified, error) = match aggregate_result(result, aggregate, alert.column_name.as_deref()) {
+            Ok(value) => {
+                let state = if value.is_some_and(|v| op.holds(v, alert.threshold)) {
+                    AlertState::Firing
+                } else {
+                    AlertState::Ok
+                };
+                let changed = state.as_str() != alert.state;
+                if changed && (state == AlertState::Firing || alert.notify_on_resolve) {
+                    let condition = describe_condition(&alert, op);
+                    match notify_alert(http, email, &alert, pipeline, state, &condition, value).await {
+                        Ok(()) => (state.as_str(), value, true, None),
+                        Err(e) => (alert.state.as_str(), value, false, Some(e.message())),
+                    }
+                } else {
+                    (state.as_str(), value, false, None)
+                }
+            }
+            Err(e) => (alert.state.as_str(), None, false, Some(e.message())),
+        };
+
+        record_alert_evaluation(pool, alert.id, state, value, notified, error.as_deref()).await?;
+        evaluations.push(AlertEvaluation {
+            alert_id: alert.id,
+            state: state.to_string(),
+            value,
+            notified,
+            error,
+        });
+    }
+    Ok(evaluations)
+}
+
+/// Check the request and encrypt its webhook URL, falling back to `current`'s URL when none was sent.
+fn alert_settings<'a>(
+    payload: &'a AlertRequest,
+    encrypted_webhook_url: &'a mut String,
+    current: Option<&ScheduleAlert>,
+) -> Result<AlertSettings<'a>, DoubledeckerError> {
+    if !payload.emails.is_empty() && !EmailSender::is_configured() {
+        return Err(DoubledeckerError::BadRequest(
+            "Email is not configured on this server; notify a webhook channel instead".to_string(),
+        ));
+    }
+    if let Some(channel) = payload.channel {
+        let current_url = current
+            .filter(|c| c.channel.as_deref() == Some(channel.as_str()))
+            .and_then(|c| c.encrypted_webhook_url.clone());
+        *encrypted_webhook_url = match (payload.webhook_url.as_deref(), current_url) {
+            (Some(url), _) => encrypt_secret(url.trim())?,
+            (None, Some(current_url)) => current_url,
+            (None, None) => {
+                return Err(DoubledeckerError::Validation(vec![FieldError::new(
+                    "webhook_url",
+                    "required",
+                    "required with a new channel",
+                )]));
+            }
+        };
+    }
+    Ok(AlertSettings {
+        name: payload.name.trim(),
+        aggregate: payload.aggregate.as_str(),
+        column_name: payload.column.as_deref().map(str::trim).filter(|_| payload.aggregate != AlertAggregate::RowCount),
+        op: payload.op.as_str(),
+        threshold: payload.threshold,
+        emails: &payload.emails,
+        channel: payload.channel.map(NotifyChannel::as_str),
+        encrypted_webhook_url: payload.channel.map(|_| encrypted_webhook_url.as_str()),
+        notify_on_resolve: payload.notify_on_resolve,
+        enabled: payload.enabled,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/alerts",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID")
+    ),
+    request_body = AlertRequest,
+    responses(
+        (status = 200, description = "Alert created; it is evaluated after each run of the schedule", body = ScheduleAlert),
+        (status = 400, description = "Email is not configured, or the schedule has an alert with this name"),
+        (status = 404, description = "Schedule not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn create_alert_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<AlertRequest>,
+) -> Result<Json<ScheduleAlert>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+
+    let mut encrypted = String::new();
+    let settings = alert_settings(&payload, &mut encrypted, None)?;
+    let alert = create_alert(&state.db_pool, workspace_id, schedule.id, &settings, auth_user.user_id).await?;
+    Ok(Json(alert))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/alerts",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Alerts of the schedule with their current state", body = PaginatedScheduleAlerts),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match"),
+        (status = 404, description = "Schedule not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn list_alerts_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+
+    let limit = pagination.effective_limit();
+    let alerts = list_alerts(&state.db_pool, schedule.id, pagination.cursor, limit).await?;
+    etagged_json(&if_none_match, &alerts)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/alerts/{alert_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID"),
+        ("alert_id" = Uuid, Path, description = "Alert ID")
+    ),
+    responses(
+        (status = 200, description = "Alert with its current state", body = ScheduleAlert),
+        (status = 404, description = "Schedule or alert not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn get_alert_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id, alert_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<ScheduleAlert>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+
+    let alert = get_alert(&state.db_pool, schedule.id, alert_id).await?;
+    Ok(Json(alert))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/alerts/{alert_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID"),
+        ("alert_id" = Uuid, Path, description = "Alert ID")
+    ),
+    request_body = AlertRequest,
+    responses(
+        (status = 200, description = "Alert replaced and reset to OK", body = ScheduleAlert),
+        (status = 404, description = "Schedule or alert not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn update_alert_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id, alert_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<AlertRequest>,
+) -> Result<Json<ScheduleAlert>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+
+    let current = get_alert(&state.db_pool, schedule.id, alert_id).await?;
+    let mut encrypted = String::new();
+    let settings = alert_settings(&payload, &mut encrypted, Some(&current))?;
+    let alert = update_alert(&state.db_pool, schedule.id, alert_id, &settings).await?;
+    Ok(Json(alert))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/alerts/{alert_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID"),
+        ("alert_id" = Uuid, Path, description = "Alert ID")
+    ),
+    responses(
+        (status = 200, description = "Alert deleted", body = DeleteResponse),
+        (status = 404, description = "Schedule not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn delete_alert_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id, alert_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+
+    let deleted = delete_alert(&state.db_pool, schedule.id, alert_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Alert not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Alert deleted successfully".to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregate_result() {
+        let result = AnalyticsQueryResponse {
+            columns: vec!["store".to_string(), "errors".to_string()],
+            rows: vec![json!(["Spotify", 3]), json!(["Apple", "4.5"]), json!(["Tidal", null])],
+            data: None,
+            metrics: None,
+            preview: false,
+        };
+        let errors = Some("errors");
+        assert_eq!(aggregate_result(&result, AlertAggregate::RowCount, None).unwrap(), Some(3.0));
+        assert_eq!(aggregate_result(&result, AlertAggregate::Sum, errors).unwrap(), Some(7.5));
+        assert_eq!(aggregate_result(&result, AlertAggregate::Min, errors).unwrap(), Some(3.0));
+        assert_eq!(aggregate_result(&result, AlertAggregate::Max, errors).unwrap(), Some(4.5));
+        assert_eq!(aggregate_result(&result, AlertAggregate::Avg, errors).unwrap(), Some(3.75));
+        assert!(aggregate_result(&result, AlertAggregate::Sum, Some("missing")).is_err());
+
+        let empty = AnalyticsQueryResponse { columns: result.columns.clone(), rows: vec![], data: None, metrics: None, preview: false };
+        assert_eq!(aggregate_result(&empty, AlertAggregate::Sum, errors).unwrap(), Some(0.0));
+        assert_eq!(aggregate_result(&empty, AlertAggregate::Max, errors).unwrap(), None);
+    }
+}
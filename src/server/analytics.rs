@@ -1,18 +1,32 @@
-use crate::db::models::{PaginatedResponse, PaginationParams, QueryHistoryRecord, WorkspaceRole};
-use crate::db::queries::{get_query_history_by_id, list_query_history, record_query_history};
-use crate::server::extractors::verify_workspace_access;
+use crate::db::models::{Dataset, DatasetStatus, PaginatedResponse, PaginationParams, QueryHistoryRecord, WorkspaceRole};
+use crate::db::queries::{
+    get_datasets_by_ids, get_query_history_by_id, list_query_history, list_workspace_datasets, record_dataset_usage,
+    record_query_history, record_slow_query,
+};
+use crate::engine::{EngineProvider, ExecutedQuery, QueryMetrics, QueryScope, lookup_table_name, referenced_tables};
+use crate::server::extractors::{
+    check_ready_datasets, expand_segments, query_scope_for_role, user_timezone, verify_workspace_access, with_lookup_datasets,
+};
+use crate::server::idempotency::{IdempotencyKey, request_hash, run_idempotent};
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
+use crate::utils::charts::{chart_data, render_png, render_svg};
 use crate::utils::error::DoubledeckerError;
-use crate::utils::helpers::{parse_batch_to_json, query_response_to_csv};
+use crate::utils::helpers::{
+    batches_to_parquet, batches_to_xlsx, localize_timestamps, parse_batch_to_json, query_response_to_csv, render_query_results,
+};
+use crate::utils::llm::reply_json;
 use axum::extract::{Path, Query, State};
 use axum::http::header;
 use axum::response::Response;
 use axum::Json;
 use crate::server::dtos::analytics::*;
 use datafusion::arrow::array::{Array, Decimal128Array, Float64Array, Int64Array, RecordBatch, StringArray};
+use datafusion::arrow::datatypes::Schema;
+use serde::Serialize;
+use sqlx::PgPool;
 use std::hash::{Hash, Hasher};
-use std::time::Instant;
 use uuid::Uuid;
 
 #[utoipa::path(
@@ -23,7 +37,8 @@ use uuid::Uuid;
     ),
     request_body = AnalyticsQueryRequest,
     responses(
-        (status = 200, description = "Analytics query executed", body = AnalyticsQueryResponse)
+        (status = 200, description = "Analytics query executed", body = AnalyticsQueryResponse),
+        (status = 429, description = "Too many concurrent queries for this user", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "analytics"
 )]
@@ -31,73 +46,556 @@ pub async fn execute_query_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<AnalyticsQueryRequest>,
+    ValidatedJson(payload): ValidatedJson<AnalyticsQueryRequest>,
 ) -> Result<Json<AnalyticsQueryResponse>, DoubledeckerError> {
-    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role)
+        .await?
+        .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+    let payload = expand_segments(&state.db_pool, workspace_id, payload).await?;
+    let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, payload.lookup_dataset_ids()).await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
 
-    let start_time = Instant::now();
     let sql = payload.to_safe_sql()?;
-    let batches = state
-        .engine
-        .execute_royalty_analytics(workspace_id, &sql)
-        .await?;
-    let elapsed_ms = start_time.elapsed().as_millis() as i64;
-    let response = parse_batch_to_json(batches).await?;
+    let (batches, metrics) = execute_analytics(&state, workspace_id, auth_user.user_id, &scope, &payload, &sql).await?;
+    let bytes_processed: usize = batches.iter().map(|b| b.get_array_memory_size()).sum();
+    let mut response = render_query_results(batches, scope.timezone.as_deref(), payload.layout).await?;
+    response.metrics = Some(metrics);
+    response.preview = payload.preview;
+    // Previews are drafts of a query; only full runs count as history and dataset usage
+    if payload.preview {
+        return Ok(Json(response));
+    }
 
-    let row_count = response.rows.len() as i64;
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
     sql.hash(&mut hasher);
     let query_id = format!("q_{:016x}", hasher.finish());
 
+    let tables = referenced_tables(&sql);
     let _ = record_query_history(
         &state.db_pool,
         workspace_id,
         Some(auth_user.user_id),
         &query_id,
         &sql,
-        row_count,
-        elapsed_ms,
+        bytes_processed as i64,
+        &metrics,
+        &tables,
     )
     .await;
+    if tables.iter().any(|t| t == "royalty_data") {
+        let dataset_ids = scope.dataset_ids.as_deref();
+        let _ = record_dataset_usage(&state.db_pool, workspace_id, dataset_ids, Some(auth_user.user_id)).await;
+    }
 
     Ok(Json(response))
 }
 
+/// Result columns of a query, possibly unfinished, without running it. Query builders use this to
+/// suggest the columns available after grouping, binning or earlier steps.
 #[utoipa::path(
     post,
-    path = "/api/workspaces/{workspace_id}/analytics/download",
+    path = "/api/workspaces/{workspace_id}/analytics/columns",
     params(
         ("workspace_id" = Uuid, Path, description = "Workspace ID")
     ),
     request_body = AnalyticsQueryRequest,
     responses(
-        (status = 200, description = "Download query result as CSV", content_type = "text/csv")
+        (status = 200, description = "Columns the query would return", body = QueryColumnsResponse),
+        (status = 400, description = "The query does not plan", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "analytics"
+)]
+pub async fn query_columns_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<AnalyticsQueryRequest>,
+) -> Result<Json<QueryColumnsResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role).await?;
+    let payload = expand_segments(&state.db_pool, workspace_id, payload).await?;
+    let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, payload.lookup_dataset_ids()).await?;
+
+    let sql = payload.to_safe_sql()?;
+    let schema = match state.engine.plan_schema(workspace_id, &scope, &sql).await {
+        Ok(schema) => schema,
+        Err(err) => return Err(locate_step_failure(&state, workspace_id, &scope, &payload, err).await),
+    };
+    Ok(Json(QueryColumnsResponse { columns: result_columns(&schema) }))
+}
+
+/// Estimate what a query would scan before running it, from the stored size and row count of the
+/// datasets it reads and the columns and `LIMIT` its optimized plan pushes into each scan, so a UI
+/// can warn before a full scan of a large workspace.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/analytics/estimate",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = AnalyticsQueryRequest,
+    responses(
+        (status = 200, description = "Estimated bytes and rows scanned", body = QueryEstimateResponse),
+        (status = 400, description = "The query does not plan", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "analytics"
+)]
+pub async fn estimate_query_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<AnalyticsQueryRequest>,
+) -> Result<Json<QueryEstimateResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role).await?;
+    let payload = expand_segments(&state.db_pool, workspace_id, payload).await?;
+    let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, payload.lookup_dataset_ids()).await?;
+
+    let sql = payload.to_safe_sql()?;
+    let reads = match state.engine.plan_table_reads(workspace_id, &scope, &sql).await {
+        Ok(reads) => reads,
+        Err(err) => return Err(locate_step_failure(&state, workspace_id, &scope, &payload, err).await),
+    };
+
+    // Stored (bytes, rows) of the datasets behind each table; other tables are small and in memory
+    let workspace_data = match &scope.dataset_ids {
+        Some(ids) => get_datasets_by_ids(&state.db_pool, workspace_id, ids).await?,
+        None => list_workspace_datasets(&state.db_pool, workspace_id).await?,
+    };
+    let totals = |datasets: &[Dataset]| {
+        datasets
+            .iter()
+            .filter(|d| d.status == DatasetStatus::Ready.as_str())
+            .fold((0, 0), |(bytes, rows), d| (bytes + d.file_size_bytes, rows + d.row_count))
+    };
+    let mut stored = vec![("royalty_data".to_string(), totals(&workspace_data))];
+    if !scope.lookup_dataset_ids.is_empty() {
+        for lookup in get_datasets_by_ids(&state.db_pool, workspace_id, &scope.lookup_dataset_ids).await? {
+            stored.push((lookup_table_name(lookup.id), totals(std::slice::from_ref(&lookup))));
+        }
+    }
+
+    let tables: Vec<TableScanEstimate> = reads
+        .into_iter()
+        .map(|read| {
+            let (bytes, rows) = stored.iter().find(|(t, _)| *t == read.table).map_or((0, 0), |(_, s)| *s);
+            // Parquet reads only the projected columns, and a pushed-down LIMIT stops the scan early
+            let mut fraction = read.columns_read as f64 / read.columns_total.max(1) as f64;
+            let mut rows_scanned = rows;
+            if let Some(fetch) = read.fetch.filter(|f| (*f as i64) < rows) {
+                fraction *= fetch as f64 / rows as f64;
+                rows_scanned = fetch as i64;
+            }
+            TableScanEstimate {
+                table: read.table,
+                columns_read: read.columns_read,
+                columns_total: read.columns_total,
+                bytes_scanned: (bytes as f64 * fraction).round() as i64,
+                rows_scanned,
+                filtered: read.filtered,
+            }
+        })
+        .collect();
+    let bytes_scanned = tables.iter().map(|t| t.bytes_scanned).sum();
+    Ok(Json(QueryEstimateResponse {
+        bytes_scanned,
+        rows_scanned: tables.iter().map(|t| t.rows_scanned).sum(),
+        cost_tier: QueryCostTier::for_bytes(bytes_scanned),
+        tables,
+    }))
+}
+
+fn result_columns(schema: &Schema) -> Vec<ResultColumn> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| ResultColumn {
+            name: f.name().clone(),
+            data_type: f.data_type().to_string(),
+        })
+        .collect()
+}
+
+/// Model replies tried per question; each retry is told why the previous reply was rejected.
+const NATURAL_LANGUAGE_ATTEMPTS: usize = 2;
+
+/// Turn a plain-words question into a structured query with the configured language model. The
+/// model's reply is validated and planned against the workspace's data like `/analytics/columns`,
+/// so only queries that would run are returned; the query itself is not run.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/analytics/nl",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = NaturalLanguageQueryRequest,
+    responses(
+        (status = 200, description = "Structured query answering the question", body = NaturalLanguageQueryResponse),
+        (status = 400, description = "No language model is configured, or it gave no valid query", body = crate::server::dtos::common::ErrorResponse),
+        (status = 429, description = "Too many concurrent queries for this user", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "analytics"
+)]
+pub async fn natural_language_query_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<NaturalLanguageQueryRequest>,
+) -> Result<Json<NaturalLanguageQueryResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let llm = state.llm.clone().ok_or_else(|| {
+        DoubledeckerError::BadRequest("Natural-language queries are not configured on this server".to_string())
+    })?;
+    let scope = query_scope_for_role(&state, workspace_id, role).await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+
+    let table = state.engine.plan_schema(workspace_id, &scope, "SELECT * FROM royalty_data").await?;
+    let system = natural_language_prompt(&result_columns(&table));
+    let question = payload.question.trim();
+    let mut prompt = question.to_string();
+    let mut rejection = String::new();
+    for _ in 0..NATURAL_LANGUAGE_ATTEMPTS {
+        let reply = llm.complete(&system, &prompt).await?;
+        match check_natural_language_reply(&state, workspace_id, &scope, &reply).await {
+            Ok(response) => return Ok(Json(response)),
+            Err(reason) => {
+                prompt = format!(
+                    "{}\n\nYour previous reply was rejected ({}):\n{}\n\nReply with a corrected JSON query.",
+                    question, reason, reply
+                );
+                rejection = reason;
+            }
+        }
+    }
+    Err(DoubledeckerError::BadRequest(format!("Could not turn the question into a valid query: {}", rejection)))
+}
+
+/// The structured query in a model's reply, if it validates and plans; otherwise why not.
+async fn check_natural_language_reply(
+    state: &AppState,
+    workspace_id: Uuid,
+    scope: &QueryScope,
+    reply: &str,
+) -> Result<NaturalLanguageQueryResponse, String> {
+    let value = reply_json(reply).ok_or_else(|| "the reply is not a JSON object".to_string())?;
+    let query: StructuredAnalyticsQuery = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    if query.lookups.is_some() {
+        return Err("lookups are not available".to_string());
+    }
+    let errors = query.field_errors();
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(|e| format!("{}: {}", e.field, e.message)).collect();
+        return Err(errors.join("; "));
+    }
+    let sql = query.to_sql().map_err(|e| e.message())?;
+    let schema = state.engine.plan_schema(workspace_id, scope, &sql).await.map_err(|e| e.message())?;
+    Ok(NaturalLanguageQueryResponse {
+        query,
+        sql,
+        columns: result_columns(&schema),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/analytics/download",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Client-chosen key; a retry with the same key and query replays the first file instead of running the query again"),
+        ExportParams
+    ),
+    request_body = AnalyticsQueryRequest,
+    responses(
+        (status = 200, description = "Download query result as CSV", content_type = "text/csv"),
+        (status = 200, description = "Download query result as Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 200, description = "Download query result as Parquet", content_type = "application/vnd.apache.parquet"),
+        (status = 400, description = "An Excel export of more rows than a worksheet holds", body = crate::server::dtos::common::ErrorResponse),
+        (status = 409, description = "A request with this Idempotency-Key is still in progress", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "analytics"
 )]
 pub async fn download_query_csv_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
+    Query(export): Query<ExportParams>,
+    IdempotencyKey(idempotency_key): IdempotencyKey,
     State(state): State<AppState>,
-    Json(payload): Json<AnalyticsQueryRequest>,
+    ValidatedJson(payload): ValidatedJson<AnalyticsQueryRequest>,
 ) -> Result<Response<axum::body::Body>, DoubledeckerError> {
-    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let format = export.format.unwrap_or_default();
+    let hash = request_hash(&[
+        workspace_id.as_bytes(),
+        &serde_json::to_vec(&payload).unwrap_or_default(),
+        &serde_json::to_vec(&format).unwrap_or_default(),
+    ]);
+    let payload = expand_segments(&state.db_pool, workspace_id, payload).await?;
+
+    run_idempotent(&state.db_pool, auth_user.user_id, "analytics/download", idempotency_key, hash, async {
+        let scope = query_scope_for_role(&state, workspace_id, role)
+            .await?
+            .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+        let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, payload.lookup_dataset_ids()).await?;
+        let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+
+        let sql = payload.to_safe_sql()?;
+        let (batches, _) = execute_analytics(&state, workspace_id, auth_user.user_id, &scope, &payload, &sql).await?;
+
+        build_export_response(batches, format, "royalty_analytics", scope.timezone.as_deref()).await
+    })
+    .await
+}
+
+/// Run an analytics query and draw its result as a bar, line or pie chart, as PNG or SVG, to embed
+/// where there is no frontend to render it (emails, chat messages). The chart's labels come from
+/// one column and its values from one or more numeric columns; see the parameters.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/analytics/chart",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ChartParams
+    ),
+    request_body = AnalyticsQueryRequest,
+    responses(
+        (status = 200, description = "Chart as PNG", content_type = "image/png"),
+        (status = 200, description = "Chart as SVG", content_type = "image/svg+xml"),
+        (status = 400, description = "The result has no rows or numeric columns to chart, or too many rows", body = crate::server::dtos::common::ErrorResponse),
+        (status = 429, description = "Too many concurrent queries for this user", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "analytics"
+)]
+pub async fn chart_query_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(chart): ValidatedQuery<ChartParams>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<AnalyticsQueryRequest>,
+) -> Result<Response<axum::body::Body>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role)
+        .await?
+        .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+    let payload = expand_segments(&state.db_pool, workspace_id, payload).await?;
+    let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, payload.lookup_dataset_ids()).await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
 
     let sql = payload.to_safe_sql()?;
-    let batches = state
-        .engine
-        .execute_royalty_analytics(workspace_id, &sql)
-        .await?;
-    let response = parse_batch_to_json(batches).await?;
-    let csv_content = query_response_to_csv(&response);
+    let (batches, _) = execute_analytics(&state, workspace_id, auth_user.user_id, &scope, &payload, &sql).await?;
+    let batches = match scope.timezone.as_deref() {
+        Some(tz) => batches.iter().map(|b| localize_timestamps(b, tz)).collect::<Result<Vec<_>, _>>()?,
+        None => batches,
+    };
+
+    let kind = chart.kind.unwrap_or_default();
+    let format = chart.format.unwrap_or_default();
+    let (width, height) = (
+        chart.width.unwrap_or(ChartParams::DEFAULT_WIDTH),
+        chart.height.unwrap_or(ChartParams::DEFAULT_HEIGHT),
+    );
+    let data = chart_data(&batches, chart.x.as_deref(), &chart.y_columns())?;
+    let title = chart.title.clone();
+    // Rasterizing is CPU-bound; keep it off the async workers
+    let (content_type, body) = tokio::task::spawn_blocking(move || match format {
+        ChartFormat::Png => render_png(kind, &data, title.as_deref(), width, height).map(|png| ("image/png", png)),
+        ChartFormat::Svg => {
+            render_svg(kind, &data, title.as_deref(), width, height).map(|svg| ("image/svg+xml", svg.into_bytes()))
+        }
+    })
+    .await
+    .map_err(|e| DoubledeckerError::Internal(format!("Chart rendering failed: {}", e)))??;
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"chart.{}\"", if format == ChartFormat::Png { "png" } else { "svg" }),
+        )
+        .body(axum::body::Body::from(body))
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)))
+}
+
+/// Run one query against two sets of datasets, e.g. last month's statement and its refreshed
+/// re-upload, and return the rows added, removed and changed between the results, matched on
+/// `key`. Both runs use the caller's column restrictions, time zone and segments. A structured
+/// query's `limit` caps each result (default 100,000 rows here); a result that reaches it is
+/// rejected rather than compared incomplete.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/analytics/diff",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = QueryDiffRequest,
+    responses(
+        (status = 200, description = "Differences between the two results", body = QueryDiffResponse),
+        (status = 400, description = "A dataset is not ready, a result reached the query limit, or the key does not identify rows of a result", body = crate::server::dtos::common::ErrorResponse),
+        (status = 404, description = "A dataset, or a key column in the result, was not found", body = crate::server::dtos::common::ErrorResponse),
+        (status = 429, description = "Too many concurrent queries for this user", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "analytics"
+)]
+pub async fn diff_query_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<QueryDiffRequest>,
+) -> Result<Json<QueryDiffResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role)
+        .await?
+        .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+    let query = expand_segments(&state.db_pool, workspace_id, payload.query).await?;
+    let scope = with_lookup_datasets(&state.db_pool, workspace_id, scope, query.lookup_dataset_ids()).await?;
+    check_ready_datasets(&state.db_pool, workspace_id, &payload.base_dataset_ids, "Dataset").await?;
+    check_ready_datasets(&state.db_pool, workspace_id, &payload.compare_dataset_ids, "Dataset").await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+
+    let mut query = AnalyticsQueryRequest { layout: ResultLayout::Rows, preview: false, ..query };
+    // Both results must be complete to compare them, so structured queries are not cut off at the
+    // usual default of 100 rows
+    let steps = query.steps.iter_mut().flatten().filter_map(|step| step.structured.as_mut());
+    for structured in query.structured.iter_mut().chain(steps) {
+        structured.limit.get_or_insert(MAX_STRUCTURED_LIMIT);
+    }
+    let last = match &query.steps {
+        Some(steps) => steps.last().and_then(|step| step.structured.as_ref()),
+        None => query.structured.as_ref(),
+    };
+    let row_cap = last.and_then(|structured| structured.limit);
+    let sql = query.to_safe_sql()?;
+    let base_scope = QueryScope { dataset_ids: Some(payload.base_dataset_ids), ..scope.clone() };
+    let compare_scope = QueryScope { dataset_ids: Some(payload.compare_dataset_ids), ..scope.clone() };
+    let (base, compare) = tokio::try_join!(
+        execute_analytics(&state, workspace_id, auth_user.user_id, &base_scope, &query, &sql),
+        execute_analytics(&state, workspace_id, auth_user.user_id, &compare_scope, &query, &sql),
+    )?;
+    let base = render_query_results(base.0, scope.timezone.as_deref(), ResultLayout::Rows).await?;
+    let compare = render_query_results(compare.0, scope.timezone.as_deref(), ResultLayout::Rows).await?;
+    for (side, rows) in [("base", &base.rows), ("compare", &compare.rows)] {
+        if let Some(row_cap) = row_cap.filter(|cap| rows.len() >= *cap) {
+            return Err(DoubledeckerError::BadRequest(format!(
+                "The {} result reached the query limit of {} rows and may be incomplete; raise query.limit or narrow the query",
+                side, row_cap
+            )));
+        }
+    }
+
+    let limit = payload.limit.unwrap_or(DEFAULT_DIFF_ROWS);
+    let diff = QueryDiffResponse::diff(compare.columns, payload.key, base.rows, compare.rows, limit)?;
+    Ok(Json(diff))
+}
+
+/// Run an analytics request's SQL, naming the failing step of a multi-step query. Slow runs are
+/// logged with the request.
+async fn execute_analytics(
+    state: &AppState,
+    workspace_id: Uuid,
+    user_id: Uuid,
+    scope: &QueryScope,
+    payload: &AnalyticsQueryRequest,
+    sql: &str,
+) -> Result<(Vec<RecordBatch>, QueryMetrics), DoubledeckerError> {
+    let scope = scope.clone().with_preview(payload.preview);
+    match state.engine.execute_with_metrics(workspace_id, &scope, sql).await {
+        Ok(query) => {
+            record_if_slow(&state.db_pool, &state.engine, workspace_id, Some(user_id), None, sql, payload, &query).await;
+            Ok((query.batches, query.metrics))
+        }
+        Err(err) => Err(locate_step_failure(state, workspace_id, &scope, payload, err).await),
+    }
+}
+
+/// Log a query that ran at or over the slow-query threshold, with the request or pipeline query
+/// (`operations`) it was built from. Best effort: the query's result does not depend on the log.
+#[allow(clippy::too_many_arguments)]
+pub async fn record_if_slow(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    workspace_id: Uuid,
+    user_id: Option<Uuid>,
+    pipeline_id: Option<Uuid>,
+    sql: &str,
+    operations: &impl Serialize,
+    query: &ExecutedQuery,
+) {
+    let (Some(plan), Some(threshold)) = (&query.slow_plan, engine.slow_query_threshold()) else {
+        return;
+    };
+    let operations = serde_json::to_value(operations).unwrap_or_default();
+    let threshold_ms = threshold.as_millis() as i64;
+    let logged =
+        record_slow_query(pool, workspace_id, user_id, pipeline_id, sql, operations, plan, &query.metrics, threshold_ms)
+            .await;
+    if let Err(e) = logged {
+        eprintln!("Could not log slow query in workspace {}: {}", workspace_id, e);
+    }
+}
+
+/// For a multi-step query that does not plan, plan its steps one by one to report which step broke
+/// and what it could read. Other errors are returned as they are.
+async fn locate_step_failure(
+    state: &AppState,
+    workspace_id: Uuid,
+    scope: &QueryScope,
+    payload: &AnalyticsQueryRequest,
+    err: DoubledeckerError,
+) -> DoubledeckerError {
+    let planning_error = matches!(
+        err,
+        DoubledeckerError::InvalidQuery(_) | DoubledeckerError::ColumnNotFound(_) | DoubledeckerError::TableNotFound(_)
+    );
+    let Some(steps) = payload.steps.as_deref().filter(|_| planning_error) else {
+        return err;
+    };
+    let step_sqls = match payload.step_sqls() {
+        Ok(step_sqls) => step_sqls.unwrap_or_default(),
+        Err(e) => return e,
+    };
+    match state.engine.diagnose_steps(workspace_id, scope, &step_sqls).await {
+        Some((index, cause, available)) => cause.in_query_step(index, &steps[index].name, steps[index].kind(), available),
+        None => err,
+    }
+}
+
+/// Builds a file download response for query batches in the requested export format,
+/// with timestamps shown in `timezone` when given
+async fn build_export_response(
+    batches: Vec<RecordBatch>,
+    format: ExportFormat,
+    base_filename: &str,
+    timezone: Option<&str>,
+) -> Result<Response<axum::body::Body>, DoubledeckerError> {
+    let (content_type, extension, body) = match format {
+        ExportFormat::Csv => {
+            let response = parse_batch_to_json(batches, timezone).await?;
+            ("text/csv", "csv", query_response_to_csv(&response).into_bytes())
+        }
+        ExportFormat::Xlsx => {
+            let batches = match timezone {
+                Some(tz) => batches
+                    .iter()
+                    .map(|b| localize_timestamps(b, tz))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => batches,
+            };
+            (
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                "xlsx",
+                batches_to_xlsx(&batches)?,
+            )
+        }
+        ExportFormat::Parquet => ("application/vnd.apache.parquet", "parquet", batches_to_parquet(&batches)?),
+    };
 
     let response = Response::builder()
-        .header(header::CONTENT_TYPE, "text/csv")
+        .header(header::CONTENT_TYPE, content_type)
         .header(
             header::CONTENT_DISPOSITION,
-            "attachment; filename=\"royalty_analytics.csv\"",
+            format!("attachment; filename=\"{}.{}\"", base_filename, extension),
         )
-        .body(axum::body::Body::from(csv_content))
+        .body(axum::body::Body::from(body))
         .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)))?;
 
     Ok(response)
@@ -119,12 +617,14 @@ pub async fn get_analytics_summary_handler(
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
 ) -> Result<Json<AnalyticsSummaryResponse>, DoubledeckerError> {
-    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role).await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
 
     let sql_summary = "SELECT COALESCE(SUM(net_revenue), 0) as total_rev, COUNT(DISTINCT isrc) as total_tracks, COALESCE(SUM(quantity), 0) as total_streams FROM royalty_data";
     let summary_batches = state
         .engine
-        .execute_royalty_analytics(workspace_id, sql_summary)
+        .execute_scoped_analytics(workspace_id, &scope, sql_summary)
         .await
         .unwrap_or_default();
 
@@ -132,37 +632,37 @@ pub async fn get_analytics_summary_handler(
     let mut total_tracks_monetized = 0i64;
     let mut total_streams = 0i64;
 
-    if let Some(batch) = summary_batches.first() {
-        if batch.num_rows() > 0 {
-            if let Some(rev_col) = batch.column(0).as_any().downcast_ref::<Decimal128Array>() {
-                if !rev_col.is_null(0) {
-                    let mantissa = rev_col.value(0);
-                    total_net_revenue = (mantissa as f64) / 1_000_000_000.0;
-                }
-            } else if let Some(rev_col) = batch.column(0).as_any().downcast_ref::<Float64Array>() {
-                if !rev_col.is_null(0) {
-                    total_net_revenue = rev_col.value(0);
-                }
+    if let Some(batch) = summary_batches.first()
+        && batch.num_rows() > 0
+    {
+        if let Some(rev_col) = batch.column(0).as_any().downcast_ref::<Decimal128Array>() {
+            if !rev_col.is_null(0) {
+                let mantissa = rev_col.value(0);
+                total_net_revenue = (mantissa as f64) / 1_000_000_000.0;
             }
+        } else if let Some(rev_col) = batch.column(0).as_any().downcast_ref::<Float64Array>()
+            && !rev_col.is_null(0)
+        {
+            total_net_revenue = rev_col.value(0);
+        }
 
-            if let Some(count_col) = batch.column(1).as_any().downcast_ref::<Int64Array>() {
-                if !count_col.is_null(0) {
-                    total_tracks_monetized = count_col.value(0);
-                }
-            }
+        if let Some(count_col) = batch.column(1).as_any().downcast_ref::<Int64Array>()
+            && !count_col.is_null(0)
+        {
+            total_tracks_monetized = count_col.value(0);
+        }
 
-            if let Some(streams_col) = batch.column(2).as_any().downcast_ref::<Int64Array>() {
-                if !streams_col.is_null(0) {
-                    total_streams = streams_col.value(0);
-                }
-            }
+        if let Some(streams_col) = batch.column(2).as_any().downcast_ref::<Int64Array>()
+            && !streams_col.is_null(0)
+        {
+            total_streams = streams_col.value(0);
         }
     }
 
     let sql_platform = "SELECT platform, SUM(net_revenue) as rev FROM royalty_data GROUP BY platform ORDER BY rev DESC LIMIT 1";
     let platform_batches = state
         .engine
-        .execute_royalty_analytics(workspace_id, sql_platform)
+        .execute_scoped_analytics(workspace_id, &scope, sql_platform)
         .await
         .unwrap_or_default();
     let top_platform = extract_first_string(&platform_batches);
@@ -170,7 +670,7 @@ pub async fn get_analytics_summary_handler(
     let sql_track = "SELECT title, SUM(net_revenue) as rev FROM royalty_data GROUP BY title ORDER BY rev DESC LIMIT 1";
     let track_batches = state
         .engine
-        .execute_royalty_analytics(workspace_id, sql_track)
+        .execute_scoped_analytics(workspace_id, &scope, sql_track)
         .await
         .unwrap_or_default();
     let top_track = extract_first_string(&track_batches);
@@ -186,14 +686,12 @@ pub async fn get_analytics_summary_handler(
 }
 
 fn extract_first_string(batches: &[RecordBatch]) -> Option<String> {
-    if let Some(batch) = batches.first() {
-        if batch.num_rows() > 0 {
-            if let Some(str_col) = batch.column(0).as_any().downcast_ref::<StringArray>() {
-                if !str_col.is_null(0) {
-                    return Some(str_col.value(0).to_string());
-                }
-            }
-        }
+    if let Some(batch) = batches.first()
+        && batch.num_rows() > 0
+        && let Some(str_col) = batch.column(0).as_any().downcast_ref::<StringArray>()
+        && !str_col.is_null(0)
+    {
+        return Some(str_col.value(0).to_string());
     }
     None
 }
@@ -212,7 +710,7 @@ fn extract_first_string(batches: &[RecordBatch]) -> Option<String> {
 pub async fn get_query_history_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<QueryHistoryRecord>>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
@@ -226,36 +724,40 @@ pub async fn get_query_history_handler(
     path = "/api/workspaces/{workspace_id}/analytics/history/{query_id}/download",
     params(
         ("workspace_id" = Uuid, Path, description = "Workspace ID"),
-        ("query_id" = String, Path, description = "Query ID")
+        ("query_id" = String, Path, description = "Query ID"),
+        ExportParams
     ),
     responses(
-        (status = 200, description = "Download history query CSV", content_type = "text/csv")
+        (status = 200, description = "Download history query CSV", content_type = "text/csv"),
+        (status = 200, description = "Download history query as Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 200, description = "Download history query as Parquet", content_type = "application/vnd.apache.parquet"),
+        (status = 400, description = "An Excel export of more rows than a worksheet holds", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "analytics"
 )]
 pub async fn download_query_history_csv_handler(
     auth_user: AuthenticatedUser,
     Path((workspace_id, query_id)): Path<(Uuid, String)>,
+    Query(export): Query<ExportParams>,
     State(state): State<AppState>,
 ) -> Result<Response<axum::body::Body>, DoubledeckerError> {
-    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let scope = query_scope_for_role(&state, workspace_id, role)
+        .await?
+        .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
 
     let history_rec = get_query_history_by_id(&state.db_pool, workspace_id, &query_id).await?;
     let batches = state
         .engine
-        .execute_royalty_analytics(workspace_id, &history_rec.sql_executed)
+        .execute_scoped_analytics(workspace_id, &scope, &history_rec.sql_executed)
         .await?;
-    let response = parse_batch_to_json(batches).await?;
-    let csv_content = query_response_to_csv(&response);
 
-    let response = Response::builder()
-        .header(header::CONTENT_TYPE, "text/csv")
-        .header(
-            header::CONTENT_DISPOSITION,
-            format!("attachment; filename=\"query_{}.csv\"", query_id),
-        )
-        .body(axum::body::Body::from(csv_content))
-        .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)))?;
-
-    Ok(response)
+    build_export_response(
+        batches,
+        export.format.unwrap_or_default(),
+        &format!("query_{}", query_id),
+        scope.timezone.as_deref(),
+    )
+    .await
 }
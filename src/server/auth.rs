@@ -1,12 +1,24 @@
-use crate::db::queries::{create_user, create_workspace, get_user_by_email, get_user_by_id, verify_password};
-use crate::server::middleware::AuthenticatedUser;
+use crate::config::jwt_config;
+use crate::db::models::{User, UserSettings};
+use crate::db::queries::{
+    count_queries_per_day, create_user, create_user_session, create_workspace,
+    get_user_by_email, get_user_by_id, get_user_query_totals, get_user_settings, get_user_stats, get_user_suspension,
+    list_active_user_sessions, most_queried_tables, revoke_user_session, upsert_user_settings,
+    verify_password,
+};
+use crate::server::dtos::DeleteResponse;
+use crate::server::middleware::{AuthenticatedUser, suspended_error};
+use crate::server::validation::ValidatedJson;
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
-use crate::utils::jwt::generate_token;
+use crate::utils::jwt::{TOKEN_EXPIRATION_HOURS, generate_token};
 use axum::Json;
-use axum::extract::State;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use crate::server::extractors::ClientIp;
 use crate::server::dtos::auth::*;
-use std::env;
+use std::net::IpAddr;
+use uuid::Uuid;
 
 #[utoipa::path(
     post,
@@ -14,37 +26,31 @@ use std::env;
     request_body = RegisterRequest,
     responses(
         (status = 200, description = "User successfully registered", body = AuthResponse),
-        (status = 400, description = "Bad request")
+        (status = 400, description = "Bad request", body = crate::server::dtos::common::ErrorResponse),
+        (status = 422, description = "Invalid fields, e.g. a password that fails the policy", body = crate::server::dtos::common::ErrorResponse)
     ),
+    security(()),
     tag = "auth"
 )]
 pub async fn signup(
     State(state): State<AppState>,
-    Json(payload): Json<RegisterRequest>,
+    ClientIp(client): ClientIp,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<RegisterRequest>,
 ) -> Result<Json<AuthResponse>, DoubledeckerError> {
-    // Validate name
-    if payload.name.trim().is_empty() {
-        return Err(DoubledeckerError::BadRequest(
-            "Name cannot be empty".to_string(),
-        ));
-    }
-
-    // Validate email format (basic check)
-    if !payload.email.contains('@') {
-        return Err(DoubledeckerError::BadRequest(
-            "Invalid email format".to_string(),
-        ));
-    }
-
-    // Validate password length
-    if payload.password.len() < 6 {
-        return Err(DoubledeckerError::BadRequest(
-            "Password must be at least 6 characters".to_string(),
-        ));
+    // Name and email are checked by `ValidatedJson`; the password against the configured policy
+    let errors = state
+        .password_policy
+        .validate(&payload.password, &[&payload.name, &payload.email]);
+    if !errors.is_empty() {
+        return Err(DoubledeckerError::Validation(errors));
     }
 
+    // The user and their default workspace are created together or not at all
+    let db_err = |e: sqlx::Error| DoubledeckerError::DatabaseError(e.to_string());
+    let mut tx = state.db_pool.begin().await.map_err(db_err)?;
     let user = create_user(
-        &state.db_pool,
+        &mut *tx,
         payload.name.clone(),
         payload.email,
         payload.password,
@@ -54,16 +60,15 @@ pub async fn signup(
 
     // Automatically create a default workspace for the user upon signup
     let _workspace = create_workspace(
-        &state.db_pool,
+        &mut *tx,
         user.id,
         format!("{}'s Catalog", user.name),
     )
     .await?;
+    tx.commit().await.map_err(db_err)?;
 
-    // Generate JWT token
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let token = generate_token(user.id, user.email.clone(), &jwt_secret)
-        .map_err(|e| DoubledeckerError::Internal(format!("Token generation failed: {}", e)))?;
+    // Open a session and generate its JWT token
+    let token = issue_session_token(&state, &user, &headers, client).await?;
 
     Ok(Json(AuthResponse {
         token,
@@ -82,28 +87,40 @@ pub async fn signup(
     request_body = LoginRequest,
     responses(
         (status = 200, description = "User successfully logged in", body = AuthResponse),
-        (status = 401, description = "Invalid credentials")
+        (status = 401, description = "Invalid credentials", body = crate::server::dtos::common::ErrorResponse),
+        (status = 403, description = "Account suspended", body = crate::server::dtos::common::ErrorResponse),
+        (status = 429, description = "Too many failed attempts; account or client temporarily locked", body = crate::server::dtos::common::ErrorResponse)
     ),
+    security(()),
     tag = "auth"
 )]
 pub async fn login(
     State(state): State<AppState>,
-    Json(payload): Json<LoginRequest>,
+    ClientIp(client): ClientIp,
+    headers: HeaderMap,
+    ValidatedJson(payload): ValidatedJson<LoginRequest>,
 ) -> Result<Json<AuthResponse>, DoubledeckerError> {
-    let user = get_user_by_email(&state.db_pool, &payload.email).await?;
-
-    let is_valid = verify_password(&payload.password, &user.password_hash)?;
+    let ip = client.map(|ip| ip.to_string());
+    state.login_guard.check(&payload.email, ip.as_deref())?;
 
-    if !is_valid {
-        return Err(DoubledeckerError::AuthenticationError(
-            "Invalid credentials".to_string(),
-        ));
+    // Unknown emails count as failures too, so probing for accounts is throttled the same way
+    let user = match get_user_by_email(&state.db_pool, &payload.email).await {
+        Ok(user) if verify_password(&payload.password, &user.password_hash)? => user,
+        Ok(_) | Err(DoubledeckerError::NotFound(_)) => {
+            state.login_guard.record_failure(&payload.email, ip.as_deref())?;
+            return Err(DoubledeckerError::AuthenticationError(
+                "Invalid credentials".to_string(),
+            ));
+        }
+        Err(e) => return Err(e),
+    };
+    state.login_guard.record_success(&payload.email, ip.as_deref())?;
+    if let Some(suspension) = get_user_suspension(&state.db_pool, user.id).await? {
+        return Err(suspended_error(&suspension));
     }
 
-    // Generate JWT token
-    let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let token = generate_token(user.id, user.email.clone(), &jwt_secret)
-        .map_err(|e| DoubledeckerError::Internal(format!("Token generation failed: {}", e)))?;
+    // Open a session and generate its JWT token
+    let token = issue_session_token(&state, &user, &headers, client).await?;
 
     Ok(Json(AuthResponse {
         token,
@@ -138,3 +155,147 @@ pub async fn get_profile(
         user_type: user.user_type,
     }))
 }
+
+/// Window covered by `queries_per_day` in the profile stats.
+const STATS_WINDOW_DAYS: i32 = 30;
+const STATS_TOP_TABLES: i64 = 10;
+
+#[utoipa::path(
+    get,
+    path = "/profile/stats",
+    responses(
+        (status = 200, description = "Query and upload usage statistics for the current user", body = ProfileStatsResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "auth"
+)]
+pub async fn get_profile_stats(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<Json<ProfileStatsResponse>, DoubledeckerError> {
+    let (stats, totals, queries_per_day, most_queried_tables) = tokio::try_join!(
+        get_user_stats(&state.db_pool, auth_user.user_id),
+        get_user_query_totals(&state.db_pool, auth_user.user_id),
+        count_queries_per_day(&state.db_pool, Some(auth_user.user_id), STATS_WINDOW_DAYS),
+        most_queried_tables(&state.db_pool, auth_user.user_id, STATS_TOP_TABLES),
+    )?;
+
+    Ok(Json(ProfileStatsResponse {
+        total_queries: stats.total_queries,
+        total_files_processed: stats.total_files_processed,
+        total_storage_bytes: stats.total_storage_bytes,
+        total_bytes_scanned: totals.total_bytes_scanned,
+        avg_execution_time_ms: totals.avg_execution_time_ms,
+        queries_per_day,
+        most_queried_tables,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/profile/settings",
+    responses(
+        (status = 200, description = "Saved preferences of the current user", body = UserSettings),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "auth"
+)]
+pub async fn get_profile_settings(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<Json<UserSettings>, DoubledeckerError> {
+    Ok(Json(get_user_settings(&state.db_pool, auth_user.user_id).await?))
+}
+
+#[utoipa::path(
+    put,
+    path = "/profile/settings",
+    request_body = UserSettings,
+    responses(
+        (status = 200, description = "Preferences replaced", body = UserSettings),
+        (status = 401, description = "Unauthorized"),
+        (status = 422, description = "Invalid setting value", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "auth"
+)]
+pub async fn update_profile_settings(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<UserSettings>,
+) -> Result<Json<UserSettings>, DoubledeckerError> {
+    Ok(Json(upsert_user_settings(&state.db_pool, auth_user.user_id, &payload).await?))
+}
+
+/// Record a session for this login (device metadata from the request) and sign a token bound to it.
+async fn issue_session_token(
+    state: &AppState,
+    user: &User,
+    headers: &HeaderMap,
+    client: Option<IpAddr>,
+) -> Result<String, DoubledeckerError> {
+    let user_agent = headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(512).collect());
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(TOKEN_EXPIRATION_HOURS);
+    let session =
+        create_user_session(&state.db_pool, user.id, user_agent, client.map(|ip| ip.to_string()), expires_at).await?;
+
+    generate_token(user.id, user.email.clone(), session.id, &jwt_config().current)
+        .map_err(|e| DoubledeckerError::Internal(format!("Token generation failed: {}", e)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/auth/sessions",
+    responses(
+        (status = 200, description = "Active sessions of the current user, most recently used first", body = Vec<SessionResponse>),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "auth"
+)]
+pub async fn list_sessions_handler(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<SessionResponse>>, DoubledeckerError> {
+    let sessions = list_active_user_sessions(&state.db_pool, auth_user.user_id).await?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|s| SessionResponse {
+                current: s.id == auth_user.session_id,
+                id: s.id,
+                user_agent: s.user_agent,
+                ip_address: s.ip_address,
+                created_at: s.created_at,
+                last_used_at: s.last_used_at,
+                expires_at: s.expires_at,
+            })
+            .collect(),
+    ))
+}
+
+/// Revoke a session; its token stops working immediately
+#[utoipa::path(
+    delete,
+    path = "/auth/sessions/{session_id}",
+    params(
+        ("session_id" = Uuid, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 200, description = "Session revoked", body = DeleteResponse),
+        (status = 404, description = "No active session with this ID")
+    ),
+    tag = "auth"
+)]
+pub async fn revoke_session_handler(
+    auth_user: AuthenticatedUser,
+    Path(session_id): Path<Uuid>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    revoke_user_session(&state.db_pool, auth_user.user_id, session_id).await?;
+    Ok(Json(DeleteResponse {
+        message: "Session revoked successfully".to_string(),
+    }))
+}
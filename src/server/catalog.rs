@@ -5,9 +5,10 @@ use crate::db::queries::{
 };
 use crate::server::dtos::DeleteResponse;
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, State};
 use axum::Json;
 use crate::server::dtos::catalog::*;
 use uuid::Uuid;
@@ -24,7 +25,7 @@ use uuid::Uuid;
 pub async fn create_artist_handler(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateArtistRequest>,
+    ValidatedJson(payload): ValidatedJson<CreateArtistRequest>,
 ) -> Result<Json<Artist>, DoubledeckerError> {
     if payload.name.trim().is_empty() {
         return Err(DoubledeckerError::BadRequest(
@@ -47,7 +48,7 @@ pub async fn create_artist_handler(
 )]
 pub async fn list_artists_handler(
     auth_user: AuthenticatedUser,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<Artist>>, DoubledeckerError> {
     let limit = pagination.effective_limit();
@@ -71,7 +72,7 @@ pub async fn update_artist_handler(
     _auth_user: AuthenticatedUser,
     Path(artist_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdateArtistRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdateArtistRequest>,
 ) -> Result<Json<Artist>, DoubledeckerError> {
     let updated = update_artist(&state.db_pool, artist_id, &payload.name).await?;
     Ok(Json(updated))
@@ -111,7 +112,7 @@ pub async fn delete_artist_handler(
 pub async fn create_album_handler(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateAlbumRequest>,
+    ValidatedJson(payload): ValidatedJson<CreateAlbumRequest>,
 ) -> Result<Json<Album>, DoubledeckerError> {
     if payload.title.trim().is_empty() {
         return Err(DoubledeckerError::BadRequest(
@@ -142,7 +143,7 @@ pub async fn create_album_handler(
 )]
 pub async fn list_albums_handler(
     auth_user: AuthenticatedUser,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<Album>>, DoubledeckerError> {
     let limit = pagination.effective_limit();
@@ -166,7 +167,7 @@ pub async fn update_album_handler(
     _auth_user: AuthenticatedUser,
     Path(album_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdateAlbumRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdateAlbumRequest>,
 ) -> Result<Json<Album>, DoubledeckerError> {
     let updated = update_album(
         &state.db_pool,
@@ -213,7 +214,7 @@ pub async fn delete_album_handler(
 pub async fn create_track_handler(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateTrackRequest>,
+    ValidatedJson(payload): ValidatedJson<CreateTrackRequest>,
 ) -> Result<Json<Track>, DoubledeckerError> {
     if payload.title.trim().is_empty() || payload.isrc.trim().is_empty() {
         return Err(DoubledeckerError::BadRequest(
@@ -244,7 +245,7 @@ pub async fn create_track_handler(
 )]
 pub async fn list_tracks_handler(
     auth_user: AuthenticatedUser,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<Track>>, DoubledeckerError> {
     let limit = pagination.effective_limit();
@@ -268,7 +269,7 @@ pub async fn update_track_handler(
     _auth_user: AuthenticatedUser,
     Path(track_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdateTrackRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdateTrackRequest>,
 ) -> Result<Json<Track>, DoubledeckerError> {
     let updated = update_track(
         &state.db_pool,
@@ -0,0 +1,184 @@
+use crate::db::models::{PaginatedResponse, PaginationParams, PipelineComment, WorkspaceRole};
+use crate::db::queries::{
+    create_pipeline_comment, delete_pipeline_comment, find_workspace_members_by_email, get_pipeline,
+    get_pipeline_comment, list_pipeline_comments, update_pipeline_comment,
+};
+use crate::server::dtos::DeleteResponse;
+use crate::server::dtos::comments::*;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::error::DoubledeckerError;
+use crate::utils::events::ActivityEventKind;
+use axum::extract::{Path, State};
+use axum::Json;
+use serde_json::json;
+use uuid::Uuid;
+
+/// Workspace members mentioned in a comment body; addresses of non-members are ignored.
+async fn resolve_mentions(state: &AppState, workspace_id: Uuid, body: &str) -> Result<Vec<Uuid>, DoubledeckerError> {
+    let emails = mentioned_emails(body);
+    if emails.is_empty() {
+        return Ok(Vec::new());
+    }
+    find_workspace_members_by_email(&state.db_pool, workspace_id, &emails).await
+}
+
+/// Tell the members newly mentioned in a comment, other than its author.
+fn notify_mentions(state: &AppState, workspace_id: Uuid, comment: &PipelineComment, already_mentioned: &[Uuid]) {
+    let notified: Vec<Uuid> = comment
+        .mentions
+        .iter()
+        .filter(|id| !already_mentioned.contains(id) && Some(**id) != comment.author_id)
+        .copied()
+        .collect();
+    let payload = json!({
+        "pipeline_id": comment.pipeline_id,
+        "comment_id": comment.id,
+        "author_id": comment.author_id,
+        "author_name": comment.author_name,
+    });
+    state.events.publish_to_users(&notified, ActivityEventKind::Mentioned, Some(workspace_id), payload);
+}
+
+/// Comment on a pipeline, or reply to one of its comments. Mentioned members get a `mentioned`
+/// event on `GET /events`.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/comments",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    request_body = CreateCommentRequest,
+    responses(
+        (status = 200, description = "Comment created", body = PipelineComment),
+        (status = 404, description = "Pipeline, or the comment replied to, not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn create_comment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<CreateCommentRequest>,
+) -> Result<Json<PipelineComment>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    if let Some(parent_id) = payload.parent_id {
+        get_pipeline_comment(&state.db_pool, pipeline_id, parent_id).await?;
+    }
+    let mentions = resolve_mentions(&state, workspace_id, &payload.body).await?;
+    let comment = create_pipeline_comment(
+        &state.db_pool,
+        pipeline_id,
+        payload.parent_id,
+        auth_user.user_id,
+        payload.body.trim(),
+        &mentions,
+    )
+    .await?;
+    notify_mentions(&state, workspace_id, &comment, &[]);
+    Ok(Json(comment))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/comments",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Comments of the pipeline, oldest first", body = PaginatedPipelineComments),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn list_comments_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<PipelineComment>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let limit = pagination.effective_limit();
+    let comments = list_pipeline_comments(&state.db_pool, pipeline_id, pagination.cursor, limit).await?;
+    Ok(Json(comments))
+}
+
+/// Edit a comment; only its author can.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/comments/{comment_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID"),
+        ("comment_id" = Uuid, Path, description = "Comment ID")
+    ),
+    request_body = UpdateCommentRequest,
+    responses(
+        (status = 200, description = "Comment edited", body = PipelineComment),
+        (status = 403, description = "The comment is someone else's"),
+        (status = 404, description = "Comment not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn update_comment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id, comment_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<UpdateCommentRequest>,
+) -> Result<Json<PipelineComment>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let existing = get_pipeline_comment(&state.db_pool, pipeline_id, comment_id).await?;
+    if existing.author_id != Some(auth_user.user_id) {
+        return Err(DoubledeckerError::Forbidden("Only the author can edit a comment".to_string()));
+    }
+    let mentions = resolve_mentions(&state, workspace_id, &payload.body).await?;
+    let comment =
+        update_pipeline_comment(&state.db_pool, pipeline_id, comment_id, payload.body.trim(), &mentions).await?;
+    notify_mentions(&state, workspace_id, &comment, &existing.mentions);
+    Ok(Json(comment))
+}
+
+/// Delete a comment and its replies. Authors can delete their own comments, admins any.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/comments/{comment_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID"),
+        ("comment_id" = Uuid, Path, description = "Comment ID")
+    ),
+    responses(
+        (status = 200, description = "Comment and its replies deleted", body = DeleteResponse),
+        (status = 403, description = "The comment is someone else's and the caller is not an admin"),
+        (status = 404, description = "Comment not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn delete_comment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id, comment_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let comment = get_pipeline_comment(&state.db_pool, pipeline_id, comment_id).await?;
+    if comment.author_id != Some(auth_user.user_id) && role < WorkspaceRole::Admin {
+        return Err(DoubledeckerError::Forbidden("Only the author or an admin can delete a comment".to_string()));
+    }
+    delete_pipeline_comment(&state.db_pool, pipeline_id, comment_id).await?;
+    Ok(Json(DeleteResponse {
+        message: "Comment deleted successfully".to_string(),
+    }))
+}
@@ -0,0 +1,387 @@
+use crate::db::models::{BackgroundJob, ColumnRestriction, DatasetAction, DatasetStatus, WorkspaceRole};
+use crate::db::queries::{
+    create_dataset, dataset_data_key, delete_datasets, enqueue_job, get_dataset_by_id, get_datasets_by_ids,
+    list_dataset_column_restrictions, record_dataset_activity, replace_dataset_column_restrictions,
+    set_dataset_file_size, transition_dataset_status,
+};
+use crate::engine::udfs::MaskMode;
+use crate::engine::{EngineProvider, QueryScope};
+use crate::server::dtos::common::DatasetResponse;
+use crate::server::dtos::uploads::*;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::uploads::user_data_key;
+use crate::server::validation::ValidatedJson;
+use crate::utils::crypto::FileEncryptor;
+use crate::utils::error::DoubledeckerError;
+use crate::utils::s3::{ObjectStorage, parquet_key};
+use crate::workers::JobHandler;
+use aes_gcm::{Aes256Gcm, Key};
+use axum::Json;
+use axum::extract::{Path, State};
+use datafusion::arrow::datatypes::SchemaRef;
+use futures::{StreamExt, stream};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::format::SortingColumn;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Background job kind for dataset compactions.
+pub const DATASET_COMPACTION_JOB: &str = "datasets.compact";
+/// Attempts of a compaction; storage and database hiccups get retried.
+const DATASET_COMPACTION_JOB_ATTEMPTS: i32 = 3;
+/// Rows per row group of a compacted file. Sorted groups of this size keep scans efficient while
+/// their min/max statistics still let a filter on the sort column skip most of the file.
+const COMPACTION_ROW_GROUP_ROWS: usize = 128 * 1024;
+/// Size of the parts a compacted file is uploaded in.
+const COMPACTION_PART_BYTES: usize = 8 * 1024 * 1024;
+/// Source files deleted at once after a compaction.
+const COMPACTION_DELETE_CONCURRENCY: usize = 8;
+
+/// Merge several datasets into one Parquet file sorted by `sort_by`, replacing many small uploads
+/// (e.g. one per monthly statement) with a file queries can prune by row group. Runs as a background
+/// job: the merged dataset is created QUEUED and becomes READY as the sources are deleted.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/compact",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = CompactDatasetsRequest,
+    responses(
+        (status = 200, description = "Merged dataset and the job writing it", body = CompactDatasetsResponse),
+        (status = 400, description = "A dataset is not READY"),
+        (status = 404, description = "Dataset not found"),
+        (status = 422, description = "Fewer than 2 datasets, or an unknown sort column")
+    ),
+    tag = "datasets"
+)]
+pub async fn compact_datasets_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<CompactDatasetsRequest>,
+) -> Result<Json<CompactDatasetsResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let datasets = get_datasets_by_ids(&state.db_pool, workspace_id, &payload.dataset_ids).await?;
+    for id in &payload.dataset_ids {
+        match datasets.iter().find(|d| d.id == *id) {
+            None => return Err(DoubledeckerError::NotFound(format!("Dataset {} not found", id))),
+            Some(d) if d.status != DatasetStatus::Ready.as_str() => {
+                return Err(DoubledeckerError::BadRequest(format!("Dataset {} is not ready", id)));
+            }
+            Some(_) => {}
+        }
+    }
+    let distributor_source = match datasets.first() {
+        Some(first) if datasets.iter().all(|d| d.distributor_source == first.distributor_source) => {
+            first.distributor_source.clone()
+        }
+        _ => "mixed".to_string(),
+    };
+
+    let dataset_id = Uuid::new_v4();
+    let dataset = create_dataset(
+        &state.db_pool,
+        dataset_id,
+        workspace_id,
+        distributor_source,
+        payload.filename.unwrap_or_else(|| "compacted.parquet".to_string()),
+        parquet_key(workspace_id, dataset_id),
+        0,
+        DatasetStatus::Queued,
+        user_data_key(&state, auth_user.user_id).await?.map(|(key_id, _)| key_id),
+    )
+    .await?;
+    let details = serde_json::json!({ "compacted_from": payload.dataset_ids });
+    record_dataset_activity(&state.db_pool, dataset_id, Some(auth_user.user_id), DatasetAction::Created, Some(details))
+        .await?;
+
+    // The merged dataset is governed like its sources, with the stricter mask where they differ
+    let mut restrictions: BTreeMap<String, MaskMode> = BTreeMap::new();
+    for id in &payload.dataset_ids {
+        for restriction in list_dataset_column_restrictions(&state.db_pool, *id).await? {
+            let mode = MaskMode::from_db_str(&restriction.mode);
+            let entry = restrictions.entry(restriction.column_name).or_insert(mode);
+            if mode.strictness() > entry.strictness() {
+                *entry = mode;
+            }
+        }
+    }
+    let restrictions: Vec<ColumnRestriction> = restrictions
+        .into_iter()
+        .map(|(column_name, mode)| ColumnRestriction { column_name, mode: mode.as_db_str().to_string() })
+        .collect();
+    replace_dataset_column_restrictions(&state.db_pool, dataset_id, &restrictions).await?;
+
+    let job = DatasetCompactionJob { dataset_id, source_ids: payload.dataset_ids, sort_by: payload.sort_by };
+    let job_payload = serde_json::to_value(job)
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize compaction job: {}", e)))?;
+    let job = enqueue_job(
+        &state.db_pool,
+        Some(workspace_id),
+        DATASET_COMPACTION_JOB,
+        job_payload,
+        DATASET_COMPACTION_JOB_ATTEMPTS,
+        Some(auth_user.user_id),
+    )
+    .await?;
+
+    Ok(Json(CompactDatasetsResponse { dataset: DatasetResponse::from_dataset(dataset), job }))
+}
+
+/// Writes compacted datasets. The merged file is uploaded before anything is removed, and the merged
+/// dataset turns READY in the same transaction that deletes the sources' rows, so a failed attempt
+/// never loses data; a retry after that transaction only deletes the source files left over. The
+/// merged file of an attempt whose swap did not commit is removed, unless another attempt has
+/// already made the dataset READY with it.
+pub struct DatasetCompactionJobHandler {
+    pub pool: PgPool,
+    pub engine: Arc<EngineProvider>,
+    pub uploader: Arc<dyn ObjectStorage>,
+}
+
+#[async_trait::async_trait]
+impl JobHandler for DatasetCompactionJobHandler {
+    async fn run(&self, job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+        let payload: DatasetCompactionJob = serde_json::from_value(job.payload.0.clone())
+            .map_err(|e| DoubledeckerError::BadRequest(format!("Invalid compaction job payload: {}", e)))?;
+        let workspace_id = job
+            .workspace_id
+            .ok_or_else(|| DoubledeckerError::BadRequest("Compaction job has no workspace".to_string()))?;
+
+        let dataset = get_dataset_by_id(&self.pool, workspace_id, payload.dataset_id).await?;
+        let unfinished = [DatasetStatus::Queued, DatasetStatus::Processing, DatasetStatus::Failed];
+        let started = dataset.status != DatasetStatus::Ready.as_str()
+            && transition_dataset_status(&self.pool, dataset.id, &unfinished, DatasetStatus::Processing, None, None)
+                .await?;
+        let (row_groups, file_size_bytes) = if !started {
+            // Swapped in by an earlier attempt; only source files may be left to delete
+            let dataset = get_dataset_by_id(&self.pool, workspace_id, payload.dataset_id).await?;
+            if dataset.status != DatasetStatus::Ready.as_str() {
+                return Err(DoubledeckerError::Conflict(format!("The merged dataset is {}", dataset.status)));
+            }
+            (None, dataset.file_size_bytes)
+        } else {
+            let data_key = dataset_data_key(&self.pool, dataset.encryption_key_id).await?;
+            let compacted = self.compact(workspace_id, &payload, &dataset.s3_parquet_key, data_key.as_ref()).await;
+            let current = get_dataset_by_id(&self.pool, workspace_id, payload.dataset_id).await?;
+            match compacted {
+                Ok(Some((row_groups, size))) => (Some(row_groups), size),
+                // Another attempt swapped its merged file in first
+                _ if current.status == DatasetStatus::Ready.as_str() => (None, current.file_size_bytes),
+                outcome => {
+                    let e = outcome.err().unwrap_or_else(|| {
+                        DoubledeckerError::Conflict("The merged dataset changed while this compaction ran".to_string())
+                    });
+                    // The swap did not commit: sources the merged file duplicates must not be read
+                    // twice by workspace queries
+                    let _ = self.uploader.delete_file(&dataset.s3_parquet_key).await;
+                    let (processing, failed) = ([DatasetStatus::Processing], DatasetStatus::Failed);
+                    let message = Some(e.to_string());
+                    let _ = transition_dataset_status(&self.pool, dataset.id, &processing, failed, None, message).await;
+                    return Err(e);
+                }
+            }
+        };
+
+        let uploader = &self.uploader;
+        let deletions: Vec<Result<(), DoubledeckerError>> = stream::iter(payload.source_ids.clone())
+            .map(|id| async move { uploader.delete_file(&parquet_key(workspace_id, id)).await })
+            .buffer_unordered(COMPACTION_DELETE_CONCURRENCY)
+            .collect()
+            .await;
+        deletions.into_iter().collect::<Result<Vec<()>, _>>()?;
+
+        let dataset = get_dataset_by_id(&self.pool, workspace_id, payload.dataset_id).await?;
+        serde_json::to_value(CompactionResult {
+            dataset_id: dataset.id,
+            row_count: dataset.row_count,
+            row_groups: row_groups.unwrap_or_default(),
+            file_size_bytes,
+            sort_by: payload.sort_by,
+            removed_dataset_ids: payload.source_ids,
+        })
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize compaction result: {}", e)))
+    }
+}
+impl DatasetCompactionJobHandler {
+    /// Stream the sources' rows, sorted, to `key` as a multipart upload, encrypted with `data_key`
+    /// if any, and swap the merged dataset in for the sources. Returns the row groups and bytes
+    /// written, or `None` if the merged dataset was no longer PROCESSING, e.g. another attempt
+    /// already swapped it in.
+    async fn compact(
+        &self,
+        workspace_id: Uuid,
+        job: &DatasetCompactionJob,
+        key: &str,
+        data_key: Option<&Key<Aes256Gcm>>,
+    ) -> Result<Option<(usize, i64)>, DoubledeckerError> {
+        // Rows go missing from the merged file if a source was removed since the job was queued
+        let changed = || DoubledeckerError::BadRequest("Some datasets changed while this compaction ran".to_string());
+        let sources = get_datasets_by_ids(&self.pool, workspace_id, &job.source_ids).await?;
+        let ready = sources.iter().all(|d| d.status == DatasetStatus::Ready.as_str());
+        if sources.len() != job.source_ids.len() || !ready {
+            return Err(changed());
+        }
+        let expected_rows: i64 = sources.iter().map(|d| d.row_count).sum();
+
+        // Compaction rewrites data as stored, so no column is masked
+        let scope = QueryScope { dataset_ids: Some(job.source_ids.clone()), ..QueryScope::default() };
+        let order: Vec<String> = job.sort_by.iter().map(|c| format!("{} ASC NULLS LAST", c)).collect();
+        let sql = format!("SELECT * FROM royalty_data ORDER BY {}", order.join(", "));
+        let mut batches = self.engine.stream_scoped_analytics(workspace_id, &scope, &sql).await?;
+
+        let parquet_err = |e: parquet::errors::ParquetError| {
+            DoubledeckerError::Internal(format!("Failed to write compacted Parquet: {}", e))
+        };
+        let schema = batches.schema();
+        let properties = compaction_properties(&schema, &job.sort_by);
+        let mut writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties)).map_err(parquet_err)?;
+        // The row group being encoded and the bytes not yet uploaded count against the memory pool
+        let mut reservation = self.engine.reserve_memory("dataset compaction");
+        let mut upload = PartUpload::start(self.uploader.as_ref(), key, data_key).await?;
+        let written = async {
+            let mut row_count = 0;
+            while let Some(batch) = batches.next().await {
+                let batch = batch.map_err(DoubledeckerError::from_query_execution)?;
+                row_count += batch.num_rows() as i64;
+                writer.write(&batch).map_err(parquet_err)?;
+                reservation
+                    .try_resize(writer.memory_size() + upload.buffered())
+                    .map_err(DoubledeckerError::from_query_execution)?;
+                if writer.inner().len() >= COMPACTION_PART_BYTES {
+                    upload.write(&std::mem::take(writer.inner_mut())).await?;
+                }
+            }
+            if row_count != expected_rows {
+                return Err(changed());
+            }
+            let metadata = writer.finish().map_err(parquet_err)?;
+            upload.write(&std::mem::take(writer.inner_mut())).await?;
+            Ok((metadata.row_groups.len(), row_count))
+        }
+        .await;
+        let (row_groups, row_count) = match written {
+            Ok(written) => written,
+            Err(e) => {
+                upload.abort().await;
+                return Err(e);
+            }
+        };
+        let size = upload.finish().await? as i64;
+
+        let db_err = |e: sqlx::Error| DoubledeckerError::DatabaseError(e.to_string());
+        let mut tx = self.pool.begin().await.map_err(db_err)?;
+        let deleted = delete_datasets(&mut *tx, workspace_id, &job.source_ids).await?;
+        if deleted.len() != job.source_ids.len() {
+            return Err(changed());
+        }
+        set_dataset_file_size(&mut *tx, job.dataset_id, size).await?;
+        let processing = [DatasetStatus::Processing];
+        let ready = DatasetStatus::Ready;
+        if !transition_dataset_status(&mut *tx, job.dataset_id, &processing, ready, Some(row_count), None).await? {
+            return Ok(None);
+        }
+        tx.commit().await.map_err(db_err)?;
+        Ok(Some((row_groups, size)))
+    }
+}
+
+/// A file streamed to storage as a multipart upload, in parts of at least `COMPACTION_PART_BYTES`
+/// (S3 takes no smaller part but the last), sealed with a data key on the way if one is given.
+struct PartUpload<'a> {
+    storage: &'a dyn ObjectStorage,
+    key: &'a str,
+    upload_id: String,
+    encryptor: Option<FileEncryptor>,
+    pending: Vec<u8>,
+    parts: Vec<(i32, String)>,
+    written: usize,
+}
+
+impl<'a> PartUpload<'a> {
+    async fn start(
+        storage: &'a dyn ObjectStorage,
+        key: &'a str,
+        data_key: Option<&Key<Aes256Gcm>>,
+    ) -> Result<Self, DoubledeckerError> {
+        Ok(Self {
+            upload_id: storage.create_multipart_upload(key).await?,
+            storage,
+            key,
+            encryptor: data_key.map(FileEncryptor::new),
+            pending: Vec::new(),
+            parts: Vec::new(),
+            written: 0,
+        })
+    }
+
+    /// Bytes held until they fill a part.
+    fn buffered(&self) -> usize {
+        self.pending.len()
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), DoubledeckerError> {
+        self.written += data.len();
+        match &mut self.encryptor {
+            Some(encryptor) => self.pending.extend(encryptor.push(data)?),
+            None => self.pending.extend_from_slice(data),
+        }
+        if self.pending.len() >= COMPACTION_PART_BYTES {
+            self.upload_pending().await?;
+        }
+        Ok(())
+    }
+
+    async fn upload_pending(&mut self) -> Result<(), DoubledeckerError> {
+        let part_number = self.parts.len() as i32 + 1;
+        let content = std::mem::take(&mut self.pending);
+        let etag = self.storage.upload_part(self.key, &self.upload_id, part_number, content).await?;
+        self.parts.push((part_number, etag));
+        Ok(())
+    }
+
+    /// Upload the last part and assemble the file. Returns the bytes written, before encryption.
+    async fn finish(mut self) -> Result<usize, DoubledeckerError> {
+        if let Some(encryptor) = self.encryptor.take() {
+            let last = encryptor.finish()?;
+            self.pending.extend(last);
+        }
+        let assembled = async {
+            self.upload_pending().await?;
+            self.storage.complete_multipart_upload(self.key, &self.upload_id, &self.parts).await
+        }
+        .await;
+        if let Err(e) = assembled {
+            self.abort().await;
+            return Err(e);
+        }
+        Ok(self.written)
+    }
+
+    async fn abort(&self) {
+        let _ = self.storage.abort_multipart_upload(self.key, &self.upload_id).await;
+    }
+}
+
+/// ZSTD-compressed, with page-level min/max statistics and the sort order recorded in the footer.
+fn compaction_properties(schema: &SchemaRef, sort_by: &[String]) -> WriterProperties {
+    let sorting_columns = sort_by
+        .iter()
+        .filter_map(|c| schema.index_of(c).ok())
+        .map(|i| SortingColumn { column_idx: i as i32, descending: false, nulls_first: false })
+        .collect();
+    WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_size(COMPACTION_ROW_GROUP_ROWS)
+        .set_statistics_enabled(EnabledStatistics::Page)
+        .set_sorting_columns(Some(sorting_columns))
+        .build()
+}
@@ -0,0 +1,200 @@
+use crate::db::models::{DataConnection, PaginatedResponse, PaginationParams, WorkspaceRole};
+use crate::db::queries::{
+    create_data_connection, delete_data_connection, get_data_connection, list_data_connections,
+};
+use crate::engine::external::{
+    ExternalTable, MAX_EXTERNAL_ROWS, connect_postgres, fetch_postgres_table, list_postgres_tables,
+};
+use crate::server::dtos::DeleteResponse;
+use crate::server::dtos::analytics::AnalyticsQueryResponse;
+use crate::server::dtos::connections::*;
+use crate::server::extractors::{user_timezone, verify_workspace_access};
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::server::state::AppState;
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+use crate::utils::error::DoubledeckerError;
+use crate::utils::helpers::render_query_results;
+use axum::extract::{Path, State};
+use axum::Json;
+use sqlx::Connection;
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/connections",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = CreateConnectionRequest,
+    responses(
+        (status = 200, description = "Connection verified and saved", body = DataConnection),
+        (status = 400, description = "Invalid or unreachable connection string")
+    ),
+    tag = "connections"
+)]
+pub async fn create_connection_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<CreateConnectionRequest>,
+) -> Result<Json<DataConnection>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err(DoubledeckerError::BadRequest("Connection name cannot be empty".to_string()));
+    }
+    let conn_str = payload.connection_string.trim();
+    if !(conn_str.starts_with("postgres://") || conn_str.starts_with("postgresql://")) {
+        return Err(DoubledeckerError::BadRequest(
+            "Only postgres:// connection strings are supported".to_string(),
+        ));
+    }
+
+    // Fail fast on bad credentials instead of at first query
+    let conn = connect_postgres(conn_str).await?;
+    let _ = conn.close().await;
+
+    let encrypted = encrypt_secret(conn_str)?;
+    let connection = create_data_connection(
+        &state.db_pool,
+        workspace_id,
+        name,
+        "POSTGRES",
+        &encrypted,
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(connection))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/connections",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams
+    ),
+    responses(
+        (status = 200, description = "List workspace connections", body = PaginatedDataConnections)
+    ),
+    tag = "connections"
+)]
+pub async fn list_connections_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<DataConnection>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let connections =
+        list_data_connections(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    Ok(Json(connections))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/connections/{connection_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("connection_id" = Uuid, Path, description = "Connection ID")
+    ),
+    responses(
+        (status = 200, description = "Connection deleted", body = DeleteResponse)
+    ),
+    tag = "connections"
+)]
+pub async fn delete_connection_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, connection_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_data_connection(&state.db_pool, workspace_id, connection_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Connection not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Connection deleted successfully".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/connections/{connection_id}/tables",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("connection_id" = Uuid, Path, description = "Connection ID")
+    ),
+    responses(
+        (status = 200, description = "Tables and views in the external database", body = Vec<ExternalTable>)
+    ),
+    tag = "connections"
+)]
+pub async fn list_connection_tables_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, connection_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<ExternalTable>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let connection = get_data_connection(&state.db_pool, workspace_id, connection_id).await?;
+    let conn_str = decrypt_secret(&connection.encrypted_connection_string)?;
+    let tables = list_postgres_tables(&conn_str).await?;
+    Ok(Json(tables))
+}
+
+/// Pulls an external table into the analytical engine and runs a structured query over it.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/connections/{connection_id}/query",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("connection_id" = Uuid, Path, description = "Connection ID")
+    ),
+    request_body = ConnectionQueryRequest,
+    responses(
+        (status = 200, description = "Query executed over the external table", body = AnalyticsQueryResponse),
+        (status = 422, description = "A column is not in the pulled table, or the table is too large for the memory limit")
+    ),
+    tag = "connections"
+)]
+pub async fn query_connection_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, connection_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ConnectionQueryRequest>,
+) -> Result<Json<AnalyticsQueryResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+    let timezone = user_timezone(&state, auth_user.user_id).await?;
+
+    let connection = get_data_connection(&state.db_pool, workspace_id, connection_id).await?;
+    let conn_str = decrypt_secret(&connection.encrypted_connection_string)?;
+
+    let schema_name = payload.schema.as_deref().unwrap_or("public");
+    let max_rows = payload.max_rows.unwrap_or(MAX_EXTERNAL_ROWS).clamp(1, MAX_EXTERNAL_ROWS);
+    // Held until the response is rendered, so the pulled rows count against the engine's memory limit
+    let mut reservation = state.engine.reserve_memory("external_table");
+    let (schema, batches) = fetch_postgres_table(
+        &conn_str,
+        schema_name,
+        &payload.table,
+        max_rows,
+        &payload.inference,
+        &mut reservation,
+    )
+    .await?;
+
+    let sql = payload.to_sql(&schema)?;
+    let results = state
+        .engine
+        .execute_on_batches(&payload.table, schema, batches, &sql, timezone.as_deref())
+        .await?;
+
+    Ok(Json(render_query_results(results, timezone.as_deref(), payload.layout).await?))
+}
@@ -0,0 +1,292 @@
+use crate::db::models::{Dashboard, DashboardTile, PaginationParams, Pipeline, WorkspaceRole};
+use crate::db::queries::{
+    create_dashboard, delete_dashboard, get_dashboard, get_datasets_by_ids, list_dashboard_tiles, list_dashboards,
+    list_workspace_pipelines, update_dashboard,
+};
+use crate::engine::QueryScope;
+use crate::server::dtos::analytics::{AnalyticsQueryRequest, AnalyticsQueryResponse, ResultLayout};
+use crate::server::dtos::dashboards::*;
+use crate::server::dtos::DeleteResponse;
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::extractors::{
+    expand_segments, query_scope_for_role, user_timezone, verify_workspace_access, with_lookup_datasets,
+};
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::error::{DoubledeckerError, FieldError};
+use crate::utils::helpers::render_query_results;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::Json;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Check that every tile's pipeline and dataset belong to the workspace.
+async fn check_tiles(state: &AppState, workspace_id: Uuid, tiles: &[DashboardTileRequest]) -> Result<(), DoubledeckerError> {
+    if tiles.is_empty() {
+        return Ok(());
+    }
+    let pipelines = list_workspace_pipelines(&state.db_pool, workspace_id).await?;
+    let dataset_ids: Vec<Uuid> = tiles.iter().filter_map(|t| t.dataset_id).collect();
+    let datasets = get_datasets_by_ids(&state.db_pool, workspace_id, &dataset_ids).await?;
+
+    let mut errors = Vec::new();
+    for (i, tile) in tiles.iter().enumerate() {
+        if !pipelines.iter().any(|p| p.id == tile.pipeline_id) {
+            errors.push(FieldError::new(
+                &format!("tiles[{}].pipeline_id", i),
+                "not_found",
+                "not a pipeline of this workspace",
+            ));
+        }
+        if tile.dataset_id.is_some_and(|id| !datasets.iter().any(|d| d.id == id)) {
+            errors.push(FieldError::new(
+                &format!("tiles[{}].dataset_id", i),
+                "not_found",
+                "not a dataset of this workspace",
+            ));
+        }
+    }
+    if errors.is_empty() { Ok(()) } else { Err(DoubledeckerError::Validation(errors)) }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/dashboards",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = DashboardRequest,
+    responses(
+        (status = 200, description = "Dashboard created", body = DashboardResponse),
+        (status = 400, description = "A dashboard with this name already exists")
+    ),
+    tag = "dashboards"
+)]
+pub async fn create_dashboard_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<DashboardRequest>,
+) -> Result<Json<DashboardResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    check_tiles(&state, workspace_id, &payload.tiles).await?;
+
+    let dashboard = create_dashboard(
+        &state.db_pool,
+        workspace_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        &payload.tiles,
+        auth_user.user_id,
+    )
+    .await?;
+    let tiles = list_dashboard_tiles(&state.db_pool, dashboard.id).await?;
+    Ok(Json(DashboardResponse { dashboard, tiles }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/dashboards",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Dashboards of the workspace, without their tiles", body = PaginatedDashboards),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
+    ),
+    tag = "dashboards"
+)]
+pub async fn list_dashboards_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let dashboards = list_dashboards(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    etagged_json(&if_none_match, &dashboards)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/dashboards/{dashboard_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dashboard_id" = Uuid, Path, description = "Dashboard ID")
+    ),
+    responses(
+        (status = 200, description = "Dashboard with its tiles", body = DashboardResponse),
+        (status = 404, description = "Dashboard not found")
+    ),
+    tag = "dashboards"
+)]
+pub async fn get_dashboard_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dashboard_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DashboardResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dashboard = get_dashboard(&state.db_pool, workspace_id, dashboard_id).await?;
+    let tiles = list_dashboard_tiles(&state.db_pool, dashboard.id).await?;
+    Ok(Json(DashboardResponse { dashboard, tiles }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/dashboards/{dashboard_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dashboard_id" = Uuid, Path, description = "Dashboard ID")
+    ),
+    request_body = DashboardRequest,
+    responses(
+        (status = 200, description = "Dashboard and its tiles replaced", body = DashboardResponse),
+        (status = 404, description = "Dashboard not found")
+    ),
+    tag = "dashboards"
+)]
+pub async fn update_dashboard_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dashboard_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<DashboardRequest>,
+) -> Result<Json<DashboardResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    check_tiles(&state, workspace_id, &payload.tiles).await?;
+
+    let dashboard = update_dashboard(
+        &state.db_pool,
+        workspace_id,
+        dashboard_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        &payload.tiles,
+    )
+    .await?;
+    let tiles = list_dashboard_tiles(&state.db_pool, dashboard.id).await?;
+    Ok(Json(DashboardResponse { dashboard, tiles }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/dashboards/{dashboard_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dashboard_id" = Uuid, Path, description = "Dashboard ID")
+    ),
+    responses(
+        (status = 200, description = "Dashboard deleted; its pipelines are kept", body = DeleteResponse)
+    ),
+    tag = "dashboards"
+)]
+pub async fn delete_dashboard_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dashboard_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_dashboard(&state.db_pool, workspace_id, dashboard_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Dashboard not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Dashboard deleted successfully".to_string(),
+    }))
+}
+
+/// Run every tile of a dashboard at once and return the results together. The tiles share one
+/// query slot of the caller, and restricted columns are masked as for any query of the caller's
+/// role.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/dashboards/{dashboard_id}/data",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dashboard_id" = Uuid, Path, description = "Dashboard ID")
+    ),
+    responses(
+        (status = 200, description = "Results of all tiles", body = DashboardDataResponse),
+        (status = 404, description = "Dashboard not found"),
+        (status = 429, description = "Too many concurrent queries for this user", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "dashboards"
+)]
+pub async fn get_dashboard_data_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dashboard_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DashboardDataResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+    let dashboard = get_dashboard(&state.db_pool, workspace_id, dashboard_id).await?;
+    let tiles = list_dashboard_tiles(&state.db_pool, dashboard.id).await?;
+    let pipelines: HashMap<Uuid, Pipeline> = list_workspace_pipelines(&state.db_pool, workspace_id)
+        .await?
+        .into_iter()
+        .map(|p| (p.id, p))
+        .collect();
+    let scope = query_scope_for_role(&state, workspace_id, role)
+        .await?
+        .with_timezone(user_timezone(&state, auth_user.user_id).await?);
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+
+    let results = futures::future::join_all(
+        tiles
+            .iter()
+            .map(|tile| run_tile(&state, &dashboard, tile, pipelines.get(&tile.pipeline_id), scope.clone())),
+    )
+    .await;
+
+    let tiles = tiles
+        .into_iter()
+        .zip(results)
+        .map(|(tile, result)| {
+            let (result, error) = match result {
+                Ok(result) => (Some(result), None),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            DashboardTileData {
+                tile_id: tile.id,
+                title: tile
+                    .title
+                    .or_else(|| pipelines.get(&tile.pipeline_id).map(|p| p.name.clone()))
+                    .unwrap_or_default(),
+                pipeline_id: tile.pipeline_id,
+                dataset_id: tile.dataset_id,
+                visualization: tile.visualization,
+                result,
+                error,
+            }
+        })
+        .collect();
+    Ok(Json(DashboardDataResponse { dashboard_id: dashboard.id, tiles }))
+}
+
+async fn run_tile(
+    state: &AppState,
+    dashboard: &Dashboard,
+    tile: &DashboardTile,
+    pipeline: Option<&Pipeline>,
+    scope: QueryScope,
+) -> Result<AnalyticsQueryResponse, DoubledeckerError> {
+    // Deleting a pipeline removes its tiles, but it may have happened since the tiles were read
+    let pipeline = pipeline.ok_or_else(|| DoubledeckerError::NotFound("Pipeline not found".to_string()))?;
+    let request = AnalyticsQueryRequest::from(pipeline.query.0.clone());
+    let request = expand_segments(&state.db_pool, dashboard.workspace_id, request).await?;
+    let scope = QueryScope {
+        dataset_ids: tile.dataset_id.map(|id| vec![id]),
+        ..scope
+    };
+    let scope = with_lookup_datasets(&state.db_pool, dashboard.workspace_id, scope, request.lookup_dataset_ids()).await?;
+
+    let sql = request.to_safe_sql()?;
+    let batches = state.engine.execute_scoped_analytics(dashboard.workspace_id, &scope, &sql).await?;
+    render_query_results(batches, scope.timezone.as_deref(), ResultLayout::Rows).await
+}
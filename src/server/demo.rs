@@ -0,0 +1,106 @@
+use crate::db::models::Pipeline;
+use crate::db::queries::{create_pipeline, create_workspace};
+use crate::normalization::InferenceOptions;
+use crate::server::dtos::admin::DemoWorkspaceResponse;
+use crate::server::dtos::analytics::StructuredAnalyticsQuery;
+use crate::server::state::AppState;
+use crate::server::uploads::{DirectUpload, store_direct_upload};
+use crate::utils::error::DoubledeckerError;
+use uuid::Uuid;
+
+pub const DEMO_WORKSPACE_NAME: &str = "Demo royalties";
+
+/// Statements bundled with the binary: (filename, distributor source, content).
+const SAMPLE_FILES: [(&str, &str, &[u8]); 2] = [
+    ("distrokid_demo.csv", "distrokid", include_bytes!("../../sample_data/distrokid_demo.csv")),
+    ("tunecore_demo.csv", "tunecore", include_bytes!("../../sample_data/tunecore_demo.csv")),
+];
+
+/// Example pipelines: (name, description, dataset pattern, query).
+fn sample_pipelines() -> Vec<(&'static str, &'static str, Option<&'static str>, StructuredAnalyticsQuery)> {
+    let strings = |values: &[&str]| Some(values.iter().map(|v| v.to_string()).collect());
+    vec![
+        (
+            "Revenue by platform",
+            "Net revenue and streams per store; runs on every demo statement once it is processed",
+            Some("*_demo.csv"),
+            StructuredAnalyticsQuery {
+                dimensions: strings(&["platform"]),
+                metrics: strings(&["net_revenue", "quantity"]),
+                ..Default::default()
+            },
+        ),
+        (
+            "Top tracks",
+            "The ten highest-earning tracks",
+            None,
+            StructuredAnalyticsQuery {
+                dimensions: strings(&["title", "artist"]),
+                metrics: strings(&["net_revenue"]),
+                limit: Some(10),
+                ..Default::default()
+            },
+        ),
+        (
+            "Monthly revenue by territory",
+            "Net revenue per reporting month and country",
+            None,
+            StructuredAnalyticsQuery {
+                dimensions: strings(&["reporting_date", "territory"]),
+                metrics: strings(&["net_revenue"]),
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+/// Create a workspace for `owner_user_id` holding the bundled sample statements and example
+/// pipelines. The statements go through the normal upload path, so they are queryable as soon as
+/// ingestion has processed them.
+pub async fn seed_demo_workspace(
+    state: &AppState,
+    owner_user_id: Uuid,
+) -> Result<DemoWorkspaceResponse, DoubledeckerError> {
+    let workspace = create_workspace(&state.db_pool, owner_user_id, DEMO_WORKSPACE_NAME.to_string()).await?;
+
+    // Pipelines first, so the patterned one applies when the statements finish processing
+    let mut pipelines: Vec<Pipeline> = Vec::new();
+    for (name, description, pattern, query) in sample_pipelines() {
+        pipelines.push(
+            create_pipeline(&state.db_pool, workspace.id, name, Some(description), pattern, &query, owner_user_id).await?,
+        );
+    }
+
+    let mut datasets = Vec::new();
+    for (filename, source, content) in SAMPLE_FILES {
+        let upload = DirectUpload {
+            distributor_source: source.to_string(),
+            filename: filename.to_string(),
+            content: content.to_vec(),
+            strict: true,
+            inference: InferenceOptions::default(),
+        };
+        datasets.push(store_direct_upload(state, owner_user_id, workspace.id, upload).await?);
+    }
+
+    Ok(DemoWorkspaceResponse { workspace, datasets, pipelines })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalization::validate_csv;
+    use crate::server::dtos::analytics::AnalyticsQueryRequest;
+    use crate::server::validation::Validate;
+
+    #[test]
+    fn test_samples_are_clean_and_pipelines_valid() {
+        for (filename, _, content) in SAMPLE_FILES {
+            assert!(validate_csv(content).is_clean(), "{} has validation problems", filename);
+        }
+        for (name, _, _, query) in sample_pipelines() {
+            let errors = AnalyticsQueryRequest::from(query).validate();
+            assert!(errors.is_empty(), "{}: {:?}", name, errors);
+        }
+    }
+}
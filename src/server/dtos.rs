@@ -1,8 +1,21 @@
+pub mod admin;
+pub mod alerts;
 pub mod analytics;
 pub mod auth;
 pub mod catalog;
+pub mod comments;
 pub mod common;
+pub mod connections;
+pub mod dashboards;
+pub mod events;
+pub mod folders;
 pub mod payees;
+pub mod pipelines;
+pub mod public;
+pub mod quality;
+pub mod schedules;
+pub mod search;
+pub mod segments;
 pub mod splits;
 pub mod uploads;
 pub mod workspaces;
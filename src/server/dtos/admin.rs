@@ -0,0 +1,102 @@
+use crate::db::models::{DailyQueryCount, Pipeline, PlatformTotals, Workspace};
+use crate::server::dtos::common::DatasetResponse;
+use crate::server::validation::{Validate, check_max_length, require_non_blank};
+use crate::utils::error::FieldError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UnlockAccountRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UnlockAccountResponse {
+    pub email: String,
+    /// Whether the account had recorded failures or an active lockout
+    pub was_locked: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SeedDemoRequest {
+    /// Existing account that will own the demo workspace
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DemoWorkspaceResponse {
+    pub workspace: Workspace,
+    /// The sample statements, queued for ingestion
+    pub datasets: Vec<DatasetResponse>,
+    /// Example pipelines over the samples
+    pub pipelines: Vec<Pipeline>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SuspendUserRequest {
+    /// Shown to the user in the 403 response
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct AdminMetricsParams {
+    /// Days of query volume to return, 1-365 (default 30)
+    pub days: Option<i32>,
+    /// Maximum entries in ranked lists, 1-100 (default 20)
+    pub limit: Option<i64>,
+}
+
+impl AdminMetricsParams {
+    pub fn effective_days(&self) -> i32 {
+        self.days.unwrap_or(30)
+    }
+
+    pub fn effective_limit(&self) -> i64 {
+        self.limit.unwrap_or(20)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminOverviewResponse {
+    pub totals: PlatformTotals,
+    /// One entry per UTC day, oldest first
+    pub queries_per_day: Vec<DailyQueryCount>,
+}
+
+impl Validate for AdminMetricsParams {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.days.is_some_and(|d| !(1..=365).contains(&d)) {
+            errors.push(FieldError::new("days", "out_of_range", "days must be between 1 and 365"));
+        }
+        if self.limit.is_some_and(|l| !(1..=100).contains(&l)) {
+            errors.push(FieldError::new("limit", "out_of_range", "limit must be between 1 and 100"));
+        }
+        errors
+    }
+}
+
+impl Validate for SuspendUserRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_blank(&mut errors, "reason", &self.reason);
+        check_max_length(&mut errors, "reason", &self.reason, 1000);
+        errors
+    }
+}
+
+impl Validate for UnlockAccountRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_blank(&mut errors, "email", &self.email);
+        errors
+    }
+}
+
+impl Validate for SeedDemoRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_blank(&mut errors, "email", &self.email);
+        errors
+    }
+}
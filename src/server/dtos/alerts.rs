@@ -0,0 +1,126 @@
+use crate::db::models::{AlertAggregate, NotifyChannel, ThresholdOp};
+use crate::server::dtos::schedules::is_webhook_url;
+use crate::server::validation::{MAX_NAME_LENGTH, Validate, check_max_length, check_name};
+use crate::utils::error::FieldError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most addresses an alert can email.
+pub const MAX_ALERT_EMAILS: usize = 20;
+
+/// Body of both creating and replacing an alert. Replacing resets it to `OK`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AlertRequest {
+    pub name: String,
+    pub aggregate: AlertAggregate,
+    /// Result column to aggregate; required unless `aggregate` is `ROW_COUNT`
+    pub column: Option<String>,
+    pub op: ThresholdOp,
+    pub threshold: f64,
+    /// Addresses emailed when the alert fires (needs EMAIL_API_KEY and EMAIL_FROM on the server)
+    #[serde(default)]
+    pub emails: Vec<String>,
+    /// Webhook channel posted to when the alert fires
+    pub channel: Option<NotifyChannel>,
+    /// Incoming webhook URL for `channel`. Omit on update to keep the current one. Never returned.
+    pub webhook_url: Option<String>,
+    /// Also notify when the condition stops holding
+    #[serde(default = "default_true")]
+    pub notify_on_resolve: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Outcome of evaluating one alert after a scheduled run, reported in the job's result.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AlertEvaluation {
+    pub alert_id: Uuid,
+    /// `OK` or `FIRING`
+    pub state: String,
+    pub value: Option<f64>,
+    /// Whether the run changed the state and notifications went out
+    pub notified: bool,
+    pub error: Option<String>,
+}
+
+impl Validate for AlertRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        match &self.column {
+            Some(column) => check_name(&mut errors, "column", column),
+            None if self.aggregate != AlertAggregate::RowCount => {
+                errors.push(FieldError::new("column", "required", "required unless aggregate is ROW_COUNT"));
+            }
+            None => {}
+        }
+        if !self.threshold.is_finite() {
+            errors.push(FieldError::new("threshold", "invalid", "must be a finite number"));
+        }
+        if self.emails.len() > MAX_ALERT_EMAILS {
+            errors.push(FieldError::new(
+                "emails",
+                "too_many",
+                format!("an alert can email at most {} addresses", MAX_ALERT_EMAILS),
+            ));
+        }
+        for (i, email) in self.emails.iter().enumerate() {
+            let field = format!("emails[{}]", i);
+            if !email.trim().contains('@') {
+                errors.push(FieldError::new(&field, "invalid_format", "Invalid email format"));
+            }
+            check_max_length(&mut errors, &field, email, MAX_NAME_LENGTH);
+        }
+        if let (Some(channel), Some(url)) = (self.channel, &self.webhook_url)
+            && !is_webhook_url(channel, url.trim())
+        {
+            errors.push(FieldError::new(
+                "webhook_url",
+                "invalid",
+                format!("must be an https incoming webhook URL of {}", channel.as_str()),
+            ));
+        }
+        if self.channel.is_none() && self.webhook_url.is_some() {
+            errors.push(FieldError::new("channel", "required", "required with a webhook_url"));
+        }
+        if self.emails.is_empty() && self.channel.is_none() {
+            errors.push(FieldError::new("emails", "required", "an alert needs emails or a webhook channel"));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_request_validation() {
+        let request: AlertRequest = serde_json::from_value(serde_json::json!({
+            "name": "Errors",
+            "aggregate": "SUM",
+            "op": "GT",
+            "threshold": 10,
+            "emails": ["ops@example.com", "nobody"]
+        }))
+        .unwrap();
+        let fields: Vec<String> = request.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["column", "emails[1]"]);
+
+        let request: AlertRequest = serde_json::from_value(serde_json::json!({
+            "name": "Missing SKUs",
+            "aggregate": "ROW_COUNT",
+            "op": "GT",
+            "threshold": 0,
+            "webhook_url": "https://hooks.slack.com/services/T000"
+        }))
+        .unwrap();
+        let fields: Vec<String> = request.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["channel", "emails"]);
+    }
+}
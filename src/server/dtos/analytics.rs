@@ -1,9 +1,48 @@
-use crate::utils::error::DoubledeckerError;
+use crate::engine::{QueryMetrics, lookup_table_name, referenced_tables};
+use crate::normalization::headers::{closest_column, resolve_column};
+use crate::normalization::unified_royalty_schema;
+use crate::server::validation::{MAX_NAME_LENGTH, Validate, check_max_length};
+use crate::utils::error::{DoubledeckerError, FieldError, FilterTypeMismatch};
 use chrono::NaiveDate;
+use datafusion::arrow::datatypes::DataType;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Columns usable as structured-query dimensions and filter fields.
+pub const ALLOWED_DIMENSIONS: [&str; 11] = [
+    "isrc",
+    "upc",
+    "title",
+    "artist",
+    "album",
+    "platform",
+    "territory",
+    "country",
+    "transaction_type",
+    "reporting_date",
+    "currency",
+];
+
+/// Metrics a structured query can aggregate.
+pub const SUPPORTED_METRICS: [&str; 2] = ["net_revenue", "quantity"];
+
+/// Numeric columns a structured query can bin into buckets.
+pub const BINNABLE_COLUMNS: [&str; 2] = ["net_revenue", "quantity"];
+
+/// Lookup datasets one structured query can join.
+pub const MAX_LOOKUPS: usize = 3;
+
+/// Upper bound on the steps of one multi-step query.
+pub const MAX_QUERY_STEPS: usize = 20;
+
+/// Tables of every query session, which step names cannot shadow.
+const RESERVED_TABLE_NAMES: [&str; 2] = ["royalty_data", "cascading_splits"];
+
+/// Upper bound on the structured-query `limit`.
+pub const MAX_STRUCTURED_LIMIT: usize = 100_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DateRangeFilter {
     pub from: Option<NaiveDate>,
@@ -30,53 +69,874 @@ pub struct QueryFilter {
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+impl QueryFilter {
+    /// SQL condition of the filter, with the value read as the column's type in `royalty_data`.
+    /// Dates are written as date literals, also from `YYYY/MM/DD` or a midnight timestamp, and
+    /// `like` on a non-text column matches its text form. A value that cannot be read as the
+    /// column's type is a `FilterTypeMismatch` rather than a failure inside the query engine.
+    pub fn to_sql(&self) -> Result<String, DoubledeckerError> {
+        let column_type = unified_royalty_schema()
+            .field_with_name(&self.field)
+            .map(|f| f.data_type().clone())
+            // Columns outside the unified schema, e.g. `country`, are text
+            .unwrap_or(DataType::Utf8);
+        self.to_sql_on(&self.field, &column_type)
+    }
+
+    /// SQL condition of the filter on `column`, an SQL reference to a column of `column_type`.
+    pub fn to_sql_on(&self, column: &str, column_type: &DataType) -> Result<String, DoubledeckerError> {
+        let text = format!("'{}'", self.value.replace('\'', "''"));
+        let operator = match self.operator {
+            FilterOperator::Eq | FilterOperator::In => "=",
+            FilterOperator::Ne => "!=",
+            FilterOperator::Gt => ">",
+            FilterOperator::Gte => ">=",
+            FilterOperator::Lt => "<",
+            FilterOperator::Lte => "<=",
+            FilterOperator::Like if *column_type == DataType::Utf8 => "LIKE",
+            FilterOperator::Like => return Ok(format!("CAST({} AS VARCHAR) LIKE {}", column, text)),
+        };
+
+        let value = match column_type {
+            DataType::Utf8 => Some(text),
+            DataType::Date32 => coerce_date(&self.value).map(|d| format!("DATE '{}'", d)),
+            t if t.is_numeric() => {
+                let number = self.value.trim();
+                number.parse::<f64>().ok().filter(|n| n.is_finite()).map(|_| number.to_string())
+            }
+            _ => Some(text),
+        };
+        let value = value.ok_or_else(|| {
+            DoubledeckerError::FilterTypeMismatch(Box::new(FilterTypeMismatch {
+                column: self.field.clone(),
+                column_type: column_type.to_string(),
+                value: self.value.clone(),
+                value_type: value_type(&self.value).to_string(),
+            }))
+        })?;
+        Ok(format!("{} {} {}", column, operator, value))
+    }
+}
+
+/// A date from `YYYY-MM-DD`, `YYYY/MM/DD` or a timestamp at midnight; `None` when reading the value
+/// as a date would change what it means, e.g. a month or a time of day.
+fn coerce_date(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    let date = ["%Y-%m-%d", "%Y/%m/%d"].iter().find_map(|f| NaiveDate::parse_from_str(value, f).ok());
+    date.or_else(|| {
+        let timestamp = value.trim_end_matches('Z');
+        ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+            .iter()
+            .find_map(|f| chrono::NaiveDateTime::parse_from_str(timestamp, f).ok())
+            .filter(|t| t.time() == chrono::NaiveTime::MIN)
+            .map(|t| t.date())
+    })
+}
+
+/// What a filter value reads as, for type mismatch errors.
+fn value_type(value: &str) -> &'static str {
+    let value = value.trim();
+    if value.parse::<f64>().is_ok() {
+        "number"
+    } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+        "boolean"
+    } else {
+        "string"
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct StructuredAnalyticsQuery {
     pub date_range: Option<DateRangeFilter>,
     pub dimensions: Option<Vec<String>>,
     pub metrics: Option<Vec<String>>,
     pub filters: Option<Vec<QueryFilter>>,
+    /// Saved segments of the workspace whose filters rows must also meet, added when the query runs
+    pub segment_ids: Option<Vec<Uuid>>,
     pub limit: Option<usize>,
+    /// Report rows that repeat the same key instead of aggregating; excludes `dimensions` and `metrics`
+    pub find_duplicates: Option<FindDuplicates>,
+    /// Bucket columns derived from numeric columns; their aliases can be used as dimensions
+    pub bins: Option<Vec<BinSpec>>,
+    /// Keep only the first `n` result rows of each group, e.g. the top 3 tracks per platform
+    pub top_n_per_group: Option<TopNPerGroup>,
+    /// Running totals or averages added as extra result columns
+    pub cumulative: Option<Vec<CumulativeSpec>>,
+    /// Each row's share of a total, as a percentage, added as extra result columns
+    pub percent_of_total: Option<Vec<PercentOfTotal>>,
+    /// Columns added to each row from another dataset, matched on key columns
+    pub lookups: Option<Vec<LookupSpec>>,
+    /// Output column names for metrics, e.g. `{"net_revenue": "revenue"}`. Unnamed metrics come back
+    /// as `total_revenue` (`net_revenue`) and `total_streams` (`quantity`); later operations such as
+    /// `cumulative` refer to metrics by these names
+    pub metric_aliases: Option<BTreeMap<String, String>>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+/// Enrich rows with columns of another, usually small, dataset of the workspace, e.g. the artist
+/// name from a reference upload keyed by `isrc`. Added columns are named `{prefix}_{column}` and can
+/// be used as dimensions; rows without a match get NULLs.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LookupSpec {
+    /// Ready dataset of the same workspace to read the added columns from
+    pub dataset_id: Uuid,
+    /// Columns whose values must match in both datasets
+    pub on: Vec<String>,
+    /// Columns of the lookup dataset to add
+    pub add_columns: Vec<String>,
+    pub prefix: String,
+}
+
+impl LookupSpec {
+    fn added_name(&self, column: &str) -> String {
+        format!("{}_{}", self.prefix, column)
+    }
+
+    /// The lookup dataset reduced to one row per key, so a key repeated in it cannot duplicate rows.
+    fn keyed_table(&self) -> String {
+        let columns: Vec<String> = self
+            .on
+            .iter()
+            .cloned()
+            .chain(self.add_columns.iter().map(|c| format!("MAX({c}) AS {c}")))
+            .collect();
+        format!(
+            "(SELECT {} FROM {} GROUP BY {})",
+            columns.join(", "),
+            lookup_table_name(self.dataset_id),
+            self.on.join(", ")
+        )
+    }
+
+    fn validate(&self, field: &str, errors: &mut Vec<FieldError>) {
+        if self.on.is_empty() {
+            errors.push(FieldError::new(&format!("{}.on", field), "required", "lookup needs at least one key column"));
+        }
+        if self.add_columns.is_empty() {
+            errors.push(FieldError::new(
+                &format!("{}.add_columns", field),
+                "required",
+                "lookup needs at least one column to add",
+            ));
+        }
+        for (name, columns) in [("on", &self.on), ("add_columns", &self.add_columns)] {
+            for (i, c) in columns.iter().enumerate() {
+                if !ALLOWED_DIMENSIONS.contains(&c.as_str()) {
+                    errors.push(FieldError::new(
+                        &format!("{}.{}[{}]", field, name, i),
+                        "not_allowed",
+                        unknown_column(format!("Column '{}' is not allowed", c), c, &ALLOWED_DIMENSIONS),
+                    ));
+                }
+            }
+        }
+        for c in &self.add_columns {
+            check_alias(errors, &format!("{}.prefix", field), &self.added_name(c));
+        }
+    }
+}
+
+/// A numeric result column as a percentage of its total over all rows, or over each partition,
+/// e.g. each platform's share of a track's revenue.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PercentOfTotal {
+    /// Numeric result column to take the share of
+    pub column: String,
+    /// Result columns whose values each have their own total; empty for the grand total
+    #[serde(default)]
+    pub partition_by: Vec<String>,
+    pub alias: String,
+}
+
+impl PercentOfTotal {
+    fn to_sql(&self) -> String {
+        let partition = if self.partition_by.is_empty() {
+            String::new()
+        } else {
+            format!("PARTITION BY {}", self.partition_by.join(", "))
+        };
+        // A zero total has no shares; NULL rather than a division error
+        format!(
+            "CAST({column} AS DOUBLE) * 100.0 / NULLIF(SUM({column}) OVER ({partition}), 0) AS {alias}",
+            column = self.column,
+            alias = self.alias,
+        )
+    }
+
+    fn validate(&self, query: &StructuredAnalyticsQuery, field: &str, errors: &mut Vec<FieldError>) {
+        if !query.is_result_measure(&self.column) {
+            errors.push(FieldError::new(
+                &format!("{}.column", field),
+                "not_allowed",
+                unknown_column(
+                    format!("'{}' is not a numeric column of this query's results", self.column),
+                    &self.column,
+                    &query.result_measure_names(),
+                ),
+            ));
+        }
+        for (i, c) in self.partition_by.iter().enumerate() {
+            if !query.is_result_dimension(c) {
+                errors.push(FieldError::new(
+                    &format!("{}.partition_by[{}]", field, i),
+                    "not_allowed",
+                    unknown_column(
+                        format!("'{}' is not a dimension of this query's results", c),
+                        c,
+                        &query.result_dimension_names(),
+                    ),
+                ));
+            }
+        }
+        check_alias(errors, &format!("{}.alias", field), &self.alias);
+    }
+}
+
+/// A running total (or average) of a numeric result column, e.g. month-to-date revenue when the
+/// query is grouped by `reporting_date`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CumulativeSpec {
+    /// Numeric result column to accumulate: a metric's output when aggregating, else `net_revenue` or `quantity`
+    pub column: String,
+    /// Result column the running value follows, e.g. `reporting_date`
+    pub order_by: String,
+    /// Result columns whose values each restart the running value
+    #[serde(default)]
+    pub partition_by: Vec<String>,
+    #[serde(default)]
+    pub function: CumulativeFunction,
+    pub alias: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CumulativeFunction {
+    #[default]
+    Sum,
+    Avg,
+}
+
+impl CumulativeSpec {
+    fn to_sql(&self) -> String {
+        let function = match self.function {
+            CumulativeFunction::Sum => "SUM",
+            CumulativeFunction::Avg => "AVG",
+        };
+        let partition = if self.partition_by.is_empty() {
+            String::new()
+        } else {
+            format!("PARTITION BY {} ", self.partition_by.join(", "))
+        };
+        format!(
+            "{function}({column}) OVER ({partition}ORDER BY {order} ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS {alias}",
+            column = self.column,
+            order = self.order_by,
+            alias = self.alias,
+        )
+    }
+
+    /// ` ORDER BY` matching the window, so each partition's running value reads top to bottom.
+    fn order_stmt(&self) -> String {
+        let mut keys = self.partition_by.clone();
+        keys.push(self.order_by.clone());
+        format!(" ORDER BY {}", keys.join(", "))
+    }
+
+    fn validate(&self, query: &StructuredAnalyticsQuery, field: &str, errors: &mut Vec<FieldError>) {
+        if !query.is_result_measure(&self.column) {
+            errors.push(FieldError::new(
+                &format!("{}.column", field),
+                "not_allowed",
+                unknown_column(
+                    format!("'{}' is not a numeric column of this query's results", self.column),
+                    &self.column,
+                    &query.result_measure_names(),
+                ),
+            ));
+        }
+        if !query.is_result_column(&self.order_by) {
+            errors.push(FieldError::new(
+                &format!("{}.order_by", field),
+                "not_allowed",
+                unknown_column(
+                    format!("'{}' is not a column of this query's results", self.order_by),
+                    &self.order_by,
+                    &query.result_column_names(),
+                ),
+            ));
+        }
+        for (i, c) in self.partition_by.iter().enumerate() {
+            if !query.is_result_dimension(c) {
+                errors.push(FieldError::new(
+                    &format!("{}.partition_by[{}]", field, i),
+                    "not_allowed",
+                    unknown_column(
+                        format!("'{}' is not a dimension of this query's results", c),
+                        c,
+                        &query.result_dimension_names(),
+                    ),
+                ));
+            }
+        }
+        check_alias(errors, &format!("{}.alias", field), &self.alias);
+    }
+}
+
+/// Ranks result rows within groups and keeps the first `n` of each. Applies after aggregation, so
+/// with `dimensions: ["platform", "title"]` and `metrics: ["net_revenue"]`, grouping by `platform`
+/// and ordering by `total_revenue` gives each platform's best-earning titles. Rows gain a
+/// `group_rank` column starting at 1.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TopNPerGroup {
+    /// Columns that define a group; dimensions of the query
+    pub group_columns: Vec<String>,
+    /// Result column to rank by: a dimension, a metric's output (`total_revenue`, `total_streams`),
+    /// or, without metrics, `net_revenue` or `quantity`
+    pub order_column: String,
+    /// Rows kept per group
+    pub n: usize,
+    /// Rank smallest first instead of largest first
+    #[serde(default)]
+    pub ascending: bool,
+}
+
+impl TopNPerGroup {
+    /// `ROW_NUMBER()` over the groups of `base`, filtered to the first `n`; ties are broken arbitrarily.
+    fn wrap(&self, base: &str) -> String {
+        let direction = if self.ascending { "ASC" } else { "DESC" };
+        let groups = self.group_columns.join(", ");
+        format!(
+            "SELECT * FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY {groups} ORDER BY {order} {direction}) AS group_rank \
+             FROM ({base}) grouped) ranked WHERE group_rank <= {n} ORDER BY {groups}, group_rank",
+            order = self.order_column,
+            n = self.n,
+        )
+    }
+
+    fn validate(&self, query: &StructuredAnalyticsQuery, errors: &mut Vec<FieldError>) {
+        if self.group_columns.is_empty() {
+            errors.push(FieldError::new(
+                "top_n_per_group.group_columns",
+                "required",
+                "top_n_per_group needs at least one group column",
+            ));
+        }
+        for (i, c) in self.group_columns.iter().enumerate() {
+            if !query.is_result_dimension(c) {
+                errors.push(FieldError::new(
+                    &format!("top_n_per_group.group_columns[{}]", i),
+                    "not_allowed",
+                    unknown_column(
+                        format!("'{}' is not a dimension of this query", c),
+                        c,
+                        &query.result_dimension_names(),
+                    ),
+                ));
+            }
+        }
+
+        if !query.is_result_column(&self.order_column) {
+            errors.push(FieldError::new(
+                "top_n_per_group.order_column",
+                "not_allowed",
+                unknown_column(
+                    format!("'{}' is not a column of this query's results", self.order_column),
+                    &self.order_column,
+                    &query.result_column_names(),
+                ),
+            ));
+        }
+        if self.n == 0 || self.n > MAX_STRUCTURED_LIMIT {
+            errors.push(FieldError::new(
+                "top_n_per_group.n",
+                "out_of_range",
+                format!("n must be between 1 and {}", MAX_STRUCTURED_LIMIT),
+            ));
+        }
+    }
+}
+
+/// `message` for a column reference that matched nothing, with the closest of `columns` as a hint.
+pub(crate) fn unknown_column<S: AsRef<str>>(message: String, name: &str, columns: &[S]) -> String {
+    match closest_column(name, columns) {
+        Some(c) => format!("{}; did you mean '{}'?", message, c),
+        None => message,
+    }
+}
+
+/// Names of derived columns: a lowercase identifier that does not shadow a column of `royalty_data`
+/// or a metric's output.
+fn check_alias(errors: &mut Vec<FieldError>, field: &str, alias: &str) {
+    let valid = alias.starts_with(|c: char| c.is_ascii_lowercase())
+        && alias.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    let shadows = ALLOWED_DIMENSIONS.contains(&alias)
+        || BINNABLE_COLUMNS.contains(&alias)
+        || SUPPORTED_METRICS.iter().filter_map(|m| metric_alias(m)).any(|a| a == alias);
+    if !valid || shadows {
+        errors.push(FieldError::new(
+            field,
+            "invalid_format",
+            "alias must start with a lowercase letter, use only lowercase letters, digits and underscores, and not shadow an existing column",
+        ));
+    }
+}
+
+/// Output column a metric is aggregated into when the query does not name it.
+fn metric_alias(metric: &str) -> Option<&'static str> {
+    match metric {
+        "net_revenue" => Some("total_revenue"),
+        "quantity" => Some("total_streams"),
+        _ => None,
+    }
+}
+
+/// Buckets a numeric column, e.g. earnings tiers, so results can be grouped by range. Give either
+/// `edges` or `width`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BinSpec {
+    /// `net_revenue` or `quantity`
+    pub column: String,
+    /// Ascending boundaries. `[1, 10]` gives the buckets `< 1`, `1-10` and `>= 10`; lower bounds are inclusive.
+    pub edges: Option<Vec<f64>>,
+    /// Equal-width buckets, each labelled by its lower bound (`width: 5` puts 7.2 in bucket 5)
+    pub width: Option<f64>,
+    /// Name of the bucket column; lowercase letters, digits and underscores
+    pub alias: String,
+}
+
+impl BinSpec {
+    /// SQL expression for the bucket of each row; NULL values stay NULL.
+    fn to_sql(&self) -> String {
+        let value = format!("CAST({} AS DOUBLE)", self.column);
+        match (&self.edges, self.width) {
+            (Some(edges), _) => {
+                let mut cases = vec![format!("WHEN {value} IS NULL THEN NULL")];
+                cases.push(format!("WHEN {value} < {} THEN '< {}'", edges[0], edges[0]));
+                for pair in edges.windows(2) {
+                    cases.push(format!("WHEN {value} < {} THEN '{}-{}'", pair[1], pair[0], pair[1]));
+                }
+                let last = edges[edges.len() - 1];
+                format!("CASE {} ELSE '>= {}' END", cases.join(" "), last)
+            }
+            (None, Some(width)) => format!("FLOOR({value} / {width}) * {width}"),
+            (None, None) => "NULL".to_string(),
+        }
+    }
+
+    fn validate(&self, field: &str, errors: &mut Vec<FieldError>) {
+        if !BINNABLE_COLUMNS.contains(&self.column.as_str()) {
+            errors.push(FieldError::new(
+                &format!("{}.column", field),
+                "not_allowed",
+                unknown_column(
+                    format!("Column '{}' cannot be binned; use one of {}", self.column, BINNABLE_COLUMNS.join(", ")),
+                    &self.column,
+                    &BINNABLE_COLUMNS,
+                ),
+            ));
+        }
+        check_alias(errors, &format!("{}.alias", field), &self.alias);
+        match (&self.edges, self.width) {
+            (Some(edges), None) => {
+                if edges.is_empty()
+                    || edges.iter().any(|e| !e.is_finite())
+                    || edges.windows(2).any(|pair| pair[0] >= pair[1])
+                {
+                    errors.push(FieldError::new(
+                        &format!("{}.edges", field),
+                        "invalid_format",
+                        "edges must be finite numbers in strictly ascending order",
+                    ));
+                }
+            }
+            (None, Some(width)) => {
+                if !width.is_finite() || width <= 0.0 {
+                    errors.push(FieldError::new(&format!("{}.width", field), "out_of_range", "width must be positive"));
+                }
+            }
+            _ => errors.push(FieldError::new(field, "invalid_format", "give exactly one of edges or width")),
+        }
+    }
+}
+
+/// Data-quality check for rows sharing the same values in `columns` (e.g. a statement line
+/// imported twice). Date range and filters narrow the rows checked.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FindDuplicates {
+    /// Key columns, from the allowed dimensions
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub output: DuplicateOutput,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateOutput {
+    /// One row per duplicated key with its `duplicate_count`, most repeated first
+    #[default]
+    Groups,
+    /// Every row whose key is duplicated, with the key's `duplicate_count`, grouped by key
+    Rows,
+}
+
+impl FindDuplicates {
+    /// Groups come from `GROUP BY <columns> HAVING COUNT(*) > 1`; rows are matched back to them with
+    /// `IS NOT DISTINCT FROM` so rows with a NULL key component are still caught.
+    fn to_sql(&self, where_stmt: &str, limit: usize) -> Result<String, DoubledeckerError> {
+        if let Some(c) = self.columns.iter().find(|c| !ALLOWED_DIMENSIONS.contains(&c.as_str())) {
+            return Err(DoubledeckerError::BadRequest(format!(
+                "Duplicate key column '{}' is not allowed",
+                c
+            )));
+        }
+        let keys = self.columns.join(", ");
+        let groups = format!(
+            "SELECT {keys}, COUNT(*) AS duplicate_count FROM royalty_data{where_stmt} GROUP BY {keys} HAVING COUNT(*) > 1"
+        );
+        let sql = match self.output {
+            DuplicateOutput::Groups => {
+                format!("{groups} ORDER BY duplicate_count DESC, {keys} LIMIT {limit}")
+            }
+            DuplicateOutput::Rows => {
+                let on = self
+                    .columns
+                    .iter()
+                    .map(|c| format!("r.{c} IS NOT DISTINCT FROM d.{c}"))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                let order = self.columns.iter().map(|c| format!("r.{c}")).collect::<Vec<_>>().join(", ");
+                format!(
+                    "SELECT r.*, d.duplicate_count FROM (SELECT * FROM royalty_data{where_stmt}) r JOIN ({groups}) d ON {on} \
+                     ORDER BY d.duplicate_count DESC, {order} LIMIT {limit}"
+                )
+            }
+        };
+        Ok(sql)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct AnalyticsQueryRequest {
     pub sql: Option<String>,
     #[serde(flatten)]
     pub structured: Option<StructuredAnalyticsQuery>,
     pub dataset_ids: Option<Vec<Uuid>>,
+    /// `rows` (default) or `columns`
+    #[serde(default)]
+    pub layout: ResultLayout,
+    /// Named intermediate results, each readable as a table by the steps after it; the last
+    /// step's rows are the result. Excludes `sql` and the structured parameters
+    pub steps: Option<Vec<QueryStep>>,
+    /// Quick look while building a query: run it on a sample of the workspace's rows and return
+    /// only the first rows of the result, so sorts and aggregations stay fast on large datasets.
+    /// Totals are over the sample; run again without `preview` for the full result
+    #[serde(default)]
+    pub preview: bool,
 }
 
-impl AnalyticsQueryRequest {
-    pub fn to_safe_sql(&self) -> Result<String, DoubledeckerError> {
-        if let Some(ref sql) = self.sql {
-            return Ok(sql.clone());
+/// One named intermediate result of a multi-step query, given as either `sql` or `structured`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueryStep {
+    /// Table name later steps read this step's rows under
+    pub name: String,
+    /// SQL over the workspace's tables and earlier steps
+    pub sql: Option<String>,
+    /// Structured query over `royalty_data`; its `limit` (default 100) applies to the step
+    pub structured: Option<StructuredAnalyticsQuery>,
+}
+
+impl QueryStep {
+    pub fn kind(&self) -> &'static str {
+        if self.structured.is_some() { "structured" } else { "sql" }
+    }
+
+    fn to_sql(&self) -> Result<String, DoubledeckerError> {
+        match (&self.sql, &self.structured) {
+            (Some(sql), None) => Ok(sql.trim().trim_end_matches(';').to_string()),
+            (None, Some(structured)) => structured.to_sql(),
+            _ => Err(DoubledeckerError::BadRequest(format!(
+                "Step '{}' needs exactly one of 'sql' or 'structured'",
+                self.name
+            ))),
         }
+    }
+}
 
-        let structured = self.structured.as_ref().ok_or_else(|| {
-            DoubledeckerError::BadRequest(
-                "Either 'sql' or structured query parameters ('dimensions', 'metrics', etc.) must be provided"
-                    .to_string(),
-            )
-        })?;
+/// Problems with a multi-step query: step names must be unique identifiers that shadow no table,
+/// and a step may only read steps before it.
+fn check_steps(steps: &[QueryStep], errors: &mut Vec<FieldError>) {
+    if steps.is_empty() {
+        errors.push(FieldError::new("steps", "required", "steps cannot be empty"));
+    } else if steps.len() > MAX_QUERY_STEPS {
+        errors.push(FieldError::new(
+            "steps",
+            "too_many",
+            format!("At most {} steps are allowed per query", MAX_QUERY_STEPS),
+        ));
+    }
+
+    for (i, step) in steps.iter().enumerate() {
+        let field = format!("steps[{}]", i);
+        let valid_name = step.name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+            && step.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            errors.push(FieldError::new(
+                &format!("{}.name", field),
+                "invalid_format",
+                "step names must use only lowercase letters, digits and underscores, and not start with a digit",
+            ));
+        } else if RESERVED_TABLE_NAMES.contains(&step.name.as_str()) || step.name.starts_with("lookup_") {
+            errors.push(FieldError::new(
+                &format!("{}.name", field),
+                "reserved",
+                format!("'{}' is the name of a workspace table", step.name),
+            ));
+        } else if steps[..i].iter().any(|s| s.name == step.name) {
+            errors.push(FieldError::new(
+                &format!("{}.name", field),
+                "duplicate",
+                format!("Another step is already named '{}'", step.name),
+            ));
+        }
+
+        match (&step.sql, &step.structured) {
+            (Some(sql), None) => {
+                if sql.trim().is_empty() {
+                    errors.push(FieldError::new(&format!("{}.sql", field), "required", "sql cannot be empty"));
+                }
+                for table in referenced_tables(sql) {
+                    if steps[i..].iter().any(|s| s.name == table) {
+                        errors.push(FieldError::new(
+                            &format!("{}.sql", field),
+                            "forward_reference",
+                            format!("Step '{}' reads '{}', which is not an earlier step", step.name, table),
+                        ));
+                    }
+                }
+            }
+            (None, Some(structured)) => {
+                errors.extend(structured.field_errors().into_iter().map(|e| FieldError {
+                    field: format!("{}.structured.{}", field, e.field),
+                    ..e
+                }));
+            }
+            _ => errors.push(FieldError::new(
+                &field,
+                "conflict",
+                "each step needs exactly one of sql or structured",
+            )),
+        }
+    }
+}
+
+impl StructuredAnalyticsQuery {
+    /// Problems with the query, with fields named relative to it.
+    pub fn field_errors(&self) -> Vec<FieldError> {
+        self.canonicalized().check()
+    }
+
+    /// Compile the query to SQL over `royalty_data`.
+    pub fn to_sql(&self) -> Result<String, DoubledeckerError> {
+        self.canonicalized().compile()
+    }
+
+    /// The query with every column reference (filters, key columns, metrics, dimensions and the
+    /// columns later operations read) resolved to the exact column name with [`resolve_column`], so
+    /// `"Net Revenue"` or `Platform` work wherever `net_revenue` or `platform` do. References that
+    /// match nothing are left as given for validation to report.
+    fn canonicalized(&self) -> Self {
+        fn resolve<S: AsRef<str>>(name: &mut String, columns: &[S]) {
+            if let Some(c) = resolve_column(name, columns) {
+                *name = c.to_string();
+            }
+        }
+
+        let mut query = self.clone();
+        for f in query.filters.iter_mut().flatten() {
+            resolve(&mut f.field, &ALLOWED_DIMENSIONS);
+        }
+        for c in query.find_duplicates.iter_mut().flat_map(|d| d.columns.iter_mut()) {
+            resolve(c, &ALLOWED_DIMENSIONS);
+        }
+        for b in query.bins.iter_mut().flatten() {
+            resolve(&mut b.column, &BINNABLE_COLUMNS);
+        }
+        for l in query.lookups.iter_mut().flatten() {
+            for c in l.on.iter_mut().chain(l.add_columns.iter_mut()) {
+                resolve(c, &ALLOWED_DIMENSIONS);
+            }
+        }
+        for m in query.metrics.iter_mut().flatten() {
+            resolve(m, &SUPPORTED_METRICS);
+        }
+        if let Some(aliases) = query.metric_aliases.take() {
+            let aliases = aliases.into_iter().map(|(mut metric, alias)| {
+                resolve(&mut metric, &SUPPORTED_METRICS);
+                (metric, alias)
+            });
+            query.metric_aliases = Some(aliases.collect());
+        }
+
+        let dimensions = query.dimension_names();
+        for d in query.dimensions.iter_mut().flatten() {
+            resolve(d, &dimensions);
+        }
+
+        let results = query.result_column_names();
+        if let Some(top) = &mut query.top_n_per_group {
+            for c in top.group_columns.iter_mut() {
+                resolve(c, &results);
+            }
+            resolve(&mut top.order_column, &results);
+        }
+        for spec in query.cumulative.iter_mut().flatten() {
+            for c in spec.partition_by.iter_mut() {
+                resolve(c, &results);
+            }
+            resolve(&mut spec.column, &results);
+            resolve(&mut spec.order_by, &results);
+        }
+        for share in query.percent_of_total.iter_mut().flatten() {
+            for c in share.partition_by.iter_mut() {
+                resolve(c, &results);
+            }
+            resolve(&mut share.column, &results);
+        }
+        query
+    }
+
+    fn check(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if let Some(top) = &self.top_n_per_group {
+            top.validate(self, &mut errors);
+        }
+        for (i, spec) in self.cumulative.iter().flatten().enumerate() {
+            spec.validate(self, &format!("cumulative[{}]", i), &mut errors);
+        }
+        for (i, share) in self.percent_of_total.iter().flatten().enumerate() {
+            share.validate(self, &format!("percent_of_total[{}]", i), &mut errors);
+        }
+        for (i, bin) in self.bins.iter().flatten().enumerate() {
+            bin.validate(&format!("bins[{}]", i), &mut errors);
+        }
+        let lookups = self.lookups.as_deref().unwrap_or_default();
+        if lookups.len() > MAX_LOOKUPS {
+            errors.push(FieldError::new(
+                "lookups",
+                "too_many",
+                format!("At most {} lookups are allowed per query", MAX_LOOKUPS),
+            ));
+        }
+        for (i, lookup) in lookups.iter().enumerate() {
+            lookup.validate(&format!("lookups[{}]", i), &mut errors);
+        }
+        for (i, d) in self.dimensions.iter().flatten().enumerate() {
+            if !self.is_dimension(d) {
+                errors.push(FieldError::new(
+                    &format!("dimensions[{}]", i),
+                    "not_allowed",
+                    unknown_column(format!("Dimension '{}' is not allowed", d), d, &self.dimension_names()),
+                ));
+            }
+        }
+        for (i, m) in self.metrics.iter().flatten().enumerate() {
+            if !SUPPORTED_METRICS.contains(&m.as_str()) {
+                errors.push(FieldError::new(
+                    &format!("metrics[{}]", i),
+                    "not_supported",
+                    unknown_column(format!("Metric '{}' is not supported", m), m, &SUPPORTED_METRICS),
+                ));
+            }
+        }
+        for (metric, alias) in self.metric_aliases.iter().flatten() {
+            let field = format!("metric_aliases.{}", metric);
+            if !self.metrics.iter().flatten().any(|m| m == metric) {
+                errors.push(FieldError::new(&field, "not_allowed", format!("'{}' is not a metric of this query", metric)));
+                continue;
+            }
+            let valid = alias.starts_with(|c: char| c.is_ascii_lowercase())
+                && alias.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+            let taken = self.is_dimension(alias)
+                || self.metrics.iter().flatten().any(|m| m != metric && self.metric_output(m) == Some(alias.as_str()));
+            if !valid || taken {
+                errors.push(FieldError::new(
+                    &field,
+                    "invalid_format",
+                    "alias must start with a lowercase letter, use only lowercase letters, digits and underscores, and not match another result column",
+                ));
+            }
+        }
+        if let Some(duplicates) = &self.find_duplicates {
+            if duplicates.columns.is_empty() {
+                errors.push(FieldError::new(
+                    "find_duplicates.columns",
+                    "required",
+                    "find_duplicates needs at least one key column",
+                ));
+            }
+            for (i, c) in duplicates.columns.iter().enumerate() {
+                if !ALLOWED_DIMENSIONS.contains(&c.as_str()) {
+                    errors.push(FieldError::new(
+                        &format!("find_duplicates.columns[{}]", i),
+                        "not_allowed",
+                        unknown_column(format!("Duplicate key column '{}' is not allowed", c), c, &ALLOWED_DIMENSIONS),
+                    ));
+                }
+            }
+            if self.dimensions.is_some() || self.metrics.is_some() {
+                errors.push(FieldError::new(
+                    "find_duplicates",
+                    "conflict",
+                    "find_duplicates cannot be combined with dimensions or metrics",
+                ));
+            }
+        }
+        for (i, f) in self.filters.iter().flatten().enumerate() {
+            if !ALLOWED_DIMENSIONS.contains(&f.field.as_str()) {
+                errors.push(FieldError::new(
+                    &format!("filters[{}].field", i),
+                    "not_allowed",
+                    unknown_column(
+                        format!("Filter field '{}' is not allowed", f.field),
+                        &f.field,
+                        &ALLOWED_DIMENSIONS,
+                    ),
+                ));
+            }
+        }
+        if let Some(DateRangeFilter { from: Some(from), to: Some(to) }) = &self.date_range
+            && from > to
+        {
+            errors.push(FieldError::new(
+                "date_range.to",
+                "out_of_range",
+                "date_range.to must not be before date_range.from",
+            ));
+        }
+        if self.limit.is_some_and(|limit| limit == 0 || limit > MAX_STRUCTURED_LIMIT) {
+            errors.push(FieldError::new(
+                "limit",
+                "out_of_range",
+                format!("limit must be between 1 and {}", MAX_STRUCTURED_LIMIT),
+            ));
+        }
+
+        errors
+    }
+
+    fn compile(&self) -> Result<String, DoubledeckerError> {
+        if self.segment_ids.as_ref().is_some_and(|ids| !ids.is_empty()) {
+            return Err(DoubledeckerError::BadRequest("Segments cannot be used in this query".to_string()));
+        }
+        let limit = self.limit.unwrap_or(100);
+        if let Some(duplicates) = &self.find_duplicates {
+            return duplicates.to_sql(&self.where_stmt()?, limit);
+        }
 
-        let allowed_dims = [
-            "isrc",
-            "upc",
-            "title",
-            "artist",
-            "album",
-            "platform",
-            "territory",
-            "country",
-            "transaction_type",
-            "reporting_date",
-            "currency",
-        ];
         let mut dims = Vec::new();
-        if let Some(ref d_list) = structured.dimensions {
+        if let Some(ref d_list) = self.dimensions {
             for d in d_list {
-                if !allowed_dims.contains(&d.as_str()) {
+                if !self.is_dimension(d) {
                     return Err(DoubledeckerError::BadRequest(
                         format!("Dimension '{}' is not allowed", d),
                     ));
@@ -86,14 +946,13 @@ impl AnalyticsQueryRequest {
         }
 
         let mut select_clauses = dims.clone();
-        if let Some(ref m_list) = structured.metrics {
+        if let Some(ref m_list) = self.metrics {
             for m in m_list {
-                match m.as_str() {
-                    "net_revenue" => select_clauses.push("SUM(net_revenue) AS total_revenue".to_string()),
-                    "quantity" => select_clauses.push("SUM(quantity) AS total_streams".to_string()),
-                    other => {
+                match self.metric_output(m) {
+                    Some(alias) => select_clauses.push(format!("SUM({}) AS {}", m, alias)),
+                    None => {
                         return Err(DoubledeckerError::BadRequest(
-                            format!("Metric '{}' is not supported", other),
+                            format!("Metric '{}' is not supported", m),
                         ));
                     }
                 }
@@ -104,8 +963,150 @@ impl AnalyticsQueryRequest {
             select_clauses.push("*".to_string());
         }
 
+        let where_stmt = self.where_stmt()?;
+
+        let group_stmt = if !dims.is_empty() && self.metrics.is_some() {
+            format!(" GROUP BY {}", dims.join(", "))
+        } else {
+            "".to_string()
+        };
+
+        let limit_stmt = format!(" LIMIT {}", limit);
+
+        let base = format!(
+            "SELECT {from_cols} FROM {source}{where_stmt}{group_stmt}",
+            from_cols = select_clauses.join(", "),
+            source = self.source(),
+            where_stmt = where_stmt,
+            group_stmt = group_stmt,
+        );
+
+        let base = self.with_window_columns(base);
+
+        let sql = match (&self.top_n_per_group, self.cumulative.as_deref()) {
+            (Some(top), _) => format!("{}{}", top.wrap(&base), limit_stmt),
+            // Running totals read naturally in the order they accumulate
+            (None, Some([first, ..])) => format!("{}{}{}", base, first.order_stmt(), limit_stmt),
+            (None, _) => format!("{}{}", base, limit_stmt),
+        };
+
+        Ok(sql)
+    }
+
+    /// The table the query reads: `royalty_data`, or a subquery adding the bucket columns.
+    fn source(&self) -> String {
+        let rows = self.enriched();
+        match self.bins.as_deref() {
+            Some(bins) if !bins.is_empty() => {
+                let columns: Vec<String> = bins.iter().map(|b| format!("{} AS {}", b.to_sql(), b.alias)).collect();
+                format!("(SELECT *, {} FROM {}) AS binned", columns.join(", "), rows)
+            }
+            _ => rows,
+        }
+    }
+
+    /// `royalty_data` with lookup columns joined on. Each lookup table is the left, build side of its
+    /// join, so the small table is hashed once and the large one streams past it.
+    fn enriched(&self) -> String {
+        let lookups = match self.lookups.as_deref() {
+            Some(lookups) if !lookups.is_empty() => lookups,
+            _ => return "royalty_data".to_string(),
+        };
+        let mut rows = "royalty_data".to_string();
+        for lookup in lookups {
+            let added: Vec<String> = lookup.add_columns.iter().map(|c| format!("l.{c} AS {}", lookup.added_name(c))).collect();
+            let on: Vec<String> = lookup.on.iter().map(|k| format!("l.{k} = r.{k}")).collect();
+            rows = format!(
+                "(SELECT r.*, {} FROM {} l RIGHT JOIN {} r ON {})",
+                added.join(", "),
+                lookup.keyed_table(),
+                rows,
+                on.join(" AND ")
+            );
+        }
+        format!("{} AS enriched", rows)
+    }
+
+    /// Output column of a supported metric: its alias, or the default name.
+    fn metric_output<'a>(&'a self, metric: &str) -> Option<&'a str> {
+        let default = metric_alias(metric)?;
+        Some(self.metric_aliases.as_ref().and_then(|a| a.get(metric)).map_or(default, String::as_str))
+    }
+
+    /// Columns usable as dimensions: the allowed ones, bin aliases and lookup columns.
+    fn dimension_names(&self) -> Vec<String> {
+        ALLOWED_DIMENSIONS
+            .iter()
+            .map(|d| d.to_string())
+            .chain(self.bins.iter().flatten().map(|b| b.alias.clone()))
+            .chain(self.lookups.iter().flatten().flat_map(|l| l.add_columns.iter().map(|c| l.added_name(c))))
+            .collect()
+    }
+
+    fn is_dimension(&self, name: &str) -> bool {
+        self.dimension_names().iter().any(|d| d == name)
+    }
+
+    /// Whether rows are grouped by `dimensions` and aggregated into metrics.
+    fn is_aggregated(&self) -> bool {
+        self.metrics.is_some() && self.dimensions.as_ref().is_some_and(|d| !d.is_empty())
+    }
+
+    /// Non-numeric columns of the query's results.
+    fn result_dimension_names(&self) -> Vec<String> {
+        match (&self.dimensions, self.is_aggregated()) {
+            (Some(dims), true) => dims.clone(),
+            _ => self.dimension_names(),
+        }
+    }
+
+    /// Numeric columns of the query's results: metric outputs when aggregated, otherwise the raw
+    /// numeric columns.
+    fn result_measure_names(&self) -> Vec<String> {
+        if self.is_aggregated() {
+            self.metrics.iter().flatten().filter_map(|m| self.metric_output(m)).map(str::to_string).collect()
+        } else {
+            BINNABLE_COLUMNS.iter().map(|c| c.to_string()).collect()
+        }
+    }
+
+    fn result_column_names(&self) -> Vec<String> {
+        let mut names = self.result_dimension_names();
+        names.extend(self.result_measure_names());
+        names
+    }
+
+    fn is_result_dimension(&self, name: &str) -> bool {
+        self.result_dimension_names().iter().any(|d| d == name)
+    }
+
+    fn is_result_measure(&self, name: &str) -> bool {
+        self.result_measure_names().iter().any(|m| m == name)
+    }
+
+    fn is_result_column(&self, name: &str) -> bool {
+        self.is_result_dimension(name) || self.is_result_measure(name)
+    }
+
+    /// Adds the window columns computed over the query's results: running totals and shares.
+    fn with_window_columns(&self, base: String) -> String {
+        let columns: Vec<String> = self
+            .cumulative
+            .iter()
+            .flatten()
+            .map(|c| c.to_sql())
+            .chain(self.percent_of_total.iter().flatten().map(|p| p.to_sql()))
+            .collect();
+        if columns.is_empty() {
+            return base;
+        }
+        format!("SELECT *, {} FROM ({}) windowed", columns.join(", "), base)
+    }
+
+    /// ` WHERE ...` for the date range and filters, or an empty string.
+    fn where_stmt(&self) -> Result<String, DoubledeckerError> {
         let mut where_clauses = Vec::new();
-        if let Some(ref dr) = structured.date_range {
+        if let Some(ref dr) = self.date_range {
             if let Some(from) = dr.from {
                 where_clauses.push(format!("reporting_date >= '{}'", from));
             }
@@ -114,50 +1115,142 @@ impl AnalyticsQueryRequest {
             }
         }
 
-        if let Some(ref filters) = structured.filters {
+        if let Some(ref filters) = self.filters {
             for f in filters {
-                if !allowed_dims.contains(&f.field.as_str()) {
+                if !ALLOWED_DIMENSIONS.contains(&f.field.as_str()) {
                     return Err(DoubledeckerError::BadRequest(
                         format!("Filter field '{}' is not allowed", f.field),
                     ));
                 }
-                let val = f.value.replace('\'', "''");
-                match f.operator {
-                    FilterOperator::Eq => where_clauses.push(format!("{} = '{}'", f.field, val)),
-                    FilterOperator::Ne => where_clauses.push(format!("{} != '{}'", f.field, val)),
-                    FilterOperator::Gt => where_clauses.push(format!("{} > '{}'", f.field, val)),
-                    FilterOperator::Gte => where_clauses.push(format!("{} >= '{}'", f.field, val)),
-                    FilterOperator::Lt => where_clauses.push(format!("{} < '{}'", f.field, val)),
-                    FilterOperator::Lte => where_clauses.push(format!("{} <= '{}'", f.field, val)),
-                    FilterOperator::Like => where_clauses.push(format!("{} LIKE '{}'", f.field, val)),
-                    FilterOperator::In => where_clauses.push(format!("{} = '{}'", f.field, val)),
-                }
+                where_clauses.push(f.to_sql()?);
             }
         }
 
-        let where_stmt = if where_clauses.is_empty() {
+        Ok(if where_clauses.is_empty() {
             "".to_string()
         } else {
             format!(" WHERE {}", where_clauses.join(" AND "))
-        };
+        })
+    }
+}
 
-        let group_stmt = if !dims.is_empty() && structured.metrics.is_some() {
-            format!(" GROUP BY {}", dims.join(", "))
-        } else {
-            "".to_string()
+impl From<StructuredAnalyticsQuery> for AnalyticsQueryRequest {
+    fn from(structured: StructuredAnalyticsQuery) -> Self {
+        Self {
+            sql: None,
+            structured: Some(structured),
+            dataset_ids: None,
+            layout: ResultLayout::Rows,
+            steps: None,
+            preview: false,
+        }
+    }
+}
+
+impl AnalyticsQueryRequest {
+    /// Datasets a structured query, or its structured steps, join in as lookups.
+    pub fn lookup_dataset_ids(&self) -> Vec<Uuid> {
+        let steps = self.steps.iter().flatten().filter_map(|s| s.structured.as_ref());
+        let mut ids: Vec<Uuid> = self
+            .structured
+            .iter()
+            .chain(steps)
+            .flat_map(|s| s.lookups.iter().flatten())
+            .map(|l| l.dataset_id)
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Segments a structured query, or its structured steps, reference.
+    pub fn segment_ids(&self) -> Vec<Uuid> {
+        let steps = self.steps.iter().flatten().filter_map(|s| s.structured.as_ref());
+        let mut ids: Vec<Uuid> =
+            self.structured.iter().chain(steps).flat_map(|s| s.segment_ids.iter().flatten()).copied().collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Replace the segment references of the query and its steps with the segments' filters.
+    pub fn expand_segments(&mut self, segments: &HashMap<Uuid, Vec<QueryFilter>>) -> Result<(), DoubledeckerError> {
+        let steps = self.steps.iter_mut().flatten().filter_map(|s| s.structured.as_mut());
+        for structured in self.structured.iter_mut().chain(steps) {
+            for id in structured.segment_ids.take().unwrap_or_default() {
+                let filters = segments
+                    .get(&id)
+                    .ok_or_else(|| DoubledeckerError::NotFound(format!("Segment {} not found", id)))?;
+                structured.filters.get_or_insert_with(Vec::new).extend(filters.iter().cloned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Each step's name and SQL, for a multi-step query.
+    pub fn step_sqls(&self) -> Result<Option<Vec<(String, String)>>, DoubledeckerError> {
+        let Some(steps) = &self.steps else {
+            return Ok(None);
         };
+        steps
+            .iter()
+            .map(|s| Ok((s.name.clone(), s.to_sql()?)))
+            .collect::<Result<Vec<_>, DoubledeckerError>>()
+            .map(Some)
+    }
 
-        let limit_stmt = format!(" LIMIT {}", structured.limit.unwrap_or(100));
+    pub fn to_safe_sql(&self) -> Result<String, DoubledeckerError> {
+        if let Some(ref sql) = self.sql {
+            return Ok(sql.clone());
+        }
 
-        let sql = format!(
-            "SELECT {from_cols} FROM royalty_data{where_stmt}{group_stmt}{limit_stmt}",
-            from_cols = select_clauses.join(", "),
-            where_stmt = where_stmt,
-            group_stmt = group_stmt,
-            limit_stmt = limit_stmt
-        );
+        // Steps become CTEs of one statement, so intermediates are planned and run in one session
+        if let Some(steps) = self.step_sqls()? {
+            let last = steps
+                .last()
+                .ok_or_else(|| DoubledeckerError::BadRequest("steps cannot be empty".to_string()))?;
+            let ctes: Vec<String> = steps.iter().map(|(name, sql)| format!("{} AS ({})", name, sql)).collect();
+            return Ok(format!("WITH {} SELECT * FROM {}", ctes.join(", "), last.0));
+        }
 
-        Ok(sql)
+        let structured = self.structured.as_ref().ok_or_else(|| {
+            DoubledeckerError::BadRequest(
+                "Either 'sql' or structured query parameters ('dimensions', 'metrics', etc.) must be provided"
+                    .to_string(),
+            )
+        })?;
+
+        structured.to_sql()
+    }
+}
+
+impl Validate for AnalyticsQueryRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        if let Some(sql) = &self.sql {
+            if sql.trim().is_empty() {
+                errors.push(FieldError::new("sql", "required", "sql cannot be empty"));
+            }
+            if self.steps.is_some() {
+                errors.push(FieldError::new("steps", "conflict", "steps cannot be combined with sql"));
+            }
+            return errors;
+        }
+        if let Some(steps) = &self.steps {
+            check_steps(steps, &mut errors);
+            return errors;
+        }
+        let Some(structured) = &self.structured else {
+            errors.push(FieldError::new(
+                "sql",
+                "required",
+                "Either sql or structured query parameters must be provided",
+            ));
+            return errors;
+        };
+
+        structured.field_errors()
     }
 }
 
@@ -166,11 +1259,151 @@ pub struct AnalyticsSummaryRequest {
     pub dataset_ids: Option<Vec<Uuid>>,
 }
 
+/// Longest question a natural-language query accepts.
+pub const MAX_QUESTION_LENGTH: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NaturalLanguageQueryRequest {
+    /// The question in plain words, e.g. "top 10 artists by revenue on Spotify in 2024"
+    pub question: String,
+}
+
+impl Validate for NaturalLanguageQueryRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.question.trim().is_empty() {
+            errors.push(FieldError::new("question", "required", "question cannot be empty"));
+        }
+        check_max_length(&mut errors, "question", &self.question, MAX_QUESTION_LENGTH);
+        errors
+    }
+}
+
+/// A structured query answering a question, checked to plan against the workspace's data but not run.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NaturalLanguageQueryResponse {
+    /// Structured query parameters, ready to send to the query, download or chart endpoints
+    pub query: StructuredAnalyticsQuery,
+    /// SQL the query compiles to
+    pub sql: String,
+    /// Columns the query returns
+    pub columns: Vec<ResultColumn>,
+}
+
+/// Instructions for a model turning questions into structured queries over `royalty_data`, given
+/// the table's columns.
+pub fn natural_language_prompt(columns: &[ResultColumn]) -> String {
+    let describe = |names: &[&str]| {
+        names
+            .iter()
+            .map(|name| match columns.iter().find(|c| c.name == *name) {
+                Some(column) => format!("{} ({})", name, column.data_type),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!(
+        r#"You turn questions about music royalty statements into a JSON query over the table royalty_data, one row per sale or stream.
+Reply with a single JSON object and nothing else. Use only these keys, leaving out the ones the question does not need:
+- "dimensions": columns to group by
+- "metrics": columns to sum per group; "net_revenue" comes back as "total_revenue" and "quantity" (streams or units) as "total_streams"
+- "filters": conditions all rows must meet, as [{{"field": column, "operator": "eq" | "ne" | "gt" | "gte" | "lt" | "lte" | "like", "value": text}}]; "like" takes SQL wildcards such as "%live%"
+- "date_range": {{"from": "YYYY-MM-DD", "to": "YYYY-MM-DD"}} on reporting_date, either end optional
+- "top_n_per_group": {{"group_columns": [dimensions], "order_column": result column, "n": rows per group}} to keep the best rows of each group
+- "limit": most rows to return, default 100
+Columns for dimensions and filters: {dimensions}
+Columns for metrics: {metrics}"#,
+        dimensions = describe(&ALLOWED_DIMENSIONS),
+        metrics = describe(&SUPPORTED_METRICS),
+    )
+}
+
+/// Shape of query results in the response body.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultLayout {
+    /// `rows`: one array of values per row
+    #[default]
+    Rows,
+    /// `data`: one array of values per column, in `columns` order; cheaper to build and parse for wide results
+    Columns,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct AnalyticsQueryResponse {
     pub columns: Vec<String>,
+    /// Row-major values; empty when the `columns` layout was requested
     #[schema(value_type = Vec<Object>)]
     pub rows: Vec<serde_json::Value>,
+    /// Column-major values, present only with the `columns` layout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<Vec<Object>>>)]
+    pub data: Option<Vec<Vec<serde_json::Value>>>,
+    /// Time, rows, bytes read and peak memory of the query, present when it was run for this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<QueryMetrics>,
+    /// Present and true when the result is a `preview` over a sample of the data
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub preview: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryColumnsResponse {
+    pub columns: Vec<ResultColumn>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ResultColumn {
+    pub name: String,
+    /// Arrow type, e.g. `Float64`, `Utf8`, `Date32`
+    pub data_type: String,
+}
+
+/// Queries estimated to scan up to this many bytes are `low` cost.
+pub const LOW_COST_MAX_BYTES: i64 = 100 * 1024 * 1024;
+/// Queries estimated to scan up to this many bytes are `medium` cost; larger ones are `high`.
+pub const MEDIUM_COST_MAX_BYTES: i64 = 1024 * 1024 * 1024;
+
+/// Rough cost of running a query, for warning before a large scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryCostTier {
+    Low,
+    Medium,
+    High,
+}
+
+impl QueryCostTier {
+    pub fn for_bytes(bytes: i64) -> Self {
+        match bytes {
+            b if b <= LOW_COST_MAX_BYTES => Self::Low,
+            b if b <= MEDIUM_COST_MAX_BYTES => Self::Medium,
+            _ => Self::High,
+        }
+    }
+}
+
+/// One table scan of an estimated query.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TableScanEstimate {
+    pub table: String,
+    pub columns_read: usize,
+    pub columns_total: usize,
+    pub bytes_scanned: i64,
+    pub rows_scanned: i64,
+    /// Filters are applied while scanning and may skip row groups, so the scan can read less
+    pub filtered: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryEstimateResponse {
+    /// Stored size of the datasets read, in proportion to the columns read and any `LIMIT`
+    pub bytes_scanned: i64,
+    /// Rows of the datasets read, before filters
+    pub rows_scanned: i64,
+    pub cost_tier: QueryCostTier,
+    pub tables: Vec<TableScanEstimate>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -182,3 +1415,712 @@ pub struct AnalyticsSummaryResponse {
     pub top_track: Option<String>,
     pub total_tracks_monetized: i64,
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+    Parquet,
+}
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct ExportParams {
+    pub format: Option<ExportFormat>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartKind {
+    #[default]
+    Bar,
+    Line,
+    Pie,
+}
+
+impl ChartKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChartKind::Bar => "bar",
+            ChartKind::Line => "line",
+            ChartKind::Pie => "pie",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+/// How to draw a query result as a chart.
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct ChartParams {
+    /// `bar` (default), `line` or `pie`
+    pub kind: Option<ChartKind>,
+    /// `png` (default) or `svg`
+    pub format: Option<ChartFormat>,
+    /// Column of category labels or x values; defaults to the first column
+    pub x: Option<String>,
+    /// Comma-separated numeric columns to plot; defaults to every numeric column but `x`. Pie
+    /// charts plot the first
+    pub y: Option<String>,
+    pub title: Option<String>,
+    /// Pixels, 200 to 2000 (default 800)
+    pub width: Option<u32>,
+    /// Pixels, 200 to 2000 (default 450)
+    pub height: Option<u32>,
+}
+
+impl ChartParams {
+    pub const DEFAULT_WIDTH: u32 = 800;
+    pub const DEFAULT_HEIGHT: u32 = 450;
+
+    pub fn y_columns(&self) -> Vec<String> {
+        self.y
+            .iter()
+            .flat_map(|y| y.split(','))
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl Validate for ChartParams {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for (field, value) in [("width", self.width), ("height", self.height)] {
+            if value.is_some_and(|v| !(200..=2000).contains(&v)) {
+                errors.push(FieldError::new(field, "out_of_range", format!("{} must be 200 to 2000 pixels", field)));
+            }
+        }
+        if self.y_columns().len() > 8 {
+            errors.push(FieldError::new("y", "too_many", "at most 8 columns can be plotted"));
+        }
+        if let Some(title) = &self.title {
+            check_max_length(&mut errors, "title", title, MAX_NAME_LENGTH);
+        }
+        errors
+    }
+}
+
+/// Rows a diff lists per kind of change when the request does not say.
+pub const DEFAULT_DIFF_ROWS: usize = 100;
+pub const MAX_DIFF_ROWS: usize = 1000;
+const MAX_DIFF_DATASETS: usize = 100;
+const MAX_DIFF_KEY_COLUMNS: usize = 8;
+
+/// One query run against two sets of datasets, e.g. a monthly statement and its corrected
+/// re-upload, with the results compared row by row.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QueryDiffRequest {
+    /// The query, in the form sent to `/analytics/query`; its `layout` and `preview` are ignored. A
+    /// structured `limit` caps each result, default 100,000 here; a result reaching it fails the diff
+    pub query: AnalyticsQueryRequest,
+    /// Datasets the query reads for the earlier result (1 to 100)
+    pub base_dataset_ids: Vec<Uuid>,
+    /// Datasets the query reads for the later result (1 to 100)
+    pub compare_dataset_ids: Vec<Uuid>,
+    /// Result columns identifying a row on both sides, e.g. `["isrc", "platform"]` (1 to 8)
+    pub key: Vec<String>,
+    /// Rows listed per kind of change, 1 to 1000 (default 100); `summary` counts every row
+    pub limit: Option<usize>,
+}
+
+impl Validate for QueryDiffRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = self.query.validate();
+        let sides = [("base_dataset_ids", &self.base_dataset_ids), ("compare_dataset_ids", &self.compare_dataset_ids)];
+        for (field, ids) in sides {
+            if ids.is_empty() || ids.len() > MAX_DIFF_DATASETS {
+                errors.push(FieldError::new(
+                    field,
+                    "out_of_range",
+                    format!("{} must list 1 to {} datasets", field, MAX_DIFF_DATASETS),
+                ));
+            }
+        }
+        if self.key.is_empty() || self.key.len() > MAX_DIFF_KEY_COLUMNS {
+            errors.push(FieldError::new(
+                "key",
+                "out_of_range",
+                format!("key must name 1 to {} columns", MAX_DIFF_KEY_COLUMNS),
+            ));
+        }
+        if self.key.iter().enumerate().any(|(i, column)| self.key[..i].contains(column)) {
+            errors.push(FieldError::new("key", "duplicate", "key names a column more than once"));
+        }
+        if self.limit.is_some_and(|n| n == 0 || n > MAX_DIFF_ROWS) {
+            errors.push(FieldError::new(
+                "limit",
+                "out_of_range",
+                format!("limit must be 1 to {}", MAX_DIFF_ROWS),
+            ));
+        }
+        errors
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryDiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+
+/// A key present on both sides whose other values differ.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangedRow {
+    /// Values of the `key` columns
+    #[schema(value_type = Vec<Object>)]
+    pub key: Vec<serde_json::Value>,
+    #[schema(value_type = Vec<Object>)]
+    pub base: serde_json::Value,
+    #[schema(value_type = Vec<Object>)]
+    pub compare: serde_json::Value,
+    /// Columns whose values differ
+    pub changed_columns: Vec<String>,
+}
+
+/// Rows only in the compare result (`added`), only in the base result (`removed`), and on both
+/// sides with different values (`changed`), matched on `key`. Rows are arrays in `columns` order.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryDiffResponse {
+    pub columns: Vec<String>,
+    pub key: Vec<String>,
+    pub summary: QueryDiffSummary,
+    #[schema(value_type = Vec<Object>)]
+    pub added: Vec<serde_json::Value>,
+    #[schema(value_type = Vec<Object>)]
+    pub removed: Vec<serde_json::Value>,
+    pub changed: Vec<ChangedRow>,
+    /// More rows were added, removed or changed than `limit`; `summary` has the full counts
+    pub truncated: bool,
+}
+
+impl QueryDiffResponse {
+    /// Compare two results with the same `columns`, given as row arrays, on the `key` columns.
+    /// Each key must identify one row per side. Removed rows keep the base order, added and
+    /// changed rows the compare order; at most `limit` of each are listed.
+    pub fn diff(
+        columns: Vec<String>,
+        key: Vec<String>,
+        base: Vec<serde_json::Value>,
+        compare: Vec<serde_json::Value>,
+        limit: usize,
+    ) -> Result<Self, DoubledeckerError> {
+        let key_indices = key
+            .iter()
+            .map(|k| columns.iter().position(|c| c == k).ok_or_else(|| DoubledeckerError::ColumnNotFound(k.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let cells = |row: &serde_json::Value| row.as_array().cloned().unwrap_or_default();
+        let key_of = |cells: &[serde_json::Value]| {
+            key_indices.iter().map(|&i| cells.get(i).cloned().unwrap_or_default()).collect::<Vec<_>>()
+        };
+        let index = |rows: &[serde_json::Value], side: &str| {
+            let mut by_key = HashMap::with_capacity(rows.len());
+            for (position, row) in rows.iter().enumerate() {
+                let row_key = key_of(&cells(row));
+                if by_key.insert(serde_json::Value::Array(row_key.clone()).to_string(), position).is_some() {
+                    return Err(DoubledeckerError::BadRequest(format!(
+                        "The key does not identify rows of the {} result: {} appears more than once",
+                        side,
+                        serde_json::Value::Array(row_key)
+                    )));
+                }
+            }
+            Ok(by_key)
+        };
+        let base_by_key = index(&base, "base")?;
+        let compare_by_key = index(&compare, "compare")?;
+
+        let mut summary = QueryDiffSummary { added: 0, removed: 0, changed: 0, unchanged: 0 };
+        let (mut added, mut removed, mut changed) = (Vec::new(), Vec::new(), Vec::new());
+        for row in &compare {
+            let compare_cells = cells(row);
+            let row_key = key_of(&compare_cells);
+            let Some(&position) = base_by_key.get(&serde_json::Value::Array(row_key.clone()).to_string()) else {
+                summary.added += 1;
+                if added.len() < limit {
+                    added.push(row.clone());
+                }
+                continue;
+            };
+            let base_cells = cells(&base[position]);
+            let changed_columns: Vec<String> = columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| base_cells.get(*i) != compare_cells.get(*i))
+                .map(|(_, c)| c.clone())
+                .collect();
+            if changed_columns.is_empty() {
+                summary.unchanged += 1;
+            } else {
+                summary.changed += 1;
+                if changed.len() < limit {
+                    changed.push(ChangedRow {
+                        key: row_key,
+                        base: base[position].clone(),
+                        compare: row.clone(),
+                        changed_columns,
+                    });
+                }
+            }
+        }
+        for row in &base {
+            let row_key = serde_json::Value::Array(key_of(&cells(row))).to_string();
+            if !compare_by_key.contains_key(&row_key) {
+                summary.removed += 1;
+                if removed.len() < limit {
+                    removed.push(row.clone());
+                }
+            }
+        }
+
+        let truncated = summary.added > limit || summary.removed > limit || summary.changed > limit;
+        Ok(Self { columns, key, summary, added, removed, changed, truncated })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, RecordBatch, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::MemTable;
+    use datafusion::prelude::SessionContext;
+    use std::sync::Arc;
+
+    /// Run a structured query against a small in-memory `royalty_data`.
+    async fn run(structured: StructuredAnalyticsQuery) -> Vec<RecordBatch> {
+        run_request(AnalyticsQueryRequest::from(structured)).await
+    }
+
+    async fn run_request(request: AnalyticsQueryRequest) -> Vec<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("isrc", DataType::Utf8, true),
+            Field::new("platform", DataType::Utf8, true),
+            Field::new("net_revenue", DataType::Float64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![Some("A"), Some("A"), Some("A"), Some("B"), None, None])),
+                Arc::new(StringArray::from(vec!["Spotify", "Spotify", "Apple", "Spotify", "Apple", "Apple"])),
+                Arc::new(Float64Array::from(vec![1.0, 1.0, 2.0, 3.0, 4.0, 5.0])),
+            ],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_table("royalty_data", Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap()))
+            .unwrap();
+
+        // Reference data for lookups, with a repeated key
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("isrc", DataType::Utf8, true),
+            Field::new("artist", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["A", "A", "C"])),
+                Arc::new(StringArray::from(vec!["Ada", "Ada", "Cy"])),
+            ],
+        )
+        .unwrap();
+        ctx.register_table(
+            lookup_table_name(Uuid::nil()),
+            Arc::new(MemTable::try_new(schema, vec![vec![batch]]).unwrap()),
+        )
+        .unwrap();
+
+        assert!(request.validate().is_empty());
+        ctx.sql(&request.to_safe_sql().unwrap()).await.unwrap().collect().await.unwrap()
+    }
+
+    fn sorted_rows(batches: &[RecordBatch]) -> Vec<String> {
+        let mut rows = rows(batches);
+        rows.sort();
+        rows
+    }
+
+    fn rows(batches: &[RecordBatch]) -> Vec<String> {
+        datafusion::arrow::util::pretty::pretty_format_batches(batches)
+            .unwrap()
+            .to_string()
+            .lines()
+            .filter(|l| l.starts_with('|'))
+            .skip(1)
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_find_duplicates() {
+        let find = |columns: &[&str], output| StructuredAnalyticsQuery {
+            find_duplicates: Some(FindDuplicates { columns: columns.iter().map(|c| c.to_string()).collect(), output }),
+            ..Default::default()
+        };
+
+        let groups = run(find(&["isrc", "platform"], DuplicateOutput::Groups)).await;
+        assert_eq!(rows(&groups), vec!["| A | Spotify | 2 |", "| | Apple | 2 |"]);
+
+        // Rows come grouped by key, most repeated key first; order within a key is unspecified
+        let duplicated = rows(&run(find(&["isrc"], DuplicateOutput::Rows)).await);
+        let (mut a, mut null) = (duplicated[..3].to_vec(), duplicated[3..].to_vec());
+        a.sort();
+        null.sort();
+        assert_eq!(a, vec!["| A | Apple | 2.0 | 3 |", "| A | Spotify | 1.0 | 3 |", "| A | Spotify | 1.0 | 3 |"]);
+        assert_eq!(null, vec!["| | Apple | 4.0 | 2 |", "| | Apple | 5.0 | 2 |"]);
+    }
+
+    #[tokio::test]
+    async fn test_bins_as_dimensions() {
+        let by_tier = run(StructuredAnalyticsQuery {
+            bins: Some(vec![BinSpec {
+                column: "net_revenue".to_string(),
+                edges: Some(vec![2.0, 4.0]),
+                width: None,
+                alias: "tier".to_string(),
+            }]),
+            dimensions: Some(vec!["tier".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(sorted_rows(&by_tier), vec!["| 2-4 | 5.0 |", "| < 2 | 2.0 |", "| >= 4 | 9.0 |"]);
+
+        let by_width = run(StructuredAnalyticsQuery {
+            bins: Some(vec![BinSpec { column: "net_revenue".to_string(), edges: None, width: Some(2.5), alias: "band".to_string() }]),
+            dimensions: Some(vec!["band".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(sorted_rows(&by_width), vec!["| 0.0 | 4.0 |", "| 2.5 | 7.0 |", "| 5.0 | 5.0 |"]);
+    }
+
+    #[tokio::test]
+    async fn test_top_n_per_group() {
+        let top = run(StructuredAnalyticsQuery {
+            dimensions: Some(vec!["platform".to_string(), "isrc".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            top_n_per_group: Some(TopNPerGroup {
+                group_columns: vec!["platform".to_string()],
+                order_column: "total_revenue".to_string(),
+                n: 1,
+                ascending: false,
+            }),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(rows(&top), vec!["| Apple | | 9.0 | 1 |", "| Spotify | B | 3.0 | 1 |"]);
+    }
+
+    #[tokio::test]
+    async fn test_cumulative_running_totals() {
+        let running = run(StructuredAnalyticsQuery {
+            dimensions: Some(vec!["platform".to_string(), "isrc".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            cumulative: Some(vec![CumulativeSpec {
+                column: "total_revenue".to_string(),
+                order_by: "isrc".to_string(),
+                partition_by: vec!["platform".to_string()],
+                function: CumulativeFunction::Sum,
+                alias: "running_revenue".to_string(),
+            }]),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(
+            rows(&running),
+            vec![
+                "| Apple | A | 2.0 | 2.0 |",
+                "| Apple | | 9.0 | 11.0 |",
+                "| Spotify | A | 2.0 | 2.0 |",
+                "| Spotify | B | 3.0 | 5.0 |",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_percent_of_total() {
+        let shares = run(StructuredAnalyticsQuery {
+            dimensions: Some(vec!["platform".to_string(), "isrc".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            percent_of_total: Some(vec![
+                PercentOfTotal { column: "total_revenue".to_string(), partition_by: vec![], alias: "share".to_string() },
+                PercentOfTotal {
+                    column: "total_revenue".to_string(),
+                    partition_by: vec!["platform".to_string()],
+                    alias: "platform_share".to_string(),
+                },
+            ]),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(
+            sorted_rows(&shares),
+            vec![
+                "| Apple | A | 2.0 | 12.5 | 18.181818181818183 |",
+                "| Apple | | 9.0 | 56.25 | 81.81818181818181 |",
+                "| Spotify | A | 2.0 | 12.5 | 40.0 |",
+                "| Spotify | B | 3.0 | 18.75 | 60.0 |",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lookup_enrichment() {
+        let enriched = run(StructuredAnalyticsQuery {
+            dimensions: Some(vec!["ref_artist".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            lookups: Some(vec![LookupSpec {
+                dataset_id: Uuid::nil(),
+                on: vec!["isrc".to_string()],
+                add_columns: vec!["artist".to_string()],
+                prefix: "ref".to_string(),
+            }]),
+            ..Default::default()
+        })
+        .await;
+        // Unmatched rows keep a NULL artist; the repeated key does not double A's revenue
+        assert_eq!(sorted_rows(&enriched), vec!["| Ada | 4.0 |", "| | 12.0 |"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_steps() {
+        let totals = |dimension: &str| StructuredAnalyticsQuery {
+            dimensions: Some(vec![dimension.to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            ..Default::default()
+        };
+        let step = |name: &str, sql: Option<&str>, structured: Option<StructuredAnalyticsQuery>| QueryStep {
+            name: name.to_string(),
+            sql: sql.map(str::to_string),
+            structured,
+        };
+        let request = AnalyticsQueryRequest {
+            steps: Some(vec![
+                step("by_track", None, Some(totals("isrc"))),
+                step("by_platform", None, Some(totals("platform"))),
+                step(
+                    "combined",
+                    Some(
+                        "SELECT 'track' AS kind, isrc AS key, total_revenue FROM by_track WHERE isrc IS NOT NULL \
+                         UNION ALL SELECT 'platform', platform, total_revenue FROM by_platform;",
+                    ),
+                    None,
+                ),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            sorted_rows(&run_request(request).await),
+            vec![
+                "| platform | Apple | 11.0 |",
+                "| platform | Spotify | 5.0 |",
+                "| track | A | 4.0 |",
+                "| track | B | 3.0 |",
+            ]
+        );
+
+        let forward = AnalyticsQueryRequest {
+            steps: Some(vec![
+                step("first", Some("SELECT * FROM second"), None),
+                step("second", Some("SELECT * FROM royalty_data"), None),
+            ]),
+            ..Default::default()
+        };
+        let errors = forward.validate();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].field.as_str(), errors[0].code.as_str()), ("steps[0].sql", "forward_reference"));
+    }
+
+    #[tokio::test]
+    async fn test_metric_aliases_carry_into_later_operations() {
+        let query = StructuredAnalyticsQuery {
+            dimensions: Some(vec!["platform".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            metric_aliases: Some([("net_revenue".to_string(), "revenue".to_string())].into()),
+            percent_of_total: Some(vec![PercentOfTotal {
+                column: "revenue".to_string(),
+                partition_by: vec![],
+                alias: "share".to_string(),
+            }]),
+            ..Default::default()
+        };
+        let batches = run(query.clone()).await;
+        let schema = batches[0].schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["platform", "revenue", "share"]);
+
+        let clash = StructuredAnalyticsQuery {
+            metric_aliases: Some([("net_revenue".to_string(), "platform".to_string())].into()),
+            ..query
+        };
+        assert!(clash.field_errors().iter().any(|e| e.field == "metric_aliases.net_revenue"));
+    }
+
+    #[tokio::test]
+    async fn test_column_references_resolve_loosely() {
+        let loose = StructuredAnalyticsQuery {
+            dimensions: Some(vec!["Platform".to_string()]),
+            metrics: Some(vec!["Net Revenue".to_string()]),
+            metric_aliases: Some([("net.revenue".to_string(), "revenue".to_string())].into()),
+            filters: Some(vec![QueryFilter {
+                field: "ISRC".to_string(),
+                operator: FilterOperator::Eq,
+                value: "A".to_string(),
+            }]),
+            percent_of_total: Some(vec![PercentOfTotal {
+                column: "Revenue".to_string(),
+                partition_by: vec![],
+                alias: "share".to_string(),
+            }]),
+            ..Default::default()
+        };
+        let exact = StructuredAnalyticsQuery {
+            dimensions: Some(vec!["platform".to_string()]),
+            metrics: Some(vec!["net_revenue".to_string()]),
+            metric_aliases: Some([("net_revenue".to_string(), "revenue".to_string())].into()),
+            filters: Some(vec![QueryFilter {
+                field: "isrc".to_string(),
+                operator: FilterOperator::Eq,
+                value: "A".to_string(),
+            }]),
+            percent_of_total: Some(vec![PercentOfTotal {
+                column: "revenue".to_string(),
+                partition_by: vec![],
+                alias: "share".to_string(),
+            }]),
+            ..Default::default()
+        };
+        assert_eq!(loose.to_sql().unwrap(), exact.to_sql().unwrap());
+        assert_eq!(sorted_rows(&run(loose).await), sorted_rows(&run(exact).await));
+
+        let typo = StructuredAnalyticsQuery {
+            dimensions: Some(vec!["plattform".to_string()]),
+            ..Default::default()
+        };
+        let errors = typo.field_errors();
+        assert_eq!(errors[0].message, "Dimension 'plattform' is not allowed; did you mean 'platform'?");
+    }
+
+    #[test]
+    fn test_segments_expand_into_filters() {
+        let segment = Uuid::new_v4();
+        let filter = |field: &str, value: &str| QueryFilter {
+            field: field.to_string(),
+            operator: FilterOperator::Eq,
+            value: value.to_string(),
+        };
+        let query = StructuredAnalyticsQuery {
+            filters: Some(vec![filter("isrc", "A")]),
+            segment_ids: Some(vec![segment]),
+            ..Default::default()
+        };
+        let mut request = AnalyticsQueryRequest {
+            steps: Some(vec![QueryStep {
+                name: "first".to_string(),
+                sql: None,
+                structured: Some(query.clone()),
+            }]),
+            ..AnalyticsQueryRequest::from(query)
+        };
+        assert_eq!(request.segment_ids(), vec![segment]);
+        assert!(request.to_safe_sql().is_err(), "unexpanded segments are refused");
+
+        let segments = HashMap::from([(segment, vec![filter("platform", "Spotify")])]);
+        request.expand_segments(&segments).unwrap();
+        assert!(request.segment_ids().is_empty());
+        for structured in [request.structured.as_ref(), request.steps.as_ref().unwrap()[0].structured.as_ref()] {
+            let filters = structured.unwrap().filters.as_ref().unwrap();
+            let fields: Vec<(&str, &str)> = filters.iter().map(|f| (f.field.as_str(), f.value.as_str())).collect();
+            assert_eq!(fields, vec![("isrc", "A"), ("platform", "Spotify")]);
+        }
+        assert!(request.to_safe_sql().unwrap().contains("platform = 'Spotify'"));
+
+        let mut missing = AnalyticsQueryRequest::from(StructuredAnalyticsQuery {
+            segment_ids: Some(vec![Uuid::new_v4()]),
+            ..Default::default()
+        });
+        assert!(matches!(missing.expand_segments(&segments), Err(DoubledeckerError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_diff_matches_rows_on_key() {
+        let columns = vec!["isrc".to_string(), "platform".to_string(), "net_revenue".to_string()];
+        let key = vec!["isrc".to_string(), "platform".to_string()];
+        let base = vec![
+            serde_json::json!(["A", "Spotify", 1.5]),
+            serde_json::json!(["A", "Apple", 2.0]),
+            serde_json::json!(["B", "Spotify", 3.0]),
+        ];
+        let compare = vec![
+            serde_json::json!(["A", "Spotify", 1.5]),
+            serde_json::json!(["A", "Apple", 2.5]),
+            serde_json::json!(["C", "Spotify", 4.0]),
+        ];
+
+        let diff = QueryDiffResponse::diff(columns.clone(), key.clone(), base.clone(), compare.clone(), 10).unwrap();
+        assert_eq!((diff.summary.added, diff.summary.removed), (1, 1));
+        assert_eq!((diff.summary.changed, diff.summary.unchanged), (1, 1));
+        assert_eq!(diff.added, vec![serde_json::json!(["C", "Spotify", 4.0])]);
+        assert_eq!(diff.removed, vec![serde_json::json!(["B", "Spotify", 3.0])]);
+        assert_eq!(diff.changed[0].key, vec![serde_json::json!("A"), serde_json::json!("Apple")]);
+        assert_eq!(diff.changed[0].changed_columns, vec!["net_revenue"]);
+        assert!(!diff.truncated);
+
+        let limited = QueryDiffResponse::diff(columns.clone(), key.clone(), base.clone(), compare.clone(), 1).unwrap();
+        assert!(!limited.truncated, "one of each fits a limit of one");
+        let everything_new = QueryDiffResponse::diff(columns.clone(), key.clone(), Vec::new(), compare, 1).unwrap();
+        assert_eq!((everything_new.added.len(), everything_new.summary.added), (1, 3));
+        assert!(everything_new.truncated);
+
+        let unknown = QueryDiffResponse::diff(columns.clone(), vec!["title".to_string()], Vec::new(), Vec::new(), 1);
+        assert!(matches!(unknown, Err(DoubledeckerError::ColumnNotFound(_))));
+        let not_unique = QueryDiffResponse::diff(columns, vec!["isrc".to_string()], base, Vec::new(), 1);
+        assert!(matches!(not_unique, Err(DoubledeckerError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_filter_values_are_coerced_to_the_column_type() {
+        let filter = |field: &str, operator: FilterOperator, value: &str| QueryFilter {
+            field: field.to_string(),
+            operator,
+            value: value.to_string(),
+        };
+        assert_eq!(filter("platform", FilterOperator::Eq, "O'Neil").to_sql().unwrap(), "platform = 'O''Neil'");
+        assert_eq!(
+            filter("reporting_date", FilterOperator::Gte, " 2026/06/01 ").to_sql().unwrap(),
+            "reporting_date >= DATE '2026-06-01'"
+        );
+        assert_eq!(
+            filter("reporting_date", FilterOperator::Lt, "2026-07-01T00:00:00Z").to_sql().unwrap(),
+            "reporting_date < DATE '2026-07-01'"
+        );
+        assert_eq!(
+            filter("reporting_date", FilterOperator::Like, "2026-06%").to_sql().unwrap(),
+            "CAST(reporting_date AS VARCHAR) LIKE '2026-06%'"
+        );
+
+        for (value, value_type) in [("June", "string"), ("202606", "number"), ("2026-06-01T12:00:00", "string")] {
+            match filter("reporting_date", FilterOperator::Eq, value).to_sql() {
+                Err(DoubledeckerError::FilterTypeMismatch(mismatch)) => {
+                    assert_eq!(mismatch.column, "reporting_date");
+                    assert_eq!(mismatch.column_type, "Date32");
+                    assert_eq!(mismatch.value_type, value_type);
+                }
+                other => panic!("expected a type mismatch for {:?}, got {:?}", value, other),
+            }
+        }
+    }
+}
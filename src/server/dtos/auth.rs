@@ -1,3 +1,7 @@
+use chrono::{DateTime, Utc};
+use crate::db::models::{DailyQueryCount, TableQueryCount};
+use crate::server::validation::{Validate, check_name, require_non_blank};
+use crate::utils::error::FieldError;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -29,3 +33,52 @@ pub struct UserInfo {
     pub email: String,
     pub user_type: String,
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The session of the token making this request
+    pub current: bool,
+}
+
+/// Usage figures for the account dashboard, aggregated from query history across all workspaces.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProfileStatsResponse {
+    pub total_queries: i64,
+    /// Uploaded datasets that finished processing
+    pub total_files_processed: i64,
+    /// Size of all datasets in the user's workspaces
+    pub total_storage_bytes: i64,
+    /// Bytes read from storage by all queries
+    pub total_bytes_scanned: i64,
+    pub avg_execution_time_ms: f64,
+    /// One entry per UTC day over the last 30 days, oldest first
+    pub queries_per_day: Vec<DailyQueryCount>,
+    pub most_queried_tables: Vec<TableQueryCount>,
+}
+
+impl Validate for RegisterRequest {
+    /// Password strength is checked separately against the configured `PasswordPolicy`.
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        if !self.email.contains('@') {
+            errors.push(FieldError::new("email", "invalid_format", "Invalid email format"));
+        }
+        errors
+    }
+}
+
+impl Validate for LoginRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_blank(&mut errors, "email", &self.email);
+        require_non_blank(&mut errors, "password", &self.password);
+        errors
+    }
+}
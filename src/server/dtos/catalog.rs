@@ -1,4 +1,6 @@
 use chrono::NaiveDate;
+use crate::server::validation::{Validate, check_max_length, check_name};
+use crate::utils::error::FieldError;
 use serde::Deserialize;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -41,3 +43,70 @@ pub struct UpdateTrackRequest {
     pub title: Option<String>,
     pub isrc: Option<String>,
 }
+
+/// Width of the `upc` and `isrc` columns.
+const MAX_CODE_LENGTH: usize = 100;
+
+impl Validate for CreateArtistRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for UpdateArtistRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for CreateAlbumRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "title", &self.title);
+        if let Some(upc) = &self.upc {
+            check_max_length(&mut errors, "upc", upc, MAX_CODE_LENGTH);
+        }
+        errors
+    }
+}
+
+impl Validate for UpdateAlbumRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(title) = &self.title {
+            check_name(&mut errors, "title", title);
+        }
+        if let Some(upc) = &self.upc {
+            check_max_length(&mut errors, "upc", upc, MAX_CODE_LENGTH);
+        }
+        errors
+    }
+}
+
+impl Validate for CreateTrackRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "title", &self.title);
+        check_name(&mut errors, "isrc", &self.isrc);
+        check_max_length(&mut errors, "isrc", &self.isrc, MAX_CODE_LENGTH);
+        errors
+    }
+}
+
+impl Validate for UpdateTrackRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(title) = &self.title {
+            check_name(&mut errors, "title", title);
+        }
+        if let Some(isrc) = &self.isrc {
+            check_name(&mut errors, "isrc", isrc);
+            check_max_length(&mut errors, "isrc", isrc, MAX_CODE_LENGTH);
+        }
+        errors
+    }
+}
@@ -0,0 +1,68 @@
+use crate::server::validation::{Validate, check_max_length, require_non_blank};
+use crate::utils::error::FieldError;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Longest comment body, in characters.
+pub const MAX_COMMENT_CHARS: usize = 10_000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateCommentRequest {
+    /// Text of the comment; `@name@example.com` mentions a workspace member
+    pub body: String,
+    /// Comment this one replies to; omit to start a thread
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateCommentRequest {
+    pub body: String,
+}
+
+impl Validate for CreateCommentRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_body(&mut errors, &self.body);
+        errors
+    }
+}
+
+impl Validate for UpdateCommentRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_body(&mut errors, &self.body);
+        errors
+    }
+}
+
+fn check_body(errors: &mut Vec<FieldError>, body: &str) {
+    require_non_blank(errors, "body", body);
+    check_max_length(errors, "body", body, MAX_COMMENT_CHARS);
+}
+
+/// Email addresses mentioned as `@name@example.com` in a comment, lowercased and without repeats.
+/// Punctuation ending a sentence after the address is not part of it.
+pub fn mentioned_emails(body: &str) -> Vec<String> {
+    let mut emails: Vec<String> = body
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|word| word.trim_end_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|email| email.split_once('@').is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.')))
+        .collect();
+    emails.sort();
+    emails.dedup();
+    emails
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mentioned_emails() {
+        let body = "@Ana@example.com can you check this? cc @bo@label.io, @ana@example.com. Mail me@home.com, not @nobody";
+        assert_eq!(mentioned_emails(body), vec!["ana@example.com", "bo@label.io"]);
+        assert!(mentioned_emails("no mentions @ all").is_empty());
+    }
+}
@@ -1,13 +1,80 @@
+use crate::server::validation::Validate;
+use crate::utils::error::FieldError;
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
+/// Most ids a single bulk request may name.
+pub const MAX_BULK_IDS: usize = 100;
+
+/// Body returned with every non-2xx response.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub status: u16,
+    /// Stable machine-readable error code, e.g. `TABLE_NOT_FOUND`, `VALIDATION_FAILED`
+    pub code: String,
+    /// Structured context, e.g. `{"column": "revenue"}` for `COLUMN_NOT_FOUND`, or the validation
+    /// report for `FILE_VALIDATION_FAILED`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<serde_json::Value>,
+    /// Per-field problems; present on 422 validation errors only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<crate::utils::error::FieldError>>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct DeleteResponse {
     pub message: String,
 }
 
+/// Whether the caller now has the dataset or pipeline `id` among their favorites.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FavoriteResponse {
+    pub id: Uuid,
+    pub favorite: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkDeleteRequest {
+    /// Up to 100 ids; duplicates are ignored
+    pub ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkDeleteItemResult {
+    pub id: Uuid,
+    pub deleted: bool,
+    /// Why the item was not deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkDeleteResponse {
+    pub deleted_count: usize,
+    /// One entry per requested id, in request order
+    pub results: Vec<BulkDeleteItemResult>,
+}
+
+impl Validate for BulkDeleteRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.ids.is_empty() {
+            errors.push(FieldError::new("ids", "required", "ids cannot be empty"));
+        } else if self.ids.len() > MAX_BULK_IDS {
+            errors.push(FieldError::new(
+                "ids",
+                "too_many",
+                format!("at most {} ids can be deleted at once", MAX_BULK_IDS),
+            ));
+        }
+        errors
+    }
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct DatasetResponse {
     pub id: Uuid,
@@ -17,8 +84,30 @@ pub struct DatasetResponse {
     pub s3_parquet_key: String,
     pub file_size_bytes: i64,
     pub row_count: i64,
+    /// `PENDING_UPLOAD`, `QUEUED`, `PROCESSING`, `READY` or `FAILED`; see `DatasetStatus`
     pub status: String,
+    /// Why processing failed, when `status` is `FAILED`
     pub error_message: Option<String>,
+    /// Present when the dataset is shared publicly via `/public/tables/{token}/query`.
+    pub public_token: Option<String>,
+    /// Data key the converted Parquet, and a direct upload's staged file, are encrypted with at
+    /// rest, if any.
+    pub encryption_key_id: Option<Uuid>,
+    /// Folder the dataset is filed under; `None` when unfiled.
+    pub folder_id: Option<Uuid>,
+    /// Headers of the uploaded file and the sanitized column names they map to; empty until processed.
+    pub source_columns: Vec<crate::normalization::SourceColumn>,
+    /// Encoding the uploaded file was detected in and transcoded to UTF-8 from, e.g. `windows-1252`;
+    /// `None` until scanned.
+    pub source_encoding: Option<String>,
+    /// Column types of the uploaded file as inferred for conversion; `None` until processed.
+    pub inferred_schema: Option<crate::normalization::InferredSchema>,
+    /// Ragged rows, encoding errors and type mismatches found in the uploaded file; `None` until scanned.
+    pub validation_report: Option<crate::normalization::ValidationReport>,
+    /// When an analytics query last read this dataset; `None` if it never has.
+    pub last_queried_at: Option<DateTime<Utc>>,
+    /// Analytics queries that have read this dataset
+    pub query_count: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -35,6 +124,15 @@ impl DatasetResponse {
             row_count: dataset.row_count,
             status: dataset.status,
             error_message: dataset.error_message,
+            public_token: dataset.public_token,
+            encryption_key_id: dataset.encryption_key_id,
+            folder_id: dataset.folder_id,
+            source_columns: dataset.source_columns.0,
+            source_encoding: dataset.source_encoding,
+            inferred_schema: dataset.inferred_schema.map(|schema| schema.0),
+            validation_report: dataset.validation_report.map(|report| report.0),
+            last_queried_at: dataset.last_queried_at,
+            query_count: dataset.query_count,
             created_at: dataset.created_at,
             updated_at: dataset.updated_at,
         }
@@ -0,0 +1,105 @@
+use crate::db::models::{Dashboard, DashboardTile, Visualization};
+use crate::server::dtos::analytics::AnalyticsQueryResponse;
+use crate::server::validation::{MAX_NAME_LENGTH, Validate, check_max_length, check_name, require_non_blank};
+use crate::utils::error::FieldError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Most tiles a dashboard can hold; all of them run on every data request.
+pub const MAX_DASHBOARD_TILES: usize = 24;
+
+/// Body of both creating and replacing a dashboard.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DashboardRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Tiles in display order (up to 24)
+    #[serde(default)]
+    pub tiles: Vec<DashboardTileRequest>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DashboardTileRequest {
+    /// Shown instead of the pipeline's name
+    pub title: Option<String>,
+    /// Pipeline whose query the tile shows
+    pub pipeline_id: Uuid,
+    /// Run on this dataset only; omit to run on every ready dataset of the workspace
+    pub dataset_id: Option<Uuid>,
+    pub visualization: Visualization,
+}
+
+/// A dashboard with its tiles.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DashboardResponse {
+    #[serde(flatten)]
+    pub dashboard: Dashboard,
+    pub tiles: Vec<DashboardTile>,
+}
+
+/// Results of all tiles of a dashboard, in display order.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DashboardDataResponse {
+    pub dashboard_id: Uuid,
+    pub tiles: Vec<DashboardTileData>,
+}
+
+/// One tile's result. A tile whose query fails has an `error` and does not affect the others.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DashboardTileData {
+    pub tile_id: Uuid,
+    /// The tile's title, or its pipeline's name
+    pub title: String,
+    pub pipeline_id: Uuid,
+    pub dataset_id: Option<Uuid>,
+    /// `TABLE`, `BAR` or `LINE`
+    pub visualization: String,
+    pub result: Option<AnalyticsQueryResponse>,
+    pub error: Option<String>,
+}
+
+impl Validate for DashboardRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        if self.tiles.len() > MAX_DASHBOARD_TILES {
+            errors.push(FieldError::new(
+                "tiles",
+                "too_many",
+                format!("a dashboard can have at most {} tiles", MAX_DASHBOARD_TILES),
+            ));
+        }
+        for (i, tile) in self.tiles.iter().enumerate() {
+            if let Some(title) = &tile.title {
+                let field = format!("tiles[{}].title", i);
+                require_non_blank(&mut errors, &field, title);
+                check_max_length(&mut errors, &field, title, MAX_NAME_LENGTH);
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_request_validation() {
+        let request: DashboardRequest = serde_json::from_value(serde_json::json!({
+            "name": "Monthly",
+            "tiles": [
+                { "pipeline_id": Uuid::nil(), "visualization": "BAR" },
+                { "title": " ", "pipeline_id": Uuid::nil(), "visualization": "LINE" }
+            ]
+        }))
+        .unwrap();
+        let fields: Vec<String> = request.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["tiles[1].title"]);
+        assert!(serde_json::from_value::<DashboardTileRequest>(
+            serde_json::json!({ "pipeline_id": Uuid::nil(), "visualization": "PIE" })
+        )
+        .is_err());
+    }
+}
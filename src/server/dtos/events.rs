@@ -0,0 +1,8 @@
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct EventStreamParams {
+    /// JWT for clients that cannot set an Authorization header (e.g. browser `EventSource`).
+    pub access_token: Option<String>,
+}
@@ -0,0 +1,73 @@
+use crate::server::dtos::common::MAX_BULK_IDS;
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::FieldError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateFolderRequest {
+    pub name: String,
+    /// Parent folder; omit to create a top-level folder
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RenameFolderRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveFolderRequest {
+    /// New parent folder; `null` moves the folder to the top level
+    pub parent_id: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveDatasetsRequest {
+    /// Up to 100 dataset ids
+    pub dataset_ids: Vec<Uuid>,
+    /// Destination folder; `null` takes the datasets out of any folder
+    pub folder_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MoveDatasetsResponse {
+    pub moved: Vec<Uuid>,
+    /// Requested ids that are not datasets of this workspace
+    pub not_found: Vec<Uuid>,
+}
+
+impl Validate for CreateFolderRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for RenameFolderRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for MoveFolderRequest {}
+
+impl Validate for MoveDatasetsRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.dataset_ids.is_empty() {
+            errors.push(FieldError::new("dataset_ids", "required", "dataset_ids cannot be empty"));
+        } else if self.dataset_ids.len() > MAX_BULK_IDS {
+            errors.push(FieldError::new(
+                "dataset_ids",
+                "too_many",
+                format!("at most {} datasets can be moved at once", MAX_BULK_IDS),
+            ));
+        }
+        errors
+    }
+}
@@ -1,3 +1,5 @@
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::FieldError;
 use serde::Deserialize;
 use utoipa::ToSchema;
 
@@ -16,3 +18,32 @@ pub struct UpdatePayeeRequest {
     pub bank_account: Option<String>,
     pub tax_id: Option<String>,
 }
+
+fn check_payee_email(errors: &mut Vec<FieldError>, email: &Option<String>) {
+    if let Some(email) = email
+        && !email.is_empty()
+        && !email.contains('@')
+    {
+        errors.push(FieldError::new("email", "invalid_format", "Invalid email format"));
+    }
+}
+
+impl Validate for CreatePayeeRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        check_payee_email(&mut errors, &self.email);
+        errors
+    }
+}
+
+impl Validate for UpdatePayeeRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(name) = &self.name {
+            check_name(&mut errors, "name", name);
+        }
+        check_payee_email(&mut errors, &self.email);
+        errors
+    }
+}
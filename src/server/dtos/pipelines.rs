@@ -0,0 +1,258 @@
+use crate::db::models::{BackgroundJob, PipelineRun};
+use crate::engine::ColumnLineage;
+use crate::server::dtos::analytics::{AnalyticsQueryRequest, StructuredAnalyticsQuery};
+use crate::server::dtos::common::MAX_BULK_IDS;
+use crate::server::validation::{MAX_NAME_LENGTH, Validate, check_max_length, check_name, require_non_blank};
+use crate::utils::error::FieldError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Body of both creating and replacing a pipeline.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PipelineRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Apply the pipeline to every dataset whose filename matches this glob once it is processed,
+    /// e.g. `sales_*`; omit to run it only on demand
+    pub dataset_pattern: Option<String>,
+    pub query: StructuredAnalyticsQuery,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RunPipelineRequest {
+    /// Datasets to run on (up to 100); omit to run on every ready dataset matching the pipeline's pattern
+    pub dataset_ids: Option<Vec<Uuid>>,
+    /// Queue the runs as a background job and return it right away instead of waiting for them
+    #[serde(default)]
+    pub background: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RunPipelineResponse {
+    /// Empty when the runs were queued
+    pub runs: Vec<PipelineRun>,
+    /// The queued job, when `background` was set; its result holds the runs once it succeeds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job: Option<BackgroundJob>,
+}
+
+/// Where a run's result came from, recorded when it ran.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RunLineage {
+    /// Datasets the run read: the one it ran on, then any joined as lookups
+    pub datasets: Vec<Uuid>,
+    /// Tables the query read, e.g. `royalty_data`
+    pub tables: Vec<String>,
+    /// The pipeline's query as it was at the time of the run
+    pub query: StructuredAnalyticsQuery,
+    pub sql: String,
+    /// One entry per result column, in result order
+    pub columns: Vec<ColumnLineage>,
+}
+
+/// `format` of a portable pipeline file.
+pub const PIPELINE_EXPORT_FORMAT: &str = "doubledecker.pipeline";
+/// Version of the portable pipeline format written by this server; files of newer versions are
+/// refused on import.
+pub const PIPELINE_EXPORT_VERSION: u32 = 1;
+
+/// A pipeline as a self-contained JSON file, to share between workspaces or keep in version
+/// control. Datasets are workspace-specific, so the ones the query reads through `lookups` are
+/// listed in `tables` and matched by filename on import.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PortablePipeline {
+    /// Always `doubledecker.pipeline`
+    pub format: String,
+    pub version: u32,
+    pub exported_at: Option<DateTime<Utc>>,
+    pub name: String,
+    pub description: Option<String>,
+    pub dataset_pattern: Option<String>,
+    pub query: StructuredAnalyticsQuery,
+    /// Datasets the query reads besides `royalty_data`
+    #[serde(default)]
+    pub tables: Vec<PortableTable>,
+}
+
+/// A dataset a portable pipeline refers to: the id used in its query, and the filename that
+/// identifies it in another workspace.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PortableTable {
+    pub dataset_id: Uuid,
+    pub filename: String,
+}
+
+impl Validate for PortablePipeline {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.format != PIPELINE_EXPORT_FORMAT {
+            errors.push(FieldError::new(
+                "format",
+                "invalid",
+                format!("format must be {}", PIPELINE_EXPORT_FORMAT),
+            ));
+        }
+        if self.version > PIPELINE_EXPORT_VERSION {
+            errors.push(FieldError::new(
+                "version",
+                "unsupported",
+                format!("version {} is newer than this server supports ({})", self.version, PIPELINE_EXPORT_VERSION),
+            ));
+        }
+        let request = PipelineRequest {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            dataset_pattern: self.dataset_pattern.clone(),
+            query: self.query.clone(),
+        };
+        errors.extend(request.validate());
+        for (i, lookup) in self.query.lookups.iter().flatten().enumerate() {
+            if !self.tables.iter().any(|t| t.dataset_id == lookup.dataset_id) {
+                errors.push(FieldError::new(
+                    &format!("query.lookups[{}].dataset_id", i),
+                    "not_found",
+                    "lookup dataset is not listed in tables",
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// Payload of a `pipeline.run` background job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PipelineRunJob {
+    pub pipeline_id: Uuid,
+    pub dataset_ids: Vec<Uuid>,
+}
+
+impl Validate for PipelineRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        if let Some(pattern) = &self.dataset_pattern {
+            require_non_blank(&mut errors, "dataset_pattern", pattern);
+            check_max_length(&mut errors, "dataset_pattern", pattern, MAX_NAME_LENGTH);
+        }
+        let request = AnalyticsQueryRequest::from(self.query.clone());
+        errors.extend(request.validate().into_iter().map(|e| FieldError {
+            field: format!("query.{}", e.field),
+            ..e
+        }));
+        errors
+    }
+}
+
+impl Validate for RunPipelineRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(ids) = &self.dataset_ids {
+            if ids.is_empty() {
+                errors.push(FieldError::new("dataset_ids", "required", "dataset_ids cannot be empty"));
+            } else if ids.len() > MAX_BULK_IDS {
+                errors.push(FieldError::new(
+                    "dataset_ids",
+                    "too_many",
+                    format!("at most {} datasets can be run at once", MAX_BULK_IDS),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// Name for a copy of the pipeline `name`: `<name> (copy)`, or `<name> (copy 2)` and so on when that
+/// is in `taken` (lowercased names), shortening `name` to keep within the name length limit.
+pub fn copy_name(name: &str, taken: &HashSet<String>) -> String {
+    (1..)
+        .map(|n| {
+            let suffix = if n == 1 { " (copy)".to_string() } else { format!(" (copy {})", n) };
+            let keep = MAX_NAME_LENGTH.saturating_sub(suffix.chars().count());
+            format!("{}{}", name.chars().take(keep).collect::<String>().trim_end(), suffix)
+        })
+        .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+        .expect("a free name is found before the counter runs out")
+}
+
+/// Whether a filename matches a pipeline's glob: `*` matches any run of characters, `?` any one
+/// character, and letters match regardless of case.
+pub fn pattern_matches(pattern: &str, filename: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = filename.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it was matched against, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    n = matched + 1;
+                    star = Some((after_star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("sales_*", "Sales_2026_06.csv"));
+        assert!(pattern_matches("*.csv", "distrokid.csv"));
+        assert!(pattern_matches("report_??.csv", "report_06.csv"));
+        assert!(pattern_matches("*_q*_*", "sales_q2_eu.csv"));
+        assert!(!pattern_matches("sales_*", "old_sales_2026.csv"));
+        assert!(!pattern_matches("report_??.csv", "report_6.csv"));
+        assert!(!pattern_matches("*.csv", "report.xlsx"));
+    }
+
+    #[test]
+    fn test_portable_pipeline_validation() {
+        let lookup_id = Uuid::new_v4();
+        let file = serde_json::json!({
+            "format": PIPELINE_EXPORT_FORMAT,
+            "version": PIPELINE_EXPORT_VERSION,
+            "name": "Revenue by artist",
+            "query": {
+                "dimensions": ["artists_artist"],
+                "metrics": ["net_revenue"],
+                "lookups": [{ "dataset_id": lookup_id, "on": ["isrc"], "add_columns": ["artist"], "prefix": "artists" }]
+            },
+            "tables": [{ "dataset_id": lookup_id, "filename": "artists.csv" }]
+        });
+        let pipeline: PortablePipeline = serde_json::from_value(file).unwrap();
+        assert!(pipeline.validate().is_empty(), "{:?}", pipeline.validate());
+
+        let mut newer = pipeline.clone();
+        newer.version = PIPELINE_EXPORT_VERSION + 1;
+        newer.tables.clear();
+        let fields: Vec<String> = newer.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["version", "query.lookups[0].dataset_id"]);
+    }
+
+    #[test]
+    fn test_copy_name() {
+        let taken: HashSet<String> = ["by store".to_string(), "by store (copy)".to_string()].into();
+        assert_eq!(copy_name("Top tracks", &taken), "Top tracks (copy)");
+        assert_eq!(copy_name("By Store", &taken), "By Store (copy 2)");
+        let long = "x".repeat(MAX_NAME_LENGTH);
+        assert_eq!(copy_name(&long, &taken).chars().count(), MAX_NAME_LENGTH);
+    }
+}
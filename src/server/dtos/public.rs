@@ -0,0 +1,16 @@
+use chrono::NaiveDate;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Query-string form of the structured analytics query, the only shape public tables accept.
+#[derive(Debug, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct PublicQueryParams {
+    /// Comma-separated dimensions, e.g. `platform,territory`
+    pub dimensions: Option<String>,
+    /// Comma-separated metrics: `net_revenue`, `quantity`
+    pub metrics: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// Row limit, capped at 1000
+    pub limit: Option<usize>,
+}
@@ -0,0 +1,243 @@
+use crate::normalization::unified_royalty_schema;
+use crate::server::validation::{Validate, check_max_length, check_name};
+use crate::utils::error::FieldError;
+use chrono::NaiveDate;
+use datafusion::arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Failing rows kept as samples for each rule of a run.
+pub const QUALITY_SAMPLE_ROWS: usize = 5;
+/// Values an `in_set` expectation lists at most.
+pub const MAX_SET_VALUES: usize = 100;
+const MAX_PATTERN_CHARS: usize = 500;
+
+/// What every value of a rule's column must meet. Nulls only fail `not_null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Expectation {
+    /// No value is null, or empty for text columns
+    NotNull,
+    /// No two rows share a value
+    Unique,
+    /// Every value is one of `values` (1 to 100)
+    InSet { values: Vec<String> },
+    /// Every value is at least `min` and at most `max`, either of which may be left out. Numeric
+    /// columns take numbers and `reporting_date` takes dates, e.g. `2026-01-01`
+    Range { min: Option<String>, max: Option<String> },
+    /// Every value matches the regular expression `pattern` somewhere; anchor it with `^...$` to
+    /// match whole values. Text columns only
+    Regex { pattern: String },
+}
+
+impl Expectation {
+    /// Errors of this expectation on `column` (a column of `royalty_data`), reported under `field`.
+    fn field_errors(&self, field: &str, column: &DataType) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        match self {
+            Expectation::NotNull | Expectation::Unique => {}
+            Expectation::InSet { values } => {
+                if values.is_empty() || values.len() > MAX_SET_VALUES {
+                    errors.push(FieldError::new(
+                        &format!("{}.values", field),
+                        "out_of_range",
+                        format!("in_set takes 1 to {} values", MAX_SET_VALUES),
+                    ));
+                }
+            }
+            Expectation::Range { min, max } => {
+                let bound = |value: &str| match column {
+                    DataType::Date32 => value.parse::<NaiveDate>().ok().map(|d| d.to_string()),
+                    c if c.is_numeric() => value.parse::<f64>().ok().filter(|n| n.is_finite()).map(|n| n.to_string()),
+                    _ => None,
+                };
+                if !matches!(column, DataType::Date32) && !column.is_numeric() {
+                    errors.push(FieldError::new(
+                        &format!("{}.type", field),
+                        "not_allowed",
+                        "range only applies to numeric columns and reporting_date",
+                    ));
+                    return errors;
+                }
+                if min.is_none() && max.is_none() {
+                    errors.push(FieldError::new(field, "required", "range needs min, max or both"));
+                }
+                for (name, value) in [("min", min), ("max", max)] {
+                    if value.as_deref().is_some_and(|v| bound(v).is_none()) {
+                        let expected = if column.is_numeric() { "a number" } else { "a date (YYYY-MM-DD)" };
+                        errors.push(FieldError::new(
+                            &format!("{}.{}", field, name),
+                            "invalid",
+                            format!("{} must be {}", name, expected),
+                        ));
+                    }
+                }
+                let parsed = (min.as_deref().and_then(bound), max.as_deref().and_then(bound));
+                if let (Some(min), Some(max)) = parsed {
+                    // Dates in ISO form and finite floats both order correctly once parsed
+                    let reversed = match column {
+                        DataType::Date32 => min > max,
+                        _ => min.parse::<f64>().ok() > max.parse::<f64>().ok(),
+                    };
+                    if reversed {
+                        errors.push(FieldError::new(&format!("{}.min", field), "invalid", "min cannot exceed max"));
+                    }
+                }
+            }
+            Expectation::Regex { pattern } => {
+                if column != &DataType::Utf8 {
+                    errors.push(FieldError::new(
+                        &format!("{}.type", field),
+                        "not_allowed",
+                        "regex only applies to text columns",
+                    ));
+                }
+                check_max_length(&mut errors, &format!("{}.pattern", field), pattern, MAX_PATTERN_CHARS);
+                if let Err(e) = regex::Regex::new(pattern) {
+                    errors.push(FieldError::new(
+                        &format!("{}.pattern", field),
+                        "invalid",
+                        format!("pattern is not a valid regular expression: {}", e),
+                    ));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Condition on `royalty_data` selecting the rows of `column` that fail the expectation. The
+    /// column and the expectation must have passed validation.
+    pub fn failing_rows_condition(&self, column: &str) -> String {
+        let literal = |value: &str| format!("'{}'", value.replace('\'', "''"));
+        let column_type = quality_column_type(column);
+        match self {
+            Expectation::NotNull if column_type == Some(DataType::Utf8) => {
+                format!("{c} IS NULL OR {c} = ''", c = column)
+            }
+            Expectation::NotNull => format!("{} IS NULL", column),
+            Expectation::Unique => {
+                format!("{c} IN (SELECT {c} FROM royalty_data GROUP BY {c} HAVING COUNT(*) > 1)", c = column)
+            }
+            Expectation::InSet { values } => {
+                let values: Vec<String> = values.iter().map(|v| literal(v)).collect();
+                format!("{} NOT IN ({})", column, values.join(", "))
+            }
+            Expectation::Range { min, max } => {
+                let bound = |value: &str| match column_type {
+                    Some(DataType::Date32) => format!("DATE {}", literal(value)),
+                    _ => value.parse::<f64>().map(|n| n.to_string()).unwrap_or_else(|_| literal(value)),
+                };
+                let mut conditions = Vec::new();
+                if let Some(min) = min {
+                    conditions.push(format!("{} < {}", column, bound(min)));
+                }
+                if let Some(max) = max {
+                    conditions.push(format!("{} > {}", column, bound(max)));
+                }
+                conditions.join(" OR ")
+            }
+            Expectation::Regex { pattern } => format!("NOT regexp_like({}, {})", column, literal(pattern)),
+        }
+    }
+
+    /// Query returning up to `QUALITY_SAMPLE_ROWS` failing rows, each with the total number of
+    /// failing rows in its last column, `failing_rows`.
+    pub fn failing_rows_sql(&self, column: &str) -> String {
+        format!(
+            "SELECT *, COUNT(*) OVER () AS failing_rows FROM royalty_data WHERE {} LIMIT {}",
+            self.failing_rows_condition(column),
+            QUALITY_SAMPLE_ROWS
+        )
+    }
+}
+
+/// Type of a `royalty_data` column rules can check; `None` for any other name.
+pub fn quality_column_type(column: &str) -> Option<DataType> {
+    unified_royalty_schema().field_with_name(column).ok().map(|f| f.data_type().clone())
+}
+
+/// Body of both creating and replacing a quality rule.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QualityRuleRequest {
+    pub name: String,
+    /// Column of `royalty_data` the rule checks, e.g. `isrc` or `net_revenue`
+    pub column_name: String,
+    pub expectation: Expectation,
+    /// Check every dataset when it finishes processing (default true); false for on-demand runs only
+    pub run_on_upload: Option<bool>,
+}
+
+impl Validate for QualityRuleRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        match quality_column_type(&self.column_name) {
+            Some(column) => errors.extend(self.expectation.field_errors("expectation", &column)),
+            None => {
+                let columns: Vec<String> =
+                    unified_royalty_schema().fields().iter().map(|f| f.name().clone()).collect();
+                errors.push(FieldError::new(
+                    "column_name",
+                    "not_allowed",
+                    format!("Column '{}' is not a column of royalty_data ({})", self.column_name, columns.join(", ")),
+                ));
+            }
+        }
+        errors
+    }
+}
+
+/// How one rule fared in a run.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RuleOutcome {
+    pub rule_id: Uuid,
+    pub name: String,
+    pub column_name: String,
+    pub expectation: Expectation,
+    pub passed: bool,
+    pub failing_rows: i64,
+    /// Up to 5 failing rows, each an object of the row's columns
+    #[schema(value_type = Vec<Object>)]
+    pub samples: Vec<serde_json::Value>,
+    /// Why the rule could not be checked, e.g. its column is hidden by a restriction; the rule then fails
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(column_name: &str, expectation: Expectation) -> QualityRuleRequest {
+        QualityRuleRequest { name: "rule".to_string(), column_name: column_name.to_string(), expectation, run_on_upload: None }
+    }
+
+    #[test]
+    fn test_expectations_are_checked_against_the_column_type() {
+        assert!(rule("isrc", Expectation::NotNull).validate().is_empty());
+        assert!(rule("isrc", Expectation::Regex { pattern: "^[A-Z]{2}".to_string() }).validate().is_empty());
+        let range = Expectation::Range { min: Some("0".to_string()), max: None };
+        assert!(rule("net_revenue", range.clone()).validate().is_empty());
+
+        assert_eq!(rule("isrcs", Expectation::NotNull).validate()[0].field, "column_name");
+        assert_eq!(rule("isrc", range).validate()[0].field, "expectation.type");
+        let bad_pattern = Expectation::Regex { pattern: "(".to_string() };
+        assert_eq!(rule("isrc", bad_pattern).validate()[0].field, "expectation.pattern");
+        let reversed = Expectation::Range { min: Some("2026-02-01".to_string()), max: Some("2026-01-01".to_string()) };
+        assert_eq!(rule("reporting_date", reversed).validate()[0].field, "expectation.min");
+        let not_a_date = Expectation::Range { min: Some("soon".to_string()), max: None };
+        assert_eq!(rule("reporting_date", not_a_date).validate()[0].field, "expectation.min");
+    }
+
+    #[test]
+    fn test_failing_rows_condition_quotes_values() {
+        let in_set = Expectation::InSet { values: vec!["Spotify".to_string(), "O'Neil".to_string()] };
+        assert_eq!(in_set.failing_rows_condition("platform"), "platform NOT IN ('Spotify', 'O''Neil')");
+        assert_eq!(Expectation::NotNull.failing_rows_condition("quantity"), "quantity IS NULL");
+        let range = Expectation::Range { min: Some("2026-01-01".to_string()), max: Some("2026-12-31".to_string()) };
+        assert_eq!(
+            range.failing_rows_condition("reporting_date"),
+            "reporting_date < DATE '2026-01-01' OR reporting_date > DATE '2026-12-31'"
+        );
+    }
+}
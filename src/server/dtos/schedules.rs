@@ -0,0 +1,163 @@
+use crate::db::models::{NotifyChannel, NotifyWhen, ThresholdOp};
+use crate::server::validation::{MAX_NAME_LENGTH, Validate, check_max_length, require_non_blank};
+use crate::utils::error::FieldError;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Shortest interval between scheduled runs.
+pub const MIN_SCHEDULE_INTERVAL_MINUTES: i32 = 5;
+/// Longest interval between scheduled runs (one week).
+pub const MAX_SCHEDULE_INTERVAL_MINUTES: i32 = 7 * 24 * 60;
+/// Most rows quoted in a summary; chat messages are not the place for full results.
+pub const MAX_SUMMARY_ROWS: i32 = 20;
+
+/// Body of both creating and replacing a schedule.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ScheduleRequest {
+    /// Pipeline to run over every ready dataset of the workspace
+    pub pipeline_id: Uuid,
+    /// Minutes between runs, from 5 up to a week
+    pub interval_minutes: i32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Where summaries are posted; required unless `notify_when` is `NEVER`
+    pub channel: Option<NotifyChannel>,
+    /// Incoming webhook URL of the Slack or Teams channel. Required with a new channel; omit on
+    /// update to keep the current one. Never returned.
+    pub webhook_url: Option<String>,
+    pub notify_when: NotifyWhen,
+    /// Required when `notify_when` is `THRESHOLD`
+    pub threshold: Option<ScheduleThreshold>,
+    /// Rows quoted in each summary (0 to 20, default 5)
+    #[serde(default = "default_top_rows")]
+    pub top_rows: i32,
+}
+
+/// Breached when any result row's `column` compares true against `value`.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ScheduleThreshold {
+    pub column: String,
+    pub op: ThresholdOp,
+    pub value: f64,
+}
+
+/// Payload of a `pipeline.schedule` background job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScheduleRunJob {
+    pub schedule_id: Uuid,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_top_rows() -> i32 {
+    5
+}
+
+/// Whether `url` is an incoming webhook of `channel`. Only the chat services' own hosts are
+/// accepted, so a schedule cannot be used to make the server call arbitrary addresses.
+pub fn is_webhook_url(channel: NotifyChannel, url: &str) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    if url.scheme() != "https" || url.port().is_some() {
+        return false;
+    }
+    match channel {
+        NotifyChannel::Slack => host == "hooks.slack.com",
+        NotifyChannel::Teams => {
+            host.ends_with(".webhook.office.com")
+                || host.ends_with(".logic.azure.com")
+                || host.ends_with(".api.powerplatform.com")
+        }
+    }
+}
+
+impl Validate for ScheduleRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if !(MIN_SCHEDULE_INTERVAL_MINUTES..=MAX_SCHEDULE_INTERVAL_MINUTES).contains(&self.interval_minutes) {
+            errors.push(FieldError::new(
+                "interval_minutes",
+                "out_of_range",
+                format!(
+                    "must be between {} and {}",
+                    MIN_SCHEDULE_INTERVAL_MINUTES, MAX_SCHEDULE_INTERVAL_MINUTES
+                ),
+            ));
+        }
+        match self.channel {
+            Some(channel) if self.webhook_url.as_deref().is_some_and(|url| !is_webhook_url(channel, url.trim())) => {
+                errors.push(FieldError::new(
+                    "webhook_url",
+                    "invalid",
+                    format!("must be an https incoming webhook URL of {}", channel.as_str()),
+                ));
+            }
+            Some(_) => {}
+            None if self.notify_when != NotifyWhen::Never => {
+                errors.push(FieldError::new("channel", "required", "required unless notify_when is NEVER"));
+            }
+            None => {}
+        }
+        match &self.threshold {
+            Some(threshold) => {
+                require_non_blank(&mut errors, "threshold.column", &threshold.column);
+                check_max_length(&mut errors, "threshold.column", &threshold.column, MAX_NAME_LENGTH);
+                if !threshold.value.is_finite() {
+                    errors.push(FieldError::new("threshold.value", "invalid", "must be a finite number"));
+                }
+            }
+            None if self.notify_when == NotifyWhen::Threshold => {
+                errors.push(FieldError::new("threshold", "required", "required when notify_when is THRESHOLD"));
+            }
+            None => {}
+        }
+        if !(0..=MAX_SUMMARY_ROWS).contains(&self.top_rows) {
+            errors.push(FieldError::new(
+                "top_rows",
+                "out_of_range",
+                format!("must be between 0 and {}", MAX_SUMMARY_ROWS),
+            ));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_url_hosts() {
+        let slack = "https://hooks.slack.com/services/T000/B000/XXXX";
+        assert!(is_webhook_url(NotifyChannel::Slack, slack));
+        assert!(!is_webhook_url(NotifyChannel::Teams, slack));
+        assert!(is_webhook_url(NotifyChannel::Teams, "https://contoso.webhook.office.com/webhookb2/abc"));
+        assert!(!is_webhook_url(NotifyChannel::Slack, "http://hooks.slack.com/services/T000"));
+        assert!(!is_webhook_url(NotifyChannel::Slack, "https://hooks.slack.com.evil.test/services"));
+        assert!(!is_webhook_url(NotifyChannel::Slack, "https://hooks.slack.com:8443/services"));
+        assert!(!is_webhook_url(NotifyChannel::Teams, "https://169.254.169.254/latest"));
+    }
+
+    #[test]
+    fn test_schedule_request_validation() {
+        let request: ScheduleRequest = serde_json::from_value(serde_json::json!({
+            "pipeline_id": Uuid::nil(),
+            "interval_minutes": 1,
+            "channel": "SLACK",
+            "webhook_url": "https://example.com/hook",
+            "notify_when": "THRESHOLD",
+            "top_rows": 50
+        }))
+        .unwrap();
+        let fields: Vec<String> = request.validate().into_iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["interval_minutes", "webhook_url", "threshold", "top_rows"]);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::server::validation::{Validate, check_max_length, require_non_blank};
+use crate::utils::error::FieldError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Results a search returns when the request does not say.
+pub const DEFAULT_SEARCH_RESULTS: usize = 20;
+pub const MAX_SEARCH_RESULTS: usize = 50;
+const MAX_SEARCH_QUERY_CHARS: usize = 200;
+
+#[derive(Debug, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct SearchParams {
+    /// Text to find in names and descriptions, ignoring case
+    pub q: String,
+    /// Results to return, 1 to 50 (default 20)
+    pub limit: Option<usize>,
+}
+
+impl Validate for SearchParams {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        require_non_blank(&mut errors, "q", &self.q);
+        check_max_length(&mut errors, "q", &self.q, MAX_SEARCH_QUERY_CHARS);
+        if self.limit.is_some_and(|n| n == 0 || n > MAX_SEARCH_RESULTS) {
+            errors.push(FieldError::new(
+                "limit",
+                "out_of_range",
+                format!("limit must be 1 to {}", MAX_SEARCH_RESULTS),
+            ));
+        }
+        errors
+    }
+}
+
+/// What a search result is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SearchResultKind {
+    Dataset,
+    Folder,
+    Pipeline,
+    Dashboard,
+    Segment,
+}
+
+impl SearchResultKind {
+    pub fn from_db_str(s: &str) -> Option<Self> {
+        match s {
+            "DATASET" => Some(SearchResultKind::Dataset),
+            "FOLDER" => Some(SearchResultKind::Folder),
+            "PIPELINE" => Some(SearchResultKind::Pipeline),
+            "DASHBOARD" => Some(SearchResultKind::Dashboard),
+            "SEGMENT" => Some(SearchResultKind::Segment),
+            _ => None,
+        }
+    }
+
+    /// API path to open a result of this kind at
+    pub fn link(self, workspace_id: Uuid, id: Uuid) -> String {
+        let base = format!("/api/workspaces/{}", workspace_id);
+        match self {
+            SearchResultKind::Dataset => format!("{}/datasets/{}/columns", base, id),
+            SearchResultKind::Folder => format!("{}/datasets?folder_id={}", base, id),
+            SearchResultKind::Pipeline => format!("{}/pipelines/{}", base, id),
+            SearchResultKind::Dashboard => format!("{}/dashboards/{}", base, id),
+            SearchResultKind::Segment => format!("{}/segments/{}", base, id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    pub kind: SearchResultKind,
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub workspace_name: String,
+    /// Filename of a dataset, name of anything else
+    pub name: String,
+    pub description: Option<String>,
+    pub link: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Matches across every workspace the caller owns or belongs to: names starting with the query
+/// first, then the most recently updated.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    /// More matched than `limit`; refine the query to see them
+    pub truncated: bool,
+}
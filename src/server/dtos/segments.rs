@@ -0,0 +1,46 @@
+use crate::server::dtos::analytics::{QueryFilter, StructuredAnalyticsQuery};
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::{DoubledeckerError, FieldError};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+/// Filters a segment holds at most.
+pub const MAX_SEGMENT_FILTERS: usize = 20;
+
+/// Body of both creating and replacing a segment.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SegmentRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Conditions rows must all meet, as in a structured query's `filters` (1 to 20)
+    pub filters: Vec<QueryFilter>,
+}
+
+impl Validate for SegmentRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        if self.filters.is_empty() {
+            errors.push(FieldError::new("filters", "required", "filters cannot be empty"));
+        } else if self.filters.len() > MAX_SEGMENT_FILTERS {
+            errors.push(FieldError::new(
+                "filters",
+                "too_many",
+                format!("At most {} filters are allowed per segment", MAX_SEGMENT_FILTERS),
+            ));
+        }
+        // Checked as the filters of a query, which is where they end up
+        let query = StructuredAnalyticsQuery {
+            filters: Some(self.filters.clone()),
+            ..Default::default()
+        };
+        errors.extend(query.field_errors());
+        // Values that would fail every query using the segment, e.g. a non-date on reporting_date
+        for (i, f) in self.filters.iter().enumerate() {
+            if let Err(e @ DoubledeckerError::FilterTypeMismatch(_)) = f.to_sql() {
+                errors.push(FieldError::new(&format!("filters[{}].value", i), "type_mismatch", e.message()));
+            }
+        }
+        errors
+    }
+}
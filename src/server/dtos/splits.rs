@@ -1,5 +1,7 @@
 use chrono::NaiveDate;
 use rust_decimal::Decimal;
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::FieldError;
 use serde::Deserialize;
 use utoipa::ToSchema;
 use uuid::Uuid;
@@ -24,3 +26,56 @@ pub struct UpdateSplitRequest {
     pub effective_from: Option<NaiveDate>,
     pub effective_to: Option<NaiveDate>,
 }
+
+fn check_percentage(errors: &mut Vec<FieldError>, percentage: Decimal) {
+    if percentage <= Decimal::ZERO || percentage > Decimal::ONE_HUNDRED {
+        errors.push(FieldError::new(
+            "percentage",
+            "out_of_range",
+            "percentage must be greater than 0 and at most 100",
+        ));
+    }
+}
+
+fn check_effective_range(errors: &mut Vec<FieldError>, from: Option<NaiveDate>, to: Option<NaiveDate>) {
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        errors.push(FieldError::new(
+            "effective_to",
+            "out_of_range",
+            "effective_to must not be before effective_from",
+        ));
+    }
+}
+
+impl Validate for CreateSplitRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.artist_id.is_none() && self.album_id.is_none() && self.track_id.is_none() {
+            errors.push(FieldError::new(
+                "track_id",
+                "required",
+                "One of artist_id, album_id or track_id is required",
+            ));
+        }
+        check_name(&mut errors, "payee_name", &self.payee_name);
+        check_percentage(&mut errors, self.percentage);
+        check_effective_range(&mut errors, self.effective_from, self.effective_to);
+        errors
+    }
+}
+
+impl Validate for UpdateSplitRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if let Some(payee_name) = &self.payee_name {
+            check_name(&mut errors, "payee_name", payee_name);
+        }
+        if let Some(percentage) = self.percentage {
+            check_percentage(&mut errors, percentage);
+        }
+        check_effective_range(&mut errors, self.effective_from, self.effective_to);
+        errors
+    }
+}
@@ -1,4 +1,14 @@
+use crate::config::{MAX_PRESIGNED_URL_EXPIRY_SECS, MIN_PRESIGNED_URL_EXPIRY_SECS};
+use crate::db::models::BackgroundJob;
+use crate::engine::udfs::MaskMode;
+use crate::normalization::inference::{InferredColumn, MAX_INFER_MAX_RECORDS};
+use crate::normalization::{InferenceOptions, SourceEncoding, unified_royalty_schema};
+use crate::server::dtos::analytics::AnalyticsQueryRequest;
+use crate::server::dtos::common::{DatasetResponse, MAX_BULK_IDS};
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::FieldError;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -7,6 +17,8 @@ pub struct PresignedUrlRequest {
     pub filename: String,
     pub distributor_source: Option<String>,
     pub file_size_bytes: i64,
+    /// Seconds the upload URL stays valid, 60 to 604800 (7 days); defaults to `UPLOAD_URL_EXPIRY_SECS`
+    pub expires_in: Option<u64>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -14,10 +26,477 @@ pub struct PresignedUrlResponse {
     pub dataset_id: Uuid,
     pub presigned_url: String,
     pub staging_key: String,
+    /// Seconds the URL stays valid
+    pub expires_in: u64,
+}
+
+/// Largest file a direct upload takes; larger files go through a presigned URL or an upload session.
+pub const MAX_DIRECT_UPLOAD_BYTES: i64 = 50 * 1024 * 1024;
+
+/// Largest file an upload session takes.
+pub const MAX_UPLOAD_SESSION_BYTES: i64 = 5 * 1024 * 1024 * 1024;
+
+/// Part size of an upload session unless the client picks one.
+pub const DEFAULT_UPLOAD_CHUNK_BYTES: i64 = 8 * 1024 * 1024;
+
+/// S3 takes multipart parts from 5MB (except the last); parts are sent through the server, so they
+/// also stay well under the upload body limit.
+pub const MIN_UPLOAD_CHUNK_BYTES: i64 = 5 * 1024 * 1024;
+pub const MAX_UPLOAD_CHUNK_BYTES: i64 = 32 * 1024 * 1024;
+
+/// Header carrying the hex SHA-256 of an upload session part.
+pub const PART_CHECKSUM_HEADER: &str = "x-checksum-sha256";
+
+/// What an upload may hold, so clients can check a file before sending it. Sizes are in bytes.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadLimitsResponse {
+    /// Columns in the header
+    pub max_columns: u64,
+    /// Data rows, excluding the header
+    pub max_rows: u64,
+    /// Any file, whichever way it is uploaded
+    pub max_file_bytes: u64,
+    /// Files sent to `POST /datasets/upload`
+    pub max_direct_upload_bytes: u64,
+    /// Files sent through an upload session
+    pub max_upload_session_bytes: u64,
+    /// Part sizes an upload session accepts as `chunk_size`
+    pub min_chunk_size: u64,
+    pub max_chunk_size: u64,
+    pub default_chunk_size: u64,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateUploadSessionRequest {
+    pub filename: String,
+    pub distributor_source: Option<String>,
+    /// Size of the whole file, up to 5GB
+    pub total_bytes: i64,
+    /// Size of every part but the last, 5MB to 32MB; defaults to 8MB
+    pub chunk_size: Option<i64>,
+    /// Reject the file on completion when validation finds ragged rows, invalid UTF-8 or type
+    /// mismatches, like a strict direct upload
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(flatten)]
+    pub inference: InferenceOptions,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct DatasetDownloadParams {
+    /// Seconds the download link stays valid, 60 to 604800 (7 days); defaults to
+    /// `DOWNLOAD_URL_EXPIRY_SECS`
+    pub expires_in: Option<u64>,
+}
+
+/// Distinct values a column-values request returns when it does not say.
+pub const DEFAULT_COLUMN_VALUES: usize = 100;
+pub const MAX_COLUMN_VALUES: usize = 1000;
+const MAX_COLUMN_VALUES_SEARCH_CHARS: usize = 200;
+
+#[derive(Debug, Default, Deserialize, ToSchema, utoipa::IntoParams)]
+pub struct ColumnValuesParams {
+    /// Values to return, 1 to 1000 (default 100)
+    pub limit: Option<usize>,
+    /// Only values starting with this text, ignoring case
+    pub search: Option<String>,
+}
+
+/// Distinct values of a dataset column, in ascending order, to fill a filter dropdown.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ColumnValuesResponse {
+    pub column: String,
+    #[schema(value_type = Vec<Object>)]
+    pub values: Vec<serde_json::Value>,
+    /// More values match than `limit`; narrow them with `search`
+    pub truncated: bool,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct ConfirmUploadRequest {
     pub dataset_id: Uuid,
     pub staging_key: String,
+    /// Fail the dataset instead of converting it when validation finds ragged rows, invalid UTF-8
+    /// or type mismatches. The report is stored on the dataset either way.
+    #[serde(default)]
+    pub strict: bool,
+    #[serde(flatten)]
+    pub inference: InferenceOptions,
+}
+
+/// Rows an upload preview returns when the request does not say.
+pub const DEFAULT_PREVIEW_ROWS: usize = 20;
+pub const MAX_PREVIEW_ROWS: usize = 1000;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PreviewUploadRequest {
+    pub dataset_id: Uuid,
+    pub staging_key: String,
+    /// Rows to return after the header, 1 to 1000 (default 20)
+    pub rows: Option<usize>,
+}
+
+/// The start of a staged upload, read before it is confirmed.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UploadPreviewResponse {
+    /// Headers, with the types inferred from the previewed rows only
+    pub columns: Vec<InferredColumn>,
+    pub rows: Vec<Vec<String>>,
+    pub source_encoding: SourceEncoding,
+    /// Statement format the file is read as unless the upload named one
+    pub detected_source: String,
+    /// Bytes read from the start of the file
+    pub bytes_read: usize,
+}
+
+/// Most columns a compaction sorts by.
+pub const MAX_COMPACTION_SORT_COLUMNS: usize = 4;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CompactDatasetsRequest {
+    /// READY datasets to merge, 2 to 100. They are deleted once the merged dataset is READY
+    pub dataset_ids: Vec<Uuid>,
+    /// Columns to sort the rows by, e.g. `["reporting_date", "artist"]`. Queries filtering on the
+    /// first one skip every row group whose range does not match
+    pub sort_by: Vec<String>,
+    /// Name of the merged dataset; defaults to `compacted.parquet`
+    pub filename: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CompactDatasetsResponse {
+    /// The merged dataset, QUEUED until the job has written it
+    pub dataset: DatasetResponse,
+    /// The compaction job; see the jobs endpoints
+    pub job: BackgroundJob,
+}
+
+/// Payload of a `datasets.compact` background job.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatasetCompactionJob {
+    /// The merged dataset being written
+    pub dataset_id: Uuid,
+    pub source_ids: Vec<Uuid>,
+    pub sort_by: Vec<String>,
+}
+
+/// What a finished compaction job wrote.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CompactionResult {
+    pub dataset_id: Uuid,
+    pub row_count: i64,
+    pub row_groups: usize,
+    pub file_size_bytes: i64,
+    pub sort_by: Vec<String>,
+    pub removed_dataset_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DatasetColumn {
+    pub name: String,
+    pub data_type: String,
+    /// Hidden or masked for members below ADMIN and for public viewers
+    pub restricted: bool,
+    /// How the column is presented when restricted
+    pub mask: Option<MaskMode>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ColumnMask {
+    pub column: String,
+    pub mode: MaskMode,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateColumnRestrictionsRequest {
+    /// Columns hidden entirely
+    pub restricted_columns: Vec<String>,
+    /// Columns shown through a masking function instead of hidden.
+    /// Together with `restricted_columns` this replaces the existing set.
+    #[serde(default)]
+    pub masked_columns: Vec<ColumnMask>,
+}
+
+impl Validate for PresignedUrlRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "filename", &self.filename);
+        if self.file_size_bytes <= 0 {
+            errors.push(FieldError::new(
+                "file_size_bytes",
+                "out_of_range",
+                "file_size_bytes must be positive",
+            ));
+        }
+        check_expires_in(&mut errors, self.expires_in);
+        errors
+    }
+}
+
+impl Validate for CreateUploadSessionRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = self.inference.validate();
+        check_name(&mut errors, "filename", &self.filename);
+        if self.total_bytes <= 0 || self.total_bytes > MAX_UPLOAD_SESSION_BYTES {
+            errors.push(FieldError::new(
+                "total_bytes",
+                "out_of_range",
+                format!(
+                    "total_bytes must be 1 to {}; upload larger files through a presigned URL",
+                    MAX_UPLOAD_SESSION_BYTES
+                ),
+            ));
+        }
+        if self.chunk_size.is_some_and(|size| !(MIN_UPLOAD_CHUNK_BYTES..=MAX_UPLOAD_CHUNK_BYTES).contains(&size)) {
+            errors.push(FieldError::new(
+                "chunk_size",
+                "out_of_range",
+                format!("chunk_size must be {} to {}", MIN_UPLOAD_CHUNK_BYTES, MAX_UPLOAD_CHUNK_BYTES),
+            ));
+        }
+        errors
+    }
+}
+
+impl Validate for DatasetDownloadParams {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_expires_in(&mut errors, self.expires_in);
+        errors
+    }
+}
+
+impl Validate for ColumnValuesParams {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.limit.is_some_and(|n| n == 0 || n > MAX_COLUMN_VALUES) {
+            errors.push(FieldError::new(
+                "limit",
+                "out_of_range",
+                format!("limit must be 1 to {}", MAX_COLUMN_VALUES),
+            ));
+        }
+        if self.search.as_ref().is_some_and(|s| s.chars().count() > MAX_COLUMN_VALUES_SEARCH_CHARS) {
+            errors.push(FieldError::new(
+                "search",
+                "too_long",
+                format!("search must be at most {} characters", MAX_COLUMN_VALUES_SEARCH_CHARS),
+            ));
+        }
+        errors
+    }
+}
+
+fn check_expires_in(errors: &mut Vec<FieldError>, expires_in: Option<u64>) {
+    if expires_in.is_some_and(|s| !(MIN_PRESIGNED_URL_EXPIRY_SECS..=MAX_PRESIGNED_URL_EXPIRY_SECS).contains(&s)) {
+        errors.push(FieldError::new(
+            "expires_in",
+            "out_of_range",
+            format!(
+                "expires_in must be {} to {} seconds",
+                MIN_PRESIGNED_URL_EXPIRY_SECS, MAX_PRESIGNED_URL_EXPIRY_SECS
+            ),
+        ));
+    }
+}
+
+impl Validate for PreviewUploadRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.rows.is_some_and(|n| n == 0 || n > MAX_PREVIEW_ROWS) {
+            errors.push(FieldError::new(
+                "rows",
+                "out_of_range",
+                format!("rows must be 1 to {}", MAX_PREVIEW_ROWS),
+            ));
+        }
+        errors
+    }
+}
+
+impl Validate for CompactDatasetsRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let mut ids = self.dataset_ids.clone();
+        ids.sort();
+        ids.dedup();
+        if ids.len() != self.dataset_ids.len() {
+            errors.push(FieldError::new("dataset_ids", "duplicate", "dataset_ids must not repeat a dataset"));
+        }
+        if ids.len() < 2 || ids.len() > MAX_BULK_IDS {
+            errors.push(FieldError::new(
+                "dataset_ids",
+                "out_of_range",
+                format!("2 to {} datasets can be compacted at once", MAX_BULK_IDS),
+            ));
+        }
+        if self.sort_by.is_empty() || self.sort_by.len() > MAX_COMPACTION_SORT_COLUMNS {
+            errors.push(FieldError::new(
+                "sort_by",
+                "out_of_range",
+                format!("sort_by needs 1 to {} columns", MAX_COMPACTION_SORT_COLUMNS),
+            ));
+        }
+        let schema = unified_royalty_schema();
+        for (i, column) in self.sort_by.iter().enumerate() {
+            if schema.field_with_name(column).is_err() {
+                errors.push(FieldError::new(
+                    &format!("sort_by[{}]", i),
+                    "unknown_column",
+                    format!("'{}' is not a dataset column", column),
+                ));
+            }
+        }
+        if let Some(filename) = &self.filename {
+            check_name(&mut errors, "filename", filename);
+        }
+        errors
+    }
+}
+
+impl Validate for ConfirmUploadRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        self.inference.validate()
+    }
+}
+
+impl Validate for InferenceOptions {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.infer_max_records.is_some_and(|n| n == 0 || n > MAX_INFER_MAX_RECORDS) {
+            errors.push(FieldError::new(
+                "infer_max_records",
+                "out_of_range",
+                format!(
+                    "infer_max_records must be between 1 and {}; use full_scan_inference to read every row",
+                    MAX_INFER_MAX_RECORDS
+                ),
+            ));
+        }
+        errors
+    }
+}
+
+impl Validate for UpdateColumnRestrictionsRequest {}
+
+/// Text columns starter queries group by, most telling first.
+pub const SUGGESTION_CATEGORICAL_COLUMNS: [&str; 7] =
+    ["platform", "territory", "artist", "title", "album", "transaction_type", "currency"];
+
+/// Numeric columns starter queries total, with the names their totals come back as.
+const SUGGESTION_NUMERIC_COLUMNS: [(&str, &str); 2] = [("net_revenue", "total_revenue"), ("quantity", "total_streams")];
+
+/// Categorical columns that get a top-values suggestion.
+const MAX_TOP_VALUE_SUGGESTIONS: usize = 3;
+
+/// Rows of each ranked suggestion.
+const SUGGESTION_ROWS: usize = 10;
+
+/// A starter query for a dataset, ready to send to the analytics query, download or chart endpoints.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuerySuggestion {
+    pub title: String,
+    pub query: AnalyticsQueryRequest,
+}
+
+/// Starter queries for a dataset from the number of distinct values of each of its visible columns
+/// (numeric columns only need to be present): the most common values of the first few categorical
+/// columns, numeric totals by the first categorical column, and rows per reporting date when the
+/// dataset spans several. Columns without values are skipped.
+pub fn starter_queries(dataset_id: Uuid, distinct_values: &HashMap<String, i64>) -> Vec<QuerySuggestion> {
+    let present = |column: &str| distinct_values.get(column).is_some_and(|n| *n > 0);
+    let categorical: Vec<&str> = SUGGESTION_CATEGORICAL_COLUMNS.iter().copied().filter(|c| present(c)).collect();
+    let numeric: Vec<(&str, &str)> = SUGGESTION_NUMERIC_COLUMNS.iter().copied().filter(|(c, _)| present(c)).collect();
+    let suggestion = |title: String, sql: String| QuerySuggestion {
+        title,
+        query: AnalyticsQueryRequest {
+            sql: Some(sql),
+            dataset_ids: Some(vec![dataset_id]),
+            ..AnalyticsQueryRequest::default()
+        },
+    };
+
+    let mut suggestions: Vec<QuerySuggestion> = categorical
+        .iter()
+        .take(MAX_TOP_VALUE_SUGGESTIONS)
+        .map(|c| {
+            suggestion(
+                format!("Most common {} values", c),
+                format!(
+                    "SELECT {c}, COUNT(*) AS row_count FROM royalty_data GROUP BY {c} ORDER BY row_count DESC LIMIT {SUGGESTION_ROWS}"
+                ),
+            )
+        })
+        .collect();
+
+    if let Some((_, order)) = numeric.first() {
+        let totals: Vec<String> = numeric.iter().map(|(c, alias)| format!("SUM({}) AS {}", c, alias)).collect();
+        let names: Vec<&str> = numeric.iter().map(|(c, _)| *c).collect();
+        suggestions.push(match categorical.first() {
+            Some(c) => suggestion(
+                format!("{} by {}", names.join(" and "), c),
+                format!(
+                    "SELECT {c}, {} FROM royalty_data GROUP BY {c} ORDER BY {order} DESC LIMIT {SUGGESTION_ROWS}",
+                    totals.join(", ")
+                ),
+            ),
+            None => suggestion(
+                format!("Total {}", names.join(" and ")),
+                format!("SELECT {} FROM royalty_data", totals.join(", ")),
+            ),
+        });
+    }
+
+    if distinct_values.get("reporting_date").is_some_and(|n| *n > 1) {
+        suggestions.push(suggestion(
+            "Rows per reporting date".to_string(),
+            "SELECT reporting_date, COUNT(*) AS row_count FROM royalty_data GROUP BY reporting_date ORDER BY reporting_date"
+                .to_string(),
+        ));
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starter_queries() {
+        let dataset_id = Uuid::new_v4();
+        let distinct_values: HashMap<String, i64> =
+            [("platform", 2), ("territory", 0), ("artist", 5), ("net_revenue", 9), ("reporting_date", 3)]
+                .into_iter()
+                .map(|(c, n)| (c.to_string(), n))
+                .collect();
+        let suggestions = starter_queries(dataset_id, &distinct_values);
+        let titles: Vec<&str> = suggestions.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Most common platform values",
+                "Most common artist values",
+                "net_revenue by platform",
+                "Rows per reporting date"
+            ]
+        );
+        assert_eq!(
+            suggestions[2].query.sql.as_deref(),
+            Some(
+                "SELECT platform, SUM(net_revenue) AS total_revenue FROM royalty_data GROUP BY platform \
+                 ORDER BY total_revenue DESC LIMIT 10"
+            )
+        );
+        assert_eq!(suggestions[0].query.dataset_ids, Some(vec![dataset_id]));
+
+        // One reporting date is no trend, and without text columns the totals are not grouped
+        let distinct_values: HashMap<String, i64> =
+            [("quantity", 4), ("reporting_date", 1)].into_iter().map(|(c, n)| (c.to_string(), n)).collect();
+        let suggestions = starter_queries(dataset_id, &distinct_values);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(
+            suggestions[0].query.sql.as_deref(),
+            Some("SELECT SUM(quantity) AS total_streams FROM royalty_data")
+        );
+    }
 }
@@ -1,5 +1,10 @@
-use crate::db::models::WorkspaceRole;
-use serde::Deserialize;
+use crate::db::models::{Dataset, Pipeline, WorkspaceRole};
+use crate::normalization::{InferredSchema, SourceColumn};
+use crate::server::dtos::analytics::StructuredAnalyticsQuery;
+use crate::server::validation::{Validate, check_name};
+use crate::utils::error::FieldError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use uuid::Uuid;
 
@@ -18,3 +23,152 @@ pub struct AddWorkspaceMemberRequest {
     pub user_id: Uuid,
     pub role: WorkspaceRole,
 }
+
+impl Validate for CreateWorkspaceRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for UpdateWorkspaceRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        check_name(&mut errors, "name", &self.name);
+        errors
+    }
+}
+
+impl Validate for AddWorkspaceMemberRequest {}
+
+/// Version of the workspace archive layout, bumped when a change would break importing older archives.
+pub const WORKSPACE_ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct ExportWorkspaceRequest {
+    /// Also add the processed Parquet file of every READY dataset (default false)
+    #[serde(default)]
+    pub include_files: bool,
+}
+
+impl Validate for ExportWorkspaceRequest {}
+
+/// `manifest.json` of a workspace archive.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WorkspaceArchiveManifest {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub workspace_id: Uuid,
+    pub workspace_name: String,
+    /// Whether `files/` holds the datasets' Parquet files
+    pub includes_files: bool,
+}
+
+/// One entry of `datasets.json`: an upload and what processing learned about it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedDataset {
+    pub id: Uuid,
+    pub filename: String,
+    pub distributor_source: String,
+    pub status: String,
+    pub row_count: i64,
+    pub file_size_bytes: i64,
+    pub source_columns: Vec<SourceColumn>,
+    pub source_encoding: Option<String>,
+    pub inferred_schema: Option<InferredSchema>,
+    pub created_at: DateTime<Utc>,
+    /// Path of the Parquet file inside the archive, when files are included and the dataset is READY
+    pub file: Option<String>,
+}
+
+impl ArchivedDataset {
+    pub fn from_dataset(dataset: Dataset, file: Option<String>) -> Self {
+        Self {
+            id: dataset.id,
+            filename: dataset.filename,
+            distributor_source: dataset.distributor_source,
+            status: dataset.status,
+            row_count: dataset.row_count,
+            file_size_bytes: dataset.file_size_bytes,
+            source_columns: dataset.source_columns.0,
+            source_encoding: dataset.source_encoding,
+            inferred_schema: dataset.inferred_schema.map(|s| s.0),
+            created_at: dataset.created_at,
+            file,
+        }
+    }
+}
+
+/// One entry of `pipelines.json`: a saved query.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedPipeline {
+    pub name: String,
+    pub description: Option<String>,
+    pub dataset_pattern: Option<String>,
+    pub query: StructuredAnalyticsQuery,
+}
+
+impl From<Pipeline> for ArchivedPipeline {
+    fn from(pipeline: Pipeline) -> Self {
+        Self {
+            name: pipeline.name,
+            description: pipeline.description,
+            dataset_pattern: pipeline.dataset_pattern,
+            query: pipeline.query.0,
+        }
+    }
+}
+
+/// What to do with an imported dataset or pipeline whose name is already taken in the workspace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportConflictPolicy {
+    /// Import under a free name, e.g. `june (2).csv`
+    #[default]
+    Rename,
+    /// Leave the existing one and do not import
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ImportOutcome {
+    Imported,
+    /// Imported under another name because the original was taken
+    Renamed,
+    Skipped,
+}
+
+/// Result of importing one dataset or pipeline of an archive.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportItemResult {
+    /// Filename or pipeline name in the archive
+    pub name: String,
+    /// Name it was imported under
+    pub imported_as: Option<String>,
+    /// Id of the created dataset or pipeline
+    pub id: Option<Uuid>,
+    pub outcome: ImportOutcome,
+    /// Why the item was skipped
+    pub reason: Option<String>,
+}
+
+impl ImportItemResult {
+    pub fn skipped(name: String, reason: impl Into<String>) -> Self {
+        Self {
+            name,
+            imported_as: None,
+            id: None,
+            outcome: ImportOutcome::Skipped,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportWorkspaceResponse {
+    pub workspace: crate::db::models::Workspace,
+    pub datasets: Vec<ImportItemResult>,
+    pub pipelines: Vec<ImportItemResult>,
+}
@@ -0,0 +1,80 @@
+use crate::utils::error::DoubledeckerError;
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+
+/// The request's `If-None-Match` header, if any.
+pub struct IfNoneMatch(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IfNoneMatch
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts.headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok());
+        Ok(IfNoneMatch(value.map(str::to_string)))
+    }
+}
+
+impl IfNoneMatch {
+    /// Weak comparison against a list of tags, as `If-None-Match` is defined: `W/` prefixes are ignored.
+    fn matches(&self, etag: &str) -> bool {
+        let strip = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        self.0
+            .as_deref()
+            .is_some_and(|tags| tags.trim() == "*" || tags.split(',').any(|tag| strip(tag) == strip(etag)))
+    }
+}
+
+/// A JSON response with a weak ETag over its body, or a bodiless 304 when the client's copy has the
+/// same tag. Hashing the body rather than row timestamps also catches deletions and fields that
+/// change without touching `updated_at`; the query still runs, but polling clients skip the download.
+pub fn etagged_json<T: Serialize>(if_none_match: &IfNoneMatch, value: &T) -> Result<Response, DoubledeckerError> {
+    let body = serde_json::to_vec(value)
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize response: {}", e)))?;
+    let digest = format!("{:x}", Sha256::digest(&body));
+    let etag = format!("W/\"{}\"", &digest[..32]);
+    let etag_value =
+        HeaderValue::from_str(&etag).map_err(|e| DoubledeckerError::Internal(format!("Invalid ETag: {}", e)))?;
+    // Cached copies must be revalidated every time, which is what makes the ETag useful
+    let headers = [
+        (header::ETAG, etag_value),
+        (header::CACHE_CONTROL, HeaderValue::from_static("private, no-cache")),
+    ];
+
+    if if_none_match.matches(&etag) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    Ok((headers, [(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_modified_when_tag_matches() {
+        let value = serde_json::json!({ "data": [1, 2, 3] });
+        let first = etagged_json(&IfNoneMatch(None), &value).unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""));
+
+        let unchanged = etagged_json(&IfNoneMatch(Some(format!("\"other\", {}", etag))), &value).unwrap();
+        assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+        // Weak comparison: a strong form of the same tag still matches
+        let strong = IfNoneMatch(Some(etag.trim_start_matches("W/").to_string()));
+        assert_eq!(etagged_json(&strong, &value).unwrap().status(), StatusCode::NOT_MODIFIED);
+
+        let changed = etagged_json(&IfNoneMatch(Some(etag)), &serde_json::json!({ "data": [1, 2] })).unwrap();
+        assert_eq!(changed.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::server::dtos::events::EventStreamParams;
+use crate::server::middleware::authenticate_token;
+use crate::server::state::AppState;
+use crate::utils::error::DoubledeckerError;
+use axum::extract::{Query, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use futures::stream::{self, Stream};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(EventStreamParams),
+    responses(
+        (status = 200, description = "Server-sent event stream of the caller's activity notifications", content_type = "text/event-stream", body = crate::utils::events::ActivityEvent),
+        (status = 401, description = "Missing or invalid token")
+    ),
+    tag = "events"
+)]
+pub async fn stream_events_handler(
+    State(state): State<AppState>,
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+    Query(params): Query<EventStreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, DoubledeckerError> {
+    let token = match (&bearer, &params.access_token) {
+        (Some(TypedHeader(Authorization(bearer))), _) => bearer.token(),
+        (None, Some(token)) => token.as_str(),
+        (None, None) => return Err(DoubledeckerError::Unauthorized),
+    };
+    let user_id = authenticate_token(&state.db_pool, token).await?.user_id;
+
+    let receiver = state.events.subscribe();
+    let events = stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) if event.user_id == user_id => {
+                    let sse_event = Event::default()
+                        .event(event.kind.as_str())
+                        .json_data(&event)
+                        .unwrap_or_else(|_| Event::default().comment("unserializable event"));
+                    return Some((Ok(sse_event), receiver));
+                }
+                Ok(_) => continue,
+                // Slow consumer: skip what was dropped and keep streaming.
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
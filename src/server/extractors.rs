@@ -1,15 +1,179 @@
+use crate::config::trusted_proxies;
 use crate::db::models::WorkspaceRole;
-use crate::db::queries::verify_workspace_permission;
+use crate::db::models::{ColumnRestriction, DatasetStatus};
+use crate::db::queries::{
+    get_datasets_by_ids, get_segments_by_ids, get_user_settings, list_workspace_column_restrictions,
+    verify_workspace_permission,
+};
+use crate::engine::udfs::MaskMode;
+use crate::engine::QueryScope;
+use crate::server::dtos::analytics::AnalyticsQueryRequest;
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::HeaderMap;
+use axum::http::request::Parts;
+use ipnet::IpNet;
+use sqlx::PgPool;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 use uuid::Uuid;
 
+/// Minimum role that can see columns marked as restricted on a dataset.
+pub const RESTRICTED_COLUMNS_MIN_ROLE: WorkspaceRole = WorkspaceRole::Admin;
+
 pub async fn verify_workspace_access(
     state: &AppState,
     workspace_id: Uuid,
     user_id: Uuid,
     required_role: WorkspaceRole,
+) -> Result<WorkspaceRole, DoubledeckerError> {
+    verify_workspace_permission(&state.db_pool, workspace_id, user_id, required_role).await
+}
+
+/// Engine scope for a workspace member: restricted columns are hidden or masked below `RESTRICTED_COLUMNS_MIN_ROLE`.
+pub async fn query_scope_for_role(
+    state: &AppState,
+    workspace_id: Uuid,
+    role: WorkspaceRole,
+) -> Result<QueryScope, DoubledeckerError> {
+    if role >= RESTRICTED_COLUMNS_MIN_ROLE {
+        return Ok(QueryScope::default());
+    }
+    let restrictions = list_workspace_column_restrictions(&state.db_pool, workspace_id).await?;
+    Ok(apply_column_restrictions(QueryScope::default(), &restrictions))
+}
+
+/// Add a query's lookup datasets to its scope. Each must be a ready dataset of the workspace.
+pub async fn with_lookup_datasets(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    scope: QueryScope,
+    dataset_ids: Vec<Uuid>,
+) -> Result<QueryScope, DoubledeckerError> {
+    if dataset_ids.is_empty() {
+        return Ok(scope);
+    }
+    check_ready_datasets(pool, workspace_id, &dataset_ids, "Lookup dataset").await?;
+    Ok(scope.with_lookup_datasets(dataset_ids))
+}
+
+/// Every dataset in `dataset_ids` must be a ready dataset of the workspace; errors name them as `label`.
+pub async fn check_ready_datasets(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    dataset_ids: &[Uuid],
+    label: &str,
 ) -> Result<(), DoubledeckerError> {
-    verify_workspace_permission(&state.db_pool, workspace_id, user_id, required_role).await?;
+    let datasets = get_datasets_by_ids(pool, workspace_id, dataset_ids).await?;
+    for id in dataset_ids {
+        match datasets.iter().find(|d| d.id == *id) {
+            None => return Err(DoubledeckerError::NotFound(format!("{} {} not found", label, id))),
+            Some(d) if d.status != DatasetStatus::Ready.as_str() => {
+                return Err(DoubledeckerError::BadRequest(format!("{} {} is not ready", label, id)));
+            }
+            Some(_) => {}
+        }
+    }
     Ok(())
 }
+
+/// The query with the segments it references replaced by their current filters. Each must be a
+/// segment of the workspace.
+pub async fn expand_segments(
+    pool: &PgPool,
+    workspace_id: Uuid,
+    mut request: AnalyticsQueryRequest,
+) -> Result<AnalyticsQueryRequest, DoubledeckerError> {
+    let segment_ids = request.segment_ids();
+    if segment_ids.is_empty() {
+        return Ok(request);
+    }
+    let segments = get_segments_by_ids(pool, workspace_id, &segment_ids).await?;
+    request.expand_segments(&segments.into_iter().map(|s| (s.id, s.filters.0)).collect())?;
+    Ok(request)
+}
+
+/// The user's preferred time zone from their settings, if they set one.
+pub async fn user_timezone(state: &AppState, user_id: Uuid) -> Result<Option<String>, DoubledeckerError> {
+    Ok(get_user_settings(&state.db_pool, user_id).await?.timezone)
+}
+
+pub fn apply_column_restrictions(scope: QueryScope, restrictions: &[ColumnRestriction]) -> QueryScope {
+    restrictions.iter().fold(scope, |scope, r| {
+        scope.with_column_mask(&r.column_name, MaskMode::from_db_str(&r.mode))
+    })
+}
+
+/// The client's address: the TCP peer, or the address a trusted proxy (`TRUSTED_PROXIES`) reports
+/// for it in `X-Forwarded-For`. `None` when the server was started without peer addresses.
+pub struct ClientIp(pub Option<IpAddr>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+        Ok(ClientIp(resolve_client_ip(peer, &parts.headers, trusted_proxies())))
+    }
+}
+
+/// Only a trusted peer's `X-Forwarded-For` is believed, read from the right: the first address not
+/// itself a trusted proxy is the client. Anything further left was written by the client.
+pub fn resolve_client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let peer = peer?;
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return Some(peer);
+    }
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .collect();
+    let mut client = peer;
+    for hop in forwarded.into_iter().rev() {
+        match hop.parse::<IpAddr>() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+        if !is_trusted(&client) {
+            break;
+        }
+    }
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_from_untrusted_peers() {
+        let peer: IpAddr = "203.0.113.7".parse().unwrap();
+        let headers = forwarded_for("198.51.100.1");
+        assert_eq!(resolve_client_ip(Some(peer), &headers, &[]), Some(peer));
+        assert_eq!(resolve_client_ip(None, &headers, &[]), None);
+    }
+
+    #[test]
+    fn test_client_ip_reads_forwarded_for_from_trusted_proxies() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let proxy: IpAddr = "10.1.2.3".parse().unwrap();
+        // The client prepended a fake address; the proxies appended the real one and their own
+        let headers = forwarded_for("1.1.1.1, 198.51.100.1, 10.9.9.9");
+        assert_eq!(resolve_client_ip(Some(proxy), &headers, &trusted), Some("198.51.100.1".parse().unwrap()));
+        assert_eq!(resolve_client_ip(Some(proxy), &HeaderMap::new(), &trusted), Some(proxy));
+        assert_eq!(resolve_client_ip(Some(proxy), &forwarded_for("garbage"), &trusted), Some(proxy));
+    }
+}
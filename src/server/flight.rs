@@ -0,0 +1,249 @@
+use crate::db::models::WorkspaceRole;
+use crate::server::dtos::analytics::AnalyticsQueryRequest;
+use crate::server::extractors::{expand_segments, query_scope_for_role, verify_workspace_access, with_lookup_datasets};
+use crate::server::middleware::{AuthenticatedUser, authenticate_token};
+use crate::server::state::AppState;
+use crate::utils::error::DoubledeckerError;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status, Streaming};
+use uuid::Uuid;
+
+/// Opaque ticket handed to `DoGet`. Carries the same query body as
+/// `POST /api/workspaces/{workspace_id}/analytics/query`, plus the target workspace.
+#[derive(Debug, Deserialize)]
+pub struct QueryTicket {
+    pub workspace_id: Uuid,
+    #[serde(flatten)]
+    pub query: AnalyticsQueryRequest,
+}
+
+#[derive(Debug, Serialize)]
+struct TableTicket<'a> {
+    workspace_id: Uuid,
+    sql: &'a str,
+}
+
+/// Arrow Flight service exposing analytics queries as Arrow record batches over gRPC.
+/// Callers authenticate with the same JWT as the HTTP API, sent as `authorization: Bearer <token>` metadata.
+#[derive(Clone)]
+pub struct AnalyticsFlightService {
+    state: AppState,
+}
+
+impl AnalyticsFlightService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<AuthenticatedUser, DoubledeckerError> {
+        let header = request
+            .metadata()
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .ok_or(DoubledeckerError::Unauthorized)?;
+        let token = header
+            .strip_prefix("Bearer ")
+            .or_else(|| header.strip_prefix("bearer "))
+            .ok_or(DoubledeckerError::Unauthorized)?;
+        authenticate_token(&self.state.db_pool, token).await
+    }
+}
+
+/// Serve the Flight service until the process exits.
+pub async fn serve_flight(state: AppState, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+    tonic::transport::Server::builder()
+        .add_service(FlightServiceServer::new(AnalyticsFlightService::new(state)))
+        .serve(addr)
+        .await
+}
+
+fn to_status(err: DoubledeckerError) -> Status {
+    match err {
+        DoubledeckerError::Unauthorized | DoubledeckerError::AuthenticationError(_) => {
+            Status::unauthenticated(err.message())
+        }
+        DoubledeckerError::Forbidden(_) | DoubledeckerError::AccountSuspended(_) => {
+            Status::permission_denied(err.message())
+        }
+        DoubledeckerError::NotFound(_) | DoubledeckerError::TableNotFound(_) => {
+            Status::not_found(err.message())
+        }
+        DoubledeckerError::BadRequest(_)
+        | DoubledeckerError::InvalidQuery(_)
+        | DoubledeckerError::ColumnNotFound(_)
+        | DoubledeckerError::QueryStepFailed(_)
+        | DoubledeckerError::FilterTypeMismatch(_)
+        | DoubledeckerError::Validation(_) => Status::invalid_argument(err.message()),
+        DoubledeckerError::RateLimited(_)
+        | DoubledeckerError::TooManyConcurrentQueries(_)
+        | DoubledeckerError::QueryMemoryExceeded(_) => {
+            Status::resource_exhausted(err.message())
+        }
+        _ => Status::internal(err.message()),
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for AnalyticsFlightService {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not supported; send a Bearer token with each call"))
+    }
+
+    /// Lists the tables of a workspace. `Criteria.expression` must be the workspace ID.
+    async fn list_flights(
+        &self,
+        request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let user = self.authenticate(&request).await.map_err(to_status)?;
+        let expression = String::from_utf8(request.into_inner().expression.to_vec())
+            .map_err(|_| Status::invalid_argument("Criteria expression must be a UTF-8 workspace ID"))?;
+        let workspace_id = Uuid::parse_str(expression.trim())
+            .map_err(|_| Status::invalid_argument("Criteria expression must be a workspace ID"))?;
+
+        let role = verify_workspace_access(&self.state, workspace_id, user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(to_status)?;
+        let scope = query_scope_for_role(&self.state, workspace_id, role)
+            .await
+            .map_err(to_status)?;
+
+        let tables = self
+            .state
+            .engine
+            .list_tables(workspace_id, &scope)
+            .await
+            .map_err(to_status)?;
+
+        let mut infos = Vec::with_capacity(tables.len());
+        for (name, schema) in tables {
+            let sql = format!("SELECT * FROM {}", name);
+            let ticket = serde_json::to_vec(&TableTicket { workspace_id, sql: &sql })
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let info = FlightInfo::new()
+                .try_with_schema(&schema)
+                .map_err(|e| Status::internal(e.to_string()))?
+                .with_descriptor(FlightDescriptor::new_path(vec![name]))
+                .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(ticket)));
+            infos.push(Ok(info));
+        }
+
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("Use ListFlights to discover tables"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("PollFlightInfo is not supported"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("Use ListFlights to discover table schemas"))
+    }
+
+    /// Executes the query in the ticket and streams the result as Arrow IPC.
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let user = self.authenticate(&request).await.map_err(to_status)?;
+        let ticket: QueryTicket = serde_json::from_slice(&request.into_inner().ticket)
+            .map_err(|e| Status::invalid_argument(format!("Invalid ticket: {}", e)))?;
+
+        let role = verify_workspace_access(&self.state, ticket.workspace_id, user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(to_status)?;
+        let scope = query_scope_for_role(&self.state, ticket.workspace_id, role)
+            .await
+            .map_err(to_status)?;
+        let query = expand_segments(&self.state.db_pool, ticket.workspace_id, ticket.query)
+            .await
+            .map_err(to_status)?;
+        let scope = with_lookup_datasets(&self.state.db_pool, ticket.workspace_id, scope, query.lookup_dataset_ids())
+            .await
+            .map_err(to_status)?;
+
+        let sql = query.to_safe_sql().map_err(to_status)?;
+        let permit = self
+            .state
+            .query_limiter
+            .acquire(user.user_id)
+            .await
+            .map_err(to_status)?;
+        let batches = self
+            .state
+            .engine
+            .stream_scoped_analytics(ticket.workspace_id, &scope, &sql)
+            .await
+            .map_err(to_status)?
+            .map_err(|e| FlightError::Tonic(to_status(DoubledeckerError::from_query_execution(e))))
+            // The query slot stays taken until the client has drained the stream
+            .inspect(move |_| {
+                let _ = &permit;
+            });
+
+        let flight_data = FlightDataEncoderBuilder::new()
+            .build(batches)
+            .map_err(Status::from);
+
+        Ok(Response::new(flight_data.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("DoPut is not supported; upload datasets over HTTP"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("DoExchange is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("DoAction is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
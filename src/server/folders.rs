@@ -0,0 +1,188 @@
+use crate::db::models::{Folder, PaginatedResponse, PaginationParams, WorkspaceRole};
+use crate::db::queries::{
+    create_folder, delete_folder, get_folder, list_folders, move_datasets_to_folder, move_folder, rename_folder,
+};
+use crate::server::dtos::DeleteResponse;
+use crate::server::dtos::folders::*;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::server::state::AppState;
+use crate::utils::error::DoubledeckerError;
+use axum::extract::{Path, State};
+use axum::Json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/folders",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = CreateFolderRequest,
+    responses(
+        (status = 200, description = "Folder created", body = Folder),
+        (status = 400, description = "A sibling folder already has this name"),
+        (status = 404, description = "Parent folder not found")
+    ),
+    tag = "folders"
+)]
+pub async fn create_folder_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<CreateFolderRequest>,
+) -> Result<Json<Folder>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    if let Some(parent_id) = payload.parent_id {
+        get_folder(&state.db_pool, workspace_id, parent_id).await?;
+    }
+    let folder = create_folder(&state.db_pool, workspace_id, payload.parent_id, payload.name.trim()).await?;
+    Ok(Json(folder))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/folders",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "All folders of the workspace", body = PaginatedFolders)
+    ),
+    tag = "folders"
+)]
+pub async fn list_folders_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<Folder>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let folders = list_folders(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    Ok(Json(folders))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/folders/{folder_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("folder_id" = Uuid, Path, description = "Folder ID")
+    ),
+    request_body = RenameFolderRequest,
+    responses(
+        (status = 200, description = "Folder renamed", body = Folder)
+    ),
+    tag = "folders"
+)]
+pub async fn rename_folder_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, folder_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<RenameFolderRequest>,
+) -> Result<Json<Folder>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let folder = rename_folder(&state.db_pool, workspace_id, folder_id, payload.name.trim()).await?;
+    Ok(Json(folder))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/folders/{folder_id}/move",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("folder_id" = Uuid, Path, description = "Folder ID")
+    ),
+    request_body = MoveFolderRequest,
+    responses(
+        (status = 200, description = "Folder moved", body = Folder),
+        (status = 400, description = "Destination is the folder itself or one of its subfolders")
+    ),
+    tag = "folders"
+)]
+pub async fn move_folder_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, folder_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<MoveFolderRequest>,
+) -> Result<Json<Folder>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    if let Some(parent_id) = payload.parent_id {
+        get_folder(&state.db_pool, workspace_id, parent_id).await?;
+    }
+    let folder = move_folder(&state.db_pool, workspace_id, folder_id, payload.parent_id).await?;
+    Ok(Json(folder))
+}
+
+/// Delete a folder and its subfolders. Datasets inside are kept and become unfiled.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/folders/{folder_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("folder_id" = Uuid, Path, description = "Folder ID")
+    ),
+    responses(
+        (status = 200, description = "Folder deleted", body = DeleteResponse)
+    ),
+    tag = "folders"
+)]
+pub async fn delete_folder_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, folder_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_folder(&state.db_pool, workspace_id, folder_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Folder not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Folder deleted successfully".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/move",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = MoveDatasetsRequest,
+    responses(
+        (status = 200, description = "Datasets moved", body = MoveDatasetsResponse),
+        (status = 404, description = "Destination folder not found")
+    ),
+    tag = "folders"
+)]
+pub async fn move_datasets_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<MoveDatasetsRequest>,
+) -> Result<Json<MoveDatasetsResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    if let Some(folder_id) = payload.folder_id {
+        get_folder(&state.db_pool, workspace_id, folder_id).await?;
+    }
+    let moved =
+        move_datasets_to_folder(&state.db_pool, workspace_id, &payload.dataset_ids, payload.folder_id).await?;
+    let mut seen = HashSet::new();
+    let not_found: Vec<Uuid> = payload
+        .dataset_ids
+        .into_iter()
+        .filter(|id| !moved.contains(id) && seen.insert(*id))
+        .collect();
+
+    Ok(Json(MoveDatasetsResponse { moved, not_found }))
+}
@@ -0,0 +1,274 @@
+use crate::db::models::{DatasetListParams, PaginatedResponse, Pipeline, PipelineListParams, WorkspaceRole};
+use crate::db::queries::{get_dataset_by_id, get_datasets, get_pipeline, get_user_by_id, list_pipelines};
+use crate::normalization::{InferredSchema, SourceColumn, ValidationReport};
+use crate::server::analytics::execute_query_handler;
+use crate::server::dtos::analytics::{AnalyticsQueryRequest, AnalyticsQueryResponse, StructuredAnalyticsQuery};
+use crate::server::dtos::common::DatasetResponse;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, check};
+use crate::utils::error::DoubledeckerError;
+use async_graphql::{
+    Context, EmptySubscription, ErrorExtensions, Json as GraphqlJson, Object, OutputType, Schema, SimpleObject,
+};
+use axum::extract::{Path, State};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// Deepest selection a request may nest; the schema has no recursive types, so this only stops abuse.
+const MAX_QUERY_DEPTH: usize = 8;
+
+pub type DoubledeckerSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// The schema, built once. Requests carry the app state and the caller as context data.
+pub fn schema() -> &'static DoubledeckerSchema {
+    static SCHEMA: OnceLock<DoubledeckerSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+            .limit_depth(MAX_QUERY_DEPTH)
+            .finish()
+    })
+}
+
+/// GraphQL over datasets, pipelines, the caller's profile and analytics queries, for clients that
+/// want several of them, with only the fields they use, in one round trip. Authenticated like the
+/// REST API; errors carry the REST error `code` in their extensions.
+pub async fn graphql_handler(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema().execute(request.data(state).data(auth_user)).await)
+}
+
+fn graphql_error(e: DoubledeckerError) -> async_graphql::Error {
+    let code = e.code();
+    async_graphql::Error::new(e.to_string()).extend_with(|_, ext| ext.set("code", code))
+}
+
+/// The app state and caller of the request being resolved.
+fn request_context<'a>(ctx: &Context<'a>) -> async_graphql::Result<(&'a AppState, &'a AuthenticatedUser)> {
+    Ok((ctx.data::<AppState>()?, ctx.data::<AuthenticatedUser>()?))
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "User")]
+pub struct UserNode {
+    pub id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub user_type: String,
+}
+
+/// An uploaded file and the table made from it.
+#[derive(SimpleObject)]
+#[graphql(name = "Dataset")]
+pub struct DatasetNode {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub distributor_source: String,
+    pub filename: String,
+    pub file_size_bytes: i64,
+    pub row_count: i64,
+    /// `PENDING_UPLOAD`, `QUEUED`, `PROCESSING`, `READY`, `FAILED` or `QUARANTINED`
+    pub status: String,
+    pub error_message: Option<String>,
+    pub public_token: Option<String>,
+    pub folder_id: Option<Uuid>,
+    pub source_columns: GraphqlJson<Vec<SourceColumn>>,
+    pub source_encoding: Option<String>,
+    pub inferred_schema: Option<GraphqlJson<InferredSchema>>,
+    pub validation_report: Option<GraphqlJson<ValidationReport>>,
+    pub last_queried_at: Option<DateTime<Utc>>,
+    pub query_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DatasetResponse> for DatasetNode {
+    fn from(dataset: DatasetResponse) -> Self {
+        Self {
+            id: dataset.id,
+            workspace_id: dataset.workspace_id,
+            distributor_source: dataset.distributor_source,
+            filename: dataset.filename,
+            file_size_bytes: dataset.file_size_bytes,
+            row_count: dataset.row_count,
+            status: dataset.status,
+            error_message: dataset.error_message,
+            public_token: dataset.public_token,
+            folder_id: dataset.folder_id,
+            source_columns: GraphqlJson(dataset.source_columns),
+            source_encoding: dataset.source_encoding,
+            inferred_schema: dataset.inferred_schema.map(GraphqlJson),
+            validation_report: dataset.validation_report.map(GraphqlJson),
+            last_queried_at: dataset.last_queried_at,
+            query_count: dataset.query_count,
+            created_at: dataset.created_at,
+            updated_at: dataset.updated_at,
+        }
+    }
+}
+
+/// A saved structured query, run over the datasets it is given or that match `datasetPattern`.
+#[derive(SimpleObject)]
+#[graphql(name = "Pipeline")]
+pub struct PipelineNode {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub dataset_pattern: Option<String>,
+    pub query: GraphqlJson<StructuredAnalyticsQuery>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<Pipeline> for PipelineNode {
+    fn from(pipeline: Pipeline) -> Self {
+        Self {
+            id: pipeline.id,
+            workspace_id: pipeline.workspace_id,
+            name: pipeline.name,
+            description: pipeline.description,
+            dataset_pattern: pipeline.dataset_pattern,
+            query: GraphqlJson(pipeline.query.0),
+            created_by: pipeline.created_by,
+            created_at: pipeline.created_at,
+            updated_at: pipeline.updated_at,
+        }
+    }
+}
+
+/// One page of a list; pass `nextCursor` as `cursor` for the next.
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "DatasetPage", params(DatasetNode)), concrete(name = "PipelinePage", params(PipelineNode)))]
+pub struct Page<T: OutputType> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T: OutputType> Page<T> {
+    fn from_response<U>(response: PaginatedResponse<U>, node: impl Fn(U) -> T) -> Self {
+        Self {
+            items: response.data.into_iter().map(node).collect(),
+            next_cursor: response.pagination.next_cursor,
+            has_more: response.pagination.has_more,
+        }
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The signed-in user
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<UserNode> {
+        let (state, auth_user) = request_context(ctx)?;
+        let user = get_user_by_id(&state.db_pool, auth_user.user_id).await.map_err(graphql_error)?;
+        Ok(UserNode { id: user.id, name: user.name, email: user.email, user_type: user.user_type })
+    }
+
+    /// Datasets of a workspace, newest first, as listed by `GET /api/workspaces/{id}/datasets`
+    #[allow(clippy::too_many_arguments)]
+    async fn datasets(
+        &self,
+        ctx: &Context<'_>,
+        workspace_id: Uuid,
+        cursor: Option<String>,
+        limit: Option<usize>,
+        search: Option<String>,
+        folder_id: Option<Uuid>,
+        favorites: Option<bool>,
+    ) -> async_graphql::Result<Page<DatasetNode>> {
+        let (state, auth_user) = request_context(ctx)?;
+        verify_workspace_access(state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(graphql_error)?;
+
+        let params = DatasetListParams { cursor, limit, search, folder_id, favorites, ..Default::default() };
+        let params = check(params).map_err(graphql_error)?;
+        let datasets = get_datasets(&state.db_pool, workspace_id, auth_user.user_id, &params)
+            .await
+            .map_err(graphql_error)?;
+        Ok(Page::from_response(datasets, |d| DatasetResponse::from_dataset(d).into()))
+    }
+
+    async fn dataset(&self, ctx: &Context<'_>, workspace_id: Uuid, id: Uuid) -> async_graphql::Result<DatasetNode> {
+        let (state, auth_user) = request_context(ctx)?;
+        verify_workspace_access(state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(graphql_error)?;
+
+        let dataset = get_dataset_by_id(&state.db_pool, workspace_id, id).await.map_err(graphql_error)?;
+        Ok(DatasetResponse::from_dataset(dataset).into())
+    }
+
+    /// Pipelines (saved queries) of a workspace, newest first
+    async fn pipelines(
+        &self,
+        ctx: &Context<'_>,
+        workspace_id: Uuid,
+        cursor: Option<String>,
+        limit: Option<usize>,
+        favorites: Option<bool>,
+    ) -> async_graphql::Result<Page<PipelineNode>> {
+        let (state, auth_user) = request_context(ctx)?;
+        verify_workspace_access(state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(graphql_error)?;
+
+        let params = check(PipelineListParams { cursor, limit, favorites }).map_err(graphql_error)?;
+        let limit = params.pagination().effective_limit();
+        let favorites_only = params.favorites.unwrap_or(false);
+        let pipelines =
+            list_pipelines(&state.db_pool, workspace_id, auth_user.user_id, favorites_only, params.cursor, limit)
+                .await
+                .map_err(graphql_error)?;
+        Ok(Page::from_response(pipelines, PipelineNode::from))
+    }
+
+    async fn pipeline(&self, ctx: &Context<'_>, workspace_id: Uuid, id: Uuid) -> async_graphql::Result<PipelineNode> {
+        let (state, auth_user) = request_context(ctx)?;
+        verify_workspace_access(state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer)
+            .await
+            .map_err(graphql_error)?;
+
+        let pipeline = get_pipeline(&state.db_pool, workspace_id, id).await.map_err(graphql_error)?;
+        Ok(pipeline.into())
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Run an analytics query. `request` takes the body of `POST /api/workspaces/{id}/analytics/query`
+    /// (SQL, or a structured query with its steps) and the result is that endpoint's response; the
+    /// query is limited, recorded and counted the same way.
+    async fn execute_query(
+        &self,
+        ctx: &Context<'_>,
+        workspace_id: Uuid,
+        request: GraphqlJson<AnalyticsQueryRequest>,
+    ) -> async_graphql::Result<GraphqlJson<AnalyticsQueryResponse>> {
+        let (state, auth_user) = request_context(ctx)?;
+        let payload = check(request.0).map_err(graphql_error)?;
+        let caller = AuthenticatedUser {
+            user_id: auth_user.user_id,
+            email: auth_user.email.clone(),
+            session_id: auth_user.session_id,
+        };
+
+        let Json(response) =
+            execute_query_handler(caller, Path(workspace_id), State(state.clone()), ValidatedJson(payload))
+                .await
+                .map_err(graphql_error)?;
+        Ok(GraphqlJson(response))
+    }
+}
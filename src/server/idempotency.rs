@@ -0,0 +1,155 @@
+use crate::db::models::IdempotencyRecord;
+use crate::db::queries::{claim_idempotency_key, complete_idempotency_key, release_idempotency_key};
+use crate::utils::error::{DoubledeckerError, FieldError};
+use axum::async_trait;
+use axum::body::Body;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::Response;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::future::Future;
+use uuid::Uuid;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Set on responses replayed from an earlier request with the same key.
+const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+const MAX_KEY_LENGTH: usize = 255;
+/// Largest response kept for replay. A bigger export is not stored, so a retry runs the query again.
+const MAX_STORED_RESPONSE_BYTES: usize = 25 * 1024 * 1024;
+
+/// The optional `Idempotency-Key` header: 1-255 visible ASCII characters, typically a UUID the client
+/// generates per logical request and resends on every retry.
+pub struct IdempotencyKey(pub Option<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for IdempotencyKey
+where
+    S: Send + Sync,
+{
+    type Rejection = DoubledeckerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Some(value) = parts.headers.get(IDEMPOTENCY_KEY_HEADER) else {
+            return Ok(IdempotencyKey(None));
+        };
+        let key = value.to_str().unwrap_or_default().trim();
+        if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.chars().all(|c| c.is_ascii_graphic()) {
+            return Err(DoubledeckerError::Validation(vec![FieldError::new(
+                IDEMPOTENCY_KEY_HEADER,
+                "invalid_format",
+                format!("{} must be 1-{} visible ASCII characters", IDEMPOTENCY_KEY_HEADER, MAX_KEY_LENGTH),
+            )]));
+        }
+        Ok(IdempotencyKey(Some(key.to_string())))
+    }
+}
+
+/// SHA-256 over the parts of a request that make it "the same request", length-prefixed so
+/// different splits of the same bytes hash differently.
+pub fn request_hash(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Run `handler` at most once per key. A retry with the same key and request gets the stored
+/// response back; one made while the first is still running gets a 409. Failed requests are not
+/// stored, so they can be retried with the same key. Without a key, `handler` just runs.
+pub async fn run_idempotent(
+    pool: &PgPool,
+    user_id: Uuid,
+    endpoint: &str,
+    key: Option<String>,
+    request_hash: String,
+    handler: impl Future<Output = Result<Response, DoubledeckerError>>,
+) -> Result<Response, DoubledeckerError> {
+    let Some(key) = key else {
+        return handler.await;
+    };
+
+    if let Some(existing) = claim_idempotency_key(pool, user_id, endpoint, &key, &request_hash).await? {
+        if existing.request_hash != request_hash {
+            return Err(DoubledeckerError::Validation(vec![FieldError::new(
+                IDEMPOTENCY_KEY_HEADER,
+                "reused",
+                format!("this {} was already used for a different request", IDEMPOTENCY_KEY_HEADER),
+            )]));
+        }
+        return replay(existing);
+    }
+
+    let response = match handler.await {
+        Ok(response) => response,
+        Err(e) => {
+            if let Err(release_err) = release_idempotency_key(pool, user_id, endpoint, &key).await {
+                eprintln!("Failed to release idempotency key: {}", release_err);
+            }
+            return Err(e);
+        }
+    };
+
+    let (parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to read response body: {}", e)))?;
+    // The work is done at this point: failing to store the response must not fail the request,
+    // which would make the client retry it
+    let stored = if bytes.len() <= MAX_STORED_RESPONSE_BYTES {
+        let header_str = |name| parts.headers.get(name).and_then(|v: &HeaderValue| v.to_str().ok());
+        complete_idempotency_key(
+            pool,
+            user_id,
+            endpoint,
+            &key,
+            parts.status.as_u16() as i16,
+            header_str(header::CONTENT_TYPE),
+            header_str(header::CONTENT_DISPOSITION),
+            &bytes,
+        )
+        .await
+    } else {
+        release_idempotency_key(pool, user_id, endpoint, &key).await
+    };
+    if let Err(e) = stored {
+        eprintln!("Failed to store idempotent response: {}", e);
+    }
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
+
+fn replay(record: IdempotencyRecord) -> Result<Response, DoubledeckerError> {
+    let Some(status) = record.status_code else {
+        return Err(DoubledeckerError::Conflict(format!(
+            "a request with this {} is still in progress",
+            IDEMPOTENCY_KEY_HEADER
+        )));
+    };
+    let mut builder = Response::builder()
+        .status(StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK))
+        .header(REPLAYED_HEADER, "true");
+    if let Some(content_type) = &record.content_type {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    if let Some(disposition) = &record.content_disposition {
+        builder = builder.header(header::CONTENT_DISPOSITION, disposition);
+    }
+    builder
+        .body(Body::from(record.body.unwrap_or_default()))
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to build replayed response: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_hash_separates_parts() {
+        assert_eq!(request_hash(&[b"ab", b"c"]), request_hash(&[b"ab", b"c"]));
+        assert_ne!(request_hash(&[b"ab", b"c"]), request_hash(&[b"a", b"bc"]));
+        assert_eq!(request_hash(&[]).len(), 64);
+    }
+}
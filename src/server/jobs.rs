@@ -0,0 +1,93 @@
+use crate::db::models::{BackgroundJob, JobListParams, WorkspaceRole};
+use crate::db::queries::{cancel_job, get_job, list_jobs};
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::ValidatedQuery;
+use crate::utils::error::DoubledeckerError;
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/jobs",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        JobListParams,
+    ),
+    responses(
+        (status = 200, description = "Background jobs of the workspace, newest first", body = PaginatedBackgroundJobs),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
+    ),
+    tag = "jobs"
+)]
+pub async fn list_jobs_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(params): ValidatedQuery<JobListParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = params.pagination().effective_limit();
+    let jobs = list_jobs(&state.db_pool, workspace_id, params.status, params.cursor, limit).await?;
+    etagged_json(&if_none_match, &jobs)
+}
+
+/// A job's status, attempts, last error and, once it has succeeded, its result. Supports
+/// `If-None-Match`, so polling for completion does not re-download an unchanged job.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/jobs/{job_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("job_id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Background job", body = BackgroundJob),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match"),
+        (status = 404, description = "Job not found")
+    ),
+    tag = "jobs"
+)]
+pub async fn get_job_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, job_id)): Path<(Uuid, Uuid)>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let job = get_job(&state.db_pool, workspace_id, job_id).await?;
+    etagged_json(&if_none_match, &job)
+}
+
+/// Cancel a queued or running job, such as a pipeline run nobody is waiting for any more. A running
+/// job's worker notices within a few seconds and drops its work, stopping the queries it was running.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/jobs/{job_id}/cancel",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("job_id" = Uuid, Path, description = "Job ID")
+    ),
+    responses(
+        (status = 200, description = "Cancelled job", body = BackgroundJob),
+        (status = 404, description = "Job not found"),
+        (status = 409, description = "The job has already finished")
+    ),
+    tag = "jobs"
+)]
+pub async fn cancel_job_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, job_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<BackgroundJob>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Manager).await?;
+
+    Ok(Json(cancel_job(&state.db_pool, workspace_id, job_id).await?))
+}
@@ -1,6 +1,15 @@
+use crate::config::jwt_config;
+use crate::db::models::UserSuspension;
+use crate::db::queries::{get_user_suspension, touch_user_session};
+use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
 use crate::utils::jwt::verify_token;
-use axum::{RequestPartsExt, async_trait, extract::FromRequestParts, http::request::Parts};
+use axum::{
+    BoxError, RequestPartsExt, async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use sqlx::PgPool;
 use axum_extra::{
     TypedHeader,
     headers::{Authorization, authorization::Bearer},
@@ -8,41 +17,108 @@ use axum_extra::{
 use std::env;
 use uuid::Uuid;
 
-/// Authenticated user extractor - validates JWT and extracts user ID
+/// Authenticated user extractor - validates JWT and its session, and extracts user ID
 pub struct AuthenticatedUser {
     pub user_id: Uuid,
     pub email: String,
+    pub session_id: Uuid,
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthenticatedUser
 where
     S: Send + Sync,
+    AppState: FromRef<S>,
 {
     type Rejection = DoubledeckerError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Extract the Authorization header
         let TypedHeader(Authorization(bearer)) = parts
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| DoubledeckerError::Unauthorized)?;
 
-        // Get JWT secret from environment
-        let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
+        let state = AppState::from_ref(state);
+        authenticate_token(&state.db_pool, bearer.token()).await
+    }
+}
 
-        // Verify and decode the token
-        let claims = verify_token(bearer.token(), &jwt_secret)
-            .map_err(|e| DoubledeckerError::AuthenticationError(format!("Invalid token: {}", e)))?;
+/// Platform operator, identified by an email listed in `ADMIN_EMAILS` (comma-separated).
+/// Distinct from workspace roles: admins act across all workspaces and users.
+pub struct AdminUser(pub AuthenticatedUser);
 
-        // Parse user ID from claims
-        let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
-            DoubledeckerError::AuthenticationError("Invalid user ID in token".to_string())
-        })?;
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    type Rejection = DoubledeckerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = AuthenticatedUser::from_request_parts(parts, state).await?;
+        let admins = env::var("ADMIN_EMAILS").unwrap_or_default();
+        let is_admin = admins
+            .split(',')
+            .map(str::trim)
+            .any(|admin| !admin.is_empty() && admin.eq_ignore_ascii_case(&user.email));
+        if !is_admin {
+            return Err(DoubledeckerError::Forbidden("Platform admin access required".to_string()));
+        }
+        Ok(AdminUser(user))
+    }
+}
+
+/// Validate a raw JWT, check its session is still active, and resolve the user it was issued for.
+/// Used by the extractor and by endpoints that accept the token outside the Authorization header.
+pub async fn authenticate_token(pool: &PgPool, token: &str) -> Result<AuthenticatedUser, DoubledeckerError> {
+    // Verify and decode the token against the configured signing keys
+    let claims = verify_token(token, jwt_config())
+        .map_err(|e| DoubledeckerError::AuthenticationError(format!("Invalid token: {}", e)))?;
+
+    // Parse user ID from claims
+    let user_id = Uuid::parse_str(&claims.sub).map_err(|_| {
+        DoubledeckerError::AuthenticationError("Invalid user ID in token".to_string())
+    })?;
+
+    let session_id = Uuid::parse_str(&claims.sid).map_err(|_| {
+        DoubledeckerError::AuthenticationError("Invalid session ID in token".to_string())
+    })?;
+    if !touch_user_session(pool, session_id, user_id).await? {
+        return Err(DoubledeckerError::AuthenticationError(
+            "Session has been revoked or has expired".to_string(),
+        ));
+    }
+
+    // Suspension takes effect immediately, including for tokens issued before it
+    if let Some(suspension) = get_user_suspension(pool, user_id).await? {
+        return Err(suspended_error(&suspension));
+    }
+
+    Ok(AuthenticatedUser {
+        user_id,
+        email: claims.email,
+        session_id,
+    })
+}
+
+pub fn suspended_error(suspension: &UserSuspension) -> DoubledeckerError {
+    DoubledeckerError::AccountSuspended(
+        suspension
+            .reason
+            .clone()
+            .unwrap_or_else(|| "contact support to restore access".to_string()),
+    )
+}
 
-        Ok(AuthenticatedUser {
-            user_id,
-            email: claims.email,
-        })
+/// Turn errors from the timeout and load-shedding layers into JSON error responses.
+pub async fn handle_overload(err: BoxError) -> DoubledeckerError {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        DoubledeckerError::RequestTimeout("the request took too long; try again or narrow it down".to_string())
+    } else if err.is::<tower::load_shed::error::Overloaded>() {
+        DoubledeckerError::ServiceOverloaded("too many requests in flight; retry shortly".to_string())
+    } else {
+        DoubledeckerError::Internal(err.to_string())
     }
 }
@@ -1,12 +1,39 @@
+pub mod admin;
+pub mod alerts;
 pub mod analytics;
 pub mod auth;
 pub mod catalog;
+pub mod comments;
+pub mod compaction;
+pub mod connections;
+pub mod dashboards;
+pub mod demo;
 pub mod dtos;
+pub mod events;
 pub mod extractors;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod etag;
+pub mod folders;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod idempotency;
+pub mod jobs;
 pub mod middleware;
 pub mod openapi;
 pub mod payees;
+pub mod pipelines;
+pub mod public;
+pub mod quality;
+pub mod schedules;
+pub mod search;
+pub mod segments;
+pub mod router;
 pub mod splits;
 pub mod state;
 pub mod uploads;
+pub mod validation;
+pub mod workspace_archive;
 pub mod workspaces;
+
+pub use router::build_app;
@@ -1,17 +1,42 @@
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
 #[derive(OpenApi)]
 #[openapi(
+    info(
+        title = "Doubledecker API",
+        description = "Royalty ingestion and analytics API. Authenticate with the JWT returned by /auth/login as a Bearer token."
+    ),
+    modifiers(&SecurityAddon),
+    security(("bearer_auth" = [])),
     paths(
         crate::server::auth::signup,
         crate::server::auth::login,
         crate::server::auth::get_profile,
+        crate::server::auth::get_profile_stats,
+        crate::server::auth::get_profile_settings,
+        crate::server::auth::update_profile_settings,
+        crate::server::auth::list_sessions_handler,
+        crate::server::auth::revoke_session_handler,
+        crate::server::admin::unlock_account_handler,
+        crate::server::admin::admin_overview_handler,
+        crate::server::admin::admin_storage_handler,
+        crate::server::admin::admin_errors_handler,
+        crate::server::admin::admin_largest_datasets_handler,
+        crate::server::admin::suspend_user_handler,
+        crate::server::admin::lift_suspension_handler,
+        crate::server::admin::list_suspensions_handler,
+        crate::server::admin::list_slow_queries_handler,
+        crate::server::admin::seed_demo_workspace_handler,
+        crate::server::events::stream_events_handler,
         crate::server::workspaces::create_workspace_handler,
         crate::server::workspaces::list_workspaces_handler,
         crate::server::workspaces::update_workspace_handler,
         crate::server::workspaces::delete_workspace_handler,
         crate::server::workspaces::add_workspace_member_handler,
         crate::server::workspaces::list_workspace_members_handler,
+        crate::server::workspace_archive::export_workspace_handler,
+        crate::server::workspace_archive::import_workspace_handler,
         crate::server::catalog::create_artist_handler,
         crate::server::catalog::list_artists_handler,
         crate::server::catalog::update_artist_handler,
@@ -34,10 +59,98 @@ use utoipa::OpenApi;
         crate::server::splits::delete_split_handler,
         crate::server::uploads::upload_dataset_direct,
         crate::server::uploads::generate_presigned_url_handler,
+        crate::server::uploads::preview_upload_handler,
+        crate::server::compaction::compact_datasets_handler,
         crate::server::uploads::confirm_upload_handler,
+        crate::server::uploads::get_upload_limits_handler,
+        crate::server::uploads::create_upload_session_handler,
+        crate::server::uploads::get_upload_session_handler,
+        crate::server::uploads::upload_part_handler,
+        crate::server::uploads::complete_upload_session_handler,
+        crate::server::uploads::delete_upload_session_handler,
         crate::server::uploads::list_datasets_handler,
+        crate::server::uploads::bulk_delete_datasets_handler,
+        crate::server::uploads::download_dataset_handler,
+        crate::server::uploads::share_dataset_public_handler,
+        crate::server::uploads::unshare_dataset_public_handler,
+        crate::server::uploads::list_dataset_columns_handler,
+        crate::server::uploads::update_dataset_columns_handler,
+        crate::server::uploads::scan_dataset_pii_handler,
+        crate::server::uploads::suggest_dataset_queries_handler,
+        crate::server::uploads::list_column_values_handler,
+        crate::server::uploads::list_dataset_activity_handler,
+        crate::server::uploads::favorite_dataset_handler,
+        crate::server::uploads::unfavorite_dataset_handler,
+        crate::server::public::public_table_query_handler,
+        crate::server::search::search_handler,
+        crate::server::folders::create_folder_handler,
+        crate::server::folders::list_folders_handler,
+        crate::server::folders::rename_folder_handler,
+        crate::server::folders::move_folder_handler,
+        crate::server::folders::delete_folder_handler,
+        crate::server::folders::move_datasets_handler,
+        crate::server::pipelines::create_pipeline_handler,
+        crate::server::pipelines::list_pipelines_handler,
+        crate::server::pipelines::get_pipeline_handler,
+        crate::server::pipelines::update_pipeline_handler,
+        crate::server::pipelines::delete_pipeline_handler,
+        crate::server::pipelines::favorite_pipeline_handler,
+        crate::server::pipelines::unfavorite_pipeline_handler,
+        crate::server::pipelines::duplicate_pipeline_handler,
+        crate::server::pipelines::export_pipeline_handler,
+        crate::server::pipelines::import_pipeline_handler,
+        crate::server::pipelines::run_pipeline_handler,
+        crate::server::pipelines::list_pipeline_runs_handler,
+        crate::server::pipelines::get_pipeline_run_lineage_handler,
+        crate::server::comments::create_comment_handler,
+        crate::server::comments::list_comments_handler,
+        crate::server::comments::update_comment_handler,
+        crate::server::comments::delete_comment_handler,
+        crate::server::segments::create_segment_handler,
+        crate::server::segments::list_segments_handler,
+        crate::server::segments::get_segment_handler,
+        crate::server::segments::update_segment_handler,
+        crate::server::segments::delete_segment_handler,
+        crate::server::quality::create_quality_rule_handler,
+        crate::server::quality::list_quality_rules_handler,
+        crate::server::quality::get_quality_rule_handler,
+        crate::server::quality::update_quality_rule_handler,
+        crate::server::quality::delete_quality_rule_handler,
+        crate::server::quality::run_quality_rules_handler,
+        crate::server::quality::list_quality_runs_handler,
+        crate::server::quality::get_quality_run_handler,
+        crate::server::dashboards::create_dashboard_handler,
+        crate::server::dashboards::list_dashboards_handler,
+        crate::server::dashboards::get_dashboard_handler,
+        crate::server::dashboards::update_dashboard_handler,
+        crate::server::dashboards::delete_dashboard_handler,
+        crate::server::dashboards::get_dashboard_data_handler,
+        crate::server::schedules::create_schedule_handler,
+        crate::server::schedules::list_schedules_handler,
+        crate::server::schedules::get_schedule_handler,
+        crate::server::schedules::update_schedule_handler,
+        crate::server::schedules::delete_schedule_handler,
+        crate::server::schedules::run_schedule_handler,
+        crate::server::alerts::create_alert_handler,
+        crate::server::alerts::list_alerts_handler,
+        crate::server::alerts::get_alert_handler,
+        crate::server::alerts::update_alert_handler,
+        crate::server::alerts::delete_alert_handler,
+        crate::server::jobs::list_jobs_handler,
+        crate::server::jobs::get_job_handler,
+        crate::server::jobs::cancel_job_handler,
+        crate::server::connections::create_connection_handler,
+        crate::server::connections::list_connections_handler,
+        crate::server::connections::delete_connection_handler,
+        crate::server::connections::list_connection_tables_handler,
+        crate::server::connections::query_connection_handler,
         crate::server::analytics::execute_query_handler,
+        crate::server::analytics::query_columns_handler,
+        crate::server::analytics::estimate_query_handler,
+        crate::server::analytics::natural_language_query_handler,
         crate::server::analytics::download_query_csv_handler,
+        crate::server::analytics::chart_query_handler,
+        crate::server::analytics::diff_query_handler,
         crate::server::analytics::get_analytics_summary_handler,
         crate::server::analytics::get_query_history_handler,
         crate::server::analytics::download_query_history_csv_handler
@@ -56,17 +169,118 @@ use utoipa::OpenApi;
             crate::db::models::Payee,
             crate::db::models::CascadingSplit,
             crate::db::models::Dataset,
+            crate::db::models::DataConnection,
+            crate::db::models::PaginatedDataConnections,
             crate::db::models::PaginationMeta,
             crate::db::models::PaginationParams,
+            crate::db::models::DatasetListParams,
+            crate::db::models::PipelineListParams,
+            crate::db::models::DatasetSortField,
+            crate::db::models::DatasetStatus,
+            crate::normalization::SourceColumn,
+            crate::normalization::SourceEncoding,
+            crate::normalization::InferenceOptions,
+            crate::normalization::InferredSchema,
+            crate::normalization::inference::InferredColumn,
+            crate::normalization::ValidationReport,
+            crate::normalization::validation::RowIssues,
+            crate::normalization::validation::RowSample,
+            crate::normalization::validation::ColumnIssue,
+            crate::normalization::validation::ExpectedType,
+            crate::db::models::SortDirection,
+            crate::db::models::PaginatedWorkspaces,
+            crate::db::models::PaginatedWorkspaceMembers,
+            crate::db::models::PaginatedArtists,
+            crate::db::models::PaginatedAlbums,
+            crate::db::models::PaginatedTracks,
+            crate::db::models::PaginatedPayees,
+            crate::db::models::PaginatedSplits,
+            crate::db::models::PaginatedDatasets,
+            crate::db::models::Folder,
+            crate::db::models::PaginatedFolders,
+            crate::db::models::Pipeline,
+            crate::db::models::PipelineRun,
+            crate::db::models::PipelineTrigger,
+            crate::db::models::PaginatedPipelines,
+            crate::db::models::Segment,
+            crate::db::models::PaginatedSegments,
+            crate::db::models::QualityRule,
+            crate::db::models::PaginatedQualityRules,
+            crate::db::models::QualityRun,
+            crate::db::models::PaginatedQualityRuns,
+            crate::db::models::DatasetAction,
+            crate::db::models::DatasetActivity,
+            crate::db::models::PaginatedDatasetActivity,
+            crate::db::models::Dashboard,
+            crate::db::models::DashboardTile,
+            crate::db::models::Visualization,
+            crate::db::models::PaginatedDashboards,
+            crate::db::models::PipelineSchedule,
+            crate::db::models::NotifyChannel,
+            crate::db::models::NotifyWhen,
+            crate::db::models::ThresholdOp,
+            crate::db::models::PaginatedPipelineSchedules,
+            crate::db::models::ScheduleAlert,
+            crate::db::models::AlertAggregate,
+            crate::db::models::AlertState,
+            crate::db::models::PaginatedScheduleAlerts,
+            crate::db::models::PaginatedPipelineRuns,
+            crate::db::models::PipelineComment,
+            crate::db::models::PaginatedPipelineComments,
+            crate::server::dtos::pipelines::RunLineage,
+            crate::engine::ColumnLineage,
+            crate::db::models::BackgroundJob,
+            crate::db::models::JobStatus,
+            crate::db::models::PaginatedBackgroundJobs,
+            crate::db::models::PaginatedQueryHistory,
             crate::server::dtos::auth::RegisterRequest,
             crate::server::dtos::auth::LoginRequest,
             crate::server::dtos::auth::AuthResponse,
             crate::server::dtos::auth::UserInfo,
+            crate::server::dtos::auth::SessionResponse,
+            crate::server::dtos::auth::ProfileStatsResponse,
+            crate::db::models::DailyQueryCount,
+            crate::db::models::TableQueryCount,
+            crate::db::models::UserSettings,
+            crate::db::models::DateFormat,
+            crate::server::dtos::admin::UnlockAccountRequest,
+            crate::server::dtos::admin::UnlockAccountResponse,
+            crate::server::dtos::admin::AdminOverviewResponse,
+            crate::db::models::PlatformTotals,
+            crate::db::models::UserStorageUsage,
+            crate::db::models::ErrorTypeCount,
+            crate::db::models::DatasetSize,
+            crate::db::models::UserSuspension,
+            crate::db::models::PaginatedUserSuspensions,
+            crate::db::models::SlowQuery,
+            crate::db::models::PaginatedSlowQueries,
+            crate::server::dtos::admin::SuspendUserRequest,
+            crate::server::dtos::admin::SeedDemoRequest,
+            crate::server::dtos::admin::DemoWorkspaceResponse,
             crate::server::dtos::workspaces::CreateWorkspaceRequest,
             crate::server::dtos::workspaces::UpdateWorkspaceRequest,
             crate::server::dtos::workspaces::AddWorkspaceMemberRequest,
+            crate::server::dtos::workspaces::ExportWorkspaceRequest,
+            crate::server::dtos::workspaces::WorkspaceArchiveManifest,
+            crate::server::dtos::workspaces::ArchivedDataset,
+            crate::server::dtos::workspaces::ArchivedPipeline,
+            crate::server::dtos::workspaces::ImportConflictPolicy,
+            crate::server::dtos::workspaces::ImportOutcome,
+            crate::server::dtos::workspaces::ImportItemResult,
+            crate::server::dtos::workspaces::ImportWorkspaceResponse,
+            crate::server::dtos::common::ErrorResponse,
+            crate::utils::error::FieldError,
+            crate::utils::error::QueryStepFailure,
+            crate::utils::error::FilterTypeMismatch,
             crate::server::dtos::common::DeleteResponse,
+            crate::server::dtos::common::FavoriteResponse,
+            crate::server::dtos::common::BulkDeleteRequest,
+            crate::server::dtos::common::BulkDeleteItemResult,
+            crate::server::dtos::common::BulkDeleteResponse,
             crate::server::dtos::common::DatasetResponse,
+            crate::server::dtos::events::EventStreamParams,
+            crate::utils::events::ActivityEvent,
+            crate::utils::events::ActivityEventKind,
             crate::server::dtos::catalog::CreateArtistRequest,
             crate::server::dtos::catalog::UpdateArtistRequest,
             crate::server::dtos::catalog::CreateAlbumRequest,
@@ -78,8 +292,61 @@ use utoipa::OpenApi;
             crate::server::dtos::splits::CreateSplitRequest,
             crate::server::dtos::splits::UpdateSplitRequest,
             crate::server::dtos::uploads::PresignedUrlRequest,
+            crate::server::dtos::uploads::CreateUploadSessionRequest,
+            crate::server::dtos::uploads::UploadLimitsResponse,
+            crate::db::models::UploadSession,
             crate::server::dtos::uploads::PresignedUrlResponse,
+            crate::server::dtos::uploads::DatasetDownloadParams,
+            crate::server::dtos::uploads::PreviewUploadRequest,
+            crate::server::dtos::uploads::UploadPreviewResponse,
+            crate::server::dtos::uploads::CompactDatasetsRequest,
+            crate::server::dtos::uploads::CompactDatasetsResponse,
+            crate::server::dtos::uploads::CompactionResult,
             crate::server::dtos::uploads::ConfirmUploadRequest,
+            crate::server::dtos::uploads::DatasetColumn,
+            crate::server::dtos::uploads::UpdateColumnRestrictionsRequest,
+            crate::server::dtos::uploads::ColumnMask,
+            crate::engine::udfs::MaskMode,
+            crate::utils::pii::PiiFinding,
+            crate::server::dtos::uploads::QuerySuggestion,
+            crate::server::dtos::uploads::ColumnValuesParams,
+            crate::server::dtos::uploads::ColumnValuesResponse,
+            crate::utils::pii::PiiKind,
+            crate::server::dtos::public::PublicQueryParams,
+            crate::server::dtos::folders::CreateFolderRequest,
+            crate::server::dtos::folders::RenameFolderRequest,
+            crate::server::dtos::folders::MoveFolderRequest,
+            crate::server::dtos::folders::MoveDatasetsRequest,
+            crate::server::dtos::folders::MoveDatasetsResponse,
+            crate::server::dtos::pipelines::PipelineRequest,
+            crate::server::dtos::pipelines::RunPipelineRequest,
+            crate::server::dtos::pipelines::PortablePipeline,
+            crate::server::dtos::pipelines::PortableTable,
+            crate::server::dtos::pipelines::RunPipelineResponse,
+            crate::server::dtos::segments::SegmentRequest,
+            crate::server::dtos::quality::QualityRuleRequest,
+            crate::server::dtos::quality::Expectation,
+            crate::server::dtos::quality::RuleOutcome,
+            crate::server::dtos::search::SearchResultKind,
+            crate::server::dtos::search::SearchResult,
+            crate::server::dtos::search::SearchResponse,
+            crate::server::dtos::comments::CreateCommentRequest,
+            crate::server::dtos::comments::UpdateCommentRequest,
+            crate::server::dtos::dashboards::DashboardRequest,
+            crate::server::dtos::dashboards::DashboardTileRequest,
+            crate::server::dtos::dashboards::DashboardResponse,
+            crate::server::dtos::dashboards::DashboardDataResponse,
+            crate::server::dtos::dashboards::DashboardTileData,
+            crate::server::dtos::schedules::ScheduleRequest,
+            crate::server::dtos::schedules::ScheduleThreshold,
+            crate::server::dtos::alerts::AlertRequest,
+            crate::server::dtos::alerts::AlertEvaluation,
+            crate::server::dtos::connections::CreateConnectionRequest,
+            crate::server::dtos::connections::ConnectionQueryRequest,
+            crate::server::dtos::connections::ConnectionAggregate,
+            crate::server::dtos::connections::AggregateFunction,
+            crate::server::dtos::connections::ConnectionOrder,
+            crate::engine::external::ExternalTable,
             crate::server::dtos::analytics::DateRangeFilter,
             crate::server::dtos::analytics::FilterOperator,
             crate::server::dtos::analytics::QueryFilter,
@@ -87,17 +354,74 @@ use utoipa::OpenApi;
             crate::server::dtos::analytics::AnalyticsQueryRequest,
             crate::server::dtos::analytics::AnalyticsSummaryRequest,
             crate::server::dtos::analytics::AnalyticsQueryResponse,
-            crate::server::dtos::analytics::AnalyticsSummaryResponse
+            crate::server::dtos::analytics::QueryColumnsResponse,
+            crate::server::dtos::analytics::QueryDiffRequest,
+            crate::server::dtos::analytics::QueryDiffResponse,
+            crate::server::dtos::analytics::QueryDiffSummary,
+            crate::server::dtos::analytics::ChangedRow,
+            crate::server::dtos::analytics::ResultColumn,
+            crate::server::dtos::analytics::QueryEstimateResponse,
+            crate::engine::QueryMetrics,
+            crate::server::dtos::analytics::TableScanEstimate,
+            crate::server::dtos::analytics::QueryCostTier,
+            crate::server::dtos::analytics::NaturalLanguageQueryRequest,
+            crate::server::dtos::analytics::NaturalLanguageQueryResponse,
+            crate::server::dtos::analytics::ResultLayout,
+            crate::server::dtos::analytics::FindDuplicates,
+            crate::server::dtos::analytics::DuplicateOutput,
+            crate::server::dtos::analytics::BinSpec,
+            crate::server::dtos::analytics::TopNPerGroup,
+            crate::server::dtos::analytics::CumulativeSpec,
+            crate::server::dtos::analytics::CumulativeFunction,
+            crate::server::dtos::analytics::PercentOfTotal,
+            crate::server::dtos::analytics::LookupSpec,
+            crate::server::dtos::analytics::QueryStep,
+            crate::server::dtos::analytics::AnalyticsSummaryResponse,
+            crate::server::dtos::analytics::ExportFormat,
+            crate::server::dtos::analytics::ExportParams,
+            crate::server::dtos::analytics::ChartKind,
+            crate::server::dtos::analytics::ChartFormat,
+            crate::server::dtos::analytics::ChartParams
         )
     ),
     tags(
         (name = "auth", description = "Authentication and User Profile endpoints"),
+        (name = "admin", description = "Platform operator endpoints (ADMIN_EMAILS only)"),
+        (name = "events", description = "Server-sent activity notification stream"),
         (name = "workspaces", description = "Workspace management and membership endpoints"),
         (name = "catalog", description = "Global user master catalog endpoints (Artists, Albums, Tracks)"),
         (name = "payees", description = "Payee Contact Book endpoints"),
         (name = "splits", description = "Cascading Splits endpoints"),
         (name = "datasets", description = "Dataset Ingestion & Presigned URL endpoints"),
+        (name = "folders", description = "Folders organizing a workspace's datasets"),
+        (name = "pipelines", description = "Saved structured queries applied to matching datasets"),
+        (name = "segments", description = "Named filter groups structured queries reuse through segment_ids"),
+        (name = "quality", description = "Column-level data quality rules and their runs over datasets"),
+        (name = "search", description = "Search across everything the caller can access"),
+        (name = "dashboards", description = "Pipelines shown together as tiles"),
+        (name = "schedules", description = "Pipelines run on an interval, with summaries and alerts sent to Slack, Teams or email"),
+        (name = "jobs", description = "Status of background jobs queued by other endpoints"),
+        (name = "connections", description = "External database connections queryable by the analytical engine"),
+        (name = "public", description = "Anonymous read-only access to publicly shared datasets"),
         (name = "analytics", description = "Analytical Engine & Royalty Analytics endpoints")
     )
 )]
 pub struct ApiDoc;
+
+/// Registers the JWT bearer scheme referenced by the global `security` requirement.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
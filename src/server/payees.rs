@@ -2,9 +2,10 @@ use crate::db::models::{PaginatedResponse, PaginationParams, Payee};
 use crate::db::queries::{create_payee, delete_payee, get_payees, update_payee};
 use crate::server::dtos::DeleteResponse;
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, State};
 use axum::Json;
 use crate::server::dtos::payees::*;
 use uuid::Uuid;
@@ -21,7 +22,7 @@ use uuid::Uuid;
 pub async fn create_payee_handler(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreatePayeeRequest>,
+    ValidatedJson(payload): ValidatedJson<CreatePayeeRequest>,
 ) -> Result<Json<Payee>, DoubledeckerError> {
     if payload.name.trim().is_empty() {
         return Err(DoubledeckerError::BadRequest(
@@ -52,7 +53,7 @@ pub async fn create_payee_handler(
 )]
 pub async fn list_payees_handler(
     auth_user: AuthenticatedUser,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<Payee>>, DoubledeckerError> {
     let limit = pagination.effective_limit();
@@ -76,7 +77,7 @@ pub async fn update_payee_handler(
     _auth_user: AuthenticatedUser,
     Path(payee_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdatePayeeRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdatePayeeRequest>,
 ) -> Result<Json<Payee>, DoubledeckerError> {
     let updated = update_payee(
         &state.db_pool,
@@ -0,0 +1,644 @@
+use crate::db::models::{
+    BackgroundJob, DatasetStatus, PaginationParams, Pipeline, PipelineListParams, PipelineRun, PipelineTrigger,
+    WorkspaceRole,
+};
+use crate::db::queries::{
+    create_pipeline, delete_pipeline, enqueue_job, get_datasets_by_ids, get_pipeline, list_patterned_pipelines,
+    get_pipeline_run_lineage, list_pipeline_runs, list_pipelines, list_ready_dataset_filenames, list_workspace_column_restrictions,
+    list_workspace_pipelines, record_pipeline_run, set_pipeline_favorite, update_pipeline,
+};
+use crate::engine::{EngineProvider, QueryScope, referenced_tables};
+use crate::server::analytics::record_if_slow;
+use crate::server::dtos::analytics::{AnalyticsQueryRequest, AnalyticsQueryResponse, ResultLayout};
+use crate::server::dtos::pipelines::*;
+use crate::server::dtos::{DeleteResponse, FavoriteResponse};
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::extractors::{
+    apply_column_restrictions, expand_segments, verify_workspace_access, with_lookup_datasets,
+};
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::error::{DoubledeckerError, FieldError};
+use crate::utils::helpers::{filename_slug, render_query_results};
+use crate::workers::JobHandler;
+use axum::extract::{Path, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Background job kind for manual pipeline runs.
+pub const PIPELINE_RUN_JOB: &str = "pipeline.run";
+/// Attempts of a queued pipeline run; query failures are recorded on the runs and not retried.
+const PIPELINE_RUN_JOB_ATTEMPTS: i32 = 3;
+
+/// Run a pipeline's query on `dataset_ids`, or on every ready dataset of the workspace, outside any
+/// request. Restricted columns are masked whoever asked for the run, since its results are shared.
+pub async fn execute_pipeline(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    pipeline: &Pipeline,
+    dataset_ids: Option<Vec<Uuid>>,
+) -> Result<AnalyticsQueryResponse, DoubledeckerError> {
+    let (scope, sql) = pipeline_query(pool, pipeline, dataset_ids).await?;
+    let batches = engine.execute_scoped_analytics(pipeline.workspace_id, &scope, &sql).await?;
+    render_query_results(batches, None, ResultLayout::Rows).await
+}
+
+/// Scope and SQL of a pipeline's query on `dataset_ids`.
+async fn pipeline_query(
+    pool: &PgPool,
+    pipeline: &Pipeline,
+    dataset_ids: Option<Vec<Uuid>>,
+) -> Result<(QueryScope, String), DoubledeckerError> {
+    let request = AnalyticsQueryRequest::from(pipeline.query.0.clone());
+    let request = expand_segments(pool, pipeline.workspace_id, request).await?;
+    let restrictions = list_workspace_column_restrictions(pool, pipeline.workspace_id).await?;
+    let scope = QueryScope {
+        dataset_ids,
+        ..QueryScope::default()
+    };
+    let scope = apply_column_restrictions(scope, &restrictions);
+    let scope = with_lookup_datasets(pool, pipeline.workspace_id, scope, request.lookup_dataset_ids()).await?;
+    Ok((scope, request.to_safe_sql()?))
+}
+
+/// Run a pipeline on one dataset and record the run, with its lineage when it succeeds.
+pub async fn run_pipeline(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    pipeline: &Pipeline,
+    dataset_id: Uuid,
+    trigger: PipelineTrigger,
+) -> Result<PipelineRun, DoubledeckerError> {
+    let mut lineage = None;
+    let outcome = async {
+        let (scope, sql) = pipeline_query(pool, pipeline, Some(vec![dataset_id])).await?;
+        let query = engine.execute_with_metrics(pipeline.workspace_id, &scope, &sql).await?;
+        let operations = &pipeline.query.0;
+        record_if_slow(pool, engine, pipeline.workspace_id, None, Some(pipeline.id), &sql, operations, &query).await;
+        let metrics = query.metrics;
+        let response = render_query_results(query.batches, None, ResultLayout::Rows).await?;
+        let result = serde_json::to_value(&response)
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize pipeline result: {}", e)))?;
+
+        // Best effort: a run whose plan cannot be traced is still recorded, without lineage
+        if let Ok(columns) = engine.plan_lineage(pipeline.workspace_id, &scope, &sql).await {
+            let run_lineage = RunLineage {
+                datasets: std::iter::once(dataset_id).chain(scope.lookup_dataset_ids.iter().copied()).collect(),
+                tables: referenced_tables(&sql),
+                query: pipeline.query.0.clone(),
+                sql,
+                columns,
+            };
+            lineage = serde_json::to_value(run_lineage).ok();
+        }
+        Ok::<_, DoubledeckerError>((result, metrics))
+    }
+    .await;
+
+    record_pipeline_run(pool, pipeline.id, dataset_id, trigger, outcome.map_err(|e| e.to_string()), lineage).await
+}
+
+/// Run every pipeline whose pattern matches a newly processed dataset. Failures are recorded on
+/// the runs and do not affect the dataset.
+pub async fn apply_matching_pipelines(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    filename: &str,
+) -> Result<Vec<PipelineRun>, DoubledeckerError> {
+    let mut runs = Vec::new();
+    for pipeline in list_patterned_pipelines(pool, workspace_id).await? {
+        let matches = pipeline.dataset_pattern.as_deref().is_some_and(|p| pattern_matches(p, filename));
+        if matches {
+            runs.push(run_pipeline(pool, engine, &pipeline, dataset_id, PipelineTrigger::Upload).await?);
+        }
+    }
+    Ok(runs)
+}
+
+/// Runs queued pipeline runs. The job's result is a `RunPipelineResponse` with the runs.
+pub struct PipelineRunJobHandler {
+    pub pool: PgPool,
+    pub engine: Arc<EngineProvider>,
+}
+
+#[async_trait::async_trait]
+impl JobHandler for PipelineRunJobHandler {
+    async fn run(&self, job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+        let payload: PipelineRunJob = serde_json::from_value(job.payload.0.clone())
+            .map_err(|e| DoubledeckerError::BadRequest(format!("Invalid pipeline job payload: {}", e)))?;
+        let workspace_id = job
+            .workspace_id
+            .ok_or_else(|| DoubledeckerError::BadRequest("Pipeline job has no workspace".to_string()))?;
+        let pipeline = get_pipeline(&self.pool, workspace_id, payload.pipeline_id).await?;
+
+        let mut runs = Vec::with_capacity(payload.dataset_ids.len());
+        for dataset_id in payload.dataset_ids {
+            runs.push(run_pipeline(&self.pool, &self.engine, &pipeline, dataset_id, PipelineTrigger::Manual).await?);
+        }
+        serde_json::to_value(RunPipelineResponse { runs, job: None })
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize pipeline runs: {}", e)))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/pipelines",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = PipelineRequest,
+    responses(
+        (status = 200, description = "Pipeline created", body = Pipeline),
+        (status = 400, description = "A pipeline with this name already exists")
+    ),
+    tag = "pipelines"
+)]
+pub async fn create_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PipelineRequest>,
+) -> Result<Json<Pipeline>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let pipeline = create_pipeline(
+        &state.db_pool,
+        workspace_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        payload.dataset_pattern.as_deref().map(str::trim),
+        &payload.query,
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(pipeline))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PipelineListParams,
+    ),
+    responses(
+        (status = 200, description = "Pipelines of the workspace", body = PaginatedPipelines),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
+    ),
+    tag = "pipelines"
+)]
+pub async fn list_pipelines_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(params): ValidatedQuery<PipelineListParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = params.pagination().effective_limit();
+    let favorites_only = params.favorites.unwrap_or(false);
+    let pipelines =
+        list_pipelines(&state.db_pool, workspace_id, auth_user.user_id, favorites_only, params.cursor, limit).await?;
+    etagged_json(&if_none_match, &pipelines)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "Pipeline", body = Pipeline),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn get_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Pipeline>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let pipeline = get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    Ok(Json(pipeline))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    request_body = PipelineRequest,
+    responses(
+        (status = 200, description = "Pipeline replaced", body = Pipeline),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn update_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PipelineRequest>,
+) -> Result<Json<Pipeline>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let pipeline = update_pipeline(
+        &state.db_pool,
+        workspace_id,
+        pipeline_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        payload.dataset_pattern.as_deref().map(str::trim),
+        &payload.query,
+    )
+    .await?;
+    Ok(Json(pipeline))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "Pipeline and its runs deleted", body = DeleteResponse)
+    ),
+    tag = "pipelines"
+)]
+pub async fn delete_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Pipeline not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Pipeline deleted successfully".to_string(),
+    }))
+}
+
+/// Add a pipeline to the caller's favorites, listed with `?favorites=true`. Favorites are per user,
+/// so any member can favorite a pipeline of the workspace.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/favorite",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "Pipeline favorited", body = FavoriteResponse),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn favorite_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    set_favorite(&state, auth_user.user_id, workspace_id, pipeline_id, true).await
+}
+
+/// Remove a pipeline from the caller's favorites.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/favorite",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "Pipeline unfavorited", body = FavoriteResponse),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn unfavorite_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    set_favorite(&state, auth_user.user_id, workspace_id, pipeline_id, false).await
+}
+
+async fn set_favorite(
+    state: &AppState,
+    user_id: Uuid,
+    workspace_id: Uuid,
+    pipeline_id: Uuid,
+    favorite: bool,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    verify_workspace_access(state, workspace_id, user_id, WorkspaceRole::Viewer).await?;
+
+    let pipeline = get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    set_pipeline_favorite(&state.db_pool, user_id, pipeline.id, favorite).await?;
+    Ok(Json(FavoriteResponse { id: pipeline.id, favorite }))
+}
+
+/// Copy a pipeline as `<name> (copy)` (or `(copy 2)`, ... when taken), to change it without
+/// touching the original. The copy keeps the dataset pattern, so it also runs on new uploads.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/duplicate",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "The new pipeline", body = Pipeline),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn duplicate_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Pipeline>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let original = get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let taken = list_workspace_pipelines(&state.db_pool, workspace_id)
+        .await?
+        .into_iter()
+        .map(|p| p.name.to_lowercase())
+        .collect();
+    let pipeline = create_pipeline(
+        &state.db_pool,
+        workspace_id,
+        &copy_name(&original.name, &taken),
+        original.description.as_deref(),
+        original.dataset_pattern.as_deref(),
+        &original.query.0,
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(pipeline))
+}
+
+/// Download a pipeline as a portable JSON file (see `PortablePipeline`), for importing into another
+/// workspace.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/export",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    responses(
+        (status = 200, description = "The pipeline as a `.pipeline.json` attachment", body = PortablePipeline),
+        (status = 404, description = "Pipeline not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn export_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let pipeline = get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let lookup_ids: Vec<Uuid> = pipeline.query.lookups.iter().flatten().map(|l| l.dataset_id).collect();
+    let tables = if lookup_ids.is_empty() {
+        Vec::new()
+    } else {
+        get_datasets_by_ids(&state.db_pool, workspace_id, &lookup_ids)
+            .await?
+            .into_iter()
+            .map(|d| PortableTable { dataset_id: d.id, filename: d.filename })
+            .collect()
+    };
+
+    let filename = format!("{}.pipeline.json", filename_slug(&pipeline.name, "pipeline"));
+    let portable = PortablePipeline {
+        format: PIPELINE_EXPORT_FORMAT.to_string(),
+        version: PIPELINE_EXPORT_VERSION,
+        exported_at: Some(chrono::Utc::now()),
+        name: pipeline.name,
+        description: pipeline.description,
+        dataset_pattern: pipeline.dataset_pattern,
+        query: pipeline.query.0,
+        tables,
+    };
+    Ok((
+        [(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))],
+        Json(portable),
+    )
+        .into_response())
+}
+
+/// Create a pipeline from a portable JSON file made by the export endpoint. Each of its `tables`
+/// must match the filename of a ready dataset of this workspace (the latest upload wins when
+/// several do), and the query's lookups are pointed at those datasets.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/pipelines/import",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = PortablePipeline,
+    responses(
+        (status = 200, description = "Pipeline created", body = Pipeline),
+        (status = 400, description = "Unsupported file, a table with no matching dataset, or a pipeline with this name already exists")
+    ),
+    tag = "pipelines"
+)]
+pub async fn import_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PortablePipeline>,
+) -> Result<Json<Pipeline>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let ready = list_ready_dataset_filenames(&state.db_pool, workspace_id).await?;
+    let mut query = payload.query;
+    let mut errors = Vec::new();
+    for (i, table) in payload.tables.iter().enumerate() {
+        let Some((dataset_id, _)) = ready.iter().rev().find(|(_, f)| f.eq_ignore_ascii_case(&table.filename)) else {
+            errors.push(FieldError::new(
+                &format!("tables[{}].filename", i),
+                "not_found",
+                format!("no ready dataset named {} in this workspace", table.filename),
+            ));
+            continue;
+        };
+        for lookup in query.lookups.iter_mut().flatten().filter(|l| l.dataset_id == table.dataset_id) {
+            lookup.dataset_id = *dataset_id;
+        }
+    }
+    if !errors.is_empty() {
+        return Err(DoubledeckerError::Validation(errors));
+    }
+
+    let pipeline = create_pipeline(
+        &state.db_pool,
+        workspace_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        payload.dataset_pattern.as_deref().map(str::trim),
+        &query,
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(pipeline))
+}
+
+/// Run a pipeline now, on the given datasets or on every ready dataset matching its pattern. With
+/// `background`, the runs are queued as a job (see the jobs endpoints) and the job is returned.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/run",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID")
+    ),
+    request_body = RunPipelineRequest,
+    responses(
+        (status = 200, description = "One run per dataset, failed runs included, or the queued job", body = RunPipelineResponse),
+        (status = 400, description = "A dataset is not ready, or no datasets were given and the pipeline has no pattern"),
+        (status = 404, description = "Pipeline or dataset not found")
+    ),
+    tag = "pipelines"
+)]
+pub async fn run_pipeline_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<RunPipelineRequest>,
+) -> Result<Json<RunPipelineResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Manager).await?;
+    let pipeline = get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+
+    let dataset_ids = match payload.dataset_ids {
+        Some(mut ids) => {
+            ids.sort();
+            ids.dedup();
+            let datasets = get_datasets_by_ids(&state.db_pool, workspace_id, &ids).await?;
+            for id in &ids {
+                match datasets.iter().find(|d| d.id == *id) {
+                    None => return Err(DoubledeckerError::NotFound(format!("Dataset {} not found", id))),
+                    Some(d) if d.status != DatasetStatus::Ready.as_str() => {
+                        return Err(DoubledeckerError::BadRequest(format!("Dataset {} is not ready", id)));
+                    }
+                    Some(_) => {}
+                }
+            }
+            ids
+        }
+        None => {
+            let pattern = pipeline.dataset_pattern.as_deref().ok_or_else(|| {
+                DoubledeckerError::BadRequest("Pipeline has no dataset pattern; pass dataset_ids".to_string())
+            })?;
+            list_ready_dataset_filenames(&state.db_pool, workspace_id)
+                .await?
+                .into_iter()
+                .filter(|(_, filename)| pattern_matches(pattern, filename))
+                .map(|(id, _)| id)
+                .collect()
+        }
+    };
+
+    if payload.background {
+        let job_payload = serde_json::to_value(PipelineRunJob { pipeline_id, dataset_ids })
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize pipeline job: {}", e)))?;
+        let job = enqueue_job(
+            &state.db_pool,
+            Some(workspace_id),
+            PIPELINE_RUN_JOB,
+            job_payload,
+            PIPELINE_RUN_JOB_ATTEMPTS,
+            Some(auth_user.user_id),
+        )
+        .await?;
+        return Ok(Json(RunPipelineResponse { runs: Vec::new(), job: Some(job) }));
+    }
+
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+    let mut runs = Vec::with_capacity(dataset_ids.len());
+    for dataset_id in dataset_ids {
+        runs.push(run_pipeline(&state.db_pool, &state.engine, &pipeline, dataset_id, PipelineTrigger::Manual).await?);
+    }
+    Ok(Json(RunPipelineResponse { runs, job: None }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/runs",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Runs of the pipeline, newest first", body = PaginatedPipelineRuns),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
+    ),
+    tag = "pipelines"
+)]
+pub async fn list_pipeline_runs_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id)): Path<(Uuid, Uuid)>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let limit = pagination.effective_limit();
+    let runs = list_pipeline_runs(&state.db_pool, pipeline_id, pagination.cursor, limit).await?;
+    etagged_json(&if_none_match, &runs)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/pipelines/{pipeline_id}/runs/{run_id}/lineage",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("pipeline_id" = Uuid, Path, description = "Pipeline ID"),
+        ("run_id" = Uuid, Path, description = "Pipeline run ID")
+    ),
+    responses(
+        (status = 200, description = "Datasets, query and column provenance of the run's result", body = RunLineage),
+        (status = 404, description = "No such run, or the run failed or predates lineage")
+    ),
+    tag = "pipelines"
+)]
+pub async fn get_pipeline_run_lineage_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, pipeline_id, run_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<RunLineage>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    get_pipeline(&state.db_pool, workspace_id, pipeline_id).await?;
+    let lineage = get_pipeline_run_lineage(&state.db_pool, pipeline_id, run_id)
+        .await?
+        .ok_or_else(|| DoubledeckerError::NotFound("This run has no recorded lineage".to_string()))?;
+    let lineage = serde_json::from_value(lineage)
+        .map_err(|e| DoubledeckerError::Internal(format!("Stored lineage is unreadable: {}", e)))?;
+    Ok(Json(lineage))
+}
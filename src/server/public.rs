@@ -0,0 +1,85 @@
+use crate::db::models::DatasetStatus;
+use crate::db::queries::{get_dataset_by_public_token, list_dataset_column_restrictions};
+use crate::server::extractors::{ClientIp, apply_column_restrictions};
+use crate::engine::QueryScope;
+use crate::server::dtos::analytics::{
+    AnalyticsQueryRequest, AnalyticsQueryResponse, DateRangeFilter, ResultLayout,
+    StructuredAnalyticsQuery,
+};
+use crate::server::dtos::public::PublicQueryParams;
+use crate::server::state::AppState;
+use crate::utils::error::DoubledeckerError;
+use crate::utils::helpers::parse_batch_to_json;
+use axum::extract::{Path, Query, State};
+use axum::Json;
+
+const MAX_PUBLIC_ROWS: usize = 1000;
+
+/// Anonymous, read-only query over a single publicly shared dataset.
+/// Only structured queries are accepted (no raw SQL) and calls are rate limited per token and client.
+#[utoipa::path(
+    get,
+    path = "/public/tables/{token}/query",
+    params(
+        ("token" = String, Path, description = "Public share token of the dataset"),
+        PublicQueryParams
+    ),
+    responses(
+        (status = 200, description = "Query result", body = AnalyticsQueryResponse),
+        (status = 404, description = "Unknown or revoked token"),
+        (status = 429, description = "Rate limit exceeded")
+    ),
+    security(()),
+    tag = "public"
+)]
+pub async fn public_table_query_handler(
+    Path(token): Path<String>,
+    Query(params): Query<PublicQueryParams>,
+    ClientIp(client): ClientIp,
+    State(state): State<AppState>,
+) -> Result<Json<AnalyticsQueryResponse>, DoubledeckerError> {
+    // Made-up tokens are turned away before they can take up room in the limiter
+    let dataset = get_dataset_by_public_token(&state.db_pool, &token).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::NotFound("Public table not found".to_string()));
+    }
+    let client = client.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+    state.public_rate_limiter.check(&format!("{}:{}", dataset.id, client)).await?;
+
+    let split = |s: &Option<String>| {
+        s.as_ref().map(|v| {
+            v.split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<_>>()
+        })
+    };
+    let request = AnalyticsQueryRequest {
+        sql: None,
+        structured: Some(StructuredAnalyticsQuery {
+            date_range: Some(DateRangeFilter {
+                from: params.from,
+                to: params.to,
+            }),
+            dimensions: split(&params.dimensions),
+            metrics: split(&params.metrics),
+            filters: None,
+            limit: Some(params.limit.unwrap_or(100).clamp(1, MAX_PUBLIC_ROWS)),
+            ..Default::default()
+        }),
+        dataset_ids: None,
+        layout: ResultLayout::Rows,
+        steps: None,
+        preview: false,
+    };
+    let sql = request.to_safe_sql()?;
+    let restrictions = list_dataset_column_restrictions(&state.db_pool, dataset.id).await?;
+    let scope = apply_column_restrictions(QueryScope::public_dataset(dataset.id), &restrictions);
+
+    let batches = state
+        .engine
+        .execute_scoped_analytics(dataset.workspace_id, &scope, &sql)
+        .await?;
+
+    Ok(Json(parse_batch_to_json(batches, None).await?))
+}
@@ -0,0 +1,346 @@
+use crate::db::models::{
+    DatasetStatus, PaginatedResponse, PaginationParams, PipelineTrigger, QualityRule, QualityRun, WorkspaceRole,
+};
+use crate::db::queries::{
+    create_quality_rule, delete_quality_rule, get_dataset_by_id, get_quality_rule, get_quality_run,
+    list_quality_rules, list_quality_runs, list_workspace_column_restrictions, list_workspace_quality_rules,
+    record_quality_run, update_quality_rule,
+};
+use crate::engine::{EngineProvider, QueryScope};
+use crate::server::dtos::analytics::ResultLayout;
+use crate::server::dtos::quality::*;
+use crate::server::dtos::DeleteResponse;
+use crate::server::extractors::{apply_column_restrictions, verify_workspace_access};
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::error::DoubledeckerError;
+use crate::utils::helpers::render_query_results;
+use axum::extract::{Path, State};
+use axum::Json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Check a dataset against `rules` and record the run. Restricted columns are masked whoever asked
+/// for the run, as for pipelines, since its samples are shared; a rule on a hidden column fails
+/// with an error.
+pub async fn run_quality_rules(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    rules: &[QualityRule],
+    trigger: PipelineTrigger,
+    created_by: Option<Uuid>,
+) -> Result<QualityRun, DoubledeckerError> {
+    let restrictions = list_workspace_column_restrictions(pool, workspace_id).await?;
+    let scope = QueryScope {
+        dataset_ids: Some(vec![dataset_id]),
+        ..QueryScope::default()
+    };
+    let scope = apply_column_restrictions(scope, &restrictions);
+
+    let mut results = Vec::with_capacity(rules.len());
+    for rule in rules {
+        results.push(check_rule(engine, workspace_id, &scope, rule).await);
+    }
+    record_quality_run(pool, dataset_id, trigger, &results, created_by).await
+}
+
+async fn check_rule(engine: &EngineProvider, workspace_id: Uuid, scope: &QueryScope, rule: &QualityRule) -> RuleOutcome {
+    let expectation = &rule.expectation.0;
+    let mut outcome = RuleOutcome {
+        rule_id: rule.id,
+        name: rule.name.clone(),
+        column_name: rule.column_name.clone(),
+        expectation: expectation.clone(),
+        passed: false,
+        failing_rows: 0,
+        samples: Vec::new(),
+        error: None,
+    };
+    let checked = async {
+        let sql = expectation.failing_rows_sql(&rule.column_name);
+        let batches = engine.execute_scoped_analytics(workspace_id, scope, &sql).await?;
+        render_query_results(batches, None, ResultLayout::Rows).await
+    }
+    .await;
+
+    match checked {
+        Ok(response) => {
+            // The last column is the `failing_rows` count, the same on every row
+            let sample_columns = &response.columns[..response.columns.len().saturating_sub(1)];
+            for row in &response.rows {
+                let Some(cells) = row.as_array() else { continue };
+                outcome.failing_rows = cells.last().and_then(|c| c.as_i64()).unwrap_or_default();
+                let sample = sample_columns.iter().cloned().zip(cells.iter().cloned()).collect();
+                outcome.samples.push(serde_json::Value::Object(sample));
+            }
+            outcome.passed = outcome.failing_rows == 0;
+        }
+        Err(e) => outcome.error = Some(e.to_string()),
+    }
+    outcome
+}
+
+/// Check a newly processed dataset against the rules that run on upload, if there are any.
+/// Failures are recorded on the run and do not affect the dataset.
+pub async fn apply_upload_quality_rules(
+    pool: &PgPool,
+    engine: &EngineProvider,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+) -> Result<Option<QualityRun>, DoubledeckerError> {
+    let rules = list_workspace_quality_rules(pool, workspace_id, true).await?;
+    if rules.is_empty() {
+        return Ok(None);
+    }
+    let run = run_quality_rules(pool, engine, workspace_id, dataset_id, &rules, PipelineTrigger::Upload, None).await?;
+    Ok(Some(run))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/quality-rules",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = QualityRuleRequest,
+    responses(
+        (status = 200, description = "Quality rule created", body = QualityRule),
+        (status = 400, description = "A quality rule with this name already exists")
+    ),
+    tag = "quality"
+)]
+pub async fn create_quality_rule_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<QualityRuleRequest>,
+) -> Result<Json<QualityRule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let rule = create_quality_rule(
+        &state.db_pool,
+        workspace_id,
+        payload.name.trim(),
+        &payload.column_name,
+        &payload.expectation,
+        payload.run_on_upload.unwrap_or(true),
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(rule))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/quality-rules",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Quality rules of the workspace", body = PaginatedQualityRules)
+    ),
+    tag = "quality"
+)]
+pub async fn list_quality_rules_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<QualityRule>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let rules = list_quality_rules(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    Ok(Json(rules))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/quality-rules/{rule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("rule_id" = Uuid, Path, description = "Quality rule ID")
+    ),
+    responses(
+        (status = 200, description = "Quality rule", body = QualityRule),
+        (status = 404, description = "Quality rule not found")
+    ),
+    tag = "quality"
+)]
+pub async fn get_quality_rule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<QualityRule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let rule = get_quality_rule(&state.db_pool, workspace_id, rule_id).await?;
+    Ok(Json(rule))
+}
+
+/// Replace a quality rule. Runs already recorded keep the rule as it was when they ran.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/quality-rules/{rule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("rule_id" = Uuid, Path, description = "Quality rule ID")
+    ),
+    request_body = QualityRuleRequest,
+    responses(
+        (status = 200, description = "Quality rule replaced", body = QualityRule),
+        (status = 404, description = "Quality rule not found")
+    ),
+    tag = "quality"
+)]
+pub async fn update_quality_rule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<QualityRuleRequest>,
+) -> Result<Json<QualityRule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let rule = update_quality_rule(
+        &state.db_pool,
+        workspace_id,
+        rule_id,
+        payload.name.trim(),
+        &payload.column_name,
+        &payload.expectation,
+        payload.run_on_upload.unwrap_or(true),
+    )
+    .await?;
+    Ok(Json(rule))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/quality-rules/{rule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("rule_id" = Uuid, Path, description = "Quality rule ID")
+    ),
+    responses(
+        (status = 200, description = "Quality rule deleted", body = DeleteResponse)
+    ),
+    tag = "quality"
+)]
+pub async fn delete_quality_rule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, rule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_quality_rule(&state.db_pool, workspace_id, rule_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Quality rule not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Quality rule deleted successfully".to_string(),
+    }))
+}
+
+/// Check a ready dataset against every quality rule of the workspace now, whether or not the
+/// rules run on upload, and return the report.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/quality-runs",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "The run, with each rule's outcome and failing-row samples", body = QualityRun),
+        (status = 400, description = "The dataset is not ready, or the workspace has no quality rules"),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "quality"
+)]
+pub async fn run_quality_rules_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<QualityRun>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Manager).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(format!("Dataset {} is not ready", dataset_id)));
+    }
+    let rules = list_workspace_quality_rules(&state.db_pool, workspace_id, false).await?;
+    if rules.is_empty() {
+        return Err(DoubledeckerError::BadRequest("The workspace has no quality rules".to_string()));
+    }
+
+    let _permit = state.query_limiter.acquire(auth_user.user_id).await?;
+    let run = run_quality_rules(
+        &state.db_pool,
+        &state.engine,
+        workspace_id,
+        dataset_id,
+        &rules,
+        PipelineTrigger::Manual,
+        Some(auth_user.user_id),
+    )
+    .await?;
+    Ok(Json(run))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/quality-runs",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Quality runs of the dataset, newest first", body = PaginatedQualityRuns),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "quality"
+)]
+pub async fn list_quality_runs_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<QualityRun>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    let limit = pagination.effective_limit();
+    let runs = list_quality_runs(&state.db_pool, dataset.id, pagination.cursor, limit).await?;
+    Ok(Json(runs))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/quality-runs/{run_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID"),
+        ("run_id" = Uuid, Path, description = "Quality run ID")
+    ),
+    responses(
+        (status = 200, description = "Quality run", body = QualityRun),
+        (status = 404, description = "Quality run not found")
+    ),
+    tag = "quality"
+)]
+pub async fn get_quality_run_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id, run_id)): Path<(Uuid, Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<QualityRun>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let run = get_quality_run(&state.db_pool, workspace_id, dataset_id, run_id).await?;
+    Ok(Json(run))
+}
@@ -0,0 +1,428 @@
+use crate::config::ServerConfig;
+use crate::server::state::AppState;
+use crate::{
+    server::{
+        analytics::{
+            chart_query_handler, diff_query_handler, download_query_csv_handler, download_query_history_csv_handler,
+            estimate_query_handler, execute_query_handler, get_analytics_summary_handler, get_query_history_handler,
+            natural_language_query_handler, query_columns_handler,
+        },
+        admin::{
+            admin_errors_handler, admin_largest_datasets_handler, admin_overview_handler,
+            admin_storage_handler, lift_suspension_handler, list_slow_queries_handler, list_suspensions_handler,
+            seed_demo_workspace_handler, suspend_user_handler, unlock_account_handler,
+        },
+        auth::{
+            get_profile, get_profile_settings, get_profile_stats, list_sessions_handler, login,
+            revoke_session_handler, signup, update_profile_settings,
+        },
+        events::stream_events_handler,
+        catalog::{
+            create_album_handler, create_artist_handler, create_track_handler,
+            delete_album_handler, delete_artist_handler, delete_track_handler,
+            list_albums_handler, list_artists_handler, list_tracks_handler,
+            update_album_handler, update_artist_handler, update_track_handler,
+        },
+        compaction::compact_datasets_handler,
+        connections::{
+            create_connection_handler, delete_connection_handler, list_connection_tables_handler,
+            list_connections_handler, query_connection_handler,
+        },
+        folders::{
+            create_folder_handler, delete_folder_handler, list_folders_handler, move_datasets_handler,
+            move_folder_handler, rename_folder_handler,
+        },
+        jobs::{cancel_job_handler, get_job_handler, list_jobs_handler},
+        middleware::handle_overload,
+        openapi::ApiDoc,
+        public::public_table_query_handler,
+        dashboards::{
+            create_dashboard_handler, delete_dashboard_handler, get_dashboard_data_handler, get_dashboard_handler,
+            list_dashboards_handler, update_dashboard_handler,
+        },
+        alerts::{
+            create_alert_handler, delete_alert_handler, get_alert_handler, list_alerts_handler, update_alert_handler,
+        },
+        schedules::{
+            create_schedule_handler, delete_schedule_handler, get_schedule_handler, list_schedules_handler,
+            run_schedule_handler, update_schedule_handler,
+        },
+        pipelines::{
+            create_pipeline_handler, delete_pipeline_handler, duplicate_pipeline_handler, export_pipeline_handler,
+            get_pipeline_handler, get_pipeline_run_lineage_handler, import_pipeline_handler, list_pipeline_runs_handler,
+            list_pipelines_handler, run_pipeline_handler, update_pipeline_handler, favorite_pipeline_handler,
+            unfavorite_pipeline_handler,
+        },
+        comments::{
+            create_comment_handler, delete_comment_handler, list_comments_handler, update_comment_handler,
+        },
+        quality::{
+            create_quality_rule_handler, delete_quality_rule_handler, get_quality_rule_handler, get_quality_run_handler,
+            list_quality_rules_handler, list_quality_runs_handler, run_quality_rules_handler, update_quality_rule_handler,
+        },
+        search::search_handler,
+        segments::{
+            create_segment_handler, delete_segment_handler, get_segment_handler, list_segments_handler,
+            update_segment_handler,
+        },
+        payees::{
+            create_payee_handler, delete_payee_handler, list_payees_handler, update_payee_handler,
+        },
+        splits::{
+            create_split_handler, delete_split_handler, list_splits_handler, update_split_handler,
+        },
+        uploads::{
+            bulk_delete_datasets_handler, confirm_upload_handler, download_dataset_handler, generate_presigned_url_handler, list_dataset_columns_handler,
+            list_datasets_handler, preview_upload_handler, scan_dataset_pii_handler, share_dataset_public_handler, suggest_dataset_queries_handler,
+            unshare_dataset_public_handler, update_dataset_columns_handler, upload_dataset_direct,
+            complete_upload_session_handler, create_upload_session_handler, delete_upload_session_handler,
+            get_upload_limits_handler, get_upload_session_handler, list_column_values_handler, list_dataset_activity_handler,
+            upload_part_handler, favorite_dataset_handler, unfavorite_dataset_handler,
+        },
+        workspace_archive::{export_workspace_handler, import_workspace_handler},
+        workspaces::{
+            add_workspace_member_handler, create_workspace_handler, delete_workspace_handler,
+            list_workspace_members_handler, list_workspaces_handler, update_workspace_handler,
+        },
+    },
+    workers::register_ingestion_workflow,
+};
+use axum::http::{Method, header};
+use axum::{
+    Router,
+    error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post, put},
+};
+use std::sync::Arc;
+use tower::ServiceBuilder;
+use tower_http::cors::{Any, CorsLayer};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+/// `/graphql`, when built with the `graphql` feature.
+#[cfg(feature = "graphql")]
+fn graphql_routes() -> Router<AppState> {
+    Router::new().route("/graphql", post(crate::server::graphql::graphql_handler))
+}
+
+#[cfg(not(feature = "graphql"))]
+fn graphql_routes() -> Router<AppState> {
+    Router::new()
+}
+
+/// The whole HTTP API: every route, the Inngest endpoint and the docs, with CORS, body-size,
+/// concurrency and timeout layers. Background work (outbox dispatch, job workers, Arrow Flight) is
+/// started separately, so the router can be embedded in tests, a serverless adapter or a larger app.
+pub fn build_app(config: &ServerConfig, state: AppState) -> Router {
+    let ServerConfig { body_limits, request_limits } = config;
+
+    // Inngest calls back into /api/inngest to run the ingestion workflow
+    let mut inngest_handler = inngest::handler::Handler::new(&state.inngest_client);
+    inngest_handler.register_fn(
+        register_ingestion_workflow(
+            &state.inngest_client,
+            state.db_pool.clone(),
+            state.uploader.clone(),
+            state.scanner.clone(),
+            state.engine.clone(),
+            state.events.clone(),
+        ),
+    );
+    let inngest_state = Arc::new(inngest_handler);
+
+    let inngest_router = Router::new()
+        .route(
+            "/api/inngest",
+            get(inngest::serve::axum::introspect)
+                .put(inngest::serve::axum::register)
+                .post(inngest::serve::axum::invoke),
+        )
+        .with_state(inngest_state);
+
+    let app = Router::new()
+        // Authentication routes
+        .route("/auth/signup", post(signup))
+        .route("/auth/login", post(login))
+        .route("/auth/sessions", get(list_sessions_handler))
+        .route("/auth/sessions/:session_id", delete(revoke_session_handler))
+        .route("/profile", get(get_profile))
+        .route("/profile/stats", get(get_profile_stats))
+        .route("/profile/settings", get(get_profile_settings).put(update_profile_settings))
+        // Platform administration
+        .route("/admin/accounts/unlock", post(unlock_account_handler))
+        .route("/admin/metrics/overview", get(admin_overview_handler))
+        .route("/admin/metrics/storage", get(admin_storage_handler))
+        .route("/admin/metrics/errors", get(admin_errors_handler))
+        .route("/admin/metrics/largest-datasets", get(admin_largest_datasets_handler))
+        .route(
+            "/admin/users/:user_id/suspension",
+            post(suspend_user_handler).delete(lift_suspension_handler),
+        )
+        .route("/admin/suspensions", get(list_suspensions_handler))
+        .route("/admin/slow-queries", get(list_slow_queries_handler))
+        .route("/admin/demo-workspaces", post(seed_demo_workspace_handler))
+        // Live activity notifications (SSE)
+        .route("/events", get(stream_events_handler))
+        .route("/limits", get(get_upload_limits_handler))
+        .route("/search", get(search_handler))
+        // Workspace routes
+        .route("/api/workspaces", post(create_workspace_handler).get(list_workspaces_handler))
+        .route(
+            "/api/workspaces/:workspace_id",
+            put(update_workspace_handler).delete(delete_workspace_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/members",
+            post(add_workspace_member_handler).get(list_workspace_members_handler),
+        )
+        .route("/api/workspaces/:workspace_id/export", post(export_workspace_handler))
+        .route(
+            "/api/workspaces/import",
+            post(import_workspace_handler).layer(DefaultBodyLimit::max(body_limits.upload_bytes)),
+        )
+        // Global User Master Catalog routes (no workspace required)
+        .route("/api/v1/catalog/artists", post(create_artist_handler).get(list_artists_handler))
+        .route(
+            "/api/v1/catalog/artists/:artist_id",
+            put(update_artist_handler).delete(delete_artist_handler),
+        )
+        .route("/api/v1/catalog/albums", post(create_album_handler).get(list_albums_handler))
+        .route(
+            "/api/v1/catalog/albums/:album_id",
+            put(update_album_handler).delete(delete_album_handler),
+        )
+        .route("/api/v1/catalog/tracks", post(create_track_handler).get(list_tracks_handler))
+        .route(
+            "/api/v1/catalog/tracks/:track_id",
+            put(update_track_handler).delete(delete_track_handler),
+        )
+        // Global Payee Contact Book routes
+        .route("/api/v1/payees", post(create_payee_handler).get(list_payees_handler))
+        .route(
+            "/api/v1/payees/:payee_id",
+            put(update_payee_handler).delete(delete_payee_handler),
+        )
+        // Cascading Splits routes
+        .route("/api/workspaces/:workspace_id/splits", post(create_split_handler).get(list_splits_handler))
+        .route(
+            "/api/workspaces/:workspace_id/splits/:split_id",
+            put(update_split_handler).delete(delete_split_handler),
+        )
+        // Dataset Ingestion routes
+        .route("/api/workspaces/:workspace_id/datasets", get(list_datasets_handler))
+        .route(
+            "/api/workspaces/:workspace_id/datasets/upload",
+            post(upload_dataset_direct).layer(DefaultBodyLimit::max(body_limits.upload_bytes)),
+        )
+        .route("/api/workspaces/:workspace_id/datasets/presigned_url", post(generate_presigned_url_handler))
+        .route("/api/workspaces/:workspace_id/datasets/preview", post(preview_upload_handler))
+        .route("/api/workspaces/:workspace_id/datasets/confirm", post(confirm_upload_handler))
+        .route("/api/workspaces/:workspace_id/datasets/upload_sessions", post(create_upload_session_handler))
+        .route(
+            "/api/workspaces/:workspace_id/datasets/upload_sessions/:session_id",
+            get(get_upload_session_handler).delete(delete_upload_session_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/upload_sessions/:session_id/parts/:part_number",
+            put(upload_part_handler).layer(DefaultBodyLimit::max(body_limits.upload_bytes)),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/upload_sessions/:session_id/complete",
+            post(complete_upload_session_handler),
+        )
+        .route("/api/workspaces/:workspace_id/datasets/bulk_delete", post(bulk_delete_datasets_handler))
+        .route("/api/workspaces/:workspace_id/datasets/compact", post(compact_datasets_handler))
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/public",
+            post(share_dataset_public_handler).delete(unshare_dataset_public_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/download",
+            get(download_dataset_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/columns",
+            get(list_dataset_columns_handler).put(update_dataset_columns_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/columns/:column/values",
+            get(list_column_values_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/activity",
+            get(list_dataset_activity_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/favorite",
+            put(favorite_dataset_handler).delete(unfavorite_dataset_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/pii-scan",
+            get(scan_dataset_pii_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/suggestions",
+            get(suggest_dataset_queries_handler),
+        )
+        .route("/api/workspaces/:workspace_id/datasets/move", post(move_datasets_handler))
+        // Dataset folders
+        .route(
+            "/api/workspaces/:workspace_id/folders",
+            post(create_folder_handler).get(list_folders_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/folders/:folder_id",
+            put(rename_folder_handler).delete(delete_folder_handler),
+        )
+        .route("/api/workspaces/:workspace_id/folders/:folder_id/move", post(move_folder_handler))
+        // Pipelines: saved queries applied to datasets
+        .route(
+            "/api/workspaces/:workspace_id/pipelines",
+            post(create_pipeline_handler).get(list_pipelines_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/pipelines/:pipeline_id",
+            get(get_pipeline_handler).put(update_pipeline_handler).delete(delete_pipeline_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/pipelines/:pipeline_id/favorite",
+            put(favorite_pipeline_handler).delete(unfavorite_pipeline_handler),
+        )
+        .route("/api/workspaces/:workspace_id/pipelines/import", post(import_pipeline_handler))
+        .route("/api/workspaces/:workspace_id/pipelines/:pipeline_id/duplicate", post(duplicate_pipeline_handler))
+        .route("/api/workspaces/:workspace_id/pipelines/:pipeline_id/export", get(export_pipeline_handler))
+        .route("/api/workspaces/:workspace_id/pipelines/:pipeline_id/run", post(run_pipeline_handler))
+        .route("/api/workspaces/:workspace_id/pipelines/:pipeline_id/runs", get(list_pipeline_runs_handler))
+        .route(
+            "/api/workspaces/:workspace_id/pipelines/:pipeline_id/runs/:run_id/lineage",
+            get(get_pipeline_run_lineage_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/pipelines/:pipeline_id/comments",
+            post(create_comment_handler).get(list_comments_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/pipelines/:pipeline_id/comments/:comment_id",
+            put(update_comment_handler).delete(delete_comment_handler),
+        )
+        // Segments: saved filter groups queries reference by id
+        .route(
+            "/api/workspaces/:workspace_id/segments",
+            post(create_segment_handler).get(list_segments_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/segments/:segment_id",
+            get(get_segment_handler).put(update_segment_handler).delete(delete_segment_handler),
+        )
+        // Quality rules: column expectations checked against datasets
+        .route(
+            "/api/workspaces/:workspace_id/quality-rules",
+            post(create_quality_rule_handler).get(list_quality_rules_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/quality-rules/:rule_id",
+            get(get_quality_rule_handler).put(update_quality_rule_handler).delete(delete_quality_rule_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/quality-runs",
+            post(run_quality_rules_handler).get(list_quality_runs_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/datasets/:dataset_id/quality-runs/:run_id",
+            get(get_quality_run_handler),
+        )
+        // Dashboards: pipelines shown together
+        .route(
+            "/api/workspaces/:workspace_id/dashboards",
+            post(create_dashboard_handler).get(list_dashboards_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/dashboards/:dashboard_id",
+            get(get_dashboard_handler).put(update_dashboard_handler).delete(delete_dashboard_handler),
+        )
+        .route("/api/workspaces/:workspace_id/dashboards/:dashboard_id/data", get(get_dashboard_data_handler))
+        // Schedules: pipelines run on an interval, posting summaries to Slack or Teams
+        .route(
+            "/api/workspaces/:workspace_id/schedules",
+            post(create_schedule_handler).get(list_schedules_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/schedules/:schedule_id",
+            get(get_schedule_handler).put(update_schedule_handler).delete(delete_schedule_handler),
+        )
+        .route("/api/workspaces/:workspace_id/schedules/:schedule_id/run", post(run_schedule_handler))
+        .route(
+            "/api/workspaces/:workspace_id/schedules/:schedule_id/alerts",
+            post(create_alert_handler).get(list_alerts_handler),
+        )
+        .route(
+            "/api/workspaces/:workspace_id/schedules/:schedule_id/alerts/:alert_id",
+            get(get_alert_handler).put(update_alert_handler).delete(delete_alert_handler),
+        )
+        // Background jobs
+        .route("/api/workspaces/:workspace_id/jobs", get(list_jobs_handler))
+        .route("/api/workspaces/:workspace_id/jobs/:job_id", get(get_job_handler))
+        .route("/api/workspaces/:workspace_id/jobs/:job_id/cancel", post(cancel_job_handler))
+        // External data connections
+        .route(
+            "/api/workspaces/:workspace_id/connections",
+            post(create_connection_handler).get(list_connections_handler),
+        )
+        .route("/api/workspaces/:workspace_id/connections/:connection_id", delete(delete_connection_handler))
+        .route("/api/workspaces/:workspace_id/connections/:connection_id/tables", get(list_connection_tables_handler))
+        .route("/api/workspaces/:workspace_id/connections/:connection_id/query", post(query_connection_handler))
+        // Analytical Engine & Royalty Analytics routes
+        .route("/api/workspaces/:workspace_id/analytics/query", post(execute_query_handler))
+        .route("/api/workspaces/:workspace_id/analytics/download", post(download_query_csv_handler))
+        .route("/api/workspaces/:workspace_id/analytics/chart", post(chart_query_handler))
+        .route("/api/workspaces/:workspace_id/analytics/diff", post(diff_query_handler))
+        .route("/api/workspaces/:workspace_id/analytics/columns", post(query_columns_handler))
+        .route("/api/workspaces/:workspace_id/analytics/estimate", post(estimate_query_handler))
+        .route("/api/workspaces/:workspace_id/analytics/nl", post(natural_language_query_handler))
+        .route("/api/workspaces/:workspace_id/analytics/summary", get(get_analytics_summary_handler))
+        .route("/api/workspaces/:workspace_id/analytics/history", get(get_query_history_handler))
+        .route("/api/workspaces/:workspace_id/analytics/history/:query_id/download", get(download_query_history_csv_handler))
+        .route("/", get(|| async { "Hello from doubledecker angels." }))
+        // API docs: /docs + /openapi.json for SDK generation; /swagger-ui kept for existing links
+        .merge(SwaggerUi::new("/docs").url("/openapi.json", ApiDoc::openapi()))
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .merge(graphql_routes())
+        .merge(inngest_router)
+        .layer(
+            CorsLayer::new()
+                .allow_origin([
+                    "http://localhost:8080"
+                        .parse::<header::HeaderValue>()
+                        .unwrap(),
+                    "https://doubledecker.vercel.app"
+                        .parse::<header::HeaderValue>()
+                        .unwrap(),
+                ])
+                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+                .allow_headers([
+                    header::CONTENT_TYPE,
+                    header::AUTHORIZATION,
+                    header::HeaderName::from_static("idempotency-key"),
+                    header::IF_NONE_MATCH,
+                ])
+                .expose_headers([header::ETAG]),
+        )
+        // JSON bodies; the upload route above overrides this with its own limit
+        .layer(DefaultBodyLimit::max(body_limits.json_bytes))
+        .with_state(state.clone());
+
+    // Public embed endpoints: anonymous, read-only, callable from any origin
+    let public_router = Router::new()
+        .route("/public/tables/:token/query", get(public_table_query_handler))
+        .layer(CorsLayer::new().allow_origin(Any).allow_methods([Method::GET]))
+        .with_state(state);
+    // Outermost: shed load past the concurrency limit with a 503 and time out slow requests with a 408
+    app.merge(public_router).layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_overload))
+            .load_shed()
+            .concurrency_limit(request_limits.max_concurrent_requests)
+            .timeout(request_limits.timeout),
+    )
+}
@@ -0,0 +1,368 @@
+use crate::db::models::{
+    BackgroundJob, NotifyChannel, NotifyWhen, PaginationParams, Pipeline, PipelineSchedule, ThresholdOp, WorkspaceRole,
+};
+use crate::db::queries::{
+    ScheduleSettings, create_schedule, delete_schedule, enqueue_job, get_pipeline, get_schedule, list_schedules,
+    record_schedule_run, update_schedule,
+};
+use crate::engine::EngineProvider;
+use crate::server::alerts::evaluate_alerts;
+use crate::server::dtos::analytics::AnalyticsQueryResponse;
+use crate::server::dtos::schedules::*;
+use crate::server::dtos::DeleteResponse;
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::pipelines::execute_pipeline;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+use crate::utils::email::EmailSender;
+use crate::utils::error::{DoubledeckerError, FieldError};
+use crate::utils::webhooks::{post_webhook, summary_text, threshold_breaches, webhook_payload};
+use crate::workers::JobHandler;
+use axum::extract::{Path, State};
+use axum::response::Response;
+use axum::Json;
+use sqlx::PgPool;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Background job kind for scheduled pipeline runs.
+pub const PIPELINE_SCHEDULE_JOB: &str = "pipeline.schedule";
+/// Attempts of a scheduled run; webhooks that are down or rate limiting get retried.
+pub const PIPELINE_SCHEDULE_JOB_ATTEMPTS: i32 = 3;
+
+/// Runs scheduled pipelines, evaluates their alerts and posts their summaries. The job's result says
+/// what was found and who was notified.
+pub struct ScheduledPipelineJobHandler {
+    pub pool: PgPool,
+    pub engine: Arc<EngineProvider>,
+    pub http: reqwest::Client,
+    /// Unset when the server has no email API configured; alerts that email then record an error
+    pub email: Option<EmailSender>,
+}
+
+impl ScheduledPipelineJobHandler {
+    /// Post the run's summary if the schedule's condition holds. Returns the rows breaching the
+    /// threshold (if any) and whether a message was posted.
+    async fn post_summary(
+        &self,
+        schedule: &PipelineSchedule,
+        pipeline: &Pipeline,
+        result: &AnalyticsQueryResponse,
+    ) -> Result<(Option<usize>, bool), DoubledeckerError> {
+        let row_count = result.rows.len();
+        let notify_when = NotifyWhen::from_str(&schedule.notify_when)
+            .map_err(|_| DoubledeckerError::Internal(format!("Unknown notify_when '{}'", schedule.notify_when)))?;
+        let (notify, shown, headline, breaches) = match notify_when {
+            NotifyWhen::Never => return Ok((None, false)),
+            NotifyWhen::Always | NotifyWhen::AnyRows => (
+                notify_when == NotifyWhen::Always || row_count > 0,
+                result.rows.iter().collect::<Vec<_>>(),
+                format!("{} row{}", row_count, if row_count == 1 { "" } else { "s" }),
+                None,
+            ),
+            NotifyWhen::Threshold => {
+                let column = schedule.threshold_column.as_deref().unwrap_or_default();
+                let op = schedule
+                    .threshold_op
+                    .as_deref()
+                    .and_then(|op| ThresholdOp::from_str(op).ok())
+                    .ok_or_else(|| DoubledeckerError::Internal("Schedule threshold has no operator".to_string()))?;
+                let value = schedule.threshold_value.unwrap_or_default();
+                let breaching = threshold_breaches(&result.columns, &result.rows, column, op, value)?;
+                let headline = format!(
+                    "Threshold breached: {} of {} rows have {} {} {}",
+                    breaching.len(),
+                    row_count,
+                    column,
+                    op.symbol(),
+                    value
+                );
+                let breached = breaching.len();
+                (breached > 0, breaching, headline, Some(breached))
+            }
+        };
+        if !notify {
+            return Ok((breaches, false));
+        }
+
+        let (Some(channel), Some(encrypted)) = (schedule.channel.as_deref(), schedule.encrypted_webhook_url.as_deref())
+        else {
+            return Err(DoubledeckerError::Internal("Schedule has no webhook".to_string()));
+        };
+        let channel = NotifyChannel::from_str(channel)
+            .map_err(|_| DoubledeckerError::Internal(format!("Unknown channel '{}'", channel)))?;
+        let top = shown.len().min(schedule.top_rows.max(0) as usize);
+        let mut text = summary_text(&headline, &result.columns, &shown[..top]);
+        if top < shown.len() {
+            text.push_str(&format!("\n…and {} more", shown.len() - top));
+        }
+        let url = decrypt_secret(encrypted)?;
+        post_webhook(&self.http, &url, &webhook_payload(channel, &pipeline.name, &text)).await?;
+        Ok((breaches, true))
+    }
+}
+
+#[async_trait::async_trait]
+impl JobHandler for ScheduledPipelineJobHandler {
+    async fn run(&self, job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+        let payload: ScheduleRunJob = serde_json::from_value(job.payload.0.clone())
+            .map_err(|e| DoubledeckerError::BadRequest(format!("Invalid schedule job payload: {}", e)))?;
+        let workspace_id = job
+            .workspace_id
+            .ok_or_else(|| DoubledeckerError::BadRequest("Schedule job has no workspace".to_string()))?;
+        let schedule = get_schedule(&self.pool, workspace_id, payload.schedule_id).await?;
+
+        let outcome = async {
+            let pipeline = get_pipeline(&self.pool, schedule.workspace_id, schedule.pipeline_id).await?;
+            let result = execute_pipeline(&self.pool, &self.engine, &pipeline, None).await?;
+            // Alerts go first: they only notify on a change of state, so a retry after the summary
+            // failed to post does not repeat them
+            let alerts =
+                evaluate_alerts(&self.pool, &self.http, self.email.as_ref(), schedule.id, &pipeline, &result).await?;
+            let (breaches, notified) = self.post_summary(&schedule, &pipeline, &result).await?;
+            Ok::<_, DoubledeckerError>((result.rows.len(), breaches, notified, alerts))
+        }
+        .await;
+
+        match outcome {
+            Ok((row_count, breaches, notified, alerts)) => {
+                let status = if notified { "NOTIFIED" } else { "QUIET" };
+                record_schedule_run(&self.pool, schedule.id, status, None).await?;
+                Ok(serde_json::json!({
+                    "schedule_id": schedule.id,
+                    "row_count": row_count,
+                    "threshold_breaches": breaches,
+                    "notified": notified,
+                    "alerts": alerts,
+                }))
+            }
+            Err(e) => {
+                record_schedule_run(&self.pool, schedule.id, "FAILED", Some(&e.message())).await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Check the request against the workspace and encrypt its webhook URL, falling back to
+/// `current`'s URL when none was sent.
+async fn schedule_settings<'a>(
+    state: &AppState,
+    workspace_id: Uuid,
+    payload: &'a ScheduleRequest,
+    encrypted_webhook_url: &'a mut String,
+    current: Option<&PipelineSchedule>,
+) -> Result<ScheduleSettings<'a>, DoubledeckerError> {
+    // Confirms the pipeline belongs to the workspace
+    get_pipeline(&state.db_pool, workspace_id, payload.pipeline_id).await?;
+
+    // Validation guarantees a channel unless summaries are off, and summaries off need no webhook
+    let channel = payload.channel.filter(|_| payload.notify_when != NotifyWhen::Never);
+    if let Some(channel) = channel {
+        let current_url = current
+            .filter(|c| c.channel.as_deref() == Some(channel.as_str()))
+            .and_then(|c| c.encrypted_webhook_url.clone());
+        *encrypted_webhook_url = match (payload.webhook_url.as_deref(), current_url) {
+            (Some(url), _) => encrypt_secret(url.trim())?,
+            // A URL is only valid for its own channel, so switching channels needs a new one
+            (None, Some(current_url)) => current_url,
+            (None, None) => {
+                return Err(DoubledeckerError::Validation(vec![FieldError::new(
+                    "webhook_url",
+                    "required",
+                    "required with a new channel",
+                )]));
+            }
+        };
+    }
+    let threshold = payload.threshold.as_ref().filter(|_| payload.notify_when == NotifyWhen::Threshold);
+    Ok(ScheduleSettings {
+        pipeline_id: payload.pipeline_id,
+        interval_minutes: payload.interval_minutes,
+        enabled: payload.enabled,
+        channel: channel.map(NotifyChannel::as_str),
+        encrypted_webhook_url: channel.map(|_| encrypted_webhook_url.as_str()),
+        notify_when: payload.notify_when.as_str(),
+        threshold_column: threshold.map(|t| t.column.trim()),
+        threshold_op: threshold.map(|t| t.op.as_str()),
+        threshold_value: threshold.map(|t| t.value),
+        top_rows: payload.top_rows,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/schedules",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = ScheduleRequest,
+    responses(
+        (status = 200, description = "Schedule created; it first runs one interval from now", body = PipelineSchedule),
+        (status = 404, description = "Pipeline not found"),
+        (status = 422, description = "Invalid interval, webhook URL or threshold")
+    ),
+    tag = "schedules"
+)]
+pub async fn create_schedule_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ScheduleRequest>,
+) -> Result<Json<PipelineSchedule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let mut encrypted = String::new();
+    let settings = schedule_settings(&state, workspace_id, &payload, &mut encrypted, None).await?;
+    let schedule = create_schedule(&state.db_pool, workspace_id, &settings, auth_user.user_id).await?;
+    Ok(Json(schedule))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/schedules",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Schedules of the workspace", body = PaginatedPipelineSchedules),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
+    ),
+    tag = "schedules"
+)]
+pub async fn list_schedules_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    if_none_match: IfNoneMatch,
+    State(state): State<AppState>,
+) -> Result<Response, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let schedules = list_schedules(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    etagged_json(&if_none_match, &schedules)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID")
+    ),
+    responses(
+        (status = 200, description = "Schedule with the outcome of its last run", body = PipelineSchedule),
+        (status = 404, description = "Schedule not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn get_schedule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<PipelineSchedule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+    Ok(Json(schedule))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID")
+    ),
+    request_body = ScheduleRequest,
+    responses(
+        (status = 200, description = "Schedule replaced; its next run is one interval from now", body = PipelineSchedule),
+        (status = 404, description = "Schedule or pipeline not found"),
+        (status = 422, description = "Invalid interval, webhook URL or threshold")
+    ),
+    tag = "schedules"
+)]
+pub async fn update_schedule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ScheduleRequest>,
+) -> Result<Json<PipelineSchedule>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let current = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+    let mut encrypted = String::new();
+    let settings = schedule_settings(&state, workspace_id, &payload, &mut encrypted, Some(&current)).await?;
+    let schedule = update_schedule(&state.db_pool, workspace_id, schedule_id, &settings).await?;
+    Ok(Json(schedule))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID")
+    ),
+    responses(
+        (status = 200, description = "Schedule deleted", body = DeleteResponse)
+    ),
+    tag = "schedules"
+)]
+pub async fn delete_schedule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Schedule not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Schedule deleted successfully".to_string(),
+    }))
+}
+
+/// Queue a run of the schedule now, e.g. to check its webhook. The regular runs are unaffected.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/schedules/{schedule_id}/run",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("schedule_id" = Uuid, Path, description = "Schedule ID")
+    ),
+    responses(
+        (status = 200, description = "Run queued; follow it through the jobs API", body = BackgroundJob),
+        (status = 404, description = "Schedule not found")
+    ),
+    tag = "schedules"
+)]
+pub async fn run_schedule_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, schedule_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<BackgroundJob>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let schedule = get_schedule(&state.db_pool, workspace_id, schedule_id).await?;
+    let job_payload = serde_json::to_value(ScheduleRunJob { schedule_id: schedule.id })
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize schedule job: {}", e)))?;
+    let job = enqueue_job(
+        &state.db_pool,
+        Some(workspace_id),
+        PIPELINE_SCHEDULE_JOB,
+        job_payload,
+        PIPELINE_SCHEDULE_JOB_ATTEMPTS,
+        Some(auth_user.user_id),
+    )
+    .await?;
+    Ok(Json(job))
+}
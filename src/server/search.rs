@@ -0,0 +1,50 @@
+use crate::db::queries::search_user_resources;
+use crate::server::dtos::search::*;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::ValidatedQuery;
+use crate::utils::error::DoubledeckerError;
+use axum::extract::State;
+use axum::Json;
+
+/// One search over the caller's datasets, folders, pipelines, dashboards and segments, in every
+/// workspace they own or belong to, for a single omnibox. Quarantined datasets are not listed.
+#[utoipa::path(
+    get,
+    path = "/search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Matching resources with links to them", body = SearchResponse),
+        (status = 422, description = "q blank or too long, or limit out of range")
+    ),
+    tag = "search"
+)]
+pub async fn search_handler(
+    auth_user: AuthenticatedUser,
+    ValidatedQuery(params): ValidatedQuery<SearchParams>,
+    State(state): State<AppState>,
+) -> Result<Json<SearchResponse>, DoubledeckerError> {
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_RESULTS);
+    // One row past the limit tells whether the results were cut short
+    let hits = search_user_resources(&state.db_pool, auth_user.user_id, &params.q, limit + 1).await?;
+
+    let truncated = hits.len() > limit;
+    let results = hits
+        .into_iter()
+        .take(limit)
+        .filter_map(|hit| {
+            let kind = SearchResultKind::from_db_str(&hit.kind)?;
+            Some(SearchResult {
+                kind,
+                link: kind.link(hit.workspace_id, hit.id),
+                id: hit.id,
+                workspace_id: hit.workspace_id,
+                workspace_name: hit.workspace_name,
+                name: hit.name,
+                description: hit.description,
+                updated_at: hit.updated_at,
+            })
+        })
+        .collect();
+    Ok(Json(SearchResponse { results, truncated }))
+}
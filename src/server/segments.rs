@@ -0,0 +1,158 @@
+use crate::db::models::{PaginatedResponse, PaginationParams, Segment, WorkspaceRole};
+use crate::db::queries::{create_segment, delete_segment, get_segment, list_segments, update_segment};
+use crate::server::dtos::DeleteResponse;
+use crate::server::dtos::segments::*;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
+use crate::utils::error::DoubledeckerError;
+use axum::extract::{Path, State};
+use axum::Json;
+use uuid::Uuid;
+
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/segments",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = SegmentRequest,
+    responses(
+        (status = 200, description = "Segment created", body = Segment),
+        (status = 400, description = "A segment with this name already exists")
+    ),
+    tag = "segments"
+)]
+pub async fn create_segment_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<SegmentRequest>,
+) -> Result<Json<Segment>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let segment = create_segment(
+        &state.db_pool,
+        workspace_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        &payload.filters,
+        auth_user.user_id,
+    )
+    .await?;
+    Ok(Json(segment))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/segments",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Segments of the workspace", body = PaginatedSegments)
+    ),
+    tag = "segments"
+)]
+pub async fn list_segments_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<Segment>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let limit = pagination.effective_limit();
+    let segments = list_segments(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    Ok(Json(segments))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/segments/{segment_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("segment_id" = Uuid, Path, description = "Segment ID")
+    ),
+    responses(
+        (status = 200, description = "Segment", body = Segment),
+        (status = 404, description = "Segment not found")
+    ),
+    tag = "segments"
+)]
+pub async fn get_segment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, segment_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Segment>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let segment = get_segment(&state.db_pool, workspace_id, segment_id).await?;
+    Ok(Json(segment))
+}
+
+/// Replace a segment. Queries referencing it use the new filters from their next run.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/segments/{segment_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("segment_id" = Uuid, Path, description = "Segment ID")
+    ),
+    request_body = SegmentRequest,
+    responses(
+        (status = 200, description = "Segment replaced", body = Segment),
+        (status = 404, description = "Segment not found")
+    ),
+    tag = "segments"
+)]
+pub async fn update_segment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, segment_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<SegmentRequest>,
+) -> Result<Json<Segment>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let segment = update_segment(
+        &state.db_pool,
+        workspace_id,
+        segment_id,
+        payload.name.trim(),
+        payload.description.as_deref(),
+        &payload.filters,
+    )
+    .await?;
+    Ok(Json(segment))
+}
+
+/// Delete a segment. Queries still referencing it fail with 404 until the reference is removed.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/segments/{segment_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("segment_id" = Uuid, Path, description = "Segment ID")
+    ),
+    responses(
+        (status = 200, description = "Segment deleted", body = DeleteResponse)
+    ),
+    tag = "segments"
+)]
+pub async fn delete_segment_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, segment_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let deleted = delete_segment(&state.db_pool, workspace_id, segment_id).await?;
+    if deleted == 0 {
+        return Err(DoubledeckerError::NotFound("Segment not found".to_string()));
+    }
+    Ok(Json(DeleteResponse {
+        message: "Segment deleted successfully".to_string(),
+    }))
+}
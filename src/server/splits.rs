@@ -3,9 +3,10 @@ use crate::db::queries::{create_split, delete_split, get_splits_for_target, upda
 use crate::server::dtos::DeleteResponse;
 use crate::server::extractors::verify_workspace_access;
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, State};
 use axum::Json;
 use crate::server::dtos::splits::*;
 use uuid::Uuid;
@@ -26,7 +27,7 @@ pub async fn create_split_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<CreateSplitRequest>,
+    ValidatedJson(payload): ValidatedJson<CreateSplitRequest>,
 ) -> Result<Json<CascadingSplit>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Manager).await?;
 
@@ -67,7 +68,7 @@ pub async fn create_split_handler(
 pub async fn list_splits_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<CascadingSplit>>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
@@ -123,7 +124,7 @@ pub async fn update_split_handler(
     auth_user: AuthenticatedUser,
     Path((workspace_id, split_id)): Path<(Uuid, Uuid)>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdateSplitRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdateSplitRequest>,
 ) -> Result<Json<CascadingSplit>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Manager).await?;
 
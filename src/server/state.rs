@@ -1,10 +1,96 @@
+use crate::config::EngineConfig;
+use crate::engine::EngineProvider;
+use crate::utils::events::EventBus;
+use crate::utils::llm::{ChatCompletionsProvider, LlmProvider};
+use crate::utils::login_guard::LoginGuard;
+use crate::utils::password::PasswordPolicy;
+use crate::utils::query_limiter::QueryLimiter;
+use crate::utils::rate_limit::RateLimiter;
+use crate::utils::s3::{ObjectStorage, S3Uploader};
+use crate::utils::scanner::{ContentScanner, scanner_from_env};
 use sqlx::PgPool;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: PgPool,
     pub engine: Arc<crate::engine::EngineProvider>,
-    pub uploader: Arc<crate::utils::s3::S3Uploader>,
+    pub uploader: Arc<dyn crate::utils::s3::ObjectStorage>,
     pub inngest_client: Arc<inngest::client::Inngest>,
+    pub events: crate::utils::events::EventBus,
+    pub public_rate_limiter: Arc<crate::utils::rate_limit::RateLimiter>,
+    pub login_guard: Arc<crate::utils::login_guard::LoginGuard>,
+    pub password_policy: Arc<crate::utils::password::PasswordPolicy>,
+    pub query_limiter: Arc<crate::utils::query_limiter::QueryLimiter>,
+    /// Model behind natural-language queries; `None` turns them off
+    pub llm: Option<Arc<dyn crate::utils::llm::LlmProvider>>,
+    /// Scans uploads before ingestion; a no-op unless `CLAMD_ADDR` is set
+    pub scanner: Arc<dyn ContentScanner>,
+}
+
+impl AppState {
+    /// Production wiring over a migrated pool: S3 storage, the query engine, the Inngest client and
+    /// the request limiters, all configured from the environment.
+    pub async fn from_env(db_pool: PgPool) -> Self {
+        // Loaded once: resolving the credential chain can take a network round trip
+        let aws_config = aws_config::load_from_env().await;
+        let uploader: Arc<dyn ObjectStorage> = Arc::new(S3Uploader::new(&aws_config));
+        let engine_config = EngineConfig::from_env();
+        eprintln!(
+            "✓ Query memory budget {} MB (spill to disk {})",
+            engine_config.memory_limit_bytes / (1024 * 1024),
+            if engine_config.spill_to_disk { "on" } else { "off" }
+        );
+        let engine = Arc::new(EngineProvider::new(db_pool.clone(), &engine_config));
+        let inngest_client = Arc::new(inngest::client::Inngest::new("doubledecker"));
+        let public_rate_limit = std::env::var("PUBLIC_RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        let public_rate_limiter = RateLimiter::new(public_rate_limit, Duration::from_secs(60));
+        // With REDIS_URL (and the redis feature), limits are shared by every instance
+        #[cfg(feature = "redis")]
+        let public_rate_limiter = match crate::utils::redis::connect_from_env().await {
+            Some(conn) => {
+                eprintln!("✓ Rate limits shared through Redis; login lockouts and query slots stay per instance");
+                public_rate_limiter.with_redis(conn, "ratelimit:public")
+            }
+            None => public_rate_limiter,
+        };
+        #[cfg(not(feature = "redis"))]
+        if std::env::var("REDIS_URL").is_ok_and(|u| !u.trim().is_empty()) {
+            eprintln!("REDIS_URL is set but this build lacks the redis feature; rate limits stay in-process");
+        }
+
+        // Per-user concurrent query cap; extra queries wait briefly for a slot, then get a 429
+        let query_limiter = QueryLimiter::new(
+            std::env::var("MAX_CONCURRENT_QUERIES_PER_USER").ok().and_then(|v| v.parse().ok()).unwrap_or(2),
+            Duration::from_millis(
+                std::env::var("QUERY_QUEUE_TIMEOUT_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(2000),
+            ),
+        );
+
+        // Failed logins allowed per account from one IP / per client IP / per account across all IPs
+        // before backoff lockouts kick in
+        let login_guard = LoginGuard::new(
+            std::env::var("LOGIN_MAX_FAILURES_PER_ACCOUNT").ok().and_then(|v| v.parse().ok()).unwrap_or(5),
+            std::env::var("LOGIN_MAX_FAILURES_PER_IP").ok().and_then(|v| v.parse().ok()).unwrap_or(20),
+            std::env::var("LOGIN_MAX_FAILURES_PER_ACCOUNT_TOTAL").ok().and_then(|v| v.parse().ok()).unwrap_or(50),
+        );
+
+        Self {
+            db_pool,
+            engine,
+            uploader,
+            inngest_client,
+            events: EventBus::new(),
+            public_rate_limiter: Arc::new(public_rate_limiter),
+            login_guard: Arc::new(login_guard),
+            password_policy: Arc::new(PasswordPolicy::from_env()),
+            query_limiter: Arc::new(query_limiter),
+            llm: ChatCompletionsProvider::from_env().map(|p| Arc::new(p) as Arc<dyn LlmProvider>),
+            scanner: scanner_from_env(),
+        }
+    }
 }
@@ -1,99 +1,308 @@
-use crate::db::models::{PaginatedResponse, PaginationParams, WorkspaceRole};
-use crate::db::queries::{create_dataset, get_dataset_by_id, get_datasets, update_dataset_status};
-use crate::server::dtos::common::DatasetResponse;
+use crate::db::models::{
+    BackgroundJob, ColumnRestriction, DatasetAction, DatasetActivity, DatasetListParams, DatasetStatus,
+    PaginatedResponse, PaginationParams, UploadSession, WorkspaceRole,
+};
+use crate::db::queries::{
+    claim_upload_session, create_dataset, create_upload_session, create_user_data_key, delete_datasets,
+    delete_idle_upload_sessions, delete_upload_session, enqueue_outbox_event, finish_upload_session, get_dataset_by_id,
+    get_datasets,
+    get_datasets_by_ids, get_upload_session, get_user_data_key, list_upload_session_parts, record_upload_part,
+    list_dataset_activity, list_dataset_column_restrictions, list_workspace_column_restrictions, record_dataset_activity,
+    replace_dataset_column_restrictions, set_dataset_favorite, set_dataset_public_token, set_dataset_source_encoding,
+    set_dataset_staging_encrypted, set_dataset_validation_report, dataset_data_key,
+    update_dataset_status,
+};
+use crate::engine::QueryScope;
+use crate::engine::udfs::MaskMode;
+use crate::utils::pii::{PiiFinding, scan_batches_for_pii};
+use crate::normalization::{
+    DistributorSource, InferenceOptions, InferredSchema, infer_csv_schema, sniff_tabular, transcode_to_utf8,
+    unified_royalty_schema, validate_csv,
+};
+use crate::server::dtos::common::{
+    BulkDeleteItemResult, BulkDeleteRequest, BulkDeleteResponse, DatasetResponse, DeleteResponse, FavoriteResponse,
+};
 use crate::server::dtos::uploads::*;
-use crate::server::extractors::verify_workspace_access;
+use crate::server::extractors::{RESTRICTED_COLUMNS_MIN_ROLE, query_scope_for_role, verify_workspace_access};
+use crate::server::etag::{IfNoneMatch, etagged_json};
+use crate::server::idempotency::{IdempotencyKey, request_hash, run_idempotent};
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{Validate, ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
-use crate::utils::error::DoubledeckerError;
-use axum::extract::{Multipart, Path, Query, State};
+use crate::config::{mask_hash_key, presign_config, upload_limits};
+use crate::utils::crypto::{
+    decrypt_file, encrypt_bytes, generate_data_key, secrets_key_configured, unwrap_data_key, wrap_data_key,
+};
+use crate::utils::error::{DoubledeckerError, FieldError};
+use crate::utils::helpers::{array_to_json_values, attachment_disposition, download_filename};
+use crate::utils::s3::{ObjectStorage, parquet_key, quarantine_key, staging_key};
+use crate::workers::JobHandler;
+use crate::workers::outbox::dispatch_soon;
+use aes_gcm::{Aes256Gcm, Key};
+use axum::extract::{Multipart, Path, State};
 use axum::Json;
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use datafusion::arrow::array::Int64Array;
+use futures::{StreamExt, stream};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use uuid::Uuid;
 
-/// Path A (<50MB): Direct multipart upload endpoint
+/// A session left COMPLETING this long, by a request that died midway, can be completed or deleted
+/// again.
+const UPLOAD_SESSION_COMPLETING_TIMEOUT_SECS: i64 = 15 * 60;
+/// Background job kind that deletes abandoned upload sessions and their stored parts.
+pub const UPLOAD_SESSION_CLEANUP_JOB: &str = "upload_sessions.cleanup";
+pub const UPLOAD_SESSION_CLEANUP_JOB_ATTEMPTS: i32 = 3;
+/// Sessions that never became a dataset are abandoned once unchanged for this long.
+const UPLOAD_SESSION_IDLE_SECS: i64 = 24 * 60 * 60;
+const UPLOAD_SESSION_CLEANUP_BATCH: i64 = 100;
+
+/// Path A (<50MB by default): Direct multipart upload endpoint. Files in UTF-16 or Windows-1252/Latin-1 (common
+/// for Excel exports) are transcoded to UTF-8 first. The file is then scanned for ragged rows, invalid
+/// UTF-8 and values that do not match their column's type; the report is stored on the dataset.
+/// Send a `strict=true` field to reject a file with any such problems instead. Column types are
+/// inferred from the first `infer_max_records` rows (default 1000), or from every row with
+/// `full_scan_inference=true`; a full-scan schema is stored and reused if the file is reprocessed.
+/// Retrying with the same `Idempotency-Key` returns the dataset created the first time.
 #[utoipa::path(
     post,
     path = "/api/workspaces/{workspace_id}/datasets/upload",
     params(
-        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Client-chosen key; a retry with the same key and file replays the first response instead of uploading again")
     ),
     responses(
-        (status = 200, description = "Dataset uploaded directly", body = DatasetResponse)
+        (status = 200, description = "Dataset uploaded directly", body = DatasetResponse),
+        (status = 409, description = "A request with this Idempotency-Key is still in progress", body = crate::server::dtos::common::ErrorResponse),
+        (status = 413, description = "File is over the upload limit (`UPLOAD_BODY_LIMIT_MB`), or has more columns or rows than allowed (see `GET /limits`)", body = crate::server::dtos::common::ErrorResponse),
+        (status = 415, description = "File is not CSV text, e.g. an image or an Excel workbook", body = crate::server::dtos::common::ErrorResponse),
+        (status = 422, description = "Strict upload failed validation; the report is in `details`", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "datasets"
 )]
 pub async fn upload_dataset_direct(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
+    IdempotencyKey(idempotency_key): IdempotencyKey,
     State(state): State<AppState>,
     mut multipart: Multipart,
-) -> Result<Json<DatasetResponse>, DoubledeckerError> {
+) -> Result<Response, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
 
     let mut distributor_source = "auto".to_string();
     let mut file_content: Option<Vec<u8>> = None;
     let mut filename = "upload.csv".to_string();
+    let mut strict = false;
+    let mut inference = InferenceOptions::default();
 
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| DoubledeckerError::BadRequest(format!("Multipart error: {}", e)))?
-    {
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            DoubledeckerError::from(e)
+        } else {
+            DoubledeckerError::BadRequest(format!("Multipart error: {}", e))
+        }
+    })? {
         let name = field.name().unwrap_or("").to_string();
         if name == "distributor_source" || name == "source" {
             if let Ok(text) = field.text().await {
                 distributor_source = text;
             }
+        } else if name == "strict" {
+            strict = field.text().await.is_ok_and(|text| parse_flag(&text));
+        } else if name == "full_scan_inference" {
+            inference.full_scan_inference = field.text().await.is_ok_and(|text| parse_flag(&text));
+        } else if name == "infer_max_records" {
+            let text = field.text().await.unwrap_or_default();
+            let records = text.trim().parse().map_err(|_| {
+                DoubledeckerError::Validation(vec![FieldError::new(
+                    "infer_max_records",
+                    "invalid_format",
+                    "infer_max_records must be a positive integer",
+                )])
+            })?;
+            inference.infer_max_records = Some(records);
         } else if name == "file" || name == "csv" {
             if let Some(fn_str) = field.file_name() {
                 filename = fn_str.to_string();
             }
-            if let Ok(bytes) = field.bytes().await {
-                file_content = Some(bytes.to_vec());
-            }
+            // Surface read errors: over the route's body limit, this is where the upload gets cut off
+            file_content = Some(field.bytes().await?.to_vec());
         }
     }
 
+    let errors = inference.validate();
+    if !errors.is_empty() {
+        return Err(DoubledeckerError::Validation(errors));
+    }
+
     let content = file_content.ok_or_else(|| DoubledeckerError::BadRequest("No file uploaded".to_string()))?;
+    let upload = DirectUpload {
+        distributor_source,
+        filename,
+        content,
+        strict,
+        inference,
+    };
+    let hash = request_hash(&[
+        workspace_id.as_bytes(),
+        upload.distributor_source.as_bytes(),
+        upload.filename.as_bytes(),
+        &[upload.strict as u8],
+        &serde_json::to_vec(&upload.inference).unwrap_or_default(),
+        &upload.content,
+    ]);
+    run_idempotent(
+        &state.db_pool,
+        auth_user.user_id,
+        "datasets/upload",
+        idempotency_key,
+        hash,
+        async {
+            let dataset = store_direct_upload(&state, auth_user.user_id, workspace_id, upload).await?;
+            Ok(Json(dataset).into_response())
+        },
+    )
+    .await
+}
+
+/// A parsed direct upload: the file and the form fields that go with it.
+pub(crate) struct DirectUpload {
+    pub distributor_source: String,
+    pub filename: String,
+    pub content: Vec<u8>,
+    pub strict: bool,
+    pub inference: InferenceOptions,
+}
+
+/// Validate, encrypt (where a master key is configured) and stage a direct upload, then queue it for ingestion.
+pub(crate) async fn store_direct_upload(
+    state: &AppState,
+    user_id: Uuid,
+    workspace_id: Uuid,
+    upload: DirectUpload,
+) -> Result<DatasetResponse, DoubledeckerError> {
+    let DirectUpload { distributor_source, filename, content, strict, inference } = upload;
     let file_size_bytes = content.len() as i64;
 
-    if file_size_bytes > 50 * 1024 * 1024 {
+    if file_size_bytes > MAX_DIRECT_UPLOAD_BYTES {
         return Err(DoubledeckerError::BadRequest(
             "File exceeds 50MB limit for direct upload. Please use Path B (presigned URL upload).".to_string(),
         ));
     }
 
+    sniff_tabular(&content)?;
+    let (content, encoding, report) = tokio::task::spawn_blocking(move || {
+        let (content, encoding) = transcode_to_utf8(content);
+        let report = validate_csv(&content);
+        (content, encoding, report)
+    })
+    .await
+    .map_err(|e| DoubledeckerError::Internal(format!("Validation task failed: {}", e)))?;
+    if strict && !report.is_clean() {
+        return Err(DoubledeckerError::FileValidation(Box::new(report)));
+    }
+    upload_limits().check_shape(report.total_columns, report.total_rows)?;
+
     let dataset_id = Uuid::new_v4();
-    let staging_key = format!("workspaces/{}/staging/{}.csv", workspace_id, dataset_id);
-    let parquet_key = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+    let staging_key = staging_key(workspace_id, user_id, dataset_id);
+    let parquet_key = parquet_key(workspace_id, dataset_id);
 
-    // 1. Upload staging file to S3
-    state.uploader.upload_csv_with_key(&staging_key, content).await?;
+    // 1. Encrypt with the uploader's data key, where a master key is configured, and upload staging file to S3
+    let (key_id, staged) = match user_data_key(state, user_id).await? {
+        Some((key_id, data_key)) => (Some(key_id), encrypt_bytes(&data_key, &content)?),
+        None => (None, content),
+    };
+    state.uploader.upload_csv_with_key(&staging_key, staged).await?;
 
-    // 2. Create dataset in DB as QUEUED
-    let dataset = create_dataset(
-        &state.db_pool,
-        workspace_id,
-        distributor_source,
-        filename,
-        parquet_key,
-        file_size_bytes,
-        "QUEUED".to_string(),
-    )
-    .await?;
+    // 2. Create dataset as QUEUED together with its ingestion event (transactional outbox).
+    //    If that fails, remove the staged object so S3 and the DB stay consistent.
+    let queued = async {
+        let mut tx = state.db_pool.begin().await.map_err(db_err)?;
+        let mut dataset = create_dataset(
+            &mut *tx,
+            dataset_id,
+            workspace_id,
+            distributor_source,
+            filename,
+            parquet_key,
+            file_size_bytes,
+            DatasetStatus::Queued,
+            key_id,
+        )
+        .await?;
+        if dataset.encryption_key_id.is_some() {
+            set_dataset_staging_encrypted(&mut *tx, dataset.id).await?;
+            dataset.staging_encrypted = true;
+        }
+        set_dataset_source_encoding(&mut *tx, dataset.id, encoding).await?;
+        set_dataset_validation_report(&mut *tx, dataset.id, &report).await?;
+        record_dataset_activity(&mut *tx, dataset.id, Some(user_id), DatasetAction::Created, None).await?;
+        dataset.source_encoding = Some(encoding.as_str().to_string());
+        dataset.row_count = report.total_rows as i64;
+        dataset.validation_report = Some(sqlx::types::Json(report));
+        enqueue_outbox_event(&mut tx, "dataset/uploaded", ingestion_event(workspace_id, dataset.id, &staging_key, strict, &inference))
+            .await?;
+        tx.commit().await.map_err(db_err)?;
+        Ok::<_, DoubledeckerError>(dataset)
+    }
+    .await;
+    let dataset = match queued {
+        Ok(dataset) => dataset,
+        Err(e) => {
+            let _ = state.uploader.delete_file(&staging_key).await;
+            return Err(e);
+        }
+    };
 
-    // 3. Send event to Inngest for background workflow orchestration
-    let evt = inngest::event::Event::new(
-        "dataset/uploaded",
-        serde_json::json!({
-            "workspace_id": workspace_id,
-            "dataset_id": dataset.id,
-            "staging_key": staging_key,
-        }),
-    );
-    let _ = state.inngest_client.send_event(&evt).await;
+    // 3. Hand the event to Inngest for background workflow orchestration
+    dispatch_soon(state.db_pool.clone(), state.inngest_client.clone());
 
-    Ok(Json(DatasetResponse::from_dataset(dataset)))
+    Ok(DatasetResponse::from_dataset(dataset))
+}
+
+/// Payload of the `dataset/uploaded` event consumed by the ingestion workflow. `strict` makes the
+/// workflow fail the dataset instead of converting a file whose validation report has problems.
+fn ingestion_event(
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    staging_key: &str,
+    strict: bool,
+    inference: &InferenceOptions,
+) -> serde_json::Value {
+    serde_json::json!({
+        "workspace_id": workspace_id,
+        "dataset_id": dataset_id,
+        "staging_key": staging_key,
+        "strict": strict,
+        "inference": inference,
+    })
+}
+
+/// Multipart form flags: `true`/`1`/`yes`, case-insensitive.
+fn parse_flag(text: &str) -> bool {
+    matches!(text.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes")
+}
+
+fn db_err(e: sqlx::Error) -> DoubledeckerError {
+    DoubledeckerError::DatabaseError(e.to_string())
+}
+
+/// The user's data key, created and wrapped with the master key on first use; `None` when no
+/// master key is configured.
+pub(crate) async fn user_data_key(state: &AppState, user_id: Uuid) -> Result<Option<(Uuid, Key<Aes256Gcm>)>, DoubledeckerError> {
+    if !secrets_key_configured() {
+        return Ok(None);
+    }
+    if let Some(existing) = get_user_data_key(&state.db_pool, user_id).await? {
+        return Ok(Some((existing.id, unwrap_data_key(&existing.wrapped_key)?)));
+    }
+    let created = create_user_data_key(&state.db_pool, user_id, &wrap_data_key(&generate_data_key())?).await?;
+    Ok(Some((created.id, unwrap_data_key(&created.wrapped_key)?)))
 }
 
 /// Path B (>50MB): Generate Presigned PUT URL for direct-to-S3 client upload
@@ -105,7 +314,8 @@ pub async fn upload_dataset_direct(
     ),
     request_body = PresignedUrlRequest,
     responses(
-        (status = 200, description = "Presigned URL generated", body = PresignedUrlResponse)
+        (status = 200, description = "Presigned URL generated", body = PresignedUrlResponse),
+        (status = 413, description = "file_size_bytes is over the upload limit (see `GET /limits`)", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "datasets"
 )]
@@ -113,36 +323,44 @@ pub async fn generate_presigned_url_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<PresignedUrlRequest>,
+    ValidatedJson(payload): ValidatedJson<PresignedUrlRequest>,
 ) -> Result<Json<PresignedUrlResponse>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    upload_limits().check_file_size(payload.file_size_bytes as u64)?;
 
     let dataset_id = Uuid::new_v4();
-    let staging_key = format!("workspaces/{}/staging/{}.csv", workspace_id, dataset_id);
-    let parquet_key = format!("workspaces/{}/processed/{}.parquet", workspace_id, dataset_id);
+    let staging_key = staging_key(workspace_id, auth_user.user_id, dataset_id);
+    let parquet_key = parquet_key(workspace_id, dataset_id);
 
     // 1. Generate presigned PUT URL
+    let expires_in = payload.expires_in.unwrap_or(presign_config().upload_expiry_secs);
     let presigned_url = state
         .uploader
-        .generate_presigned_put_url(&staging_key, Some(3600))
+        .generate_presigned_put_url(&staging_key, Some(expires_in))
         .await?;
 
-    // 2. Create dataset as PENDING_UPLOAD
+    // 2. Create dataset as PENDING_UPLOAD. The client PUTs straight to S3, so the staged file is
+    //    stored as sent; the Parquet converted from it is encrypted with the uploader's data key.
+    let key_id = user_data_key(&state, auth_user.user_id).await?.map(|(key_id, _)| key_id);
     let _dataset = create_dataset(
         &state.db_pool,
+        dataset_id,
         workspace_id,
         payload.distributor_source.unwrap_or_else(|| "auto".to_string()),
         payload.filename.clone(),
         parquet_key,
         payload.file_size_bytes,
-        "PENDING_UPLOAD".to_string(),
+        DatasetStatus::PendingUpload,
+        key_id,
     )
     .await?;
+    record_dataset_activity(&state.db_pool, dataset_id, Some(auth_user.user_id), DatasetAction::Created, None).await?;
 
     Ok(Json(PresignedUrlResponse {
         dataset_id,
         presigned_url,
         staging_key,
+        expires_in,
     }))
 }
 
@@ -155,7 +373,9 @@ pub async fn generate_presigned_url_handler(
     ),
     request_body = ConfirmUploadRequest,
     responses(
-        (status = 200, description = "Dataset upload confirmed", body = DatasetResponse)
+        (status = 200, description = "Dataset upload confirmed", body = DatasetResponse),
+        (status = 400, description = "Dataset is not awaiting confirmation"),
+        (status = 403, description = "staging_key was not issued to this user for this dataset", body = crate::server::dtos::common::ErrorResponse)
     ),
     tag = "datasets"
 )]
@@ -163,51 +383,1039 @@ pub async fn confirm_upload_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<ConfirmUploadRequest>,
+    ValidatedJson(payload): ValidatedJson<ConfirmUploadRequest>,
 ) -> Result<Json<DatasetResponse>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
 
-    let _ = update_dataset_status(&state.db_pool, payload.dataset_id, "QUEUED", 0, None).await?;
-    
-    // Send event to Inngest for background workflow orchestration
-    let evt = inngest::event::Event::new(
-        "dataset/uploaded",
-        serde_json::json!({
-            "workspace_id": workspace_id,
-            "dataset_id": payload.dataset_id,
-            "staging_key": payload.staging_key,
-        }),
-    );
-    let _ = state.inngest_client.send_event(&evt).await;
+    // Only the user the upload URL was issued to can confirm it, and only for that dataset's key
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, payload.dataset_id).await?;
+    if payload.staging_key != staging_key(workspace_id, auth_user.user_id, dataset.id) {
+        return Err(DoubledeckerError::Forbidden(
+            "staging_key does not belong to this dataset and user".to_string(),
+        ));
+    }
+    // A FAILED dataset still has its staged file, so confirming again retries the conversion
+    let status = dataset.status.as_str();
+    if status != DatasetStatus::PendingUpload.as_str() && status != DatasetStatus::Failed.as_str() {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "Dataset is already {}; only PENDING_UPLOAD or FAILED uploads can be confirmed",
+            status
+        )));
+    }
+
+    let mut tx = state.db_pool.begin().await.map_err(db_err)?;
+    update_dataset_status(&mut *tx, dataset.id, DatasetStatus::Queued, None, None).await?;
+    enqueue_outbox_event(&mut tx, "dataset/uploaded", ingestion_event(workspace_id, dataset.id, &payload.staging_key, payload.strict, &payload.inference))
+        .await?;
+    tx.commit().await.map_err(db_err)?;
+
+    // Hand the event to Inngest for background workflow orchestration
+    dispatch_soon(state.db_pool.clone(), state.inngest_client.clone());
 
     let dataset = get_dataset_by_id(&state.db_pool, workspace_id, payload.dataset_id).await?;
     Ok(Json(DatasetResponse::from_dataset(dataset)))
 }
 
+/// Path B (>50MB): Preview a staged upload before confirming it. Only the header and the first
+/// `rows` rows are read from storage, so previews of multi-GB files stay fast; column types are
+/// inferred from those rows alone and may differ from the full conversion's.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/preview",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = PreviewUploadRequest,
+    responses(
+        (status = 200, description = "Start of the staged file", body = UploadPreviewResponse),
+        (status = 400, description = "Dataset is not awaiting confirmation"),
+        (status = 403, description = "staging_key was not issued to this user for this dataset", body = crate::server::dtos::common::ErrorResponse),
+        (status = 404, description = "Nothing has been uploaded to staging_key yet"),
+        (status = 413, description = "The file has more columns than allowed (see `GET /limits`)", body = crate::server::dtos::common::ErrorResponse),
+        (status = 415, description = "The staged file is not CSV text", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "datasets"
+)]
+pub async fn preview_upload_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<PreviewUploadRequest>,
+) -> Result<Json<UploadPreviewResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    // Same ownership rules as confirming the upload
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, payload.dataset_id).await?;
+    if payload.staging_key != staging_key(workspace_id, auth_user.user_id, dataset.id) {
+        return Err(DoubledeckerError::Forbidden(
+            "staging_key does not belong to this dataset and user".to_string(),
+        ));
+    }
+    let status = dataset.status.as_str();
+    if (status != DatasetStatus::PendingUpload.as_str() && status != DatasetStatus::Failed.as_str())
+        || dataset.staging_encrypted
+    {
+        return Err(DoubledeckerError::BadRequest(
+            "Only presigned uploads awaiting confirmation can be previewed".to_string(),
+        ));
+    }
+
+    let rows = payload.rows.unwrap_or(DEFAULT_PREVIEW_ROWS);
+    let head = state.uploader.read_csv_head(&payload.staging_key, rows + 1).await?;
+    let bytes_read = head.len();
+    sniff_tabular(&head)?;
+    let (head, encoding) = transcode_to_utf8(head);
+
+    let schema = infer_csv_schema(&head, &InferenceOptions::default())?;
+    // Rows are only known once the whole file is read, on confirmation
+    upload_limits().check_shape(schema.fields().len() as u64, 0)?;
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(head.as_slice());
+    let rows = reader
+        .byte_records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(|field| String::from_utf8_lossy(field).into_owned()).collect())
+                .map_err(|e| DoubledeckerError::BadRequest(format!("File is not valid CSV: {}", e)))
+        })
+        .collect::<Result<Vec<Vec<String>>, _>>()?;
+
+    Ok(Json(UploadPreviewResponse {
+        columns: InferredSchema::from_schema(&schema, false).columns,
+        rows,
+        source_encoding: encoding,
+        detected_source: DistributorSource::detect_from_csv_bytes(&head).as_str().to_string(),
+        bytes_read,
+    }))
+}
+
+/// Limits on uploads, for clients to check a file against before sending it. Uploads over a limit
+/// are rejected with 413 `UPLOAD_LIMIT_EXCEEDED`, naming the limit in `details`.
 #[utoipa::path(
     get,
-    path = "/api/workspaces/{workspace_id}/datasets",
+    path = "/limits",
+    responses(
+        (status = 200, description = "Current upload limits", body = UploadLimitsResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    tag = "datasets"
+)]
+pub async fn get_upload_limits_handler(_auth_user: AuthenticatedUser) -> Json<UploadLimitsResponse> {
+    let limits = upload_limits();
+    Json(UploadLimitsResponse {
+        max_columns: limits.max_columns,
+        max_rows: limits.max_rows,
+        max_file_bytes: limits.max_file_bytes,
+        max_direct_upload_bytes: limits.max_file_bytes.min(MAX_DIRECT_UPLOAD_BYTES as u64),
+        max_upload_session_bytes: limits.max_file_bytes.min(MAX_UPLOAD_SESSION_BYTES as u64),
+        min_chunk_size: MIN_UPLOAD_CHUNK_BYTES as u64,
+        max_chunk_size: MAX_UPLOAD_CHUNK_BYTES as u64,
+        default_chunk_size: DEFAULT_UPLOAD_CHUNK_BYTES as u64,
+    })
+}
+
+/// Start a resumable upload (up to 5GB): the file is then PUT as numbered parts of `chunk_size`
+/// bytes, in any order and as many times as needed, and completed into a dataset. A client on a slow
+/// link can show progress from `bytes_received` and, after a dropped connection, send only the parts
+/// missing from `received_parts` instead of starting over.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/upload_sessions",
     params(
         ("workspace_id" = Uuid, Path, description = "Workspace ID")
     ),
+    request_body = CreateUploadSessionRequest,
+    responses(
+        (status = 200, description = "Upload session created", body = UploadSession),
+        (status = 413, description = "total_bytes is over the upload limit (see `GET /limits`)", body = crate::server::dtos::common::ErrorResponse),
+        (status = 422, description = "total_bytes is over 5GB, or chunk_size is not 5MB to 32MB")
+    ),
+    tag = "datasets"
+)]
+pub async fn create_upload_session_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<CreateUploadSessionRequest>,
+) -> Result<Json<UploadSession>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+    upload_limits().check_file_size(payload.total_bytes as u64)?;
+
+    let inference = serde_json::to_value(payload.inference)
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to serialize inference options: {}", e)))?;
+    // The session's file is staged under the id of the dataset it becomes
+    let session_id = Uuid::new_v4();
+    let staging_key = staging_key(workspace_id, auth_user.user_id, session_id);
+    let upload_id = state.uploader.create_multipart_upload(&staging_key).await?;
+    let session = create_upload_session(
+        &state.db_pool,
+        session_id,
+        workspace_id,
+        auth_user.user_id,
+        &payload.filename,
+        payload.distributor_source.as_deref().unwrap_or("auto"),
+        payload.total_bytes,
+        payload.chunk_size.unwrap_or(DEFAULT_UPLOAD_CHUNK_BYTES),
+        &staging_key,
+        &upload_id,
+        payload.strict,
+        inference,
+    )
+    .await;
+    if session.is_err() {
+        let _ = state.uploader.abort_multipart_upload(&staging_key, &upload_id).await;
+    }
+    Ok(Json(session?))
+}
+
+/// How far an upload session has got. After a failed part, send the parts missing from
+/// `received_parts`.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/upload_sessions/{session_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID")
+    ),
     responses(
-        (status = 200, description = "List workspace datasets", body = PaginatedDatasets)
+        (status = 200, description = "Upload session", body = UploadSession),
+        (status = 404, description = "No such session of this user")
+    ),
+    tag = "datasets"
+)]
+pub async fn get_upload_session_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, session_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<UploadSession>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    Ok(Json(get_upload_session(&state.db_pool, workspace_id, auth_user.user_id, session_id).await?))
+}
+
+/// Store part `part_number` (1 to `part_count`) of an upload session, sent as the raw request body
+/// with its hex SHA-256 in `X-Checksum-SHA256`. Parts may arrive in any order; sending a part again,
+/// e.g. after a dropped connection, replaces it. Every part but the last is `chunk_size` bytes.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/datasets/upload_sessions/{session_id}/parts/{part_number}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID"),
+        ("part_number" = i32, Path, description = "Part number, from 1"),
+        ("X-Checksum-SHA256" = String, Header, description = "Hex SHA-256 of the part")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Part stored; the session with its new bytes_received", body = UploadSession),
+        (status = 400, description = "No such part, the part has the wrong size, or its checksum does not match"),
+        (status = 404, description = "No such session of this user"),
+        (status = 409, description = "The session is completed or being completed")
+    ),
+    tag = "datasets"
+)]
+pub async fn upload_part_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, session_id, part_number)): Path<(Uuid, Uuid, i32)>,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    part: Bytes,
+) -> Result<Json<UploadSession>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let session = get_upload_session(&state.db_pool, workspace_id, auth_user.user_id, session_id).await?;
+    if session.status != "OPEN" {
+        return Err(DoubledeckerError::Conflict(format!("Upload session is {}", session.status)));
+    }
+    if !(1..=session.part_count).contains(&part_number) {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "Part number must be 1 to {}",
+            session.part_count
+        )));
+    }
+    let expected_len = if part_number == session.part_count {
+        session.total_bytes - session.chunk_size * (session.part_count as i64 - 1)
+    } else {
+        session.chunk_size
+    };
+    if part.len() as i64 != expected_len {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "Part {} must be {} bytes, got {}",
+            part_number,
+            expected_len,
+            part.len()
+        )));
+    }
+    let Some(checksum) = headers.get(PART_CHECKSUM_HEADER).and_then(|v| v.to_str().ok()) else {
+        return Err(DoubledeckerError::BadRequest("X-Checksum-SHA256 header is required".to_string()));
+    };
+    let sha256 = format!("{:x}", Sha256::digest(&part));
+    if !checksum.trim().eq_ignore_ascii_case(&sha256) {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "Part {} does not match its checksum; it was corrupted in transit, send it again",
+            part_number
+        )));
+    }
+
+    let etag = state
+        .uploader
+        .upload_part(&session.staging_key, &session.multipart_upload_id, part_number, part.to_vec())
+        .await?;
+    record_upload_part(&state.db_pool, session.id, part_number, expected_len, &sha256, &etag)
+        .await?
+        .map(Json)
+        .ok_or_else(|| DoubledeckerError::Conflict("Upload session is no longer open".to_string()))
+}
+
+/// Assemble a fully received upload session into a dataset, which is then validated and ingested
+/// like a presigned upload. Completing a completed session again returns the same dataset, and a
+/// completion that stalled for 15 minutes can be retried.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/upload_sessions/{session_id}/complete",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID")
+    ),
+    responses(
+        (status = 200, description = "Dataset created from the uploaded file", body = DatasetResponse),
+        (status = 404, description = "No such session of this user"),
+        (status = 409, description = "Parts are still missing, or the session is being completed")
+    ),
+    tag = "datasets"
+)]
+pub async fn complete_upload_session_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, session_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DatasetResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let session = get_upload_session(&state.db_pool, workspace_id, auth_user.user_id, session_id).await?;
+    if let Some(dataset_id) = session.dataset_id {
+        let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+        return Ok(Json(DatasetResponse::from_dataset(dataset)));
+    }
+    let Some(session) = claim_upload_session(&state.db_pool, session.id, UPLOAD_SESSION_COMPLETING_TIMEOUT_SECS).await?
+    else {
+        return Err(DoubledeckerError::Conflict(format!(
+            "Upload session is {} with {} of {} parts received",
+            session.status,
+            session.received_parts.len(),
+            session.part_count
+        )));
+    };
+
+    let queued = async {
+        // An earlier attempt may have gotten further before it failed or died: the dataset exists,
+        // or S3 assembled the file, after which the upload id is gone
+        if let Ok(dataset) = get_dataset_by_id(&state.db_pool, workspace_id, session.id).await {
+            return Ok(dataset);
+        }
+        if state.uploader.object_size(&session.staging_key).await?.is_none() {
+            let parts = list_upload_session_parts(&state.db_pool, session.id).await?;
+            let parts: Vec<(i32, String)> = parts.into_iter().map(|part| (part.part_number, part.etag)).collect();
+            state
+                .uploader
+                .complete_multipart_upload(&session.staging_key, &session.multipart_upload_id, &parts)
+                .await?;
+        }
+
+        // Like a confirmed presigned upload: the staged file is stored as sent, its Parquet is
+        // encrypted with the uploader's data key, and the ingestion workflow validates it
+        let inference: InferenceOptions = serde_json::from_value(session.inference.0.clone()).unwrap_or_default();
+        let key_id = user_data_key(&state, session.user_id).await?.map(|(key_id, _)| key_id);
+        let mut tx = state.db_pool.begin().await.map_err(db_err)?;
+        let dataset = create_dataset(
+            &mut *tx,
+            session.id,
+            workspace_id,
+            session.distributor_source.clone(),
+            session.filename.clone(),
+            parquet_key(workspace_id, session.id),
+            session.total_bytes,
+            DatasetStatus::Queued,
+            key_id,
+        )
+        .await?;
+        record_dataset_activity(&mut *tx, dataset.id, Some(session.user_id), DatasetAction::Created, None).await?;
+        enqueue_outbox_event(
+            &mut tx,
+            "dataset/uploaded",
+            ingestion_event(workspace_id, dataset.id, &session.staging_key, session.strict, &inference),
+        )
+        .await?;
+        tx.commit().await.map_err(db_err)?;
+        Ok::<_, DoubledeckerError>(dataset)
+    }
+    .await;
+    let dataset = match queued {
+        Ok(dataset) => dataset,
+        Err(e) => {
+            finish_upload_session(&state.db_pool, session.id, None).await?;
+            return Err(e);
+        }
+    };
+    finish_upload_session(&state.db_pool, session.id, Some(dataset.id)).await?;
+
+    dispatch_soon(state.db_pool.clone(), state.inngest_client.clone());
+
+    Ok(Json(DatasetResponse::from_dataset(dataset)))
+}
+
+/// Abandon an upload session and discard the parts stored so far.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/datasets/upload_sessions/{session_id}",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("session_id" = Uuid, Path, description = "Upload session ID")
+    ),
+    responses(
+        (status = 200, description = "Upload session deleted", body = DeleteResponse),
+        (status = 404, description = "No such session of this user"),
+        (status = 409, description = "The session is being completed")
+    ),
+    tag = "datasets"
+)]
+pub async fn delete_upload_session_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, session_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let session = get_upload_session(&state.db_pool, workspace_id, auth_user.user_id, session_id).await?;
+    if !delete_upload_session(&state.db_pool, session.id, UPLOAD_SESSION_COMPLETING_TIMEOUT_SECS).await? {
+        return Err(DoubledeckerError::Conflict("Upload session is being completed".to_string()));
+    }
+    // A completed session's file now belongs to its dataset
+    if session.dataset_id.is_none() {
+        discard_upload_session_file(state.uploader.as_ref(), &session.staging_key, &session.multipart_upload_id).await;
+    }
+    Ok(Json(DeleteResponse { message: "Upload session deleted successfully".to_string() }))
+}
+
+/// Discard what a session stored that never became a dataset: its parts, or the staged file when S3
+/// assembled it before the session failed. Failures are left to the bucket's lifecycle rules.
+async fn discard_upload_session_file(uploader: &dyn ObjectStorage, staging_key: &str, upload_id: &str) {
+    let _ = uploader.abort_multipart_upload(staging_key, upload_id).await;
+    let _ = uploader.delete_file(staging_key).await;
+}
+
+/// Deletes upload sessions abandoned for `UPLOAD_SESSION_IDLE_SECS`, with the parts they stored.
+/// Queued periodically by `workers::maintenance`.
+pub struct UploadSessionCleanupJobHandler {
+    pub pool: PgPool,
+    pub uploader: Arc<dyn ObjectStorage>,
+}
+
+#[async_trait::async_trait]
+impl JobHandler for UploadSessionCleanupJobHandler {
+    async fn run(&self, _job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+        let mut deleted = 0;
+        loop {
+            let sessions =
+                delete_idle_upload_sessions(&self.pool, UPLOAD_SESSION_IDLE_SECS, UPLOAD_SESSION_CLEANUP_BATCH).await?;
+            for (staging_key, upload_id) in &sessions {
+                discard_upload_session_file(self.uploader.as_ref(), staging_key, upload_id).await;
+            }
+            deleted += sessions.len();
+            if (sessions.len() as i64) < UPLOAD_SESSION_CLEANUP_BATCH {
+                return Ok(serde_json::json!({ "deleted_sessions": deleted }));
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        DatasetListParams,
+    ),
+    responses(
+        (status = 200, description = "List workspace datasets", body = PaginatedDatasets),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match")
     ),
     tag = "datasets"
 )]
 pub async fn list_datasets_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(params): ValidatedQuery<DatasetListParams>,
+    if_none_match: IfNoneMatch,
     State(state): State<AppState>,
-) -> Result<Json<PaginatedResponse<DatasetResponse>>, DoubledeckerError> {
+) -> Result<Response, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
 
-    let limit = pagination.effective_limit();
-    let paginated_datasets = get_datasets(&state.db_pool, workspace_id, pagination.cursor, limit).await?;
+    let paginated_datasets = get_datasets(&state.db_pool, workspace_id, auth_user.user_id, &params).await?;
     let responses = PaginatedResponse {
         data: paginated_datasets.data.into_iter().map(DatasetResponse::from_dataset).collect(),
         pagination: paginated_datasets.pagination,
     };
-    Ok(Json(responses))
+    etagged_json(&if_none_match, &responses)
+}
+
+/// Download a dataset's processed Parquet file. Redirects (302) to a short-lived presigned S3 URL,
+/// generated on demand so links never end up in list responses or caches; a file stored encrypted
+/// is decrypted and sent by the server instead (200). The file is saved under the uploaded
+/// filename with a `.parquet` extension rather than its storage key. Members below
+/// `RESTRICTED_COLUMNS_MIN_ROLE` cannot download from workspaces with column restrictions, since the
+/// raw file would expose the restricted columns.
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/download",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID"),
+        DatasetDownloadParams
+    ),
+    responses(
+        (status = 200, description = "The decrypted file, for a dataset stored encrypted", content_type = "application/vnd.apache.parquet"),
+        (status = 302, description = "Redirect to a presigned download URL"),
+        (status = 400, description = "Dataset is not READY"),
+        (status = 422, description = "expires_in out of range"),
+        (status = 403, description = "Workspace has column restrictions that apply to the caller"),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "datasets"
+)]
+pub async fn download_dataset_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<DatasetDownloadParams>,
+) -> Result<Response, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(
+            "Only READY datasets can be downloaded".to_string(),
+        ));
+    }
+    if role < RESTRICTED_COLUMNS_MIN_ROLE
+        && !list_workspace_column_restrictions(&state.db_pool, workspace_id).await?.is_empty()
+    {
+        return Err(DoubledeckerError::Forbidden(
+            "This workspace restricts columns; query the dataset or export the results instead".to_string(),
+        ));
+    }
+
+    let filename = download_filename(&dataset.filename, "parquet");
+    if let Some(data_key) = dataset_data_key(&state.db_pool, dataset.encryption_key_id).await? {
+        let file = decrypt_file(&data_key, &state.uploader.download_csv(&dataset.s3_parquet_key).await?)?;
+        let _ = record_dataset_activity(&state.db_pool, dataset.id, Some(auth_user.user_id), DatasetAction::Downloaded, None)
+            .await;
+        return Response::builder()
+            .header(header::CONTENT_TYPE, "application/vnd.apache.parquet")
+            .header(header::CONTENT_DISPOSITION, attachment_disposition(&filename))
+            .header(header::CACHE_CONTROL, "no-store")
+            .body(Body::from(file))
+            .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)));
+    }
+
+    let expires_in = params.expires_in.unwrap_or(presign_config().download_expiry_secs);
+    let url = state
+        .uploader
+        .generate_presigned_url(&dataset.s3_parquet_key, Some(expires_in), Some(&filename))
+        .await?;
+    let _ = record_dataset_activity(&state.db_pool, dataset.id, Some(auth_user.user_id), DatasetAction::Downloaded, None)
+        .await;
+
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, url)
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::empty())
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)))
+}
+
+/// Add a dataset to the caller's favorites, listed with `?favorites=true`. Favorites are per user,
+/// so any member can favorite a dataset of the workspace.
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/favorite",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Dataset favorited", body = FavoriteResponse),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "datasets"
+)]
+pub async fn favorite_dataset_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    set_favorite(&state, auth_user.user_id, workspace_id, dataset_id, true).await
+}
+
+/// Remove a dataset from the caller's favorites.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/favorite",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Dataset unfavorited", body = FavoriteResponse),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "datasets"
+)]
+pub async fn unfavorite_dataset_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    set_favorite(&state, auth_user.user_id, workspace_id, dataset_id, false).await
+}
+
+async fn set_favorite(
+    state: &AppState,
+    user_id: Uuid,
+    workspace_id: Uuid,
+    dataset_id: Uuid,
+    favorite: bool,
+) -> Result<Json<FavoriteResponse>, DoubledeckerError> {
+    verify_workspace_access(state, workspace_id, user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    set_dataset_favorite(&state.db_pool, user_id, dataset.id, favorite).await?;
+    Ok(Json(FavoriteResponse { id: dataset.id, favorite }))
+}
+
+/// S3 deletes issued at once by a bulk delete.
+const BULK_DELETE_CONCURRENCY: usize = 8;
+
+/// Delete several datasets at once. Stored files are removed first, concurrently; the rows of every
+/// dataset whose files are gone are then deleted in one transaction. A dataset whose file could not be
+/// removed is kept and reported as failed, so it can be retried.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/bulk_delete",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID")
+    ),
+    request_body = BulkDeleteRequest,
+    responses(
+        (status = 200, description = "Per-dataset deletion results", body = BulkDeleteResponse),
+        (status = 422, description = "No ids, or more than 100")
+    ),
+    tag = "datasets"
+)]
+pub async fn bulk_delete_datasets_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<BulkDeleteRequest>,
+) -> Result<Json<BulkDeleteResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let mut requested = payload.ids;
+    let mut seen = HashSet::new();
+    requested.retain(|id| seen.insert(*id));
+
+    let datasets = get_datasets_by_ids(&state.db_pool, workspace_id, &requested).await?;
+
+    let uploader = &state.uploader;
+    let storage_results: HashMap<Uuid, Result<(), DoubledeckerError>> = stream::iter(datasets)
+        .map(|dataset| async move {
+            let mut result = uploader.delete_file(&dataset.s3_parquet_key).await;
+            if result.is_ok() && dataset.status == DatasetStatus::Quarantined.as_str() {
+                result = uploader.delete_file(&quarantine_key(workspace_id, dataset.id)).await;
+            }
+            (dataset.id, result)
+        })
+        .buffer_unordered(BULK_DELETE_CONCURRENCY)
+        .collect()
+        .await;
+
+    let removable: Vec<Uuid> = storage_results
+        .iter()
+        .filter(|(_, result)| result.is_ok())
+        .map(|(id, _)| *id)
+        .collect();
+    let db_err = |e: sqlx::Error| DoubledeckerError::DatabaseError(e.to_string());
+    let mut tx = state.db_pool.begin().await.map_err(db_err)?;
+    let deleted: HashSet<Uuid> = delete_datasets(&mut *tx, workspace_id, &removable).await?.into_iter().collect();
+    tx.commit().await.map_err(db_err)?;
+
+    let results: Vec<BulkDeleteItemResult> = requested
+        .into_iter()
+        .map(|id| {
+            let error = match storage_results.get(&id) {
+                None => Some("Dataset not found".to_string()),
+                Some(Err(e)) => Some(format!("Failed to delete stored file: {}", e)),
+                Some(Ok(())) if !deleted.contains(&id) => Some("Dataset not found".to_string()),
+                Some(Ok(())) => None,
+            };
+            BulkDeleteItemResult { id, deleted: error.is_none(), error }
+        })
+        .collect();
+
+    Ok(Json(BulkDeleteResponse {
+        deleted_count: deleted.len(),
+        results,
+    }))
+}
+
+/// Mark a dataset public-read. Returns the dataset with its (new or existing) share token.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/public",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Dataset shared publicly", body = DatasetResponse)
+    ),
+    tag = "datasets"
+)]
+pub async fn share_dataset_public_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DatasetResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.public_token.is_some() {
+        return Ok(Json(DatasetResponse::from_dataset(dataset)));
+    }
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(
+            "Only READY datasets can be shared publicly".to_string(),
+        ));
+    }
+
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let dataset = set_dataset_public_token(&state.db_pool, workspace_id, dataset_id, Some(&token)).await?;
+    record_dataset_activity(&state.db_pool, dataset.id, Some(auth_user.user_id), DatasetAction::Shared, None).await?;
+    Ok(Json(DatasetResponse::from_dataset(dataset)))
+}
+
+/// Revoke public access. The old token stops working immediately.
+#[utoipa::path(
+    delete,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/public",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Public access revoked", body = DatasetResponse)
+    ),
+    tag = "datasets"
+)]
+pub async fn unshare_dataset_public_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<DatasetResponse>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let dataset = set_dataset_public_token(&state.db_pool, workspace_id, dataset_id, None).await?;
+    record_dataset_activity(&state.db_pool, dataset.id, Some(auth_user.user_id), DatasetAction::Unshared, None).await?;
+    Ok(Json(DatasetResponse::from_dataset(dataset)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/columns",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Dataset columns with their restriction flag", body = Vec<DatasetColumn>)
+    ),
+    tag = "datasets"
+)]
+pub async fn list_dataset_columns_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<DatasetColumn>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    let restrictions = list_dataset_column_restrictions(&state.db_pool, dataset.id).await?;
+    Ok(Json(dataset_columns(&restrictions)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/columns",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    request_body = UpdateColumnRestrictionsRequest,
+    responses(
+        (status = 200, description = "Column restrictions updated", body = Vec<DatasetColumn>),
+        (status = 400, description = "A hash mask on a server without MASK_HASH_KEY", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "datasets"
+)]
+pub async fn update_dataset_columns_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<UpdateColumnRestrictionsRequest>,
+) -> Result<Json<Vec<DatasetColumn>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    let restrictions: Vec<ColumnRestriction> = payload
+        .restricted_columns
+        .iter()
+        .map(|column| (column.clone(), MaskMode::Hide))
+        .chain(payload.masked_columns.iter().map(|m| (m.column.clone(), m.mode)))
+        .map(|(column_name, mode)| ColumnRestriction {
+            column_name,
+            mode: mode.as_db_str().to_string(),
+        })
+        .collect();
+
+    if mask_hash_key().is_none() && payload.masked_columns.iter().any(|m| m.mode == MaskMode::Hash) {
+        return Err(DoubledeckerError::BadRequest(
+            "The hash mask needs MASK_HASH_KEY (at least 32 bytes) to be set on the server".to_string(),
+        ));
+    }
+    let schema = unified_royalty_schema();
+    for (i, restriction) in restrictions.iter().enumerate() {
+        if schema.field_with_name(&restriction.column_name).is_err() {
+            return Err(DoubledeckerError::ColumnNotFound(restriction.column_name.clone()));
+        }
+        if restrictions[..i].iter().any(|r| r.column_name == restriction.column_name) {
+            return Err(DoubledeckerError::BadRequest(format!(
+                "Column '{}' is listed more than once",
+                restriction.column_name
+            )));
+        }
+    }
+
+    replace_dataset_column_restrictions(&state.db_pool, dataset.id, &restrictions).await?;
+    let restrictions = list_dataset_column_restrictions(&state.db_pool, dataset.id).await?;
+    Ok(Json(dataset_columns(&restrictions)))
+}
+
+/// Number of rows sampled by the PII scan.
+const PII_SCAN_SAMPLE_ROWS: usize = 1000;
+
+/// Scan a sample of a dataset for columns that look like emails, phone numbers or SSNs
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/pii-scan",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Columns with PII-like values, most likely first", body = Vec<PiiFinding>)
+    ),
+    tag = "datasets"
+)]
+pub async fn scan_dataset_pii_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PiiFinding>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(
+            "Only READY datasets can be scanned".to_string(),
+        ));
+    }
+
+    let scope = QueryScope {
+        dataset_ids: Some(vec![dataset.id]),
+        ..QueryScope::default()
+    };
+    let sql = format!("SELECT * FROM royalty_data LIMIT {}", PII_SCAN_SAMPLE_ROWS);
+    let batches = state
+        .engine
+        .execute_scoped_analytics(workspace_id, &scope, &sql)
+        .await?;
+
+    Ok(Json(scan_batches_for_pii(&batches)))
+}
+
+/// Starter queries for a ready dataset, picked from the columns it fills and the caller can see
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/suggestions",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID")
+    ),
+    responses(
+        (status = 200, description = "Queries to try on the dataset", body = Vec<QuerySuggestion>),
+        (status = 400, description = "The dataset is not ready")
+    ),
+    tag = "datasets"
+)]
+pub async fn suggest_dataset_queries_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<QuerySuggestion>>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(
+            "Queries can only be suggested for READY datasets".to_string(),
+        ));
+    }
+
+    let scope = QueryScope {
+        dataset_ids: Some(vec![dataset.id]),
+        ..query_scope_for_role(&state, workspace_id, role).await?
+    };
+    // Hidden columns are not in the caller's view of the table, so nothing is suggested over them
+    let visible = state.engine.plan_schema(workspace_id, &scope, "SELECT * FROM royalty_data").await?;
+    let columns: Vec<&String> = visible.fields().iter().map(|f| f.name()).collect();
+    let counts: Vec<String> = columns.iter().map(|c| format!("COUNT(DISTINCT {c}) AS {c}")).collect();
+    let sql = format!("SELECT {} FROM royalty_data", counts.join(", "));
+    let batches = state.engine.execute_scoped_analytics(workspace_id, &scope, &sql).await?;
+
+    let mut distinct_values = HashMap::new();
+    if let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) {
+        for (column, array) in columns.iter().zip(batch.columns()) {
+            if let Some(counts) = array.as_any().downcast_ref::<Int64Array>() {
+                distinct_values.insert(column.to_string(), counts.value(0));
+            }
+        }
+    }
+    Ok(Json(starter_queries(dataset.id, &distinct_values)))
+}
+
+/// Distinct values of one column of a ready dataset, optionally only those starting with `search`,
+/// so query builders can fill filter dropdowns without a GROUP BY of their own
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/columns/{column}/values",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID"),
+        ("column" = String, Path, description = "Column name"),
+        ColumnValuesParams
+    ),
+    responses(
+        (status = 200, description = "Distinct values of the column", body = ColumnValuesResponse),
+        (status = 400, description = "The dataset is not ready"),
+        (status = 404, description = "Dataset not found, or no such column visible to the caller"),
+        (status = 422, description = "limit out of range or search too long")
+    ),
+    tag = "datasets"
+)]
+pub async fn list_column_values_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id, column)): Path<(Uuid, Uuid, String)>,
+    State(state): State<AppState>,
+    ValidatedQuery(params): ValidatedQuery<ColumnValuesParams>,
+) -> Result<Json<ColumnValuesResponse>, DoubledeckerError> {
+    let role = verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    if dataset.status != DatasetStatus::Ready.as_str() {
+        return Err(DoubledeckerError::BadRequest(
+            "Column values can only be listed for READY datasets".to_string(),
+        ));
+    }
+
+    let scope = QueryScope {
+        dataset_ids: Some(vec![dataset.id]),
+        ..query_scope_for_role(&state, workspace_id, role).await?
+    };
+    // Only columns in the caller's view of the table are listed, which also keeps the name safe to quote
+    let visible = state.engine.plan_schema(workspace_id, &scope, "SELECT * FROM royalty_data").await?;
+    if visible.field_with_name(&column).is_err() || column.contains('"') {
+        return Err(DoubledeckerError::ColumnNotFound(column));
+    }
+
+    let limit = params.limit.unwrap_or(DEFAULT_COLUMN_VALUES);
+    let mut filter = format!("\"{}\" IS NOT NULL", column);
+    if let Some(search) = params.search.as_deref().filter(|s| !s.is_empty()) {
+        filter.push_str(&format!(
+            " AND starts_with(lower(CAST(\"{}\" AS VARCHAR)), '{}')",
+            column,
+            search.to_lowercase().replace('\'', "''")
+        ));
+    }
+    // One row past the limit tells whether the list was cut short
+    let sql = format!(
+        "SELECT DISTINCT \"{c}\" FROM royalty_data WHERE {filter} ORDER BY \"{c}\" LIMIT {}",
+        limit + 1,
+        c = column
+    );
+    let batches = state.engine.execute_scoped_analytics(workspace_id, &scope, &sql).await?;
+
+    let mut values = Vec::new();
+    for batch in &batches {
+        values.extend(array_to_json_values(batch.column(0))?);
+    }
+    let truncated = values.len() > limit;
+    values.truncate(limit);
+    Ok(Json(ColumnValuesResponse { column, values, truncated }))
+}
+
+/// What has happened to a dataset, newest first: who created, queried, downloaded or shared it,
+/// and the pipeline runs over it
+#[utoipa::path(
+    get,
+    path = "/api/workspaces/{workspace_id}/datasets/{dataset_id}/activity",
+    params(
+        ("workspace_id" = Uuid, Path, description = "Workspace ID"),
+        ("dataset_id" = Uuid, Path, description = "Dataset ID"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "Activity of the dataset", body = PaginatedDatasetActivity),
+        (status = 404, description = "Dataset not found")
+    ),
+    tag = "datasets"
+)]
+pub async fn list_dataset_activity_handler(
+    auth_user: AuthenticatedUser,
+    Path((workspace_id, dataset_id)): Path<(Uuid, Uuid)>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
+    State(state): State<AppState>,
+) -> Result<Json<PaginatedResponse<DatasetActivity>>, DoubledeckerError> {
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let dataset = get_dataset_by_id(&state.db_pool, workspace_id, dataset_id).await?;
+    let limit = pagination.effective_limit();
+    let activity = list_dataset_activity(&state.db_pool, dataset.id, pagination.cursor, limit).await?;
+    Ok(Json(activity))
+}
+
+fn dataset_columns(restrictions: &[ColumnRestriction]) -> Vec<DatasetColumn> {
+    unified_royalty_schema()
+        .fields()
+        .iter()
+        .map(|f| {
+            let mask = restrictions
+                .iter()
+                .find(|r| &r.column_name == f.name())
+                .map(|r| MaskMode::from_db_str(&r.mode));
+            DatasetColumn {
+                name: f.name().clone(),
+                data_type: f.data_type().to_string(),
+                restricted: mask.is_some(),
+                mask,
+            }
+        })
+        .collect()
 }
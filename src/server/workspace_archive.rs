@@ -0,0 +1,499 @@
+use crate::db::models::{DatasetAction, DatasetStatus, WorkspaceRole};
+use crate::db::queries::{
+    create_dataset, create_pipeline, create_workspace, dataset_data_key, delete_datasets, delete_pipeline,
+    delete_workspace, get_workspace, list_workspace_datasets, list_workspace_pipelines, record_dataset_activity,
+    set_dataset_inferred_schema, set_dataset_source_columns, update_dataset_status,
+};
+use crate::config::upload_limits;
+use crate::normalization::unified_royalty_schema;
+use crate::server::dtos::pipelines::PipelineRequest;
+use crate::server::dtos::workspaces::*;
+use crate::server::extractors::verify_workspace_access;
+use crate::server::middleware::AuthenticatedUser;
+use crate::server::state::AppState;
+use crate::server::uploads::user_data_key;
+use crate::server::validation::{Validate, ValidatedJson, check_name};
+use crate::utils::crypto::{decrypt_file, encrypt_file};
+use crate::utils::error::DoubledeckerError;
+use crate::utils::helpers::filename_slug;
+use crate::utils::s3::parquet_key;
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Multipart, Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::Response;
+use bytes::Bytes;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::SimpleFileOptions;
+
+/// Export a workspace as a zip archive for backup or for moving it to another instance:
+/// `manifest.json`, `datasets.json` (the uploads and what processing learned about them) and
+/// `pipelines.json` (the saved queries). With `include_files`, `files/` also holds the processed
+/// Parquet file of every READY dataset. The archive is assembled in a temporary file and streamed.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/{workspace_id}/export",
+    params(("workspace_id" = Uuid, Path, description = "Workspace ID")),
+    request_body = ExportWorkspaceRequest,
+    responses(
+        (status = 200, description = "Workspace archive", content_type = "application/zip"),
+        (status = 403, description = "Caller is not an admin of the workspace", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "workspaces"
+)]
+pub async fn export_workspace_handler(
+    auth_user: AuthenticatedUser,
+    Path(workspace_id): Path<Uuid>,
+    State(state): State<AppState>,
+    ValidatedJson(payload): ValidatedJson<ExportWorkspaceRequest>,
+) -> Result<Response, DoubledeckerError> {
+    // The Parquet files hold every column, so the archive is for those who see unrestricted data
+    verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+
+    let workspace = get_workspace(&state.db_pool, workspace_id).await?;
+    let (datasets, pipelines) = tokio::try_join!(
+        list_workspace_datasets(&state.db_pool, workspace_id),
+        list_workspace_pipelines(&state.db_pool, workspace_id),
+    )?;
+
+    let manifest = WorkspaceArchiveManifest {
+        version: WORKSPACE_ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now(),
+        workspace_id,
+        workspace_name: workspace.name.clone(),
+        includes_files: payload.include_files,
+    };
+    let mut archive = ArchiveWriter::new().await?;
+
+    let mut archived = Vec::with_capacity(datasets.len());
+    for dataset in datasets {
+        let file = if payload.include_files && dataset.status == DatasetStatus::Ready.as_str() {
+            let path = format!("files/{}.parquet", dataset.id);
+            let mut content = state.uploader.download_csv(&dataset.s3_parquet_key).await?;
+            // Archives leave the server, so files are exported decrypted
+            if let Some(data_key) = dataset_data_key(&state.db_pool, dataset.encryption_key_id).await? {
+                content = decrypt_file(&data_key, &content)?;
+            }
+            // Parquet is compressed already
+            archive.add(path.clone(), content, CompressionMethod::Stored).await?;
+            Some(path)
+        } else {
+            None
+        };
+        archived.push(ArchivedDataset::from_dataset(dataset, file));
+    }
+    let pipelines: Vec<ArchivedPipeline> = pipelines.into_iter().map(ArchivedPipeline::from).collect();
+
+    archive.add_json("manifest.json", &manifest).await?;
+    archive.add_json("datasets.json", &archived).await?;
+    archive.add_json("pipelines.json", &pipelines).await?;
+    let file = archive.finish().await?;
+
+    let filename = format!("{}-{}.zip", filename_slug(&workspace.name, "workspace"), manifest.exported_at.format("%Y%m%d"));
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename))
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(file))))
+        .map_err(|e| DoubledeckerError::Internal(format!("Failed to build response: {}", e)))
+}
+
+/// Most bytes an archive may unpack to, so a small zip cannot expand without bound in memory.
+const MAX_IMPORT_UNPACKED_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Import a workspace archive made by the export endpoint, as multipart form data: the zip in
+/// `archive`, and optionally `workspace_id` to import into an existing workspace (instead of a new
+/// one named `name`, or after the exported workspace) and `on_conflict` (`rename` or `skip`) for
+/// datasets and pipelines whose names are taken. Datasets are recreated from the archive's Parquet
+/// files, stored under the target workspace and READY right away; datasets without a file in the
+/// archive, or over the upload limits, are skipped. The archive is checked in full before anything
+/// is created, and if the import fails partway what it created is removed again.
+#[utoipa::path(
+    post,
+    path = "/api/workspaces/import",
+    responses(
+        (status = 200, description = "What was imported, renamed or skipped", body = ImportWorkspaceResponse),
+        (status = 400, description = "Not a readable workspace archive", body = crate::server::dtos::common::ErrorResponse),
+        (status = 403, description = "Caller is not an admin of the target workspace", body = crate::server::dtos::common::ErrorResponse),
+        (status = 413, description = "Archive is over the upload limit (`UPLOAD_BODY_LIMIT_MB`)", body = crate::server::dtos::common::ErrorResponse)
+    ),
+    tag = "workspaces"
+)]
+pub async fn import_workspace_handler(
+    auth_user: AuthenticatedUser,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Json<ImportWorkspaceResponse>, DoubledeckerError> {
+    let mut archive: Option<Vec<u8>> = None;
+    let mut target_workspace: Option<Uuid> = None;
+    let mut name: Option<String> = None;
+    let mut on_conflict = ImportConflictPolicy::default();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            DoubledeckerError::from(e)
+        } else {
+            DoubledeckerError::BadRequest(format!("Multipart error: {}", e))
+        }
+    })? {
+        match field.name().unwrap_or("") {
+            "archive" | "file" => archive = Some(field.bytes().await?.to_vec()),
+            "workspace_id" => {
+                let text = field.text().await?;
+                let id = Uuid::parse_str(text.trim())
+                    .map_err(|_| DoubledeckerError::BadRequest("workspace_id must be a UUID".to_string()))?;
+                target_workspace = Some(id);
+            }
+            "name" => name = Some(field.text().await?.trim().to_string()),
+            "on_conflict" => {
+                let text = field.text().await?;
+                on_conflict = serde_json::from_value(serde_json::Value::String(text.trim().to_lowercase()))
+                    .map_err(|_| DoubledeckerError::BadRequest("on_conflict must be rename or skip".to_string()))?;
+            }
+            _ => {}
+        }
+    }
+    let archive = archive.ok_or_else(|| DoubledeckerError::BadRequest("No archive uploaded".to_string()))?;
+    let archive = blocking(move || read_archive(archive)).await?;
+
+    let workspace = match target_workspace {
+        Some(workspace_id) => {
+            verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
+            get_workspace(&state.db_pool, workspace_id).await?
+        }
+        None => {
+            let name = name.filter(|n| !n.is_empty()).unwrap_or_else(|| archive.manifest.workspace_name.clone());
+            let mut errors = Vec::new();
+            check_name(&mut errors, "name", &name);
+            if !errors.is_empty() {
+                return Err(DoubledeckerError::Validation(errors));
+            }
+            create_workspace(&state.db_pool, auth_user.user_id, name).await?
+        }
+    };
+    let created_workspace = target_workspace.is_none();
+
+    // Whatever was created is removed again if the import fails partway
+    let mut created = ImportedItems::default();
+    let imported = async {
+        let (existing_datasets, existing_pipelines) = tokio::try_join!(
+            list_workspace_datasets(&state.db_pool, workspace.id),
+            list_workspace_pipelines(&state.db_pool, workspace.id),
+        )?;
+        let mut pipeline_names: HashSet<String> = existing_pipelines.iter().map(|p| p.name.to_lowercase()).collect();
+        let mut filenames: HashSet<String> = existing_datasets.iter().map(|d| d.filename.to_lowercase()).collect();
+
+        let mut pipelines = Vec::with_capacity(archive.pipelines.len());
+        for pipeline in archive.pipelines {
+            let request = PipelineRequest {
+                name: pipeline.name,
+                description: pipeline.description,
+                dataset_pattern: pipeline.dataset_pattern,
+                query: pipeline.query,
+            };
+            if let Some(error) = request.validate().into_iter().next() {
+                pipelines.push(ImportItemResult::skipped(request.name, format!("{}: {}", error.field, error.message)));
+                continue;
+            }
+            let Some((imported_as, outcome)) = claim_name(&request.name, &mut pipeline_names, on_conflict) else {
+                pipelines.push(ImportItemResult::skipped(request.name, "A pipeline with this name already exists"));
+                continue;
+            };
+            let pipeline = create_pipeline(
+                &state.db_pool,
+                workspace.id,
+                &imported_as,
+                request.description.as_deref(),
+                request.dataset_pattern.as_deref(),
+                &request.query,
+                auth_user.user_id,
+            )
+            .await?;
+            created.pipelines.push(pipeline.id);
+            pipelines.push(ImportItemResult {
+                name: request.name,
+                imported_as: Some(imported_as),
+                id: Some(pipeline.id),
+                outcome,
+                reason: None,
+            });
+        }
+
+        // Imported files are stored encrypted with the importing user's data key
+        let data_key = user_data_key(&state, auth_user.user_id).await?;
+        let mut datasets = Vec::with_capacity(archive.datasets.len());
+        for (entry, file) in archive.datasets {
+            let Some(file) = file else {
+                datasets.push(ImportItemResult::skipped(entry.filename, "The archive has no file for this dataset"));
+                continue;
+            };
+            // Held to the same limits as an upload
+            let limits = upload_limits();
+            let within_limits = limits
+                .check_file_size(file.content.len() as u64)
+                .and_then(|_| limits.check_shape(entry.source_columns.len() as u64, file.row_count.max(0) as u64));
+            if let Err(e) = within_limits {
+                datasets.push(ImportItemResult::skipped(entry.filename, e.message()));
+                continue;
+            }
+            let Some((imported_as, outcome)) = claim_name(&entry.filename, &mut filenames, on_conflict) else {
+                datasets.push(ImportItemResult::skipped(entry.filename, "A dataset with this filename already exists"));
+                continue;
+            };
+
+            let dataset_id = Uuid::new_v4();
+            let key = parquet_key(workspace.id, dataset_id);
+            let size = file.content.len() as i64;
+            let content = match &data_key {
+                Some((_, data_key)) => encrypt_file(data_key, &file.content)?,
+                None => file.content.to_vec(),
+            };
+            state.uploader.upload_parquet(&key, content).await?;
+            created.files.push(key.clone());
+            create_dataset(
+                &state.db_pool,
+                dataset_id,
+                workspace.id,
+                entry.distributor_source,
+                imported_as.clone(),
+                key,
+                size,
+                DatasetStatus::Ready,
+                data_key.as_ref().map(|(key_id, _)| *key_id),
+            )
+            .await?;
+            created.datasets.push(dataset_id);
+            update_dataset_status(&state.db_pool, dataset_id, DatasetStatus::Ready, Some(file.row_count), None).await?;
+            let details = serde_json::json!({ "imported_from": entry.filename });
+            let user_id = Some(auth_user.user_id);
+            record_dataset_activity(&state.db_pool, dataset_id, user_id, DatasetAction::Created, Some(details)).await?;
+            set_dataset_source_columns(&state.db_pool, dataset_id, &entry.source_columns).await?;
+            if let Some(schema) = &entry.inferred_schema {
+                set_dataset_inferred_schema(&state.db_pool, dataset_id, schema).await?;
+            }
+            datasets.push(ImportItemResult {
+                name: entry.filename,
+                imported_as: Some(imported_as),
+                id: Some(dataset_id),
+                outcome,
+                reason: None,
+            });
+        }
+        Ok::<_, DoubledeckerError>((datasets, pipelines))
+    }
+    .await;
+    let (datasets, pipelines) = match imported {
+        Ok(imported) => imported,
+        Err(e) => {
+            created.remove(&state, workspace.id, created_workspace).await;
+            return Err(e);
+        }
+    };
+
+    let workspace = get_workspace(&state.db_pool, workspace.id).await?;
+    Ok(Json(ImportWorkspaceResponse { workspace, datasets, pipelines }))
+}
+
+/// What an import has created so far.
+#[derive(Default)]
+struct ImportedItems {
+    pipelines: Vec<Uuid>,
+    datasets: Vec<Uuid>,
+    files: Vec<String>,
+}
+
+impl ImportedItems {
+    /// Undo a failed import, best effort: delete what it created, and the workspace if it made one.
+    async fn remove(&self, state: &AppState, workspace_id: Uuid, created_workspace: bool) {
+        for pipeline_id in &self.pipelines {
+            let _ = delete_pipeline(&state.db_pool, workspace_id, *pipeline_id).await;
+        }
+        let _ = delete_datasets(&state.db_pool, workspace_id, &self.datasets).await;
+        for key in &self.files {
+            let _ = state.uploader.delete_file(key).await;
+        }
+        if created_workspace {
+            let _ = delete_workspace(&state.db_pool, workspace_id).await;
+        }
+    }
+}
+
+/// Contents of a workspace archive, read and checked.
+struct ImportArchive {
+    manifest: WorkspaceArchiveManifest,
+    datasets: Vec<(ArchivedDataset, Option<ArchivedFile>)>,
+    pipelines: Vec<ArchivedPipeline>,
+}
+
+/// A dataset's Parquet file from an archive, with its row count.
+struct ArchivedFile {
+    content: Bytes,
+    row_count: i64,
+}
+
+fn read_archive(bytes: Vec<u8>) -> Result<ImportArchive, DoubledeckerError> {
+    let invalid = |message: String| DoubledeckerError::BadRequest(format!("Invalid workspace archive: {}", message));
+    let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| invalid(e.to_string()))?;
+    let mut budget = MAX_IMPORT_UNPACKED_BYTES;
+
+    let manifest: WorkspaceArchiveManifest = read_json(&mut zip, "manifest.json", &mut budget)?;
+    if manifest.version > WORKSPACE_ARCHIVE_VERSION {
+        return Err(invalid(format!(
+            "version {} is newer than this server supports ({})",
+            manifest.version, WORKSPACE_ARCHIVE_VERSION
+        )));
+    }
+    let entries: Vec<ArchivedDataset> = read_json(&mut zip, "datasets.json", &mut budget)?;
+    let pipelines: Vec<ArchivedPipeline> = read_json(&mut zip, "pipelines.json", &mut budget)?;
+
+    let schema = unified_royalty_schema();
+    let mut files: HashMap<String, ArchivedFile> = HashMap::new();
+    for path in entries.iter().filter_map(|d| d.file.clone()) {
+        let content = Bytes::from(read_entry(&mut zip, &path, &mut budget)?);
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(content.clone())
+            .map_err(|e| invalid(format!("{} is not a Parquet file: {}", path, e)))?;
+        // Only files in the unified layout can be queried alongside the workspace's other datasets
+        let columns = |s: &arrow::datatypes::Schema| {
+            s.fields().iter().map(|f| (f.name().clone(), f.data_type().clone())).collect::<Vec<_>>()
+        };
+        if columns(reader.schema()) != columns(&schema) {
+            return Err(invalid(format!("{} does not have the royalty data columns", path)));
+        }
+        let row_count = reader.metadata().file_metadata().num_rows();
+        files.insert(path, ArchivedFile { content, row_count });
+    }
+
+    let datasets = entries
+        .into_iter()
+        .map(|entry| {
+            let file = entry.file.as_ref().and_then(|path| files.remove(path));
+            (entry, file)
+        })
+        .collect();
+    Ok(ImportArchive { manifest, datasets, pipelines })
+}
+
+fn read_json<T: DeserializeOwned>(
+    zip: &mut zip::ZipArchive<Cursor<Vec<u8>>>,
+    path: &str,
+    budget: &mut u64,
+) -> Result<T, DoubledeckerError> {
+    let content = read_entry(zip, path, budget)?;
+    serde_json::from_slice(&content)
+        .map_err(|e| DoubledeckerError::BadRequest(format!("Invalid workspace archive: {}: {}", path, e)))
+}
+
+/// Read one archive entry, counting what it unpacks to against `budget`.
+fn read_entry(zip: &mut zip::ZipArchive<Cursor<Vec<u8>>>, path: &str, budget: &mut u64) -> Result<Vec<u8>, DoubledeckerError> {
+    let invalid = |message: String| DoubledeckerError::BadRequest(format!("Invalid workspace archive: {}", message));
+    let entry = zip.by_name(path).map_err(|_| invalid(format!("{} is missing", path)))?;
+    let mut content = Vec::new();
+    entry.take(*budget + 1).read_to_end(&mut content).map_err(|e| invalid(format!("{}: {}", path, e)))?;
+    if content.len() as u64 > *budget {
+        return Err(invalid(format!("unpacks to more than {} MB", MAX_IMPORT_UNPACKED_BYTES / (1024 * 1024))));
+    }
+    *budget -= content.len() as u64;
+    Ok(content)
+}
+
+/// Reserve a name in `taken` (lowercased), renaming on conflict when the policy allows. Returns the
+/// name to use and whether it was renamed, or `None` to skip the item.
+fn claim_name(name: &str, taken: &mut HashSet<String>, policy: ImportConflictPolicy) -> Option<(String, ImportOutcome)> {
+    if taken.insert(name.to_lowercase()) {
+        return Some((name.to_string(), ImportOutcome::Imported));
+    }
+    if policy == ImportConflictPolicy::Skip {
+        return None;
+    }
+    // `june.csv` becomes `june (2).csv`, `june (3).csv`, ...
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (2..)
+        .map(|n| format!("{} ({}){}", stem, n, extension))
+        .find(|candidate| taken.insert(candidate.to_lowercase()))
+        .map(|candidate| (candidate, ImportOutcome::Renamed))
+}
+
+/// Zip archive in an anonymous temporary file, written on the blocking pool.
+struct ArchiveWriter {
+    zip: Option<ZipWriter<File>>,
+}
+
+impl ArchiveWriter {
+    async fn new() -> Result<Self, DoubledeckerError> {
+        let file = blocking(|| tempfile::tempfile().map_err(archive_err)).await?;
+        Ok(Self { zip: Some(ZipWriter::new(file)) })
+    }
+
+    async fn add(&mut self, path: String, content: Vec<u8>, method: CompressionMethod) -> Result<(), DoubledeckerError> {
+        let mut zip = self.zip.take().ok_or_else(|| archive_err("archive already finished"))?;
+        let zip = blocking(move || {
+            zip.start_file(path, SimpleFileOptions::default().compression_method(method)).map_err(archive_err)?;
+            zip.write_all(&content).map_err(archive_err)?;
+            Ok(zip)
+        })
+        .await?;
+        self.zip = Some(zip);
+        Ok(())
+    }
+
+    async fn add_json<T: Serialize>(&mut self, path: &str, value: &T) -> Result<(), DoubledeckerError> {
+        let content = serde_json::to_vec_pretty(value).map_err(archive_err)?;
+        self.add(path.to_string(), content, CompressionMethod::Deflated).await
+    }
+
+    /// Complete the archive and return its file, rewound for reading.
+    async fn finish(mut self) -> Result<File, DoubledeckerError> {
+        let zip = self.zip.take().ok_or_else(|| archive_err("archive already finished"))?;
+        blocking(move || {
+            let mut file = zip.finish().map_err(archive_err)?;
+            file.seek(SeekFrom::Start(0)).map_err(archive_err)?;
+            Ok(file)
+        })
+        .await
+    }
+}
+
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, DoubledeckerError> + Send + 'static,
+) -> Result<T, DoubledeckerError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| DoubledeckerError::Internal(format!("Archive task failed: {}", e)))?
+}
+
+fn archive_err(e: impl std::fmt::Display) -> DoubledeckerError {
+    DoubledeckerError::Internal(format!("Workspace archive error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflicting_names_renamed_or_skipped() {
+        let mut taken: HashSet<String> = ["june.csv".to_string(), "june (2).csv".to_string()].into();
+        assert_eq!(
+            claim_name("July.csv", &mut taken, ImportConflictPolicy::Rename),
+            Some(("July.csv".to_string(), ImportOutcome::Imported))
+        );
+        assert_eq!(
+            claim_name("JUNE.csv", &mut taken, ImportConflictPolicy::Rename),
+            Some(("JUNE (3).csv".to_string(), ImportOutcome::Renamed))
+        );
+        assert_eq!(
+            claim_name("Top tracks", &mut HashSet::from(["top tracks".to_string()]), ImportConflictPolicy::Rename),
+            Some(("Top tracks (2)".to_string(), ImportOutcome::Renamed))
+        );
+        assert_eq!(claim_name("june.csv", &mut taken, ImportConflictPolicy::Skip), None);
+    }
+}
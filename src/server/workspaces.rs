@@ -6,9 +6,10 @@ use crate::db::queries::{
 use crate::server::dtos::DeleteResponse;
 use crate::server::extractors::verify_workspace_access;
 use crate::server::middleware::AuthenticatedUser;
+use crate::server::validation::{ValidatedJson, ValidatedQuery};
 use crate::server::state::AppState;
 use crate::utils::error::DoubledeckerError;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Path, State};
 use axum::Json;
 use crate::server::dtos::workspaces::*;
 use uuid::Uuid;
@@ -25,7 +26,7 @@ use uuid::Uuid;
 pub async fn create_workspace_handler(
     auth_user: AuthenticatedUser,
     State(state): State<AppState>,
-    Json(payload): Json<CreateWorkspaceRequest>,
+    ValidatedJson(payload): ValidatedJson<CreateWorkspaceRequest>,
 ) -> Result<Json<Workspace>, DoubledeckerError> {
     if payload.name.trim().is_empty() {
         return Err(DoubledeckerError::BadRequest(
@@ -48,7 +49,7 @@ pub async fn create_workspace_handler(
 )]
 pub async fn list_workspaces_handler(
     auth_user: AuthenticatedUser,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<Workspace>>, DoubledeckerError> {
     let limit = pagination.effective_limit();
@@ -72,7 +73,7 @@ pub async fn update_workspace_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<UpdateWorkspaceRequest>,
+    ValidatedJson(payload): ValidatedJson<UpdateWorkspaceRequest>,
 ) -> Result<Json<Workspace>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Owner).await?;
     let updated = update_workspace(&state.db_pool, workspace_id, &payload.name).await?;
@@ -118,7 +119,7 @@ pub async fn add_workspace_member_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
     State(state): State<AppState>,
-    Json(payload): Json<AddWorkspaceMemberRequest>,
+    ValidatedJson(payload): ValidatedJson<AddWorkspaceMemberRequest>,
 ) -> Result<Json<WorkspaceMember>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Admin).await?;
 
@@ -141,7 +142,7 @@ pub async fn add_workspace_member_handler(
 pub async fn list_workspace_members_handler(
     auth_user: AuthenticatedUser,
     Path(workspace_id): Path<Uuid>,
-    Query(pagination): Query<PaginationParams>,
+    ValidatedQuery(pagination): ValidatedQuery<PaginationParams>,
     State(state): State<AppState>,
 ) -> Result<Json<PaginatedResponse<WorkspaceMember>>, DoubledeckerError> {
     verify_workspace_access(&state, workspace_id, auth_user.user_id, WorkspaceRole::Viewer).await?;
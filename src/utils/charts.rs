@@ -0,0 +1,746 @@
+//! Bar, line and pie charts of query results, drawn on the server as SVG or PNG so they can be
+//! embedded where no frontend runs, like emails and chat messages. Both formats share one layout
+//! through `Canvas`; PNG text uses a built-in 5x8 bitmap font, so it needs no font files.
+
+use crate::server::dtos::analytics::ChartKind;
+use crate::utils::error::DoubledeckerError;
+use datafusion::arrow::array::{Array, AsArray, RecordBatch};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Float64Type};
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use std::f64::consts::PI;
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+/// Most bars or pie slices in a chart; pie charts fold slices past `MAX_PIE_SLICES` into "Other".
+pub const MAX_CHART_CATEGORIES: usize = 100;
+/// Most points per line in a line chart.
+pub const MAX_CHART_POINTS: usize = 2000;
+/// Slices drawn in a pie chart, including "Other".
+const MAX_PIE_SLICES: usize = 12;
+/// Labels longer than this are cut short.
+const MAX_LABEL_CHARS: usize = 16;
+
+type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [255, 255, 255];
+const TEXT: Rgb = [51, 51, 51];
+const MUTED: Rgb = [110, 110, 110];
+const GRID: Rgb = [229, 229, 229];
+const AXIS: Rgb = [150, 150, 150];
+const PALETTE: [Rgb; 8] = [
+    [78, 121, 167],
+    [242, 142, 43],
+    [225, 87, 89],
+    [118, 183, 178],
+    [89, 161, 79],
+    [237, 201, 72],
+    [176, 122, 161],
+    [156, 117, 95],
+];
+
+/// Category labels (or x values) and the numeric series plotted against them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartData {
+    pub labels: Vec<String>,
+    pub series: Vec<Series>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    /// `NaN` where the value is NULL
+    pub values: Vec<f64>,
+}
+
+/// Read chart data from query results: labels from column `x` (the first column when `None`) and
+/// one series per column of `y` (every other numeric column when empty).
+pub fn chart_data(batches: &[RecordBatch], x: Option<&str>, y: &[String]) -> Result<ChartData, DoubledeckerError> {
+    let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    let Some(schema) = batches.first().map(|b| b.schema()).filter(|_| rows > 0) else {
+        return Err(DoubledeckerError::BadRequest("The query returned no rows to chart".to_string()));
+    };
+    let column_index = |name: &str| {
+        schema
+            .index_of(name)
+            .map_err(|_| DoubledeckerError::BadRequest(format!("Column '{}' is not in the query result", name)))
+    };
+
+    let x_index = match x {
+        Some(name) => column_index(name)?,
+        None => 0,
+    };
+    let y_indices: Vec<usize> = if y.is_empty() {
+        (0..schema.fields().len())
+            .filter(|i| *i != x_index && schema.field(*i).data_type().is_numeric())
+            .collect()
+    } else {
+        let mut indices = Vec::with_capacity(y.len());
+        for name in y {
+            let index = column_index(name)?;
+            if !schema.field(index).data_type().is_numeric() {
+                return Err(DoubledeckerError::BadRequest(format!("Column '{}' is not numeric", name)));
+            }
+            indices.push(index);
+        }
+        indices
+    };
+    if y_indices.is_empty() {
+        return Err(DoubledeckerError::BadRequest(
+            "The query result has no numeric column to chart".to_string(),
+        ));
+    }
+
+    let arrow_err = |e: datafusion::arrow::error::ArrowError| {
+        DoubledeckerError::Internal(format!("Failed to read chart values: {}", e))
+    };
+    let options = FormatOptions::default().with_null("NULL");
+    let mut labels = Vec::with_capacity(rows);
+    let mut series: Vec<Series> = y_indices
+        .iter()
+        .map(|i| Series { name: schema.field(*i).name().clone(), values: Vec::with_capacity(rows) })
+        .collect();
+    for batch in batches {
+        let formatter = ArrayFormatter::try_new(batch.column(x_index).as_ref(), &options).map_err(arrow_err)?;
+        labels.extend((0..batch.num_rows()).map(|row| formatter.value(row).to_string()));
+        for (series, index) in series.iter_mut().zip(&y_indices) {
+            let values = cast(batch.column(*index), &DataType::Float64).map_err(arrow_err)?;
+            let values = values.as_primitive::<Float64Type>();
+            series.values.extend((0..values.len()).map(|row| {
+                if values.is_null(row) { f64::NAN } else { values.value(row) }
+            }));
+        }
+    }
+    Ok(ChartData { labels, series })
+}
+
+/// Draw a chart as an SVG document.
+pub fn render_svg(
+    kind: ChartKind,
+    data: &ChartData,
+    title: Option<&str>,
+    width: u32,
+    height: u32,
+) -> Result<String, DoubledeckerError> {
+    let mut canvas = SvgCanvas::new(width, height);
+    draw(&mut canvas, kind, data, title)?;
+    Ok(canvas.finish())
+}
+
+/// Draw a chart as a PNG image.
+pub fn render_png(
+    kind: ChartKind,
+    data: &ChartData,
+    title: Option<&str>,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, DoubledeckerError> {
+    let mut canvas = PixelCanvas::new(width, height);
+    draw(&mut canvas, kind, data, title)?;
+    canvas.finish()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Drawing surface. Coordinates are in output pixels from the top left; text is vertically
+/// centered on `y`.
+trait Canvas {
+    fn size(&self) -> (f64, f64);
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgb);
+    fn text(&mut self, x: f64, y: f64, text: &str, size: f64, anchor: Anchor, color: Rgb);
+    fn text_width(&self, text: &str, size: f64) -> f64;
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Rgb) {
+        self.fill_polygon(&[(x, y), (x + width, y), (x + width, y + height), (x, y + height)], color);
+    }
+
+    /// Line through `points` as one quad per segment.
+    fn polyline(&mut self, points: &[(f64, f64)], thickness: f64, color: Rgb) {
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let length = ((x1 - x0).powi(2) + (y1 - y0).powi(2)).sqrt();
+            if length == 0.0 {
+                continue;
+            }
+            let (nx, ny) = (-(y1 - y0) / length * thickness / 2.0, (x1 - x0) / length * thickness / 2.0);
+            self.fill_polygon(&[(x0 + nx, y0 + ny), (x1 + nx, y1 + ny), (x1 - nx, y1 - ny), (x0 - nx, y0 - ny)], color);
+        }
+    }
+
+    fn dot(&mut self, x: f64, y: f64, radius: f64, color: Rgb) {
+        let points: Vec<(f64, f64)> = (0..16)
+            .map(|i| {
+                let angle = i as f64 / 16.0 * 2.0 * PI;
+                (x + radius * angle.cos(), y + radius * angle.sin())
+            })
+            .collect();
+        self.fill_polygon(&points, color);
+    }
+}
+
+fn draw(canvas: &mut impl Canvas, kind: ChartKind, data: &ChartData, title: Option<&str>) -> Result<(), DoubledeckerError> {
+    let limit = if kind == ChartKind::Line { MAX_CHART_POINTS } else { MAX_CHART_CATEGORIES };
+    if data.labels.len() > limit {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "The query returned {} rows; a {} chart shows at most {}, so add a limit",
+            data.labels.len(),
+            kind.as_str(),
+            limit
+        )));
+    }
+
+    let (width, height) = canvas.size();
+    canvas.fill_rect(0.0, 0.0, width, height, BACKGROUND);
+    let mut top = 16.0;
+    if let Some(title) = title.map(str::trim).filter(|t| !t.is_empty()) {
+        canvas.text(width / 2.0, 22.0, title, 16.0, Anchor::Middle, TEXT);
+        top = 44.0;
+    }
+    match kind {
+        ChartKind::Pie => draw_pie(canvas, data, top),
+        ChartKind::Bar | ChartKind::Line => {
+            draw_xy(canvas, kind, data, top);
+            Ok(())
+        }
+    }
+}
+
+fn draw_xy(canvas: &mut impl Canvas, kind: ChartKind, data: &ChartData, top: f64) {
+    let (width, height) = canvas.size();
+    let finite = data.series.iter().flat_map(|s| s.values.iter().copied()).filter(|v| v.is_finite());
+    let (min, max) = finite.fold((0.0_f64, 0.0_f64), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let ticks = nice_ticks(min, max);
+    let step = ticks.get(1).map(|t| t - ticks[0]).unwrap_or(1.0);
+    let (lo, hi) = (ticks[0], ticks[ticks.len() - 1]);
+    let tick_labels: Vec<String> = ticks.iter().map(|t| format_value(*t, step)).collect();
+
+    let legend_height = if data.series.len() > 1 { 24.0 } else { 0.0 };
+    let left = tick_labels.iter().map(|l| canvas.text_width(l, 11.0)).fold(0.0, f64::max) + 18.0;
+    let right = width - 16.0;
+    let bottom = height - 30.0 - legend_height;
+    let (plot_width, plot_height) = ((right - left).max(1.0), (bottom - top).max(1.0));
+    let y_of = |v: f64| bottom - (v - lo) / (hi - lo) * plot_height;
+
+    for (tick, label) in ticks.iter().zip(&tick_labels) {
+        let y = y_of(*tick);
+        canvas.fill_rect(left, y - 0.5, plot_width, 1.0, if *tick == 0.0 { AXIS } else { GRID });
+        canvas.text(left - 8.0, y, label, 11.0, Anchor::End, MUTED);
+    }
+
+    let count = data.labels.len().max(1);
+    let slot = plot_width / count as f64;
+    let labels: Vec<String> = data.labels.iter().map(|l| shorten(l)).collect();
+    let widest = labels.iter().map(|l| canvas.text_width(l, 11.0)).fold(0.0, f64::max);
+    let every = ((widest + 10.0) / slot).ceil().max(1.0) as usize;
+    for (i, label) in labels.iter().enumerate().step_by(every) {
+        canvas.text(left + slot * (i as f64 + 0.5), bottom + 14.0, label, 11.0, Anchor::Middle, MUTED);
+    }
+
+    let zero = y_of(0.0_f64.clamp(lo, hi));
+    for (s, series) in data.series.iter().enumerate() {
+        let color = PALETTE[s % PALETTE.len()];
+        match kind {
+            ChartKind::Bar => {
+                let group = slot * 0.8;
+                let bar = group / data.series.len() as f64;
+                for (i, value) in series.values.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+                    let x = left + slot * i as f64 + (slot - group) / 2.0 + bar * s as f64;
+                    let y = y_of(*value);
+                    canvas.fill_rect(x, y.min(zero), (bar - 1.0).max(1.0), (y - zero).abs(), color);
+                }
+            }
+            _ => {
+                // NULLs break the line
+                let mut segment = Vec::new();
+                for (i, value) in series.values.iter().enumerate() {
+                    if value.is_finite() {
+                        segment.push((left + slot * (i as f64 + 0.5), y_of(*value)));
+                    } else {
+                        canvas.polyline(&segment, 2.0, color);
+                        segment.clear();
+                    }
+                }
+                canvas.polyline(&segment, 2.0, color);
+                if count <= 60 {
+                    for (i, value) in series.values.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+                        canvas.dot(left + slot * (i as f64 + 0.5), y_of(*value), 3.0, color);
+                    }
+                }
+            }
+        }
+    }
+
+    if data.series.len() > 1 {
+        let mut x = left;
+        let y = height - 16.0;
+        for (s, series) in data.series.iter().enumerate() {
+            canvas.fill_rect(x, y - 5.0, 10.0, 10.0, PALETTE[s % PALETTE.len()]);
+            canvas.text(x + 14.0, y, &series.name, 11.0, Anchor::Start, TEXT);
+            x += 14.0 + canvas.text_width(&series.name, 11.0) + 16.0;
+        }
+    }
+}
+
+fn draw_pie(canvas: &mut impl Canvas, data: &ChartData, top: f64) -> Result<(), DoubledeckerError> {
+    let (width, height) = canvas.size();
+    let values = &data.series[0].values;
+    let mut slices: Vec<(String, f64)> = data
+        .labels
+        .iter()
+        .zip(values)
+        .filter(|(_, v)| v.is_finite() && **v > 0.0)
+        .map(|(l, v)| (shorten(l), *v))
+        .collect();
+    let total: f64 = slices.iter().map(|(_, v)| v).sum();
+    if total <= 0.0 {
+        return Err(DoubledeckerError::BadRequest("A pie chart needs positive values".to_string()));
+    }
+    slices.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if slices.len() > MAX_PIE_SLICES {
+        let other: f64 = slices.drain(MAX_PIE_SLICES - 1..).map(|(_, v)| v).sum();
+        slices.push(("Other".to_string(), other));
+    }
+
+    let legend: Vec<String> =
+        slices.iter().map(|(l, v)| format!("{} ({:.1}%)", l, v / total * 100.0)).collect();
+    let legend_width = legend.iter().map(|l| canvas.text_width(l, 11.0)).fold(0.0, f64::max) + 30.0;
+    let legend_width = legend_width.min(width * 0.45);
+    let area_width = width - legend_width - 16.0;
+    let radius = ((area_width - 32.0).min(height - top - 16.0) / 2.0).max(4.0);
+    let (cx, cy) = (16.0 + area_width / 2.0, top + (height - top - 16.0) / 2.0);
+
+    let mut start = -PI / 2.0;
+    for (i, (_, value)) in slices.iter().enumerate() {
+        let sweep = value / total * 2.0 * PI;
+        let steps = (sweep / (PI / 90.0)).ceil().max(1.0) as usize;
+        let mut points = vec![(cx, cy)];
+        points.extend((0..=steps).map(|k| {
+            let angle = start + sweep * k as f64 / steps as f64;
+            (cx + radius * angle.cos(), cy + radius * angle.sin())
+        }));
+        canvas.fill_polygon(&points, PALETTE[i % PALETTE.len()]);
+        start += sweep;
+    }
+
+    let legend_x = width - legend_width;
+    let legend_top = cy - (legend.len() as f64 * 18.0) / 2.0 + 9.0;
+    for (i, label) in legend.iter().enumerate() {
+        let y = legend_top + i as f64 * 18.0;
+        canvas.fill_rect(legend_x, y - 5.0, 10.0, 10.0, PALETTE[i % PALETTE.len()]);
+        canvas.text(legend_x + 16.0, y, label, 11.0, Anchor::Start, TEXT);
+    }
+    Ok(())
+}
+
+/// Round axis ticks covering `min..=max`, about five of them.
+fn nice_ticks(min: f64, max: f64) -> Vec<f64> {
+    let (min, max) = if max > min { (min, max) } else { (min, min + 1.0) };
+    let raw = (max - min) / 5.0;
+    let exponent = raw.log10().floor() as i32;
+    let nice = match raw / 10f64.powi(exponent) {
+        n if n <= 1.0 => 1.0,
+        n if n <= 2.0 => 2.0,
+        n if n <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+    // Dividing by a power of ten rather than multiplying by its (inexact) inverse keeps 3 * 0.2 at 0.6
+    let tick = |i: i64| {
+        if exponent < 0 { i as f64 * nice / 10f64.powi(-exponent) } else { i as f64 * nice * 10f64.powi(exponent) }
+    };
+    let step = tick(1);
+    let first = (min / step).floor() as i64;
+    let last = (max / step).ceil() as i64;
+    (first..=last).map(tick).map(|t| if t == 0.0 { 0.0 } else { t }).collect()
+}
+
+/// Axis label for `value`: thousands and up with a k/M/B suffix, smaller values with as many
+/// decimals as the tick `step` needs.
+fn format_value(value: f64, step: f64) -> String {
+    let trim = |s: String| {
+        if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.').to_string() } else { s }
+    };
+    for (unit, suffix) in [(1e9, "B"), (1e6, "M"), (1e3, "k")] {
+        if value.abs() >= unit && step >= unit / 10.0 {
+            return format!("{}{}", trim(format!("{:.1}", value / unit)), suffix);
+        }
+    }
+    let decimals = if step >= 1.0 { 0 } else { (-step.log10()).ceil() as usize };
+    trim(format!("{:.*}", decimals, value))
+}
+
+fn shorten(label: &str) -> String {
+    if label.chars().count() <= MAX_LABEL_CHARS {
+        return label.to_string();
+    }
+    let mut short: String = label.chars().take(MAX_LABEL_CHARS - 2).collect();
+    short.push_str("..");
+    short
+}
+
+struct SvgCanvas {
+    width: u32,
+    height: u32,
+    body: String,
+}
+
+impl SvgCanvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self { width, height, body: String::new() }
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+             font-family=\"Helvetica, Arial, sans-serif\">{body}</svg>",
+            w = self.width,
+            h = self.height,
+            body = self.body
+        )
+    }
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl Canvas for SvgCanvas {
+    fn size(&self) -> (f64, f64) {
+        (self.width as f64, self.height as f64)
+    }
+
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgb) {
+        let points: Vec<String> = points.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+        let _ = write!(self.body, "<polygon points=\"{}\" fill=\"{}\"/>", points.join(" "), hex(color));
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Rgb) {
+        let _ = write!(
+            self.body,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+            x,
+            y,
+            width,
+            height,
+            hex(color)
+        );
+    }
+
+    fn polyline(&mut self, points: &[(f64, f64)], thickness: f64, color: Rgb) {
+        if points.len() < 2 {
+            return;
+        }
+        let points: Vec<String> = points.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+        let _ = write!(
+            self.body,
+            "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\"/>",
+            points.join(" "),
+            hex(color),
+            thickness
+        );
+    }
+
+    fn dot(&mut self, x: f64, y: f64, radius: f64, color: Rgb) {
+        let _ = write!(self.body, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"{}\"/>", x, y, radius, hex(color));
+    }
+
+    fn text(&mut self, x: f64, y: f64, text: &str, size: f64, anchor: Anchor, color: Rgb) {
+        let anchor = match anchor {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+            Anchor::End => "end",
+        };
+        let _ = write!(
+            self.body,
+            "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+            x,
+            y,
+            size,
+            anchor,
+            hex(color),
+            xml_escape(text)
+        );
+    }
+
+    fn text_width(&self, text: &str, size: f64) -> f64 {
+        text.chars().count() as f64 * size * 0.6
+    }
+}
+
+/// Drawn at `SUPERSAMPLE` times the output size and averaged down, for smooth edges.
+const SUPERSAMPLE: usize = 3;
+
+struct PixelCanvas {
+    width: usize,
+    height: usize,
+    /// RGB at `SUPERSAMPLE` times the output size
+    pixels: Vec<Rgb>,
+}
+
+impl PixelCanvas {
+    fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self { width, height, pixels: vec![BACKGROUND; width * height * SUPERSAMPLE * SUPERSAMPLE] }
+    }
+
+    /// Fill whole supersampled pixels `x0..x1` × `y0..y1`, clipped to the canvas.
+    fn fill_samples(&mut self, x0: i64, y0: i64, x1: i64, y1: i64, color: Rgb) {
+        let (w, h) = ((self.width * SUPERSAMPLE) as i64, (self.height * SUPERSAMPLE) as i64);
+        for y in y0.max(0)..y1.min(h) {
+            let row = y as usize * w as usize;
+            for x in x0.max(0)..x1.min(w) {
+                self.pixels[row + x as usize] = color;
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>, DoubledeckerError> {
+        let s = SUPERSAMPLE;
+        let sample_width = self.width * s;
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for y in 0..self.height {
+            // Filter type 0: the row as is
+            raw.push(0);
+            for x in 0..self.width {
+                let mut sum = [0u32; 3];
+                for sy in 0..s {
+                    for sx in 0..s {
+                        let pixel = self.pixels[(y * s + sy) * sample_width + x * s + sx];
+                        for c in 0..3 {
+                            sum[c] += pixel[c] as u32;
+                        }
+                    }
+                }
+                raw.extend(sum.iter().map(|c| (c / (s * s) as u32) as u8));
+            }
+        }
+        encode_png(self.width as u32, self.height as u32, &raw)
+    }
+}
+
+impl Canvas for PixelCanvas {
+    fn size(&self) -> (f64, f64) {
+        (self.width as f64, self.height as f64)
+    }
+
+    /// Scanline fill with the even-odd rule, sampling pixel centers.
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgb) {
+        if points.len() < 3 {
+            return;
+        }
+        let s = SUPERSAMPLE as f64;
+        let points: Vec<(f64, f64)> = points.iter().map(|(x, y)| (x * s, y * s)).collect();
+        let min_y = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min).floor().max(0.0) as i64;
+        let max_y = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max).ceil() as i64;
+        let mut crossings = Vec::new();
+        for y in min_y..max_y.min((self.height * SUPERSAMPLE) as i64) {
+            let sample_y = y as f64 + 0.5;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                if (y0 <= sample_y) != (y1 <= sample_y) {
+                    crossings.push(x0 + (sample_y - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+            for pair in crossings.chunks_exact(2) {
+                let (from, to) = ((pair[0] - 0.5).ceil() as i64, (pair[1] - 0.5).ceil() as i64);
+                self.fill_samples(from, y, to, y + 1, color);
+            }
+        }
+    }
+
+    fn text(&mut self, x: f64, y: f64, text: &str, size: f64, anchor: Anchor, color: Rgb) {
+        let unit = glyph_unit(size);
+        let x = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - self.text_width(text, size) / 2.0,
+            Anchor::End => x - self.text_width(text, size),
+        };
+        let s = SUPERSAMPLE as f64;
+        let dot = (unit * s).round() as i64;
+        let left = (x * s).round() as i64;
+        // Center the cap height (rows 0 to 6); row 7 holds descenders
+        let top = ((y - unit * 3.5) * s).round() as i64;
+        for (i, c) in text.chars().enumerate() {
+            let glyph = glyph(c);
+            for (col, bits) in glyph.iter().enumerate() {
+                for row in 0..8 {
+                    if bits & (1 << row) != 0 {
+                        let gx = left + (i as i64 * GLYPH_ADVANCE + col as i64) * dot;
+                        let gy = top + row * dot;
+                        self.fill_samples(gx, gy, gx + dot, gy + dot, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn text_width(&self, text: &str, size: f64) -> f64 {
+        text.chars().count() as f64 * GLYPH_ADVANCE as f64 * glyph_unit(size)
+    }
+}
+
+/// Output pixels per font pixel at a text size, in whole supersampled pixels so glyphs stay sharp.
+fn glyph_unit(size: f64) -> f64 {
+    let s = SUPERSAMPLE as f64;
+    (size / 8.0 * s).round().max(1.0) / s
+}
+
+/// Font pixels from one character to the next: 5 columns and a blank one.
+const GLYPH_ADVANCE: i64 = 6;
+
+/// Columns of a 5x8 glyph, least significant bit at the top. Characters outside printable ASCII
+/// are drawn as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    FONT[index]
+}
+
+/// The classic 5x7 LCD font (with descenders in the eighth row), printable ASCII from space.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00],
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62],
+    [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x08, 0x07, 0x03, 0x00], [0x00, 0x1C, 0x22, 0x41, 0x00],
+    [0x00, 0x41, 0x22, 0x1C, 0x00], [0x2A, 0x1C, 0x7F, 0x1C, 0x2A], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x80, 0x70, 0x30, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x00, 0x60, 0x60, 0x00],
+    [0x20, 0x10, 0x08, 0x04, 0x02], [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00],
+    [0x72, 0x49, 0x49, 0x49, 0x46], [0x21, 0x41, 0x49, 0x4D, 0x33], [0x18, 0x14, 0x12, 0x7F, 0x10],
+    [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x31], [0x41, 0x21, 0x11, 0x09, 0x07],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x46, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x00, 0x14, 0x00, 0x00],
+    [0x00, 0x40, 0x34, 0x00, 0x00], [0x00, 0x08, 0x14, 0x22, 0x41], [0x14, 0x14, 0x14, 0x14, 0x14],
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x59, 0x09, 0x06], [0x3E, 0x41, 0x5D, 0x59, 0x4E],
+    [0x7C, 0x12, 0x11, 0x12, 0x7C], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x41, 0x3E], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01],
+    [0x3E, 0x41, 0x41, 0x51, 0x73], [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00],
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], [0x7F, 0x40, 0x40, 0x40, 0x40],
+    [0x7F, 0x02, 0x1C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46],
+    [0x26, 0x49, 0x49, 0x49, 0x32], [0x03, 0x01, 0x7F, 0x01, 0x03], [0x3F, 0x40, 0x40, 0x40, 0x3F],
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F], [0x63, 0x14, 0x08, 0x14, 0x63],
+    [0x03, 0x04, 0x78, 0x04, 0x03], [0x61, 0x59, 0x49, 0x4D, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x41],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x41, 0x7F], [0x04, 0x02, 0x01, 0x02, 0x04],
+    [0x40, 0x40, 0x40, 0x40, 0x40], [0x00, 0x03, 0x07, 0x08, 0x00], [0x20, 0x54, 0x54, 0x78, 0x40],
+    [0x7F, 0x28, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x28], [0x38, 0x44, 0x44, 0x28, 0x7F],
+    [0x38, 0x54, 0x54, 0x54, 0x18], [0x00, 0x08, 0x7E, 0x09, 0x02], [0x18, 0xA4, 0xA4, 0x9C, 0x78],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x40, 0x3D, 0x00],
+    [0x7F, 0x10, 0x28, 0x44, 0x00], [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x78, 0x04, 0x78],
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], [0xFC, 0x18, 0x24, 0x24, 0x18],
+    [0x18, 0x24, 0x24, 0x18, 0xFC], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x24],
+    [0x04, 0x04, 0x3F, 0x44, 0x24], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C],
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], [0x44, 0x28, 0x10, 0x28, 0x44], [0x4C, 0x90, 0x90, 0x90, 0x7C],
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], [0x00, 0x00, 0x77, 0x00, 0x00],
+    [0x00, 0x41, 0x36, 0x08, 0x00], [0x02, 0x01, 0x02, 0x04, 0x02],
+];
+
+/// 8-bit RGB PNG of rows that already carry their filter byte.
+fn encode_png(width: u32, height: u32, filtered_rows: &[u8]) -> Result<Vec<u8>, DoubledeckerError> {
+    let png_err = |e: std::io::Error| DoubledeckerError::Internal(format!("PNG encoding error: {}", e));
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(filtered_rows).map_err(png_err)?;
+    let compressed = encoder.finish().map_err(png_err)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression and filtering, no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    for (kind, data) in [(b"IHDR", header.as_slice()), (b"IDAT", &compressed), (b"IEND", &[])] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let crc = crc32fast::hash(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    fn sample() -> Vec<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("platform", DataType::Utf8, true),
+            Field::new("total_revenue", DataType::Float64, true),
+            Field::new("total_streams", DataType::Int64, true),
+        ]));
+        vec![
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    Arc::new(StringArray::from(vec![Some("Spotify"), Some("Apple"), None])),
+                    Arc::new(Float64Array::from(vec![Some(12.5), None, Some(3.0)])),
+                    Arc::new(Int64Array::from(vec![1200, 800, 50])),
+                ],
+            )
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_chart_data_defaults_to_numeric_columns() {
+        let data = chart_data(&sample(), None, &[]).unwrap();
+        assert_eq!(data.labels, vec!["Spotify", "Apple", "NULL"]);
+        assert_eq!(data.series.len(), 2);
+        assert_eq!(data.series[0].name, "total_revenue");
+        assert!(data.series[0].values[1].is_nan());
+        assert_eq!(data.series[1].values, vec![1200.0, 800.0, 50.0]);
+
+        let streams = chart_data(&sample(), Some("platform"), &["total_streams".to_string()]).unwrap();
+        assert_eq!(streams.series.len(), 1);
+        assert!(chart_data(&sample(), None, &["platform".to_string()]).is_err());
+        assert!(chart_data(&sample(), Some("isrc"), &[]).is_err());
+    }
+
+    #[test]
+    fn test_nice_ticks_and_labels() {
+        assert_eq!(nice_ticks(0.0, 12.5), vec![0.0, 5.0, 10.0, 15.0]);
+        assert_eq!(nice_ticks(-3.0, 9.0), vec![-5.0, 0.0, 5.0, 10.0]);
+        assert_eq!(nice_ticks(0.0, 0.0), vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0]);
+        assert_eq!(format_value(1500.0, 500.0), "1.5k");
+        assert_eq!(format_value(2_000_000.0, 1_000_000.0), "2M");
+        assert_eq!(format_value(0.25, 0.05), "0.25");
+        assert_eq!(format_value(40.0, 20.0), "40");
+    }
+
+    #[test]
+    fn test_render_svg_and_png() {
+        let data = chart_data(&sample(), None, &[]).unwrap();
+        let svg = render_svg(ChartKind::Bar, &data, Some("Revenue & streams"), 640, 360).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("Revenue &amp; streams"));
+        assert!(svg.contains("total_streams"), "legend names the series");
+
+        for kind in [ChartKind::Bar, ChartKind::Line, ChartKind::Pie] {
+            let png = render_png(kind, &data, None, 320, 200).unwrap();
+            assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+            assert_eq!(&png[16..24], &[0, 0, 1, 64, 0, 0, 0, 200], "IHDR has the size");
+        }
+
+        let empty = ChartData { labels: vec!["a".to_string()], series: vec![Series { name: "v".to_string(), values: vec![0.0] }] };
+        assert!(render_svg(ChartKind::Pie, &empty, None, 320, 200).is_err());
+    }
+}
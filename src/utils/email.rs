@@ -0,0 +1,60 @@
+use crate::utils::error::DoubledeckerError;
+use serde_json::json;
+use std::time::Duration;
+
+/// Resend's send endpoint; any API taking the same JSON body and bearer key works.
+const DEFAULT_EMAIL_API_URL: &str = "https://api.resend.com/emails";
+const EMAIL_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn env_setting(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Sends plain-text mail through an HTTP email API: `{from, to, subject, text}` posted with a bearer
+/// key. Configured with `EMAIL_API_KEY` and `EMAIL_FROM`, and `EMAIL_API_URL` for a provider other
+/// than Resend.
+#[derive(Clone)]
+pub struct EmailSender {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    from: String,
+}
+
+impl EmailSender {
+    pub fn new(api_url: &str, api_key: &str, from: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            from: from.to_string(),
+        }
+    }
+
+    /// The sender configured in the environment, if any.
+    pub fn from_env() -> Option<Self> {
+        let api_url = env_setting("EMAIL_API_URL").unwrap_or_else(|| DEFAULT_EMAIL_API_URL.to_string());
+        Some(Self::new(&api_url, &env_setting("EMAIL_API_KEY")?, &env_setting("EMAIL_FROM")?))
+    }
+
+    pub fn is_configured() -> bool {
+        env_setting("EMAIL_API_KEY").is_some() && env_setting("EMAIL_FROM").is_some()
+    }
+
+    /// Send one message to all of `to`. Failures are internal errors, so a job sending it can retry.
+    pub async fn send(&self, to: &[String], subject: &str, text: &str) -> Result<(), DoubledeckerError> {
+        let response = self
+            .client
+            .post(&self.api_url)
+            .bearer_auth(&self.api_key)
+            .timeout(EMAIL_TIMEOUT)
+            .json(&json!({ "from": self.from, "to": to, "subject": subject, "text": text }))
+            .send()
+            .await
+            .map_err(|e| DoubledeckerError::Internal(format!("Email request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(DoubledeckerError::Internal(format!("Email API rejected the message: {}", response.status())));
+        }
+        Ok(())
+    }
+}
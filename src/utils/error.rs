@@ -6,6 +6,52 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use utoipa::ToSchema;
+
+/// A single invalid request field, returned in the `fields` array of a validation error.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    /// Stable machine-readable reason, e.g. `too_short`, `common_password`
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Where a multi-step query broke: the failing step and what it could have read.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct QueryStepFailure {
+    /// Zero-based position in `steps`
+    pub step_index: usize,
+    pub step_name: String,
+    /// `sql` or `structured`
+    pub step_kind: String,
+    /// Columns of each table the step could read: workspace tables and earlier steps
+    pub available_columns: std::collections::BTreeMap<String, Vec<String>>,
+    /// `code` and `error` of the underlying failure
+    #[schema(value_type = Object)]
+    pub cause: serde_json::Value,
+}
+
+/// A filter value that cannot be read as its column's type.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FilterTypeMismatch {
+    pub column: String,
+    /// Arrow type of the column, e.g. `Date32`
+    pub column_type: String,
+    pub value: String,
+    /// What the value reads as: `string`, `number` or `boolean`
+    pub value_type: String,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum DoubledeckerError {
@@ -13,7 +59,15 @@ pub enum DoubledeckerError {
     FileUpload(String),
     MultipartError(String),
     InvalidFilePath,
-    S3Error(String),
+    /// A strict upload whose file failed validation; the report goes out as `details`
+    FileValidation(Box<crate::normalization::ValidationReport>),
+    /// The upload is not CSV text, e.g. an image or a spreadsheet workbook
+    UnsupportedFileType(String),
+    /// An upload over one of the `UploadLimits`; `limit` is `file_bytes`, `columns` or `rows`
+    UploadLimitExceeded { limit: String, max: u64, actual: u64 },
+    /// A storage call failed; `retryable` when it was throttled or S3 was briefly unavailable, and
+    /// still failing after the client's own retries
+    S3Error { message: String, retryable: bool },
 
     // DataFusion/DataFrame errors
     DataFusionError(String),
@@ -23,6 +77,11 @@ pub enum DoubledeckerError {
     // Query errors
     QueryExecution(String),
     InvalidQuery(String),
+    QueryMemoryExceeded(String),
+    /// A step of a multi-step query failed; the failure goes out as `details`
+    QueryStepFailed(Box<QueryStepFailure>),
+    /// A structured-query filter whose value does not fit its column; the mismatch goes out as `details`
+    FilterTypeMismatch(Box<FilterTypeMismatch>),
 
     // Database errors
     DatabaseError(String),
@@ -30,10 +89,24 @@ pub enum DoubledeckerError {
     NotFound(String),
     Unauthorized,
     Forbidden(String),
+    AccountSuspended(String),
+    /// The request clashes with one still in progress
+    Conflict(String),
+
+    // Request limits
+    RateLimited(String),
+    TooManyConcurrentQueries(String),
+    /// The request body is over the limit of its route
+    PayloadTooLarge(String),
+    /// The request did not produce a response within the server's timeout
+    RequestTimeout(String),
+    /// The server is at its concurrent request limit
+    ServiceOverloaded(String),
 
     // General errors
     Internal(String),
     BadRequest(String),
+    Validation(Vec<FieldError>),
 }
 
 impl DoubledeckerError {
@@ -43,41 +116,152 @@ impl DoubledeckerError {
             DoubledeckerError::FileUpload(_) => StatusCode::BAD_REQUEST,
             DoubledeckerError::MultipartError(_) => StatusCode::BAD_REQUEST,
             DoubledeckerError::InvalidFilePath => StatusCode::BAD_REQUEST,
-            DoubledeckerError::S3Error(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            DoubledeckerError::FileValidation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DoubledeckerError::UnsupportedFileType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            DoubledeckerError::UploadLimitExceeded { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            DoubledeckerError::S3Error { retryable: true, .. } => StatusCode::SERVICE_UNAVAILABLE,
+            DoubledeckerError::S3Error { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             DoubledeckerError::DataFusionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             DoubledeckerError::ColumnNotFound(_) => StatusCode::NOT_FOUND,
             DoubledeckerError::TableNotFound(_) => StatusCode::NOT_FOUND,
             DoubledeckerError::QueryExecution(_) => StatusCode::INTERNAL_SERVER_ERROR,
             DoubledeckerError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            DoubledeckerError::QueryMemoryExceeded(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            DoubledeckerError::QueryStepFailed(_) => StatusCode::BAD_REQUEST,
+            DoubledeckerError::FilterTypeMismatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
             DoubledeckerError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             DoubledeckerError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
             DoubledeckerError::NotFound(_) => StatusCode::NOT_FOUND,
             DoubledeckerError::Unauthorized => StatusCode::UNAUTHORIZED,
             DoubledeckerError::Forbidden(_) => StatusCode::FORBIDDEN,
+            DoubledeckerError::AccountSuspended(_) => StatusCode::FORBIDDEN,
+            DoubledeckerError::Conflict(_) => StatusCode::CONFLICT,
+            DoubledeckerError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            DoubledeckerError::TooManyConcurrentQueries(_) => StatusCode::TOO_MANY_REQUESTS,
+            DoubledeckerError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            DoubledeckerError::RequestTimeout(_) => StatusCode::REQUEST_TIMEOUT,
+            DoubledeckerError::ServiceOverloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
             DoubledeckerError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
             DoubledeckerError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            DoubledeckerError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
         }
     }
 
+    /// Stable machine-readable code clients can branch on, sent as `code` in every error response
+    pub fn code(&self) -> &'static str {
+        match self {
+            DoubledeckerError::FileUpload(_) => "FILE_UPLOAD_FAILED",
+            DoubledeckerError::MultipartError(_) => "INVALID_MULTIPART",
+            DoubledeckerError::InvalidFilePath => "INVALID_FILE_PATH",
+            DoubledeckerError::FileValidation(_) => "FILE_VALIDATION_FAILED",
+            DoubledeckerError::UnsupportedFileType(_) => "UNSUPPORTED_FILE_TYPE",
+            DoubledeckerError::UploadLimitExceeded { .. } => "UPLOAD_LIMIT_EXCEEDED",
+            DoubledeckerError::S3Error { retryable: true, .. } => "STORAGE_UNAVAILABLE",
+            DoubledeckerError::S3Error { .. } => "STORAGE_ERROR",
+            DoubledeckerError::DataFusionError(_) => "QUERY_ENGINE_ERROR",
+            DoubledeckerError::ColumnNotFound(_) => "COLUMN_NOT_FOUND",
+            DoubledeckerError::TableNotFound(_) => "TABLE_NOT_FOUND",
+            DoubledeckerError::QueryExecution(_) => "QUERY_EXECUTION_FAILED",
+            DoubledeckerError::InvalidQuery(_) => "INVALID_QUERY",
+            DoubledeckerError::QueryMemoryExceeded(_) => "QUERY_MEMORY_EXCEEDED",
+            DoubledeckerError::QueryStepFailed(_) => "QUERY_STEP_FAILED",
+            DoubledeckerError::FilterTypeMismatch(_) => "FILTER_TYPE_MISMATCH",
+            DoubledeckerError::DatabaseError(_) => "DATABASE_ERROR",
+            DoubledeckerError::AuthenticationError(_) => "AUTHENTICATION_FAILED",
+            DoubledeckerError::NotFound(_) => "NOT_FOUND",
+            DoubledeckerError::Unauthorized => "UNAUTHORIZED",
+            DoubledeckerError::Forbidden(_) => "FORBIDDEN",
+            DoubledeckerError::AccountSuspended(_) => "ACCOUNT_SUSPENDED",
+            DoubledeckerError::Conflict(_) => "CONFLICT",
+            DoubledeckerError::RateLimited(_) => "RATE_LIMITED",
+            DoubledeckerError::TooManyConcurrentQueries(_) => "TOO_MANY_CONCURRENT_QUERIES",
+            DoubledeckerError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            DoubledeckerError::RequestTimeout(_) => "REQUEST_TIMEOUT",
+            DoubledeckerError::ServiceOverloaded(_) => "SERVICE_OVERLOADED",
+            DoubledeckerError::Internal(_) => "INTERNAL_ERROR",
+            DoubledeckerError::BadRequest(_) => "BAD_REQUEST",
+            DoubledeckerError::Validation(_) => "VALIDATION_FAILED",
+        }
+    }
+
+    /// Classify an error from planning user-supplied SQL. Unknown columns and tables get their
+    /// own codes; anything else is a problem with the query itself rather than a server fault.
+    pub fn from_query_planning(err: datafusion::error::DataFusionError) -> Self {
+        classify_schema_error(&err).unwrap_or_else(|| DoubledeckerError::InvalidQuery(err.to_string()))
+    }
+
+    /// Classify an error from running a planned query. Running out of the engine's memory pool gets its
+    /// own error so clients can tell "too big" apart from a server fault.
+    pub fn from_query_execution(err: datafusion::error::DataFusionError) -> Self {
+        classify_resource_error(&err).unwrap_or_else(|| DoubledeckerError::QueryExecution(err.to_string()))
+    }
+
+    /// Attribute a failure to a step of a multi-step query.
+    pub fn in_query_step(
+        self,
+        step_index: usize,
+        step_name: &str,
+        step_kind: &str,
+        available_columns: std::collections::BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        DoubledeckerError::QueryStepFailed(Box::new(QueryStepFailure {
+            step_index,
+            step_name: step_name.to_string(),
+            step_kind: step_kind.to_string(),
+            available_columns,
+            cause: json!({ "code": self.code(), "error": self.message() }),
+        }))
+    }
+
     /// Get the error message
     pub fn message(&self) -> String {
         match self {
             DoubledeckerError::FileUpload(msg) => format!("File upload error: {}", msg),
             DoubledeckerError::InvalidFilePath => "Invalid file path".to_string(),
-            DoubledeckerError::S3Error(msg) => format!("S3 error: {}", msg),
+            DoubledeckerError::FileValidation(report) => format!("File failed validation: {}", report.summary()),
+            DoubledeckerError::UnsupportedFileType(msg) => format!("Unsupported file type: {}", msg),
+            DoubledeckerError::UploadLimitExceeded { limit, max, actual } => {
+                format!("Upload exceeds the {} limit: {} is over the maximum of {}", limit, actual, max)
+            }
+            DoubledeckerError::S3Error { message, .. } => format!("S3 error: {}", message),
             DoubledeckerError::DataFusionError(msg) => format!("DataFrame error: {}", msg),
             DoubledeckerError::ColumnNotFound(col) => format!("Column not found: {}", col),
             DoubledeckerError::TableNotFound(table) => format!("Table not found: {}", table),
             DoubledeckerError::QueryExecution(msg) => format!("Query execution error: {}", msg),
             DoubledeckerError::InvalidQuery(msg) => format!("Invalid query: {}", msg),
+            DoubledeckerError::QueryMemoryExceeded(msg) => format!(
+                "Query exceeded memory budget; filter or aggregate further to reduce its working set: {}",
+                msg
+            ),
+            DoubledeckerError::QueryStepFailed(failure) => format!(
+                "Step {} ('{}') failed: {}",
+                failure.step_index,
+                failure.step_name,
+                failure.cause.get("error").and_then(|e| e.as_str()).unwrap_or_default()
+            ),
+            DoubledeckerError::FilterTypeMismatch(mismatch) => format!(
+                "Filter on '{}' expects a {} value but got the {} '{}'",
+                mismatch.column, mismatch.column_type, mismatch.value_type, mismatch.value
+            ),
             DoubledeckerError::DatabaseError(msg) => format!("Database error: {}", msg),
             DoubledeckerError::AuthenticationError(msg) => format!("Authentication error: {}", msg),
             DoubledeckerError::NotFound(msg) => format!("Not found: {}", msg),
             DoubledeckerError::Unauthorized => "Unauthorized".to_string(),
             DoubledeckerError::Forbidden(msg) => format!("Forbidden: {}", msg),
+            DoubledeckerError::AccountSuspended(reason) => format!("Account suspended: {}", reason),
+            DoubledeckerError::Conflict(msg) => format!("Conflict: {}", msg),
+            DoubledeckerError::RateLimited(msg) => format!("Too many requests: {}", msg),
+            DoubledeckerError::TooManyConcurrentQueries(msg) => format!("Too many concurrent queries: {}", msg),
+            DoubledeckerError::PayloadTooLarge(msg) => format!("Payload too large: {}", msg),
+            DoubledeckerError::RequestTimeout(msg) => format!("Request timed out: {}", msg),
+            DoubledeckerError::ServiceOverloaded(msg) => format!("Service overloaded: {}", msg),
             DoubledeckerError::Internal(msg) => format!("Internal error: {}", msg),
             DoubledeckerError::BadRequest(msg) => format!("Bad request: {}", msg),
             DoubledeckerError::MultipartError(msg) => format!("Multipart error: {}", msg),
+            DoubledeckerError::Validation(fields) => format!(
+                "Validation failed: {}",
+                fields.iter().map(|f| f.message.as_str()).collect::<Vec<_>>().join("; ")
+            ),
         }
     }
 }
@@ -88,19 +272,63 @@ impl IntoResponse for DoubledeckerError {
         let status = self.status_code();
         let message = self.message();
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": message,
             "status": status.as_u16(),
-        }));
+        });
+        body["code"] = json!(self.code());
+        match &self {
+            DoubledeckerError::Validation(fields) => body["fields"] = json!(fields),
+            DoubledeckerError::ColumnNotFound(column) => body["details"] = json!({ "column": column }),
+            DoubledeckerError::TableNotFound(table) => body["details"] = json!({ "table": table }),
+            DoubledeckerError::FileValidation(report) => body["details"] = json!(report),
+            DoubledeckerError::UploadLimitExceeded { limit, max, actual } => {
+                body["details"] = json!({ "limit": limit, "max": max, "actual": actual })
+            }
+            DoubledeckerError::QueryStepFailed(failure) => body["details"] = json!(failure),
+            DoubledeckerError::FilterTypeMismatch(mismatch) => body["details"] = json!(mismatch),
+            _ => {}
+        }
 
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
 // Convert from datafusion::error::DataFusionError
 impl From<datafusion::error::DataFusionError> for DoubledeckerError {
     fn from(err: datafusion::error::DataFusionError) -> Self {
-        DoubledeckerError::DataFusionError(err.to_string())
+        classify_schema_error(&err)
+            .or_else(|| classify_resource_error(&err))
+            .unwrap_or_else(|| DoubledeckerError::DataFusionError(err.to_string()))
+    }
+}
+
+/// Map DataFusion's "no field named" / "table not found" errors to `ColumnNotFound` / `TableNotFound`.
+fn classify_schema_error(err: &datafusion::error::DataFusionError) -> Option<DoubledeckerError> {
+    use datafusion::common::SchemaError;
+    use datafusion::error::DataFusionError;
+
+    match err.find_root() {
+        DataFusionError::SchemaError(SchemaError::FieldNotFound { field, .. }, _) => {
+            Some(DoubledeckerError::ColumnNotFound(field.name.clone()))
+        }
+        DataFusionError::Plan(msg) => {
+            let table = msg.strip_prefix("table '")?.strip_suffix("' not found")?;
+            // Drop the implicit `datafusion.public.` catalog/schema qualifiers
+            let table = table.rsplit('.').next().unwrap_or(table);
+            Some(DoubledeckerError::TableNotFound(table.to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Map memory pool exhaustion, however deeply DataFusion wrapped it, to `QueryMemoryExceeded`.
+fn classify_resource_error(err: &datafusion::error::DataFusionError) -> Option<DoubledeckerError> {
+    match err.find_root() {
+        datafusion::error::DataFusionError::ResourcesExhausted(msg) => {
+            Some(DoubledeckerError::QueryMemoryExceeded(msg.clone()))
+        }
+        _ => None,
     }
 }
 
@@ -120,6 +348,9 @@ impl From<String> for DoubledeckerError {
 
 impl From<MultipartError> for DoubledeckerError {
     fn from(err: MultipartError) -> Self {
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return DoubledeckerError::PayloadTooLarge(err.body_text());
+        }
         DoubledeckerError::FileUpload(err.to_string())
     }
 }
@@ -146,3 +377,47 @@ impl From<inngest::result::Error> for DoubledeckerError {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::common::{DFSchema, unqualified_field_not_found};
+    use datafusion::error::DataFusionError;
+
+    #[test]
+    fn test_schema_errors_get_specific_codes() {
+        let missing_column = unqualified_field_not_found("revenue", &DFSchema::empty());
+        let err = DoubledeckerError::from_query_planning(missing_column);
+        assert_eq!(err.code(), "COLUMN_NOT_FOUND");
+        assert!(matches!(err, DoubledeckerError::ColumnNotFound(ref c) if c == "revenue"));
+
+        let missing_table = DataFusionError::Plan("table 'datafusion.public.sales' not found".to_string());
+        let err = DoubledeckerError::from_query_planning(missing_table);
+        assert!(matches!(err, DoubledeckerError::TableNotFound(ref t) if t == "sales"));
+
+        let syntax = DataFusionError::Plan("Invalid function 'sum2'".to_string());
+        assert_eq!(DoubledeckerError::from_query_planning(syntax).code(), "INVALID_QUERY");
+    }
+
+    #[test]
+    fn test_memory_exhaustion_is_distinct() {
+        let exhausted = DataFusionError::ResourcesExhausted("Failed to allocate 1 MB".to_string())
+            .context("GroupedHashAggregateStream");
+        let err = DoubledeckerError::from_query_execution(exhausted);
+        assert_eq!(err.code(), "QUERY_MEMORY_EXCEEDED");
+
+        let other = DataFusionError::Execution("division by zero".to_string());
+        assert_eq!(DoubledeckerError::from_query_execution(other).code(), "QUERY_EXECUTION_FAILED");
+    }
+
+    #[test]
+    fn test_query_step_failure_keeps_cause() {
+        let available = [("royalty_data".to_string(), vec!["isrc".to_string(), "net_revenue".to_string()])].into();
+        let err = DoubledeckerError::ColumnNotFound("revenue".to_string()).in_query_step(1, "by_track", "sql", available);
+        assert_eq!(err.code(), "QUERY_STEP_FAILED");
+        assert_eq!(err.message(), "Step 1 ('by_track') failed: Column not found: revenue");
+        let DoubledeckerError::QueryStepFailed(failure) = err else { unreachable!() };
+        assert_eq!(failure.cause["code"], "COLUMN_NOT_FOUND");
+        assert_eq!(failure.available_columns["royalty_data"], vec!["isrc", "net_revenue"]);
+    }
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Number of undelivered events buffered per subscriber before slow clients start skipping.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityEventKind {
+    DatasetProcessed,
+    DatasetFailed,
+    /// A content scan flagged the uploaded file
+    DatasetQuarantined,
+    JobFinished,
+    QuotaWarning,
+    /// Someone mentioned the user in a comment
+    Mentioned,
+}
+
+impl ActivityEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityEventKind::DatasetProcessed => "dataset_processed",
+            ActivityEventKind::DatasetFailed => "dataset_failed",
+            ActivityEventKind::DatasetQuarantined => "dataset_quarantined",
+            ActivityEventKind::JobFinished => "job_finished",
+            ActivityEventKind::QuotaWarning => "quota_warning",
+            ActivityEventKind::Mentioned => "mentioned",
+        }
+    }
+}
+
+/// A notification addressed to a single user, streamed over `GET /events`.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ActivityEvent {
+    pub kind: ActivityEventKind,
+    #[serde(skip)]
+    pub user_id: Uuid,
+    pub workspace_id: Option<Uuid>,
+    #[schema(value_type = Object)]
+    pub payload: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ActivityEvent {
+    pub fn new(kind: ActivityEventKind, user_id: Uuid, workspace_id: Option<Uuid>, payload: Value) -> Self {
+        Self {
+            kind,
+            user_id,
+            workspace_id,
+            payload,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// In-process fan-out of activity events. Every `/events` connection holds its own receiver
+/// and filters for its user; events published with no listeners are dropped.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<ActivityEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUS_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: ActivityEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Publish the same event to each of the given users.
+    pub fn publish_to_users(
+        &self,
+        user_ids: &[Uuid],
+        kind: ActivityEventKind,
+        workspace_id: Option<Uuid>,
+        payload: Value,
+    ) {
+        for user_id in user_ids {
+            self.publish(ActivityEvent::new(kind, *user_id, workspace_id, payload.clone()));
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ActivityEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_subscribers_receive_published_events() {
+        let bus = EventBus::new();
+        let mut receiver = bus.subscribe();
+        let (user_a, user_b, workspace_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let payload = json!({ "limits": [{ "limit": "rows", "max": 100, "actual": 90 }] });
+        bus.publish_to_users(&[user_a, user_b], ActivityEventKind::QuotaWarning, Some(workspace_id), payload.clone());
+
+        for user_id in [user_a, user_b] {
+            let event = receiver.try_recv().unwrap();
+            assert_eq!(event.user_id, user_id);
+            assert_eq!(event.kind, ActivityEventKind::QuotaWarning);
+            assert_eq!(event.workspace_id, Some(workspace_id));
+            assert_eq!(event.payload, payload);
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_events_without_subscribers_are_dropped() {
+        let bus = EventBus::new();
+        bus.publish(ActivityEvent::new(ActivityEventKind::JobFinished, Uuid::new_v4(), None, json!({})));
+        let mut late = bus.subscribe();
+        assert!(late.try_recv().is_err());
+    }
+}
@@ -1,63 +1,294 @@
 use crate::utils::error::DoubledeckerError;
-use crate::server::dtos::analytics::AnalyticsQueryResponse;
-use arrow_json::writer::{JsonArray, WriterBuilder};
-use datafusion::arrow::array::RecordBatch;
+use crate::server::dtos::analytics::{AnalyticsQueryResponse, ResultLayout};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike};
+use datafusion::arrow::array::timezone::Tz;
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, Date32Array, Float64Array, PrimitiveArray, RecordBatch,
+    TimestampMillisecondArray,
+};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{
+    ArrowPrimitiveType, DataType, Decimal128Type, Decimal256Type, DecimalType, Field, Float16Type, Float32Type,
+    Float64Type, Int8Type, Int16Type, Int32Type, Int64Type, Schema, TimeUnit, UInt8Type, UInt16Type, UInt32Type,
+    UInt64Type,
+};
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet};
+use serde_json::Value;
+use std::sync::Arc;
 
+/// Re-express every timestamp column in `timezone` (an IANA name). Timestamps without a zone are
+/// taken to be UTC. The instants don't change, only the zone they are rendered in and that
+/// `date_trunc` works in, so "per day" means the user's local day.
+pub fn localize_timestamps(batch: &RecordBatch, timezone: &str) -> Result<RecordBatch, DoubledeckerError> {
+    let cast_err = |e: datafusion::arrow::error::ArrowError| {
+        DoubledeckerError::Internal(format!("Timestamp conversion error: {}", e))
+    };
+
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        match field.data_type() {
+            DataType::Timestamp(unit, tz) => {
+                // A naive -> UTC cast keeps the values; zone -> zone casts only change metadata
+                let utc = match tz {
+                    None => cast(column, &DataType::Timestamp(*unit, Some("UTC".into()))).map_err(cast_err)?,
+                    Some(_) => column.clone(),
+                };
+                let local_type = DataType::Timestamp(*unit, Some(timezone.into()));
+                columns.push(cast(&utc, &local_type).map_err(cast_err)?);
+                fields.push(Field::new(field.name(), local_type, field.is_nullable()));
+            }
+            _ => {
+                columns.push(column.clone());
+                fields.push(field.as_ref().clone());
+            }
+        }
+    }
+
+    let schema = Schema::new_with_metadata(fields, schema.metadata().clone());
+    RecordBatch::try_new(Arc::new(schema), columns).map_err(cast_err)
+}
+
+/// Render query results as JSON in the requested layout.
+pub async fn render_query_results(
+    batches: Vec<RecordBatch>,
+    timezone: Option<&str>,
+    layout: ResultLayout,
+) -> Result<AnalyticsQueryResponse, DoubledeckerError> {
+    match layout {
+        ResultLayout::Rows => parse_batch_to_json(batches, timezone).await,
+        ResultLayout::Columns => parse_batch_to_columnar_json(batches, timezone).await,
+    }
+}
+
+/// Render query results as JSON rows. With a `timezone`, timestamp columns are written with that
+/// zone's offset (`2024-03-01T09:30:00+01:00`) instead of in UTC. See `array_to_json_values` for how
+/// each type is written.
 pub async fn parse_batch_to_json(
     batches: Vec<RecordBatch>,
+    timezone: Option<&str>,
 ) -> Result<AnalyticsQueryResponse, DoubledeckerError> {
+    let batches = match timezone {
+        Some(tz) => batches
+            .iter()
+            .map(|b| localize_timestamps(b, tz))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => batches,
+    };
+
     if batches.is_empty() {
         return Ok(AnalyticsQueryResponse {
             columns: vec![],
             rows: vec![],
+            data: None,
+            metrics: None,
+        preview: false,
         });
     }
 
     let schema = batches[0].schema();
     let columns: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
 
-    let mut buf = Vec::new();
-    let mut writer = WriterBuilder::new()
-        .with_explicit_nulls(true)
-        .build::<_, JsonArray>(&mut buf);
-
-    let batch_refs: Vec<&RecordBatch> = batches.iter().collect();
-    writer
-        .write_batches(&batch_refs)
-        .map_err(|e| DoubledeckerError::DataFusionError(format!("JSON conversion error: {}", e)))?;
-    writer.finish().map_err(|e| {
-        DoubledeckerError::DataFusionError(format!("JSON finalization error: {}", e))
-    })?;
-
-    let json_str = String::from_utf8(buf).map_err(|e| {
-        DoubledeckerError::DataFusionError(format!("UTF-8 conversion error: {}", e))
-    })?;
-
-    let json_objects: Vec<serde_json::Map<String, serde_json::Value>> =
-        serde_json::from_str(&json_str)
-            .map_err(|e| DoubledeckerError::DataFusionError(format!("JSON parse error: {}", e)))?;
-
-    let json_rows: Vec<serde_json::Value> = json_objects
-        .into_iter()
-        .map(|obj| {
-            let row_array: Vec<serde_json::Value> = columns
-                .iter()
-                .map(|col_name| {
-                    obj.get(col_name)
-                        .cloned()
-                        .unwrap_or(serde_json::Value::Null)
-                })
-                .collect();
-            serde_json::Value::Array(row_array)
-        })
-        .collect();
+    let mut json_rows = Vec::with_capacity(batches.iter().map(|b| b.num_rows()).sum());
+    for batch in &batches {
+        let mut rows: Vec<Vec<Value>> = (0..batch.num_rows())
+            .map(|_| Vec::with_capacity(batch.num_columns()))
+            .collect();
+        for column in batch.columns() {
+            for (row, value) in rows.iter_mut().zip(array_to_json_values(column)?) {
+                row.push(value);
+            }
+        }
+        json_rows.extend(rows.into_iter().map(Value::Array));
+    }
 
     Ok(AnalyticsQueryResponse {
         columns,
         rows: json_rows,
+        data: None,
+        metrics: None,
+        preview: false,
+    })
+}
+
+/// Render query results as one JSON array per column, skipping the transpose into rows.
+pub async fn parse_batch_to_columnar_json(
+    batches: Vec<RecordBatch>,
+    timezone: Option<&str>,
+) -> Result<AnalyticsQueryResponse, DoubledeckerError> {
+    let batches = match timezone {
+        Some(tz) => batches
+            .iter()
+            .map(|b| localize_timestamps(b, tz))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => batches,
+    };
+
+    let Some(first) = batches.first() else {
+        return Ok(AnalyticsQueryResponse {
+            columns: vec![],
+            rows: vec![],
+            data: Some(vec![]),
+            metrics: None,
+        preview: false,
+        });
+    };
+
+    let columns: Vec<String> = first.schema().fields().iter().map(|f| f.name().clone()).collect();
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    let mut data: Vec<Vec<Value>> = columns.iter().map(|_| Vec::with_capacity(total_rows)).collect();
+    for batch in &batches {
+        for (values, column) in data.iter_mut().zip(batch.columns()) {
+            values.extend(array_to_json_values(column)?);
+        }
+    }
+
+    Ok(AnalyticsQueryResponse {
+        columns,
+        rows: vec![],
+        data: Some(data),
+        metrics: None,
+        preview: false,
     })
 }
 
+/// Significant digits a JSON number (an IEEE double) holds exactly.
+const MAX_EXACT_JSON_DIGITS: usize = 15;
+
+/// Render one Arrow column as JSON, one value per row. Numbers stay numbers; decimals are written
+/// as numbers too unless they carry more than 15 significant digits, in which case they are written
+/// as strings so no digits are lost. Dictionary columns are written as their values, dates as
+/// `YYYY-MM-DD`, and timestamps as ISO-8601 (with an offset when the column has a zone). Lists and
+/// structs become arrays and objects; anything else is written as its display string.
+pub fn array_to_json_values(array: &ArrayRef) -> Result<Vec<Value>, DoubledeckerError> {
+    let convert_err = |e: datafusion::arrow::error::ArrowError| {
+        DoubledeckerError::DataFusionError(format!("JSON conversion error: {}", e))
+    };
+
+    let values = match array.data_type() {
+        DataType::Null => vec![Value::Null; array.len()],
+        DataType::Boolean => array.as_boolean().iter().map(|v| v.map_or(Value::Null, Value::Bool)).collect(),
+        DataType::Int8 => integers::<Int8Type>(array),
+        DataType::Int16 => integers::<Int16Type>(array),
+        DataType::Int32 => integers::<Int32Type>(array),
+        DataType::Int64 => integers::<Int64Type>(array),
+        DataType::UInt8 => integers::<UInt8Type>(array),
+        DataType::UInt16 => integers::<UInt16Type>(array),
+        DataType::UInt32 => integers::<UInt32Type>(array),
+        DataType::UInt64 => integers::<UInt64Type>(array),
+        DataType::Float16 => floats::<Float16Type>(array),
+        DataType::Float32 => floats::<Float32Type>(array),
+        DataType::Float64 => floats::<Float64Type>(array),
+        DataType::Utf8 => strings(array.as_string::<i32>().iter()),
+        DataType::LargeUtf8 => strings(array.as_string::<i64>().iter()),
+        DataType::Utf8View => strings(array.as_string_view().iter()),
+        DataType::Decimal128(_, _) => decimals(array.as_primitive::<Decimal128Type>()),
+        DataType::Decimal256(_, _) => decimals(array.as_primitive::<Decimal256Type>()),
+        DataType::Dictionary(_, value_type) => {
+            array_to_json_values(&cast(array, value_type).map_err(convert_err)?)?
+        }
+        DataType::List(_) => lists(array.as_list::<i32>().iter())?,
+        DataType::LargeList(_) => lists(array.as_list::<i64>().iter())?,
+        DataType::FixedSizeList(_, _) => lists(array.as_fixed_size_list().iter())?,
+        DataType::Struct(fields) => {
+            let structs = array.as_struct();
+            let children = structs
+                .columns()
+                .iter()
+                .map(array_to_json_values)
+                .collect::<Result<Vec<_>, _>>()?;
+            (0..structs.len())
+                .map(|i| {
+                    if structs.is_null(i) {
+                        return Value::Null;
+                    }
+                    let object = fields
+                        .iter()
+                        .zip(&children)
+                        .map(|(field, child)| (field.name().clone(), child[i].clone()))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect()
+        }
+        _ => {
+            let formatter = ArrayFormatter::try_new(array.as_ref(), &FormatOptions::default()).map_err(convert_err)?;
+            (0..array.len())
+                .map(|i| {
+                    if array.is_null(i) {
+                        Value::Null
+                    } else {
+                        Value::String(formatter.value(i).to_string())
+                    }
+                })
+                .collect()
+        }
+    };
+    Ok(values)
+}
+
+fn integers<T>(array: &ArrayRef) -> Vec<Value>
+where
+    T: ArrowPrimitiveType,
+    T::Native: Into<serde_json::Number>,
+{
+    array
+        .as_primitive::<T>()
+        .iter()
+        .map(|v| v.map_or(Value::Null, |v| Value::Number(v.into())))
+        .collect()
+}
+
+/// NaN and infinities have no JSON form and are written as `null`.
+fn floats<T>(array: &ArrayRef) -> Vec<Value>
+where
+    T: ArrowPrimitiveType,
+    T::Native: Into<f64>,
+{
+    array
+        .as_primitive::<T>()
+        .iter()
+        .map(|v| v.and_then(|v| serde_json::Number::from_f64(v.into())).map_or(Value::Null, Value::Number))
+        .collect()
+}
+
+fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Vec<Value> {
+    values.map(|v| v.map_or(Value::Null, |s| Value::String(s.to_string()))).collect()
+}
+
+fn decimals<T: DecimalType>(array: &PrimitiveArray<T>) -> Vec<Value> {
+    (0..array.len())
+        .map(|i| if array.is_null(i) { Value::Null } else { decimal_to_json(&array.value_as_string(i)) })
+        .collect()
+}
+
+/// A decimal's exact text as a JSON number when a double holds it exactly, otherwise as a string.
+fn decimal_to_json(text: &str) -> Value {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+    let significant = digits.trim_start_matches('0').trim_end_matches('0').len();
+    if significant > MAX_EXACT_JSON_DIGITS {
+        return Value::String(text.to_string());
+    }
+    if let Ok(int) = text.parse::<i64>() {
+        return Value::Number(int.into());
+    }
+    text.parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map_or_else(|| Value::String(text.to_string()), Value::Number)
+}
+
+fn lists(values: impl Iterator<Item = Option<ArrayRef>>) -> Result<Vec<Value>, DoubledeckerError> {
+    values
+        .map(|v| match v {
+            Some(items) => Ok(Value::Array(array_to_json_values(&items)?)),
+            None => Ok(Value::Null),
+        })
+        .collect()
+}
+
 pub fn query_response_to_csv(response: &AnalyticsQueryResponse) -> String {
     let mut csv = String::new();
 
@@ -89,3 +320,495 @@ pub fn query_response_to_csv(response: &AnalyticsQueryResponse) -> String {
 
     csv
 }
+
+/// Rows of an Excel worksheet, the header row included.
+pub const XLSX_MAX_ROWS: usize = 1_048_576;
+
+/// Render query batches as an .xlsx workbook with typed cells.
+/// Numbers, dates and booleans keep their native Excel types; strings are written as text
+/// so identifiers like ISRCs and UPCs keep their leading zeros. The header row is frozen.
+/// Dates Excel cannot represent (before 1900 or after 9999) are written as ISO 8601 text. A result
+/// with more rows than a sheet holds is refused.
+pub fn batches_to_xlsx(batches: &[RecordBatch]) -> Result<Vec<u8>, DoubledeckerError> {
+    let xlsx_err = |e: rust_xlsxwriter::XlsxError| {
+        DoubledeckerError::Internal(format!("XLSX export error: {}", e))
+    };
+
+    let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    if rows >= XLSX_MAX_ROWS {
+        return Err(DoubledeckerError::BadRequest(format!(
+            "The result has {} rows, but an .xlsx sheet holds at most {} below its header; export it as CSV \
+             or Parquet instead",
+            rows,
+            XLSX_MAX_ROWS - 1
+        )));
+    }
+
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold();
+    let date_format = Format::new().set_num_format("yyyy-mm-dd");
+    let datetime_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Results").map_err(xlsx_err)?;
+
+    if let Some(first) = batches.first() {
+        for (col_idx, field) in first.schema().fields().iter().enumerate() {
+            worksheet
+                .write_string_with_format(0, col_idx as u16, field.name(), &header_format)
+                .map_err(xlsx_err)?;
+        }
+    }
+    worksheet.set_freeze_panes(1, 0).map_err(xlsx_err)?;
+
+    let mut row_offset: u32 = 1;
+    for batch in batches {
+        for (col_idx, column) in batch.columns().iter().enumerate() {
+            write_xlsx_column(
+                worksheet,
+                column,
+                row_offset,
+                col_idx as u16,
+                &date_format,
+                &datetime_format,
+            )?;
+        }
+        row_offset += batch.num_rows() as u32;
+    }
+
+    workbook.save_to_buffer().map_err(xlsx_err)
+}
+
+/// `name` reduced to lowercase letters, digits and dashes, safe in a download filename; `fallback`
+/// when nothing is left.
+pub fn filename_slug(name: &str, fallback: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() { fallback.to_string() } else { slug }
+}
+
+/// The name a stored file is downloaded under: the stem of the uploaded `filename` with
+/// `extension`, e.g. `my_sales_data.csv` as `my_sales_data.parquet`. Directories a browser may
+/// have included are dropped.
+pub fn download_filename(filename: &str, extension: &str) -> String {
+    let name = filename.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let stem = match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    };
+    let stem = if stem.is_empty() { "dataset" } else { stem };
+    format!("{}.{}", stem, extension)
+}
+
+/// A `Content-Disposition` value downloading as `filename`. Browsers that read the RFC 6266
+/// `filename*` parameter get the name as is; others get it with non-ASCII characters, quotes
+/// and control characters replaced by `_`.
+pub fn attachment_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+/// Render query batches as a Parquet file, keeping their Arrow types. An empty result is written
+/// with an empty schema.
+pub fn batches_to_parquet(batches: &[RecordBatch]) -> Result<Vec<u8>, DoubledeckerError> {
+    let parquet_err = |e: parquet::errors::ParquetError| {
+        DoubledeckerError::Internal(format!("Parquet export error: {}", e))
+    };
+
+    let schema = batches
+        .first()
+        .map(|b| b.schema())
+        .unwrap_or_else(|| Arc::new(datafusion::arrow::datatypes::Schema::empty()));
+    let mut buffer = Vec::new();
+    let mut writer = parquet::arrow::ArrowWriter::try_new(&mut buffer, schema, None).map_err(parquet_err)?;
+    for batch in batches {
+        writer.write(batch).map_err(parquet_err)?;
+    }
+    writer.close().map_err(parquet_err)?;
+    Ok(buffer)
+}
+
+/// Whether Excel can hold a date in `year`; its dates run from 1900 to 9999.
+fn excel_year(year: i32) -> bool {
+    (1900..=9999).contains(&year)
+}
+
+fn write_xlsx_column(
+    worksheet: &mut Worksheet,
+    column: &datafusion::arrow::array::ArrayRef,
+    row_offset: u32,
+    col: u16,
+    date_format: &Format,
+    datetime_format: &Format,
+) -> Result<(), DoubledeckerError> {
+    let xlsx_err = |e: rust_xlsxwriter::XlsxError| {
+        DoubledeckerError::Internal(format!("XLSX export error: {}", e))
+    };
+    let cast_err = |e: datafusion::arrow::error::ArrowError| {
+        DoubledeckerError::Internal(format!("XLSX cell conversion error: {}", e))
+    };
+
+    match column.data_type() {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _) => {
+            let values = cast(column, &DataType::Float64).map_err(cast_err)?;
+            let values = values.as_any().downcast_ref::<Float64Array>().ok_or_else(|| {
+                DoubledeckerError::Internal("XLSX numeric conversion failed".to_string())
+            })?;
+            for i in 0..values.len() {
+                if !values.is_null(i) {
+                    worksheet
+                        .write_number(row_offset + i as u32, col, values.value(i))
+                        .map_err(xlsx_err)?;
+                }
+            }
+        }
+        DataType::Boolean => {
+            let values = column.as_any().downcast_ref::<BooleanArray>().ok_or_else(|| {
+                DoubledeckerError::Internal("XLSX boolean conversion failed".to_string())
+            })?;
+            for i in 0..values.len() {
+                if !values.is_null(i) {
+                    worksheet
+                        .write_boolean(row_offset + i as u32, col, values.value(i))
+                        .map_err(xlsx_err)?;
+                }
+            }
+        }
+        DataType::Date32 | DataType::Date64 => {
+            let values = cast(column, &DataType::Date32).map_err(cast_err)?;
+            let values = values.as_any().downcast_ref::<Date32Array>().ok_or_else(|| {
+                DoubledeckerError::Internal("XLSX date conversion failed".to_string())
+            })?;
+            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+            for i in 0..values.len() {
+                if values.is_null(i) {
+                    continue;
+                }
+                let date = epoch + chrono::Duration::days(values.value(i) as i64);
+                let row = row_offset + i as u32;
+                if !excel_year(date.year()) {
+                    worksheet.write_string(row, col, date.format("%Y-%m-%d").to_string()).map_err(xlsx_err)?;
+                    continue;
+                }
+                let excel_date =
+                    ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+                        .map_err(xlsx_err)?;
+                worksheet.write_datetime_with_format(row, col, &excel_date, date_format).map_err(xlsx_err)?;
+            }
+        }
+        DataType::Timestamp(_, tz) => {
+            // Excel has no time zones, so write the wall-clock time of the column's zone
+            let tz: Option<Tz> = tz.as_deref().and_then(|tz| tz.parse().ok());
+            let values = cast(column, &DataType::Timestamp(TimeUnit::Millisecond, None))
+                .map_err(cast_err)?;
+            let values = values
+                .as_any()
+                .downcast_ref::<TimestampMillisecondArray>()
+                .ok_or_else(|| {
+                    DoubledeckerError::Internal("XLSX timestamp conversion failed".to_string())
+                })?;
+            for i in 0..values.len() {
+                if values.is_null(i) {
+                    continue;
+                }
+                if let Some(ts) = DateTime::from_timestamp_millis(values.value(i)) {
+                    let wall_clock = match &tz {
+                        Some(tz) => ts.with_timezone(tz).naive_local(),
+                        None => ts.naive_utc(),
+                    };
+                    let row = row_offset + i as u32;
+                    if !excel_year(wall_clock.year()) {
+                        let text = wall_clock.format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+                        worksheet.write_string(row, col, text).map_err(xlsx_err)?;
+                        continue;
+                    }
+                    let excel_ts = ExcelDateTime::from_ymd(
+                        wall_clock.year() as u16,
+                        wall_clock.month() as u8,
+                        wall_clock.day() as u8,
+                    )
+                    .and_then(|date| {
+                        date.and_hms_milli(
+                            wall_clock.hour() as u16,
+                            wall_clock.minute() as u8,
+                            wall_clock.second() as u8,
+                            (wall_clock.nanosecond() / 1_000_000).min(999) as u16,
+                        )
+                    })
+                    .map_err(xlsx_err)?;
+                    worksheet
+                        .write_datetime_with_format(row, col, &excel_ts, datetime_format)
+                        .map_err(xlsx_err)?;
+                }
+            }
+        }
+        _ => {
+            let values = cast(column, &DataType::Utf8).map_err(cast_err)?;
+            let values = values.as_string::<i32>();
+            for i in 0..values.len() {
+                if !values.is_null(i) {
+                    worksheet
+                        .write_string(row_offset + i as u32, col, values.value(i))
+                        .map_err(xlsx_err)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{
+        Decimal128Array, DictionaryArray, Int64Array, StringArray, TimestampSecondArray,
+    };
+
+    #[tokio::test]
+    async fn test_timestamps_rendered_in_user_timezone() {
+        // 2024-01-01T23:30:00Z, stored without a zone
+        let schema = Schema::new(vec![Field::new("at", DataType::Timestamp(TimeUnit::Second, None), true)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(TimestampSecondArray::from(vec![1_704_151_800]))],
+        )
+        .unwrap();
+
+        let utc = parse_batch_to_json(vec![batch.clone()], None).await.unwrap();
+        assert_eq!(utc.rows[0][0], "2024-01-01T23:30:00");
+
+        let tokyo = parse_batch_to_json(vec![batch], Some("Asia/Tokyo")).await.unwrap();
+        assert_eq!(tokyo.rows[0][0], "2024-01-02T08:30:00+09:00");
+    }
+
+    #[tokio::test]
+    async fn test_typed_columns_rendered_as_json() {
+        let revenue = Decimal128Array::from(vec![Some(12_500_000_000), Some(123_456_789_123_456_789_123), None])
+            .with_precision_and_scale(38, 9)
+            .unwrap();
+        let day = Date32Array::from(vec![Some(19_723), None, Some(0)]);
+        let store: DictionaryArray<Int32Type> = vec!["Spotify", "Apple", "Spotify"].into_iter().collect();
+        let schema = Schema::new(vec![
+            Field::new("revenue", revenue.data_type().clone(), true),
+            Field::new("day", DataType::Date32, true),
+            Field::new("store", store.data_type().clone(), false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(revenue), Arc::new(day), Arc::new(store)],
+        )
+        .unwrap();
+
+        let response = parse_batch_to_json(vec![batch], None).await.unwrap();
+        assert_eq!(response.rows[0], serde_json::json!([12.5, "2024-01-01", "Spotify"]));
+        // Too many digits for a double: kept exact as a string
+        assert_eq!(response.rows[1], serde_json::json!(["123456789123.456789123", null, "Apple"]));
+        assert_eq!(response.rows[2], serde_json::json!([null, "1970-01-01", "Spotify"]));
+    }
+
+    /// The XML of cell `reference`, e.g. `<c r="A2"><v>12.5</v></c>`, from a worksheet.
+    fn xlsx_cell<'a>(sheet: &'a str, reference: &str) -> &'a str {
+        let start = sheet.find(&format!("<c r=\"{}\"", reference)).expect("cell is written");
+        let end = start + sheet[start..].find("</c>").unwrap() + "</c>".len();
+        &sheet[start..end]
+    }
+
+    #[test]
+    fn test_xlsx_cells_keep_their_types_under_a_frozen_header() {
+        use std::io::Read;
+
+        let schema = Schema::new(vec![
+            Field::new("revenue", DataType::Float64, true),
+            Field::new("day", DataType::Date32, true),
+            Field::new("store", DataType::Utf8, true),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Float64Array::from(vec![12.5])),
+                Arc::new(Date32Array::from(vec![19_723])),
+                Arc::new(StringArray::from(vec!["Spotify"])),
+            ],
+        )
+        .unwrap();
+
+        let xlsx = batches_to_xlsx(&[batch]).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut sheet = String::new();
+        archive.by_name("xl/worksheets/sheet1.xml").unwrap().read_to_string(&mut sheet).unwrap();
+
+        let frozen_header = r#"<pane ySplit="1" topLeftCell="A2" activePane="bottomLeft" state="frozen"/>"#;
+        assert!(sheet.contains(frozen_header), "{}", sheet);
+        assert!(xlsx_cell(&sheet, "A1").contains(r#"t="s""#), "header is text");
+        let revenue = xlsx_cell(&sheet, "A2");
+        assert!(!revenue.contains("t=") && revenue.contains("<v>12.5</v>"), "{}", revenue);
+        // 2024-01-01 as an Excel date serial with a date format, not as text
+        let day = xlsx_cell(&sheet, "B2");
+        assert!(!day.contains("t=") && day.contains(" s=") && day.contains("<v>45292</v>"), "{}", day);
+        assert!(xlsx_cell(&sheet, "C2").contains(r#"t="s""#));
+    }
+
+    #[test]
+    fn test_xlsx_keeps_milliseconds_and_writes_dates_excel_cannot_hold_as_text() {
+        use std::io::Read;
+
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+        let old = (NaiveDate::from_ymd_opt(1850, 3, 1).unwrap() - epoch).num_days() as i32;
+        let schema = Schema::new(vec![
+            Field::new("at", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new("day", DataType::Date32, true),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                // 2024-01-01T00:00:00.500 and 1850-03-01T12:00:00
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    1_704_067_200_500,
+                    old as i64 * 86_400_000 + 43_200_000,
+                ])),
+                Arc::new(Date32Array::from(vec![19_723, old])),
+            ],
+        )
+        .unwrap();
+
+        let xlsx = batches_to_xlsx(&[batch]).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(xlsx)).unwrap();
+        let mut sheet = String::new();
+        archive.by_name("xl/worksheets/sheet1.xml").unwrap().read_to_string(&mut sheet).unwrap();
+        let mut strings = String::new();
+        archive.by_name("xl/sharedStrings.xml").unwrap().read_to_string(&mut strings).unwrap();
+
+        assert!(xlsx_cell(&sheet, "A2").contains("<v>45292.0000057870"), "{}", xlsx_cell(&sheet, "A2"));
+        assert!(xlsx_cell(&sheet, "A3").contains(r#"t="s""#) && xlsx_cell(&sheet, "B3").contains(r#"t="s""#));
+        assert!(strings.contains("1850-03-01 12:00:00.000") && strings.contains(">1850-03-01<"), "{}", strings);
+    }
+
+    #[test]
+    fn test_xlsx_refuses_more_rows_than_a_sheet_holds() {
+        let schema = Schema::new(vec![Field::new("n", DataType::Null, true)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(datafusion::arrow::array::NullArray::new(XLSX_MAX_ROWS))],
+        )
+        .unwrap();
+        let Err(DoubledeckerError::BadRequest(message)) = batches_to_xlsx(&[batch]) else {
+            panic!("a result over the sheet limit is refused");
+        };
+        assert!(message.contains("1048575"), "{}", message);
+    }
+
+        fn wide_batch(columns: usize, rows: usize) -> RecordBatch {
+        let fields: Vec<Field> = (0..columns)
+            .map(|c| match c % 3 {
+                0 => Field::new(format!("n{}", c), DataType::Int64, true),
+                1 => Field::new(format!("f{}", c), DataType::Float64, true),
+                _ => Field::new(format!("s{}", c), DataType::Utf8, true),
+            })
+            .collect();
+        let arrays: Vec<ArrayRef> = (0..columns)
+            .map(|c| -> ArrayRef {
+                match c % 3 {
+                    0 => Arc::new(Int64Array::from_iter_values((0..rows).map(|r| r as i64))),
+                    1 => Arc::new(Float64Array::from_iter_values((0..rows).map(|r| r as f64 * 0.25))),
+                    _ => Arc::new(StringArray::from_iter_values((0..rows).map(|r| format!("value-{}", r)))),
+                }
+            })
+            .collect();
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_columnar_layout_matches_rows() {
+        let batch = wide_batch(6, 4);
+        let rows = parse_batch_to_json(vec![batch.clone(), batch.clone()], None).await.unwrap();
+        let columnar = parse_batch_to_columnar_json(vec![batch.clone(), batch], None).await.unwrap();
+
+        assert!(columnar.rows.is_empty());
+        let data = columnar.data.unwrap();
+        assert_eq!(columnar.columns, rows.columns);
+        for (r, row) in rows.rows.iter().enumerate() {
+            for (c, column) in data.iter().enumerate() {
+                assert_eq!(row[c], column[r]);
+            }
+        }
+    }
+
+    /// Serialization cost of each layout on a wide result, including encoding the response body.
+    /// Run with `cargo test --release bench_result_layouts -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore]
+    async fn bench_result_layouts() {
+        let batches: Vec<RecordBatch> = (0..10).map(|_| wide_batch(60, 10_000)).collect();
+        for layout in [ResultLayout::Rows, ResultLayout::Columns] {
+            let start = std::time::Instant::now();
+            let response = render_query_results(batches.clone(), None, layout).await.unwrap();
+            let converted = start.elapsed();
+            let body = serde_json::to_vec(&serde_json::json!({ "rows": response.rows, "data": response.data })).unwrap();
+            println!(
+                "{:?}: convert {:?}, convert+encode {:?}, {} bytes",
+                layout,
+                converted,
+                start.elapsed(),
+                body.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parquet_export_keeps_types() {
+        let batch = wide_batch(3, 10);
+        let file = batches_to_parquet(std::slice::from_ref(&batch)).unwrap();
+
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(file))
+            .unwrap()
+            .build()
+            .unwrap();
+        let read: Vec<RecordBatch> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(read, vec![batch]);
+        assert!(!batches_to_parquet(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_download_filename_and_disposition() {
+        assert_eq!(download_filename("my_sales_data.csv", "parquet"), "my_sales_data.parquet");
+        assert_eq!(download_filename("C:\\exports\\q1.report.csv", "parquet"), "q1.report.parquet");
+        assert_eq!(download_filename(".csv", "parquet"), ".csv.parquet");
+        assert_eq!(download_filename("", "parquet"), "dataset.parquet");
+
+        assert_eq!(
+            attachment_disposition("my_sales_data.parquet"),
+            "attachment; filename=\"my_sales_data.parquet\"; filename*=UTF-8''my_sales_data.parquet"
+        );
+        assert_eq!(
+            attachment_disposition("Ventes \"été\".parquet"),
+            "attachment; filename=\"Ventes __t__.parquet\"; filename*=UTF-8''Ventes%20%22%C3%A9t%C3%A9%22.parquet"
+        );
+    }
+}
@@ -1,51 +1,87 @@
+use crate::config::{JwtConfig, JwtKey};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use jsonwebtoken::errors::{Error, ErrorKind};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, decode_header, encode};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Lifetime of issued tokens and of the sessions backing them.
+pub const TOKEN_EXPIRATION_HOURS: i64 = 24;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,   // Subject (user ID)
     pub email: String, // User email
+    pub sid: String,   // Session ID (user_sessions row)
     pub exp: i64,      // Expiration time
     pub iat: i64,      // Issued at
 }
 
 impl Claims {
-    pub fn new(user_id: Uuid, email: String, expiration_hours: i64) -> Self {
+    pub fn new(user_id: Uuid, email: String, session_id: Uuid, expiration_hours: i64) -> Self {
         let now = Utc::now();
         let exp = (now + Duration::hours(expiration_hours)).timestamp();
 
         Self {
             sub: user_id.to_string(),
             email,
+            sid: session_id.to_string(),
             exp,
             iat: now.timestamp(),
         }
     }
 }
 
-/// Generate a JWT token for a user
+/// Generate a JWT token for a user session, signed with `key` and tagged with its kid
 pub fn generate_token(
     user_id: Uuid,
     email: String,
-    secret: &str,
-) -> Result<String, jsonwebtoken::errors::Error> {
-    let claims = Claims::new(user_id, email, 24); // 24 hour expiration
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )?;
+    session_id: Uuid,
+    key: &JwtKey,
+) -> Result<String, Error> {
+    let claims = Claims::new(user_id, email, session_id, TOKEN_EXPIRATION_HOURS);
+    let header = Header {
+        kid: Some(key.kid.clone()),
+        ..Header::default()
+    };
+    let token = encode(&header, &claims, &EncodingKey::from_secret(key.secret.as_bytes()))?;
     Ok(token)
 }
 
-/// Verify and decode a JWT token
-pub fn verify_token(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )?;
-    Ok(token_data.claims)
+/// Verify and decode a JWT token against the current or any previous signing key
+pub fn verify_token(token: &str, config: &JwtConfig) -> Result<Claims, Error> {
+    let header = decode_header(token)?;
+    let mut result = Err(Error::from(ErrorKind::InvalidSignature));
+    for key in config.decoding_keys(header.kid.as_deref()) {
+        result = decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(key.secret.as_bytes()),
+            &Validation::default(),
+        )
+        .map(|data| data.claims);
+        if !matches!(&result, Err(e) if *e.kind() == ErrorKind::InvalidSignature) {
+            break;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_survive_secret_rotation() {
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let before = JwtConfig::new("old-secret", []);
+        let token = generate_token(user_id, "a@x.com".into(), session_id, &before.current).unwrap();
+
+        let rotated = JwtConfig::new("new-secret", ["old-secret"]);
+        let claims = verify_token(&token, &rotated).unwrap();
+        assert_eq!(claims.sid, session_id.to_string());
+
+        let dropped = JwtConfig::new("new-secret", []);
+        assert!(verify_token(&token, &dropped).is_err());
+    }
 }
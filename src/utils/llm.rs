@@ -0,0 +1,105 @@
+use crate::utils::error::DoubledeckerError;
+use serde_json::json;
+use std::time::Duration;
+
+/// OpenAI's chat completions endpoint; any API taking the same body and bearer key works.
+const DEFAULT_LLM_API_URL: &str = "https://api.openai.com/v1/chat/completions";
+const DEFAULT_LLM_MODEL: &str = "gpt-4o-mini";
+const LLM_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn env_setting(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// A language model answering one prompt at a time.
+#[async_trait::async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// The model's reply to `prompt` under the `system` instructions. Failures are internal errors.
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String, DoubledeckerError>;
+}
+
+/// A chat completions API in OpenAI's format, which most hosted and self-hosted models offer.
+/// Configured with `LLM_API_KEY`, and `LLM_API_URL` and `LLM_MODEL` for other providers or models.
+pub struct ChatCompletionsProvider {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl ChatCompletionsProvider {
+    pub fn new(api_url: &str, api_key: &str, model: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url: api_url.to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        }
+    }
+
+    /// The provider configured in the environment, if any.
+    pub fn from_env() -> Option<Self> {
+        let api_url = env_setting("LLM_API_URL").unwrap_or_else(|| DEFAULT_LLM_API_URL.to_string());
+        let model = env_setting("LLM_MODEL").unwrap_or_else(|| DEFAULT_LLM_MODEL.to_string());
+        Some(Self::new(&api_url, &env_setting("LLM_API_KEY")?, &model))
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for ChatCompletionsProvider {
+    async fn complete(&self, system: &str, prompt: &str) -> Result<String, DoubledeckerError> {
+        let body = json!({
+            "model": self.model,
+            "temperature": 0,
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": prompt },
+            ],
+        });
+        let response = self
+            .client
+            .post(&self.api_url)
+            .bearer_auth(&self.api_key)
+            .timeout(LLM_TIMEOUT)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| DoubledeckerError::Internal(format!("LLM request failed: {}", e.without_url())))?;
+        if !response.status().is_success() {
+            return Err(DoubledeckerError::Internal(format!("LLM API rejected the request: {}", response.status())));
+        }
+        let reply: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| DoubledeckerError::Internal(format!("LLM API sent an unreadable reply: {}", e)))?;
+        reply["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| DoubledeckerError::Internal("LLM API reply has no message".to_string()))
+    }
+}
+
+/// The JSON object in a model's reply, which models often wrap in a markdown fence or a sentence.
+pub fn reply_json(reply: &str) -> Option<serde_json::Value> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    if end < start {
+        return None;
+    }
+    serde_json::from_str(&reply[start..=end]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_json() {
+        let fenced = "Here you go:\n```json\n{\"dimensions\": [\"artist\"], \"metrics\": [\"net_revenue\"]}\n```";
+        assert_eq!(reply_json(fenced).unwrap(), json!({ "dimensions": ["artist"], "metrics": ["net_revenue"] }));
+        assert_eq!(reply_json("{\"limit\": 5}").unwrap(), json!({ "limit": 5 }));
+        assert!(reply_json("I can't answer that").is_none());
+        assert!(reply_json("} nothing {").is_none());
+        assert!(reply_json("{not json}").is_none());
+    }
+}
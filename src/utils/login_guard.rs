@@ -0,0 +1,224 @@
+use crate::utils::error::DoubledeckerError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Failures older than this are forgotten.
+const FAILURE_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// First lockout length; doubles with every further failure.
+const BASE_LOCKOUT: Duration = Duration::from_secs(30);
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, Copy)]
+struct FailureState {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Tracks failed logins per account from each client IP, per client IP, and per account across all
+/// IPs, locking any of them out with exponential backoff. An account is first locked only for the
+/// address guessing at it, so failures from elsewhere cannot lock its owner out; the much higher
+/// cross-IP threshold catches guessing spread over many addresses. In-process like `RateLimiter`:
+/// counters are per server instance and reset on restart.
+pub struct LoginGuard {
+    account_threshold: u32,
+    ip_threshold: u32,
+    account_total_threshold: u32,
+    entries: Mutex<HashMap<String, FailureState>>,
+}
+
+impl LoginGuard {
+    /// `account_threshold`/`ip_threshold`/`account_total_threshold` are the failures allowed before
+    /// the first lockout.
+    pub fn new(account_threshold: u32, ip_threshold: u32, account_total_threshold: u32) -> Self {
+        Self {
+            account_threshold,
+            ip_threshold,
+            account_total_threshold,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `account:{ip}|{email}`; IPs never contain `|`, so the email is everything after the first one.
+    fn account_key(email: &str, ip: Option<&str>) -> String {
+        format!("account:{}|{}", ip.unwrap_or("unknown"), normalize_email(email))
+    }
+
+    /// The account's failures from every IP.
+    fn account_total_key(email: &str) -> String {
+        format!("account_total:{}", normalize_email(email))
+    }
+
+    fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+
+    /// Reject the attempt with `RateLimited` while the account, from this IP or from all of them, or
+    /// the IP is locked out.
+    pub fn check(&self, email: &str, ip: Option<&str>) -> Result<(), DoubledeckerError> {
+        let now = Instant::now();
+        let entries = self.lock()?;
+        let keys = [Self::account_key(email, ip), Self::account_total_key(email)]
+            .into_iter()
+            .chain(ip.map(Self::ip_key));
+
+        let remaining = keys
+            .filter_map(|key| entries.get(&key).and_then(|s| s.locked_until))
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+            .max();
+
+        match remaining {
+            Some(remaining) => Err(DoubledeckerError::RateLimited(format!(
+                "too many failed login attempts, retry in {}s",
+                remaining.as_secs().max(1)
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a failed attempt against the account from this IP, the account overall, and the IP.
+    pub fn record_failure(&self, email: &str, ip: Option<&str>) -> Result<(), DoubledeckerError> {
+        let now = Instant::now();
+        let mut entries = self.lock()?;
+
+        if entries.len() > 10_000 {
+            entries.retain(|_, s| {
+                now.duration_since(s.last_failure) < FAILURE_WINDOW
+                    || s.locked_until.is_some_and(|until| until > now)
+            });
+        }
+
+        let targets = [
+            (Self::account_key(email, ip), self.account_threshold),
+            (Self::account_total_key(email), self.account_total_threshold),
+        ]
+        .into_iter()
+        .chain(ip.map(|ip| (Self::ip_key(ip), self.ip_threshold)));
+        for (key, threshold) in targets {
+            let state = entries.entry(key).or_insert(FailureState {
+                failures: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            if now.duration_since(state.last_failure) >= FAILURE_WINDOW {
+                state.failures = 0;
+            }
+            state.failures += 1;
+            state.last_failure = now;
+            if state.failures > threshold {
+                state.locked_until = Some(now + lockout_for(state.failures - threshold));
+            }
+        }
+        Ok(())
+    }
+
+    /// Clear the account's failures from this IP after a successful login. IP and cross-IP counters
+    /// are left alone so a login can't reset guessing from other addresses.
+    pub fn record_success(&self, email: &str, ip: Option<&str>) -> Result<(), DoubledeckerError> {
+        self.lock()?.remove(&Self::account_key(email, ip));
+        Ok(())
+    }
+
+    /// Lift any lockout on an account, from every IP. Returns whether there was anything to clear.
+    pub fn unlock_account(&self, email: &str) -> Result<bool, DoubledeckerError> {
+        let email = normalize_email(email);
+        let mut entries = self.lock()?;
+        let before = entries.len();
+        entries.remove(&Self::account_total_key(&email));
+        entries.retain(|key, _| {
+            let account = key.strip_prefix("account:").and_then(|rest| rest.split_once('|'));
+            account.is_none_or(|(_, entry_email)| entry_email != email)
+        });
+        Ok(entries.len() < before)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, FailureState>>, DoubledeckerError> {
+        self.entries
+            .lock()
+            .map_err(|_| DoubledeckerError::Internal("Login guard lock poisoned".to_string()))
+    }
+}
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Lockout after the n-th failure past the threshold (n >= 1): 30s, 60s, 120s, ... capped at 15 minutes.
+fn lockout_for(excess_failures: u32) -> Duration {
+    let factor = 1u32 << (excess_failures - 1).min(16);
+    BASE_LOCKOUT.saturating_mul(factor).min(MAX_LOCKOUT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locks_account_after_threshold_and_unlocks() {
+        let guard = LoginGuard::new(2, 100, 100);
+        for _ in 0..2 {
+            assert!(guard.check("a@x.com", Some("1.1.1.1")).is_ok());
+            guard.record_failure("a@x.com", Some("1.1.1.1")).unwrap();
+        }
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_ok());
+        guard.record_failure("A@x.com", Some("1.1.1.1")).unwrap();
+        assert!(matches!(
+            guard.check("a@x.com", Some("1.1.1.1")),
+            Err(DoubledeckerError::RateLimited(_))
+        ));
+        assert!(guard.check("b@x.com", Some("1.1.1.1")).is_ok());
+
+        assert!(guard.unlock_account("a@x.com").unwrap());
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_ok());
+        assert!(!guard.unlock_account("a@x.com").unwrap());
+    }
+
+    #[test]
+    fn test_account_lockout_is_per_client_ip() {
+        let guard = LoginGuard::new(1, 100, 100);
+        for _ in 0..3 {
+            guard.record_failure("a@x.com", Some("6.6.6.6")).unwrap();
+        }
+        assert!(guard.check("a@x.com", Some("6.6.6.6")).is_err());
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_ok(), "the owner's address is not locked out");
+
+        guard.record_failure("a@x.com", Some("1.1.1.1")).unwrap();
+        guard.record_success("a@x.com", Some("1.1.1.1")).unwrap();
+        assert!(guard.check("a@x.com", Some("6.6.6.6")).is_err(), "a login elsewhere does not clear it");
+    }
+
+    #[test]
+    fn test_ip_lockout_covers_every_account() {
+        let guard = LoginGuard::new(100, 2, 100);
+        for i in 0..3 {
+            guard.record_failure(&format!("user{}@x.com", i), Some("6.6.6.6")).unwrap();
+        }
+        assert!(guard.check("someone@x.com", Some("6.6.6.6")).is_err());
+        assert!(guard.check("someone@x.com", Some("1.1.1.1")).is_ok());
+    }
+
+    #[test]
+    fn test_account_locks_across_ips_at_the_total_threshold() {
+        let guard = LoginGuard::new(100, 100, 3);
+        for i in 0..4 {
+            guard.record_failure("a@x.com", Some(&format!("6.6.6.{}", i))).unwrap();
+        }
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_err(), "spread guessing locks the account everywhere");
+        assert!(guard.check("b@x.com", Some("6.6.6.0")).is_ok());
+
+        guard.record_success("a@x.com", Some("1.1.1.1")).unwrap();
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_err());
+        assert!(guard.unlock_account("A@x.com").unwrap());
+        assert!(guard.check("a@x.com", Some("1.1.1.1")).is_ok());
+    }
+
+    #[test]
+    fn test_lockout_backoff_is_exponential_and_capped() {
+        assert_eq!(lockout_for(1), Duration::from_secs(30));
+        assert_eq!(lockout_for(2), Duration::from_secs(60));
+        assert_eq!(lockout_for(3), Duration::from_secs(120));
+        assert_eq!(lockout_for(40), MAX_LOCKOUT);
+    }
+}
@@ -1,4 +1,18 @@
+pub mod charts;
+pub mod crypto;
+pub mod email;
 pub mod error;
+pub mod events;
 pub mod helpers;
 pub mod jwt;
+pub mod llm;
+pub mod login_guard;
+pub mod password;
+pub mod pii;
+pub mod query_limiter;
+pub mod rate_limit;
+#[cfg(feature = "redis")]
+pub mod redis;
 pub mod s3;
+pub mod scanner;
+pub mod webhooks;
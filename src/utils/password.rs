@@ -0,0 +1,197 @@
+use crate::utils::error::FieldError;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// bcrypt silently ignores everything past 72 bytes, so longer passwords give a false sense of strength.
+const MAX_PASSWORD_BYTES: usize = 72;
+
+static COMMON_PASSWORDS: LazyLock<HashSet<&'static str>> =
+    LazyLock::new(|| include_str!("common_passwords.txt").lines().map(str::trim).collect());
+
+/// Password requirements for signup, configurable through `PASSWORD_*` environment variables.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// How many of lowercase, uppercase, digits and symbols must appear
+    pub min_char_classes: usize,
+    /// Minimum estimated guessing entropy, see `estimate_entropy_bits`
+    pub min_entropy_bits: f64,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            min_char_classes: 2,
+            min_entropy_bits: 40.0,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str| std::env::var(name).ok();
+        Self {
+            min_length: var("PASSWORD_MIN_LENGTH")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_length),
+            min_char_classes: var("PASSWORD_MIN_CHAR_CLASSES")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_char_classes),
+            min_entropy_bits: var("PASSWORD_MIN_ENTROPY_BITS")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default.min_entropy_bits),
+        }
+    }
+
+    /// Check a password against the policy. `personal_inputs` (name, email...) must not appear in it.
+    /// Returns every violated rule so the client can show them together; empty means acceptable.
+    pub fn validate(&self, password: &str, personal_inputs: &[&str]) -> Vec<FieldError> {
+        let field = |code: &str, message: String| FieldError::new("password", code, message);
+        let mut errors = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length {
+            errors.push(field(
+                "too_short",
+                format!("Password must be at least {} characters", self.min_length),
+            ));
+        }
+        if password.len() > MAX_PASSWORD_BYTES {
+            errors.push(field(
+                "too_long",
+                format!("Password must be at most {} bytes", MAX_PASSWORD_BYTES),
+            ));
+        }
+
+        if char_classes(password) < self.min_char_classes {
+            errors.push(field(
+                "too_few_character_classes",
+                format!(
+                    "Password must mix at least {} of: lowercase, uppercase, digits, symbols",
+                    self.min_char_classes
+                ),
+            ));
+        }
+
+        if is_common_password(password) {
+            errors.push(field(
+                "common_password",
+                "Password is too common; choose something less guessable".to_string(),
+            ));
+        }
+
+        let lowered = password.to_lowercase();
+        let personal = personal_inputs
+            .iter()
+            .flat_map(|input| input.split(|c: char| c == '@' || c.is_whitespace()))
+            .map(str::to_lowercase)
+            .filter(|part| part.chars().count() >= 3)
+            .any(|part| lowered.contains(&part));
+        if personal {
+            errors.push(field(
+                "contains_personal_info",
+                "Password must not contain your name or email".to_string(),
+            ));
+        }
+
+        if errors.is_empty() && estimate_entropy_bits(password) < self.min_entropy_bits {
+            errors.push(field(
+                "too_predictable",
+                "Password is too predictable; make it longer or less repetitive".to_string(),
+            ));
+        }
+
+        errors
+    }
+}
+
+fn char_classes(password: &str) -> usize {
+    [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .iter()
+    .filter(|present| **present)
+    .count()
+}
+
+/// Deny-list match on the password itself and on its "core": lowercased, leetspeak undone,
+/// with leading/trailing digits and symbols stripped (so `P@ssw0rd123!` still matches `password`).
+fn is_common_password(password: &str) -> bool {
+    let lowered = password.to_lowercase();
+    if COMMON_PASSWORDS.contains(lowered.as_str()) {
+        return true;
+    }
+    let trimmed = lowered.trim_matches(|c: char| !c.is_alphabetic());
+    let core: String = trimmed
+        .chars()
+        .map(|c| match c {
+            '@' | '4' => 'a',
+            '3' => 'e',
+            '1' | '!' => 'i',
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' => 't',
+            other => other,
+        })
+        .collect();
+    COMMON_PASSWORDS.contains(trimmed) || COMMON_PASSWORDS.contains(core.as_str())
+}
+
+/// Rough zxcvbn-style guessing entropy: each character is worth log2 of the character pool in use,
+/// but repeats and ascending/descending runs (`aaaa`, `1234`, `cba`) only count one bit each.
+pub fn estimate_entropy_bits(password: &str) -> f64 {
+    let pool = [
+        (password.chars().any(|c| c.is_ascii_lowercase()), 26),
+        (password.chars().any(|c| c.is_ascii_uppercase()), 26),
+        (password.chars().any(|c| c.is_ascii_digit()), 10),
+        (password.chars().any(|c| !c.is_ascii_alphanumeric()), 33),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum::<u32>()
+    .max(1);
+    let per_char = f64::from(pool).log2();
+
+    let chars: Vec<char> = password.chars().collect();
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, c)| match i.checked_sub(1).map(|p| chars[p]) {
+            Some(prev) if prev == *c || (prev as u32).abs_diff(*c as u32) == 1 => 1.0,
+            _ => per_char,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(errors: Vec<FieldError>) -> Vec<String> {
+        errors.into_iter().map(|e| e.code).collect()
+    }
+
+    #[test]
+    fn test_password_policy() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.validate("Tangerine-Harbor-42", &[]).is_empty());
+        assert_eq!(codes(policy.validate("short1", &[])), vec!["too_short"]);
+        assert!(codes(policy.validate("P@ssw0rd123!", &[])).contains(&"common_password".to_string()));
+        assert_eq!(
+            codes(policy.validate("aaaaaaaaaa1", &[])),
+            vec!["too_predictable"]
+        );
+        assert_eq!(
+            codes(policy.validate("Jane-Tangerine-42", &["Jane Doe", "jane@example.com"])),
+            vec!["contains_personal_info"]
+        );
+        assert!(estimate_entropy_bits("abcdefgh") < estimate_entropy_bits("qmzrtkwx"));
+    }
+}
@@ -0,0 +1,136 @@
+use datafusion::arrow::array::{Array, RecordBatch, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use regex::Regex;
+use serde::Serialize;
+use std::sync::LazyLock;
+use utoipa::ToSchema;
+
+/// Share of non-empty sampled values that must match before a column is reported as likely PII.
+const LIKELY_PII_RATIO: f64 = 0.5;
+
+static EMAIL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}$").unwrap());
+// Separators or a leading `+` are required so bare 12-13 digit UPC/EAN codes don't match
+static PHONE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:\+\d{1,3}[\s.-]?)?\(?\d{3}\)?[\s.-]\d{3}[\s.-]\d{4}$|^\+\d{8,15}$").unwrap()
+});
+static SSN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{3}-\d{2}-\d{4}$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    Ssn,
+}
+
+impl PiiKind {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            PiiKind::Email => EMAIL_RE.is_match(value),
+            PiiKind::Phone => PHONE_RE.is_match(value),
+            PiiKind::Ssn => SSN_RE.is_match(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PiiFinding {
+    pub column: String,
+    pub kind: PiiKind,
+    pub matched: usize,
+    pub sampled: usize,
+    pub match_ratio: f64,
+    /// `match_ratio` is at or above the reporting threshold
+    pub likely_pii: bool,
+}
+
+/// Regex heuristics over every column of a sample. Non-string columns are scanned by their text form.
+pub fn scan_batches_for_pii(batches: &[RecordBatch]) -> Vec<PiiFinding> {
+    let Some(first) = batches.first() else {
+        return vec![];
+    };
+    let schema = first.schema();
+    let kinds = [PiiKind::Email, PiiKind::Phone, PiiKind::Ssn];
+
+    let mut findings = Vec::new();
+    for (idx, field) in schema.fields().iter().enumerate() {
+        let mut sampled = 0usize;
+        let mut counts = [0usize; 3];
+
+        for batch in batches {
+            let Ok(column) = cast(batch.column(idx), &DataType::Utf8) else {
+                continue;
+            };
+            let Some(values) = column.as_any().downcast_ref::<StringArray>() else {
+                continue;
+            };
+            for value in values.iter().flatten() {
+                let value = value.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                sampled += 1;
+                for (k, kind) in kinds.iter().enumerate() {
+                    if kind.matches(value) {
+                        counts[k] += 1;
+                    }
+                }
+            }
+        }
+
+        for (k, kind) in kinds.iter().enumerate() {
+            if counts[k] == 0 {
+                continue;
+            }
+            let match_ratio = counts[k] as f64 / sampled as f64;
+            findings.push(PiiFinding {
+                column: field.name().clone(),
+                kind: *kind,
+                matched: counts[k],
+                sampled,
+                match_ratio,
+                likely_pii: match_ratio >= LIKELY_PII_RATIO,
+            });
+        }
+    }
+
+    findings.sort_by(|a, b| b.match_ratio.total_cmp(&a.match_ratio));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_scan_detects_pii_and_ignores_upcs() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("contact", DataType::Utf8, true),
+            Field::new("upc", DataType::Utf8, true),
+            Field::new("tax", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("a@b.com"), Some("c@d.org"), None])),
+                Arc::new(StringArray::from(vec!["123456789012", "0987654321098", "5556667777"])),
+                Arc::new(StringArray::from(vec!["123-45-6789", "n/a", "(555) 123-4567"])),
+            ],
+        )
+        .unwrap();
+
+        let findings = scan_batches_for_pii(&[batch]);
+        let email = findings.iter().find(|f| f.column == "contact").unwrap();
+        assert_eq!(email.kind, PiiKind::Email);
+        assert!(email.likely_pii);
+        assert!(!findings.iter().any(|f| f.column == "upc"));
+        let ssn = findings.iter().find(|f| f.column == "tax" && f.kind == PiiKind::Ssn).unwrap();
+        assert_eq!(ssn.matched, 1);
+        assert!(!ssn.likely_pii);
+        assert!(findings.iter().any(|f| f.column == "tax" && f.kind == PiiKind::Phone));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::utils::error::DoubledeckerError;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use uuid::Uuid;
+
+/// Caps how many analytical queries one user can run at once. Each query builds its own
+/// DataFusion session and pulls Parquet from S3, so a burst from one user can starve the rest.
+/// Requests over the limit wait up to `queue_timeout` for a slot before being rejected. Slots are
+/// per server instance.
+pub struct QueryLimiter {
+    max_concurrent: usize,
+    queue_timeout: Duration,
+    semaphores: Mutex<HashMap<Uuid, Arc<Semaphore>>>,
+}
+
+impl QueryLimiter {
+    pub fn new(max_concurrent: usize, queue_timeout: Duration) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            queue_timeout,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reserve a query slot for `user_id`. The slot is released when the permit is dropped,
+    /// so hold it until the query's results have been fully produced.
+    pub async fn acquire(&self, user_id: Uuid) -> Result<OwnedSemaphorePermit, DoubledeckerError> {
+        let semaphore = self.semaphore_for(user_id)?;
+
+        let permit = if self.queue_timeout.is_zero() {
+            semaphore.try_acquire_owned().ok()
+        } else {
+            tokio::time::timeout(self.queue_timeout, semaphore.acquire_owned())
+                .await
+                .ok()
+                .and_then(Result::ok)
+        };
+
+        permit.ok_or_else(|| {
+            DoubledeckerError::TooManyConcurrentQueries(format!(
+                "at most {} queries may run at once per user",
+                self.max_concurrent
+            ))
+        })
+    }
+
+    fn semaphore_for(&self, user_id: Uuid) -> Result<Arc<Semaphore>, DoubledeckerError> {
+        let mut semaphores = self
+            .semaphores
+            .lock()
+            .map_err(|_| DoubledeckerError::Internal("Query limiter lock poisoned".to_string()))?;
+
+        // Forget idle users so the map doesn't grow without bound
+        if semaphores.len() > 10_000 {
+            let max = self.max_concurrent;
+            semaphores.retain(|_, s| Arc::strong_count(s) > 1 || s.available_permits() < max);
+        }
+
+        Ok(semaphores
+            .entry(user_id)
+            .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent)))
+            .clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_concurrent_queries_per_user() {
+        let limiter = QueryLimiter::new(1, Duration::from_millis(20));
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let held = limiter.acquire(alice).await.unwrap();
+        assert!(matches!(
+            limiter.acquire(alice).await,
+            Err(DoubledeckerError::TooManyConcurrentQueries(_))
+        ));
+        assert!(limiter.acquire(bob).await.is_ok());
+
+        drop(held);
+        assert!(limiter.acquire(alice).await.is_ok());
+    }
+}
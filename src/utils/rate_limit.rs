@@ -0,0 +1,134 @@
+use crate::utils::error::DoubledeckerError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Request counts by key, with the last time expired windows were dropped.
+struct Windows {
+    counts: HashMap<String, (Instant, u32)>,
+    last_sweep: Instant,
+}
+
+/// Fixed-window request limiter keyed by an arbitrary string (token, IP, user...). State is
+/// in-process, so limits are per server process, unless the limiter is given a Redis connection
+/// (`redis` feature), which makes them cluster-wide.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    windows: Mutex<Windows>,
+    #[cfg(feature = "redis")]
+    redis: Option<(redis::aio::ConnectionManager, String)>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            windows: Mutex::new(Windows { counts: HashMap::new(), last_sweep: Instant::now() }),
+            #[cfg(feature = "redis")]
+            redis: None,
+        }
+    }
+
+    /// Keep the counts in Redis under `{prefix}:{key}`, shared by every instance. A request Redis
+    /// cannot answer is counted in-process instead of being refused.
+    #[cfg(feature = "redis")]
+    pub fn with_redis(mut self, conn: redis::aio::ConnectionManager, prefix: &str) -> Self {
+        self.redis = Some((conn, prefix.to_string()));
+        self
+    }
+
+    /// Count a request against `key`, failing with `RateLimited` once the window is exhausted.
+    pub async fn check(&self, key: &str) -> Result<(), DoubledeckerError> {
+        #[cfg(feature = "redis")]
+        if let Some((conn, prefix)) = &self.redis {
+            match self.check_redis(conn.clone(), &format!("{}:{}", prefix, key)).await {
+                Ok(outcome) => return outcome,
+                Err(e) => eprintln!("Redis rate limit check failed, counting in-process: {}", e),
+            }
+        }
+        self.check_local(key)
+    }
+
+    /// One window per key: created with the window as its expiry by the first request, then
+    /// incremented, in one transaction.
+    #[cfg(feature = "redis")]
+    async fn check_redis(
+        &self,
+        mut conn: redis::aio::ConnectionManager,
+        key: &str,
+    ) -> redis::RedisResult<Result<(), DoubledeckerError>> {
+        let (count, ttl_ms): (u32, i64) = redis::pipe()
+            .atomic()
+            .cmd("SET").arg(key).arg(0).arg("PX").arg(self.window.as_millis() as u64).arg("NX").ignore()
+            .cmd("INCR").arg(key)
+            .cmd("PTTL").arg(key)
+            .query_async(&mut conn)
+            .await?;
+        if count > self.max_requests {
+            return Ok(Err(DoubledeckerError::RateLimited(format!(
+                "retry in {}s",
+                (ttl_ms.max(0) as u64).div_ceil(1000).max(1)
+            ))));
+        }
+        Ok(Ok(()))
+    }
+
+    fn check_local(&self, key: &str) -> Result<(), DoubledeckerError> {
+        let now = Instant::now();
+        let mut windows = self
+            .windows
+            .lock()
+            .map_err(|_| DoubledeckerError::Internal("Rate limiter lock poisoned".to_string()))?;
+
+        // Drop expired windows once per window, so keys seen once do not stay forever
+        if now.duration_since(windows.last_sweep) >= self.window {
+            let window = self.window;
+            windows.counts.retain(|_, (started, _)| now.duration_since(*started) < window);
+            windows.last_sweep = now;
+        }
+
+        let entry = windows.counts.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        if entry.1 >= self.max_requests {
+            let retry_after = self.window.saturating_sub(now.duration_since(entry.0));
+            return Err(DoubledeckerError::RateLimited(format!(
+                "retry in {}s",
+                retry_after.as_secs().max(1)
+            )));
+        }
+        entry.1 += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_limits_per_key_within_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("token:1.2.3.4").await.is_ok());
+        assert!(limiter.check("token:1.2.3.4").await.is_ok());
+        assert!(matches!(
+            limiter.check("token:1.2.3.4").await,
+            Err(DoubledeckerError::RateLimited(_))
+        ));
+        assert!(limiter.check("token:5.6.7.8").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_expired_windows_are_evicted() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(20));
+        for i in 0..100 {
+            limiter.check(&format!("token:{}", i)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        limiter.check("token:fresh").await.unwrap();
+        assert_eq!(limiter.windows.lock().unwrap().counts.len(), 1);
+    }
+}
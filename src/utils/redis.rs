@@ -0,0 +1,22 @@
+use redis::aio::ConnectionManager;
+
+/// Connect to the Redis at `REDIS_URL`, if set. Any failure is logged and yields `None`, so state
+/// that would have been shared stays in-process rather than keeping the server from starting.
+/// The connection manager reconnects on its own after later outages.
+pub async fn connect_from_env() -> Option<ConnectionManager> {
+    let url = std::env::var("REDIS_URL").ok().filter(|u| !u.trim().is_empty())?;
+    let client = match redis::Client::open(url) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Invalid REDIS_URL, keeping rate limits in-process: {}", e);
+            return None;
+        }
+    };
+    match ConnectionManager::new(client).await {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            eprintln!("Could not connect to Redis, keeping rate limits in-process: {}", e);
+            None
+        }
+    }
+}
@@ -1,72 +1,350 @@
 use crate::utils::error::DoubledeckerError;
+use crate::utils::helpers::attachment_disposition;
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore;
 use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use bytes::Bytes;
 use std::env;
+use std::future::Future;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Everything a workspace (the tenant) stores lives under this prefix, so lifecycle rules
+/// and audits can be scoped per tenant.
+pub fn workspace_prefix(workspace_id: Uuid) -> String {
+    format!("workspaces/{}/", workspace_id)
+}
+
+/// Raw CSV uploads, namespaced by workspace and uploading user.
+pub fn staging_prefix(workspace_id: Uuid, user_id: Uuid) -> String {
+    format!("{}users/{}/uploads/", workspace_prefix(workspace_id), user_id)
+}
+
+pub fn staging_key(workspace_id: Uuid, user_id: Uuid, dataset_id: Uuid) -> String {
+    format!("{}{}.csv", staging_prefix(workspace_id, user_id), dataset_id)
+}
+
+/// Normalized Parquet, shared by the workspace. The query engine reads `processed/` under the workspace prefix.
+pub fn parquet_key(workspace_id: Uuid, dataset_id: Uuid) -> String {
+    format!("{}processed/{}.parquet", workspace_prefix(workspace_id), dataset_id)
+}
+
+/// Uploads a content scan flagged, kept apart from staging for review until the dataset is deleted.
+pub fn quarantine_key(workspace_id: Uuid, dataset_id: Uuid) -> String {
+    format!("{}quarantine/{}.csv", workspace_prefix(workspace_id), dataset_id)
+}
+
+/// Reject keys outside `prefix` (including via `..` segments) before reading or deleting them.
+pub fn ensure_key_in_prefix(key: &str, prefix: &str) -> Result<(), DoubledeckerError> {
+    let escapes = key.split('/').any(|segment| segment == ".." || segment == ".");
+    if escapes || !key.starts_with(prefix) {
+        return Err(DoubledeckerError::Forbidden(format!(
+            "Object key '{}' is outside '{}'",
+            key, prefix
+        )));
+    }
+    Ok(())
+}
+
+/// S3 error codes of throttling and transient server trouble, retried whatever their status.
+const RETRYABLE_S3_CODES: [&str; 5] = ["SlowDown", "Throttling", "RequestTimeout", "InternalError", "ServiceUnavailable"];
+
+/// How S3 calls are retried after transient failures: throttling, 5xx responses, timeouts and
+/// dropped connections. Waits grow exponentially and are drawn at random up to that bound (full
+/// jitter), so instances throttled together do not retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first included
+    pub max_attempts: u32,
+    /// Bound of the wait before the first retry; doubled for every further one
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default policy, with `S3_MAX_ATTEMPTS` attempts if set (1 turns retries off).
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: env::var("S3_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default.max_attempts),
+            ..default
+        }
+    }
+
+    /// Longest wait before retrying after failed attempt `attempt` (1-based).
+    fn max_backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let unit = OsRng.next_u32() as f64 / u32::MAX as f64;
+        self.max_backoff(attempt).mul_f64(unit)
+    }
+
+    /// Run `op` until it succeeds, fails with an error that is not retryable, or runs out of attempts.
+    pub async fn run<T, F, Fut>(&self, mut op: F) -> Result<T, DoubledeckerError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, DoubledeckerError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.max_attempts && is_retryable(&e) => {
+                    tokio::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_retryable(error: &DoubledeckerError) -> bool {
+    matches!(error, DoubledeckerError::S3Error { retryable: true, .. })
+}
+
+/// An S3 SDK failure as a storage error, retryable when it is throttling, a 5xx or a failure to
+/// get any response at all.
+fn s3_error<E>(e: SdkError<E, HttpResponse>) -> DoubledeckerError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    let retryable = match &e {
+        SdkError::TimeoutError(_) | SdkError::ResponseError(_) => true,
+        SdkError::DispatchFailure(failure) => !failure.is_user(),
+        SdkError::ServiceError(context) => {
+            let status = context.raw().status().as_u16();
+            status == 429 || status >= 500 || context.err().code().is_some_and(|c| RETRYABLE_S3_CODES.contains(&c))
+        }
+        _ => false,
+    };
+    DoubledeckerError::S3Error {
+        message: DisplayErrorContext(&e).to_string(),
+        retryable,
+    }
+}
+
+/// Size of the first ranged read of a CSV head; each further read is twice the previous one.
+const CSV_HEAD_FIRST_RANGE: u64 = 64 * 1024;
+/// Most of a file read for its head, so a file without line breaks is not downloaded whole.
+pub const CSV_HEAD_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Length of the first `records` CSV records (header included) at the start of `bytes`, or `None`
+/// when more bytes are needed. Records are split as the CSV reader does, so quoted fields may span
+/// lines. Unless `whole_file`, the last record counts only once another one starts after it, as the
+/// cut may fall inside it; a whole file shorter than `records` is returned entirely.
+pub fn csv_head_len(bytes: &[u8], records: usize, whole_file: bool) -> Option<usize> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(bytes);
+    let mut record = csv::ByteRecord::new();
+    let mut read = 0;
+    while read < records && reader.read_byte_record(&mut record).ok()? {
+        read += 1;
+    }
+    let end = reader.position().byte() as usize;
+    if whole_file {
+        Some(if read < records { bytes.len() } else { end })
+    } else {
+        (read == records && end < bytes.len()).then_some(end)
+    }
+}
+
+fn presigning_config(expiration_secs: u64) -> Result<PresigningConfig, DoubledeckerError> {
+    PresigningConfig::expires_in(Duration::from_secs(expiration_secs)).map_err(|e| DoubledeckerError::S3Error {
+        message: e.to_string(),
+        retryable: false,
+    })
+}
+
+/// Where uploads and processed datasets are stored. `S3Uploader` is the production implementation;
+/// the integration tests use an in-memory one.
+#[async_trait::async_trait]
+pub trait ObjectStorage: Send + Sync {
+    async fn upload_csv_with_key(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError>;
+    async fn download_csv(&self, key: &str) -> Result<Vec<u8>, DoubledeckerError>;
+    /// The first `records` CSV records of an object (header included), see `csv_head_len`.
+    async fn read_csv_head(&self, key: &str, records: usize) -> Result<Vec<u8>, DoubledeckerError>;
+    async fn upload_parquet(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError>;
+    async fn delete_file(&self, key: &str) -> Result<(), DoubledeckerError>;
+    /// Size of the object at `key`, or `None` when there is none.
+    async fn object_size(&self, key: &str) -> Result<Option<u64>, DoubledeckerError>;
+    /// A presigned GET URL; with `download_filename`, browsers save the file under that name
+    /// instead of the last segment of the key.
+    async fn generate_presigned_url(
+        &self,
+        key: &str,
+        expiration_secs: Option<u64>,
+        download_filename: Option<&str>,
+    ) -> Result<String, DoubledeckerError>;
+    async fn generate_presigned_put_url(
+        &self,
+        key: &str,
+        expiration_secs: Option<u64>,
+    ) -> Result<String, DoubledeckerError>;
+    /// Start a multipart upload to `key` and return its upload id.
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, DoubledeckerError>;
+    /// Upload part `part_number` (from 1) and return its ETag. Uploading a part again replaces it.
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        content: Vec<u8>,
+    ) -> Result<String, DoubledeckerError>;
+    /// Assemble `key` from `parts`, (part number, ETag) pairs in ascending part order.
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<(), DoubledeckerError>;
+    /// Discard a multipart upload and the parts uploaded so far.
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), DoubledeckerError>;
+}
+
 pub struct S3Uploader {
     client: S3Client,
     bucket: String,
+    retry: RetryPolicy,
 }
 
 impl S3Uploader {
-    pub async fn new() -> Self {
-        let config = aws_config::load_from_env().await;
-        let client = S3Client::new(&config);
+    /// A client over an AWS config loaded once per process, so every request shares its
+    /// connections and cached credentials instead of resolving the credential chain again.
+    pub fn new(config: &aws_config::SdkConfig) -> Self {
+        // Retries are `RetryPolicy`'s alone, so attempts do not multiply with the SDK's own
+        let s3_config = aws_sdk_s3::config::Builder::from(config).retry_config(RetryConfig::disabled()).build();
+        let client = S3Client::from_conf(s3_config);
         let bucket = env::var("S3_BUCKET").unwrap_or_else(|_| "dd-query-csv-bucket".to_string());
 
-        Self { client, bucket }
+        Self {
+            client,
+            bucket,
+            retry: RetryPolicy::from_env(),
+        }
     }
 
-    /// Upload CSV content to S3 and return the S3 key
-    pub async fn upload_csv(&self, content: Vec<u8>) -> Result<String, DoubledeckerError> {
-        let key = format!("{}.csv", Uuid::new_v4());
-
-        self.client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(content))
-            .content_type("text/csv")
-            .send()
-            .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
-
+    /// Upload CSV content to the user's staging area in a workspace and return the S3 key
+    pub async fn upload_csv(
+        &self,
+        workspace_id: Uuid,
+        user_id: Uuid,
+        content: Vec<u8>,
+    ) -> Result<String, DoubledeckerError> {
+        let key = staging_key(workspace_id, user_id, Uuid::new_v4());
+        self.put_object(&key, content, "text/csv").await?;
         Ok(key)
     }
 
     /// Upload CSV content to a specific S3 key
     pub async fn upload_csv_with_key(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError> {
-        self.client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .body(ByteStream::from(content))
-            .content_type("text/csv")
-            .send()
-            .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
-
+        self.put_object(key, content, "text/csv").await?;
         Ok(key.to_string())
     }
 
-    /// Download CSV from S3 by key
+    /// Download CSV from S3 by key. A body cut off midway is retried along with the request.
     pub async fn download_csv(&self, key: &str) -> Result<Vec<u8>, DoubledeckerError> {
-        let response = self
-            .client
-            .get_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
+        self.retry
+            .run(|| async {
+                let response = self
+                    .client
+                    .get_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .send()
+                    .await
+                    .map_err(s3_error)?;
+                let data = response.body.collect().await.map_err(|e| DoubledeckerError::S3Error {
+                    message: e.to_string(),
+                    retryable: true,
+                })?;
+                Ok(data.into_bytes().to_vec())
+            })
             .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
+    }
+
+    /// Read the first `records` CSV records of an object with ranged GETs: 64KB, then ranges
+    /// doubling in size until enough complete records have arrived, so previewing a multi-GB file
+    /// reads only its start.
+    pub async fn read_csv_head(&self, key: &str, records: usize) -> Result<Vec<u8>, DoubledeckerError> {
+        let mut head = Vec::new();
+        let mut range = CSV_HEAD_FIRST_RANGE;
+        loop {
+            let start = head.len() as u64;
+            let (chunk, size) = self.get_range(key, start, range).await?;
+            head.extend_from_slice(&chunk);
+            let whole_file = size.is_none_or(|size| head.len() as u64 >= size);
+            if let Some(len) = csv_head_len(&head, records, whole_file) {
+                head.truncate(len);
+                return Ok(head);
+            }
+            if head.len() >= CSV_HEAD_MAX_BYTES {
+                return Err(DoubledeckerError::BadRequest(format!(
+                    "The first {} rows of the file are larger than {}MB",
+                    records.saturating_sub(1),
+                    CSV_HEAD_MAX_BYTES / (1024 * 1024)
+                )));
+            }
+            range = (range * 2).min((CSV_HEAD_MAX_BYTES - head.len()) as u64);
+        }
+    }
 
-        let data = response
-            .body
-            .collect()
+    /// Up to `len` bytes of an object from `start`, with the object's size. The size is `None` when
+    /// the whole object came back; an empty object has no range to read and comes back empty.
+    async fn get_range(&self, key: &str, start: u64, len: u64) -> Result<(Vec<u8>, Option<u64>), DoubledeckerError> {
+        self.retry
+            .run(|| async {
+                let response = self
+                    .client
+                    .get_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .range(format!("bytes={}-{}", start, start + len - 1))
+                    .send()
+                    .await;
+                let response = match response {
+                    Ok(response) => response,
+                    Err(SdkError::ServiceError(context)) if context.raw().status().as_u16() == 416 => {
+                        return Ok((Vec::new(), Some(start)));
+                    }
+                    Err(SdkError::ServiceError(context)) if context.raw().status().as_u16() == 404 => {
+                        return Err(DoubledeckerError::NotFound(format!("No file has been uploaded to {}", key)));
+                    }
+                    Err(e) => return Err(s3_error(e)),
+                };
+                // `Content-Range: bytes 0-65535/1048576`
+                let size = response
+                    .content_range()
+                    .and_then(|range| range.rsplit_once('/'))
+                    .and_then(|(_, size)| size.parse::<u64>().ok());
+                let data = response.body.collect().await.map_err(|e| DoubledeckerError::S3Error {
+                    message: e.to_string(),
+                    retryable: true,
+                })?;
+                Ok((data.into_bytes().to_vec(), size))
+            })
             .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
-        Ok(data.into_bytes().to_vec())
     }
 
     /// Get S3 URI for a key
@@ -74,57 +352,70 @@ impl S3Uploader {
         format!("s3://{}/{}", self.bucket, key)
     }
 
-    /// Generate a presigned URL for downloading a file from S3
+    /// Generate a presigned URL for downloading a file from S3, served as an attachment named
+    /// `download_filename` when given (S3's `response-content-disposition` override).
     /// Default expiration: 1 hour (3600 seconds)
+    /// Signed on every call rather than cached: signing is local to the shared client, listings carry
+    /// no presigned URLs, and a reused URL would have less lifetime left than the caller asked for.
     pub async fn generate_presigned_url(
         &self,
         key: &str,
         expiration_secs: Option<u64>,
+        download_filename: Option<&str>,
     ) -> Result<String, DoubledeckerError> {
         let expiration = expiration_secs.unwrap_or(3600);
 
+        // Signing is local, but resolving credentials for it can need the network
         let presigned_request = self
-            .client
-            .get_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .presigned(
-                aws_sdk_s3::presigning::PresigningConfig::expires_in(
-                    std::time::Duration::from_secs(expiration),
-                )
-                .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?,
-            )
-            .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
+            .retry
+            .run(|| async {
+                self.client
+                    .get_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .set_response_content_disposition(download_filename.map(attachment_disposition))
+                    .presigned(presigning_config(expiration)?)
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
 
         Ok(presigned_request.uri().to_string())
     }
 
     /// Delete file from S3 by key
     pub async fn delete_file(&self, key: &str) -> Result<(), DoubledeckerError> {
-        self.client
-            .delete_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .send()
-            .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
+        self.retry
+            .run(|| async {
+                self.client
+                    .delete_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
 
         Ok(())
     }
 
-    /// Upload Parquet content to S3 and return the S3 key
-    pub async fn upload_parquet(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError> {
-        self.client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .body(ByteStream::from(content))
-            .content_type("application/vnd.apache.parquet")
-            .send()
+    /// Size of the object at `key` from a HEAD request, or `None` when there is none
+    pub async fn object_size(&self, key: &str) -> Result<Option<u64>, DoubledeckerError> {
+        self.retry
+            .run(|| async {
+                match self.client.head_object().bucket(&self.bucket).key(key).send().await {
+                    Ok(response) => Ok(Some(response.content_length().unwrap_or(0).max(0) as u64)),
+                    Err(SdkError::ServiceError(context)) if context.err().is_not_found() => Ok(None),
+                    Err(e) => Err(s3_error(e)),
+                }
+            })
             .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
+    }
 
+    /// Upload Parquet content to S3 and return the S3 key
+    pub async fn upload_parquet(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError> {
+        self.put_object(key, content, "application/vnd.apache.parquet").await?;
         Ok(key.to_string())
     }
 
@@ -137,19 +428,295 @@ impl S3Uploader {
         let expiration = expiration_secs.unwrap_or(3600);
 
         let presigned_request = self
-            .client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(key)
-            .presigned(
-                aws_sdk_s3::presigning::PresigningConfig::expires_in(
-                    std::time::Duration::from_secs(expiration),
-                )
-                .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?,
-            )
-            .await
-            .map_err(|e| DoubledeckerError::S3Error(e.to_string()))?;
+            .retry
+            .run(|| async {
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .presigned(presigning_config(expiration)?)
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
 
         Ok(presigned_request.uri().to_string())
     }
+
+    /// Start a multipart upload of a CSV to `key` and return its upload id
+    pub async fn create_multipart_upload(&self, key: &str) -> Result<String, DoubledeckerError> {
+        let response = self
+            .retry
+            .run(|| async {
+                self.client
+                    .create_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .content_type("text/csv")
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
+        response.upload_id().map(str::to_string).ok_or_else(|| DoubledeckerError::S3Error {
+            message: format!("S3 returned no upload id for {}", key),
+            retryable: false,
+        })
+    }
+
+    /// Upload one part of a multipart upload and return its ETag. Parts other than the last must
+    /// be at least 5MB.
+    pub async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        content: Vec<u8>,
+    ) -> Result<String, DoubledeckerError> {
+        let body = Bytes::from(content);
+        let response = self
+            .retry
+            .run(|| async {
+                self.client
+                    .upload_part()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .body(ByteStream::from(body.clone()))
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
+        response.e_tag().map(str::to_string).ok_or_else(|| DoubledeckerError::S3Error {
+            message: format!("S3 returned no ETag for part {} of {}", part_number, key),
+            retryable: false,
+        })
+    }
+
+    /// Assemble a multipart upload from its parts, given in ascending part order
+    pub async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<(), DoubledeckerError> {
+        let upload = CompletedMultipartUpload::builder()
+            .set_parts(Some(
+                parts
+                    .iter()
+                    .map(|(number, etag)| CompletedPart::builder().part_number(*number).e_tag(etag).build())
+                    .collect(),
+            ))
+            .build();
+        self.retry
+            .run(|| async {
+                self.client
+                    .complete_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .multipart_upload(upload.clone())
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Abort a multipart upload, deleting the parts stored so far
+    pub async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), DoubledeckerError> {
+        self.retry
+            .run(|| async {
+                self.client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Put `content` at `key`, sending the same bytes again on every attempt.
+    async fn put_object(&self, key: &str, content: Vec<u8>, content_type: &str) -> Result<(), DoubledeckerError> {
+        let body = Bytes::from(content);
+        self.retry
+            .run(|| async {
+                self.client
+                    .put_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .body(ByteStream::from(body.clone()))
+                    .content_type(content_type)
+                    .send()
+                    .await
+                    .map_err(s3_error)
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ObjectStorage for S3Uploader {
+    async fn upload_csv_with_key(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError> {
+        S3Uploader::upload_csv_with_key(self, key, content).await
+    }
+
+    async fn download_csv(&self, key: &str) -> Result<Vec<u8>, DoubledeckerError> {
+        S3Uploader::download_csv(self, key).await
+    }
+
+    async fn read_csv_head(&self, key: &str, records: usize) -> Result<Vec<u8>, DoubledeckerError> {
+        S3Uploader::read_csv_head(self, key, records).await
+    }
+
+    async fn upload_parquet(&self, key: &str, content: Vec<u8>) -> Result<String, DoubledeckerError> {
+        S3Uploader::upload_parquet(self, key, content).await
+    }
+
+    async fn delete_file(&self, key: &str) -> Result<(), DoubledeckerError> {
+        S3Uploader::delete_file(self, key).await
+    }
+
+    async fn object_size(&self, key: &str) -> Result<Option<u64>, DoubledeckerError> {
+        S3Uploader::object_size(self, key).await
+    }
+
+    async fn generate_presigned_url(
+        &self,
+        key: &str,
+        expiration_secs: Option<u64>,
+        download_filename: Option<&str>,
+    ) -> Result<String, DoubledeckerError> {
+        S3Uploader::generate_presigned_url(self, key, expiration_secs, download_filename).await
+    }
+
+    async fn generate_presigned_put_url(
+        &self,
+        key: &str,
+        expiration_secs: Option<u64>,
+    ) -> Result<String, DoubledeckerError> {
+        S3Uploader::generate_presigned_put_url(self, key, expiration_secs).await
+    }
+
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, DoubledeckerError> {
+        S3Uploader::create_multipart_upload(self, key).await
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        content: Vec<u8>,
+    ) -> Result<String, DoubledeckerError> {
+        S3Uploader::upload_part(self, key, upload_id, part_number, content).await
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[(i32, String)],
+    ) -> Result<(), DoubledeckerError> {
+        S3Uploader::complete_multipart_upload(self, key, upload_id, parts).await
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), DoubledeckerError> {
+        S3Uploader::abort_multipart_upload(self, key, upload_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_keys_are_scoped_to_workspace_and_user() {
+        let (ws, user, dataset) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let key = staging_key(ws, user, dataset);
+        assert_eq!(key, format!("workspaces/{}/users/{}/uploads/{}.csv", ws, user, dataset));
+
+        assert!(ensure_key_in_prefix(&key, &workspace_prefix(ws)).is_ok());
+        assert!(ensure_key_in_prefix(&key, &staging_prefix(ws, Uuid::new_v4())).is_err());
+        assert!(ensure_key_in_prefix(&key, &workspace_prefix(Uuid::new_v4())).is_err());
+        let escaped = format!("{}../{}/processed/x.parquet", workspace_prefix(ws), Uuid::new_v4());
+        assert!(ensure_key_in_prefix(&escaped, &workspace_prefix(ws)).is_err());
+    }
+
+    #[test]
+    fn test_csv_head_len_waits_for_complete_records() {
+        let csv = b"artist,notes\nAda,\"two\nlines\"\nBo,short\n";
+        assert_eq!(csv_head_len(csv, 2, false), Some(29));
+        assert_eq!(&csv[..29], b"artist,notes\nAda,\"two\nlines\"\n");
+        // The cut falls inside the quoted field, or right after the last record read
+        assert_eq!(csv_head_len(&csv[..20], 2, false), None);
+        assert_eq!(csv_head_len(&csv[..29], 2, false), None);
+        assert_eq!(csv_head_len(&csv[..29], 2, true), Some(29));
+        assert_eq!(csv_head_len(csv, 10, true), Some(csv.len()));
+        assert_eq!(csv_head_len(csv, 10, false), None);
+        assert_eq!(csv_head_len(b"", 3, true), Some(0));
+    }
+
+    #[test]
+    fn test_retry_backoff_doubles_up_to_a_cap() {
+        let policy = RetryPolicy::default();
+        let bounds: Vec<u128> = (1..=8).map(|a| policy.max_backoff(a).as_millis()).collect();
+        assert_eq!(bounds, vec![100, 200, 400, 800, 1600, 3200, 5000, 5000]);
+        assert_eq!(policy.max_backoff(u32::MAX), policy.max_delay);
+        for attempt in 1..=8 {
+            assert!(policy.backoff(attempt) <= policy.max_backoff(attempt));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy_retries_only_transient_errors() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        let failure = |retryable| DoubledeckerError::S3Error {
+            message: "boom".to_string(),
+            retryable,
+        };
+
+        let calls = Mutex::new(0);
+        let result = policy
+            .run(|| async {
+                *calls.lock().unwrap() += 1;
+                if *calls.lock().unwrap() < 3 { Err(failure(true)) } else { Ok("done") }
+            })
+            .await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(*calls.lock().unwrap(), 3);
+
+        let calls = Mutex::new(0);
+        let result: Result<(), _> = policy
+            .run(|| async {
+                *calls.lock().unwrap() += 1;
+                Err(failure(true))
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), "STORAGE_UNAVAILABLE");
+        assert_eq!(*calls.lock().unwrap(), 3);
+
+        let calls = Mutex::new(0);
+        let result: Result<(), _> = policy
+            .run(|| async {
+                *calls.lock().unwrap() += 1;
+                Err(failure(false))
+            })
+            .await;
+        assert_eq!(result.unwrap_err().code(), "STORAGE_ERROR");
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
 }
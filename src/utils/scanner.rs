@@ -0,0 +1,133 @@
+use crate::utils::error::DoubledeckerError;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// clamd's default `StreamMaxLength` is 25MB; chunks stay well under it and are sent one by one.
+const CLAMD_CHUNK_BYTES: usize = 1024 * 1024;
+const CLAMD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What a content scan found in an uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanVerdict {
+    Clean,
+    /// Flagged, with the scanner's name for what it found (e.g. a virus signature)
+    Flagged(String),
+}
+
+/// Scans uploaded files before they are converted. A flagged file is quarantined instead of
+/// becoming queryable; a scanner failure fails the ingestion so the file is never let through
+/// unscanned.
+#[async_trait::async_trait]
+pub trait ContentScanner: Send + Sync {
+    async fn scan(&self, content: &[u8]) -> Result<ScanVerdict, DoubledeckerError>;
+}
+
+/// The default when no scanner is configured: every file is clean.
+pub struct NoopScanner;
+
+#[async_trait::async_trait]
+impl ContentScanner for NoopScanner {
+    async fn scan(&self, _content: &[u8]) -> Result<ScanVerdict, DoubledeckerError> {
+        Ok(ScanVerdict::Clean)
+    }
+}
+
+/// ClamAV's daemon over TCP (`CLAMD_ADDR`, e.g. `clamav:3310`), streaming the file with `INSTREAM`.
+/// ICAP gateways fronting other engines usually offer a clamd-compatible listener as well.
+pub struct ClamdScanner {
+    addr: String,
+}
+
+impl ClamdScanner {
+    pub fn new(addr: &str) -> Self {
+        Self { addr: addr.to_string() }
+    }
+
+    async fn instream(&self, content: &[u8]) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(&self.addr).await?;
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in content.chunks(CLAMD_CHUNK_BYTES) {
+            stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0u32.to_be_bytes()).await?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await?;
+        Ok(String::from_utf8_lossy(&reply).trim_end_matches(['\0', '\n']).to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl ContentScanner for ClamdScanner {
+    async fn scan(&self, content: &[u8]) -> Result<ScanVerdict, DoubledeckerError> {
+        let reply = tokio::time::timeout(CLAMD_TIMEOUT, self.instream(content))
+            .await
+            .map_err(|_| DoubledeckerError::Internal("Content scan timed out".to_string()))?
+            .map_err(|e| DoubledeckerError::Internal(format!("Content scanner unreachable: {}", e)))?;
+        parse_clamd_reply(&reply)
+    }
+}
+
+/// `stream: OK`, `stream: Eicar-Signature FOUND`, or an error such as
+/// `INSTREAM size limit exceeded. ERROR`.
+fn parse_clamd_reply(reply: &str) -> Result<ScanVerdict, DoubledeckerError> {
+    let result = reply.strip_prefix("stream:").map(str::trim);
+    match result {
+        Some("OK") => Ok(ScanVerdict::Clean),
+        Some(found) if found.ends_with(" FOUND") => {
+            Ok(ScanVerdict::Flagged(found.trim_end_matches(" FOUND").to_string()))
+        }
+        _ => Err(DoubledeckerError::Internal(format!("Content scan failed: {}", reply))),
+    }
+}
+
+/// `ClamdScanner` when `CLAMD_ADDR` is set, otherwise `NoopScanner`.
+pub fn scanner_from_env() -> std::sync::Arc<dyn ContentScanner> {
+    match std::env::var("CLAMD_ADDR").ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()) {
+        Some(addr) => std::sync::Arc::new(ClamdScanner::new(&addr)),
+        None => std::sync::Arc::new(NoopScanner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_clamd_streams_the_file_and_reads_the_verdict() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut command = [0u8; 10];
+            socket.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut received = Vec::new();
+            loop {
+                let len = socket.read_u32().await.unwrap() as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                socket.read_exact(&mut chunk).await.unwrap();
+                received.extend(chunk);
+            }
+            let reply: &[u8] = if received.windows(5).any(|w| w == b"EICAR") {
+                b"stream: Eicar-Signature FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            socket.write_all(reply).await.unwrap();
+        });
+
+        let content = [vec![b'a'; CLAMD_CHUNK_BYTES + 10], b"EICAR".to_vec()].concat();
+        let verdict = ClamdScanner::new(&addr).scan(&content).await.unwrap();
+        assert_eq!(verdict, ScanVerdict::Flagged("Eicar-Signature".to_string()));
+        server.await.unwrap();
+
+        assert_eq!(parse_clamd_reply("stream: OK").unwrap(), ScanVerdict::Clean);
+        assert!(parse_clamd_reply("INSTREAM size limit exceeded. ERROR").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::db::models::{NotifyChannel, ThresholdOp};
+use crate::utils::error::DoubledeckerError;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+/// How long a chat service gets to accept a message before the attempt counts as failed.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Cells longer than this are cut in summaries so tables stay readable in a chat window.
+const MAX_CELL_CHARS: usize = 32;
+
+/// Rows of a query result whose `column` compares true against `threshold`. Numbers and numeric
+/// strings (decimals are rendered as strings) are compared; other values never match. Errors when
+/// the result has no such column.
+pub fn threshold_breaches<'a>(
+    columns: &[String],
+    rows: &'a [Value],
+    column: &str,
+    op: ThresholdOp,
+    threshold: f64,
+) -> Result<Vec<&'a Value>, DoubledeckerError> {
+    let index = columns.iter().position(|c| c == column).ok_or_else(|| {
+        DoubledeckerError::BadRequest(format!("Threshold column '{}' is not in the pipeline's result", column))
+    })?;
+    Ok(rows
+        .iter()
+        .filter(|row| {
+            let value = match row.get(index) {
+                Some(Value::Number(n)) => n.as_f64(),
+                Some(Value::String(s)) => s.trim().parse().ok(),
+                _ => None,
+            };
+            value.is_some_and(|v| op.holds(v, threshold))
+        })
+        .collect())
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => truncate_cell(s),
+        other => truncate_cell(&other.to_string()),
+    }
+}
+
+fn truncate_cell(text: &str) -> String {
+    if text.chars().count() > MAX_CELL_CHARS {
+        let cut: String = text.chars().take(MAX_CELL_CHARS - 1).collect();
+        format!("{}…", cut)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A summary message: `headline`, then `rows` as a fixed-width table in a code block, which both
+/// Slack and Teams render in a monospace font.
+pub fn summary_text(headline: &str, columns: &[String], rows: &[&Value]) -> String {
+    if rows.is_empty() || columns.is_empty() {
+        return headline.to_string();
+    }
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| (0..columns.len()).map(|i| row.get(i).map(cell_text).unwrap_or_default()).collect())
+        .collect();
+    let header: Vec<String> = columns.iter().map(|c| truncate_cell(c)).collect();
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| cells.iter().map(|row| row[i].chars().count()).fold(name.chars().count(), usize::max))
+        .collect();
+    let line = |values: Vec<String>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{:<w$}", v, w = *w))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut table = vec![line(header)];
+    table.push(widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>().join("  "));
+    table.extend(cells.into_iter().map(line));
+    format!("{}\n```\n{}\n```", headline, table.join("\n"))
+}
+
+/// The JSON a channel's incoming webhook expects for a message with a title and a markdown body.
+pub fn webhook_payload(channel: NotifyChannel, title: &str, text: &str) -> Value {
+    match channel {
+        NotifyChannel::Slack => json!({ "text": format!("*{}*\n{}", title, text) }),
+        NotifyChannel::Teams => json!({
+            "@type": "MessageCard",
+            "@context": "https://schema.org/extensions",
+            "summary": title,
+            "title": title,
+            "text": text,
+        }),
+    }
+}
+
+/// Post a message to an incoming webhook. A rejected or unreachable webhook is an internal error,
+/// so a background job posting it is retried.
+pub async fn post_webhook(client: &reqwest::Client, url: &str, payload: &Value) -> Result<(), DoubledeckerError> {
+    let response = client
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(payload)
+        .send()
+        .await
+        // The URL is a credential; keep it out of the error
+        .map_err(|e| DoubledeckerError::Internal(format!("Webhook request failed: {}", e.without_url())))?;
+    if !response.status().is_success() {
+        return Err(DoubledeckerError::Internal(format!("Webhook rejected the message: {}", response.status())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_breaches() {
+        let columns = vec!["isrc".to_string(), "net_revenue".to_string()];
+        let rows = vec![json!(["A", 12.5]), json!(["B", "1500.25"]), json!(["C", null]), json!(["D", 999])];
+        let breaches = threshold_breaches(&columns, &rows, "net_revenue", ThresholdOp::Gte, 999.0).unwrap();
+        assert_eq!(breaches, vec![&rows[1], &rows[3]]);
+        assert!(threshold_breaches(&columns, &rows, "gross", ThresholdOp::Gt, 0.0).is_err());
+    }
+
+    #[test]
+    fn test_summary_text() {
+        let columns = vec!["order_id".to_string(), "sku".to_string()];
+        let rows = [json!([1042, null]), json!([7, "X".repeat(40)])];
+        let text = summary_text("2 rows", &columns, &rows.iter().collect::<Vec<_>>());
+        let expected = format!(
+            "2 rows\n```\norder_id  sku\n--------  {}\n1042\n7         {}…\n```",
+            "-".repeat(32),
+            "X".repeat(31)
+        );
+        assert_eq!(text, expected);
+        assert_eq!(summary_text("0 rows", &columns, &[]), "0 rows");
+    }
+}
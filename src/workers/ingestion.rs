@@ -1,11 +1,23 @@
+use crate::db::models::DatasetStatus;
 use crate::db::queries::{
-    get_dataset_by_id, update_dataset_status,
+    dataset_data_key, get_dataset_by_id, list_workspace_user_ids, set_dataset_source_columns,
+    set_dataset_inferred_schema, set_dataset_source_encoding, set_dataset_validation_report, update_dataset_status,
 };
-use crate::normalization::{DistributorSource, RoyaltyAdapter, unified_royalty_schema};
+use crate::config::upload_limits;
+use crate::engine::EngineProvider;
+use crate::normalization::{
+    DistributorSource, RoyaltyAdapter, SourceColumn, InferenceOptions, InferredSchema, infer_csv_schema,
+    sanitize_headers, sniff_tabular, transcode_to_utf8, unified_royalty_schema, validate_csv,
+};
+use crate::server::pipelines::apply_matching_pipelines;
+use crate::server::quality::apply_upload_quality_rules;
+use crate::utils::crypto::{decrypt_bytes, encrypt_file};
 use crate::utils::error::DoubledeckerError;
-use crate::utils::s3::S3Uploader;
+use crate::utils::events::{ActivityEventKind, EventBus};
+use crate::utils::s3::{ObjectStorage, ensure_key_in_prefix, quarantine_key, workspace_prefix};
+use crate::utils::scanner::{ContentScanner, ScanVerdict};
 use arrow::array::Array;
-use arrow_csv::reader::Format;
+use arrow::datatypes::{Field, Schema};
 use inngest::{
     client::Inngest,
     function::{FunctionOpts, Input, ServableFn, Trigger},
@@ -25,15 +37,35 @@ pub struct CatalogItem {
     pub track_title: String,
 }
 
+/// Result of converting an uploaded CSV to the unified Parquet layout.
+pub struct ConvertedCsv {
+    pub parquet: Vec<u8>,
+    pub total_rows: i64,
+    pub catalog_items: Vec<CatalogItem>,
+    /// The file's headers and the sanitized names they were read under
+    pub source_columns: Vec<SourceColumn>,
+}
+
+/// Convert `csv_bytes` to the unified layout, reading the file's columns as typed by `raw_schema`
+/// (see `infer_csv_schema`).
 pub fn process_csv_and_extract_catalog(
     csv_bytes: &[u8],
     adapter: &dyn RoyaltyAdapter,
-) -> Result<(Vec<u8>, i64, Vec<CatalogItem>), DoubledeckerError> {
-    let mut cursor = std::io::Cursor::new(csv_bytes);
-    let format = Format::default().with_header(true);
-    let (inferred_schema, _) = format.infer_schema(&mut cursor, Some(100))
-        .map_err(|e| DoubledeckerError::Internal(format!("CSV schema infer error: {}", e)))?;
-    cursor.set_position(0);
+    raw_schema: &Schema,
+) -> Result<ConvertedCsv, DoubledeckerError> {
+    let cursor = std::io::Cursor::new(csv_bytes);
+
+    // Read the columns under sanitized, unique names; the header row itself is skipped by the reader
+    let raw_names: Vec<&str> = raw_schema.fields().iter().map(|f| f.name().as_str()).collect();
+    let source_columns = sanitize_headers(&raw_names);
+    let inferred_schema = Schema::new(
+        raw_schema
+            .fields()
+            .iter()
+            .zip(&source_columns)
+            .map(|(field, column)| field.as_ref().clone().with_name(column.normalized.clone()))
+            .collect::<Vec<Field>>(),
+    );
     let reader = arrow_csv::ReaderBuilder::new(Arc::new(inferred_schema))
         .with_header(true)
         .build(cursor)
@@ -57,30 +89,29 @@ pub fn process_csv_and_extract_catalog(
         let mut upc_vec: Vec<Option<String>> = vec![None; batch.num_rows()];
 
         for (idx, field) in batch.schema().fields().iter().enumerate() {
-            let name_lower = field.name().to_lowercase();
-            let name_trimmed = name_lower.trim();
-            if name_trimmed == "artist" || name_trimmed == "artist name" || name_trimmed == "band" || name_trimmed == "performer" {
+            let name_trimmed = field.name().as_str();
+            if ["artist", "artist_name", "band", "performer"].contains(&name_trimmed) {
                 if let Some(str_arr) = batch.column(idx).as_any().downcast_ref::<arrow::array::StringArray>() {
-                    for i in 0..batch.num_rows() {
+                    for (i, slot) in artist_vec.iter_mut().enumerate() {
                         if !str_arr.is_null(i) && !str_arr.value(i).trim().is_empty() {
-                            artist_vec[i] = str_arr.value(i).trim().to_string();
+                            *slot = str_arr.value(i).trim().to_string();
                         }
                     }
                 }
-            } else if ["album", "album title", "release", "release title", "project", "project title", "album name", "release name"].contains(&name_trimmed) {
+            } else if ["album", "album_title", "release", "release_title", "project", "project_title", "album_name", "release_name"].contains(&name_trimmed) {
                 if let Some(str_arr) = batch.column(idx).as_any().downcast_ref::<arrow::array::StringArray>() {
-                    for i in 0..batch.num_rows() {
+                    for (i, slot) in album_vec.iter_mut().enumerate() {
                         if !str_arr.is_null(i) && !str_arr.value(i).trim().is_empty() {
-                            album_vec[i] = Some(str_arr.value(i).trim().to_string());
+                            *slot = Some(str_arr.value(i).trim().to_string());
                         }
                     }
                 }
-            } else if ["upc", "upc code", "barcode", "gtin", "ean", "album upc", "release upc", "ean/upc", "upc/ean", "bar code", "upc/barcode"].contains(&name_trimmed) {
-                if let Some(str_arr) = batch.column(idx).as_any().downcast_ref::<arrow::array::StringArray>() {
-                    for i in 0..batch.num_rows() {
-                        if !str_arr.is_null(i) && !str_arr.value(i).trim().is_empty() {
-                            upc_vec[i] = Some(str_arr.value(i).trim().to_string());
-                        }
+            } else if ["upc", "upc_code", "barcode", "gtin", "ean", "album_upc", "release_upc", "ean_upc", "upc_ean", "bar_code", "upc_barcode"].contains(&name_trimmed)
+                && let Some(str_arr) = batch.column(idx).as_any().downcast_ref::<arrow::array::StringArray>()
+            {
+                for (i, slot) in upc_vec.iter_mut().enumerate() {
+                    if !str_arr.is_null(i) && !str_arr.value(i).trim().is_empty() {
+                        *slot = Some(str_arr.value(i).trim().to_string());
                     }
                 }
             }
@@ -132,25 +163,41 @@ pub fn process_csv_and_extract_catalog(
         .close()
         .map_err(|e| DoubledeckerError::Internal(format!("Parquet writer close error: {}", e)))?;
 
-    Ok((buffer, total_rows, discovered_items.into_iter().collect()))
+    Ok(ConvertedCsv {
+        parquet: buffer,
+        total_rows,
+        catalog_items: discovered_items.into_iter().collect(),
+        source_columns,
+    })
 }
 
 pub fn register_ingestion_workflow(
     client: &Inngest,
     db_pool: PgPool,
-    uploader: Arc<S3Uploader>,
-) -> ServableFn<Value, DoubledeckerError> {
+    uploader: Arc<dyn ObjectStorage>,
+    scanner: Arc<dyn ContentScanner>,
+    engine: Arc<EngineProvider>,
+    events: EventBus,
+) -> ServableFn<Value, inngest::result::Error> {
     client.create_function(
         FunctionOpts::new("process-dataset").name("Process Royalty Dataset"),
         Trigger::event("dataset/uploaded"),
         move |input: Input<Value>, step: StepTool| {
             let db_pool = db_pool.clone();
             let uploader = uploader.clone();
+            let scanner = scanner.clone();
+            let engine = engine.clone();
+            let events = events.clone();
             async move {
                 let data = &input.event.data;
                 let dataset_id_str = data.get("dataset_id").and_then(|v| v.as_str()).unwrap_or_default();
                 let workspace_id_str = data.get("workspace_id").and_then(|v| v.as_str()).unwrap_or_default();
                 let staging_key = data.get("staging_key").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+                let strict = data.get("strict").and_then(|v| v.as_bool()).unwrap_or(false);
+                let inference: InferenceOptions = data
+                    .get("inference")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_default();
 
                 let dataset_id = uuid::Uuid::parse_str(dataset_id_str).map_err(|e| {
                     inngest::result::Error::Dev(inngest::result::DevError::Basic(format!("Invalid dataset_id: {}", e)))
@@ -166,7 +213,7 @@ pub fn register_ingestion_workflow(
                     let db_pool = db_pool.clone();
                     async move {
                         tokio::spawn(async move {
-                            let _ = update_dataset_status(&db_pool, dataset_id, "PROCESSING", 0, None).await;
+                            let _ = update_dataset_status(&db_pool, dataset_id, DatasetStatus::Processing, None, None).await;
                             Ok::<_, DoubledeckerError>(json!({ "status": "PROCESSING" }))
                         })
                         .await
@@ -174,10 +221,13 @@ pub fn register_ingestion_workflow(
                     }
                 }).await?;
 
-                // Step 2: Download CSV, normalize to Parquet, upload Parquet, delete staging
-                let (total_rows, discovered_items) = step.run(&format!("convert-csv-to-parquet-{}", step_prefix), || {
+                // Step 2: Download CSV, scan it, normalize to Parquet, upload Parquet, delete staging.
+                // `None` when the scan flagged the file and it was quarantined instead.
+                let converted: Option<(i64, Vec<_>)> = step.run(&format!("convert-csv-to-parquet-{}", step_prefix), || {
                     let db_pool = db_pool.clone();
                     let uploader = uploader.clone();
+                    let scanner = scanner.clone();
+                    let events = events.clone();
                     let staging_key = staging_key.clone();
                     async move {
                         // tokio::spawn is required here: aws-smithy-runtime futures (IdentityFuture,
@@ -185,25 +235,123 @@ pub fn register_ingestion_workflow(
                         // must return a Sync future. Spawning isolates the non-Sync S3 futures in a
                         // separate task; JoinHandle<T> is Send + Sync regardless of T.
                         tokio::spawn(async move {
-                            let dataset = get_dataset_by_id(&db_pool, workspace_id, dataset_id).await?;
-                            let csv_bytes = uploader.download_csv(&staging_key).await?;
+                            let converted = async {
+                                let dataset = get_dataset_by_id(&db_pool, workspace_id, dataset_id).await?;
+                                // Never read or delete objects of another tenant, whatever the event says
+                                ensure_key_in_prefix(&staging_key, &workspace_prefix(workspace_id))?;
+                                let mut csv_bytes = uploader.download_csv(&staging_key).await?;
+                                let data_key = dataset_data_key(&db_pool, dataset.encryption_key_id).await?;
+                                if let Some(data_key) = data_key.as_ref().filter(|_| dataset.staging_encrypted) {
+                                    csv_bytes = decrypt_bytes(data_key, &csv_bytes)?;
+                                }
+
+                                // Presigned and session uploads reach the server here first
+                                sniff_tabular(&csv_bytes)?;
+
+                                // A flagged file is moved out of staging as stored (still encrypted for a direct upload)
+                                if let ScanVerdict::Flagged(finding) = scanner.scan(&csv_bytes).await? {
+                                    let stored = uploader.download_csv(&staging_key).await?;
+                                    uploader.upload_csv_with_key(&quarantine_key(workspace_id, dataset_id), stored).await?;
+                                    let _ = uploader.delete_file(&staging_key).await;
+                                    let message = format!("Quarantined: the content scan found {}", finding);
+                                    update_dataset_status(&db_pool, dataset_id, DatasetStatus::Quarantined, None, Some(message.clone()))
+                                        .await?;
+                                    if let Ok(recipients) = list_workspace_user_ids(&db_pool, workspace_id).await {
+                                        events.publish_to_users(
+                                            &recipients,
+                                            ActivityEventKind::DatasetQuarantined,
+                                            Some(workspace_id),
+                                            json!({ "dataset_id": dataset_id, "status": DatasetStatus::Quarantined, "error": message }),
+                                        );
+                                    }
+                                    return Ok(None);
+                                }
 
-                            let source = DistributorSource::from_str_lenient(&dataset.distributor_source)
-                                .unwrap_or_else(|| DistributorSource::detect_from_csv_bytes(&csv_bytes));
-                            let adapter: Box<dyn RoyaltyAdapter> = source.to_adapter();
+                                // Direct uploads were transcoded and scanned when received; presigned ones are handled here
+                                let (csv_bytes, encoding) = transcode_to_utf8(csv_bytes);
+                                if dataset.source_encoding.is_none() {
+                                    set_dataset_source_encoding(&db_pool, dataset_id, encoding).await?;
+                                }
+                                let report = match dataset.validation_report {
+                                    Some(report) => report.0,
+                                    None => {
+                                        let report = validate_csv(&csv_bytes);
+                                        set_dataset_validation_report(&db_pool, dataset_id, &report).await?;
+                                        report
+                                    }
+                                };
+                                if strict && !report.is_clean() {
+                                    return Err(DoubledeckerError::FileValidation(Box::new(report)));
+                                }
+                                upload_limits().check_shape(report.total_columns, report.total_rows)?;
+                                let nearing =
+                                    upload_limits().nearing(csv_bytes.len() as u64, report.total_columns, report.total_rows);
+                                if !nearing.is_empty()
+                                    && let Ok(recipients) = list_workspace_user_ids(&db_pool, workspace_id).await
+                                {
+                                    let limits: Vec<Value> = nearing
+                                        .iter()
+                                        .map(|(limit, max, actual)| json!({ "limit": limit, "max": max, "actual": actual }))
+                                        .collect();
+                                    events.publish_to_users(
+                                        &recipients,
+                                        ActivityEventKind::QuotaWarning,
+                                        Some(workspace_id),
+                                        json!({ "dataset_id": dataset_id, "limits": limits }),
+                                    );
+                                }
+
+                                let source = DistributorSource::from_str_lenient(&dataset.distributor_source)
+                                    .unwrap_or_else(|| DistributorSource::detect_from_csv_bytes(&csv_bytes));
+                                let adapter: Box<dyn RoyaltyAdapter> = source.to_adapter();
+
+                                // A full-scan schema from an earlier attempt is reused; sampling is cheap enough to redo
+                                let raw_schema = match &dataset.inferred_schema {
+                                    Some(stored) if stored.full_scan => stored.to_schema()?,
+                                    _ => {
+                                        let schema = infer_csv_schema(&csv_bytes, &inference)?;
+                                        let stored = InferredSchema::from_schema(&schema, inference.full_scan_inference);
+                                        set_dataset_inferred_schema(&db_pool, dataset_id, &stored).await?;
+                                        schema
+                                    }
+                                };
 
-                            let (parquet_bytes, total_rows, discovered_items) = process_csv_and_extract_catalog(&csv_bytes, &*adapter)?;
+                                let converted = process_csv_and_extract_catalog(&csv_bytes, &*adapter, &raw_schema)?;
 
-                            let s3_parquet_key = dataset.s3_parquet_key.clone();
-                            uploader.upload_parquet(&s3_parquet_key, parquet_bytes).await?;
-                            let _ = uploader.delete_file(&staging_key).await;
+                                let s3_parquet_key = dataset.s3_parquet_key.clone();
+                                let parquet = match &data_key {
+                                    Some(data_key) => encrypt_file(data_key, &converted.parquet)?,
+                                    None => converted.parquet,
+                                };
+                                uploader.upload_parquet(&s3_parquet_key, parquet).await?;
+                                set_dataset_source_columns(&db_pool, dataset_id, &converted.source_columns).await?;
+                                let _ = uploader.delete_file(&staging_key).await;
 
-                            Ok::<_, DoubledeckerError>((total_rows, discovered_items))
+                                Ok::<_, DoubledeckerError>(Some((converted.total_rows, converted.catalog_items)))
+                            }
+                            .await;
+
+                            // Surface the failure on the dataset so clients stop waiting for READY
+                            if let Err(e) = &converted {
+                                let _ = update_dataset_status(&db_pool, dataset_id, DatasetStatus::Failed, None, Some(e.to_string())).await;
+                                if let Ok(recipients) = list_workspace_user_ids(&db_pool, workspace_id).await {
+                                    events.publish_to_users(
+                                        &recipients,
+                                        ActivityEventKind::DatasetFailed,
+                                        Some(workspace_id),
+                                        json!({ "dataset_id": dataset_id, "status": DatasetStatus::Failed, "error": e.to_string() }),
+                                    );
+                                }
+                            }
+                            converted
                         })
                         .await
                         .map_err(|e| DoubledeckerError::Internal(e.to_string()))?
                     }
                 }).await?;
+                let Some((total_rows, discovered_items)) = converted else {
+                    return Ok(json!({ "success": false, "quarantined": true }));
+                };
 
                 // Step 3: Auto-Catalog Discovery
                 let _ = step.run(&format!("auto-catalog-discovery-{}", step_prefix), || {
@@ -211,33 +359,33 @@ pub fn register_ingestion_workflow(
                     let discovered_items = discovered_items.clone();
                     async move {
                         tokio::spawn(async move {
-                            if !discovered_items.is_empty() {
-                                if let Ok(workspace) = sqlx::query_as::<_, crate::db::models::Workspace>(
-                                    "SELECT id, owner_user_id, name, storage_used_bytes, created_at, updated_at FROM workspaces WHERE id = $1"
+                            if !discovered_items.is_empty()
+                                && let Ok(workspace) = sqlx::query_as::<_, crate::db::models::Workspace>(
+                                    "SELECT w.id, w.owner_user_id, w.name, s.storage_used_bytes, w.created_at, w.updated_at FROM workspaces w JOIN workspace_stats s ON s.workspace_id = w.id WHERE w.id = $1"
                                 )
                                 .bind(workspace_id)
                                 .fetch_one(&db_pool)
-                                .await {
-                                    let owner_user_id = workspace.owner_user_id;
-                                    for item in discovered_items {
-                                        if let Ok(artist) = crate::db::queries::upsert_artist(&db_pool, owner_user_id, item.artist).await {
-                                            let album_id = if let Some(album_title) = item.album_title {
-                                                crate::db::queries::upsert_album(
-                                                    &db_pool,
-                                                    owner_user_id,
-                                                    artist.id,
-                                                    album_title,
-                                                    item.upc.clone(),
-                                                    None,
-                                                )
-                                                .await
-                                                .map(|a| a.id)
-                                                .ok()
-                                            } else {
-                                                None
-                                            };
-                                            let _ = crate::db::queries::upsert_track(&db_pool, owner_user_id, artist.id, album_id, item.isrc, item.track_title).await;
-                                        }
+                                .await
+                            {
+                                let owner_user_id = workspace.owner_user_id;
+                                for item in discovered_items {
+                                    if let Ok(artist) = crate::db::queries::upsert_artist(&db_pool, owner_user_id, item.artist).await {
+                                        let album_id = if let Some(album_title) = item.album_title {
+                                            crate::db::queries::upsert_album(
+                                                &db_pool,
+                                                owner_user_id,
+                                                artist.id,
+                                                album_title,
+                                                item.upc.clone(),
+                                                None,
+                                            )
+                                            .await
+                                            .map(|a| a.id)
+                                            .ok()
+                                        } else {
+                                            None
+                                        };
+                                        let _ = crate::db::queries::upsert_track(&db_pool, owner_user_id, artist.id, album_id, item.isrc, item.track_title).await;
                                     }
                                 }
                             }
@@ -248,12 +396,21 @@ pub fn register_ingestion_workflow(
                     }
                 }).await?;
 
-                // Step 4: Update status to READY
+                // Step 4: Update status to READY and notify workspace users
                 let _ = step.run(&format!("set-status-ready-{}", step_prefix), || {
                     let db_pool = db_pool.clone();
+                    let events = events.clone();
                     async move {
                         tokio::spawn(async move {
-                            let _ = update_dataset_status(&db_pool, dataset_id, "READY", total_rows, None).await;
+                            let _ = update_dataset_status(&db_pool, dataset_id, DatasetStatus::Ready, Some(total_rows), None).await;
+                            if let Ok(recipients) = list_workspace_user_ids(&db_pool, workspace_id).await {
+                                events.publish_to_users(
+                                    &recipients,
+                                    ActivityEventKind::DatasetProcessed,
+                                    Some(workspace_id),
+                                    json!({ "dataset_id": dataset_id, "status": "READY", "row_count": total_rows }),
+                                );
+                            }
                             Ok::<_, DoubledeckerError>(json!({ "status": "READY" }))
                         })
                         .await
@@ -261,8 +418,61 @@ pub fn register_ingestion_workflow(
                     }
                 }).await?;
 
+                // Step 5: Apply pipelines whose dataset pattern matches the filename
+                let _ = step.run(&format!("apply-pipelines-{}", step_prefix), || {
+                    let db_pool = db_pool.clone();
+                    let engine = engine.clone();
+                    async move {
+                        tokio::spawn(async move {
+                            let dataset = get_dataset_by_id(&db_pool, workspace_id, dataset_id).await?;
+                            let runs = apply_matching_pipelines(&db_pool, &engine, workspace_id, dataset_id, &dataset.filename).await?;
+                            Ok::<_, DoubledeckerError>(json!({ "pipeline_runs": runs.len() }))
+                        })
+                        .await
+                        .map_err(|e| DoubledeckerError::Internal(e.to_string()))?
+                    }
+                }).await?;
+
+                // Step 6: Check the dataset against the workspace's quality rules that run on upload
+                let _ = step.run(&format!("check-quality-rules-{}", step_prefix), || {
+                    let db_pool = db_pool.clone();
+                    let engine = engine.clone();
+                    async move {
+                        tokio::spawn(async move {
+                            let run = apply_upload_quality_rules(&db_pool, &engine, workspace_id, dataset_id).await?;
+                            Ok::<_, DoubledeckerError>(json!({ "quality_run": run.as_ref().map(|r| r.id), "passed": run.map(|r| r.passed) }))
+                        })
+                        .await
+                        .map_err(|e| DoubledeckerError::Internal(e.to_string()))?
+                    }
+                }).await?;
+
                 Ok(json!({ "success": true, "rows": total_rows }))
             }
         },
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normalization::DistroKidAdapter;
+
+    #[test]
+    fn test_quoted_fields_survive_conversion() {
+        let csv = "ISRC,Song Title,Store,Reporting Month,Earnings (USD),Song Title\n\
+                   US1234567890,\"Line one\nline two, with comma\",Spotify,2026-06,1.50,dup\n\
+                   US0987654321,\"She said \"\"hi\"\"\",Apple,2026-06,2.00,dup\n";
+        let schema = infer_csv_schema(csv.as_bytes(), &InferenceOptions::default()).unwrap();
+        let converted = process_csv_and_extract_catalog(csv.as_bytes(), &DistroKidAdapter, &schema).unwrap();
+
+        assert_eq!(converted.total_rows, 2);
+        let mut titles: Vec<String> = converted.catalog_items.into_iter().map(|i| i.track_title).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["Line one\nline two, with comma", "She said \"hi\""]);
+
+        let normalized: Vec<&str> = converted.source_columns.iter().map(|c| c.normalized.as_str()).collect();
+        assert_eq!(normalized[1], "song_title");
+        assert_eq!(normalized[5], "song_title_2");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::db::models::BackgroundJob;
+use crate::db::queries::{
+    claim_next_job, complete_job, fail_expired_jobs, fail_job, list_workspace_user_ids, renew_job_lease,
+};
+use crate::utils::error::DoubledeckerError;
+use crate::utils::events::{ActivityEventKind, EventBus};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long an idle worker waits before looking for due jobs again.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// A RUNNING job whose lease has not been renewed within this long is assumed orphaned by a dead
+/// worker and claimed again. Workers renew the leases of the jobs they run every
+/// `LEASE_RENEW_INTERVAL`.
+const JOB_LEASE_SECS: i64 = 5 * 60;
+/// Delay before the first retry; doubled for every further attempt.
+const RETRY_BASE_SECS: i64 = 30;
+const MAX_RETRY_SECS: i64 = 60 * 60;
+/// How often a running job's lease is renewed, which also finds out whether it was cancelled.
+const LEASE_RENEW_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Runs one kind of background job. A job can run more than once (a retry after an error, or a
+/// second claim after its worker died), so handlers should tolerate repeats.
+#[async_trait::async_trait]
+pub trait JobHandler: Send + Sync {
+    /// Do the work; the returned value is stored as the job's `result`.
+    async fn run(&self, job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError>;
+}
+
+/// Claims jobs from `background_jobs` and runs them with the handler registered for their kind.
+pub struct JobRunner {
+    pool: PgPool,
+    events: EventBus,
+    handlers: HashMap<&'static str, Arc<dyn JobHandler>>,
+}
+
+impl JobRunner {
+    pub fn new(pool: PgPool, events: EventBus) -> Self {
+        Self {
+            pool,
+            events,
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn register(mut self, kind: &'static str, handler: impl JobHandler + 'static) -> Self {
+        self.handlers.insert(kind, Arc::new(handler));
+        self
+    }
+
+    /// Claim and run one due job. Returns whether there was one.
+    pub async fn run_next(&self) -> Result<bool, DoubledeckerError> {
+        for job in fail_expired_jobs(&self.pool, JOB_LEASE_SECS).await? {
+            self.notify_finished(&job, "FAILED", job.last_error.as_deref()).await;
+        }
+        let Some(job) = claim_next_job(&self.pool, JOB_LEASE_SECS).await? else {
+            return Ok(false);
+        };
+
+        let Some(handler) = self.handlers.get(job.kind.as_str()) else {
+            let message = format!("No handler for job kind '{}'", job.kind);
+            fail_job(&self.pool, job.id, &message, None).await?;
+            self.notify_finished(&job, "FAILED", Some(&message)).await;
+            return Ok(true);
+        };
+
+        // A cancelled job's work is dropped where it is; the queries it was running stop with it
+        let outcome = tokio::select! {
+            outcome = handler.run(&job) => outcome,
+            _ = self.cancelled(&job) => return Ok(true),
+        };
+        match outcome {
+            Ok(result) => {
+                if complete_job(&self.pool, job.id, result).await? {
+                    self.notify_finished(&job, "SUCCEEDED", None).await;
+                }
+            }
+            Err(e) => {
+                // Requests the job itself got wrong fail the same way every time; only retry the rest
+                let retry = (e.status_code().is_server_error() || e.status_code().as_u16() == 429)
+                    .then(|| retry_delay_secs(job.attempts));
+                let message = e.message();
+                if fail_job(&self.pool, job.id, &message, retry).await? {
+                    self.notify_finished(&job, "FAILED", Some(&message)).await;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Tell whoever queued the job that it is done, or the workspace's members for one queued by a
+    /// schedule. Retries in between are not announced.
+    async fn notify_finished(&self, job: &BackgroundJob, status: &str, error: Option<&str>) {
+        let recipients = match (job.created_by, job.workspace_id) {
+            (Some(user_id), _) => vec![user_id],
+            (None, Some(workspace_id)) => list_workspace_user_ids(&self.pool, workspace_id).await.unwrap_or_default(),
+            (None, None) => return,
+        };
+        self.events.publish_to_users(
+            &recipients,
+            ActivityEventKind::JobFinished,
+            job.workspace_id,
+            json!({ "job_id": job.id, "kind": job.kind, "status": status, "error": error }),
+        );
+    }
+
+    /// Renews `job`'s lease while it runs; resolves once it is no longer RUNNING, e.g. cancelled.
+    /// A failed renewal is tried again on the next tick.
+    async fn cancelled(&self, job: &BackgroundJob) {
+        loop {
+            tokio::time::sleep(LEASE_RENEW_INTERVAL).await;
+            if !renew_job_lease(&self.pool, job.id).await.unwrap_or(true) {
+                return;
+            }
+        }
+    }
+
+    /// Start `workers` loops that each run jobs back to back while any are due, then poll.
+    pub fn spawn(self, workers: usize) {
+        let runner = Arc::new(self);
+        for _ in 0..workers.max(1) {
+            let runner = runner.clone();
+            tokio::spawn(async move {
+                loop {
+                    match runner.run_next().await {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(e) => eprintln!("Background job runner failed: {}", e),
+                    }
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+            });
+        }
+    }
+}
+
+/// Wait before retrying after the given attempt (1-based): 30s, 60s, 120s, ... up to an hour.
+pub fn retry_delay_secs(attempt: i32) -> i64 {
+    let doublings = (attempt - 1).clamp(0, 16) as u32;
+    (RETRY_BASE_SECS << doublings).min(MAX_RETRY_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_backs_off_to_a_cap() {
+        let delays: Vec<i64> = (1..=9).map(retry_delay_secs).collect();
+        assert_eq!(delays, vec![30, 60, 120, 240, 480, 960, 1920, 3600, 3600]);
+        assert_eq!(retry_delay_secs(0), 30);
+        assert_eq!(retry_delay_secs(i32::MAX), MAX_RETRY_SECS);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::db::queries::enqueue_job_unless_pending;
+use crate::server::uploads::{UPLOAD_SESSION_CLEANUP_JOB, UPLOAD_SESSION_CLEANUP_JOB_ATTEMPTS};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// How often housekeeping jobs are queued.
+const TICK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically queue the housekeeping jobs, unless one is still pending from this or another
+/// replica. The job workers run them.
+pub fn spawn_maintenance_ticker(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) =
+                enqueue_job_unless_pending(&pool, UPLOAD_SESSION_CLEANUP_JOB, UPLOAD_SESSION_CLEANUP_JOB_ATTEMPTS).await
+            {
+                eprintln!("Queueing the upload session cleanup failed: {}", e);
+            }
+        }
+    });
+}
@@ -1,2 +1,53 @@
 pub mod ingestion;
+pub mod jobs;
+pub mod maintenance;
+pub mod outbox;
+pub mod schedules;
 pub use ingestion::register_ingestion_workflow;
+pub use jobs::{JobHandler, JobRunner};
+
+use crate::server::compaction::{DatasetCompactionJobHandler, DATASET_COMPACTION_JOB};
+use crate::server::pipelines::{PipelineRunJobHandler, PIPELINE_RUN_JOB};
+use crate::server::schedules::{ScheduledPipelineJobHandler, PIPELINE_SCHEDULE_JOB};
+use crate::server::state::AppState;
+use crate::server::uploads::{UploadSessionCleanupJobHandler, UPLOAD_SESSION_CLEANUP_JOB};
+use crate::utils::email::EmailSender;
+
+/// Start the outbox dispatcher, the schedule and maintenance tickers and `job_workers` background job
+/// workers. Every replica runs them: outbox rows, schedules and jobs are claimed with SKIP LOCKED, so
+/// replicas never process the same one.
+pub fn spawn_background_workers(state: &AppState, job_workers: usize) {
+    // Retry ingestion events that could not be sent when their upload was recorded
+    outbox::spawn_outbox_dispatcher(state.db_pool.clone(), state.inngest_client.clone());
+
+    schedules::spawn_schedule_ticker(state.db_pool.clone());
+    maintenance::spawn_maintenance_ticker(state.db_pool.clone());
+
+    JobRunner::new(state.db_pool.clone(), state.events.clone())
+        .register(
+            PIPELINE_RUN_JOB,
+            PipelineRunJobHandler { pool: state.db_pool.clone(), engine: state.engine.clone() },
+        )
+        .register(
+            PIPELINE_SCHEDULE_JOB,
+            ScheduledPipelineJobHandler {
+                pool: state.db_pool.clone(),
+                engine: state.engine.clone(),
+                http: reqwest::Client::new(),
+                email: EmailSender::from_env(),
+            },
+        )
+        .register(
+            DATASET_COMPACTION_JOB,
+            DatasetCompactionJobHandler {
+                pool: state.db_pool.clone(),
+                engine: state.engine.clone(),
+                uploader: state.uploader.clone(),
+            },
+        )
+        .register(
+            UPLOAD_SESSION_CLEANUP_JOB,
+            UploadSessionCleanupJobHandler { pool: state.db_pool.clone(), uploader: state.uploader.clone() },
+        )
+        .spawn(job_workers);
+}
@@ -0,0 +1,62 @@
+use crate::db::queries::{
+    claim_pending_outbox_events, mark_outbox_event_dispatched, record_outbox_event_failure,
+};
+use crate::utils::error::DoubledeckerError;
+use inngest::client::Inngest;
+use inngest::event::Event;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the background dispatcher retries events that could not be sent right away.
+const DISPATCH_INTERVAL: Duration = Duration::from_secs(15);
+const DISPATCH_BATCH: i64 = 100;
+
+/// Send pending outbox events to Inngest. Each event carries its outbox id as the Inngest event id,
+/// so a send that succeeded but wasn't marked (crash, lost commit) is deduplicated on retry.
+pub async fn dispatch_pending_events(pool: &PgPool, client: &Inngest) -> Result<usize, DoubledeckerError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+
+    let pending = claim_pending_outbox_events(&mut tx, DISPATCH_BATCH).await?;
+    let mut sent = 0;
+    for event in pending {
+        let evt = Event::new(&event.event_name, event.payload).id(&format!("outbox-{}", event.id));
+        match client.send_event(&evt).await {
+            Ok(_) => {
+                mark_outbox_event_dispatched(&mut tx, event.id).await?;
+                sent += 1;
+            }
+            Err(e) => record_outbox_event_failure(&mut tx, event.id, &format!("{:?}", e)).await?,
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| DoubledeckerError::DatabaseError(e.to_string()))?;
+    Ok(sent)
+}
+
+/// Dispatch right after a commit without making the request wait on Inngest.
+pub fn dispatch_soon(pool: PgPool, client: Arc<Inngest>) {
+    tokio::spawn(async move {
+        if let Err(e) = dispatch_pending_events(&pool, &client).await {
+            eprintln!("Outbox dispatch failed: {}", e);
+        }
+    });
+}
+
+/// Periodically retry anything left in the outbox.
+pub fn spawn_outbox_dispatcher(pool: PgPool, client: Arc<Inngest>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DISPATCH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = dispatch_pending_events(&pool, &client).await {
+                eprintln!("Outbox dispatch failed: {}", e);
+            }
+        }
+    });
+}
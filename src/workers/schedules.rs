@@ -0,0 +1,21 @@
+use crate::db::queries::enqueue_due_schedules;
+use crate::server::schedules::{PIPELINE_SCHEDULE_JOB, PIPELINE_SCHEDULE_JOB_ATTEMPTS};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// How often due schedules are looked for; a run can start up to this late.
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+const TICK_BATCH: i64 = 100;
+
+/// Periodically queue a `pipeline.schedule` job for every due schedule. The job workers run them.
+pub fn spawn_schedule_ticker(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = enqueue_due_schedules(&pool, PIPELINE_SCHEDULE_JOB, PIPELINE_SCHEDULE_JOB_ATTEMPTS, TICK_BATCH).await {
+                eprintln!("Queueing scheduled pipelines failed: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,2138 @@
+//! End-to-end flows through the HTTP API. They need Postgres: run with
+//! `cargo test --test end_to_end -- --ignored` where Docker is available, or point
+//! `TEST_DATABASE_URL` at a server.
+
+mod common;
+
+use axum::http::{StatusCode, header};
+use common::{ADMIN_EMAIL, TestApp};
+use doubledecker::config::EngineConfig;
+use doubledecker::utils::error::DoubledeckerError;
+use doubledecker::utils::llm::LlmProvider;
+use futures::StreamExt;
+use object_store::ObjectStore;
+use object_store::path::Path;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const DISTROKID_CSV: &str = "ISRC,Song Title,Store,Reporting Month,Earnings (USD)\n\
+                             US1234567890,First Song,Spotify,2026-06,1.50\n\
+                             US0987654321,Second Song,Apple,2026-06,2.25\n";
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_signup_upload_query_download() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("e2e@example.com").await;
+
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    assert_eq!(workspace.status, StatusCode::OK, "{}", workspace.text());
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    assert_eq!(upload.status, StatusCode::OK, "{}", upload.text());
+    assert_eq!(upload.json()["status"], "QUEUED");
+    assert_eq!(upload.json()["row_count"], 2, "the row count is known once the upload is validated");
+
+    app.run_ingestion().await;
+
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    assert_eq!(datasets.status, StatusCode::OK);
+    let dataset = &datasets.json()["data"][0];
+    assert_eq!(dataset["status"], "READY", "{}", dataset);
+    assert_eq!(dataset["row_count"], 2);
+    let parquet_key = Path::from(dataset["s3_parquet_key"].as_str().unwrap());
+    assert!(app.storage.head(&parquet_key).await.is_ok(), "ingestion writes the Parquet file");
+
+    let query = json!({ "sql": "SELECT COUNT(*) AS tracks FROM royalty_data" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query.clone())
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    assert_eq!(result.json()["rows"], json!([[2]]), "{}", result.text());
+
+    let download = app
+        .post_json(&format!("/api/workspaces/{}/analytics/download", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(download.status, StatusCode::OK, "{}", download.text());
+    assert!(download.headers[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/csv"));
+    assert_eq!(download.text().lines().collect::<Vec<_>>(), vec!["tracks", "2"]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_workspace_data_needs_membership() {
+    let app = TestApp::spawn().await;
+    let owner = app.signup("owner@example.com").await;
+    let outsider = app.signup("outsider@example.com").await;
+
+    let workspace = app.post_json("/api/workspaces", Some(&owner), json!({ "name": "Private" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &outsider).await;
+    assert_eq!(datasets.status, StatusCode::UNAUTHORIZED, "{}", datasets.text());
+    let query = app
+        .post_json(
+            &format!("/api/workspaces/{}/analytics/query", workspace_id),
+            Some(&outsider),
+            json!({ "sql": "SELECT 1" }),
+        )
+        .await;
+    assert_eq!(query.status, StatusCode::UNAUTHORIZED, "{}", query.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_demo_workspace_is_queryable() {
+    let app = TestApp::spawn().await;
+    let admin = app.signup(ADMIN_EMAIL).await;
+    let user = app.signup("newcomer@example.com").await;
+
+    let demo = app
+        .post_json("/admin/demo-workspaces", Some(&admin), json!({ "email": "newcomer@example.com" }))
+        .await;
+    assert_eq!(demo.status, StatusCode::OK, "{}", demo.text());
+    let demo = demo.json();
+    assert_eq!(demo["datasets"].as_array().unwrap().len(), 2);
+    let workspace_id = demo["workspace"]["id"].as_str().unwrap().to_string();
+
+    app.run_ingestion().await;
+
+    let query = json!({ "sql": "SELECT COUNT(DISTINCT isrc) AS tracks FROM royalty_data" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&user), query)
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    assert_eq!(result.json()["rows"], json!([[6]]), "{}", result.text());
+
+    let pipelines = app.get(&format!("/api/workspaces/{}/pipelines", workspace_id), &user).await;
+    assert_eq!(pipelines.json()["data"].as_array().unwrap().len(), 3);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_cli_upload_and_query_to_parquet() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("cli@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Scripted" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let url = app.serve().await;
+
+    let dir = std::env::temp_dir().join(format!("dd-cli-{}", workspace_id));
+    std::fs::create_dir_all(&dir).unwrap();
+    let csv = dir.join("june.csv");
+    std::fs::write(&csv, DISTROKID_CSV).unwrap();
+    let cli = |args: &[&str]| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_doubledecker-cli"));
+        command.args(["--url", &url]).args(args).env("DOUBLEDECKER_TOKEN", &token);
+        command
+    };
+
+    let upload = cli(&["upload", "--workspace", &workspace_id, csv.to_str().unwrap(), "--source", "distrokid"])
+        .output()
+        .await
+        .unwrap();
+    assert!(upload.status.success(), "{}", String::from_utf8_lossy(&upload.stderr));
+    app.run_ingestion().await;
+
+    let out = dir.join("result.parquet");
+    let query = cli(&["query", "--workspace", &workspace_id, "--sql", "SELECT isrc FROM royalty_data", "--out", out.to_str().unwrap()])
+        .output()
+        .await
+        .unwrap();
+    assert!(query.status.success(), "{}", String::from_utf8_lossy(&query.stderr));
+    let file = std::fs::File::open(&out).unwrap();
+    let rows = parquet::file::reader::SerializedFileReader::new(file).unwrap();
+    assert_eq!(parquet::file::reader::FileReader::metadata(&rows).file_metadata().num_rows(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_workspace_export_archive() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("archive@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label Backup" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let created = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+
+    let export = app
+        .post_json(&format!("/api/workspaces/{}/export", workspace_id), Some(&token), json!({ "include_files": true }))
+        .await;
+    assert_eq!(export.status, StatusCode::OK, "{}", export.text());
+    assert_eq!(export.headers[header::CONTENT_TYPE], "application/zip");
+    assert!(export.headers[header::CONTENT_DISPOSITION].to_str().unwrap().contains("label-backup-"));
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(export.body.to_vec())).unwrap();
+    let read_json = |archive: &mut zip::ZipArchive<_>, name: &str| -> serde_json::Value {
+        serde_json::from_reader(archive.by_name(name).unwrap()).unwrap()
+    };
+    assert_eq!(read_json(&mut archive, "manifest.json")["workspace_name"], "Label Backup");
+    let datasets = read_json(&mut archive, "datasets.json");
+    assert_eq!(datasets[0]["filename"], "june.csv");
+    assert_eq!(datasets[0]["row_count"], 2);
+    assert_eq!(read_json(&mut archive, "pipelines.json")[0]["name"], "By store");
+
+    let file = datasets[0]["file"].as_str().unwrap().to_string();
+    let mut parquet = Vec::new();
+    std::io::Read::read_to_end(&mut archive.by_name(&file).unwrap(), &mut parquet).unwrap();
+    assert!(parquet.starts_with(b"PAR1"));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_workspace_import_renames_conflicts() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("restore@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    let export = app
+        .post_json(&format!("/api/workspaces/{}/export", workspace_id), Some(&token), json!({ "include_files": true }))
+        .await;
+    let archive = ("archive", "label.zip", "application/zip");
+
+    let restored = app.upload_file("/api/workspaces/import", &token, archive, &export.body, &[("name", "Restored")]).await;
+    assert_eq!(restored.status, StatusCode::OK, "{}", restored.text());
+    let restored = restored.json();
+    assert_eq!(restored["workspace"]["name"], "Restored");
+    assert_eq!(restored["datasets"][0]["outcome"], "IMPORTED", "{}", restored);
+    assert_eq!(restored["pipelines"][0]["outcome"], "IMPORTED", "{}", restored);
+    let restored_id = restored["workspace"]["id"].as_str().unwrap();
+    let count = app
+        .post_json(
+            &format!("/api/workspaces/{}/analytics/query", restored_id),
+            Some(&token),
+            json!({ "sql": "SELECT COUNT(*) FROM royalty_data" }),
+        )
+        .await;
+    assert_eq!(count.json()["rows"], json!([[2]]), "{}", count.text());
+
+    let merged = app
+        .upload_file("/api/workspaces/import", &token, archive, &export.body, &[("workspace_id", &workspace_id)])
+        .await;
+    assert_eq!(merged.status, StatusCode::OK, "{}", merged.text());
+    let merged = merged.json();
+    assert_eq!(merged["datasets"][0]["outcome"], "RENAMED");
+    assert_eq!(merged["datasets"][0]["imported_as"], "june (2).csv");
+    assert_eq!(merged["pipelines"][0]["imported_as"], "By store (2)");
+
+    let skipped = app
+        .upload_file(
+            "/api/workspaces/import",
+            &token,
+            archive,
+            &export.body,
+            &[("workspace_id", &workspace_id), ("on_conflict", "skip")],
+        )
+        .await;
+    assert_eq!(skipped.json()["datasets"][0]["outcome"], "SKIPPED", "{}", skipped.text());
+
+    let outsider = app.signup("outsider@example.com").await;
+    let denied = app
+        .upload_file("/api/workspaces/import", &outsider, archive, &export.body, &[("workspace_id", &workspace_id)])
+        .await;
+    assert!(denied.status.is_client_error(), "{}", denied.text());
+
+    // A dataset over the upload limits (1000 columns by default) is skipped like an upload would be
+    let mut exported = zip::ZipArchive::new(std::io::Cursor::new(export.body.to_vec())).unwrap();
+    let mut wide = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for i in 0..exported.len() {
+        let mut entry = exported.by_index(i).unwrap();
+        let name = entry.name().to_string();
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut content).unwrap();
+        if name == "datasets.json" {
+            let mut datasets: serde_json::Value = serde_json::from_slice(&content).unwrap();
+            let column = datasets[0]["source_columns"][0].clone();
+            datasets[0]["source_columns"] = json!(vec![column; 1001]);
+            content = serde_json::to_vec(&datasets).unwrap();
+        }
+        wide.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut wide, &content).unwrap();
+    }
+    let wide = wide.finish().unwrap().into_inner();
+    let too_wide = app.upload_file("/api/workspaces/import", &token, archive, &wide, &[("name", "Too wide")]).await;
+    assert_eq!(too_wide.status, StatusCode::OK, "{}", too_wide.text());
+    assert_eq!(too_wide.json()["datasets"][0]["outcome"], "SKIPPED");
+    assert!(too_wide.json()["datasets"][0]["reason"].as_str().unwrap().contains("columns"), "{}", too_wide.text());
+    assert_eq!(too_wide.json()["pipelines"][0]["outcome"], "IMPORTED");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_pipeline_export_imports_into_another_workspace() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("portable@example.com").await;
+    let source = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Source" })).await;
+    let source_id = source.json()["id"].as_str().unwrap().to_string();
+    let target = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Target" })).await;
+    let target_id = target.json()["id"].as_str().unwrap().to_string();
+
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let created = app.post_json(&format!("/api/workspaces/{}/pipelines", source_id), Some(&token), pipeline).await;
+    let pipeline_id = created.json()["id"].as_str().unwrap().to_string();
+
+    let export = app.get(&format!("/api/workspaces/{}/pipelines/{}/export", source_id, pipeline_id), &token).await;
+    assert_eq!(export.status, StatusCode::OK, "{}", export.text());
+    assert!(export.headers[header::CONTENT_DISPOSITION].to_str().unwrap().contains("by-store.pipeline.json"));
+    let file = export.json();
+    assert_eq!(file["format"], "doubledecker.pipeline");
+    assert_eq!(file["version"], 1);
+
+    let imported = app.post_json(&format!("/api/workspaces/{}/pipelines/import", target_id), Some(&token), file).await;
+    assert_eq!(imported.status, StatusCode::OK, "{}", imported.text());
+    assert_eq!(imported.json()["workspace_id"], target_id);
+    assert_eq!(imported.json()["query"]["dimensions"], json!(["platform"]));
+
+    let duplicate = app
+        .post_json(
+            &format!("/api/workspaces/{}/pipelines/{}/duplicate", source_id, pipeline_id),
+            Some(&token),
+            json!({}),
+        )
+        .await;
+    assert_eq!(duplicate.status, StatusCode::OK, "{}", duplicate.text());
+    assert_eq!(duplicate.json()["name"], "By store (copy)");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_dashboard_runs_all_tiles() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("dashboards@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    let pipeline_id = pipeline.json()["id"].as_str().unwrap().to_string();
+
+    let dashboard = json!({
+        "name": "Overview",
+        "tiles": [
+            { "pipeline_id": pipeline_id, "visualization": "BAR" },
+            { "title": "June", "pipeline_id": pipeline_id, "dataset_id": dataset_id, "visualization": "TABLE" }
+        ]
+    });
+    let created = app.post_json(&format!("/api/workspaces/{}/dashboards", workspace_id), Some(&token), dashboard).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    let created = created.json();
+    assert_eq!(created["tiles"].as_array().unwrap().len(), 2);
+    let dashboard_id = created["id"].as_str().unwrap();
+
+    let data = app.get(&format!("/api/workspaces/{}/dashboards/{}/data", workspace_id, dashboard_id), &token).await;
+    assert_eq!(data.status, StatusCode::OK, "{}", data.text());
+    let tiles = data.json()["tiles"].clone();
+    assert_eq!(tiles[0]["title"], "By store");
+    assert_eq!(tiles[0]["visualization"], "BAR");
+    assert_eq!(tiles[1]["title"], "June");
+    for tile in tiles.as_array().unwrap() {
+        assert!(tile["error"].is_null(), "{}", tile);
+        assert_eq!(tile["result"]["rows"].as_array().unwrap().len(), 2, "{}", tile);
+    }
+
+    let foreign = json!({ "name": "Broken", "tiles": [{ "pipeline_id": uuid::Uuid::new_v4(), "visualization": "LINE" }] });
+    let rejected = app.post_json(&format!("/api/workspaces/{}/dashboards", workspace_id), Some(&token), foreign).await;
+    assert_eq!(rejected.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", rejected.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_result_as_chart() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("charts@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+
+    let query = json!({ "dimensions": ["platform"], "metrics": ["net_revenue"] });
+    let png = app
+        .post_json(&format!("/api/workspaces/{}/analytics/chart", workspace_id), Some(&token), query.clone())
+        .await;
+    assert_eq!(png.status, StatusCode::OK, "{}", png.text());
+    assert_eq!(png.headers[header::CONTENT_TYPE], "image/png");
+    assert!(png.body.starts_with(b"\x89PNG"));
+
+    let svg = app
+        .post_json(
+            &format!("/api/workspaces/{}/analytics/chart?kind=pie&format=svg&title=June", workspace_id),
+            Some(&token),
+            query.clone(),
+        )
+        .await;
+    assert_eq!(svg.status, StatusCode::OK, "{}", svg.text());
+    assert_eq!(svg.headers[header::CONTENT_TYPE], "image/svg+xml");
+    assert!(svg.text().contains(">June</text>"));
+
+    let text_only = app
+        .post_json(
+            &format!("/api/workspaces/{}/analytics/chart?y=platform", workspace_id),
+            Some(&token),
+            query,
+        )
+        .await;
+    assert_eq!(text_only.status, StatusCode::BAD_REQUEST, "{}", text_only.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_schedule_runs_when_due() {
+    use doubledecker::db::queries::enqueue_due_schedules;
+    use doubledecker::server::schedules::{PIPELINE_SCHEDULE_JOB, ScheduledPipelineJobHandler};
+    use doubledecker::workers::JobRunner;
+
+    let app = TestApp::spawn().await;
+    let token = app.signup("schedules@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    let pipeline_id = pipeline.json()["id"].as_str().unwrap().to_string();
+
+    let schedule = json!({
+        "pipeline_id": pipeline_id,
+        "interval_minutes": 60,
+        "channel": "SLACK",
+        "webhook_url": "http://127.0.0.1:9/hook",
+        "notify_when": "THRESHOLD",
+        "threshold": { "column": "total_revenue", "op": "GT", "value": 1000 }
+    });
+    let uri = format!("/api/workspaces/{}/schedules", workspace_id);
+    let rejected = app.post_json(&uri, Some(&token), schedule.clone()).await;
+    assert_eq!(rejected.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", rejected.text());
+
+    let mut schedule = schedule;
+    schedule["webhook_url"] = json!("https://hooks.slack.com/services/T000/B000/XXXX");
+    let created = app.post_json(&uri, Some(&token), schedule).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    assert!(!created.text().contains("hooks.slack.com"));
+    let schedule_id = created.json()["id"].as_str().unwrap().to_string();
+
+    sqlx::query("UPDATE pipeline_schedules SET next_run_at = NOW() - INTERVAL '1 minute'")
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(enqueue_due_schedules(&app.pool, PIPELINE_SCHEDULE_JOB, 3, 10).await.unwrap(), 1);
+    assert_eq!(enqueue_due_schedules(&app.pool, PIPELINE_SCHEDULE_JOB, 3, 10).await.unwrap(), 0);
+
+    // No store earned over 1000, so the run posts nothing
+    let handler = ScheduledPipelineJobHandler {
+        pool: app.pool.clone(),
+        engine: app.engine.clone(),
+        http: reqwest::Client::new(),
+        email: None,
+    };
+    let runner = JobRunner::new(app.pool.clone(), app.events.clone()).register(PIPELINE_SCHEDULE_JOB, handler);
+    assert!(runner.run_next().await.unwrap());
+
+    let fetched = app.get(&format!("{}/{}", uri, schedule_id), &token).await;
+    assert_eq!(fetched.json()["last_status"], "QUIET", "{}", fetched.text());
+    let jobs = app.get(&format!("/api/workspaces/{}/jobs", workspace_id), &token).await;
+    let job = jobs.json()["data"][0].clone();
+    assert_eq!(job["status"], "SUCCEEDED", "{}", job);
+    assert_eq!(job["result"]["row_count"], 2);
+    assert_eq!(job["result"]["threshold_breaches"], 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_alert_notifies_once_while_firing() {
+    use axum::routing::post;
+    use doubledecker::server::schedules::{PIPELINE_SCHEDULE_JOB, ScheduledPipelineJobHandler};
+    use doubledecker::utils::email::EmailSender;
+    use doubledecker::workers::JobRunner;
+    use std::sync::{Arc, Mutex};
+
+    // Stands in for the email API
+    let sent: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+    let inbox = sent.clone();
+    let email_api = axum::Router::new().route(
+        "/emails",
+        post(move |axum::Json(body): axum::Json<serde_json::Value>| async move {
+            inbox.lock().unwrap().push(body);
+            "{}"
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let email_url = format!("http://{}/emails", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, email_api).await });
+
+    let app = TestApp::spawn().await;
+    let token = app.signup("alerts@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    let pipeline_id = pipeline.json()["id"].as_str().unwrap().to_string();
+
+    let schedules_uri = format!("/api/workspaces/{}/schedules", workspace_id);
+    let schedule = json!({ "pipeline_id": pipeline_id, "interval_minutes": 60, "notify_when": "NEVER" });
+    let schedule = app.post_json(&schedules_uri, Some(&token), schedule).await;
+    assert_eq!(schedule.status, StatusCode::OK, "{}", schedule.text());
+    let schedule_id = schedule.json()["id"].as_str().unwrap().to_string();
+
+    let alerts_uri = format!("{}/{}/alerts", schedules_uri, schedule_id);
+    let alert = json!({
+        "name": "Revenue",
+        "aggregate": "SUM",
+        "column": "total_revenue",
+        "op": "GT",
+        "threshold": 3,
+        "emails": ["ops@example.com"]
+    });
+    let alert = app.post_json(&alerts_uri, Some(&token), alert).await;
+    assert_eq!(alert.status, StatusCode::OK, "{}", alert.text());
+    let alert_id = alert.json()["id"].as_str().unwrap().to_string();
+
+    let handler = ScheduledPipelineJobHandler {
+        pool: app.pool.clone(),
+        engine: app.engine.clone(),
+        http: reqwest::Client::new(),
+        email: Some(EmailSender::new(&email_url, "test-key", "alerts@doubledecker.test")),
+    };
+    let runner = JobRunner::new(app.pool.clone(), app.events.clone()).register(PIPELINE_SCHEDULE_JOB, handler);
+    // Two runs while revenue stays over the threshold: one email
+    for _ in 0..2 {
+        let run = app.post_json(&format!("{}/{}/run", schedules_uri, schedule_id), Some(&token), json!({})).await;
+        assert_eq!(run.status, StatusCode::OK, "{}", run.text());
+        assert!(runner.run_next().await.unwrap());
+    }
+
+    let alert = app.get(&format!("{}/{}", alerts_uri, alert_id), &token).await.json();
+    assert_eq!(alert["state"], "FIRING", "{}", alert);
+    assert_eq!(alert["last_value"], 3.75);
+    let sent = sent.lock().unwrap();
+    assert_eq!(sent.len(), 1, "{:?}", sent);
+    assert_eq!(sent[0]["to"], json!(["ops@example.com"]));
+    assert_eq!(sent[0]["subject"], "Alert firing: Revenue");
+}
+
+/// Replies with canned answers in order and keeps the prompts it was sent.
+struct ScriptedLlm {
+    replies: std::sync::Mutex<Vec<String>>,
+    prompts: Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for ScriptedLlm {
+    async fn complete(&self, _system: &str, prompt: &str) -> Result<String, DoubledeckerError> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        Ok(self.replies.lock().unwrap().remove(0))
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_natural_language_query_is_validated() {
+    let prompts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let llm = ScriptedLlm {
+        replies: std::sync::Mutex::new(vec![
+            r#"{"dimensions": ["genre"], "metrics": ["net_revenue"]}"#.to_string(),
+            "```json\n{\"dimensions\": [\"platform\"], \"metrics\": [\"net_revenue\"], \"limit\": 5}\n```".to_string(),
+        ]),
+        prompts: prompts.clone(),
+    };
+    let app = TestApp::spawn_with_llm(Some(Arc::new(llm))).await;
+    let token = app.signup("questions@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+
+    let response = app
+        .post_json(
+            &format!("/api/workspaces/{}/analytics/nl", workspace_id),
+            Some(&token),
+            json!({ "question": "Revenue per store, top 5" }),
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let body = response.json();
+    assert_eq!(body["query"]["dimensions"], json!(["platform"]));
+    assert_eq!(body["query"]["limit"], 5);
+    let columns: Vec<&str> = body["columns"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+    assert_eq!(columns, vec!["platform", "total_revenue"]);
+
+    // The retry told the model what was wrong with its first reply
+    let second_prompt = prompts.lock().unwrap()[1].clone();
+    assert!(second_prompt.contains("genre"), "{}", second_prompt);
+
+    let query = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), body["query"].clone())
+        .await;
+    assert_eq!(query.status, StatusCode::OK, "{}", query.text());
+    assert_eq!(query.json()["rows"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_dataset_query_suggestions_run() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("suggestions@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+
+    let response = app
+        .get(&format!("/api/workspaces/{}/datasets/{}/suggestions", workspace_id, dataset_id), &token)
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.text());
+    let suggestions = response.json();
+    let suggestions = suggestions.as_array().unwrap();
+    assert!(suggestions.iter().any(|s| s["title"] == "Most common platform values"), "{:?}", suggestions);
+
+    for suggestion in suggestions {
+        let result = app
+            .post_json(
+                &format!("/api/workspaces/{}/analytics/query", workspace_id),
+                Some(&token),
+                suggestion["query"].clone(),
+            )
+            .await;
+        assert_eq!(result.status, StatusCode::OK, "{}: {}", suggestion["title"], result.text());
+        assert!(!result.json()["rows"].as_array().unwrap().is_empty(), "{}", suggestion["title"]);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_column_values_fill_filter_dropdowns() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("values@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    let values = format!("/api/workspaces/{}/datasets/{}/columns", workspace_id, dataset_id);
+
+    let not_ready = app.get(&format!("{}/title/values", values), &token).await;
+    assert_eq!(not_ready.status, StatusCode::BAD_REQUEST, "{}", not_ready.text());
+    app.run_ingestion().await;
+
+    let all = app.get(&format!("{}/title/values", values), &token).await;
+    assert_eq!(all.status, StatusCode::OK, "{}", all.text());
+    assert_eq!(all.json()["values"], json!(["First Song", "Second Song"]));
+    assert_eq!(all.json()["truncated"], false);
+
+    let searched = app.get(&format!("{}/title/values?search=sec", values), &token).await;
+    assert_eq!(searched.json()["values"], json!(["Second Song"]), "{}", searched.text());
+    let quoted = app.get(&format!("{}/title/values?search=it%27s", values), &token).await;
+    assert_eq!(quoted.status, StatusCode::OK, "{}", quoted.text());
+    assert_eq!(quoted.json()["values"], json!([]));
+
+    let limited = app.get(&format!("{}/title/values?limit=1", values), &token).await;
+    assert_eq!(limited.json()["values"], json!(["First Song"]));
+    assert_eq!(limited.json()["truncated"], true);
+
+    // Without MASK_HASH_KEY the hash mask cannot be chosen; the other masks can
+    let hashed = json!({ "restricted_columns": [], "masked_columns": [{ "column": "title", "mode": "hash" }] });
+    let refused = app.put_json(&values, &token, hashed).await;
+    assert_eq!(refused.status, StatusCode::BAD_REQUEST, "{}", refused.text());
+    assert!(refused.text().contains("MASK_HASH_KEY"));
+    let redacted = json!({ "restricted_columns": [], "masked_columns": [{ "column": "title", "mode": "redact" }] });
+    assert_eq!(app.put_json(&values, &token, redacted).await.status, StatusCode::OK);
+
+    let too_many = app.get(&format!("{}/title/values?limit=5000", values), &token).await;
+    assert_eq!(too_many.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let unknown = app.get(&format!("{}/nope/values", values), &token).await;
+    assert_eq!(unknown.status, StatusCode::NOT_FOUND, "{}", unknown.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_segments_expand_when_queries_run() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("segments@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+
+    let segments = format!("/api/workspaces/{}/segments", workspace_id);
+    let unknown_field = json!({ "name": "Bad", "filters": [{ "field": "nope", "operator": "eq", "value": "x" }] });
+    let invalid = app.post_json(&segments, Some(&token), unknown_field).await;
+    assert_eq!(invalid.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", invalid.text());
+    let spotify = json!({ "name": "Spotify", "filters": [{ "field": "platform", "operator": "eq", "value": "Spotify" }] });
+    let created = app.post_json(&segments, Some(&token), spotify).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    let segment = format!("{}/{}", segments, created.json()["id"].as_str().unwrap());
+    let listed = app.get(&segments, &token).await;
+    assert_eq!(listed.json()["data"][0]["name"], "Spotify");
+
+    let query = json!({ "dimensions": ["title"], "segment_ids": [created.json()["id"]] });
+    let pipeline = app
+        .post_json(
+            &format!("/api/workspaces/{}/pipelines", workspace_id),
+            Some(&token),
+            json!({ "name": "Segmented", "query": query.clone() }),
+        )
+        .await;
+    let pipeline_id = pipeline.json()["id"].as_str().unwrap().to_string();
+    let analytics = format!("/api/workspaces/{}/analytics/query", workspace_id);
+    let result = app.post_json(&analytics, Some(&token), query.clone()).await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    assert_eq!(result.json()["rows"], json!([["First Song"]]));
+
+    // Editing the segment changes every query using it, saved ones included
+    let apple = json!({ "name": "Apple", "filters": [{ "field": "platform", "operator": "eq", "value": "Apple" }] });
+    let updated = app.put_json(&segment, &token, apple).await;
+    assert_eq!(updated.status, StatusCode::OK, "{}", updated.text());
+    let result = app.post_json(&analytics, Some(&token), query.clone()).await;
+    assert_eq!(result.json()["rows"], json!([["Second Song"]]));
+    let run = app
+        .post_json(
+            &format!("/api/workspaces/{}/pipelines/{}/run", workspace_id, pipeline_id),
+            Some(&token),
+            json!({ "dataset_ids": [dataset_id] }),
+        )
+        .await;
+    assert_eq!(run.json()["runs"][0]["result"]["rows"], json!([["Second Song"]]), "{}", run.text());
+
+    assert_eq!(app.delete(&segment, &token).await.status, StatusCode::OK);
+    let result = app.post_json(&analytics, Some(&token), query).await;
+    assert_eq!(result.status, StatusCode::NOT_FOUND, "{}", result.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_pipeline_comment_threads() {
+    let app = TestApp::spawn().await;
+    let owner = app.signup("reviewer@example.com").await;
+    let member = app.signup("analyst@example.com").await;
+    let member_id = app.get("/profile", &member).await.json()["id"].clone();
+    let workspace = app.post_json("/api/workspaces", Some(&owner), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let added = app
+        .post_json(
+            &format!("/api/workspaces/{}/members", workspace_id),
+            Some(&owner),
+            json!({ "user_id": member_id, "role": "VIEWER" }),
+        )
+        .await;
+    assert_eq!(added.status, StatusCode::OK, "{}", added.text());
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&owner), pipeline).await;
+    let comments =
+        format!("/api/workspaces/{}/pipelines/{}/comments", workspace_id, pipeline.json()["id"].as_str().unwrap());
+
+    let body = "@Analyst@example.com should refunds be excluded? (@stranger@example.com is not a member)";
+    let root = app.post_json(&comments, Some(&owner), json!({ "body": body })).await;
+    assert_eq!(root.status, StatusCode::OK, "{}", root.text());
+    assert_eq!(root.json()["mentions"], json!([member_id]));
+    let root_id = root.json()["id"].clone();
+    let reply = app.post_json(&comments, Some(&member), json!({ "body": "Yes", "parent_id": root_id })).await;
+    assert_eq!(reply.status, StatusCode::OK, "{}", reply.text());
+    let orphan = app
+        .post_json(&comments, Some(&member), json!({ "body": "Hm", "parent_id": uuid::Uuid::new_v4() }))
+        .await;
+    assert_eq!(orphan.status, StatusCode::NOT_FOUND);
+
+    let thread = app.get(&comments, &member).await;
+    assert_eq!(thread.status, StatusCode::OK, "{}", thread.text());
+    let thread = thread.json();
+    assert_eq!(thread["data"].as_array().unwrap().len(), 2);
+    assert_eq!(thread["data"][0]["author_name"], "Test User");
+    assert_eq!(thread["data"][1]["parent_id"], root_id);
+
+    let root_uri = format!("{}/{}", comments, root_id.as_str().unwrap());
+    let not_theirs = app.put_json(&root_uri, &member, json!({ "body": "Edited" })).await;
+    assert_eq!(not_theirs.status, StatusCode::FORBIDDEN);
+    assert_eq!(app.delete(&root_uri, &member).await.status, StatusCode::FORBIDDEN);
+    let edited = app.put_json(&root_uri, &owner, json!({ "body": "Refunds are excluded now" })).await;
+    assert_eq!(edited.status, StatusCode::OK, "{}", edited.text());
+    assert_eq!(edited.json()["mentions"], json!([]));
+
+    // Deleting a comment takes its replies with it
+    assert_eq!(app.delete(&root_uri, &owner).await.status, StatusCode::OK);
+    assert_eq!(app.get(&comments, &owner).await.json()["data"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_dataset_activity_feed() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("activity@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+    let dataset = format!("/api/workspaces/{}/datasets/{}", workspace_id, dataset_id);
+
+    let query = json!({ "sql": "SELECT COUNT(*) AS tracks FROM royalty_data" });
+    let result = app.post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query).await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    let shared = app.post_json(&format!("{}/public", dataset), Some(&token), json!({})).await;
+    assert_eq!(shared.status, StatusCode::OK, "{}", shared.text());
+    assert_eq!(app.delete(&format!("{}/public", dataset), &token).await.status, StatusCode::OK);
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline = app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+    let pipeline_id = pipeline.json()["id"].as_str().unwrap().to_string();
+    let run = app
+        .post_json(
+            &format!("/api/workspaces/{}/pipelines/{}/run", workspace_id, pipeline_id),
+            Some(&token),
+            json!({ "dataset_ids": [dataset_id] }),
+        )
+        .await;
+    assert_eq!(run.status, StatusCode::OK, "{}", run.text());
+
+    let feed = app.get(&format!("{}/activity", dataset), &token).await;
+    assert_eq!(feed.status, StatusCode::OK, "{}", feed.text());
+    let feed = feed.json();
+    let entries = feed["data"].as_array().unwrap();
+    let actions: Vec<&str> = entries.iter().map(|a| a["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["PIPELINE_RUN", "UNSHARED", "SHARED", "QUERIED", "CREATED"]);
+    assert_eq!(feed["data"][0]["details"]["pipeline_name"], "By store");
+    assert_eq!(feed["data"][3]["user_name"], "Test User");
+
+    let page = app.get(&format!("{}/activity?limit=2", dataset), &token).await.json();
+    assert_eq!(page["data"].as_array().unwrap().len(), 2);
+    let cursor = page["pagination"]["next_cursor"].as_str().unwrap();
+    let rest = app.get(&format!("{}/activity?limit=2&cursor={}", dataset, cursor), &token).await.json();
+    assert_eq!(rest["data"][0]["action"], "SHARED");
+
+    let missing = format!("/api/workspaces/{}/datasets/{}/activity", workspace_id, uuid::Uuid::new_v4());
+    let missing = app.get(&missing, &token).await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_favorites_filter_lists_per_user() {
+    let app = TestApp::spawn().await;
+    let owner = app.signup("favorites@example.com").await;
+    let member = app.signup("member@example.com").await;
+    let member_id = app.get("/profile", &member).await.json()["id"].clone();
+    let workspace = app.post_json("/api/workspaces", Some(&owner), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let added = app
+        .post_json(
+            &format!("/api/workspaces/{}/members", workspace_id),
+            Some(&owner),
+            json!({ "user_id": member_id, "role": "VIEWER" }),
+        )
+        .await;
+    assert_eq!(added.status, StatusCode::OK, "{}", added.text());
+    let mut dataset_ids = Vec::new();
+    for filename in ["june.csv", "july.csv"] {
+        let upload = app
+            .upload_csv(
+                &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+                &owner,
+                filename,
+                DISTROKID_CSV,
+                &[("distributor_source", "distrokid")],
+            )
+            .await;
+        dataset_ids.push(upload.json()["id"].as_str().unwrap().to_string());
+    }
+    let pipelines = format!("/api/workspaces/{}/pipelines", workspace_id);
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipeline_id = app.post_json(&pipelines, Some(&owner), pipeline).await.json()["id"].clone();
+    let pipeline = json!({ "name": "By title", "query": { "dimensions": ["title"], "metrics": ["net_revenue"] } });
+    assert_eq!(app.post_json(&pipelines, Some(&owner), pipeline).await.status, StatusCode::OK);
+
+    // A viewer can favorite resources shared with them, for themselves only
+    let datasets = format!("/api/workspaces/{}/datasets", workspace_id);
+    let favorite = format!("{}/{}/favorite", datasets, dataset_ids[0]);
+    let favorited = app.put_json(&favorite, &member, json!({})).await;
+    assert_eq!(favorited.status, StatusCode::OK, "{}", favorited.text());
+    assert_eq!(favorited.json()["favorite"], true);
+    assert_eq!(app.put_json(&favorite, &member, json!({})).await.status, StatusCode::OK);
+    let pipeline_favorite = format!("{}/{}/favorite", pipelines, pipeline_id.as_str().unwrap());
+    assert_eq!(app.put_json(&pipeline_favorite, &member, json!({})).await.status, StatusCode::OK);
+
+    let mine = app.get(&format!("{}?favorites=true", datasets), &member).await.json();
+    assert_eq!(mine["data"].as_array().unwrap().len(), 1);
+    assert_eq!(mine["data"][0]["id"], dataset_ids[0].as_str());
+    let theirs = app.get(&format!("{}?favorites=true", datasets), &owner).await.json();
+    assert_eq!(theirs["data"], json!([]));
+    assert_eq!(app.get(&datasets, &member).await.json()["data"].as_array().unwrap().len(), 2);
+    let mine = app.get(&format!("{}?favorites=true", pipelines), &member).await.json();
+    assert_eq!(mine["data"].as_array().unwrap().len(), 1);
+    assert_eq!(mine["data"][0]["id"], pipeline_id);
+
+    let unfavorited = app.delete(&favorite, &member).await;
+    assert_eq!(unfavorited.json()["favorite"], false, "{}", unfavorited.text());
+    assert_eq!(app.get(&format!("{}?favorites=true", datasets), &member).await.json()["data"], json!([]));
+    let missing = format!("{}/{}/favorite", datasets, uuid::Uuid::new_v4());
+    assert_eq!(app.put_json(&missing, &member, json!({})).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_search_across_accessible_workspaces() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("search@example.com").await;
+    let other = app.signup("elsewhere@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "royalties_june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].clone();
+    let pipeline = json!({
+        "name": "By store",
+        "description": "Royalties per store",
+        "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] }
+    });
+    let pipelines = format!("/api/workspaces/{}/pipelines", workspace_id);
+    let pipeline_id = app.post_json(&pipelines, Some(&token), pipeline).await.json()["id"].clone();
+    let foreign = app.post_json("/api/workspaces", Some(&other), json!({ "name": "Theirs" })).await;
+    let foreign = format!("/api/workspaces/{}/pipelines", foreign.json()["id"].as_str().unwrap());
+    let pipeline = json!({ "name": "Royalties", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    assert_eq!(app.post_json(&foreign, Some(&other), pipeline).await.status, StatusCode::OK);
+
+    let found = app.get("/search?q=ROYALTIES", &token).await;
+    assert_eq!(found.status, StatusCode::OK, "{}", found.text());
+    let found = found.json();
+    assert_eq!(found["truncated"], false);
+    let results = found["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2, "{}", found);
+    // Names starting with the query rank above description matches
+    assert_eq!(results[0]["kind"], "DATASET");
+    assert_eq!(results[0]["id"], dataset_id);
+    assert_eq!(results[0]["workspace_name"], "Label");
+    let link = format!("/api/workspaces/{}/datasets/{}/columns", workspace_id, dataset_id.as_str().unwrap());
+    assert_eq!(results[0]["link"], link.as_str());
+    assert_eq!(results[1]["kind"], "PIPELINE");
+    assert_eq!(results[1]["id"], pipeline_id);
+
+    let limited = app.get("/search?q=royalties&limit=1", &token).await.json();
+    assert_eq!(limited["results"].as_array().unwrap().len(), 1);
+    assert_eq!(limited["truncated"], true);
+    // LIKE wildcards in the query are matched literally
+    assert_eq!(app.get("/search?q=%25", &token).await.json()["results"], json!([]));
+    assert_eq!(app.get("/search?q=%20", &token).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[cfg(feature = "graphql")]
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_graphql_fetches_and_queries_in_one_request() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("graphql@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    app.post_json(&format!("/api/workspaces/{}/pipelines", workspace_id), Some(&token), pipeline).await;
+
+    let query = r#"query Home($ws: UUID!) {
+        me { email }
+        datasets(workspaceId: $ws) { items { filename status rowCount } hasMore }
+        pipelines(workspaceId: $ws, limit: 10) { items { name } }
+    }"#;
+    let home = app
+        .post_json("/graphql", Some(&token), json!({ "query": query, "variables": { "ws": workspace_id } }))
+        .await;
+    assert_eq!(home.status, StatusCode::OK, "{}", home.text());
+    let home = home.json();
+    assert_eq!(home["errors"], serde_json::Value::Null, "{}", home);
+    assert_eq!(home["data"]["me"], json!({ "email": "graphql@example.com" }));
+    assert_eq!(
+        home["data"]["datasets"],
+        json!({ "items": [{ "filename": "june.csv", "status": "READY", "rowCount": 2 }], "hasMore": false })
+    );
+    assert_eq!(home["data"]["pipelines"]["items"], json!([{ "name": "By store" }]));
+
+    let mutation = r#"mutation Run($ws: UUID!, $request: JSON!) { executeQuery(workspaceId: $ws, request: $request) }"#;
+    let spotify = json!([{ "field": "platform", "operator": "eq", "value": "Spotify" }]);
+    let request = json!({ "dimensions": ["title"], "filters": spotify });
+    let run = app
+        .post_json(
+            "/graphql",
+            Some(&token),
+            json!({ "query": mutation, "variables": { "ws": workspace_id, "request": request } }),
+        )
+        .await
+        .json();
+    assert_eq!(run["data"]["executeQuery"]["rows"], json!([["First Song"]]), "{}", run);
+
+    // REST errors come back as GraphQL errors with the same code
+    let foreign = json!({ "query": query, "variables": { "ws": uuid::Uuid::new_v4() } });
+    let denied = app.post_json("/graphql", Some(&token), foreign).await.json();
+    assert!(denied["errors"][0]["extensions"]["code"].is_string(), "{}", denied);
+    let anonymous = app.post_json("/graphql", None, json!({ "query": "{ me { id } }" })).await;
+    assert_eq!(anonymous.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_pipeline_run_records_lineage() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("lineage@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipelines_uri = format!("/api/workspaces/{}/pipelines", workspace_id);
+    let created = app.post_json(&pipelines_uri, Some(&token), pipeline).await;
+    let pipeline_id = created.json()["id"].as_str().unwrap().to_string();
+    let run = app
+        .post_json(
+            &format!("{}/{}/run", pipelines_uri, pipeline_id),
+            Some(&token),
+            json!({ "dataset_ids": [dataset_id] }),
+        )
+        .await;
+    assert_eq!(run.status, StatusCode::OK, "{}", run.text());
+    let run_id = run.json()["runs"][0]["id"].as_str().unwrap().to_string();
+
+    let lineage = app.get(&format!("{}/{}/runs/{}/lineage", pipelines_uri, pipeline_id, run_id), &token).await;
+    assert_eq!(lineage.status, StatusCode::OK, "{}", lineage.text());
+    let lineage = lineage.json();
+    assert_eq!(lineage["datasets"], json!([dataset_id]));
+    assert_eq!(lineage["tables"], json!(["royalty_data"]));
+    assert_eq!(lineage["query"]["dimensions"], json!(["platform"]));
+    assert_eq!(lineage["columns"][0]["name"], "platform");
+    assert_eq!(lineage["columns"][0]["sources"], json!(["royalty_data.platform"]));
+    assert_eq!(lineage["columns"][1]["name"], "total_revenue");
+    assert_eq!(lineage["columns"][1]["sources"], json!(["royalty_data.net_revenue"]));
+    assert!(lineage["columns"][1]["transformations"][0].as_str().unwrap().contains("sum("), "{}", lineage);
+
+    let missing = app
+        .get(&format!("{}/{}/runs/{}/lineage", pipelines_uri, pipeline_id, uuid::Uuid::new_v4()), &token)
+        .await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_staged_upload_preview_reads_first_rows() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("preview@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let presigned = app
+        .post_json(
+            &format!("/api/workspaces/{}/datasets/presigned_url", workspace_id),
+            Some(&token),
+            json!({ "filename": "june.csv", "file_size_bytes": DISTROKID_CSV.len() }),
+        )
+        .await;
+    assert_eq!(presigned.status, StatusCode::OK, "{}", presigned.text());
+    let (dataset_id, staging_key) = (presigned.json()["dataset_id"].clone(), presigned.json()["staging_key"].clone());
+    let preview_path = format!("/api/workspaces/{}/datasets/preview", workspace_id);
+    let request = json!({ "dataset_id": dataset_id, "staging_key": staging_key, "rows": 1 });
+
+    let missing = app.post_json(&preview_path, Some(&token), request.clone()).await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND, "nothing has been PUT to the staging key yet");
+
+    let key = Path::from(staging_key.as_str().unwrap());
+    app.storage.put(&key, DISTROKID_CSV.as_bytes().to_vec().into()).await.unwrap();
+    let preview = app.post_json(&preview_path, Some(&token), request).await;
+    assert_eq!(preview.status, StatusCode::OK, "{}", preview.text());
+    let body = preview.json();
+    assert_eq!(body["rows"], json!([["US1234567890", "First Song", "Spotify", "2026-06", "1.50"]]));
+    assert_eq!(body["columns"][4], json!({ "name": "Earnings (USD)", "data_type": "Float64" }));
+    assert_eq!(body["detected_source"], "DistroKid");
+    assert_eq!(body["bytes_read"], DISTROKID_CSV.find("US0987654321").unwrap());
+
+    let too_many = app
+        .post_json(&preview_path, Some(&token), json!({ "dataset_id": dataset_id, "staging_key": staging_key, "rows": 5000 }))
+        .await;
+    assert_eq!(too_many.status, StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_compaction_merges_datasets_sorted() {
+    use doubledecker::server::compaction::{DATASET_COMPACTION_JOB, DatasetCompactionJobHandler};
+    use doubledecker::workers::JobRunner;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let app = TestApp::spawn().await;
+    let token = app.signup("compaction@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let may = "ISRC,Song Title,Store,Reporting Month,Earnings (USD)\nUS1111111111,Old Song,Deezer,2026-05,0.75\n";
+    for (filename, csv) in [("june.csv", DISTROKID_CSV), ("may.csv", may)] {
+        let upload = app
+            .upload_csv(
+                &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+                &token,
+                filename,
+                csv,
+                &[("distributor_source", "distrokid")],
+            )
+            .await;
+        assert_eq!(upload.status, StatusCode::OK, "{}", upload.text());
+    }
+    app.run_ingestion().await;
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    let sources = datasets.json()["data"].as_array().unwrap().clone();
+    let ids: Vec<_> = sources.iter().map(|d| d["id"].clone()).collect();
+
+    let uri = format!("/api/workspaces/{}/datasets/compact", workspace_id);
+    let unknown = app
+        .post_json(&uri, Some(&token), json!({ "dataset_ids": ids, "sort_by": ["earnings"] }))
+        .await;
+    assert_eq!(unknown.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", unknown.text());
+    let compact = app
+        .post_json(&uri, Some(&token), json!({ "dataset_ids": ids, "sort_by": ["reporting_date"], "filename": "2026.parquet" }))
+        .await;
+    assert_eq!(compact.status, StatusCode::OK, "{}", compact.text());
+    assert_eq!(compact.json()["dataset"]["status"], "QUEUED");
+
+    let handler = DatasetCompactionJobHandler {
+        pool: app.pool.clone(),
+        engine: app.engine.clone(),
+        uploader: app.uploader.clone(),
+    };
+    let runner = JobRunner::new(app.pool.clone(), app.events.clone()).register(DATASET_COMPACTION_JOB, handler);
+    let mut events = app.events.subscribe();
+    assert!(runner.run_next().await.unwrap());
+    let finished = events.try_recv().expect("the job announces it finished");
+    assert_eq!(finished.kind.as_str(), "job_finished");
+    assert_eq!(finished.payload["kind"], DATASET_COMPACTION_JOB);
+    assert_eq!(finished.payload["status"], "SUCCEEDED");
+
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    let data = datasets.json()["data"].clone();
+    assert_eq!(data.as_array().unwrap().len(), 1, "the sources are replaced: {}", data);
+    assert_eq!(data[0]["filename"], "2026.parquet");
+    assert_eq!(data[0]["status"], "READY");
+    assert_eq!(data[0]["row_count"], 3);
+    for source in &sources {
+        let key = Path::from(source["s3_parquet_key"].as_str().unwrap());
+        assert!(app.storage.head(&key).await.is_err(), "source files are deleted");
+    }
+
+    let query = json!({ "sql": "SELECT platform FROM royalty_data" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.json()["rows"][0], json!(["Deezer"]), "rows are stored in reporting_date order");
+    assert_eq!(result.json()["rows"].as_array().unwrap().len(), 3);
+
+    let key = Path::from(data[0]["s3_parquet_key"].as_str().unwrap());
+    let stored = app.storage.get(&key).await.unwrap().bytes().await.unwrap();
+    assert!(!stored.starts_with(b"PAR1"), "the merged file is stored encrypted");
+    let uri = format!("/api/workspaces/{}/datasets/{}/download", workspace_id, data[0]["id"].as_str().unwrap());
+    let download = app.get(&uri, &token).await;
+    assert_eq!(download.status, StatusCode::OK, "encrypted files are decrypted by the server");
+    let metadata = SerializedFileReader::new(download.body).unwrap().metadata().clone();
+    let row_group = metadata.row_group(0);
+    assert!(row_group.sorting_columns().is_some_and(|c| c.len() == 1));
+    assert!(row_group.columns().iter().all(|c| c.statistics().is_some()));
+
+    // A second run of the job, e.g. after its lease expired, keeps the merged file it finds READY
+    let handler = DatasetCompactionJobHandler {
+        pool: app.pool.clone(),
+        engine: app.engine.clone(),
+        uploader: app.uploader.clone(),
+    };
+    let job: doubledecker::db::models::BackgroundJob = serde_json::from_value(compact.json()["job"].clone()).unwrap();
+    let rerun = doubledecker::workers::JobHandler::run(&handler, &job).await.unwrap();
+    assert_eq!(rerun["row_count"], 3);
+    assert!(app.storage.head(&key).await.is_ok(), "the merged file is kept");
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    assert_eq!(datasets.json()["data"][0]["status"], "READY");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_upload_session_resumes_and_completes() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("sessions@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let sessions = format!("/api/workspaces/{}/datasets/upload_sessions", workspace_id);
+    let too_big = app
+        .post_json(&sessions, Some(&token), json!({ "filename": "june.csv", "total_bytes": 6_i64 << 30 }))
+        .await;
+    assert_eq!(too_big.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let tiny_parts = app
+        .post_json(&sessions, Some(&token), json!({ "filename": "june.csv", "total_bytes": 100, "chunk_size": 1024 }))
+        .await;
+    assert_eq!(tiny_parts.status, StatusCode::UNPROCESSABLE_ENTITY);
+    let csv = DISTROKID_CSV.as_bytes();
+    let body = json!({ "filename": "june.csv", "distributor_source": "distrokid", "total_bytes": csv.len() });
+    let created = app.post_json(&sessions, Some(&token), body).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    assert_eq!(created.json()["part_count"], 1);
+    let session = format!("{}/{}", sessions, created.json()["id"].as_str().unwrap());
+    let part = format!("{}/parts/1", session);
+
+    let unchecked = app.put_bytes(&part, &token, csv, &[]).await;
+    assert_eq!(unchecked.status, StatusCode::BAD_REQUEST);
+    let corrupted = app.put_bytes(&part, &token, csv, &[("X-Checksum-SHA256", &sha256_hex(b"other"))]).await;
+    assert_eq!(corrupted.status, StatusCode::BAD_REQUEST, "{}", corrupted.text());
+    let early = app.post_json(&format!("{}/complete", session), Some(&token), json!({})).await;
+    assert_eq!(early.status, StatusCode::CONFLICT);
+    let progress = app.get(&session, &token).await;
+    assert_eq!(progress.json()["bytes_received"], 0);
+    assert_eq!(progress.json()["received_parts"], json!([]));
+
+    let checksum = sha256_hex(csv);
+    let put = app.put_bytes(&part, &token, csv, &[("X-Checksum-SHA256", &checksum)]).await;
+    assert_eq!(put.status, StatusCode::OK, "{}", put.text());
+    assert_eq!(put.json()["bytes_received"], csv.len());
+    // A retried part whose response was lost replaces the first copy
+    let again = app.put_bytes(&part, &token, csv, &[("X-Checksum-SHA256", &checksum)]).await;
+    assert_eq!(again.status, StatusCode::OK);
+    assert_eq!(again.json()["bytes_received"], csv.len());
+    assert_eq!(again.json()["received_parts"], json!([1]));
+
+    let completed = app.post_json(&format!("{}/complete", session), Some(&token), json!({})).await;
+    assert_eq!(completed.status, StatusCode::OK, "{}", completed.text());
+    assert_eq!(completed.json()["status"], "QUEUED");
+    assert_eq!(completed.json()["file_size_bytes"], csv.len());
+    let retried = app.post_json(&format!("{}/complete", session), Some(&token), json!({})).await;
+    assert_eq!(retried.json()["id"], completed.json()["id"], "completing again returns the same dataset");
+    let late = app.put_bytes(&part, &token, csv, &[("X-Checksum-SHA256", &checksum)]).await;
+    assert_eq!(late.status, StatusCode::CONFLICT);
+    assert!(completed.json()["encryption_key_id"].is_string());
+
+    app.run_ingestion().await;
+    // Staged as sent, but converted to encrypted Parquet like a direct upload
+    let parquet = Path::from(completed.json()["s3_parquet_key"].as_str().unwrap());
+    let stored = app.storage.get(&parquet).await.unwrap().bytes().await.unwrap();
+    assert!(!stored.starts_with(b"PAR1"), "the Parquet file is stored encrypted");
+    let query = json!({ "sql": "SELECT title FROM royalty_data ORDER BY title" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.json()["rows"], json!([["First Song"], ["Second Song"]]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_upload_session_parts_arrive_out_of_order() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("parts@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    // Three parts of the smallest size S3 takes, the last one shorter
+    let chunk_size = 5 * 1024 * 1024;
+    let mut csv = b"ISRC,Song Title,Store,Reporting Month,Earnings (USD)\n".to_vec();
+    while csv.len() < 2 * chunk_size + 1000 {
+        csv.extend_from_slice(b"US1234567890,First Song,Spotify,2026-06,1.50\n");
+    }
+    let parts: Vec<&[u8]> = csv.chunks(chunk_size).collect();
+    assert_eq!(parts.len(), 3);
+
+    let sessions = format!("/api/workspaces/{}/datasets/upload_sessions", workspace_id);
+    let body = json!({ "filename": "june.csv", "total_bytes": csv.len(), "chunk_size": chunk_size });
+    let created = app.post_json(&sessions, Some(&token), body).await;
+    assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    assert_eq!(created.json()["part_count"], 3);
+    let session = format!("{}/{}", sessions, created.json()["id"].as_str().unwrap());
+    let put_part = |number: usize, content: &[u8], checksum: String| {
+        let uri = format!("{}/parts/{}", session, number);
+        let content = content.to_vec();
+        let app = &app;
+        let token = &token;
+        async move { app.put_bytes(&uri, token, &content, &[("X-Checksum-SHA256", &checksum)]).await }
+    };
+
+    let last = put_part(3, parts[2], sha256_hex(parts[2])).await;
+    assert_eq!(last.status, StatusCode::OK, "{}", last.text());
+    let first = put_part(1, parts[0], sha256_hex(parts[0])).await;
+    assert_eq!(first.json()["received_parts"], json!([1, 3]));
+    assert_eq!(first.json()["bytes_received"], parts[0].len() + parts[2].len());
+
+    let short = put_part(2, &parts[1][1..], sha256_hex(&parts[1][1..])).await;
+    assert_eq!(short.status, StatusCode::BAD_REQUEST, "parts but the last are chunk_size bytes");
+    let mut damaged = parts[1].to_vec();
+    damaged[100] ^= 1;
+    let corrupted = put_part(2, &damaged, sha256_hex(parts[1])).await;
+    assert_eq!(corrupted.status, StatusCode::BAD_REQUEST, "{}", corrupted.text());
+    let early = app.post_json(&format!("{}/complete", session), Some(&token), json!({})).await;
+    assert_eq!(early.status, StatusCode::CONFLICT);
+
+    let middle = put_part(2, parts[1], sha256_hex(parts[1])).await;
+    assert_eq!(middle.json()["bytes_received"], csv.len());
+    let resent = put_part(1, parts[0], sha256_hex(parts[0])).await;
+    assert_eq!(resent.json()["received_parts"], json!([1, 2, 3]));
+
+    let completed = app.post_json(&format!("{}/complete", session), Some(&token), json!({})).await;
+    assert_eq!(completed.status, StatusCode::OK, "{}", completed.text());
+    let dataset_id = completed.json()["id"].as_str().unwrap().to_string();
+    let staged: Vec<_> = app.storage.list(Some(&Path::from(format!("workspaces/{}", workspace_id)))).collect().await;
+    let staged = staged
+        .into_iter()
+        .map(|object| object.unwrap().location)
+        .find(|location| location.as_ref().ends_with(&format!("{}.csv", dataset_id)))
+        .expect("the assembled file is staged under the dataset id");
+    let assembled = app.storage.get(&staged).await.unwrap().bytes().await.unwrap();
+    assert!(assembled.as_ref() == csv.as_slice(), "parts are assembled in part order");
+    let leftovers: Vec<_> = app.storage.list(Some(&Path::from("multipart"))).collect().await;
+    assert!(leftovers.is_empty());
+
+    // Abandoning a session discards its parts
+    let body = json!({ "filename": "july.csv", "total_bytes": csv.len(), "chunk_size": chunk_size });
+    let abandoned = app.post_json(&sessions, Some(&token), body).await;
+    let abandoned = format!("{}/{}", sessions, abandoned.json()["id"].as_str().unwrap());
+    let uri = format!("{}/parts/1", abandoned);
+    let put = app.put_bytes(&uri, &token, parts[0], &[("X-Checksum-SHA256", &sha256_hex(parts[0]))]).await;
+    assert_eq!(put.status, StatusCode::OK);
+    let deleted = app.delete(&abandoned, &token).await;
+    assert_eq!(deleted.status, StatusCode::OK, "{}", deleted.text());
+    let leftovers: Vec<_> = app.storage.list(Some(&Path::from("multipart"))).collect().await;
+    assert!(leftovers.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_stalled_upload_sessions_are_reclaimed_and_abandoned_ones_cleaned_up() {
+    use doubledecker::db::queries::enqueue_job_unless_pending;
+    use doubledecker::server::uploads::{UPLOAD_SESSION_CLEANUP_JOB, UploadSessionCleanupJobHandler};
+    use doubledecker::workers::JobRunner;
+
+    let app = TestApp::spawn().await;
+    let token = app.signup("stalled@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let sessions = format!("/api/workspaces/{}/datasets/upload_sessions", workspace_id);
+    let csv = DISTROKID_CSV.as_bytes();
+    let start_session = || async {
+        let body = json!({ "filename": "june.csv", "distributor_source": "distrokid", "total_bytes": csv.len() });
+        let created = app.post_json(&sessions, Some(&token), body).await;
+        let id = uuid::Uuid::parse_str(created.json()["id"].as_str().unwrap()).unwrap();
+        let part = format!("{}/{}/parts/1", sessions, id);
+        let put = app.put_bytes(&part, &token, csv, &[("X-Checksum-SHA256", &sha256_hex(csv))]).await;
+        assert_eq!(put.status, StatusCode::OK, "{}", put.text());
+        id
+    };
+
+    // A completion that died after S3 assembled the file, leaving the session COMPLETING
+    let stalled = start_session().await;
+    let (staging_key, upload_id): (String, String) =
+        sqlx::query_as("SELECT staging_key, multipart_upload_id FROM upload_sessions WHERE id = $1")
+            .bind(stalled)
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    app.uploader.complete_multipart_upload(&staging_key, &upload_id, &[(1, String::new())]).await.unwrap();
+    sqlx::query("UPDATE upload_sessions SET status = 'COMPLETING' WHERE id = $1")
+        .bind(stalled)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let complete = format!("{}/{}/complete", sessions, stalled);
+    let busy = app.post_json(&complete, Some(&token), json!({})).await;
+    assert_eq!(busy.status, StatusCode::CONFLICT, "a recent completion is left to finish");
+
+    sqlx::query("UPDATE upload_sessions SET updated_at = NOW() - INTERVAL '1 hour' WHERE id = $1")
+        .bind(stalled)
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let reclaimed = app.post_json(&complete, Some(&token), json!({})).await;
+    assert_eq!(reclaimed.status, StatusCode::OK, "{}", reclaimed.text());
+    assert_eq!(reclaimed.json()["file_size_bytes"], csv.len());
+
+    // Sessions idle for a day are deleted with their parts; completed ones stay
+    let abandoned = start_session().await;
+    let recent = start_session().await;
+    sqlx::query("UPDATE upload_sessions SET updated_at = NOW() - INTERVAL '2 days' WHERE id = ANY($1)")
+        .bind(vec![abandoned, stalled])
+        .execute(&app.pool)
+        .await
+        .unwrap();
+    let handler = UploadSessionCleanupJobHandler { pool: app.pool.clone(), uploader: app.uploader.clone() };
+    let runner = JobRunner::new(app.pool.clone(), app.events.clone()).register(UPLOAD_SESSION_CLEANUP_JOB, handler);
+    assert!(enqueue_job_unless_pending(&app.pool, UPLOAD_SESSION_CLEANUP_JOB, 3).await.unwrap());
+    assert!(!enqueue_job_unless_pending(&app.pool, UPLOAD_SESSION_CLEANUP_JOB, 3).await.unwrap());
+    assert!(runner.run_next().await.unwrap());
+    let result: (serde_json::Value,) = sqlx::query_as("SELECT result FROM background_jobs WHERE kind = $1")
+        .bind(UPLOAD_SESSION_CLEANUP_JOB)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert_eq!(result.0["deleted_sessions"], 1);
+
+    assert_eq!(app.get(&format!("{}/{}", sessions, abandoned), &token).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(app.get(&format!("{}/{}", sessions, recent), &token).await.status, StatusCode::OK);
+    assert_eq!(app.get(&format!("{}/{}", sessions, stalled), &token).await.status, StatusCode::OK);
+    let leftovers: Vec<_> = app.storage.list(Some(&Path::from("multipart"))).collect().await;
+    assert_eq!(leftovers.len(), 1, "only the recent session's part is left");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_non_tabular_upload_is_rejected() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("sniff@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0\x1f\x15\xc4\x89";
+    let upload = app
+        .upload_file(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            ("file", "data.csv", "text/csv"),
+            png,
+            &[],
+        )
+        .await;
+    assert_eq!(upload.status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{}", upload.text());
+    assert_eq!(upload.json()["code"], "UNSUPPORTED_FILE_TYPE");
+    assert!(upload.json()["error"].as_str().unwrap().contains("PNG"), "{}", upload.text());
+
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    assert_eq!(datasets.json()["data"], json!([]), "nothing is stored");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_uploads_over_the_limits_are_rejected() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("limits@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let limits = app.get("/limits", &token).await;
+    assert_eq!(limits.status, StatusCode::OK, "{}", limits.text());
+    assert_eq!(limits.json()["max_columns"], 1000);
+    let max_file_bytes = limits.json()["max_file_bytes"].as_u64().unwrap();
+
+    let presigned = app
+        .post_json(
+            &format!("/api/workspaces/{}/datasets/presigned_url", workspace_id),
+            Some(&token),
+            json!({ "filename": "huge.csv", "file_size_bytes": max_file_bytes + 1 }),
+        )
+        .await;
+    assert_eq!(presigned.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", presigned.text());
+    assert_eq!(presigned.json()["code"], "UPLOAD_LIMIT_EXCEEDED");
+    assert_eq!(presigned.json()["details"]["limit"], "file_bytes");
+
+    let header: Vec<String> = (0..1001).map(|i| format!("c{}", i)).collect();
+    let row: Vec<&str> = vec!["1"; 1001];
+    let wide = format!("{}\n{}\n", header.join(","), row.join(","));
+    let upload = app
+        .upload_csv(&format!("/api/workspaces/{}/datasets/upload", workspace_id), &token, "wide.csv", &wide, &[])
+        .await;
+    assert_eq!(upload.status, StatusCode::PAYLOAD_TOO_LARGE, "{}", upload.text());
+    assert_eq!(upload.json()["details"], json!({ "limit": "columns", "max": 1000, "actual": 1001 }));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_flagged_upload_is_quarantined() {
+    use doubledecker::utils::scanner::{ContentScanner, ScanVerdict};
+
+    /// Flags files containing the EICAR test string, like a real antivirus engine would.
+    struct EicarScanner;
+
+    #[async_trait::async_trait]
+    impl ContentScanner for EicarScanner {
+        async fn scan(&self, content: &[u8]) -> Result<ScanVerdict, DoubledeckerError> {
+            let flagged = content.windows(5).any(|w| w == b"EICAR");
+            Ok(if flagged { ScanVerdict::Flagged("Eicar-Test-Signature".to_string()) } else { ScanVerdict::Clean })
+        }
+    }
+
+    let app = TestApp::spawn_with_scanner(Arc::new(EicarScanner)).await;
+    let token = app.signup("scan@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload_uri = format!("/api/workspaces/{}/datasets/upload", workspace_id);
+
+    let clean = app.upload_csv(&upload_uri, &token, "june.csv", DISTROKID_CSV, &[("distributor_source", "distrokid")]).await;
+    assert_eq!(clean.status, StatusCode::OK, "{}", clean.text());
+    let infected_csv = format!("{}US0000000001,EICAR Song,Spotify,2026-06,0.10\n", DISTROKID_CSV);
+    let infected = app.upload_csv(&upload_uri, &token, "july.csv", &infected_csv, &[("distributor_source", "distrokid")]).await;
+    assert_eq!(infected.status, StatusCode::OK, "{}", infected.text());
+    let infected_id = infected.json()["id"].as_str().unwrap().to_string();
+
+    app.run_ingestion().await;
+
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await;
+    let quarantined = datasets.json()["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["id"] == infected_id.as_str())
+        .cloned()
+        .unwrap();
+    assert_eq!(quarantined["status"], "QUARANTINED", "{}", quarantined);
+    assert!(quarantined["error_message"].as_str().unwrap().contains("Eicar-Test-Signature"));
+    let quarantine = Path::from(format!("workspaces/{}/quarantine/{}.csv", workspace_id, infected_id));
+    assert!(app.storage.head(&quarantine).await.is_ok(), "the flagged file is kept in quarantine");
+    assert!(app.storage.head(&Path::from(quarantined["s3_parquet_key"].as_str().unwrap())).await.is_err());
+
+    // Only the clean file is queryable
+    let query = json!({ "sql": "SELECT COUNT(*) AS tracks FROM royalty_data" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.json()["rows"], json!([[2]]), "{}", result.text());
+
+    let deleted = app
+        .post_json(
+            &format!("/api/workspaces/{}/datasets/bulk_delete", workspace_id),
+            Some(&token),
+            json!({ "ids": [infected_id] }),
+        )
+        .await;
+    assert_eq!(deleted.json()["results"][0]["deleted"], true, "{}", deleted.text());
+    assert!(app.storage.head(&quarantine).await.is_err(), "deleting the dataset removes the quarantined file");
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{:x}", Sha256::digest(content))
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_running_job_can_be_cancelled() {
+    use doubledecker::db::models::BackgroundJob;
+    use doubledecker::db::queries::enqueue_job;
+    use doubledecker::utils::error::DoubledeckerError;
+    use doubledecker::workers::{JobHandler, JobRunner};
+
+    struct NeverFinishes;
+
+    #[async_trait::async_trait]
+    impl JobHandler for NeverFinishes {
+        async fn run(&self, _job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+            std::future::pending().await
+        }
+    }
+
+    let app = TestApp::spawn().await;
+    let token = app.signup("cancel@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().parse().unwrap();
+    let job = enqueue_job(&app.pool, Some(workspace_id), "test.never_finishes", json!({}), 1, None).await.unwrap();
+
+    let runner = JobRunner::new(app.pool.clone(), app.events.clone()).register("test.never_finishes", NeverFinishes);
+    let running = tokio::spawn(async move { runner.run_next().await });
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let uri = format!("/api/workspaces/{}/jobs/{}/cancel", workspace_id, job.id);
+    let cancelled = app.post_json(&uri, Some(&token), json!({})).await;
+    assert_eq!(cancelled.status, StatusCode::OK, "{}", cancelled.text());
+    assert_eq!(cancelled.json()["status"], "CANCELLED");
+    let stopped = tokio::time::timeout(std::time::Duration::from_secs(10), running).await;
+    assert!(stopped.is_ok(), "the worker drops a cancelled job");
+
+    let job = app.get(&format!("/api/workspaces/{}/jobs/{}", workspace_id, job.id), &token).await;
+    assert_eq!(job.json()["status"], "CANCELLED");
+    let again = app.post_json(&uri, Some(&token), json!({})).await;
+    assert_eq!(again.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_job_lease_is_renewed_while_running_and_expires_on_the_last_attempt() {
+    use doubledecker::db::models::BackgroundJob;
+    use doubledecker::db::queries::enqueue_job;
+    use doubledecker::utils::error::DoubledeckerError;
+    use doubledecker::workers::{JobHandler, JobRunner};
+
+    struct NeverFinishes;
+
+    #[async_trait::async_trait]
+    impl JobHandler for NeverFinishes {
+        async fn run(&self, _job: &BackgroundJob) -> Result<serde_json::Value, DoubledeckerError> {
+            std::future::pending().await
+        }
+    }
+
+    let app = TestApp::spawn().await;
+    let job = enqueue_job(&app.pool, None, "test.never_finishes", json!({}), 1, None).await.unwrap();
+    let runner = || JobRunner::new(app.pool.clone(), app.events.clone()).register("test.never_finishes", NeverFinishes);
+    let first = runner();
+    let running = tokio::spawn(async move { first.run_next().await });
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let backdate = "UPDATE background_jobs SET locked_at = NOW() - INTERVAL '1 hour' WHERE id = $1";
+    sqlx::query(backdate).bind(job.id).execute(&app.pool).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    let renewed = "SELECT locked_at > NOW() - INTERVAL '1 minute' FROM background_jobs WHERE id = $1";
+    let renewed: bool = sqlx::query_scalar(renewed)
+        .bind(job.id)
+        .fetch_one(&app.pool)
+        .await
+        .unwrap();
+    assert!(renewed, "the running worker renews the lease");
+    assert!(!runner().run_next().await.unwrap(), "a renewed job is not claimed again");
+
+    // The worker dies on the job's only attempt: it fails instead of running a second time
+    running.abort();
+    sqlx::query(backdate).bind(job.id).execute(&app.pool).await.unwrap();
+    assert!(!runner().run_next().await.unwrap());
+    let (status, attempts, error): (String, i32, Option<String>) =
+        sqlx::query_as("SELECT status, attempts, last_error FROM background_jobs WHERE id = $1")
+            .bind(job.id)
+            .fetch_one(&app.pool)
+            .await
+            .unwrap();
+    assert_eq!((status.as_str(), attempts), ("FAILED", 1));
+    assert!(error.unwrap().contains("stopped responding"));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_estimate_before_running() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("estimate@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let uri = format!("/api/workspaces/{}/analytics/estimate", workspace_id);
+
+    let full = app.post_json(&uri, Some(&token), json!({ "sql": "SELECT * FROM royalty_data" })).await;
+    assert_eq!(full.status, StatusCode::OK, "{}", full.text());
+    let full = full.json();
+    assert_eq!(full["bytes_scanned"], DISTROKID_CSV.len(), "{}", full);
+    assert_eq!(full["rows_scanned"], 2);
+    assert_eq!(full["cost_tier"], "low");
+    assert_eq!(full["tables"][0]["table"], "royalty_data");
+
+    let narrow = app
+        .post_json(&uri, Some(&token), json!({ "dimensions": ["platform"], "metrics": ["net_revenue"] }))
+        .await
+        .json();
+    assert!(narrow["bytes_scanned"].as_i64().unwrap() < full["bytes_scanned"].as_i64().unwrap(), "{}", narrow);
+    assert_eq!(narrow["tables"][0]["columns_read"], 2);
+
+    let limited = app.post_json(&uri, Some(&token), json!({ "sql": "SELECT * FROM royalty_data LIMIT 1" })).await;
+    assert_eq!(limited.json()["rows_scanned"], 1, "{}", limited.text());
+
+    let invalid = app.post_json(&uri, Some(&token), json!({ "sql": "SELECT nope FROM royalty_data" })).await;
+    assert_eq!(invalid.json()["code"], "COLUMN_NOT_FOUND", "queries that would fail are not estimated");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_resource_usage_is_recorded() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("usage@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    let dataset_id = upload.json()["id"].as_str().unwrap().to_string();
+    app.run_ingestion().await;
+
+    let query = json!({ "sql": "SELECT platform, net_revenue FROM royalty_data ORDER BY net_revenue" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    let metrics = result.json()["metrics"].clone();
+    assert_eq!(metrics["output_rows"], 2, "{}", metrics);
+    assert!(metrics["bytes_scanned"].as_i64().unwrap() > 0, "{}", metrics);
+    assert!(metrics["peak_memory_bytes"].as_i64().unwrap() > 0, "the sort reserves memory: {}", metrics);
+
+    let history = app.get(&format!("/api/workspaces/{}/analytics/history", workspace_id), &token).await;
+    let recorded = history.json()["data"][0].clone();
+    assert_eq!(recorded["row_count"], 2);
+    assert_eq!(recorded["bytes_scanned"], metrics["bytes_scanned"], "{}", recorded);
+    assert_eq!(recorded["peak_memory_bytes"], metrics["peak_memory_bytes"]);
+
+    let pipeline = json!({ "name": "By store", "query": { "dimensions": ["platform"], "metrics": ["net_revenue"] } });
+    let pipelines_uri = format!("/api/workspaces/{}/pipelines", workspace_id);
+    let created = app.post_json(&pipelines_uri, Some(&token), pipeline).await;
+    let pipeline_id = created.json()["id"].as_str().unwrap().to_string();
+    let run = app
+        .post_json(
+            &format!("{}/{}/run", pipelines_uri, pipeline_id),
+            Some(&token),
+            json!({ "dataset_ids": [dataset_id] }),
+        )
+        .await;
+    let run = run.json()["runs"][0].clone();
+    assert_eq!(run["row_count"], 2, "{}", run);
+    assert!(run["bytes_scanned"].as_i64().unwrap() > 0, "{}", run);
+    assert!(run["result"].get("metrics").is_none(), "stored results stay as they were");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_slow_queries_are_logged_for_admins() {
+    let config = EngineConfig { slow_query_threshold: Some(Duration::ZERO), ..EngineConfig::from_env() };
+    let app = TestApp::spawn_with_engine_config(config).await;
+    let token = app.signup("slow@example.com").await;
+    let admin = app.signup(ADMIN_EMAIL).await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+
+    let query = json!({ "dimensions": ["platform"], "metrics": ["net_revenue"] });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query.clone())
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+
+    assert_eq!(app.get("/admin/slow-queries", &token).await.status, StatusCode::FORBIDDEN);
+    let listed = app.get(&format!("/admin/slow-queries?workspace_id={}", workspace_id), &admin).await;
+    assert_eq!(listed.status, StatusCode::OK, "{}", listed.text());
+    let slow = listed.json()["data"][0].clone();
+    assert_eq!(slow["workspace_id"], workspace_id.as_str());
+    assert_eq!(slow["operations"]["dimensions"], query["dimensions"]);
+    assert_eq!(slow["row_count"], 2);
+    assert_eq!(slow["threshold_ms"], 0);
+    assert!(slow["sql_executed"].as_str().unwrap().contains("royalty_data"), "{}", slow);
+    assert!(slow["plan"].as_str().unwrap().contains("metrics=[output_rows="), "{}", slow);
+
+    let other = app.get(&format!("/admin/slow-queries?workspace_id={}", uuid::Uuid::new_v4()), &admin).await;
+    assert_eq!(other.json()["data"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_preview_queries_are_not_recorded() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("preview@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let uri = format!("/api/workspaces/{}/analytics/query", workspace_id);
+
+    let query = json!({ "dimensions": ["platform"], "metrics": ["net_revenue"], "preview": true });
+    let preview = app.post_json(&uri, Some(&token), query).await;
+    assert_eq!(preview.status, StatusCode::OK, "{}", preview.text());
+    assert_eq!(preview.json()["preview"], true);
+    assert_eq!(preview.json()["rows"].as_array().unwrap().len(), 2);
+
+    let full = app.post_json(&uri, Some(&token), json!({ "sql": "SELECT COUNT(*) FROM royalty_data" })).await.json();
+    assert_eq!(full["rows"], json!([[2]]));
+    assert!(full.get("preview").is_none());
+
+    let history = app.get(&format!("/api/workspaces/{}/analytics/history", workspace_id), &token).await;
+    assert_eq!(history.json()["data"].as_array().unwrap().len(), 1, "previews are not recorded");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_results_diff_between_datasets() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("diff@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let refreshed = "ISRC,Song Title,Store,Reporting Month,Earnings (USD)\n\
+                     US1234567890,First Song,Spotify,2026-06,1.75\n\
+                     US5555555555,Third Song,Deezer,2026-06,0.40\n";
+    for (filename, csv) in [("june.csv", DISTROKID_CSV), ("june-refreshed.csv", refreshed)] {
+        app.upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            filename,
+            csv,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    }
+    app.run_ingestion().await;
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await.json();
+    let id_of = |filename: &str| {
+        datasets["data"].as_array().unwrap().iter().find(|d| d["filename"] == filename).unwrap()["id"].clone()
+    };
+    let uri = format!("/api/workspaces/{}/analytics/diff", workspace_id);
+    let body = json!({
+        "query": { "dimensions": ["title", "platform"], "metrics": ["net_revenue"] },
+        "base_dataset_ids": [id_of("june.csv")],
+        "compare_dataset_ids": [id_of("june-refreshed.csv")],
+        "key": ["title", "platform"],
+    });
+
+    let diff = app.post_json(&uri, Some(&token), body.clone()).await;
+    assert_eq!(diff.status, StatusCode::OK, "{}", diff.text());
+    let diff = diff.json();
+    assert_eq!(diff["summary"], json!({ "added": 1, "removed": 1, "changed": 1, "unchanged": 0 }));
+    assert_eq!(diff["added"][0][0], "Third Song");
+    assert_eq!(diff["removed"][0][0], "Second Song");
+    assert_eq!(diff["changed"][0]["key"], json!(["First Song", "Spotify"]));
+    assert_eq!(diff["truncated"], false);
+
+    let mut no_key = body.clone();
+    no_key["key"] = json!([]);
+    assert_eq!(app.post_json(&uri, Some(&token), no_key).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let mut unknown = body;
+    unknown["compare_dataset_ids"] = json!([uuid::Uuid::new_v4()]);
+    assert_eq!(app.post_json(&uri, Some(&token), unknown).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_diff_compares_results_over_100_rows() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("diff-large@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    // 150 songs; the refresh corrects only the last one
+    let statement = |last_earnings: &str| {
+        let mut csv = "ISRC,Song Title,Store,Reporting Month,Earnings (USD)\n".to_string();
+        for i in 0..150 {
+            let earnings = if i == 149 { last_earnings } else { "1.00" };
+            csv.push_str(&format!("US{:010},Song {:03},Spotify,2026-06,{}\n", i, i, earnings));
+        }
+        csv
+    };
+    for (filename, csv) in [("june.csv", statement("1.00")), ("june-refreshed.csv", statement("2.00"))] {
+        app.upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            filename,
+            &csv,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    }
+    app.run_ingestion().await;
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await.json();
+    let id_of = |filename: &str| {
+        datasets["data"].as_array().unwrap().iter().find(|d| d["filename"] == filename).unwrap()["id"].clone()
+    };
+    let uri = format!("/api/workspaces/{}/analytics/diff", workspace_id);
+    let body = json!({
+        "query": { "dimensions": ["title"], "metrics": ["net_revenue"] },
+        "base_dataset_ids": [id_of("june.csv")],
+        "compare_dataset_ids": [id_of("june-refreshed.csv")],
+        "key": ["title"],
+    });
+
+    let diff = app.post_json(&uri, Some(&token), body.clone()).await;
+    assert_eq!(diff.status, StatusCode::OK, "{}", diff.text());
+    let diff = diff.json();
+    assert_eq!(diff["summary"], json!({ "added": 0, "removed": 0, "changed": 1, "unchanged": 149 }));
+    assert_eq!(diff["changed"][0]["key"], json!(["Song 149"]));
+
+    // A result cut off by the query's limit would show rows as added or removed that are not
+    let mut capped = body;
+    capped["query"]["limit"] = json!(100);
+    let capped = app.post_json(&uri, Some(&token), capped).await;
+    assert_eq!(capped.status, StatusCode::BAD_REQUEST, "{}", capped.text());
+    assert!(capped.text().contains("query limit of 100 rows"), "{}", capped.text());
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_quality_rules_run_on_upload_and_on_demand() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("quality@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let rules_uri = format!("/api/workspaces/{}/quality-rules", workspace_id);
+    let rules = [
+        json!({ "name": "ISRC present", "column_name": "isrc", "expectation": { "type": "not_null" } }),
+        json!({ "name": "ISRC format", "column_name": "isrc", "expectation": { "type": "regex", "pattern": "^US[0-9]{10}$" } }),
+        json!({ "name": "Stores", "column_name": "platform", "expectation": { "type": "in_set", "values": ["Spotify"] } }),
+        json!({ "name": "Earnings", "column_name": "net_revenue", "expectation": { "type": "range", "min": "0", "max": "2" } }),
+        json!({ "name": "One row per store", "column_name": "platform", "expectation": { "type": "unique" } }),
+    ];
+    for rule in rules {
+        let created = app.post_json(&rules_uri, Some(&token), rule).await;
+        assert_eq!(created.status, StatusCode::OK, "{}", created.text());
+    }
+    let invalid = json!({ "name": "Bad", "column_name": "net_revenue", "expectation": { "type": "regex", "pattern": "x" } });
+    assert_eq!(app.post_json(&rules_uri, Some(&token), invalid).await.status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let datasets = app.get(&format!("/api/workspaces/{}/datasets", workspace_id), &token).await.json();
+    let dataset_id = datasets["data"][0]["id"].as_str().unwrap().to_string();
+    let runs_uri = format!("/api/workspaces/{}/datasets/{}/quality-runs", workspace_id, dataset_id);
+
+    let runs = app.get(&runs_uri, &token).await.json();
+    assert_eq!(runs["data"].as_array().unwrap().len(), 1, "rules run when the dataset is processed");
+    assert_eq!(runs["data"][0]["trigger"], "UPLOAD");
+
+    let run = app.post_json(&runs_uri, Some(&token), json!({})).await;
+    assert_eq!(run.status, StatusCode::OK, "{}", run.text());
+    let run = run.json();
+    assert_eq!(run["trigger"], "MANUAL");
+    assert_eq!(run["passed"], false);
+    let outcome = |name: &str| run["results"].as_array().unwrap().iter().find(|r| r["name"] == name).unwrap().clone();
+    assert_eq!(outcome("ISRC present")["passed"], true);
+    assert_eq!(outcome("ISRC format")["passed"], true, "{}", outcome("ISRC format"));
+    let stores = outcome("Stores");
+    assert_eq!(stores["failing_rows"], 1);
+    assert_eq!(stores["samples"][0]["title"], "Second Song");
+    assert_eq!(outcome("Earnings")["failing_rows"], 1);
+    assert_eq!(outcome("One row per store")["passed"], true, "{}", outcome("One row per store"));
+
+    let fetched = app.get(&format!("{}/{}", runs_uri, run["id"].as_str().unwrap()), &token).await;
+    assert_eq!(fetched.status, StatusCode::OK);
+    assert_eq!(fetched.json()["results"], run["results"]);
+
+    let missing = format!("/api/workspaces/{}/datasets/{}/quality-runs", workspace_id, uuid::Uuid::new_v4());
+    assert_eq!(app.post_json(&missing, Some(&token), json!({})).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_filter_values_are_checked_against_column_types() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("filters@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let uri = format!("/api/workspaces/{}/analytics/query", workspace_id);
+    let query = |field: &str, operator: &str, value: &str| {
+        json!({ "metrics": ["quantity"], "filters": [{ "field": field, "operator": operator, "value": value }] })
+    };
+
+    for (operator, value) in [("gte", "2026/06/01"), ("lt", "2026-07-01T00:00:00Z"), ("like", "2026-06%")] {
+        let result = app.post_json(&uri, Some(&token), query("reporting_date", operator, value)).await;
+        assert_eq!(result.status, StatusCode::OK, "{} {}: {}", operator, value, result.text());
+    }
+
+    let mismatch = app.post_json(&uri, Some(&token), query("reporting_date", "eq", "June")).await;
+    assert_eq!(mismatch.status, StatusCode::UNPROCESSABLE_ENTITY, "{}", mismatch.text());
+    let body = mismatch.json();
+    assert_eq!(body["code"], "FILTER_TYPE_MISMATCH");
+    assert_eq!(
+        body["details"],
+        json!({ "column": "reporting_date", "column_type": "Date32", "value": "June", "value_type": "string" })
+    );
+
+    let segment = json!({ "name": "June", "filters": [{ "field": "reporting_date", "operator": "eq", "value": "June" }] });
+    let segment = app.post_json(&format!("/api/workspaces/{}/segments", workspace_id), Some(&token), segment).await;
+    assert_eq!(segment.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(segment.json()["fields"][0]["code"], "type_mismatch");
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_connections_to_internal_addresses_are_refused() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("connections@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    let uri = format!("/api/workspaces/{}/connections", workspace_id);
+
+    for host in ["127.0.0.1:5432", "localhost", "169.254.169.254", "[::1]"] {
+        let body = json!({ "name": "Warehouse", "connection_string": format!("postgres://postgres@{}/postgres", host) });
+        let created = app.post_json(&uri, Some(&token), body).await;
+        assert_eq!(created.status, StatusCode::BAD_REQUEST, "{}: {}", host, created.text());
+        assert!(created.text().contains("private or internal addresses"), "{}", created.text());
+    }
+    let listed = app.get(&uri, &token).await;
+    assert_eq!(listed.json()["data"], json!([]));
+}
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_query_stats_count_bytes_scanned_and_queries_per_day() {
+    let app = TestApp::spawn().await;
+    let token = app.signup("stats@example.com").await;
+    let admin = app.signup(ADMIN_EMAIL).await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+    app.upload_csv(
+        &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+        &token,
+        "june.csv",
+        DISTROKID_CSV,
+        &[("distributor_source", "distrokid")],
+    )
+    .await;
+    app.run_ingestion().await;
+    let query = json!({ "dimensions": ["platform"], "metrics": ["net_revenue"] });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+
+    let stats = app.get("/profile/stats", &token).await.json();
+    assert_eq!(stats["total_queries"], 1);
+    assert!(stats["total_bytes_scanned"].as_i64().unwrap() > 0, "{}", stats);
+    let per_day = stats["queries_per_day"].as_array().unwrap();
+    assert_eq!(per_day.len(), 30);
+    assert_eq!(per_day.last().unwrap()["query_count"], 1);
+    assert_eq!(app.get("/profile/stats", &admin).await.json()["queries_per_day"][29]["query_count"], 0);
+
+    let overview = app.get("/admin/metrics/overview?days=7", &admin).await;
+    assert_eq!(overview.status, StatusCode::OK, "{}", overview.text());
+    let per_day = overview.json()["queries_per_day"].as_array().unwrap().clone();
+    assert_eq!(per_day.len(), 7);
+    assert_eq!(per_day.last().unwrap()["query_count"], 1);
+}
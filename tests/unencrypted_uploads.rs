@@ -0,0 +1,47 @@
+//! Uploads on a server without `SECRETS_ENCRYPTION_KEY`. The key is process-wide, so this runs as
+//! its own test binary: `cargo test --test unencrypted_uploads -- --ignored`.
+
+#[allow(dead_code)]
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+
+const DISTROKID_CSV: &str = "ISRC,Song Title,Store,Reporting Month,Earnings (USD)\n\
+                             US1234567890,First Song,Spotify,2026-06,1.50\n\
+                             US0987654321,Second Song,Apple,2026-06,2.25\n";
+
+#[tokio::test]
+#[ignore = "needs Docker, or TEST_DATABASE_URL pointing at a Postgres server"]
+async fn test_direct_upload_without_master_key_is_stored_unencrypted() {
+    // SAFETY: the only test of this binary, run before any app reads the environment. An empty key
+    // counts as unset, and keeps the harness from configuring one.
+    unsafe { std::env::set_var("SECRETS_ENCRYPTION_KEY", "") };
+    let app = TestApp::spawn().await;
+    let token = app.signup("plain@example.com").await;
+    let workspace = app.post_json("/api/workspaces", Some(&token), json!({ "name": "Label" })).await;
+    let workspace_id = workspace.json()["id"].as_str().unwrap().to_string();
+
+    let upload = app
+        .upload_csv(
+            &format!("/api/workspaces/{}/datasets/upload", workspace_id),
+            &token,
+            "june.csv",
+            DISTROKID_CSV,
+            &[("distributor_source", "distrokid")],
+        )
+        .await;
+    assert_eq!(upload.status, StatusCode::OK, "{}", upload.text());
+    assert_eq!(upload.json()["encryption_key_id"], json!(null));
+    let keys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_data_keys").fetch_one(&app.pool).await.unwrap();
+    assert_eq!(keys, 0, "no data key is created without a master key");
+
+    app.run_ingestion().await;
+    let query = json!({ "sql": "SELECT SUM(net_revenue) AS total FROM royalty_data" });
+    let result = app
+        .post_json(&format!("/api/workspaces/{}/analytics/query", workspace_id), Some(&token), query)
+        .await;
+    assert_eq!(result.status, StatusCode::OK, "{}", result.text());
+    assert_eq!(result.json()["rows"].as_array().unwrap().len(), 1);
+}